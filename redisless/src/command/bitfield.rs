@@ -0,0 +1,78 @@
+use super::command_error::RedisCommandError;
+use super::util::{check_bit_offset, get_bytes_vec, parse_bit_offset, parse_integer};
+use crate::protocol::Resp;
+use crate::storage::models::bitfield::{BitFieldOp, BitFieldType, Overflow};
+
+// `i1` to `i64` or `u1` to `u63`
+fn parse_type(bytes: Vec<u8>) -> Result<BitFieldType, RedisCommandError> {
+    let (signed, bits) = match bytes.split_first() {
+        Some((b'i', bits)) | Some((b'I', bits)) => (true, bits),
+        Some((b'u', bits)) | Some((b'U', bits)) => (false, bits),
+        _ => return Err(RedisCommandError::BitFieldType),
+    };
+
+    let max_bits = if signed { 64 } else { 63 };
+    match std::str::from_utf8(bits).map(|bits| bits.parse::<u32>()) {
+        Ok(Ok(bits)) if bits >= 1 && bits <= max_bits => Ok(BitFieldType { signed, bits }),
+        _ => Err(RedisCommandError::BitFieldType),
+    }
+}
+
+// a bit offset, or `#n` for the n-th field of the type
+fn parse_offset(bytes: Vec<u8>, field_type: BitFieldType) -> Result<usize, RedisCommandError> {
+    match bytes.split_first() {
+        Some((b'#', index)) => {
+            let index = parse_bit_offset(index.to_vec())? as u64;
+            check_bit_offset(index * field_type.bits as u64)
+        }
+        _ => parse_bit_offset(bytes),
+    }
+}
+
+/// parse the `GET type offset`, `SET type offset value`, `INCRBY type offset
+/// increment` and `OVERFLOW WRAP | SAT | FAIL` subcommands of BITFIELD, only
+/// GET being accepted by BITFIELD_RO
+pub fn get_bitfield_ops(
+    resp: &[Resp],
+    read_only: bool,
+) -> Result<Vec<BitFieldOp>, RedisCommandError> {
+    let mut operations = vec![];
+    let mut overflow = Overflow::Wrap;
+
+    let mut args = resp.iter();
+    while let Some(subcommand) = args.next() {
+        let subcommand = get_bytes_vec(Some(subcommand))?.to_ascii_uppercase();
+        if read_only && subcommand != b"GET" {
+            return Err(RedisCommandError::BitFieldReadOnly);
+        }
+
+        if subcommand == b"OVERFLOW" {
+            overflow = match get_bytes_vec(args.next())?.to_ascii_uppercase().as_slice() {
+                b"WRAP" => Overflow::Wrap,
+                b"SAT" => Overflow::Sat,
+                b"FAIL" => Overflow::Fail,
+                _ => return Err(RedisCommandError::BitFieldOverflow),
+            };
+            continue;
+        }
+
+        let field_type = get_bytes_vec(args.next()).and_then(parse_type)?;
+        let offset = parse_offset(get_bytes_vec(args.next())?, field_type)?;
+        let operation = match subcommand.as_slice() {
+            b"GET" => BitFieldOp::Get(field_type, offset),
+            b"SET" => {
+                let value = get_bytes_vec(args.next()).and_then(parse_integer)?;
+                BitFieldOp::Set(field_type, offset, value, overflow)
+            }
+            b"INCRBY" => {
+                let increment = get_bytes_vec(args.next()).and_then(parse_integer)?;
+                BitFieldOp::IncrBy(field_type, offset, increment, overflow)
+            }
+            _ => return Err(RedisCommandError::Syntax),
+        };
+
+        operations.push(operation);
+    }
+
+    Ok(operations)
+}
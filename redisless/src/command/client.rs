@@ -0,0 +1,113 @@
+use super::command_error::RedisCommandError;
+use super::util::{get_bytes_vec, parse_duration};
+use super::Keys;
+use crate::protocol::Resp;
+
+/// the connections CLIENT KILL closes, those matching every filter given
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct ClientKillFilter {
+    pub ids: Vec<u64>,
+    pub addr: Option<String>,
+    pub laddr: Option<String>,
+    pub user: Option<String>,
+    pub client_type: Option<Vec<u8>>,
+    // whether the connection calling CLIENT KILL is left open
+    pub skip_me: bool,
+}
+
+/// how CLIENT TRACKING tells the connection about the keys that changed
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct TrackingOptions {
+    // connection the invalidations are sent to instead
+    pub redirect: Option<u64>,
+    // told about every key starting with one of the prefixes, whether read
+    // or not
+    pub bcast: bool,
+    pub prefixes: Keys,
+    // not told about the keys it changed itself
+    pub noloop: bool,
+}
+
+// ON|OFF [REDIRECT client-id] [PREFIX prefix [PREFIX prefix ...]] [BCAST]
+// [NOLOOP], None when tracking is turned off
+pub fn get_tracking_options(resp: &[Resp]) -> Result<Option<TrackingOptions>, RedisCommandError> {
+    match get_bytes_vec(resp.first())?.to_ascii_uppercase().as_slice() {
+        b"ON" => {}
+        b"OFF" => return Ok(None),
+        _ => return Err(RedisCommandError::Syntax),
+    }
+
+    let mut options = TrackingOptions::default();
+    let mut args = resp[1..].iter();
+    while let Some(arg) = args.next() {
+        match get_bytes_vec(Some(arg))?.to_ascii_uppercase().as_slice() {
+            b"REDIRECT" => {
+                options.redirect = Some(get_bytes_vec(args.next()).and_then(parse_duration)?)
+            }
+            b"PREFIX" => options.prefixes.push(get_bytes_vec(args.next())?),
+            b"BCAST" => options.bcast = true,
+            b"NOLOOP" => options.noloop = true,
+            b"OPTIN" | b"OPTOUT" => {
+                return Err(RedisCommandError::NotSupported(
+                    "CLIENT TRACKING OPTIN and OPTOUT".to_string(),
+                ))
+            }
+            _ => return Err(RedisCommandError::Syntax),
+        }
+    }
+
+    Ok(Some(options))
+}
+
+// ip:port, or [ID client-id] [ADDR ip:port] [LADDR ip:port] [USER username]
+// [TYPE normal|master|replica|pubsub] [SKIPME yes|no]
+pub fn get_client_kill_filter(
+    resp: &[Resp],
+) -> Result<(ClientKillFilter, bool), RedisCommandError> {
+    match resp {
+        [] => return Err(RedisCommandError::ArgNumber),
+        [addr] => {
+            let filter = ClientKillFilter {
+                addr: Some(String::from_utf8_lossy(&get_bytes_vec(Some(addr))?).to_string()),
+                ..ClientKillFilter::default()
+            };
+            return Ok((filter, true));
+        }
+        _ => {}
+    }
+
+    let mut filter = ClientKillFilter {
+        skip_me: true,
+        ..ClientKillFilter::default()
+    };
+
+    let chunks = resp.chunks_exact(2);
+    if !chunks.remainder().is_empty() {
+        return Err(RedisCommandError::Syntax);
+    }
+    for pair in chunks {
+        let option = get_bytes_vec(pair.first())?;
+        let value = get_bytes_vec(pair.get(1))?;
+
+        match option.to_ascii_uppercase().as_slice() {
+            b"ID" => filter.ids.push(parse_duration(value)?),
+            b"ADDR" => filter.addr = Some(String::from_utf8_lossy(&value).to_string()),
+            b"LADDR" => filter.laddr = Some(String::from_utf8_lossy(&value).to_string()),
+            b"USER" => filter.user = Some(String::from_utf8_lossy(&value).to_string()),
+            b"TYPE" => match value.to_ascii_lowercase().as_slice() {
+                b"normal" | b"master" | b"replica" | b"pubsub" => {
+                    filter.client_type = Some(value.to_ascii_lowercase())
+                }
+                _ => return Err(RedisCommandError::Syntax),
+            },
+            b"SKIPME" => match value.to_ascii_lowercase().as_slice() {
+                b"yes" => filter.skip_me = true,
+                b"no" => filter.skip_me = false,
+                _ => return Err(RedisCommandError::Syntax),
+            },
+            _ => return Err(RedisCommandError::Syntax),
+        }
+    }
+
+    Ok((filter, false))
+}
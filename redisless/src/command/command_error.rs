@@ -17,9 +17,69 @@ pub enum RedisCommandError {
     BadString(Utf8Error),
     // Could not parse string for a u64
     IntParse(ParseIntError),
+    // Could not parse string for a f64, or got NaN
+    FloatParse,
     // Command is not supported by Redisless
     NotSupported(String),
     ProtocolParse(RedisError),
+    // Unexpected or misplaced option
+    Syntax,
+    // Weight given to a sorted set is not a float
+    WeightParse,
+    // Score range bound is not a float
+    ScoreRange,
+    // Lexicographic range bound does not start with `[` or `(`, nor is `-` or `+`
+    LexRange,
+    // Bit offset is not an integer or is beyond 512MB
+    BitOffset,
+    // Bit is neither 0 nor 1
+    BitValue,
+    // Bit searched by BITPOS is neither 0 nor 1
+    BitArgument,
+    // BITOP NOT given several keys
+    BitNotKeys,
+    // BITFIELD type is neither i1 to i64 nor u1 to u63
+    BitFieldType,
+    // BITFIELD OVERFLOW is neither WRAP, SAT nor FAIL
+    BitFieldOverflow,
+    // BITFIELD_RO given another subcommand than GET
+    BitFieldReadOnly,
+    // Longitude or latitude out of the range that can be indexed
+    GeoPosition(f64, f64),
+    // Distance unit is neither M, KM, MI nor FT
+    GeoUnit,
+    // Missing, repeated or out of range GEOSEARCH option, holds the reason
+    GeoArgument(&'static str),
+    // Stream id is not ms-seq nor ms
+    StreamId,
+    // XADD given 0-0 as id
+    StreamIdZero,
+    // XREAD given a different number of keys and ids
+    StreamsUnbalanced,
+    // Exclusive stream range bound that cannot be made inclusive, holds the reason
+    StreamRange(&'static str),
+    // XAUTOCLAIM COUNT is not a positive integer
+    StreamCount,
+    // EVAL numkeys is negative or beyond the arguments, holds the reason
+    ScriptKeys(&'static str),
+    // Options that cannot be given together, holds the reason
+    IncompatibleOptions(&'static str),
+    // ZADD INCR given several score and member pairs
+    IncrPairs,
+    // Blocking timeout is not a float
+    Timeout,
+    // Blocking timeout is lower than 0
+    NegativeTimeout,
+    // Blocking timeout is too large to be waited for
+    TimeoutRange,
+    // REPLICAOF port is not an integer or out of range
+    MasterPort,
+    // CLUSTER MEET port is not an integer or out of range
+    NodePort,
+    // FAILOVER timeout is not an integer greater than 0
+    FailoverTimeout,
+    // a slot is not an integer or beyond the slots of a cluster
+    Slot,
     InvalidCommand,
     CommandNotFound,
 }
@@ -33,10 +93,62 @@ impl Display for RedisCommandError {
             Self::TimeOverflow(e) => write!(f, "{:?}", e),
             Self::BadString(e) => write!(f, "{}", e),
             Self::IntParse(e) => write!(f, "{}", e),
+            Self::FloatParse => write!(f, "value is not a valid float"),
             Self::NotSupported(cmd) => {
                 write!(f, "command {} not supported by redisless", cmd)
             }
             Self::ProtocolParse(err) => write!(f, "{}", err),
+            Self::Syntax => write!(f, "syntax error"),
+            Self::WeightParse => write!(f, "weight value is not a float"),
+            Self::ScoreRange => write!(f, "min or max is not a float"),
+            Self::LexRange => write!(f, "min or max not valid string range item"),
+            Self::BitOffset => write!(f, "bit offset is not an integer or out of range"),
+            Self::BitValue => write!(f, "bit is not an integer or out of range"),
+            Self::BitArgument => write!(f, "The bit argument must be 1 or 0."),
+            Self::BitNotKeys => {
+                write!(f, "BITOP NOT must be called with a single source key.")
+            }
+            Self::BitFieldType => write!(
+                f,
+                "Invalid bitfield type. Use something like i16 u8. Note that u64 is not supported but i64 is."
+            ),
+            Self::BitFieldOverflow => write!(f, "Invalid OVERFLOW type specified"),
+            Self::BitFieldReadOnly => {
+                write!(f, "BITFIELD_RO only supports the GET subcommand")
+            }
+            Self::GeoPosition(longitude, latitude) => write!(
+                f,
+                "invalid longitude,latitude pair {:.6},{:.6}",
+                longitude, latitude
+            ),
+            Self::GeoUnit => {
+                write!(f, "unsupported unit provided. please use M, KM, FT, MI")
+            }
+            Self::GeoArgument(reason) => write!(f, "{}", reason),
+            Self::StreamId => {
+                write!(f, "Invalid stream ID specified as stream command argument")
+            }
+            Self::StreamIdZero => {
+                write!(f, "The ID specified in XADD must be greater than 0-0")
+            }
+            Self::StreamsUnbalanced => write!(
+                f,
+                "Unbalanced 'xread' list of streams: for each stream key an ID or '$' must be specified."
+            ),
+            Self::StreamRange(reason) => write!(f, "{}", reason),
+            Self::StreamCount => write!(f, "COUNT must be > 0"),
+            Self::ScriptKeys(reason) => write!(f, "{}", reason),
+            Self::IncompatibleOptions(reason) => write!(f, "{}", reason),
+            Self::IncrPairs => {
+                write!(f, "INCR option supports a single increment-element pair")
+            }
+            Self::Timeout => write!(f, "timeout is not a float or out of range"),
+            Self::NegativeTimeout => write!(f, "timeout is negative"),
+            Self::TimeoutRange => write!(f, "timeout is out of range"),
+            Self::MasterPort => write!(f, "Invalid master port"),
+            Self::NodePort => write!(f, "Invalid base port specified"),
+            Self::FailoverTimeout => write!(f, "FAILOVER timeout must be greater than 0"),
+            Self::Slot => write!(f, "Invalid or out of range slot"),
             Self::InvalidCommand => write!(f, "invalid command"),
             Self::CommandNotFound => write!(f, "command not found"),
         }
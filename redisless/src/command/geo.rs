@@ -0,0 +1,136 @@
+use super::command_error::RedisCommandError;
+use super::util::{get_bytes_vec, parse_duration, parse_score};
+use crate::protocol::Resp;
+use crate::storage::models::geo::{
+    self, DistanceUnit, GeoOrigin, GeoQuery, GeoReplyOptions, GeoShape,
+};
+
+/// longitude latitude member [longitude latitude member ...], turned into
+/// the geohash score of each member
+pub fn get_geo_members(resp: &[Resp]) -> Result<Vec<(f64, Vec<u8>)>, RedisCommandError> {
+    let chunks = resp.chunks_exact(3);
+    if resp.is_empty() || !chunks.remainder().is_empty() {
+        return Err(RedisCommandError::ArgNumber);
+    }
+
+    chunks
+        .map(|triple| {
+            let longitude = get_bytes_vec(triple.first()).and_then(parse_score)?;
+            let latitude = get_bytes_vec(triple.get(1)).and_then(parse_score)?;
+            let member = get_bytes_vec(triple.get(2))?;
+
+            if !geo::is_valid(longitude, latitude) {
+                return Err(RedisCommandError::GeoPosition(longitude, latitude));
+            }
+
+            Ok((geo::encode(longitude, latitude), member))
+        })
+        .collect()
+}
+
+// M, KM, MI or FT
+pub fn parse_distance_unit(bytes: Vec<u8>) -> Result<DistanceUnit, RedisCommandError> {
+    match bytes.to_ascii_uppercase().as_slice() {
+        b"M" => Ok(DistanceUnit::Meters),
+        b"KM" => Ok(DistanceUnit::Kilometers),
+        b"MI" => Ok(DistanceUnit::Miles),
+        b"FT" => Ok(DistanceUnit::Feet),
+        _ => Err(RedisCommandError::GeoUnit),
+    }
+}
+
+// FROMMEMBER member | FROMLONLAT longitude latitude,
+// BYRADIUS radius unit | BYBOX width height unit,
+// [ASC | DESC] [COUNT count [ANY]] followed by the WITHCOORD, WITHDIST
+// and WITHHASH flags, or by STOREDIST when storing
+pub fn get_geosearch_options(
+    resp: &[Resp],
+    store: bool,
+) -> Result<(GeoQuery, GeoReplyOptions, bool), RedisCommandError> {
+    use RedisCommandError::{GeoArgument, Syntax};
+
+    let mut origin = None;
+    let mut shape = None;
+    let mut desc = None;
+    let mut count = None;
+    let mut reply = GeoReplyOptions::default();
+    let mut store_dist = false;
+
+    let mut options = resp.iter();
+    let mut next = || get_bytes_vec(options.next()).map_err(|_| Syntax);
+    while let Ok(option) = next() {
+        match option.to_ascii_uppercase().as_slice() {
+            b"FROMMEMBER" if origin.is_none() => origin = Some(GeoOrigin::Member(next()?)),
+            b"FROMLONLAT" if origin.is_none() => {
+                let longitude = parse_score(next()?)?;
+                let latitude = parse_score(next()?)?;
+                if !geo::is_valid(longitude, latitude) {
+                    return Err(RedisCommandError::GeoPosition(longitude, latitude));
+                }
+                origin = Some(GeoOrigin::Position(longitude, latitude));
+            }
+            b"FROMMEMBER" | b"FROMLONLAT" => {
+                return Err(GeoArgument(
+                    "exactly one of FROMMEMBER or FROMLONLAT can be specified for GEOSEARCH",
+                ))
+            }
+            b"BYRADIUS" if shape.is_none() => {
+                let radius = parse_score(next()?)?;
+                if radius < 0.0 {
+                    return Err(GeoArgument("radius cannot be negative"));
+                }
+                shape = Some((GeoShape::Radius(radius), parse_distance_unit(next()?)?));
+            }
+            b"BYBOX" if shape.is_none() => {
+                let width = parse_score(next()?)?;
+                let height = parse_score(next()?)?;
+                if width < 0.0 || height < 0.0 {
+                    return Err(GeoArgument("height or width cannot be negative"));
+                }
+                shape = Some((GeoShape::Box(width, height), parse_distance_unit(next()?)?));
+            }
+            b"BYRADIUS" | b"BYBOX" => {
+                return Err(GeoArgument(
+                    "exactly one of BYRADIUS and BYBOX can be specified for GEOSEARCH",
+                ))
+            }
+            b"ASC" => desc = Some(false),
+            b"DESC" => desc = Some(true),
+            b"COUNT" => match parse_duration(next()?) {
+                Ok(0) | Err(_) => return Err(GeoArgument("COUNT must be > 0")),
+                Ok(c) => count = Some((c as usize, false)),
+            },
+            b"ANY" => match count {
+                Some((c, _)) => count = Some((c, true)),
+                None => return Err(GeoArgument("the ANY argument requires COUNT argument")),
+            },
+            b"WITHCOORD" if !store => reply.with_coord = true,
+            b"WITHDIST" if !store => reply.with_dist = true,
+            b"WITHHASH" if !store => reply.with_hash = true,
+            b"STOREDIST" if store => store_dist = true,
+            _ => return Err(Syntax),
+        }
+    }
+
+    let origin = origin.ok_or(GeoArgument(
+        "exactly one of FROMMEMBER or FROMLONLAT can be specified for GEOSEARCH",
+    ))?;
+    let (shape, unit) = shape.ok_or(GeoArgument(
+        "exactly one of BYRADIUS and BYBOX can be specified for GEOSEARCH",
+    ))?;
+
+    let query = GeoQuery {
+        origin,
+        shape: match shape {
+            GeoShape::Radius(radius) => GeoShape::Radius(unit.to_meters(radius)),
+            GeoShape::Box(width, height) => {
+                GeoShape::Box(unit.to_meters(width), unit.to_meters(height))
+            }
+        },
+        unit,
+        desc,
+        count,
+    };
+
+    Ok((query, reply, store_dist))
+}
@@ -1,17 +1,58 @@
 #[cfg(test)]
 mod tests;
 
+mod bitfield;
+pub mod client;
 pub mod command_error;
+mod geo;
+mod range;
+pub mod replication;
+mod sort;
+mod stream;
+pub mod table;
 mod util;
 
+use std::convert::TryFrom;
+use std::time::Duration;
+
 use crate::protocol::Resp;
-use crate::storage::models::Expiry;
+use crate::storage::models::{
+    bitfield::BitFieldOp,
+    geo::{DistanceUnit, GeoQuery, GeoReplyOptions},
+    redis_sorted_set::{AddOptions, Aggregate, RangeBy, RangeQuery},
+    redis_stream::{
+        ClaimOptions, PendingQuery, StreamId, StreamTrim, XAddId, XReadGroupId, XReadId,
+    },
+    redis_value::{BitOperation, BitUnit, LcsOptions},
+    sort::SortQuery,
+    Expiry, ExpiryCondition,
+};
+use client::{ClientKillFilter, TrackingOptions};
 use command_error::RedisCommandError;
+use replication::{get_failover_options, FailoverOptions};
 
 type Key = Vec<u8>;
 type Value = Vec<u8>;
 type Items = Vec<(Key, Value)>;
 type Keys = Vec<Key>;
+type WithScores = bool;
+type Reversed = bool;
+type PopMax = bool;
+type Weights = Vec<f64>;
+type StoreDist = bool;
+type NoMkStream = bool;
+type MkStream = bool;
+type NoAck = bool;
+type JustId = bool;
+type Replace = bool;
+type WithCode = bool;
+type ReadOnly = bool;
+type OldForm = bool;
+type WriteOnly = bool;
+// None for the summary, Some(count) for the FULL form, 0 meaning every entry
+type Full = Option<usize>;
+// None not blocking, Some(None) blocking forever
+type Block = Option<Option<Duration>>;
 
 #[derive(Debug, PartialEq)]
 pub enum Command {
@@ -26,16 +67,284 @@ pub enum Command {
     Get(Key),
     GetSet(Key, Value),
     MGet(Keys),
+    HSet(Key, Items),
+    HStrlen(Key, Key),
+    HExpire(Key, Expiry, Option<ExpiryCondition>, Keys),
+    HPExpire(Key, Expiry, Option<ExpiryCondition>, Keys),
+    HTtl(Key, Keys),
+    HPTtl(Key, Keys),
+    HPersist(Key, Keys),
+    SAdd(Key, Keys),
+    SRem(Key, Keys),
+    SMembers(Key),
+    SIsMember(Key, Value),
+    SMIsMember(Key, Keys),
+    SCard(Key),
+    SScan(Key, u64, Option<Value>, usize),
+    SMove(Key, Key, Value),
+    SInterCard(Keys, usize),
+    ZAdd(Key, AddOptions, Vec<(f64, Value)>),
+    ZScore(Key, Value),
+    ZRem(Key, Keys),
+    ZRange(Key, RangeQuery, WithScores),
+    ZRangeStore(Key, Key, RangeQuery),
+    ZRemRange(Key, RangeBy),
+    ZIncrBy(Key, f64, Value),
+    ZCard(Key),
+    ZRandMember(Key, Option<i64>, WithScores),
+    ZScan(Key, u64, Option<Value>, usize),
+    ZMScore(Key, Keys),
+    ZCount(Key, RangeBy),
+    // rank from the highest score when reversed
+    ZRank(Key, Value, Reversed, WithScores),
+    ZPop(Key, Option<u64>, PopMax),
+    ZUnion(Keys, Weights, Aggregate, WithScores),
+    ZInter(Keys, Weights, Aggregate, WithScores),
+    ZDiff(Keys, WithScores),
+    ZUnionStore(Key, Keys, Weights, Aggregate),
+    ZInterStore(Key, Keys, Weights, Aggregate),
+    ZDiffStore(Key, Keys),
+    // blocks until a member can be popped from one of the keys, None blocking forever
+    BZPop(Keys, Option<Duration>, PopMax),
+    SInter(Keys),
+    SUnion(Keys),
+    SDiff(Keys),
+    SInterStore(Key, Keys),
+    SUnionStore(Key, Keys),
+    SDiffStore(Key, Keys),
+    SPop(Key, Option<u64>),
+    SRandMember(Key, Option<i64>),
     Del(Key),
+    // key and index of the database it is moved to
+    Move(Key, i64),
     Incr(Key),
+    SetBit(Key, usize, bool),
+    GetBit(Key, usize),
+    // inclusive start and end
+    BitCount(Key, Option<(i64, i64)>, BitUnit),
+    // bit, start and end
+    BitPos(Key, bool, Option<i64>, Option<i64>, BitUnit),
+    // operation, destination and source keys
+    BitOp(BitOperation, Key, Keys),
+    BitField(Key, Vec<BitFieldOp>),
+    Lcs(Key, Key, LcsOptions),
+    GeoPos(Key, Keys),
+    GeoDist(Key, Value, Value, DistanceUnit),
+    GeoSearch(Key, GeoQuery, GeoReplyOptions),
+    // destination and source keys
+    GeoSearchStore(Key, Key, GeoQuery, StoreDist),
+    XAdd(Key, NoMkStream, Option<StreamTrim>, XAddId, Items),
+    XTrim(Key, StreamTrim),
+    XDel(Key, Vec<StreamId>),
+    XLen(Key),
+    // inclusive start and end, walked from the end when reversed
+    XRange(Key, StreamId, StreamId, Option<usize>, Reversed),
+    // keys and ids, count and block
+    XRead(Vec<(Key, XReadId)>, Option<usize>, Block),
+    // key, group and the id of the last entry delivered to the group
+    XGroupCreate(Key, Value, XReadId, MkStream),
+    XGroupSetId(Key, Value, XReadId),
+    XGroupDestroy(Key, Value),
+    // key, group and consumer
+    XGroupCreateConsumer(Key, Value, Value),
+    XGroupDelConsumer(Key, Value, Value),
+    // group, consumer, keys and ids, count and block
+    XReadGroup(
+        Value,
+        Value,
+        Vec<(Key, XReadGroupId)>,
+        Option<usize>,
+        Block,
+        NoAck,
+    ),
+    // key, group and ids
+    XAck(Key, Value, Vec<StreamId>),
+    // key, group and the query of the extended form
+    XPending(Key, Value, Option<PendingQuery>),
+    // key, group, consumer, min idle time and ids
+    XClaim(Key, Value, Value, u64, Vec<StreamId>, ClaimOptions),
+    // key, group, consumer, min idle time, start and count
+    XAutoClaim(Key, Value, Value, u64, StreamId, usize, JustId),
+    XInfoStream(Key, Full),
+    XInfoGroups(Key),
+    // key and group
+    XInfoConsumers(Key, Value),
+    Sort(Key, SortQuery),
+    // channels
+    Subscribe(Keys),
+    // channels, every channel subscribed to when there is none
+    Unsubscribe(Keys),
+    // glob patterns
+    PSubscribe(Keys),
+    // patterns, every pattern subscribed to when there is none
+    PUnsubscribe(Keys),
+    // channel and message
+    Publish(Key, Value),
+    // glob pattern the channels match
+    PubSubChannels(Option<Key>),
+    PubSubNumSub(Keys),
+    PubSubNumPat,
+    PubSubShardChannels(Option<Key>),
+    PubSubShardNumSub(Keys),
+    // shard channels
+    SSubscribe(Keys),
+    // shard channels, every shard channel subscribed to when there is none
+    SUnsubscribe(Keys),
+    // shard channel and message
+    SPublish(Key, Value),
+    // key and elements, which may be none
+    PfAdd(Key, Keys),
+    PfCount(Keys),
+    // destination and source keys, which may be none
+    PfMerge(Key, Keys),
     Exists(Key),
-    Info,
-    Ping,
+    // glob patterns of the parameters
+    ConfigGet(Keys),
+    // parameters and values
+    ConfigSet(Items),
+    Multi,
+    Exec,
+    Discard,
+    Watch(Keys),
+    Unwatch,
+    // script, keys and arguments
+    Eval(Value, Keys, Keys),
+    // SHA1 of the script, keys and arguments
+    EvalSha(Value, Keys, Keys),
+    ScriptLoad(Value),
+    // SHA1 of the scripts
+    ScriptExists(Keys),
+    ScriptFlush,
+    ScriptKill,
+    // library code
+    FunctionLoad(Value, Replace),
+    // glob pattern of the library names
+    FunctionList(Option<Value>, WithCode),
+    // library name
+    FunctionDelete(Value),
+    FunctionDump,
+    FunctionFlush,
+    FunctionKill,
+    // function, keys and arguments
+    FCall(Value, Keys, Keys, ReadOnly),
+    // username, the default user when none, and password
+    Auth(Option<Value>, Value),
+    // version of RESP to switch to, user and password to authenticate with,
+    // and name of the client
+    Hello(Option<i64>, Option<(Value, Value)>, Option<Value>),
+    // username and rules
+    AclSetUser(Value, Keys),
+    AclGetUser(Value),
+    AclDelUser(Keys),
+    AclList,
+    AclUsers,
+    AclWhoAmI,
+    // category whose commands to list, the categories when none
+    AclCat(Option<Value>),
+    // index of the database the connection switches to
+    Select(i64),
+    ClientId,
+    ClientSetName(Value),
+    ClientGetName,
+    // type of the clients to list and their ids, all of them when none
+    ClientList(Option<Value>, Vec<u64>),
+    ClientInfo,
+    // connections to close, the old form being given an address only
+    ClientKill(ClientKillFilter, OldForm),
+    // milliseconds during which the commands, or only the writes, are held
+    // back
+    ClientPause(u64, WriteOnly),
+    ClientUnpause,
+    // how the connection is told about the keys that changed, None when
+    // turned off
+    ClientTracking(Option<TrackingOptions>),
+    // indexes of the two databases whose contents are swapped
+    SwapDb(i64, i64),
+    // every command run from now on is told to the connection
+    Monitor,
+    // how long the connection sleeps for
+    DebugSleep(Duration),
+    DebugObject(Key),
+    // whether keys are removed in the background once expired
+    DebugSetActiveExpire(bool),
+    DebugJmap,
+    // whether a snapshot is taken before the server stops, as the save
+    // points say when not given
+    Shutdown(Option<bool>),
+    Save,
+    BgSave,
+    LastSave,
+    BgRewriteAof,
+    // the host and the port of the master to replicate, None to stop
+    // replicating
+    ReplicaOf(Option<(Value, u16)>),
+    // hand the master role over to a replica
+    Failover(FailoverOptions),
+    FailoverAbort,
+    Sync,
+    // the replication id the replica knows, and the offset of the first
+    // byte of changes it lacks
+    PSync(Value, i64),
+    // what the replica tells about itself, such as the port it listens on
+    ReplConf(Vec<Value>),
+    // whether the connection reads the keys of a replica
+    ReadOnly,
+    ReadWrite,
+    // number of entries to reply, all of them if negative
+    SlowLogGet(Option<i64>),
+    SlowLogLen,
+    SlowLogReset,
+    // event whose latency spikes are replied
+    LatencyHistory(Value),
+    LatencyLatest,
+    // events whose spikes are forgotten, all of them when none
+    LatencyReset(Vec<Value>),
+    LatencyDoctor,
+    ClusterInfo,
+    ClusterMyId,
+    ClusterSlots,
+    ClusterShards,
+    ClusterNodes,
+    ClusterKeySlot(Key),
+    // the ip and the port of the node to meet
+    ClusterMeet(Value, u16),
+    // the slots to serve, or to stop serving, given one by one or as ranges
+    ClusterAddSlots(Vec<u16>),
+    ClusterDelSlots(Vec<u16>),
+    SentinelMasters,
+    // the name of the master asked about
+    SentinelMaster(Value),
+    SentinelGetMasterAddrByName(Value),
+    MemoryUsage(Key),
+    MemoryStats,
+    MemoryDoctor,
+    // what COMMAND tells about every command
+    Commands,
+    CountCommands,
+    // names of the commands to describe, all of them when none
+    DescribeCommands(Keys),
+    DocumentCommands(Keys),
+    // a command along with its arguments, whose keys are told
+    GetKeys(Keys),
+    Info(Vec<Value>),
+    // message replied instead of PONG
+    Ping(Option<Value>),
+    Echo(Value),
+    Lolwut,
+    Time,
+    Wait(i64, Option<Duration>),
     Quit,
+    Reset,
 }
 
 impl Command {
     pub fn parse(v: Vec<Resp>) -> Result<Self, RedisCommandError> {
+        use bitfield::*;
+        use client::*;
+        use geo::*;
+        use range::*;
+        use sort::*;
+        use stream::*;
         use util::*;
         use Command::*;
         use RedisCommandError::*;
@@ -154,21 +463,1311 @@ impl Command {
 
                     Ok(MGet(keys_vec))
                 }
+                b"HSET" | b"hset" | b"HSet" | b"Hset" => {
+                    let key = get_bytes_vec(v.get(1))?;
+                    let items = get_pairs(&v[2..])?; // will never panic, key is at index 1
+
+                    Ok(HSet(key, items))
+                }
+                b"HSTRLEN" | b"hstrlen" | b"HStrlen" | b"Hstrlen" => {
+                    let key = get_bytes_vec(v.get(1))?;
+                    let field = get_bytes_vec(v.get(2))?;
+
+                    Ok(HStrlen(key, field))
+                }
+                b"HEXPIRE" | b"hexpire" | b"HExpire" | b"Hexpire" => {
+                    let key = get_bytes_vec(v.get(1))?;
+                    let duration = get_bytes_vec(v.get(2)).and_then(parse_duration)?;
+                    let expiry = Expiry::new_from_secs(duration)?;
+                    let (condition, fields) = get_hexpire_options(&v[3..])?;
+
+                    Ok(HExpire(key, expiry, condition, fields))
+                }
+                b"HPEXPIRE" | b"hpexpire" | b"HPExpire" | b"Hpexpire" => {
+                    let key = get_bytes_vec(v.get(1))?;
+                    let duration = get_bytes_vec(v.get(2)).and_then(parse_duration)?;
+                    let expiry = Expiry::new_from_millis(duration)?;
+                    let (condition, fields) = get_hexpire_options(&v[3..])?;
+
+                    Ok(HPExpire(key, expiry, condition, fields))
+                }
+                b"HTTL" | b"httl" | b"HTtl" | b"Httl" => {
+                    let key = get_bytes_vec(v.get(1))?;
+                    let fields = get_fields(&v[2..])?;
+
+                    Ok(HTtl(key, fields))
+                }
+                b"HPTTL" | b"hpttl" | b"HPTtl" | b"Hpttl" => {
+                    let key = get_bytes_vec(v.get(1))?;
+                    let fields = get_fields(&v[2..])?;
+
+                    Ok(HPTtl(key, fields))
+                }
+                b"HPERSIST" | b"hpersist" | b"HPersist" | b"Hpersist" => {
+                    let key = get_bytes_vec(v.get(1))?;
+                    let fields = get_fields(&v[2..])?;
+
+                    Ok(HPersist(key, fields))
+                }
+                b"SADD" | b"sadd" | b"SAdd" | b"Sadd" => {
+                    let key = get_bytes_vec(v.get(1))?;
+                    let members = get_bytes_vecs(&v[2..])?;
+
+                    Ok(SAdd(key, members))
+                }
+                b"SREM" | b"srem" | b"SRem" | b"Srem" => {
+                    let key = get_bytes_vec(v.get(1))?;
+                    let members = get_bytes_vecs(&v[2..])?;
+
+                    Ok(SRem(key, members))
+                }
+                b"SMEMBERS" | b"smembers" | b"SMembers" | b"Smembers" => {
+                    let key = get_bytes_vec(v.get(1))?;
+                    Ok(SMembers(key))
+                }
+                b"SISMEMBER" | b"sismember" | b"SIsMember" | b"Sismember" => {
+                    let key = get_bytes_vec(v.get(1))?;
+                    let member = get_bytes_vec(v.get(2))?;
+
+                    Ok(SIsMember(key, member))
+                }
+                b"SMISMEMBER" | b"smismember" | b"SMIsMember" | b"Smismember" => {
+                    let key = get_bytes_vec(v.get(1))?;
+                    let members = get_bytes_vecs(&v[2..])?;
+
+                    Ok(SMIsMember(key, members))
+                }
+                b"SCARD" | b"scard" | b"SCard" | b"Scard" => {
+                    let key = get_bytes_vec(v.get(1))?;
+                    Ok(SCard(key))
+                }
+                b"SSCAN" | b"sscan" | b"SScan" | b"Sscan" => {
+                    let key = get_bytes_vec(v.get(1))?;
+                    let cursor = get_bytes_vec(v.get(2)).and_then(parse_duration)?;
+                    let (pattern, count) = get_scan_options(&v[3..])?;
+
+                    Ok(SScan(key, cursor, pattern, count))
+                }
+                b"SMOVE" | b"smove" | b"SMove" | b"Smove" => {
+                    let source = get_bytes_vec(v.get(1))?;
+                    let destination = get_bytes_vec(v.get(2))?;
+                    let member = get_bytes_vec(v.get(3))?;
+
+                    Ok(SMove(source, destination, member))
+                }
+                b"SINTER" | b"sinter" | b"SInter" | b"Sinter" => {
+                    let keys = get_bytes_vecs(&v[1..])?;
+                    Ok(SInter(keys))
+                }
+                b"SUNION" | b"sunion" | b"SUnion" | b"Sunion" => {
+                    let keys = get_bytes_vecs(&v[1..])?;
+                    Ok(SUnion(keys))
+                }
+                b"SDIFF" | b"sdiff" | b"SDiff" | b"Sdiff" => {
+                    let keys = get_bytes_vecs(&v[1..])?;
+                    Ok(SDiff(keys))
+                }
+                b"SINTERCARD" | b"sintercard" | b"SInterCard" | b"Sintercard" => {
+                    let (keys, options) = get_numkeys(&v[1..])?;
+                    let limit = match options {
+                        [] => 0,
+                        [option, limit] => match get_bytes_vec(Some(option))? {
+                            option if option.eq_ignore_ascii_case(b"LIMIT") => {
+                                get_bytes_vec(Some(limit)).and_then(parse_duration)? as usize
+                            }
+                            _ => return Err(Syntax),
+                        },
+                        _ => return Err(Syntax),
+                    };
+
+                    Ok(SInterCard(keys, limit))
+                }
+                b"SINTERSTORE" | b"sinterstore" | b"SInterStore" | b"Sinterstore" => {
+                    let destination = get_bytes_vec(v.get(1))?;
+                    let keys = get_bytes_vecs(&v[2..])?;
+
+                    Ok(SInterStore(destination, keys))
+                }
+                b"SUNIONSTORE" | b"sunionstore" | b"SUnionStore" | b"Sunionstore" => {
+                    let destination = get_bytes_vec(v.get(1))?;
+                    let keys = get_bytes_vecs(&v[2..])?;
+
+                    Ok(SUnionStore(destination, keys))
+                }
+                b"SDIFFSTORE" | b"sdiffstore" | b"SDiffStore" | b"Sdiffstore" => {
+                    let destination = get_bytes_vec(v.get(1))?;
+                    let keys = get_bytes_vecs(&v[2..])?;
+
+                    Ok(SDiffStore(destination, keys))
+                }
+                b"SPOP" | b"spop" | b"SPop" | b"Spop" => {
+                    let key = get_bytes_vec(v.get(1))?;
+                    let count = match v.get(2) {
+                        Some(count) => Some(get_bytes_vec(Some(count)).and_then(parse_duration)?),
+                        None => None,
+                    };
+
+                    Ok(SPop(key, count))
+                }
+                b"SRANDMEMBER" | b"srandmember" | b"SRandMember" | b"Srandmember" => {
+                    let key = get_bytes_vec(v.get(1))?;
+                    let count = match v.get(2) {
+                        Some(count) => Some(get_bytes_vec(Some(count)).and_then(parse_integer)?),
+                        None => None,
+                    };
+
+                    Ok(SRandMember(key, count))
+                }
+                b"ZADD" | b"zadd" | b"ZAdd" | b"Zadd" => {
+                    let key = get_bytes_vec(v.get(1))?;
+                    let (options, pairs) = get_zadd_options(&v[2..])?;
+
+                    let mut members = vec![];
+                    for (score, member) in get_pairs(pairs)? {
+                        members.push((parse_score(score)?, member));
+                    }
+
+                    if options.incr && members.len() > 1 {
+                        return Err(IncrPairs);
+                    }
+
+                    Ok(ZAdd(key, options, members))
+                }
+                b"ZSCORE" | b"zscore" | b"ZScore" | b"Zscore" => {
+                    let key = get_bytes_vec(v.get(1))?;
+                    let member = get_bytes_vec(v.get(2))?;
+
+                    Ok(ZScore(key, member))
+                }
+                b"ZMSCORE" | b"zmscore" | b"ZMScore" | b"Zmscore" => {
+                    let key = get_bytes_vec(v.get(1))?;
+                    let members = get_bytes_vecs(&v[2..])?;
+
+                    Ok(ZMScore(key, members))
+                }
+                b"ZREM" | b"zrem" | b"ZRem" | b"Zrem" => {
+                    let key = get_bytes_vec(v.get(1))?;
+                    let members = get_bytes_vecs(&v[2..])?;
+
+                    Ok(ZRem(key, members))
+                }
+                b"ZINCRBY" | b"zincrby" | b"ZIncrBy" | b"Zincrby" => {
+                    let key = get_bytes_vec(v.get(1))?;
+                    let increment = get_bytes_vec(v.get(2)).and_then(parse_score)?;
+                    let member = get_bytes_vec(v.get(3))?;
+
+                    Ok(ZIncrBy(key, increment, member))
+                }
+                b"ZCARD" | b"zcard" | b"ZCard" | b"Zcard" => {
+                    let key = get_bytes_vec(v.get(1))?;
+
+                    Ok(ZCard(key))
+                }
+                b"ZRANDMEMBER" | b"zrandmember" | b"ZRandMember" | b"Zrandmember" => {
+                    let key = get_bytes_vec(v.get(1))?;
+                    let (count, with_scores) = match v.get(2) {
+                        Some(count) => (
+                            Some(get_bytes_vec(Some(count)).and_then(parse_integer)?),
+                            get_flag(&v[3..], b"WITHSCORES")?,
+                        ),
+                        None => (None, false),
+                    };
+
+                    Ok(ZRandMember(key, count, with_scores))
+                }
+                b"ZSCAN" | b"zscan" | b"ZScan" | b"Zscan" => {
+                    let key = get_bytes_vec(v.get(1))?;
+                    let cursor = get_bytes_vec(v.get(2)).and_then(parse_duration)?;
+                    let (pattern, count) = get_scan_options(&v[3..])?;
+
+                    Ok(ZScan(key, cursor, pattern, count))
+                }
+                b"ZCOUNT" | b"zcount" | b"ZCount" | b"Zcount" => {
+                    let key = get_bytes_vec(v.get(1))?;
+                    let min = get_bytes_vec(v.get(2)).and_then(parse_score_bound)?;
+                    let max = get_bytes_vec(v.get(3)).and_then(parse_score_bound)?;
+
+                    Ok(ZCount(key, RangeBy::Score(min, max)))
+                }
+                b"ZLEXCOUNT" | b"zlexcount" | b"ZLexCount" | b"Zlexcount" => {
+                    let key = get_bytes_vec(v.get(1))?;
+                    let min = get_bytes_vec(v.get(2)).and_then(parse_lex_bound)?;
+                    let max = get_bytes_vec(v.get(3)).and_then(parse_lex_bound)?;
+
+                    Ok(ZCount(key, RangeBy::Lex(min, max)))
+                }
+                b"ZRANK" | b"zrank" | b"ZRank" | b"Zrank" => {
+                    let key = get_bytes_vec(v.get(1))?;
+                    let member = get_bytes_vec(v.get(2))?;
+                    let with_score = get_flag(&v[3..], b"WITHSCORE")?;
+
+                    Ok(ZRank(key, member, false, with_score))
+                }
+                b"ZREVRANK" | b"zrevrank" | b"ZRevRank" | b"Zrevrank" => {
+                    let key = get_bytes_vec(v.get(1))?;
+                    let member = get_bytes_vec(v.get(2))?;
+                    let with_score = get_flag(&v[3..], b"WITHSCORE")?;
+
+                    Ok(ZRank(key, member, true, with_score))
+                }
+                b"ZPOPMIN" | b"zpopmin" | b"ZPopMin" | b"Zpopmin" => {
+                    let key = get_bytes_vec(v.get(1))?;
+                    let count = match v.get(2) {
+                        Some(count) => Some(get_bytes_vec(Some(count)).and_then(parse_duration)?),
+                        None => None,
+                    };
+
+                    Ok(ZPop(key, count, false))
+                }
+                b"ZPOPMAX" | b"zpopmax" | b"ZPopMax" | b"Zpopmax" => {
+                    let key = get_bytes_vec(v.get(1))?;
+                    let count = match v.get(2) {
+                        Some(count) => Some(get_bytes_vec(Some(count)).and_then(parse_duration)?),
+                        None => None,
+                    };
+
+                    Ok(ZPop(key, count, true))
+                }
+                b"BZPOPMIN" | b"bzpopmin" | b"BZPopMin" | b"Bzpopmin" => {
+                    let (timeout, keys) = v[1..].split_last().ok_or(ArgNumber)?;
+                    let keys = get_bytes_vecs(keys)?;
+                    let timeout = get_bytes_vec(Some(timeout)).and_then(parse_timeout)?;
+
+                    Ok(BZPop(keys, timeout, false))
+                }
+                b"BZPOPMAX" | b"bzpopmax" | b"BZPopMax" | b"Bzpopmax" => {
+                    let (timeout, keys) = v[1..].split_last().ok_or(ArgNumber)?;
+                    let keys = get_bytes_vecs(keys)?;
+                    let timeout = get_bytes_vec(Some(timeout)).and_then(parse_timeout)?;
+
+                    Ok(BZPop(keys, timeout, true))
+                }
+                b"ZUNION" | b"zunion" | b"ZUnion" | b"Zunion" => {
+                    let (keys, options) = get_numkeys(&v[1..])?;
+                    let (weights, aggregate, with_scores) =
+                        get_zcombine_options(options, keys.len(), true)?;
+
+                    Ok(ZUnion(keys, weights, aggregate, with_scores))
+                }
+                b"ZINTER" | b"zinter" | b"ZInter" | b"Zinter" => {
+                    let (keys, options) = get_numkeys(&v[1..])?;
+                    let (weights, aggregate, with_scores) =
+                        get_zcombine_options(options, keys.len(), true)?;
+
+                    Ok(ZInter(keys, weights, aggregate, with_scores))
+                }
+                b"ZDIFF" | b"zdiff" | b"ZDiff" | b"Zdiff" => {
+                    let (keys, options) = get_numkeys(&v[1..])?;
+                    let with_scores = get_flag(options, b"WITHSCORES")?;
+
+                    Ok(ZDiff(keys, with_scores))
+                }
+                b"ZUNIONSTORE" | b"zunionstore" | b"ZUnionStore" | b"Zunionstore" => {
+                    let destination = get_bytes_vec(v.get(1))?;
+                    let (keys, options) = get_numkeys(&v[2..])?;
+                    let (weights, aggregate, _) = get_zcombine_options(options, keys.len(), false)?;
+
+                    Ok(ZUnionStore(destination, keys, weights, aggregate))
+                }
+                b"ZINTERSTORE" | b"zinterstore" | b"ZInterStore" | b"Zinterstore" => {
+                    let destination = get_bytes_vec(v.get(1))?;
+                    let (keys, options) = get_numkeys(&v[2..])?;
+                    let (weights, aggregate, _) = get_zcombine_options(options, keys.len(), false)?;
+
+                    Ok(ZInterStore(destination, keys, weights, aggregate))
+                }
+                b"ZDIFFSTORE" | b"zdiffstore" | b"ZDiffStore" | b"Zdiffstore" => {
+                    let destination = get_bytes_vec(v.get(1))?;
+                    let (keys, options) = get_numkeys(&v[2..])?;
+                    if !options.is_empty() {
+                        return Err(Syntax);
+                    }
+
+                    Ok(ZDiffStore(destination, keys))
+                }
+                b"ZRANGE" | b"zrange" | b"ZRange" | b"Zrange" => {
+                    let key = get_bytes_vec(v.get(1))?;
+                    let (query, with_scores) = get_zrange_query(&v[2..])?;
+
+                    Ok(ZRange(key, query, with_scores))
+                }
+                b"ZRANGESTORE" | b"zrangestore" | b"ZRangeStore" | b"Zrangestore" => {
+                    let destination = get_bytes_vec(v.get(1))?;
+                    let source = get_bytes_vec(v.get(2))?;
+                    let query = match get_zrange_query(&v[3..])? {
+                        (query, false) => query,
+                        (_, true) => return Err(Syntax),
+                    };
+
+                    Ok(ZRangeStore(destination, source, query))
+                }
+                b"ZREVRANGE" | b"zrevrange" | b"ZRevRange" | b"Zrevrange" => {
+                    let key = get_bytes_vec(v.get(1))?;
+                    let start = get_bytes_vec(v.get(2)).and_then(parse_integer)?;
+                    let stop = get_bytes_vec(v.get(3)).and_then(parse_integer)?;
+                    let with_scores = match v.get(4) {
+                        Some(option) => match get_bytes_vec(Some(option))? {
+                            option if option.eq_ignore_ascii_case(b"WITHSCORES") => true,
+                            _ => return Err(Syntax),
+                        },
+                        None => false,
+                    };
+
+                    let query = RangeQuery {
+                        by: RangeBy::Rank(start, stop),
+                        rev: true,
+                        limit: None,
+                    };
+
+                    Ok(ZRange(key, query, with_scores))
+                }
+                b"ZRANGEBYSCORE" | b"zrangebyscore" | b"ZRangeByScore" | b"Zrangebyscore" => {
+                    let key = get_bytes_vec(v.get(1))?;
+                    let (query, with_scores) = get_legacy_zrange_query(&v[2..], false, false)?;
+
+                    Ok(ZRange(key, query, with_scores))
+                }
+                b"ZREVRANGEBYSCORE" | b"zrevrangebyscore" | b"ZRevRangeByScore"
+                | b"Zrevrangebyscore" => {
+                    let key = get_bytes_vec(v.get(1))?;
+                    let (query, with_scores) = get_legacy_zrange_query(&v[2..], false, true)?;
+
+                    Ok(ZRange(key, query, with_scores))
+                }
+                b"ZRANGEBYLEX" | b"zrangebylex" | b"ZRangeByLex" | b"Zrangebylex" => {
+                    let key = get_bytes_vec(v.get(1))?;
+                    let (query, with_scores) = get_legacy_zrange_query(&v[2..], true, false)?;
+
+                    Ok(ZRange(key, query, with_scores))
+                }
+                b"ZREVRANGEBYLEX" | b"zrevrangebylex" | b"ZRevRangeByLex" | b"Zrevrangebylex" => {
+                    let key = get_bytes_vec(v.get(1))?;
+                    let (query, with_scores) = get_legacy_zrange_query(&v[2..], true, true)?;
+
+                    Ok(ZRange(key, query, with_scores))
+                }
+                b"ZREMRANGEBYRANK" | b"zremrangebyrank" | b"ZRemRangeByRank"
+                | b"Zremrangebyrank" => {
+                    let key = get_bytes_vec(v.get(1))?;
+                    let start = get_bytes_vec(v.get(2)).and_then(parse_integer)?;
+                    let stop = get_bytes_vec(v.get(3)).and_then(parse_integer)?;
+
+                    Ok(ZRemRange(key, RangeBy::Rank(start, stop)))
+                }
+                b"ZREMRANGEBYSCORE" | b"zremrangebyscore" | b"ZRemRangeByScore"
+                | b"Zremrangebyscore" => {
+                    let key = get_bytes_vec(v.get(1))?;
+                    let min = get_bytes_vec(v.get(2)).and_then(parse_score_bound)?;
+                    let max = get_bytes_vec(v.get(3)).and_then(parse_score_bound)?;
+
+                    Ok(ZRemRange(key, RangeBy::Score(min, max)))
+                }
+                b"ZREMRANGEBYLEX" | b"zremrangebylex" | b"ZRemRangeByLex" | b"Zremrangebylex" => {
+                    let key = get_bytes_vec(v.get(1))?;
+                    let min = get_bytes_vec(v.get(2)).and_then(parse_lex_bound)?;
+                    let max = get_bytes_vec(v.get(3)).and_then(parse_lex_bound)?;
+
+                    Ok(ZRemRange(key, RangeBy::Lex(min, max)))
+                }
                 b"DEL" | b"del" | b"Del" => {
                     let key = get_bytes_vec(v.get(1))?;
                     Ok(Del(key))
                 }
+                b"MOVE" | b"move" | b"Move" => {
+                    if v.len() != 3 {
+                        return Err(ArgNumber);
+                    }
+                    let key = get_bytes_vec(v.get(1))?;
+                    let db = get_bytes_vec(v.get(2)).and_then(parse_integer)?;
+
+                    Ok(Move(key, db))
+                }
                 b"INCR" | b"incr" | b"Incr" => {
                     let key = get_bytes_vec(v.get(1))?;
                     Ok(Incr(key))
                 }
+                b"SETBIT" | b"setbit" | b"SetBit" | b"Setbit" => {
+                    let key = get_bytes_vec(v.get(1))?;
+                    let offset = get_bytes_vec(v.get(2)).and_then(parse_bit_offset)?;
+                    let bit = match get_bytes_vec(v.get(3))?.as_slice() {
+                        b"0" => false,
+                        b"1" => true,
+                        _ => return Err(BitValue),
+                    };
+
+                    Ok(SetBit(key, offset, bit))
+                }
+                b"GETBIT" | b"getbit" | b"GetBit" | b"Getbit" => {
+                    let key = get_bytes_vec(v.get(1))?;
+                    let offset = get_bytes_vec(v.get(2)).and_then(parse_bit_offset)?;
+
+                    Ok(GetBit(key, offset))
+                }
+                b"BITCOUNT" | b"bitcount" | b"BitCount" | b"Bitcount" => {
+                    let key = get_bytes_vec(v.get(1))?;
+                    let (range, unit) = match &v[2..] {
+                        [] => (None, BitUnit::Byte),
+                        [start, end, options @ ..] => {
+                            let start = get_bytes_vec(Some(start)).and_then(parse_integer)?;
+                            let end = get_bytes_vec(Some(end)).and_then(parse_integer)?;
+                            let unit = match options {
+                                [] => BitUnit::Byte,
+                                [unit] => get_bytes_vec(Some(unit)).and_then(parse_bit_unit)?,
+                                _ => return Err(Syntax),
+                            };
+
+                            (Some((start, end)), unit)
+                        }
+                        _ => return Err(Syntax),
+                    };
+
+                    Ok(BitCount(key, range, unit))
+                }
+                b"BITPOS" | b"bitpos" | b"BitPos" | b"Bitpos" => {
+                    let key = get_bytes_vec(v.get(1))?;
+                    let bit = match get_bytes_vec(v.get(2))?.as_slice() {
+                        b"0" => false,
+                        b"1" => true,
+                        _ => return Err(BitArgument),
+                    };
+
+                    let start = match v.get(3) {
+                        Some(start) => Some(get_bytes_vec(Some(start)).and_then(parse_integer)?),
+                        None => None,
+                    };
+                    let end = match v.get(4) {
+                        Some(end) => Some(get_bytes_vec(Some(end)).and_then(parse_integer)?),
+                        None => None,
+                    };
+                    let unit = match v.get(5) {
+                        Some(unit) => get_bytes_vec(Some(unit)).and_then(parse_bit_unit)?,
+                        None => BitUnit::Byte,
+                    };
+
+                    if v.len() > 6 {
+                        return Err(Syntax);
+                    }
+
+                    Ok(BitPos(key, bit, start, end, unit))
+                }
+                b"BITOP" | b"bitop" | b"BitOp" | b"Bitop" => {
+                    let operation = match get_bytes_vec(v.get(1))?.to_ascii_uppercase().as_slice() {
+                        b"AND" => BitOperation::And,
+                        b"OR" => BitOperation::Or,
+                        b"XOR" => BitOperation::Xor,
+                        b"NOT" => BitOperation::Not,
+                        _ => return Err(Syntax),
+                    };
+                    let destination = get_bytes_vec(v.get(2))?;
+                    let keys = get_bytes_vecs(&v[3..])?;
+
+                    if operation == BitOperation::Not && keys.len() > 1 {
+                        return Err(BitNotKeys);
+                    }
+
+                    Ok(BitOp(operation, destination, keys))
+                }
+                b"BITFIELD" | b"bitfield" | b"BitField" | b"Bitfield" => {
+                    let key = get_bytes_vec(v.get(1))?;
+                    let operations = get_bitfield_ops(&v[2..], false)?;
+
+                    Ok(BitField(key, operations))
+                }
+                b"BITFIELD_RO" | b"bitfield_ro" | b"BitField_RO" | b"Bitfield_ro" => {
+                    let key = get_bytes_vec(v.get(1))?;
+                    let operations = get_bitfield_ops(&v[2..], true)?;
+
+                    Ok(BitField(key, operations))
+                }
+                b"GEOADD" | b"geoadd" | b"GeoAdd" | b"Geoadd" => {
+                    let key = get_bytes_vec(v.get(1))?;
+                    let (options, triples) = get_zadd_options(&v[2..])?;
+                    if options.gt || options.lt || options.incr {
+                        return Err(Syntax);
+                    }
+
+                    // members are stored in a sorted set, scored by their geohash
+                    let members = get_geo_members(triples)?;
+                    Ok(ZAdd(key, options, members))
+                }
+                b"GEOPOS" | b"geopos" | b"GeoPos" | b"Geopos" => {
+                    let key = get_bytes_vec(v.get(1))?;
+                    let members = get_bytes_vecs(&v[2..])?;
+
+                    Ok(GeoPos(key, members))
+                }
+                b"GEODIST" | b"geodist" | b"GeoDist" | b"Geodist" => {
+                    let key = get_bytes_vec(v.get(1))?;
+                    let member1 = get_bytes_vec(v.get(2))?;
+                    let member2 = get_bytes_vec(v.get(3))?;
+                    let unit = match v.get(4) {
+                        Some(unit) => parse_distance_unit(get_bytes_vec(Some(unit))?)?,
+                        None => DistanceUnit::Meters,
+                    };
+                    if v.len() > 5 {
+                        return Err(Syntax);
+                    }
+
+                    Ok(GeoDist(key, member1, member2, unit))
+                }
+                b"GEOSEARCH" | b"geosearch" | b"GeoSearch" | b"Geosearch" => {
+                    let key = get_bytes_vec(v.get(1))?;
+                    let (query, reply, _) = get_geosearch_options(&v[2..], false)?;
+
+                    Ok(GeoSearch(key, query, reply))
+                }
+                b"GEOSEARCHSTORE" | b"geosearchstore" | b"GeoSearchStore" | b"Geosearchstore" => {
+                    let destination = get_bytes_vec(v.get(1))?;
+                    let source = get_bytes_vec(v.get(2))?;
+                    let (query, _, store_dist) = get_geosearch_options(&v[3..], true)?;
+
+                    Ok(GeoSearchStore(destination, source, query, store_dist))
+                }
+                b"XADD" | b"xadd" | b"XAdd" | b"Xadd" => {
+                    let key = get_bytes_vec(v.get(1))?;
+                    let (no_mkstream, trim, rest) = get_xadd_options(&v[2..])?;
+                    let id = get_bytes_vec(rest.first()).and_then(parse_xadd_id)?;
+                    let fields = get_pairs(&rest[1..])?;
+
+                    Ok(XAdd(key, no_mkstream, trim, id, fields))
+                }
+                b"XTRIM" | b"xtrim" | b"XTrim" | b"Xtrim" => {
+                    let key = get_bytes_vec(v.get(1))?;
+                    match get_stream_trim(&v[2..])? {
+                        (trim, []) => Ok(XTrim(key, trim)),
+                        _ => Err(Syntax),
+                    }
+                }
+                b"XDEL" | b"xdel" | b"XDel" | b"Xdel" => {
+                    let key = get_bytes_vec(v.get(1))?;
+
+                    let mut ids = vec![];
+                    for id in get_bytes_vecs(&v[2..])? {
+                        ids.push(parse_stream_id(&id, 0)?);
+                    }
+
+                    Ok(XDel(key, ids))
+                }
+                b"XLEN" | b"xlen" | b"XLen" | b"Xlen" => {
+                    let key = get_bytes_vec(v.get(1))?;
+                    Ok(XLen(key))
+                }
+                b"XRANGE" | b"xrange" | b"XRange" | b"Xrange" => {
+                    let key = get_bytes_vec(v.get(1))?;
+                    let start = get_bytes_vec(v.get(2))?;
+                    let end = get_bytes_vec(v.get(3))?;
+                    let count = get_stream_count(&v[4..])?;
+
+                    let start = parse_stream_bound(start, true)?;
+                    let end = parse_stream_bound(end, false)?;
+                    Ok(XRange(key, start, end, count, false))
+                }
+                b"XREVRANGE" | b"xrevrange" | b"XRevRange" | b"Xrevrange" => {
+                    let key = get_bytes_vec(v.get(1))?;
+                    let end = get_bytes_vec(v.get(2))?;
+                    let start = get_bytes_vec(v.get(3))?;
+                    let count = get_stream_count(&v[4..])?;
+
+                    let start = parse_stream_bound(start, true)?;
+                    let end = parse_stream_bound(end, false)?;
+                    Ok(XRange(key, start, end, count, true))
+                }
+                b"XREAD" | b"xread" | b"XRead" | b"Xread" => {
+                    let (count, block, _, streams) = get_xread_options(&v[1..], false)?;
+                    let streams = get_streams(streams, parse_xread_id)?;
+
+                    Ok(XRead(streams, count, block))
+                }
+                b"XGROUP" | b"xgroup" | b"XGroup" | b"Xgroup" => {
+                    let subcommand = get_bytes_vec(v.get(1))?;
+                    let key = get_bytes_vec(v.get(2))?;
+                    let group = get_bytes_vec(v.get(3))?;
+
+                    match subcommand.to_ascii_uppercase().as_slice() {
+                        b"CREATE" => {
+                            let id = get_bytes_vec(v.get(4))?;
+                            let mkstream = get_flag(&v[5..], b"MKSTREAM")?;
+
+                            Ok(XGroupCreate(key, group, parse_xread_id(&id)?, mkstream))
+                        }
+                        b"SETID" if v.len() == 5 => {
+                            let id = get_bytes_vec(v.get(4))?;
+                            Ok(XGroupSetId(key, group, parse_xread_id(&id)?))
+                        }
+                        b"DESTROY" if v.len() == 4 => Ok(XGroupDestroy(key, group)),
+                        b"CREATECONSUMER" if v.len() == 5 => {
+                            let consumer = get_bytes_vec(v.get(4))?;
+                            Ok(XGroupCreateConsumer(key, group, consumer))
+                        }
+                        b"DELCONSUMER" if v.len() == 5 => {
+                            let consumer = get_bytes_vec(v.get(4))?;
+                            Ok(XGroupDelConsumer(key, group, consumer))
+                        }
+                        _ => Err(Syntax),
+                    }
+                }
+                b"XREADGROUP" | b"xreadgroup" | b"XReadGroup" | b"Xreadgroup" => {
+                    match get_bytes_vec(v.get(1))? {
+                        keyword if keyword.eq_ignore_ascii_case(b"GROUP") => {}
+                        _ => return Err(Syntax),
+                    }
+                    let group = get_bytes_vec(v.get(2))?;
+                    let consumer = get_bytes_vec(v.get(3))?;
+                    let (count, block, no_ack, streams) = get_xread_options(&v[4..], true)?;
+                    let streams = get_streams(streams, parse_xreadgroup_id)?;
+
+                    Ok(XReadGroup(group, consumer, streams, count, block, no_ack))
+                }
+                b"XACK" | b"xack" | b"XAck" | b"Xack" => {
+                    let key = get_bytes_vec(v.get(1))?;
+                    let group = get_bytes_vec(v.get(2))?;
+
+                    let mut ids = vec![];
+                    for id in get_bytes_vecs(&v[3..])? {
+                        ids.push(parse_stream_id(&id, 0)?);
+                    }
+
+                    Ok(XAck(key, group, ids))
+                }
+                b"XPENDING" | b"xpending" | b"XPending" | b"Xpending" => {
+                    let key = get_bytes_vec(v.get(1))?;
+                    let group = get_bytes_vec(v.get(2))?;
+                    let query = get_pending_query(&v[3..])?;
+
+                    Ok(XPending(key, group, query))
+                }
+                b"XCLAIM" | b"xclaim" | b"XClaim" | b"Xclaim" => {
+                    let key = get_bytes_vec(v.get(1))?;
+                    let group = get_bytes_vec(v.get(2))?;
+                    let consumer = get_bytes_vec(v.get(3))?;
+                    let min_idle = get_bytes_vec(v.get(4)).and_then(parse_duration)?;
+                    let (ids, options) = get_xclaim_ids(&v[5..])?;
+
+                    Ok(XClaim(key, group, consumer, min_idle, ids, options))
+                }
+                b"XAUTOCLAIM" | b"xautoclaim" | b"XAutoClaim" | b"Xautoclaim" => {
+                    let key = get_bytes_vec(v.get(1))?;
+                    let group = get_bytes_vec(v.get(2))?;
+                    let consumer = get_bytes_vec(v.get(3))?;
+                    let min_idle = get_bytes_vec(v.get(4)).and_then(parse_duration)?;
+                    let start = parse_stream_id(&get_bytes_vec(v.get(5))?, 0)?;
+                    let (count, just_id) = get_xautoclaim_options(&v[6..])?;
+
+                    Ok(XAutoClaim(
+                        key, group, consumer, min_idle, start, count, just_id,
+                    ))
+                }
+                b"XINFO" | b"xinfo" | b"XInfo" | b"Xinfo" => {
+                    let subcommand = get_bytes_vec(v.get(1))?;
+                    let key = get_bytes_vec(v.get(2))?;
+
+                    match subcommand.to_ascii_uppercase().as_slice() {
+                        b"STREAM" => Ok(XInfoStream(key, get_xinfo_full(&v[3..])?)),
+                        b"GROUPS" if v.len() == 3 => Ok(XInfoGroups(key)),
+                        b"CONSUMERS" if v.len() == 4 => {
+                            let group = get_bytes_vec(v.get(3))?;
+                            Ok(XInfoConsumers(key, group))
+                        }
+                        _ => Err(Syntax),
+                    }
+                }
+                b"LCS" | b"lcs" | b"Lcs" => {
+                    let key1 = get_bytes_vec(v.get(1))?;
+                    let key2 = get_bytes_vec(v.get(2))?;
+                    let options = get_lcs_options(&v[3..])?;
+
+                    Ok(Lcs(key1, key2, options))
+                }
+                b"SORT" | b"sort" | b"Sort" => {
+                    let key = get_bytes_vec(v.get(1))?;
+                    Ok(Sort(key, get_sort_query(&v[2..], false)?))
+                }
+                b"SORT_RO" | b"sort_ro" | b"Sort_Ro" | b"Sort_ro" => {
+                    let key = get_bytes_vec(v.get(1))?;
+                    Ok(Sort(key, get_sort_query(&v[2..], true)?))
+                }
+                b"SUBSCRIBE" | b"subscribe" | b"Subscribe" => {
+                    let channels = get_bytes_vecs(&v[1..])?;
+                    Ok(Subscribe(channels))
+                }
+                b"UNSUBSCRIBE" | b"unsubscribe" | b"Unsubscribe" => {
+                    let channels = match v.len() {
+                        1 => vec![],
+                        _ => get_bytes_vecs(&v[1..])?,
+                    };
+                    Ok(Unsubscribe(channels))
+                }
+                b"PSUBSCRIBE" | b"psubscribe" | b"PSubscribe" | b"Psubscribe" => {
+                    let patterns = get_bytes_vecs(&v[1..])?;
+                    Ok(PSubscribe(patterns))
+                }
+                b"PUNSUBSCRIBE" | b"punsubscribe" | b"PUnsubscribe" | b"Punsubscribe" => {
+                    let patterns = match v.len() {
+                        1 => vec![],
+                        _ => get_bytes_vecs(&v[1..])?,
+                    };
+                    Ok(PUnsubscribe(patterns))
+                }
+                b"PUBSUB" | b"pubsub" | b"PubSub" | b"Pubsub" => {
+                    let subcommand = get_bytes_vec(v.get(1))?;
+                    let pattern = match v.len() {
+                        2 => None,
+                        _ => Some(get_bytes_vec(v.get(2))?),
+                    };
+
+                    match subcommand.to_ascii_uppercase().as_slice() {
+                        b"CHANNELS" if v.len() <= 3 => Ok(PubSubChannels(pattern)),
+                        b"NUMSUB" => match v.len() {
+                            2 => Ok(PubSubNumSub(vec![])),
+                            _ => Ok(PubSubNumSub(get_bytes_vecs(&v[2..])?)),
+                        },
+                        b"NUMPAT" if v.len() == 2 => Ok(PubSubNumPat),
+                        b"SHARDCHANNELS" if v.len() <= 3 => Ok(PubSubShardChannels(pattern)),
+                        b"SHARDNUMSUB" => match v.len() {
+                            2 => Ok(PubSubShardNumSub(vec![])),
+                            _ => Ok(PubSubShardNumSub(get_bytes_vecs(&v[2..])?)),
+                        },
+                        _ => Err(Syntax),
+                    }
+                }
+                b"SSUBSCRIBE" | b"ssubscribe" | b"SSubscribe" | b"Ssubscribe" => {
+                    let channels = get_bytes_vecs(&v[1..])?;
+                    Ok(SSubscribe(channels))
+                }
+                b"SUNSUBSCRIBE" | b"sunsubscribe" | b"SUnsubscribe" | b"Sunsubscribe" => {
+                    let channels = match v.len() {
+                        1 => vec![],
+                        _ => get_bytes_vecs(&v[1..])?,
+                    };
+                    Ok(SUnsubscribe(channels))
+                }
+                b"SPUBLISH" | b"spublish" | b"SPublish" | b"Spublish" => {
+                    let channel = get_bytes_vec(v.get(1))?;
+                    let message = get_bytes_vec(v.get(2))?;
+                    match v.len() {
+                        3 => Ok(SPublish(channel, message)),
+                        _ => Err(ArgNumber),
+                    }
+                }
+                b"PUBLISH" | b"publish" | b"Publish" => {
+                    let channel = get_bytes_vec(v.get(1))?;
+                    let message = get_bytes_vec(v.get(2))?;
+                    match v.len() {
+                        3 => Ok(Publish(channel, message)),
+                        _ => Err(ArgNumber),
+                    }
+                }
+                b"PFADD" | b"pfadd" | b"PfAdd" | b"Pfadd" => {
+                    let key = get_bytes_vec(v.get(1))?;
+                    let elements = match v.len() {
+                        2 => vec![],
+                        _ => get_bytes_vecs(&v[2..])?,
+                    };
+
+                    Ok(PfAdd(key, elements))
+                }
+                b"PFCOUNT" | b"pfcount" | b"PfCount" | b"Pfcount" => {
+                    let keys = get_bytes_vecs(&v[1..])?;
+                    Ok(PfCount(keys))
+                }
+                b"PFMERGE" | b"pfmerge" | b"PfMerge" | b"Pfmerge" => {
+                    let destination = get_bytes_vec(v.get(1))?;
+                    let keys = match v.len() {
+                        2 => vec![],
+                        _ => get_bytes_vecs(&v[2..])?,
+                    };
+
+                    Ok(PfMerge(destination, keys))
+                }
                 b"EXISTS" | b"exists" | b"Exists" => {
                     let key = get_bytes_vec(v.get(1))?;
                     Ok(Exists(key))
                 }
-                b"INFO" | b"info" | b"Info" => Ok(Info),
-                b"PING" | b"ping" | b"Ping" => Ok(Ping),
+                b"CONFIG" | b"config" | b"Config" => {
+                    let subcommand = get_bytes_vec(v.get(1))?;
+                    match subcommand.to_ascii_uppercase().as_slice() {
+                        b"GET" => Ok(ConfigGet(get_bytes_vecs(&v[2..])?)),
+                        b"SET" => Ok(ConfigSet(get_pairs(&v[2..])?)),
+                        _ => Err(Syntax),
+                    }
+                }
+                b"CLIENT" | b"client" | b"Client" => {
+                    let subcommand = get_bytes_vec(v.get(1))?;
+                    match subcommand.to_ascii_uppercase().as_slice() {
+                        b"ID" if v.len() == 2 => Ok(ClientId),
+                        b"SETNAME" if v.len() == 3 => Ok(ClientSetName(get_bytes_vec(v.get(2))?)),
+                        b"GETNAME" if v.len() == 2 => Ok(ClientGetName),
+                        b"LIST" => {
+                            let (client_type, ids) = get_client_list_options(&v[2..])?;
+                            Ok(ClientList(client_type, ids))
+                        }
+                        b"INFO" if v.len() == 2 => Ok(ClientInfo),
+                        b"KILL" => {
+                            let (filter, old_form) = get_client_kill_filter(&v[2..])?;
+                            Ok(ClientKill(filter, old_form))
+                        }
+                        b"PAUSE" if v.len() == 3 || v.len() == 4 => {
+                            let timeout = get_bytes_vec(v.get(2)).and_then(parse_duration)?;
+                            let write_only = match v.get(3) {
+                                Some(mode) => {
+                                    match get_bytes_vec(Some(mode))?.to_ascii_uppercase().as_slice()
+                                    {
+                                        b"WRITE" => true,
+                                        b"ALL" => false,
+                                        _ => return Err(Syntax),
+                                    }
+                                }
+                                None => false,
+                            };
+
+                            Ok(ClientPause(timeout, write_only))
+                        }
+                        b"UNPAUSE" if v.len() == 2 => Ok(ClientUnpause),
+                        b"TRACKING" => Ok(ClientTracking(get_tracking_options(&v[2..])?)),
+                        _ => Err(Syntax),
+                    }
+                }
+                b"MULTI" | b"multi" | b"Multi" => Ok(Multi),
+                b"EXEC" | b"exec" | b"Exec" => Ok(Exec),
+                b"DISCARD" | b"discard" | b"Discard" => Ok(Discard),
+                b"WATCH" | b"watch" | b"Watch" => {
+                    let keys = get_bytes_vecs(&v[1..])?;
+                    Ok(Watch(keys))
+                }
+                b"EVAL" | b"eval" | b"Eval" => {
+                    let script = get_bytes_vec(v.get(1))?;
+                    let (keys, args) = get_script_keys_and_args(&v[2..])?;
+                    Ok(Eval(script, keys, args))
+                }
+                b"EVALSHA" | b"evalsha" | b"EvalSha" | b"Evalsha" => {
+                    let sha = get_bytes_vec(v.get(1))?;
+                    let (keys, args) = get_script_keys_and_args(&v[2..])?;
+                    Ok(EvalSha(sha, keys, args))
+                }
+                b"SCRIPT" | b"script" | b"Script" => {
+                    let subcommand = get_bytes_vec(v.get(1))?;
+                    match subcommand.to_ascii_uppercase().as_slice() {
+                        b"LOAD" if v.len() == 3 => Ok(ScriptLoad(get_bytes_vec(v.get(2))?)),
+                        b"EXISTS" => Ok(ScriptExists(get_bytes_vecs(&v[2..])?)),
+                        // scripts are always flushed right away
+                        b"FLUSH" => match v.len() {
+                            2 => Ok(ScriptFlush),
+                            3 => match get_bytes_vec(v.get(2))?.to_ascii_uppercase().as_slice() {
+                                b"ASYNC" | b"SYNC" => Ok(ScriptFlush),
+                                _ => Err(Syntax),
+                            },
+                            _ => Err(ArgNumber),
+                        },
+                        b"KILL" if v.len() == 2 => Ok(ScriptKill),
+                        _ => Err(Syntax),
+                    }
+                }
+                b"FUNCTION" | b"function" | b"Function" => {
+                    let subcommand = get_bytes_vec(v.get(1))?;
+                    match subcommand.to_ascii_uppercase().as_slice() {
+                        b"LOAD" => match v.len() {
+                            3 => Ok(FunctionLoad(get_bytes_vec(v.get(2))?, false)),
+                            4 => match get_bytes_vec(v.get(2))?.to_ascii_uppercase().as_slice() {
+                                b"REPLACE" => Ok(FunctionLoad(get_bytes_vec(v.get(3))?, true)),
+                                _ => Err(Syntax),
+                            },
+                            _ => Err(ArgNumber),
+                        },
+                        b"LIST" => {
+                            let mut pattern = None;
+                            let mut with_code = false;
+                            let mut options = v[2..].iter();
+                            while let Some(option) = options.next() {
+                                let option = get_bytes_vec(Some(option))?;
+                                match option.to_ascii_uppercase().as_slice() {
+                                    b"WITHCODE" => with_code = true,
+                                    b"LIBRARYNAME" => {
+                                        pattern = Some(get_bytes_vec(options.next())?)
+                                    }
+                                    _ => return Err(Syntax),
+                                }
+                            }
+                            Ok(FunctionList(pattern, with_code))
+                        }
+                        b"DELETE" if v.len() == 3 => Ok(FunctionDelete(get_bytes_vec(v.get(2))?)),
+                        b"DUMP" if v.len() == 2 => Ok(FunctionDump),
+                        // functions are always flushed right away
+                        b"FLUSH" => match v.len() {
+                            2 => Ok(FunctionFlush),
+                            3 => match get_bytes_vec(v.get(2))?.to_ascii_uppercase().as_slice() {
+                                b"ASYNC" | b"SYNC" => Ok(FunctionFlush),
+                                _ => Err(Syntax),
+                            },
+                            _ => Err(ArgNumber),
+                        },
+                        b"KILL" if v.len() == 2 => Ok(FunctionKill),
+                        _ => Err(Syntax),
+                    }
+                }
+                b"FCALL" | b"fcall" | b"FCall" | b"Fcall" => {
+                    let function = get_bytes_vec(v.get(1))?;
+                    let (keys, args) = get_script_keys_and_args(&v[2..])?;
+                    Ok(FCall(function, keys, args, false))
+                }
+                b"FCALL_RO" | b"fcall_ro" | b"FCall_RO" | b"Fcall_ro" => {
+                    let function = get_bytes_vec(v.get(1))?;
+                    let (keys, args) = get_script_keys_and_args(&v[2..])?;
+                    Ok(FCall(function, keys, args, true))
+                }
+                b"UNWATCH" | b"unwatch" | b"Unwatch" => match v.len() {
+                    1 => Ok(Unwatch),
+                    _ => Err(ArgNumber),
+                },
+                b"AUTH" | b"auth" | b"Auth" => match v.len() {
+                    2 => Ok(Auth(None, get_bytes_vec(v.get(1))?)),
+                    3 => Ok(Auth(
+                        Some(get_bytes_vec(v.get(1))?),
+                        get_bytes_vec(v.get(2))?,
+                    )),
+                    _ => Err(Syntax),
+                },
+                b"HELLO" | b"hello" | b"Hello" => {
+                    let protocol = match v.get(1) {
+                        Some(protocol) => {
+                            Some(get_bytes_vec(Some(protocol)).and_then(parse_integer)?)
+                        }
+                        None => None,
+                    };
+                    let mut auth = None;
+                    let mut name = None;
+                    let mut index = 2;
+                    while index < v.len() {
+                        match get_bytes_vec(v.get(index))?.to_ascii_uppercase().as_slice() {
+                            b"AUTH" if index + 2 < v.len() => {
+                                auth = Some((
+                                    get_bytes_vec(v.get(index + 1))?,
+                                    get_bytes_vec(v.get(index + 2))?,
+                                ));
+                                index += 3;
+                            }
+                            b"SETNAME" if index + 1 < v.len() => {
+                                name = Some(get_bytes_vec(v.get(index + 1))?);
+                                index += 2;
+                            }
+                            _ => return Err(Syntax),
+                        }
+                    }
+                    Ok(Hello(protocol, auth, name))
+                }
+                b"ACL" | b"acl" | b"Acl" => {
+                    let subcommand = get_bytes_vec(v.get(1))?;
+                    match subcommand.to_ascii_uppercase().as_slice() {
+                        b"SETUSER" => {
+                            let username = get_bytes_vec(v.get(2))?;
+                            let rules = match v.len() {
+                                3 => vec![],
+                                _ => get_bytes_vecs(&v[3..])?,
+                            };
+                            Ok(AclSetUser(username, rules))
+                        }
+                        b"GETUSER" if v.len() == 3 => Ok(AclGetUser(get_bytes_vec(v.get(2))?)),
+                        b"DELUSER" => Ok(AclDelUser(get_bytes_vecs(&v[2..])?)),
+                        b"LIST" if v.len() == 2 => Ok(AclList),
+                        b"USERS" if v.len() == 2 => Ok(AclUsers),
+                        b"WHOAMI" if v.len() == 2 => Ok(AclWhoAmI),
+                        b"CAT" => match v.len() {
+                            2 => Ok(AclCat(None)),
+                            3 => Ok(AclCat(Some(get_bytes_vec(v.get(2))?))),
+                            _ => Err(ArgNumber),
+                        },
+                        _ => Err(Syntax),
+                    }
+                }
+                b"SELECT" | b"select" | b"Select" => match v.len() {
+                    2 => Ok(Select(get_bytes_vec(v.get(1)).and_then(parse_integer)?)),
+                    _ => Err(ArgNumber),
+                },
+                b"SWAPDB" | b"swapdb" | b"SwapDb" => {
+                    if v.len() != 3 {
+                        return Err(ArgNumber);
+                    }
+                    let first = get_bytes_vec(v.get(1)).and_then(parse_integer)?;
+                    let second = get_bytes_vec(v.get(2)).and_then(parse_integer)?;
+
+                    Ok(SwapDb(first, second))
+                }
+                b"COMMAND" | b"command" | b"Command" => match v.get(1) {
+                    None => Ok(Commands),
+                    Some(subcommand) => {
+                        let names = v[2..]
+                            .iter()
+                            .map(|name| get_bytes_vec(Some(name)))
+                            .collect::<Result<_, _>>()?;
+                        match get_bytes_vec(Some(subcommand))?
+                            .to_ascii_uppercase()
+                            .as_slice()
+                        {
+                            b"COUNT" if v.len() == 2 => Ok(CountCommands),
+                            b"INFO" => Ok(DescribeCommands(names)),
+                            b"DOCS" => Ok(DocumentCommands(names)),
+                            b"GETKEYS" if !names.is_empty() => Ok(GetKeys(names)),
+                            _ => Err(Syntax),
+                        }
+                    }
+                },
+                b"MONITOR" | b"monitor" | b"Monitor" => match v.len() {
+                    1 => Ok(Monitor),
+                    _ => Err(ArgNumber),
+                },
+                b"DEBUG" | b"debug" | b"Debug" => {
+                    let subcommand = get_bytes_vec(v.get(1))?;
+                    match subcommand.to_ascii_uppercase().as_slice() {
+                        b"SLEEP" if v.len() == 3 => {
+                            let seconds = get_bytes_vec(v.get(2)).and_then(parse_score)?;
+                            // inf or a number of seconds too large to sleep for
+                            match Duration::try_from_secs_f64(seconds.max(0.0)) {
+                                Ok(duration) => Ok(DebugSleep(duration)),
+                                Err(_) => Err(TimeoutRange),
+                            }
+                        }
+                        b"OBJECT" if v.len() == 3 => Ok(DebugObject(get_bytes_vec(v.get(2))?)),
+                        b"SET-ACTIVE-EXPIRE" if v.len() == 3 => {
+                            match get_bytes_vec(v.get(2))?.as_slice() {
+                                b"0" => Ok(DebugSetActiveExpire(false)),
+                                b"1" => Ok(DebugSetActiveExpire(true)),
+                                _ => Err(Syntax),
+                            }
+                        }
+                        b"JMAP" if v.len() == 2 => Ok(DebugJmap),
+                        _ => Err(Syntax),
+                    }
+                }
+                b"SHUTDOWN" | b"shutdown" | b"Shutdown" => match v.get(1) {
+                    Some(_) if v.len() > 2 => Err(Syntax),
+                    Some(modifier) => match get_bytes_vec(Some(modifier))?
+                        .to_ascii_uppercase()
+                        .as_slice()
+                    {
+                        b"NOSAVE" => Ok(Shutdown(Some(false))),
+                        b"SAVE" => Ok(Shutdown(Some(true))),
+                        _ => Err(Syntax),
+                    },
+                    None => Ok(Shutdown(None)),
+                },
+                b"SAVE" | b"save" | b"Save" => match v.len() {
+                    1 => Ok(Save),
+                    _ => Err(ArgNumber),
+                },
+                b"BGSAVE" | b"bgsave" | b"Bgsave" | b"BgSave" => match v.len() {
+                    1 => Ok(BgSave),
+                    _ => Err(Syntax),
+                },
+                b"LASTSAVE" | b"lastsave" | b"Lastsave" | b"LastSave" => {
+                    if v.len() != 1 {
+                        return Err(ArgNumber);
+                    }
+                    Ok(LastSave)
+                }
+                b"BGREWRITEAOF" | b"bgrewriteaof" | b"BgRewriteAof" => {
+                    if v.len() != 1 {
+                        return Err(ArgNumber);
+                    }
+                    Ok(BgRewriteAof)
+                }
+                b"REPLICAOF" | b"replicaof" | b"ReplicaOf" | b"SLAVEOF" | b"slaveof"
+                | b"SlaveOf" => {
+                    if v.len() != 3 {
+                        return Err(ArgNumber);
+                    }
+                    let host = get_bytes_vec(v.get(1))?;
+                    let port = get_bytes_vec(v.get(2))?;
+                    match (
+                        host.to_ascii_uppercase().as_slice(),
+                        port.to_ascii_uppercase().as_slice(),
+                    ) {
+                        (b"NO", b"ONE") => Ok(ReplicaOf(None)),
+                        _ => match parse_integer(port).map(u16::try_from) {
+                            Ok(Ok(port)) => Ok(ReplicaOf(Some((host, port)))),
+                            _ => Err(MasterPort),
+                        },
+                    }
+                }
+                b"FAILOVER" | b"failover" | b"Failover" => match get_failover_options(&v[1..])? {
+                    Some(options) => Ok(Failover(options)),
+                    None => Ok(FailoverAbort),
+                },
+                b"SYNC" | b"sync" | b"Sync" => match v.len() {
+                    1 => Ok(Sync),
+                    _ => Err(ArgNumber),
+                },
+                b"PSYNC" | b"psync" | b"PSync" | b"Psync" => match v.len() {
+                    3 => Ok(PSync(
+                        get_bytes_vec(v.get(1))?,
+                        get_bytes_vec(v.get(2)).and_then(parse_integer)?,
+                    )),
+                    _ => Err(ArgNumber),
+                },
+                b"READONLY" | b"readonly" | b"ReadOnly" | b"Readonly" => {
+                    if v.len() != 1 {
+                        return Err(ArgNumber);
+                    }
+                    Ok(ReadOnly)
+                }
+                b"READWRITE" | b"readwrite" | b"ReadWrite" | b"Readwrite" => {
+                    if v.len() != 1 {
+                        return Err(ArgNumber);
+                    }
+                    Ok(ReadWrite)
+                }
+                b"REPLCONF" | b"replconf" | b"ReplConf" | b"Replconf" => {
+                    Ok(ReplConf(get_bytes_vecs(&v[1..]).unwrap_or_default()))
+                }
+                b"LATENCY" | b"latency" | b"Latency" => {
+                    let subcommand = get_bytes_vec(v.get(1))?;
+                    match subcommand.to_ascii_uppercase().as_slice() {
+                        b"HISTORY" if v.len() == 3 => Ok(LatencyHistory(get_bytes_vec(v.get(2))?)),
+                        b"LATEST" if v.len() == 2 => Ok(LatencyLatest),
+                        b"RESET" => Ok(LatencyReset(
+                            v[2..]
+                                .iter()
+                                .map(|event| get_bytes_vec(Some(event)))
+                                .collect::<Result<_, _>>()?,
+                        )),
+                        b"DOCTOR" if v.len() == 2 => Ok(LatencyDoctor),
+                        _ => Err(Syntax),
+                    }
+                }
+                b"CLUSTER" | b"cluster" | b"Cluster" => {
+                    let subcommand = get_bytes_vec(v.get(1))?;
+                    match subcommand.to_ascii_uppercase().as_slice() {
+                        b"INFO" if v.len() == 2 => Ok(ClusterInfo),
+                        b"MYID" if v.len() == 2 => Ok(ClusterMyId),
+                        b"SLOTS" if v.len() == 2 => Ok(ClusterSlots),
+                        b"SHARDS" if v.len() == 2 => Ok(ClusterShards),
+                        b"NODES" if v.len() == 2 => Ok(ClusterNodes),
+                        b"KEYSLOT" if v.len() == 3 => Ok(ClusterKeySlot(get_bytes_vec(v.get(2))?)),
+                        b"MEET" if v.len() == 4 || v.len() == 5 => {
+                            let ip = get_bytes_vec(v.get(2))?;
+                            let port = get_bytes_vec(v.get(3))?;
+                            match parse_integer(port).map(u16::try_from) {
+                                Ok(Ok(port)) => Ok(ClusterMeet(ip, port)),
+                                _ => Err(NodePort),
+                            }
+                        }
+                        b"ADDSLOTS" | b"DELSLOTS" if v.len() >= 3 => {
+                            let slots = v[2..]
+                                .iter()
+                                .map(|slot| get_bytes_vec(Some(slot)).and_then(parse_slot))
+                                .collect::<Result<_, _>>()?;
+                            match subcommand.to_ascii_uppercase().as_slice() {
+                                b"ADDSLOTS" => Ok(ClusterAddSlots(slots)),
+                                _ => Ok(ClusterDelSlots(slots)),
+                            }
+                        }
+                        b"ADDSLOTSRANGE" | b"DELSLOTSRANGE" if v.len() >= 4 => {
+                            let ranges = v[2..].chunks_exact(2);
+                            if !ranges.remainder().is_empty() {
+                                return Err(ArgNumber);
+                            }
+                            let mut slots = vec![];
+                            for range in ranges {
+                                let start = get_bytes_vec(range.first()).and_then(parse_slot)?;
+                                let end = get_bytes_vec(range.get(1)).and_then(parse_slot)?;
+                                if start > end {
+                                    return Err(Slot);
+                                }
+                                slots.extend(start..=end);
+                            }
+                            match subcommand.to_ascii_uppercase().as_slice() {
+                                b"ADDSLOTSRANGE" => Ok(ClusterAddSlots(slots)),
+                                _ => Ok(ClusterDelSlots(slots)),
+                            }
+                        }
+                        _ => Err(Syntax),
+                    }
+                }
+                b"SENTINEL" | b"sentinel" | b"Sentinel" => {
+                    let subcommand = get_bytes_vec(v.get(1))?;
+                    match subcommand.to_ascii_uppercase().as_slice() {
+                        b"MASTERS" if v.len() == 2 => Ok(SentinelMasters),
+                        b"MASTER" if v.len() == 3 => Ok(SentinelMaster(get_bytes_vec(v.get(2))?)),
+                        b"GET-MASTER-ADDR-BY-NAME" if v.len() == 3 => {
+                            Ok(SentinelGetMasterAddrByName(get_bytes_vec(v.get(2))?))
+                        }
+                        _ => Err(Syntax),
+                    }
+                }
+                b"MEMORY" | b"memory" | b"Memory" => {
+                    let subcommand = get_bytes_vec(v.get(1))?;
+                    match subcommand.to_ascii_uppercase().as_slice() {
+                        b"USAGE" if v.len() == 3 || v.len() == 5 => {
+                            // the values are measured as a whole rather than
+                            // sampled, the number of samples is only checked
+                            if let Some(option) = v.get(3) {
+                                match get_bytes_vec(Some(option))?.to_ascii_uppercase().as_slice() {
+                                    b"SAMPLES" => {
+                                        get_bytes_vec(v.get(4)).and_then(parse_integer)?
+                                    }
+                                    _ => return Err(Syntax),
+                                };
+                            }
+                            Ok(MemoryUsage(get_bytes_vec(v.get(2))?))
+                        }
+                        b"STATS" if v.len() == 2 => Ok(MemoryStats),
+                        b"DOCTOR" if v.len() == 2 => Ok(MemoryDoctor),
+                        _ => Err(Syntax),
+                    }
+                }
+                b"SLOWLOG" | b"slowlog" | b"SlowLog" | b"Slowlog" => {
+                    let subcommand = get_bytes_vec(v.get(1))?;
+                    match subcommand.to_ascii_uppercase().as_slice() {
+                        b"GET" if v.len() <= 3 => match v.get(2) {
+                            Some(count) => Ok(SlowLogGet(Some(
+                                get_bytes_vec(Some(count)).and_then(parse_integer)?,
+                            ))),
+                            None => Ok(SlowLogGet(None)),
+                        },
+                        b"LEN" if v.len() == 2 => Ok(SlowLogLen),
+                        b"RESET" if v.len() == 2 => Ok(SlowLogReset),
+                        _ => Err(Syntax),
+                    }
+                }
+                b"INFO" | b"info" | b"Info" => match v.len() {
+                    1 => Ok(Info(vec![])),
+                    _ => Ok(Info(get_bytes_vecs(&v[1..])?)),
+                },
+                b"PING" | b"ping" | b"Ping" => match v.len() {
+                    1 => Ok(Ping(None)),
+                    2 => Ok(Ping(Some(get_bytes_vec(v.get(1))?))),
+                    _ => Err(Syntax),
+                },
+                b"ECHO" | b"echo" | b"Echo" => match v.len() {
+                    2 => Ok(Echo(get_bytes_vec(v.get(1))?)),
+                    _ => Err(ArgNumber),
+                },
+                b"TIME" | b"time" | b"Time" => match v.len() {
+                    1 => Ok(Time),
+                    _ => Err(ArgNumber),
+                },
+                b"LOLWUT" | b"lolwut" | b"Lolwut" => match v.len() {
+                    1 => Ok(Lolwut),
+                    // there is a single banner whatever the version asked
+                    3 => match get_bytes_vec(v.get(1))?.to_ascii_uppercase().as_slice() {
+                        b"VERSION" => {
+                            get_bytes_vec(v.get(2)).and_then(parse_integer)?;
+                            Ok(Lolwut)
+                        }
+                        _ => Err(Syntax),
+                    },
+                    _ => Err(Syntax),
+                },
+                b"WAIT" | b"wait" | b"Wait" => match v.len() {
+                    3 => Ok(Wait(
+                        get_bytes_vec(v.get(1)).and_then(parse_integer)?,
+                        parse_block(get_bytes_vec(v.get(2))?)?,
+                    )),
+                    _ => Err(ArgNumber),
+                },
                 b"QUIT" | b"quit" | b"Quit" => Ok(Quit),
+                b"RESET" | b"reset" | b"Reset" => match v.len() {
+                    1 => Ok(Reset),
+                    _ => Err(ArgNumber),
+                },
                 unsupported_command => Err(NotSupported(
                     std::str::from_utf8(unsupported_command)
                         .unwrap()
@@ -178,4 +1777,341 @@ impl Command {
             _ => Err(InvalidCommand),
         }
     }
+
+    /// the command as run by EXEC, where blocking commands give up at once
+    pub fn without_blocking(self) -> Self {
+        match self {
+            Command::BZPop(keys, _, max) => Command::BZPop(keys, Some(Duration::ZERO), max),
+            Command::XRead(streams, count, Some(_)) => Command::XRead(streams, count, None),
+            Command::XReadGroup(group, consumer, streams, count, Some(_), no_ack) => {
+                Command::XReadGroup(group, consumer, streams, count, None, no_ack)
+            }
+            command => command,
+        }
+    }
+
+    /// transactions, subscriptions and scripts cannot be run by a script
+    #[cfg(feature = "scripting")]
+    pub fn is_allowed_in_script(&self) -> bool {
+        !matches!(
+            self,
+            Command::Multi
+                | Command::Exec
+                | Command::Discard
+                | Command::Watch(_)
+                | Command::Unwatch
+                | Command::Eval(..)
+                | Command::EvalSha(..)
+                | Command::ScriptLoad(_)
+                | Command::ScriptExists(_)
+                | Command::ScriptFlush
+                | Command::ScriptKill
+                | Command::FunctionLoad(..)
+                | Command::FunctionList(..)
+                | Command::FunctionDelete(_)
+                | Command::FunctionDump
+                | Command::FunctionFlush
+                | Command::FunctionKill
+                | Command::FCall(..)
+                | Command::Auth(..)
+                | Command::Hello(..)
+                | Command::AclSetUser(..)
+                | Command::AclDelUser(_)
+                | Command::ClientSetName(_)
+                | Command::ClientList(..)
+                | Command::ClientInfo
+                | Command::ClientKill(..)
+                | Command::ClientPause(..)
+                | Command::ClientUnpause
+                | Command::ClientTracking(_)
+                | Command::Monitor
+                | Command::DebugSleep(_)
+                | Command::DebugObject(_)
+                | Command::DebugSetActiveExpire(_)
+                | Command::DebugJmap
+                | Command::Shutdown(_)
+                | Command::Save
+                | Command::BgSave
+                | Command::BgRewriteAof
+                | Command::ReplicaOf(_)
+                | Command::Failover(_)
+                | Command::FailoverAbort
+                | Command::SentinelMasters
+                | Command::SentinelMaster(_)
+                | Command::SentinelGetMasterAddrByName(_)
+                | Command::Sync
+                | Command::PSync(..)
+                | Command::ReplConf(_)
+                | Command::LatencyHistory(_)
+                | Command::LatencyLatest
+                | Command::LatencyReset(_)
+                | Command::LatencyDoctor
+                | Command::Wait(..)
+                | Command::Subscribe(_)
+                | Command::Unsubscribe(_)
+                | Command::PSubscribe(_)
+                | Command::PUnsubscribe(_)
+                | Command::SSubscribe(_)
+                | Command::SUnsubscribe(_)
+                | Command::Quit
+                | Command::Reset
+        )
+    }
+
+    /// whether the command may change the dataset, refused from read-only
+    /// scripts
+    pub fn is_write(&self) -> bool {
+        use Command::*;
+
+        match self {
+            BitField(_, operations) => operations.iter().any(|op| !op.is_read_only()),
+            Set(..)
+            | Setnx(..)
+            | Setex(..)
+            | PSetex(..)
+            | MSet(_)
+            | MSetnx(_)
+            | Expire(..)
+            | PExpire(..)
+            | GetSet(..)
+            | HSet(..)
+            | HExpire(..)
+            | HPExpire(..)
+            | HPersist(..)
+            | SAdd(..)
+            | SRem(..)
+            | SMove(..)
+            | ZAdd(..)
+            | ZRem(..)
+            | ZRangeStore(..)
+            | ZRemRange(..)
+            | ZIncrBy(..)
+            | ZPop(..)
+            | ZUnionStore(..)
+            | ZInterStore(..)
+            | ZDiffStore(..)
+            | BZPop(..)
+            | SInterStore(..)
+            | SUnionStore(..)
+            | SDiffStore(..)
+            | SPop(..)
+            | Del(_)
+            | Move(..)
+            | Incr(_)
+            | SetBit(..)
+            | BitOp(..)
+            | GeoSearchStore(..)
+            | XAdd(..)
+            | XTrim(..)
+            | XDel(..)
+            | XGroupCreate(..)
+            | XGroupSetId(..)
+            | XGroupDestroy(..)
+            | XGroupCreateConsumer(..)
+            | XGroupDelConsumer(..)
+            | XReadGroup(..)
+            | XAck(..)
+            | XClaim(..)
+            | XAutoClaim(..)
+            | PfAdd(..)
+            | PfMerge(..)
+            | SwapDb(..) => true,
+            _ => false,
+        }
+    }
+
+    /// whether CLIENT PAUSE WRITE holds the command back, the scripts and
+    /// publishing being held back too
+    pub fn may_replicate(&self) -> bool {
+        use Command::*;
+
+        self.is_write()
+            || matches!(
+                self,
+                Eval(..)
+                    | EvalSha(..)
+                    | FCall(_, _, _, false)
+                    | FunctionLoad(..)
+                    | FunctionDelete(_)
+                    | FunctionFlush
+                    | Publish(..)
+                    | SPublish(..)
+            )
+    }
+
+    /// the keys the command reads or writes, checked against the key
+    /// patterns of the user
+    pub fn keys(&self) -> Vec<&[u8]> {
+        use Command::*;
+
+        match self {
+            Set(k, _)
+            | Setnx(k, _)
+            | Setex(k, _, _)
+            | PSetex(k, _, _)
+            | Expire(k, _)
+            | PExpire(k, _)
+            | Get(k)
+            | GetSet(k, _)
+            | HSet(k, _)
+            | HStrlen(k, _)
+            | HExpire(k, _, _, _)
+            | HPExpire(k, _, _, _)
+            | HTtl(k, _)
+            | HPTtl(k, _)
+            | HPersist(k, _)
+            | SAdd(k, _)
+            | SRem(k, _)
+            | SMembers(k)
+            | SIsMember(k, _)
+            | SMIsMember(k, _)
+            | SCard(k)
+            | SScan(k, _, _, _)
+            | ZAdd(k, _, _)
+            | ZScore(k, _)
+            | ZRem(k, _)
+            | ZRange(k, _, _)
+            | ZRemRange(k, _)
+            | ZIncrBy(k, _, _)
+            | ZCard(k)
+            | ZRandMember(k, _, _)
+            | ZScan(k, _, _, _)
+            | ZMScore(k, _)
+            | ZCount(k, _)
+            | ZRank(k, _, _, _)
+            | ZPop(k, _, _)
+            | SPop(k, _)
+            | SRandMember(k, _)
+            | Del(k)
+            | Move(k, _)
+            | Incr(k)
+            | SetBit(k, _, _)
+            | GetBit(k, _)
+            | BitCount(k, _, _)
+            | BitPos(k, _, _, _, _)
+            | BitField(k, _)
+            | GeoPos(k, _)
+            | GeoDist(k, _, _, _)
+            | GeoSearch(k, _, _)
+            | XAdd(k, _, _, _, _)
+            | XTrim(k, _)
+            | XDel(k, _)
+            | XLen(k)
+            | XRange(k, _, _, _, _)
+            | XGroupCreate(k, _, _, _)
+            | XGroupSetId(k, _, _)
+            | XGroupDestroy(k, _)
+            | XGroupCreateConsumer(k, _, _)
+            | XGroupDelConsumer(k, _, _)
+            | XAck(k, _, _)
+            | XPending(k, _, _)
+            | XClaim(k, _, _, _, _, _)
+            | XAutoClaim(k, _, _, _, _, _, _)
+            | XInfoStream(k, _)
+            | XInfoGroups(k)
+            | XInfoConsumers(k, _)
+            | Sort(k, _)
+            | PfAdd(k, _)
+            | MemoryUsage(k)
+            | Exists(k) => {
+                vec![k]
+            }
+            MSet(items) | MSetnx(items) => items.iter().map(|(k, _)| k.as_slice()).collect(),
+            MGet(keys)
+            | SInterCard(keys, _)
+            | ZUnion(keys, _, _, _)
+            | ZInter(keys, _, _, _)
+            | ZDiff(keys, _)
+            | BZPop(keys, _, _)
+            | SInter(keys)
+            | SUnion(keys)
+            | SDiff(keys)
+            | PfCount(keys)
+            | Watch(keys)
+            | Eval(_, keys, _)
+            | EvalSha(_, keys, _)
+            | FCall(_, keys, _, _) => keys.iter().map(|k| k.as_slice()).collect(),
+            SMove(source, destination, _)
+            | ZRangeStore(destination, source, _)
+            | Lcs(source, destination, _)
+            | GeoSearchStore(destination, source, _, _) => vec![source, destination],
+            ZUnionStore(destination, keys, _, _)
+            | ZInterStore(destination, keys, _, _)
+            | ZDiffStore(destination, keys)
+            | SInterStore(destination, keys)
+            | SUnionStore(destination, keys)
+            | SDiffStore(destination, keys)
+            | BitOp(_, destination, keys)
+            | PfMerge(destination, keys) => std::iter::once(destination)
+                .chain(keys.iter())
+                .map(|k| k.as_slice())
+                .collect(),
+            XRead(streams, _, _) => streams.iter().map(|(k, _)| k.as_slice()).collect(),
+            XReadGroup(_, _, streams, _, _, _) => {
+                streams.iter().map(|(k, _)| k.as_slice()).collect()
+            }
+            _ => vec![],
+        }
+    }
+
+    /// the channels the command publishes or subscribes to, checked against
+    /// the channel patterns of the user
+    pub fn channels(&self) -> Vec<&[u8]> {
+        use Command::*;
+
+        match self {
+            Publish(channel, _) | SPublish(channel, _) => vec![channel],
+            Subscribe(channels) | PSubscribe(channels) | SSubscribe(channels) => {
+                channels.iter().map(|channel| channel.as_slice()).collect()
+            }
+            _ => vec![],
+        }
+    }
+
+    /// whether a connection subscribed to channels can run the command
+    pub fn is_allowed_when_subscribed(&self) -> bool {
+        matches!(
+            self,
+            Command::Subscribe(_)
+                | Command::Unsubscribe(_)
+                | Command::PSubscribe(_)
+                | Command::PUnsubscribe(_)
+                | Command::SSubscribe(_)
+                | Command::SUnsubscribe(_)
+                | Command::Ping(_)
+                | Command::Quit
+                | Command::Reset
+        )
+    }
+
+    /// the commands a sentinel answers, which a server is not
+    pub fn is_sentinel(&self) -> bool {
+        matches!(
+            self,
+            Command::SentinelMasters
+                | Command::SentinelMaster(_)
+                | Command::SentinelGetMasterAddrByName(_)
+        )
+    }
+
+    /// the connections to the sentinel listener run the commands of a
+    /// sentinel, along with the ones to connect and to be told about the
+    /// changes of master
+    pub fn is_allowed_in_sentinel(&self) -> bool {
+        self.is_sentinel()
+            || matches!(
+                self,
+                Command::Auth(..)
+                    | Command::Hello(..)
+                    | Command::ClientSetName(_)
+                    | Command::ClientInfo
+                    | Command::Subscribe(_)
+                    | Command::Unsubscribe(_)
+                    | Command::PSubscribe(_)
+                    | Command::PUnsubscribe(_)
+                    | Command::Ping(_)
+                    | Command::Quit
+                    | Command::Reset
+            )
+    }
 }
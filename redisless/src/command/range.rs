@@ -0,0 +1,207 @@
+use super::command_error::RedisCommandError;
+use super::util::{get_bytes_vec, parse_integer, parse_score};
+use super::{Weights, WithScores};
+use crate::protocol::Resp;
+use crate::storage::models::redis_sorted_set::{
+    AddOptions, Aggregate, LexBound, RangeBy, RangeQuery, ScoreBound,
+};
+
+// `1.5`, `(1.5`, `-inf` or `+inf`
+pub fn parse_score_bound(bytes: Vec<u8>) -> Result<ScoreBound, RedisCommandError> {
+    let (bound, score): (fn(f64) -> ScoreBound, &[u8]) = match bytes.split_first() {
+        Some((b'(', score)) => (ScoreBound::Exclusive, score),
+        _ => (ScoreBound::Inclusive, &bytes[..]),
+    };
+
+    match std::str::from_utf8(score).map(|score| score.parse::<f64>()) {
+        Ok(Ok(score)) if !score.is_nan() => Ok(bound(score)),
+        _ => Err(RedisCommandError::ScoreRange),
+    }
+}
+
+// `[member`, `(member`, `-` or `+`
+pub fn parse_lex_bound(bytes: Vec<u8>) -> Result<LexBound, RedisCommandError> {
+    match bytes.split_first() {
+        Some((b'[', member)) => Ok(LexBound::Inclusive(member.to_vec())),
+        Some((b'(', member)) => Ok(LexBound::Exclusive(member.to_vec())),
+        Some((b'-', [])) => Ok(LexBound::Min),
+        Some((b'+', [])) => Ok(LexBound::Max),
+        _ => Err(RedisCommandError::LexRange),
+    }
+}
+
+/// parse `start stop [BYSCORE | BYLEX] [REV] [LIMIT offset count] [WITHSCORES]`
+/// as given to ZRANGE
+pub fn get_zrange_query(resp: &[Resp]) -> Result<(RangeQuery, WithScores), RedisCommandError> {
+    let start = get_bytes_vec(resp.first())?;
+    let stop = get_bytes_vec(resp.get(1))?;
+
+    let mut by_score = false;
+    let mut by_lex = false;
+    let mut rev = false;
+    let mut limit = None;
+    let mut with_scores = false;
+
+    let mut options = resp.iter().skip(2);
+    while let Some(option) = options.next() {
+        match get_bytes_vec(Some(option))?.to_ascii_uppercase().as_slice() {
+            b"BYSCORE" => by_score = true,
+            b"BYLEX" => by_lex = true,
+            b"REV" => rev = true,
+            b"WITHSCORES" => with_scores = true,
+            b"LIMIT" => limit = Some(get_limit(options.next(), options.next())?),
+            _ => return Err(RedisCommandError::Syntax),
+        }
+    }
+
+    let by = match (by_score, by_lex) {
+        (true, true) => return Err(RedisCommandError::Syntax),
+        (false, false) if limit.is_some() => return Err(RedisCommandError::Syntax),
+        (false, false) => RangeBy::Rank(parse_integer(start)?, parse_integer(stop)?),
+        (by_score, _) => {
+            // in reverse mode the highest bound comes first
+            let (min, max) = match rev {
+                true => (stop, start),
+                false => (start, stop),
+            };
+
+            match by_score {
+                true => RangeBy::Score(parse_score_bound(min)?, parse_score_bound(max)?),
+                false if with_scores => return Err(RedisCommandError::Syntax),
+                false => RangeBy::Lex(parse_lex_bound(min)?, parse_lex_bound(max)?),
+            }
+        }
+    };
+
+    Ok((RangeQuery { by, rev, limit }, with_scores))
+}
+
+/// parse `min max [WITHSCORES] [LIMIT offset count]` as given to the legacy
+/// ZRANGEBYSCORE and ZRANGEBYLEX commands (`max min` for their REV versions)
+pub fn get_legacy_zrange_query(
+    resp: &[Resp],
+    by_lex: bool,
+    rev: bool,
+) -> Result<(RangeQuery, WithScores), RedisCommandError> {
+    let first = get_bytes_vec(resp.first())?;
+    let second = get_bytes_vec(resp.get(1))?;
+
+    let mut limit = None;
+    let mut with_scores = false;
+
+    let mut options = resp.iter().skip(2);
+    while let Some(option) = options.next() {
+        match get_bytes_vec(Some(option))?.to_ascii_uppercase().as_slice() {
+            b"WITHSCORES" if !by_lex => with_scores = true,
+            b"LIMIT" => limit = Some(get_limit(options.next(), options.next())?),
+            _ => return Err(RedisCommandError::Syntax),
+        }
+    }
+
+    let (min, max) = match rev {
+        true => (second, first),
+        false => (first, second),
+    };
+
+    let by = match by_lex {
+        true => RangeBy::Lex(parse_lex_bound(min)?, parse_lex_bound(max)?),
+        false => RangeBy::Score(parse_score_bound(min)?, parse_score_bound(max)?),
+    };
+
+    Ok((RangeQuery { by, rev, limit }, with_scores))
+}
+
+/// parse `[WEIGHTS weight [weight ...]] [AGGREGATE SUM | MIN | MAX] [WITHSCORES]`
+/// following the keys of ZUNION and ZINTER, WITHSCORES being rejected by the
+/// store variants
+pub fn get_zcombine_options(
+    resp: &[Resp],
+    numkeys: usize,
+    allow_with_scores: bool,
+) -> Result<(Weights, Aggregate, WithScores), RedisCommandError> {
+    let mut weights = vec![1.0; numkeys];
+    let mut aggregate = Aggregate::Sum;
+    let mut with_scores = false;
+
+    let mut options = resp.iter();
+    while let Some(option) = options.next() {
+        match get_bytes_vec(Some(option))?.to_ascii_uppercase().as_slice() {
+            b"WEIGHTS" => {
+                for weight in weights.iter_mut() {
+                    *weight = match options.next() {
+                        Some(w) => get_bytes_vec(Some(w))
+                            .and_then(parse_score)
+                            .map_err(|_| RedisCommandError::WeightParse)?,
+                        None => return Err(RedisCommandError::Syntax),
+                    };
+                }
+            }
+            b"AGGREGATE" => {
+                let function =
+                    get_bytes_vec(options.next()).map_err(|_| RedisCommandError::Syntax)?;
+                aggregate = match function.to_ascii_uppercase().as_slice() {
+                    b"SUM" => Aggregate::Sum,
+                    b"MIN" => Aggregate::Min,
+                    b"MAX" => Aggregate::Max,
+                    _ => return Err(RedisCommandError::Syntax),
+                };
+            }
+            b"WITHSCORES" if allow_with_scores => with_scores = true,
+            _ => return Err(RedisCommandError::Syntax),
+        }
+    }
+
+    Ok((weights, aggregate, with_scores))
+}
+
+/// parse the `[NX | XX] [GT | LT] [CH] [INCR]` flags heading the scores and
+/// members of ZADD, returns them along with the remaining arguments
+pub fn get_zadd_options<'a>(
+    resp: &'a [Resp<'a>],
+) -> Result<(AddOptions, &'a [Resp<'a>]), RedisCommandError> {
+    let mut options = AddOptions::default();
+
+    let mut rest = resp;
+    while let Some((option, others)) = rest.split_first() {
+        match get_bytes_vec(Some(option))?.to_ascii_uppercase().as_slice() {
+            b"NX" => options.nx = true,
+            b"XX" => options.xx = true,
+            b"GT" => options.gt = true,
+            b"LT" => options.lt = true,
+            b"CH" => options.ch = true,
+            b"INCR" => options.incr = true,
+            _ => break,
+        }
+        rest = others;
+    }
+
+    if options.nx && options.xx {
+        return Err(RedisCommandError::IncompatibleOptions(
+            "XX and NX options at the same time are not compatible",
+        ));
+    }
+
+    if (options.gt && options.lt) || (options.nx && (options.gt || options.lt)) {
+        return Err(RedisCommandError::IncompatibleOptions(
+            "GT, LT, and/or NX options at the same time are not compatible",
+        ));
+    }
+
+    Ok((options, rest))
+}
+
+/// parse an optional single flag, e.g. the `WITHSCORE` of ZRANK
+pub fn get_flag(resp: &[Resp], flag: &[u8]) -> Result<bool, RedisCommandError> {
+    match resp {
+        [] => Ok(false),
+        [option] if get_bytes_vec(Some(option))?.eq_ignore_ascii_case(flag) => Ok(true),
+        _ => Err(RedisCommandError::Syntax),
+    }
+}
+
+fn get_limit(offset: Option<&Resp>, count: Option<&Resp>) -> Result<(i64, i64), RedisCommandError> {
+    let offset = get_bytes_vec(offset).map_err(|_| RedisCommandError::Syntax)?;
+    let count = get_bytes_vec(count).map_err(|_| RedisCommandError::Syntax)?;
+
+    Ok((parse_integer(offset)?, parse_integer(count)?))
+}
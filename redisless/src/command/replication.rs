@@ -0,0 +1,63 @@
+use std::convert::TryFrom;
+
+use super::command_error::RedisCommandError;
+use super::util::{get_bytes_vec, parse_integer};
+use super::Value;
+use crate::protocol::Resp;
+
+/// how FAILOVER hands the master role over to a replica
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct FailoverOptions {
+    // the host and the port of the replica promoted, the one furthest in
+    // the changes when not given
+    pub target: Option<(Value, u16)>,
+    // promoted once the timeout is reached even if it didn't catch up
+    pub force: bool,
+    // milliseconds to wait for the replica to catch up, forever if none
+    pub timeout: Option<u64>,
+}
+
+// [TO host port [FORCE]] [ABORT] [TIMEOUT milliseconds], None when the
+// failover in progress is aborted
+pub fn get_failover_options(resp: &[Resp]) -> Result<Option<FailoverOptions>, RedisCommandError> {
+    let mut options = FailoverOptions::default();
+    let mut abort = false;
+    let mut args = resp.iter();
+    while let Some(arg) = args.next() {
+        match get_bytes_vec(Some(arg))?.to_ascii_uppercase().as_slice() {
+            b"TO" if options.target.is_none() => {
+                let host = get_bytes_vec(args.next())?;
+                let port = match parse_integer(get_bytes_vec(args.next())?).map(u16::try_from) {
+                    Ok(Ok(port)) => port,
+                    _ => return Err(RedisCommandError::MasterPort),
+                };
+                options.target = Some((host, port));
+            }
+            b"FORCE" if !options.force => options.force = true,
+            b"ABORT" if !abort => abort = true,
+            b"TIMEOUT" if options.timeout.is_none() => {
+                let timeout = get_bytes_vec(args.next()).and_then(parse_integer)?;
+                match u64::try_from(timeout) {
+                    Ok(timeout) if timeout > 0 => options.timeout = Some(timeout),
+                    _ => return Err(RedisCommandError::FailoverTimeout),
+                }
+            }
+            _ => return Err(RedisCommandError::Syntax),
+        }
+    }
+
+    if abort {
+        return match options == FailoverOptions::default() {
+            true => Ok(None),
+            false => Err(RedisCommandError::IncompatibleOptions(
+                "FAILOVER abort cannot be used with other options.",
+            )),
+        };
+    }
+    if options.force && (options.target.is_none() || options.timeout.is_none()) {
+        return Err(RedisCommandError::IncompatibleOptions(
+            "FAILOVER with force option requires both a timeout and target HOST and IP.",
+        ));
+    }
+    Ok(Some(options))
+}
@@ -0,0 +1,35 @@
+use super::command_error::RedisCommandError;
+use super::util::{get_bytes_vec, parse_integer};
+use crate::protocol::Resp;
+use crate::storage::models::sort::SortQuery;
+
+// [BY pattern] [LIMIT offset count] [GET pattern [GET pattern ...]]
+// [ASC | DESC] [ALPHA] [STORE destination], STORE being only allowed when
+// read_only is false
+pub fn get_sort_query(resp: &[Resp], read_only: bool) -> Result<SortQuery, RedisCommandError> {
+    let mut query = SortQuery::default();
+
+    let mut options = resp.iter();
+    while let Some(option) = options.next() {
+        let mut value = || get_bytes_vec(options.next()).map_err(|_| RedisCommandError::Syntax);
+        match get_bytes_vec(Some(option))?.to_ascii_uppercase().as_slice() {
+            b"BY" => query.by = Some(value()?),
+            b"LIMIT" => {
+                let offset = parse_integer(value()?)?;
+                let count = parse_integer(value()?)?;
+                query.limit = Some((offset, count));
+            }
+            b"GET" => query.get.push(value()?),
+            b"ASC" => query.desc = false,
+            b"DESC" => query.desc = true,
+            b"ALPHA" => query.alpha = true,
+            // there are no lists to store the result in
+            b"STORE" if !read_only => {
+                return Err(RedisCommandError::NotSupported("SORT STORE".to_string()))
+            }
+            _ => return Err(RedisCommandError::Syntax),
+        }
+    }
+
+    Ok(query)
+}
@@ -0,0 +1,322 @@
+use std::time::Duration;
+
+use super::command_error::RedisCommandError;
+use super::util::{get_bytes_vec, get_bytes_vecs, parse_duration, parse_integer};
+use super::{Block, Full, JustId, NoAck, NoMkStream};
+use crate::protocol::Resp;
+use crate::storage::models::redis_stream::{
+    ClaimOptions, PendingQuery, StreamId, StreamTrim, TrimStrategy, XAddId, XReadGroupId, XReadId,
+};
+
+const DEFAULT_AUTOCLAIM_COUNT: usize = 100;
+const DEFAULT_XINFO_COUNT: usize = 10;
+
+// ms-seq, or ms alone in which case seq defaults to default_seq
+pub fn parse_stream_id(bytes: &[u8], default_seq: u64) -> Result<StreamId, RedisCommandError> {
+    let parse =
+        |bytes: &[u8]| parse_duration(bytes.to_vec()).map_err(|_| RedisCommandError::StreamId);
+
+    match bytes.iter().position(|byte| *byte == b'-') {
+        Some(dash) => Ok(StreamId::new(
+            parse(&bytes[..dash])?,
+            parse(&bytes[dash + 1..])?,
+        )),
+        None => Ok(StreamId::new(parse(bytes)?, default_seq)),
+    }
+}
+
+// `*`, `ms-*` or an explicit id greater than 0-0
+pub fn parse_xadd_id(bytes: Vec<u8>) -> Result<XAddId, RedisCommandError> {
+    match bytes.as_slice() {
+        b"*" => Ok(XAddId::Auto),
+        [ms @ .., b'-', b'*'] => parse_stream_id(ms, 0).map(|id| XAddId::AutoSeq(id.ms)),
+        _ => match parse_stream_id(&bytes, 0)? {
+            StreamId::MIN => Err(RedisCommandError::StreamIdZero),
+            id => Ok(XAddId::Explicit(id)),
+        },
+    }
+}
+
+// MAXLEN | MINID [= | ~] threshold [LIMIT count] followed by the remaining
+// arguments. Trimming is always exact, `~` only allowing a LIMIT.
+pub fn get_stream_trim<'a>(
+    resp: &'a [Resp<'a>],
+) -> Result<(StreamTrim, &'a [Resp<'a>]), RedisCommandError> {
+    let strategy = get_bytes_vec(resp.first())?.to_ascii_uppercase();
+    let (approximate, resp) = match get_bytes_vec(resp.get(1))?.as_slice() {
+        b"~" => (true, &resp[2..]),
+        b"=" => (false, &resp[2..]),
+        _ => (false, &resp[1..]),
+    };
+
+    let threshold = get_bytes_vec(resp.first())?;
+    let strategy = match strategy.as_slice() {
+        b"MAXLEN" => TrimStrategy::MaxLen(parse_duration(threshold)? as usize),
+        b"MINID" => TrimStrategy::MinId(parse_stream_id(&threshold, 0)?),
+        _ => return Err(RedisCommandError::Syntax),
+    };
+
+    match resp.get(1).map(|option| get_bytes_vec(Some(option))) {
+        Some(Ok(option)) if option.eq_ignore_ascii_case(b"LIMIT") => {
+            if !approximate {
+                return Err(RedisCommandError::IncompatibleOptions(
+                    "syntax error, LIMIT cannot be used without the special ~ option",
+                ));
+            }
+
+            let limit = get_bytes_vec(resp.get(2)).and_then(parse_duration)? as usize;
+            let trim = StreamTrim {
+                strategy,
+                // 0 lifts the limit
+                limit: Some(limit).filter(|limit| *limit > 0),
+            };
+            Ok((trim, &resp[3..]))
+        }
+        _ => Ok((
+            StreamTrim {
+                strategy,
+                limit: None,
+            },
+            &resp[1..],
+        )),
+    }
+}
+
+// [NOMKSTREAM] [MAXLEN | MINID [= | ~] threshold [LIMIT count]] followed by
+// the id and the fields
+pub fn get_xadd_options<'a>(
+    resp: &'a [Resp<'a>],
+) -> Result<(NoMkStream, Option<StreamTrim>, &'a [Resp<'a>]), RedisCommandError> {
+    let mut no_mkstream = false;
+    let mut trim = None;
+
+    let mut rest = resp;
+    while let Some(option) = rest.first() {
+        match get_bytes_vec(Some(option))?.to_ascii_uppercase().as_slice() {
+            b"NOMKSTREAM" => {
+                no_mkstream = true;
+                rest = &rest[1..];
+            }
+            b"MAXLEN" | b"MINID" => {
+                let (stream_trim, others) = get_stream_trim(rest)?;
+                trim = Some(stream_trim);
+                rest = others;
+            }
+            _ => break,
+        }
+    }
+
+    Ok((no_mkstream, trim, rest))
+}
+
+// `-`, `+`, an id or an exclusive `(id`, turned into an inclusive bound
+pub fn parse_stream_bound(bytes: Vec<u8>, start: bool) -> Result<StreamId, RedisCommandError> {
+    let (default_seq, error) = match start {
+        true => (0, "invalid start ID for the interval"),
+        false => (u64::MAX, "invalid end ID for the interval"),
+    };
+
+    match bytes.as_slice() {
+        b"-" => Ok(StreamId::MIN),
+        b"+" => Ok(StreamId::MAX),
+        [b'(', id @ ..] => {
+            let id = parse_stream_id(id, default_seq)?;
+            match start {
+                true => id.next(),
+                false => id.previous(),
+            }
+            .ok_or(RedisCommandError::StreamRange(error))
+        }
+        id => parse_stream_id(id, default_seq),
+    }
+}
+
+// [COUNT count]
+pub fn get_stream_count(resp: &[Resp]) -> Result<Option<usize>, RedisCommandError> {
+    match resp {
+        [] => Ok(None),
+        [option, count] if get_bytes_vec(Some(option))?.eq_ignore_ascii_case(b"COUNT") => {
+            let count = get_bytes_vec(Some(count)).and_then(parse_duration)?;
+            Ok(Some(count as usize))
+        }
+        _ => Err(RedisCommandError::Syntax),
+    }
+}
+
+// BLOCK milliseconds, 0 meaning forever
+pub fn parse_block(bytes: Vec<u8>) -> Result<Option<Duration>, RedisCommandError> {
+    match parse_integer(bytes).map_err(|_| RedisCommandError::Timeout)? {
+        ms if ms < 0 => Err(RedisCommandError::NegativeTimeout),
+        0 => Ok(None),
+        ms => Ok(Some(Duration::from_millis(ms as u64))),
+    }
+}
+
+// STREAMS key [key ...] id [id ...], each id being parsed by parse_id
+pub fn get_streams<T>(
+    resp: &[Resp],
+    parse_id: fn(&[u8]) -> Result<T, RedisCommandError>,
+) -> Result<Vec<(Vec<u8>, T)>, RedisCommandError> {
+    match get_bytes_vec(resp.first()) {
+        Ok(keyword) if keyword.eq_ignore_ascii_case(b"STREAMS") => {}
+        _ => return Err(RedisCommandError::Syntax),
+    }
+
+    let streams = get_bytes_vecs(&resp[1..])?;
+    if streams.len() % 2 != 0 {
+        return Err(RedisCommandError::StreamsUnbalanced);
+    }
+
+    let (keys, ids): (&[Vec<u8>], &[Vec<u8>]) = streams.split_at(streams.len() / 2);
+    keys.iter()
+        .zip(ids)
+        .map(|(key, id)| Ok((key.clone(), parse_id(id)?)))
+        .collect()
+}
+
+// `$` or ms-seq
+pub fn parse_xread_id(bytes: &[u8]) -> Result<XReadId, RedisCommandError> {
+    match bytes {
+        b"$" => Ok(XReadId::Last),
+        id => Ok(XReadId::After(parse_stream_id(id, 0)?)),
+    }
+}
+
+// `>` or ms-seq
+pub fn parse_xreadgroup_id(bytes: &[u8]) -> Result<XReadGroupId, RedisCommandError> {
+    match bytes {
+        b">" => Ok(XReadGroupId::New),
+        id => Ok(XReadGroupId::Pending(parse_stream_id(id, 0)?)),
+    }
+}
+
+// count, block and NOACK, followed by the streams
+type XReadOptions<'a> = (Option<usize>, Block, NoAck, &'a [Resp<'a>]);
+
+// [COUNT count] [BLOCK milliseconds] [NOACK] followed by the streams,
+// NOACK being only allowed when reading from a group
+pub fn get_xread_options<'a>(
+    resp: &'a [Resp<'a>],
+    group: bool,
+) -> Result<XReadOptions<'a>, RedisCommandError> {
+    let mut count = None;
+    let mut block = None;
+    let mut no_ack = false;
+
+    let mut rest = resp;
+    while let Some((option, others)) = rest.split_first() {
+        let value = || get_bytes_vec(others.first()).map_err(|_| RedisCommandError::Syntax);
+        match get_bytes_vec(Some(option))?.to_ascii_uppercase().as_slice() {
+            b"COUNT" => count = Some(value().and_then(parse_duration)? as usize),
+            b"BLOCK" => block = Some(value().and_then(parse_block)?),
+            b"NOACK" if group => {
+                no_ack = true;
+                rest = others;
+                continue;
+            }
+            _ => break,
+        }
+        rest = &others[1..];
+    }
+
+    Ok((count, block, no_ack, rest))
+}
+
+// [[IDLE min-idle-time] start end count [consumer]], the summary being
+// replied when none is given
+pub fn get_pending_query(resp: &[Resp]) -> Result<Option<PendingQuery>, RedisCommandError> {
+    if resp.is_empty() {
+        return Ok(None);
+    }
+
+    let (min_idle, resp) = match get_bytes_vec(resp.first())? {
+        keyword if keyword.eq_ignore_ascii_case(b"IDLE") => {
+            let min_idle = get_bytes_vec(resp.get(1)).and_then(parse_duration)?;
+            (Some(min_idle), &resp[2..])
+        }
+        _ => (None, resp),
+    };
+
+    let (start, end, count, consumer) = match resp {
+        [start, end, count] => (start, end, count, None),
+        [start, end, count, consumer] => (start, end, count, Some(consumer)),
+        _ => return Err(RedisCommandError::Syntax),
+    };
+
+    Ok(Some(PendingQuery {
+        min_idle,
+        start: parse_stream_bound(get_bytes_vec(Some(start))?, true)?,
+        end: parse_stream_bound(get_bytes_vec(Some(end))?, false)?,
+        // a negative count is an empty range
+        count: parse_integer(get_bytes_vec(Some(count))?)?.max(0) as usize,
+        consumer: consumer.map(|c| get_bytes_vec(Some(c))).transpose()?,
+    }))
+}
+
+// id [id ...] [IDLE ms] [TIME unix-time-milliseconds] [RETRYCOUNT count]
+// [FORCE] [JUSTID] [LASTID id]
+pub fn get_xclaim_ids(resp: &[Resp]) -> Result<(Vec<StreamId>, ClaimOptions), RedisCommandError> {
+    let mut ids = vec![];
+    let mut rest = resp;
+    while let Some((id, others)) = rest.split_first() {
+        match parse_stream_id(&get_bytes_vec(Some(id))?, 0) {
+            Ok(id) => ids.push(id),
+            Err(_) if !ids.is_empty() => break,
+            Err(error) => return Err(error),
+        }
+        rest = others;
+    }
+
+    if ids.is_empty() {
+        return Err(RedisCommandError::ArgNumber);
+    }
+
+    let mut options = ClaimOptions::default();
+    let mut rest = rest.iter();
+    while let Some(option) = rest.next() {
+        let mut value = || get_bytes_vec(rest.next()).map_err(|_| RedisCommandError::Syntax);
+        match get_bytes_vec(Some(option))?.to_ascii_uppercase().as_slice() {
+            b"IDLE" => options.idle = Some(value().and_then(parse_duration)?),
+            b"TIME" => options.time = Some(value().and_then(parse_duration)?),
+            b"RETRYCOUNT" => options.retry_count = Some(value().and_then(parse_duration)?),
+            b"LASTID" => options.last_id = Some(parse_stream_id(&value()?, 0)?),
+            b"FORCE" => options.force = true,
+            b"JUSTID" => options.just_id = true,
+            _ => return Err(RedisCommandError::Syntax),
+        }
+    }
+
+    Ok((ids, options))
+}
+
+// [COUNT count] [JUSTID]
+pub fn get_xautoclaim_options(resp: &[Resp]) -> Result<(usize, JustId), RedisCommandError> {
+    let mut count = DEFAULT_AUTOCLAIM_COUNT;
+    let mut just_id = false;
+
+    let mut options = resp.iter();
+    while let Some(option) = options.next() {
+        match get_bytes_vec(Some(option))?.to_ascii_uppercase().as_slice() {
+            b"COUNT" => match get_bytes_vec(options.next()).and_then(parse_duration) {
+                Ok(c) if c > 0 => count = c as usize,
+                _ => return Err(RedisCommandError::StreamCount),
+            },
+            b"JUSTID" => just_id = true,
+            _ => return Err(RedisCommandError::Syntax),
+        }
+    }
+
+    Ok((count, just_id))
+}
+
+// [FULL [COUNT count]]
+pub fn get_xinfo_full(resp: &[Resp]) -> Result<Full, RedisCommandError> {
+    match resp {
+        [] => Ok(None),
+        [full, rest @ ..] if get_bytes_vec(Some(full))?.eq_ignore_ascii_case(b"FULL") => {
+            let count = get_stream_count(rest)?;
+            Ok(Some(count.unwrap_or(DEFAULT_XINFO_COUNT)))
+        }
+        _ => Err(RedisCommandError::Syntax),
+    }
+}
@@ -0,0 +1,374 @@
+/// a command, or a subcommand named `<container>|<subcommand>`, along with
+/// what COMMAND tells about it and the ACL categories it belongs to
+pub struct CommandInfo {
+    pub name: &'static str,
+    // the number of arguments including the name, or minus the minimum if
+    // it takes a variable number of them
+    pub arity: i64,
+    pub flags: &'static [&'static str],
+    // positions of the first and last keys, negative counting from the end,
+    // and the step between keys, all 0 if the keys are not at fixed positions
+    pub first_key: i64,
+    pub last_key: i64,
+    pub step: i64,
+    pub categories: &'static [&'static str],
+}
+
+macro_rules! commands {
+    ($($name:literal => $arity:expr, [$($flag:literal),*], ($first:expr, $last:expr, $step:expr), [$($category:literal),*],)*) => {
+        &[$(CommandInfo {
+            name: $name,
+            arity: $arity,
+            flags: &[$($flag),*],
+            first_key: $first,
+            last_key: $last,
+            step: $step,
+            categories: &[$($category),*],
+        },)*]
+    };
+}
+
+/// the ACL categories, as ACL CAT lists them
+pub const CATEGORIES: &[&str] = &[
+    "keyspace",
+    "read",
+    "write",
+    "set",
+    "sortedset",
+    "list",
+    "hash",
+    "string",
+    "bitmap",
+    "hyperloglog",
+    "geo",
+    "stream",
+    "pubsub",
+    "admin",
+    "fast",
+    "slow",
+    "blocking",
+    "dangerous",
+    "connection",
+    "transaction",
+    "scripting",
+];
+
+/// every command supported, the subcommands of a container following it
+pub const COMMANDS: &[CommandInfo] = commands![
+    "get" => 2, ["readonly", "fast"], (1, 1, 1), ["read", "string", "fast"],
+    "set" => -3, ["write", "denyoom"], (1, 1, 1), ["write", "string", "slow"],
+    "setex" => 4, ["write", "denyoom"], (1, 1, 1), ["write", "string", "slow"],
+    "psetex" => 4, ["write", "denyoom"], (1, 1, 1), ["write", "string", "slow"],
+    "setnx" => 3, ["write", "denyoom", "fast"], (1, 1, 1), ["write", "string", "fast"],
+    "mset" => -3, ["write", "denyoom"], (1, -1, 2), ["write", "string", "slow"],
+    "msetnx" => -3, ["write", "denyoom"], (1, -1, 2), ["write", "string", "slow"],
+    "getset" => 3, ["write", "denyoom", "fast"], (1, 1, 1), ["write", "string", "fast"],
+    "mget" => -2, ["readonly", "fast"], (1, -1, 1), ["read", "string", "fast"],
+    "incr" => 2, ["write", "denyoom", "fast"], (1, 1, 1), ["write", "string", "fast"],
+    "lcs" => -3, ["readonly"], (1, 2, 1), ["read", "string", "slow"],
+    "expire" => -3, ["write", "fast"], (1, 1, 1), ["keyspace", "write", "fast"],
+    "pexpire" => -3, ["write", "fast"], (1, 1, 1), ["keyspace", "write", "fast"],
+    "del" => -2, ["write"], (1, -1, 1), ["keyspace", "write", "slow"],
+    "move" => 3, ["write", "fast"], (1, 1, 1), ["keyspace", "write", "fast"],
+    "exists" => -2, ["readonly", "fast"], (1, -1, 1), ["keyspace", "read", "fast"],
+    "swapdb" => 3, ["write", "fast"], (0, 0, 0), ["keyspace", "write", "fast", "dangerous"],
+    "sort" => -2, ["write", "denyoom", "movablekeys"], (1, 1, 1), ["write", "set", "sortedset", "list", "slow", "dangerous"],
+    "sort_ro" => -2, ["readonly", "movablekeys"], (1, 1, 1), ["read", "set", "sortedset", "list", "slow", "dangerous"],
+    "hset" => -4, ["write", "denyoom", "fast"], (1, 1, 1), ["write", "hash", "fast"],
+    "hstrlen" => 3, ["readonly", "fast"], (1, 1, 1), ["read", "hash", "fast"],
+    "hexpire" => -6, ["write", "fast"], (1, 1, 1), ["write", "hash", "fast"],
+    "hpexpire" => -6, ["write", "fast"], (1, 1, 1), ["write", "hash", "fast"],
+    "httl" => -5, ["readonly", "fast"], (1, 1, 1), ["read", "hash", "fast"],
+    "hpttl" => -5, ["readonly", "fast"], (1, 1, 1), ["read", "hash", "fast"],
+    "hpersist" => -5, ["write", "fast"], (1, 1, 1), ["write", "hash", "fast"],
+    "sadd" => -3, ["write", "denyoom", "fast"], (1, 1, 1), ["write", "set", "fast"],
+    "srem" => -3, ["write", "fast"], (1, 1, 1), ["write", "set", "fast"],
+    "smembers" => 2, ["readonly"], (1, 1, 1), ["read", "set", "slow"],
+    "sismember" => 3, ["readonly", "fast"], (1, 1, 1), ["read", "set", "fast"],
+    "smismember" => -3, ["readonly", "fast"], (1, 1, 1), ["read", "set", "fast"],
+    "scard" => 2, ["readonly", "fast"], (1, 1, 1), ["read", "set", "fast"],
+    "sscan" => -3, ["readonly"], (1, 1, 1), ["read", "set", "slow"],
+    "smove" => 4, ["write", "fast"], (1, 2, 1), ["write", "set", "fast"],
+    "sinter" => -2, ["readonly"], (1, -1, 1), ["read", "set", "slow"],
+    "sunion" => -2, ["readonly"], (1, -1, 1), ["read", "set", "slow"],
+    "sdiff" => -2, ["readonly"], (1, -1, 1), ["read", "set", "slow"],
+    "sintercard" => -3, ["readonly", "movablekeys"], (0, 0, 0), ["read", "set", "slow"],
+    "sinterstore" => -3, ["write", "denyoom"], (1, -1, 1), ["write", "set", "slow"],
+    "sunionstore" => -3, ["write", "denyoom"], (1, -1, 1), ["write", "set", "slow"],
+    "sdiffstore" => -3, ["write", "denyoom"], (1, -1, 1), ["write", "set", "slow"],
+    "spop" => -2, ["write", "fast"], (1, 1, 1), ["write", "set", "fast"],
+    "srandmember" => -2, ["readonly"], (1, 1, 1), ["read", "set", "slow"],
+    "zadd" => -4, ["write", "denyoom", "fast"], (1, 1, 1), ["write", "sortedset", "fast"],
+    "zscore" => 3, ["readonly", "fast"], (1, 1, 1), ["read", "sortedset", "fast"],
+    "zmscore" => -3, ["readonly", "fast"], (1, 1, 1), ["read", "sortedset", "fast"],
+    "zrem" => -3, ["write", "fast"], (1, 1, 1), ["write", "sortedset", "fast"],
+    "zincrby" => 4, ["write", "denyoom", "fast"], (1, 1, 1), ["write", "sortedset", "fast"],
+    "zcard" => 2, ["readonly", "fast"], (1, 1, 1), ["read", "sortedset", "fast"],
+    "zrandmember" => -2, ["readonly"], (1, 1, 1), ["read", "sortedset", "slow"],
+    "zscan" => -3, ["readonly"], (1, 1, 1), ["read", "sortedset", "slow"],
+    "zcount" => 4, ["readonly", "fast"], (1, 1, 1), ["read", "sortedset", "fast"],
+    "zlexcount" => 4, ["readonly", "fast"], (1, 1, 1), ["read", "sortedset", "fast"],
+    "zrank" => -3, ["readonly", "fast"], (1, 1, 1), ["read", "sortedset", "fast"],
+    "zrevrank" => -3, ["readonly", "fast"], (1, 1, 1), ["read", "sortedset", "fast"],
+    "zpopmin" => -2, ["write", "fast"], (1, 1, 1), ["write", "sortedset", "fast"],
+    "zpopmax" => -2, ["write", "fast"], (1, 1, 1), ["write", "sortedset", "fast"],
+    "bzpopmin" => -3, ["write", "blocking", "fast"], (1, -2, 1), ["write", "sortedset", "fast", "blocking"],
+    "bzpopmax" => -3, ["write", "blocking", "fast"], (1, -2, 1), ["write", "sortedset", "fast", "blocking"],
+    "zunion" => -3, ["readonly", "movablekeys"], (0, 0, 0), ["read", "sortedset", "slow"],
+    "zinter" => -3, ["readonly", "movablekeys"], (0, 0, 0), ["read", "sortedset", "slow"],
+    "zdiff" => -3, ["readonly", "movablekeys"], (0, 0, 0), ["read", "sortedset", "slow"],
+    "zunionstore" => -4, ["write", "denyoom", "movablekeys"], (1, 1, 1), ["write", "sortedset", "slow"],
+    "zinterstore" => -4, ["write", "denyoom", "movablekeys"], (1, 1, 1), ["write", "sortedset", "slow"],
+    "zdiffstore" => -4, ["write", "denyoom", "movablekeys"], (1, 1, 1), ["write", "sortedset", "slow"],
+    "zrange" => -4, ["readonly"], (1, 1, 1), ["read", "sortedset", "slow"],
+    "zrangestore" => -5, ["write", "denyoom"], (1, 2, 1), ["write", "sortedset", "slow"],
+    "zrevrange" => -4, ["readonly"], (1, 1, 1), ["read", "sortedset", "slow"],
+    "zrangebyscore" => -4, ["readonly"], (1, 1, 1), ["read", "sortedset", "slow"],
+    "zrevrangebyscore" => -4, ["readonly"], (1, 1, 1), ["read", "sortedset", "slow"],
+    "zrangebylex" => -4, ["readonly"], (1, 1, 1), ["read", "sortedset", "slow"],
+    "zrevrangebylex" => -4, ["readonly"], (1, 1, 1), ["read", "sortedset", "slow"],
+    "zremrangebyrank" => 4, ["write"], (1, 1, 1), ["write", "sortedset", "slow"],
+    "zremrangebyscore" => 4, ["write"], (1, 1, 1), ["write", "sortedset", "slow"],
+    "zremrangebylex" => 4, ["write"], (1, 1, 1), ["write", "sortedset", "slow"],
+    "setbit" => 4, ["write", "denyoom"], (1, 1, 1), ["write", "bitmap", "slow"],
+    "getbit" => 3, ["readonly", "fast"], (1, 1, 1), ["read", "bitmap", "fast"],
+    "bitcount" => -2, ["readonly"], (1, 1, 1), ["read", "bitmap", "slow"],
+    "bitpos" => -3, ["readonly"], (1, 1, 1), ["read", "bitmap", "slow"],
+    "bitop" => -4, ["write", "denyoom"], (2, -1, 1), ["write", "bitmap", "slow"],
+    "bitfield" => -2, ["write", "denyoom"], (1, 1, 1), ["write", "bitmap", "slow"],
+    "bitfield_ro" => -2, ["readonly", "fast"], (1, 1, 1), ["read", "bitmap", "fast"],
+    "geoadd" => -5, ["write", "denyoom"], (1, 1, 1), ["write", "geo", "slow"],
+    "geopos" => -2, ["readonly"], (1, 1, 1), ["read", "geo", "slow"],
+    "geodist" => -4, ["readonly"], (1, 1, 1), ["read", "geo", "slow"],
+    "geosearch" => -7, ["readonly"], (1, 1, 1), ["read", "geo", "slow"],
+    "geosearchstore" => -8, ["write", "denyoom"], (1, 2, 1), ["write", "geo", "slow"],
+    "xadd" => -5, ["write", "denyoom", "fast"], (1, 1, 1), ["write", "stream", "fast"],
+    "xtrim" => -4, ["write"], (1, 1, 1), ["write", "stream", "slow"],
+    "xdel" => -3, ["write", "fast"], (1, 1, 1), ["write", "stream", "fast"],
+    "xlen" => 2, ["readonly", "fast"], (1, 1, 1), ["read", "stream", "fast"],
+    "xrange" => -4, ["readonly"], (1, 1, 1), ["read", "stream", "slow"],
+    "xrevrange" => -4, ["readonly"], (1, 1, 1), ["read", "stream", "slow"],
+    "xread" => -4, ["readonly", "blocking", "movablekeys"], (0, 0, 0), ["read", "stream", "slow", "blocking"],
+    "xreadgroup" => -7, ["write", "blocking", "movablekeys"], (0, 0, 0), ["write", "stream", "slow", "blocking"],
+    "xack" => -4, ["write", "fast"], (1, 1, 1), ["write", "stream", "fast"],
+    "xpending" => -3, ["readonly"], (1, 1, 1), ["read", "stream", "slow"],
+    "xclaim" => -6, ["write", "fast"], (1, 1, 1), ["write", "stream", "fast"],
+    "xautoclaim" => -6, ["write", "fast"], (1, 1, 1), ["write", "stream", "fast"],
+    "xgroup" => -2, [], (0, 0, 0), [],
+    "xgroup|create" => -5, ["write", "denyoom"], (2, 2, 1), ["write", "stream", "slow"],
+    "xgroup|setid" => -5, ["write"], (2, 2, 1), ["write", "stream", "slow"],
+    "xgroup|destroy" => 4, ["write"], (2, 2, 1), ["write", "stream", "slow"],
+    "xgroup|createconsumer" => 5, ["write", "denyoom"], (2, 2, 1), ["write", "stream", "slow"],
+    "xgroup|delconsumer" => 5, ["write"], (2, 2, 1), ["write", "stream", "slow"],
+    "xinfo" => -2, [], (0, 0, 0), [],
+    "xinfo|stream" => -3, ["readonly"], (2, 2, 1), ["read", "stream", "slow"],
+    "xinfo|groups" => 3, ["readonly"], (2, 2, 1), ["read", "stream", "slow"],
+    "xinfo|consumers" => 4, ["readonly"], (2, 2, 1), ["read", "stream", "slow"],
+    "pfadd" => -2, ["write", "denyoom", "fast"], (1, 1, 1), ["write", "hyperloglog", "fast"],
+    "pfcount" => -2, ["readonly", "may_replicate"], (1, -1, 1), ["read", "hyperloglog", "slow"],
+    "pfmerge" => -2, ["write", "denyoom"], (1, -1, 1), ["write", "hyperloglog", "slow"],
+    "subscribe" => -2, ["pubsub", "noscript", "loading", "stale"], (0, 0, 0), ["pubsub", "slow"],
+    "unsubscribe" => -1, ["pubsub", "noscript", "loading", "stale"], (0, 0, 0), ["pubsub", "slow"],
+    "psubscribe" => -2, ["pubsub", "noscript", "loading", "stale"], (0, 0, 0), ["pubsub", "slow"],
+    "punsubscribe" => -1, ["pubsub", "noscript", "loading", "stale"], (0, 0, 0), ["pubsub", "slow"],
+    "ssubscribe" => -2, ["pubsub", "noscript", "loading", "stale"], (1, -1, 1), ["pubsub", "slow"],
+    "sunsubscribe" => -1, ["pubsub", "noscript", "loading", "stale"], (1, -1, 1), ["pubsub", "slow"],
+    "publish" => 3, ["pubsub", "loading", "stale", "fast", "may_replicate"], (0, 0, 0), ["pubsub", "fast"],
+    "spublish" => 3, ["pubsub", "loading", "stale", "fast", "may_replicate"], (1, 1, 1), ["pubsub", "fast"],
+    "pubsub" => -2, [], (0, 0, 0), [],
+    "pubsub|channels" => -2, ["pubsub", "loading", "stale"], (0, 0, 0), ["pubsub", "slow"],
+    "pubsub|numsub" => -2, ["pubsub", "loading", "stale"], (0, 0, 0), ["pubsub", "slow"],
+    "pubsub|numpat" => 2, ["pubsub", "loading", "stale"], (0, 0, 0), ["pubsub", "slow"],
+    "pubsub|shardchannels" => -2, ["pubsub", "loading", "stale"], (0, 0, 0), ["pubsub", "slow"],
+    "pubsub|shardnumsub" => -2, ["pubsub", "loading", "stale"], (0, 0, 0), ["pubsub", "slow"],
+    "multi" => 1, ["noscript", "loading", "stale", "fast", "allow_busy"], (0, 0, 0), ["fast", "transaction"],
+    "exec" => 1, ["noscript", "loading", "stale", "skip_slowlog"], (0, 0, 0), ["slow", "transaction"],
+    "discard" => 1, ["noscript", "loading", "stale", "fast", "allow_busy"], (0, 0, 0), ["fast", "transaction"],
+    "watch" => -2, ["noscript", "loading", "stale", "fast", "allow_busy"], (1, -1, 1), ["fast", "transaction"],
+    "unwatch" => 1, ["noscript", "loading", "stale", "fast", "allow_busy"], (0, 0, 0), ["fast", "transaction"],
+    "eval" => -3, ["noscript", "stale", "skip_monitor", "may_replicate", "movablekeys"], (0, 0, 0), ["slow", "scripting"],
+    "evalsha" => -3, ["noscript", "stale", "skip_monitor", "may_replicate", "movablekeys"], (0, 0, 0), ["slow", "scripting"],
+    "fcall" => -3, ["noscript", "stale", "skip_monitor", "may_replicate", "movablekeys"], (0, 0, 0), ["slow", "scripting"],
+    "fcall_ro" => -3, ["readonly", "noscript", "stale", "skip_monitor", "movablekeys"], (0, 0, 0), ["slow", "scripting"],
+    "script" => -2, [], (0, 0, 0), [],
+    "script|load" => 3, ["noscript", "stale"], (0, 0, 0), ["slow", "scripting"],
+    "script|exists" => -3, ["noscript"], (0, 0, 0), ["slow", "scripting"],
+    "script|flush" => -2, ["noscript", "may_replicate"], (0, 0, 0), ["slow", "scripting"],
+    "script|kill" => 2, ["noscript", "allow_busy"], (0, 0, 0), ["slow", "scripting"],
+    "function" => -2, [], (0, 0, 0), [],
+    "function|load" => -3, ["write", "denyoom", "noscript"], (0, 0, 0), ["write", "slow", "scripting"],
+    "function|list" => -2, ["noscript"], (0, 0, 0), ["slow", "scripting"],
+    "function|delete" => 3, ["write", "noscript"], (0, 0, 0), ["write", "slow", "scripting"],
+    "function|dump" => 2, ["noscript"], (0, 0, 0), ["slow", "scripting"],
+    "function|flush" => -2, ["write", "noscript"], (0, 0, 0), ["write", "slow", "scripting"],
+    "function|kill" => 2, ["noscript", "allow_busy"], (0, 0, 0), ["slow", "scripting"],
+    "config" => -2, [], (0, 0, 0), [],
+    "config|get" => -3, ["admin", "noscript", "loading", "stale"], (0, 0, 0), ["admin", "slow", "dangerous"],
+    "config|set" => -4, ["admin", "noscript", "loading", "stale"], (0, 0, 0), ["admin", "slow", "dangerous"],
+    "acl" => -2, [], (0, 0, 0), [],
+    "acl|setuser" => -3, ["admin", "noscript", "loading", "stale"], (0, 0, 0), ["admin", "slow", "dangerous"],
+    "acl|getuser" => 3, ["admin", "noscript", "loading", "stale"], (0, 0, 0), ["admin", "slow", "dangerous"],
+    "acl|deluser" => -3, ["admin", "noscript", "loading", "stale"], (0, 0, 0), ["admin", "slow", "dangerous"],
+    "acl|list" => 2, ["admin", "noscript", "loading", "stale"], (0, 0, 0), ["admin", "slow", "dangerous"],
+    "acl|users" => 2, ["admin", "noscript", "loading", "stale"], (0, 0, 0), ["admin", "slow", "dangerous"],
+    "acl|whoami" => 2, ["noscript", "loading", "stale"], (0, 0, 0), ["slow"],
+    "acl|cat" => -2, ["noscript", "loading", "stale"], (0, 0, 0), ["slow"],
+    "auth" => -2, ["noscript", "loading", "stale", "fast", "no_auth", "allow_busy"], (0, 0, 0), ["fast", "connection"],
+    "hello" => -1, ["noscript", "loading", "stale", "fast", "no_auth", "allow_busy"], (0, 0, 0), ["fast", "connection"],
+    "select" => 2, ["loading", "stale", "fast"], (0, 0, 0), ["fast", "connection"],
+    "client" => -2, [], (0, 0, 0), [],
+    "client|id" => 2, ["noscript", "loading", "stale"], (0, 0, 0), ["slow", "connection"],
+    "client|setname" => 3, ["noscript", "loading", "stale"], (0, 0, 0), ["slow", "connection"],
+    "client|getname" => 2, ["noscript", "loading", "stale"], (0, 0, 0), ["slow", "connection"],
+    "client|list" => -2, ["admin", "noscript", "loading", "stale"], (0, 0, 0), ["admin", "slow", "dangerous", "connection"],
+    "client|info" => 2, ["noscript", "loading", "stale"], (0, 0, 0), ["slow", "connection"],
+    "client|kill" => -3, ["admin", "noscript", "loading", "stale"], (0, 0, 0), ["admin", "slow", "dangerous", "connection"],
+    "client|pause" => -3, ["admin", "noscript", "loading", "stale"], (0, 0, 0), ["admin", "slow", "dangerous", "connection"],
+    "client|unpause" => 2, ["admin", "noscript", "loading", "stale"], (0, 0, 0), ["admin", "slow", "dangerous", "connection"],
+    "client|tracking" => -3, ["noscript", "loading", "stale"], (0, 0, 0), ["slow", "connection"],
+    "ping" => -1, ["fast"], (0, 0, 0), ["fast", "connection"],
+    "echo" => 2, ["fast"], (0, 0, 0), ["fast", "connection"],
+    "time" => 1, ["loading", "stale", "fast"], (0, 0, 0), ["fast"],
+    "wait" => 3, ["noscript"], (0, 0, 0), ["slow", "connection"],
+    "lolwut" => -1, ["readonly", "fast"], (0, 0, 0), ["read", "fast"],
+    "reset" => 1, ["noscript", "loading", "stale", "fast", "no_auth", "allow_busy"], (0, 0, 0), ["fast", "connection"],
+    "quit" => -1, ["noscript", "loading", "stale", "fast", "no_auth", "allow_busy"], (0, 0, 0), ["fast", "connection"],
+    "info" => -1, ["loading", "stale"], (0, 0, 0), ["slow", "dangerous"],
+    "monitor" => 1, ["admin", "noscript", "loading", "stale"], (0, 0, 0), ["admin", "slow", "dangerous"],
+    "debug" => -2, ["admin", "noscript", "loading", "stale"], (0, 0, 0), ["admin", "slow", "dangerous"],
+    "shutdown" => -1, ["admin", "noscript", "loading", "stale"], (0, 0, 0), ["admin", "slow", "dangerous"],
+    "save" => 1, ["admin", "noscript"], (0, 0, 0), ["admin", "slow", "dangerous"],
+    "bgsave" => -1, ["admin", "noscript"], (0, 0, 0), ["admin", "slow", "dangerous"],
+    "lastsave" => 1, ["loading", "stale", "fast"], (0, 0, 0), ["admin", "fast", "dangerous"],
+    "bgrewriteaof" => 1, ["admin", "noscript", "no_async_loading"], (0, 0, 0), ["admin", "slow", "dangerous"],
+    "replicaof" => 3, ["admin", "noscript", "stale", "no_async_loading"], (0, 0, 0), ["admin", "slow", "dangerous"],
+    "failover" => -1, ["admin", "noscript", "stale"], (0, 0, 0), ["admin", "slow", "dangerous"],
+    "slaveof" => 3, ["admin", "noscript", "stale", "no_async_loading"], (0, 0, 0), ["admin", "slow", "dangerous"],
+    "sync" => 1, ["admin", "noscript", "no_async_loading", "no_multi"], (0, 0, 0), ["admin", "slow", "dangerous"],
+    "psync" => -3, ["admin", "noscript", "no_async_loading", "no_multi"], (0, 0, 0), ["admin", "slow", "dangerous"],
+    "readonly" => 1, ["loading", "stale", "fast"], (0, 0, 0), ["fast", "connection"],
+    "readwrite" => 1, ["loading", "stale", "fast"], (0, 0, 0), ["fast", "connection"],
+    "replconf" => -1, ["admin", "noscript", "loading", "stale", "allow_busy"], (0, 0, 0), ["admin", "slow", "dangerous"],
+    "slowlog" => -2, [], (0, 0, 0), [],
+    "slowlog|get" => -2, ["admin", "loading", "stale"], (0, 0, 0), ["admin", "slow", "dangerous"],
+    "slowlog|len" => 2, ["admin", "loading", "stale"], (0, 0, 0), ["admin", "slow", "dangerous"],
+    "slowlog|reset" => 2, ["admin", "loading", "stale"], (0, 0, 0), ["admin", "slow", "dangerous"],
+    "memory" => -2, [], (0, 0, 0), [],
+    "memory|usage" => -3, ["readonly"], (2, 2, 1), ["read", "slow"],
+    "memory|stats" => 2, [], (0, 0, 0), ["slow"],
+    "memory|doctor" => 2, [], (0, 0, 0), ["slow"],
+    "latency" => -2, [], (0, 0, 0), [],
+    "latency|history" => 3, ["admin", "noscript", "loading", "stale"], (0, 0, 0), ["admin", "slow", "dangerous"],
+    "latency|latest" => 2, ["admin", "noscript", "loading", "stale"], (0, 0, 0), ["admin", "slow", "dangerous"],
+    "latency|reset" => -2, ["admin", "noscript", "loading", "stale"], (0, 0, 0), ["admin", "slow", "dangerous"],
+    "latency|doctor" => 2, ["admin", "noscript", "loading", "stale"], (0, 0, 0), ["admin", "slow", "dangerous"],
+    "cluster" => -2, [], (0, 0, 0), [],
+    "cluster|info" => 2, ["stale"], (0, 0, 0), ["slow"],
+    "cluster|myid" => 2, ["stale"], (0, 0, 0), ["slow"],
+    "cluster|slots" => 2, ["loading", "stale"], (0, 0, 0), ["slow"],
+    "cluster|shards" => 2, ["loading", "stale"], (0, 0, 0), ["slow"],
+    "cluster|nodes" => 2, ["loading", "stale"], (0, 0, 0), ["slow"],
+    "cluster|keyslot" => 3, ["stale"], (0, 0, 0), ["slow"],
+    "cluster|meet" => -4, ["admin", "stale", "no_async_loading"], (0, 0, 0), ["admin", "slow", "dangerous"],
+    "cluster|addslots" => -3, ["admin", "stale", "no_async_loading"], (0, 0, 0), ["admin", "slow", "dangerous"],
+    "cluster|addslotsrange" => -4, ["admin", "stale", "no_async_loading"], (0, 0, 0), ["admin", "slow", "dangerous"],
+    "cluster|delslots" => -3, ["admin", "stale", "no_async_loading"], (0, 0, 0), ["admin", "slow", "dangerous"],
+    "cluster|delslotsrange" => -4, ["admin", "stale", "no_async_loading"], (0, 0, 0), ["admin", "slow", "dangerous"],
+    "sentinel" => -2, [], (0, 0, 0), [],
+    "sentinel|masters" => 2, ["admin", "sentinel", "only_sentinel"], (0, 0, 0), ["admin", "slow", "dangerous"],
+    "sentinel|master" => 3, ["admin", "sentinel", "only_sentinel"], (0, 0, 0), ["admin", "slow", "dangerous"],
+    "sentinel|get-master-addr-by-name" => 3, ["admin", "sentinel", "only_sentinel"], (0, 0, 0), ["admin", "slow", "dangerous"],
+    "command" => -1, ["loading", "stale"], (0, 0, 0), ["slow", "connection"],
+    "command|count" => 2, ["loading", "stale"], (0, 0, 0), ["slow", "connection"],
+    "command|info" => -2, ["loading", "stale"], (0, 0, 0), ["slow", "connection"],
+    "command|docs" => -2, ["loading", "stale"], (0, 0, 0), ["slow", "connection"],
+    "command|getkeys" => -3, ["loading", "stale"], (0, 0, 0), ["slow", "connection"],
+];
+
+/// the subcommand if the command is a container and it is known, the
+/// command otherwise
+pub fn lookup(name: &str, subcommand: Option<&str>) -> Option<&'static CommandInfo> {
+    let full_name = subcommand.map(|subcommand| format!("{}|{}", name, subcommand));
+    COMMANDS
+        .iter()
+        .find(|info| Some(info.name) == full_name.as_deref())
+        .or_else(|| COMMANDS.iter().find(|info| info.name == name))
+}
+
+/// the command or subcommand named exactly so, in lowercase
+pub fn find(name: &str) -> Option<&'static CommandInfo> {
+    COMMANDS.iter().find(|info| info.name == name)
+}
+
+/// the commands, without the subcommands of the containers
+pub fn commands() -> impl Iterator<Item = &'static CommandInfo> {
+    COMMANDS.iter().filter(|info| !info.name.contains('|'))
+}
+
+/// the subcommands of a container, none if it is not one
+pub fn subcommands(container: &'static str) -> impl Iterator<Item = &'static CommandInfo> {
+    COMMANDS.iter().filter(move |info| {
+        matches!(info.name.strip_prefix(container), Some(name) if name.starts_with('|'))
+    })
+}
+
+impl CommandInfo {
+    /// whether the command takes that many arguments, its name included
+    pub fn accepts(&self, argc: usize) -> bool {
+        match self.arity < 0 {
+            true => argc as i64 >= -self.arity,
+            false => argc as i64 == self.arity,
+        }
+    }
+
+    /// positions of the keys among that many arguments, after the first
+    /// and last keys of the command and the step between them
+    pub fn key_positions(&self, argc: usize) -> Vec<usize> {
+        let last = match self.last_key < 0 {
+            true => argc as i64 + self.last_key,
+            false => self.last_key.min(argc as i64 - 1),
+        };
+        match (self.first_key, self.step) {
+            (0, _) | (_, 0) => vec![],
+            (first, step) => (first..=last)
+                .step_by(step as usize)
+                .map(|position| position as usize)
+                .collect(),
+        }
+    }
+
+    /// the group COMMAND DOCS puts the command in, after the type of the
+    /// values it works on, a container being in the group of its
+    /// subcommands
+    pub fn group(&self) -> &'static str {
+        let categories = match subcommands(self.name).next() {
+            Some(subcommand) if self.categories.is_empty() => subcommand.categories,
+            _ => self.categories,
+        };
+        let group = [
+            ("string", "string"),
+            ("hash", "hash"),
+            ("list", "list"),
+            ("set", "set"),
+            ("sortedset", "sorted-set"),
+            ("bitmap", "bitmap"),
+            ("hyperloglog", "hyperloglog"),
+            ("geo", "geo"),
+            ("stream", "stream"),
+            ("pubsub", "pubsub"),
+            ("transaction", "transactions"),
+            ("scripting", "scripting"),
+            ("connection", "connection"),
+            ("keyspace", "generic"),
+        ]
+        .iter()
+        .find(|(category, _)| categories.contains(category))
+        .map(|(_, group)| *group);
+
+        match (self.name, group) {
+            ("sort", _) | ("sort_ro", _) => "generic",
+            (_, Some(group)) => group,
+            (_, None) => "server",
+        }
+    }
+}
@@ -1,4 +1,4 @@
-use crate::command::Command;
+use crate::command::{command_error::RedisCommandError, Command};
 use crate::protocol::Resp;
 
 #[test]
@@ -15,3 +15,40 @@ fn set_command() {
         assert_eq!(command, Command::Set(b"mykey".to_vec(), b"value".to_vec()));
     }
 }
+
+#[test]
+fn wrong_number_of_arguments() {
+    let commands: Vec<&[&[u8]]> = vec![
+        &[b"MOVE", b"key"],
+        &[b"SELECT"],
+        &[b"SWAPDB", b"0"],
+        &[b"MONITOR", b"x"],
+        &[b"SAVE", b"x"],
+        &[b"LASTSAVE", b"x"],
+        &[b"BGREWRITEAOF", b"x"],
+        &[b"REPLICAOF", b"NO"],
+        &[b"SYNC", b"x"],
+        &[b"PSYNC", b"?"],
+        &[b"READONLY", b"x"],
+        &[b"READWRITE", b"x"],
+        &[b"TIME", b"x"],
+        &[b"ECHO"],
+        &[b"WAIT", b"0"],
+        &[b"RESET", b"x"],
+        &[b"SINTERCARD", b"18446744073709551615", b"key"],
+        &[
+            b"ZUNIONSTORE",
+            b"destination",
+            b"18446744073709551615",
+            b"key",
+        ],
+        &[b"ZDIFFSTORE", b"destination", b"0"],
+    ];
+    for command in commands {
+        let resp = command.iter().map(|arg| Resp::BulkString(arg)).collect();
+        assert!(matches!(
+            Command::parse(resp),
+            Err(RedisCommandError::ArgNumber)
+        ));
+    }
+}
@@ -1,5 +1,16 @@
+use std::convert::TryFrom;
+use std::time::Duration;
+
 use super::command_error::RedisCommandError;
+use super::{Items, Keys};
 use crate::protocol::Resp;
+use crate::storage::models::{
+    redis_value::{BitUnit, LcsOptions},
+    ExpiryCondition,
+};
+
+const DEFAULT_SCAN_COUNT: usize = 10;
+const MAX_BIT_OFFSET: u64 = 512 * 1024 * 1024 * 8;
 
 pub fn get_bytes_vec(resp: Option<&Resp>) -> Result<Vec<u8>, RedisCommandError> {
     match resp {
@@ -8,7 +19,253 @@ pub fn get_bytes_vec(resp: Option<&Resp>) -> Result<Vec<u8>, RedisCommandError>
     }
 }
 
+// numkeys key [key ...] followed by the remaining arguments
+pub fn get_numkeys<'a>(resp: &'a [Resp<'a>]) -> Result<(Keys, &'a [Resp<'a>]), RedisCommandError> {
+    let numkeys = get_bytes_vec(resp.first()).and_then(parse_duration)?;
+    // against the arguments left rather than numkeys + 1, which overflows
+    if numkeys == 0 || numkeys > (resp.len() - 1) as u64 {
+        return Err(RedisCommandError::ArgNumber);
+    }
+
+    let numkeys = numkeys as usize;
+    let keys = get_bytes_vecs(&resp[1..numkeys + 1])?;
+    Ok((keys, &resp[numkeys + 1..]))
+}
+
+// numkeys [key ...] [arg ...] of EVAL, where numkeys may be 0
+pub fn get_script_keys_and_args(resp: &[Resp]) -> Result<(Keys, Keys), RedisCommandError> {
+    let numkeys = parse_integer(get_bytes_vec(resp.first())?)?;
+    if numkeys < 0 {
+        return Err(RedisCommandError::ScriptKeys(
+            "Number of keys can't be negative",
+        ));
+    }
+    if numkeys as usize > resp.len() - 1 {
+        return Err(RedisCommandError::ScriptKeys(
+            "Number of keys can't be greater than number of args",
+        ));
+    }
+
+    let (keys, args) = resp[1..].split_at(numkeys as usize);
+    let keys = keys
+        .iter()
+        .map(|x| get_bytes_vec(Some(x)))
+        .collect::<Result<_, _>>()?;
+    let args = args
+        .iter()
+        .map(|x| get_bytes_vec(Some(x)))
+        .collect::<Result<_, _>>()?;
+    Ok((keys, args))
+}
+
+// one or more arguments, e.g. key [key ...] or member [member ...]
+pub fn get_bytes_vecs(resp: &[Resp]) -> Result<Keys, RedisCommandError> {
+    if resp.is_empty() {
+        return Err(RedisCommandError::ArgNumber);
+    }
+
+    resp.iter().map(|x| get_bytes_vec(Some(x))).collect()
+}
+
+// &[key, value, key, value, ...] should be even in length
+// and is turned into [(key, value), (key, value), ...]
+pub fn get_pairs(resp: &[Resp]) -> Result<Items, RedisCommandError> {
+    let chunks = resp.chunks_exact(2);
+    if resp.is_empty() || !chunks.remainder().is_empty() {
+        return Err(RedisCommandError::ArgNumber);
+    }
+
+    chunks
+        .map(|pair| Ok((get_bytes_vec(pair.first())?, get_bytes_vec(pair.get(1))?)))
+        .collect()
+}
+
+// FIELDS numfields field [field ...]
+pub fn get_fields(resp: &[Resp]) -> Result<Keys, RedisCommandError> {
+    match get_bytes_vec(resp.first()) {
+        Ok(keyword) if keyword.eq_ignore_ascii_case(b"FIELDS") => {}
+        _ => return Err(RedisCommandError::Syntax),
+    }
+
+    let numfields = get_bytes_vec(resp.get(1)).and_then(parse_duration)? as usize;
+    let fields = &resp[2..];
+    if numfields == 0 || numfields != fields.len() {
+        return Err(RedisCommandError::ArgNumber);
+    }
+
+    fields
+        .iter()
+        .map(|field| get_bytes_vec(Some(field)))
+        .collect()
+}
+
+// [NX | XX | GT | LT] FIELDS numfields field [field ...]
+pub fn get_hexpire_options(
+    resp: &[Resp],
+) -> Result<(Option<ExpiryCondition>, Keys), RedisCommandError> {
+    match get_bytes_vec(resp.first()).map(|option| parse_expiry_condition(&option)) {
+        Ok(Some(condition)) => Ok((Some(condition), get_fields(&resp[1..])?)),
+        _ => Ok((None, get_fields(resp)?)),
+    }
+}
+
+pub fn parse_expiry_condition(bytes: &[u8]) -> Option<ExpiryCondition> {
+    match bytes.to_ascii_uppercase().as_slice() {
+        b"NX" => Some(ExpiryCondition::Nx),
+        b"XX" => Some(ExpiryCondition::Xx),
+        b"GT" => Some(ExpiryCondition::Gt),
+        b"LT" => Some(ExpiryCondition::Lt),
+        _ => None,
+    }
+}
+
+// [MATCH pattern] [COUNT count]
+pub fn get_scan_options(resp: &[Resp]) -> Result<(Option<Vec<u8>>, usize), RedisCommandError> {
+    let mut pattern = None;
+    let mut count = DEFAULT_SCAN_COUNT;
+
+    let mut options = resp.iter();
+    while let Some(option) = options.next() {
+        let option = get_bytes_vec(Some(option))?;
+        let value = get_bytes_vec(options.next()).map_err(|_| RedisCommandError::Syntax)?;
+
+        match option.to_ascii_uppercase().as_slice() {
+            b"MATCH" => pattern = Some(value),
+            b"COUNT" => match parse_duration(value)? {
+                0 => return Err(RedisCommandError::Syntax),
+                c => count = c as usize,
+            },
+            _ => return Err(RedisCommandError::Syntax),
+        }
+    }
+
+    Ok((pattern, count))
+}
+
+// [TYPE normal|master|replica|pubsub] [ID client-id [client-id ...]]
+pub fn get_client_list_options(
+    resp: &[Resp],
+) -> Result<(Option<Vec<u8>>, Vec<u64>), RedisCommandError> {
+    let mut client_type = None;
+    let mut ids = vec![];
+
+    let mut options = resp.iter();
+    while let Some(option) = options.next() {
+        match get_bytes_vec(Some(option))?.to_ascii_uppercase().as_slice() {
+            b"TYPE" => {
+                let value = get_bytes_vec(options.next()).map_err(|_| RedisCommandError::Syntax)?;
+                match value.to_ascii_lowercase().as_slice() {
+                    b"normal" | b"master" | b"replica" | b"pubsub" => {
+                        client_type = Some(value.to_ascii_lowercase())
+                    }
+                    _ => return Err(RedisCommandError::Syntax),
+                }
+            }
+            b"ID" => {
+                for id in options.by_ref() {
+                    ids.push(parse_duration(get_bytes_vec(Some(id))?)?);
+                }
+                if ids.is_empty() {
+                    return Err(RedisCommandError::Syntax);
+                }
+            }
+            _ => return Err(RedisCommandError::Syntax),
+        }
+    }
+
+    Ok((client_type, ids))
+}
+
 pub fn parse_duration(bytes: Vec<u8>) -> Result<u64, RedisCommandError> {
     let duration = std::str::from_utf8(&bytes[..])?;
     Ok(duration.parse::<u64>()?)
 }
+
+pub fn parse_integer(bytes: Vec<u8>) -> Result<i64, RedisCommandError> {
+    let integer = std::str::from_utf8(&bytes[..])?;
+    Ok(integer.parse::<i64>()?)
+}
+
+// a hash slot of a cluster, which has 16384 of them
+pub fn parse_slot(bytes: Vec<u8>) -> Result<u16, RedisCommandError> {
+    match parse_integer(bytes).map(u16::try_from) {
+        Ok(Ok(slot)) if slot < 16384 => Ok(slot),
+        _ => Err(RedisCommandError::Slot),
+    }
+}
+
+// offset of a bit within a string, which is 512MB at most
+pub fn parse_bit_offset(bytes: Vec<u8>) -> Result<usize, RedisCommandError> {
+    parse_duration(bytes)
+        .map_err(|_| RedisCommandError::BitOffset)
+        .and_then(check_bit_offset)
+}
+
+pub fn check_bit_offset(offset: u64) -> Result<usize, RedisCommandError> {
+    match offset < MAX_BIT_OFFSET {
+        true => Ok(offset as usize),
+        false => Err(RedisCommandError::BitOffset),
+    }
+}
+
+// BYTE or BIT, the unit of the ranges of BITCOUNT and BITPOS
+pub fn parse_bit_unit(bytes: Vec<u8>) -> Result<BitUnit, RedisCommandError> {
+    match bytes.to_ascii_uppercase().as_slice() {
+        b"BYTE" => Ok(BitUnit::Byte),
+        b"BIT" => Ok(BitUnit::Bit),
+        _ => Err(RedisCommandError::Syntax),
+    }
+}
+
+// [LEN] [IDX] [MINMATCHLEN len] [WITHMATCHLEN]
+pub fn get_lcs_options(resp: &[Resp]) -> Result<LcsOptions, RedisCommandError> {
+    let mut options = LcsOptions::default();
+
+    let mut resp = resp.iter();
+    while let Some(option) = resp.next() {
+        match get_bytes_vec(Some(option))?.to_ascii_uppercase().as_slice() {
+            b"LEN" => options.len = true,
+            b"IDX" => options.idx = true,
+            b"MINMATCHLEN" => {
+                let len = get_bytes_vec(resp.next()).map_err(|_| RedisCommandError::Syntax)?;
+                // a negative length leaves no range out
+                options.min_match_len = parse_integer(len)?.max(0) as usize;
+            }
+            b"WITHMATCHLEN" => options.with_match_len = true,
+            _ => return Err(RedisCommandError::Syntax),
+        }
+    }
+
+    match options.len && options.idx {
+        true => Err(RedisCommandError::IncompatibleOptions(
+            "If you want both the length and indexes, please just use IDX.",
+        )),
+        false => Ok(options),
+    }
+}
+
+// timeout of a blocking command in seconds, 0 meaning forever
+pub fn parse_timeout(bytes: Vec<u8>) -> Result<Option<Duration>, RedisCommandError> {
+    let timeout = match std::str::from_utf8(&bytes[..])?.parse::<f64>() {
+        Ok(timeout) if timeout.is_finite() => timeout,
+        _ => return Err(RedisCommandError::Timeout),
+    };
+
+    if timeout < 0.0 {
+        Err(RedisCommandError::NegativeTimeout)
+    } else if timeout == 0.0 {
+        Ok(None)
+    } else {
+        Duration::try_from_secs_f64(timeout)
+            .map(Some)
+            .map_err(|_| RedisCommandError::TimeoutRange)
+    }
+}
+
+// accepts what Redis accepts as a score, including inf, +inf and -inf
+pub fn parse_score(bytes: Vec<u8>) -> Result<f64, RedisCommandError> {
+    match std::str::from_utf8(&bytes[..])?.parse::<f64>() {
+        Ok(score) if !score.is_nan() => Ok(score),
+        _ => Err(RedisCommandError::FloatParse),
+    }
+}
@@ -0,0 +1,73 @@
+#[cfg(test)]
+mod tests;
+
+/// Redis style glob matching, supporting `*`, `?`, `[abc]`, `[^abc]`, `[a-z]`
+/// and `\` to escape special characters
+pub fn glob_match(pattern: &[u8], string: &[u8]) -> bool {
+    match pattern.split_first() {
+        None => string.is_empty(),
+        Some((b'*', pattern)) => {
+            // consecutive stars behave as a single one
+            let pattern = &pattern[pattern.iter().take_while(|p| **p == b'*').count()..];
+            if pattern.is_empty() {
+                return true;
+            }
+
+            // try to match the rest of the pattern from every position
+            (0..=string.len()).any(|i| glob_match(pattern, &string[i..]))
+        }
+        Some((b'?', pattern)) => match string.split_first() {
+            Some((_, string)) => glob_match(pattern, string),
+            None => false,
+        },
+        Some((b'[', class)) => match string.split_first() {
+            Some((c, string)) => match match_class(class, *c) {
+                Some((true, pattern)) => glob_match(pattern, string),
+                _ => false,
+            },
+            None => false,
+        },
+        Some((b'\\', pattern)) if !pattern.is_empty() => match string.split_first() {
+            Some((c, string)) if *c == pattern[0] => glob_match(&pattern[1..], string),
+            _ => false,
+        },
+        Some((p, pattern)) => match string.split_first() {
+            Some((c, string)) if c == p => glob_match(pattern, string),
+            _ => false,
+        },
+    }
+}
+
+/// match c against a character class, class being the pattern right after `[`;
+/// returns whether it matched and the pattern left after the closing `]`
+fn match_class(class: &[u8], c: u8) -> Option<(bool, &[u8])> {
+    let (negate, mut class) = match class.split_first() {
+        Some((b'^', class)) => (true, class),
+        _ => (false, class),
+    };
+
+    let mut matched = false;
+    loop {
+        match class {
+            [] => return None, // unterminated class
+            [b']', rest @ ..] => return Some((matched != negate, rest)),
+            [b'\\', escaped, rest @ ..] => {
+                matched |= *escaped == c;
+                class = rest;
+            }
+            [start, b'-', end, rest @ ..] if *end != b']' => {
+                let (start, end) = if start <= end {
+                    (*start, *end)
+                } else {
+                    (*end, *start)
+                };
+                matched |= start <= c && c <= end;
+                class = rest;
+            }
+            [x, rest @ ..] => {
+                matched |= *x == c;
+                class = rest;
+            }
+        }
+    }
+}
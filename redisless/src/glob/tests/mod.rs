@@ -0,0 +1,21 @@
+use crate::glob::glob_match;
+
+#[test]
+fn glob() {
+    assert!(glob_match(b"*", b""));
+    assert!(glob_match(b"*", b"anything"));
+    assert!(glob_match(b"h?llo", b"hello"));
+    assert!(!glob_match(b"h?llo", b"hllo"));
+    assert!(glob_match(b"h*llo", b"heeeello"));
+    assert!(glob_match(b"h[ae]llo", b"hallo"));
+    assert!(!glob_match(b"h[ae]llo", b"hillo"));
+    assert!(glob_match(b"h[^e]llo", b"hallo"));
+    assert!(!glob_match(b"h[^e]llo", b"hello"));
+    assert!(glob_match(b"h[a-b]llo", b"hbllo"));
+    assert!(!glob_match(b"h[a-b]llo", b"hcllo"));
+    assert!(glob_match(b"user:*:name", b"user:42:name"));
+    assert!(!glob_match(b"user:*:name", b"user:42:age"));
+    assert!(glob_match(b"h\\*llo", b"h*llo"));
+    assert!(!glob_match(b"h\\*llo", b"hello"));
+    assert!(!glob_match(b"h[ello", b"hello"));
+}
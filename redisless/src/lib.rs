@@ -2,9 +2,16 @@
 #[macro_use]
 extern crate serial_test;
 
+use std::ffi::CStr;
+use std::os::raw::c_char;
+use std::ptr;
+
 use storage::in_memory::InMemoryStorage;
 
-use crate::server::{Server, ServerState};
+use crate::server::{Server, ServerBuilder, ServerState};
+
+// the port of a server whose configuration file gives none, as in Redis
+const DEFAULT_PORT: u16 = 6379;
 
 #[cfg(test)]
 mod tests;
@@ -12,6 +19,7 @@ mod tests;
 mod cluster;
 mod command;
 mod error;
+mod glob;
 mod protocol;
 pub mod server;
 pub mod storage;
@@ -21,6 +29,25 @@ pub unsafe extern "C" fn redisless_server_new(port: u16) -> *mut Server {
     Box::into_raw(Box::new(Server::new(InMemoryStorage::new(), port)))
 }
 
+/// a server set up by a redis.conf file, listening on the port it gives or
+/// 6379, null if the file cannot be read or is invalid
+///
+/// # Safety
+///
+/// `path` is null or a nul-terminated string
+#[no_mangle]
+pub unsafe extern "C" fn redisless_server_new_from_config_file(path: *const c_char) -> *mut Server {
+    if path.is_null() {
+        return ptr::null_mut();
+    }
+
+    let path = CStr::from_ptr(path).to_string_lossy().to_string();
+    match ServerBuilder::new(InMemoryStorage::new(), DEFAULT_PORT).config_file(path) {
+        Ok(builder) => Box::into_raw(Box::new(builder.build())),
+        Err(_) => ptr::null_mut(),
+    }
+}
+
 #[no_mangle]
 pub unsafe extern "C" fn redisless_server_free(server: *mut Server) {
     let _ = Box::from_raw(server);
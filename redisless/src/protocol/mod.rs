@@ -3,6 +3,7 @@ mod tests;
 
 pub mod error;
 pub mod parser;
+pub mod response;
 
 use error::RedisError;
 
@@ -14,8 +15,12 @@ const LF: u8 = b'\n';
 
 pub const OK: &[u8; 5] = b"+OK\r\n";
 pub const PONG: &[u8; 7] = b"+PONG\r\n";
-pub const EMPTY_LIST: &[u8; 6] = b"$0\r\n\r\n";
 pub const NIL: &[u8; 5] = b"$-1\r\n";
+pub const NIL_ARRAY: &[u8; 5] = b"*-1\r\n";
+pub const RESP3_NULL: &[u8; 3] = b"_\r\n";
+pub const WRONGTYPE: &[u8; 68] =
+    b"-WRONGTYPE Operation against a key holding the wrong kind of value\r\n";
+pub const INVALID_HLL: &[u8; 57] = b"-WRONGTYPE Key is not a valid HyperLogLog string value.\r\n";
 
 #[derive(Debug, Eq, PartialEq)]
 pub enum Resp<'a> {
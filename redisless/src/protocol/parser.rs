@@ -74,6 +74,11 @@ impl RedisProtocolParser {
     }
 
     pub fn parse_arrays(input: &[u8]) -> Result {
+        // Check Null Arrays.
+        if RedisProtocolParser::check_null_value(input) {
+            return Ok((Resp::Nil, &input[NIL_VALUE_SIZE..]));
+        }
+
         let (size_str, input) = RedisProtocolParser::parse_everything_until_crlf(input)?;
         let size = std::str::from_utf8(size_str)?.parse::<u64>()?;
         let sizes = size as usize;
@@ -0,0 +1,60 @@
+#[derive(Debug, PartialEq, Clone)]
+pub enum RedisResponse {
+    Status(String),
+    Error(String),
+    Integer(i64),
+    BulkString(Vec<u8>),
+    Array(Vec<RedisResponse>),
+    // out of band data of RESP3, e.g. key invalidations
+    Push(Vec<RedisResponse>),
+    // fields and their values, flattened into an array in RESP2
+    Map(Vec<(RedisResponse, RedisResponse)>),
+    Nil,
+}
+
+impl RedisResponse {
+    pub fn to_vec(&self) -> Vec<u8> {
+        self.to_vec_in(2)
+    }
+
+    /// the response in the version of RESP a connection speaks, maps and
+    /// nils having their own types in RESP3
+    pub fn to_vec_in(&self, protocol: u8) -> Vec<u8> {
+        let mut buf = Vec::new();
+        self.write_to(&mut buf, protocol == 3);
+        buf
+    }
+
+    fn write_to(&self, buf: &mut Vec<u8>, resp3: bool) {
+        match self {
+            RedisResponse::Status(s) => buf.extend_from_slice(format!("+{}\r\n", s).as_bytes()),
+            RedisResponse::Error(e) => buf.extend_from_slice(format!("-{}\r\n", e).as_bytes()),
+            RedisResponse::Integer(i) => buf.extend_from_slice(format!(":{}\r\n", i).as_bytes()),
+            RedisResponse::BulkString(bytes) => {
+                buf.extend_from_slice(format!("${}\r\n", bytes.len()).as_bytes());
+                buf.extend_from_slice(bytes);
+                buf.extend_from_slice(b"\r\n");
+            }
+            RedisResponse::Array(items) => {
+                buf.extend_from_slice(format!("*{}\r\n", items.len()).as_bytes());
+                items.iter().for_each(|item| item.write_to(buf, resp3));
+            }
+            RedisResponse::Push(items) => {
+                buf.extend_from_slice(format!(">{}\r\n", items.len()).as_bytes());
+                items.iter().for_each(|item| item.write_to(buf, resp3));
+            }
+            RedisResponse::Map(fields) => {
+                match resp3 {
+                    true => buf.extend_from_slice(format!("%{}\r\n", fields.len()).as_bytes()),
+                    false => buf.extend_from_slice(format!("*{}\r\n", fields.len() * 2).as_bytes()),
+                }
+                for (field, value) in fields {
+                    field.write_to(buf, resp3);
+                    value.write_to(buf, resp3);
+                }
+            }
+            RedisResponse::Nil if resp3 => buf.extend_from_slice(super::RESP3_NULL),
+            RedisResponse::Nil => buf.extend_from_slice(super::NIL),
+        }
+    }
+}
@@ -101,3 +101,30 @@ pub fn test_array_of_arrays() -> std::result::Result<(), RedisError> {
     assert!(left.is_empty());
     Ok(())
 }
+
+#[test]
+pub fn test_response() {
+    use crate::protocol::response::RedisResponse;
+
+    let response = RedisResponse::Array(vec![
+        RedisResponse::Integer(-2),
+        RedisResponse::BulkString(b"foo".to_vec()),
+        RedisResponse::Nil,
+        RedisResponse::Array(vec![RedisResponse::Status("OK".to_string())]),
+    ]);
+    assert_eq!(
+        response.to_vec(),
+        b"*4\r\n:-2\r\n$3\r\nfoo\r\n$-1\r\n*1\r\n+OK\r\n".to_vec()
+    );
+
+    // maps are flattened into arrays in RESP2
+    let response = RedisResponse::Map(vec![(
+        RedisResponse::BulkString(b"proto".to_vec()),
+        RedisResponse::Nil,
+    )]);
+    assert_eq!(response.to_vec(), b"*2\r\n$5\r\nproto\r\n$-1\r\n".to_vec());
+    assert_eq!(
+        response.to_vec_in(3),
+        b"%1\r\n$5\r\nproto\r\n_\r\n".to_vec()
+    );
+}
@@ -1,5 +1,7 @@
-use std::io::ErrorKind;
+use std::io::{self, ErrorKind};
 use std::net::{IpAddr, Ipv4Addr, SocketAddr, TcpListener, TcpStream};
+use std::path::Path;
+use std::sync::atomic::Ordering;
 use std::sync::{Arc, Mutex};
 use std::thread;
 use std::time::{Duration, SystemTime};
@@ -9,16 +11,35 @@ use mpb::MPB;
 use rayon::ThreadPool;
 use uuid::Uuid;
 
+use util::acl::Acl;
+use util::aof::{Aof, Fsync};
+use util::cluster::{self, Cluster};
+use util::config::{self, Config};
+use util::connection::Connection;
+use util::events::Events;
+use util::replication::Replication;
+use util::shards::Shards;
+use util::shared::Shared;
 use util::*;
 
 use crate::cluster::peer::{Peer, PeersDiscovery, DEFAULT_NODE_LISTENING_PORT};
-use crate::storage::Storage;
+use crate::storage::{Sibling, Storage};
 
 #[cfg(test)]
 mod tests;
 
 mod util;
 
+pub use util::events::KeyEvent;
+
+// each connection keeps a thread busy, including while blocked on a command
+const CONNECTION_THREADS: usize = 32;
+// logical databases SELECT switches between, as many as Redis has
+const DEFAULT_DATABASES: usize = 16;
+// how long a connection waits for a request before delivering the messages
+// published to it
+const READ_TIMEOUT: Duration = Duration::from_millis(10);
+
 type CloseConnection = bool;
 type ReceivedDataLength = usize;
 type CommandResponse = Vec<u8>;
@@ -26,6 +47,7 @@ type CommandResponse = Vec<u8>;
 pub struct Server {
     server_state_bus: MPB<ServerState>,
     cluster_options: ServerClusterOptions,
+    events: Events,
 }
 
 #[derive(Debug, Eq, PartialEq, Clone)]
@@ -72,29 +94,202 @@ impl Default for ServerClusterOptions {
     }
 }
 
-impl Server {
-    pub fn new<T: Storage + Send + 'static>(storage: T, port: u16) -> Self {
-        Server::new_with_cluster_options(storage, ServerClusterOptions::default(), port)
+/// server with options besides the storage and the port
+pub struct ServerBuilder<T> {
+    storage: T,
+    port: u16,
+    cluster_options: ServerClusterOptions,
+    requirepass: Option<String>,
+    databases: usize,
+    config: Config,
+    // users and their rules, as ACL SETUSER takes them
+    users: Vec<(String, Vec<Vec<u8>>)>,
+    shards: usize,
+}
+
+impl<T: Storage + Sibling + Send + Sync + 'static> ServerBuilder<T> {
+    pub fn new(storage: T, port: u16) -> Self {
+        let shards = storage.shards();
+        ServerBuilder {
+            storage,
+            port,
+            cluster_options: ServerClusterOptions::default(),
+            requirepass: None,
+            databases: DEFAULT_DATABASES,
+            config: Config::new(),
+            users: vec![],
+            shards,
+        }
     }
 
-    pub fn new_with_cluster_options<T: Storage + Send + 'static>(
-        storage: T,
-        cluster_options: ServerClusterOptions,
-        port: u16,
-    ) -> Self {
+    pub fn cluster_options(mut self, cluster_options: ServerClusterOptions) -> Self {
+        self.cluster_options = cluster_options;
+        self
+    }
+
+    /// password clients have to give to AUTH before running other commands
+    pub fn requirepass<P: Into<String>>(mut self, password: P) -> Self {
+        self.requirepass = Some(password.into());
+        self
+    }
+
+    /// number of logical databases, the storage given being the first one
+    /// and the others its siblings, such as sled databases next to it
+    pub fn databases(mut self, databases: usize) -> Self {
+        self.databases = databases.max(1);
+        self
+    }
+
+    /// spread the keys over shards locked apart, so that the commands whose
+    /// keys are in different shards run at the same time, the keys sharing
+    /// a hash tag being in the same one. The keys of the storage given are
+    /// moved to the siblings of it the shards start with. Unless told, the
+    /// storages in memory are split into a few shards and the ones on disk
+    /// into one
+    pub fn shards(mut self, shards: usize) -> Self {
+        self.shards = shards.max(1);
+        self
+    }
+
+    /// run in cluster mode, serving the slots CLUSTER ADDSLOTS assigns and
+    /// telling the clients about the nodes CLUSTER MEET introduced
+    pub fn cluster_enabled(mut self, enabled: bool) -> Self {
+        let value: &[u8] = match enabled {
+            true => b"yes",
+            false => b"no",
+        };
+        let _ = self.config.init(b"cluster-enabled", value);
+        self
+    }
+
+    /// answer as a sentinel on another port, telling the applications that
+    /// look the master up by name about the server, or about the master it
+    /// replicates, and publishing `+switch-master` once it changes
+    pub fn sentinel<N: Into<String>>(mut self, port: u16, master_name: N) -> Self {
+        let _ = self
+            .config
+            .init(b"sentinel-port", port.to_string().as_bytes());
+        let _ = self
+            .config
+            .init(b"sentinel-master-name", master_name.into().as_bytes());
+        self
+    }
+
+    /// the directives of a redis.conf file, such as `port`, `databases`,
+    /// `requirepass`, `user` or the parameters CONFIG GET reads, the ones
+    /// RedisLess has no use for being ignored
+    pub fn config_file<P: AsRef<Path>>(mut self, path: P) -> io::Result<Self> {
+        let path = path.as_ref();
+        // each `save` line adds to the others rather than replacing them
+        let mut save: Option<Vec<Vec<u8>>> = None;
+        let _ = self.config.init(b"port", self.port.to_string().as_bytes());
+        let _ = self
+            .config
+            .init(b"databases", self.databases.to_string().as_bytes());
+
+        for directive in config::read_file(path)? {
+            let invalid = |reason: String| {
+                io::Error::new(
+                    ErrorKind::InvalidData,
+                    format!("{}:{}: {}", path.display(), directive.line, reason),
+                )
+            };
+
+            match (directive.name.as_slice(), directive.args.as_slice()) {
+                (b"requirepass", [password]) => {
+                    self.requirepass = Some(String::from_utf8_lossy(password).to_string())
+                }
+                (b"user", [username, rules @ ..]) => {
+                    let username = String::from_utf8_lossy(username).to_string();
+                    Acl::new(None).set_user(&username, rules).map_err(invalid)?;
+                    self.users.push((username, rules.to_vec()));
+                }
+                (b"save", args) => save.get_or_insert_with(Vec::new).extend(args.to_vec()),
+                (name, args) if Config::knows(name) => {
+                    self.config.init(name, &args.join(&b' ')).map_err(invalid)?;
+                }
+                _ => {}
+            }
+        }
+
+        if let Some(save) = save {
+            let save = save.into_iter().filter(|arg| !arg.is_empty());
+            self.config
+                .init(b"save", &save.collect::<Vec<_>>().join(&b' '))
+                .map_err(|reason| io::Error::new(ErrorKind::InvalidData, reason))?;
+        }
+        // the port and the databases of the file win over the ones given
+        // to the builder
+        if let Ok(port) = String::from_utf8_lossy(self.config.value("port")).parse() {
+            self.port = port;
+        }
+        if let Ok(databases) = String::from_utf8_lossy(self.config.value("databases")).parse() {
+            self.databases = databases;
+        }
+
+        Ok(self)
+    }
+
+    pub fn build(self) -> Server {
         let s = Server {
             server_state_bus: MPB::new(),
-            cluster_options,
+            cluster_options: self.cluster_options,
+            events: Events::new(),
         };
 
-        s._init_configuration(format!("0.0.0.0:{}", port), storage);
+        // the parameters the builder was given, valid whatever they are
+        let mut config = self.config;
+        let _ = config.init(b"port", self.port.to_string().as_bytes());
+        let _ = config.init(b"databases", self.databases.to_string().as_bytes());
+
+        // the users of a configuration file were checked once it was read
+        let mut acl = Acl::new(self.requirepass);
+        for (username, rules) in &self.users {
+            let _ = acl.set_user(username, rules);
+        }
+        let mut aof = Aof::new();
+        aof.fsync = Fsync::parse(config.value("appendfsync"));
+        let mut replication = Replication::new();
+        replication.backlog_size = config.integer("repl-backlog-size") as usize;
+        let mut cluster = Cluster::new();
+        cluster.enabled = config.value("cluster-enabled") == b"yes";
+        let shared = Shared {
+            cluster: Mutex::new(cluster),
+            acl: Mutex::new(acl),
+            aof: Mutex::new(aof),
+            replication: Mutex::new(replication),
+            config: Mutex::new(config),
+            events: s.events.clone(),
+            ..Shared::new()
+        };
+        let addr = format!("0.0.0.0:{}", self.port);
+        s._init_configuration(addr, self.storage, self.databases, self.shards, shared);
         s
     }
+}
 
-    fn _init_configuration<A: Into<String>, T: Storage + Send + 'static>(
+impl Server {
+    pub fn new<T: Storage + Sibling + Send + Sync + 'static>(storage: T, port: u16) -> Self {
+        ServerBuilder::new(storage, port).build()
+    }
+
+    pub fn new_with_cluster_options<T: Storage + Sibling + Send + Sync + 'static>(
+        storage: T,
+        cluster_options: ServerClusterOptions,
+        port: u16,
+    ) -> Self {
+        ServerBuilder::new(storage, port)
+            .cluster_options(cluster_options)
+            .build()
+    }
+
+    fn _init_configuration<A: Into<String>, T: Storage + Sibling + Send + Sync + 'static>(
         &self,
         addr: A,
         storage: T,
+        databases: usize,
+        shards: usize,
+        shared: Shared,
     ) {
         let addr = addr.into();
         let state_send = self.server_state_bus.sender();
@@ -111,17 +306,39 @@ impl Server {
 
         let _ = thread::spawn(move || {
             let addr = addr;
-            let storage = Arc::new(Mutex::new(storage));
+            // the siblings of the storage that can't be opened keep the
+            // server from starting, as a dataset that can't be loaded does
+            let databases = match Shards::new(storage, databases, shards) {
+                Ok(databases) => databases,
+                Err(error) => return refuse_start(&state_send, &state_recv, error.to_string()),
+            };
+            // a dataset that can't be loaded keeps the server from starting,
+            // rather than it starting empty and overwriting the file
+            let load_error = load(&mut lock_then_release(&databases), &shared)
+                .err()
+                .map(|error| error.to_string());
+            let databases = Arc::new(databases);
+            let shared = Arc::new(shared);
+            aof::start_fsync_thread(&shared);
 
             loop {
-                if let Ok(server_state) = state_recv.recv() {
-                    if server_state == ServerState::Start {
+                match state_recv.recv() {
+                    Ok(ServerState::Start) if load_error.is_some() => {
+                        let _ = state_send
+                            .send(ServerState::Error(load_error.clone().unwrap_or_default()));
+                    }
+                    Ok(ServerState::Start) => {
                         // start local RESP server
-                        start_server(&addr, &state_send, &state_recv, &storage);
+                        start_server(&addr, &state_send, &state_recv, &databases, &shared);
 
                         // start current node listener
                         cluster_node.start_listener();
                     }
+                    Ok(ServerState::Stop) => {
+                        // already stopped, such as by SHUTDOWN
+                        let _ = state_send.send(ServerState::Stopped);
+                    }
+                    _ => {}
                 }
             }
         });
@@ -151,6 +368,9 @@ impl Server {
             if server_state == post_change_to_state {
                 return Some(server_state);
             }
+            if let ServerState::Error(_) = server_state {
+                return Some(server_state);
+            }
         }
 
         Some(ServerState::Timeout)
@@ -165,13 +385,56 @@ impl Server {
     pub fn stop(&self) -> Option<ServerState> {
         self.change_state(ServerState::Stop)
     }
+
+    /// a channel receiving the keys that expire or are evicted from now on,
+    /// for the application to tell how its cache churns
+    pub fn key_events(&self) -> Receiver<KeyEvent> {
+        self.events.subscribe()
+    }
+}
+
+/// load the dataset from the append only file when it is on, from the
+/// snapshot otherwise, the append only file being then rewritten from what
+/// was loaded for the changes to be appended to it
+fn load<T: Storage>(databases: &mut [T], shared: &Shared) -> io::Result<()> {
+    let config = lock_then_release(&shared.config);
+    if config.value("appendonly") != b"yes" {
+        return persistence::load(databases, &config);
+    }
+
+    if !aof::load(databases, &config)? {
+        persistence::load(databases, &config)?;
+    }
+    drop(config);
+    aof::rewrite(databases, shared)
+}
+
+/// reply the error to every request to start the server, one whose
+/// storages can't be opened
+fn refuse_start(
+    state_send: &Sender<ServerState>,
+    state_recv: &Receiver<ServerState>,
+    error: String,
+) {
+    loop {
+        match state_recv.recv() {
+            Ok(ServerState::Start) => {
+                let _ = state_send.send(ServerState::Error(error.clone()));
+            }
+            Ok(ServerState::Stop) => {
+                let _ = state_send.send(ServerState::Stopped);
+            }
+            _ => {}
+        }
+    }
 }
 
-fn start_server<T: Storage + Send + 'static>(
+fn start_server<T: Storage + Send + Sync + 'static>(
     addr: &str,
     state_send: &Sender<ServerState>,
     state_recv: &Receiver<ServerState>,
-    storage: &Arc<Mutex<T>>,
+    databases: &Arc<Shards<T>>,
+    shared: &Arc<Shared>,
 ) {
     let listener = match TcpListener::bind(addr) {
         Ok(listener) => {
@@ -186,8 +449,22 @@ fn start_server<T: Storage + Send + 'static>(
         }
     };
 
+    // the sentinel listener is polled along with the one of the server
+    let sentinel_port = lock_then_release(&shared.config).integer("sentinel-port");
+    let sentinel_listener = match sentinel_port {
+        0 => None,
+        port => match TcpListener::bind(format!("0.0.0.0:{}", port)) {
+            Ok(listener) => {
+                let _ = listener.set_nonblocking(true);
+                Some(listener)
+            }
+            Err(_) => None,
+        },
+    };
+
     let thread_pool = match rayon::ThreadPoolBuilder::new()
         .thread_name(|_| "request handler".to_string())
+        .num_threads(CONNECTION_THREADS)
         .build()
     {
         Ok(pool) => pool,
@@ -200,7 +477,15 @@ fn start_server<T: Storage + Send + 'static>(
     for stream in listener.incoming() {
         match stream {
             Ok(tcp_stream) => {
-                handle_tcp_stream(tcp_stream, &thread_pool, &state_send, &state_recv, &storage);
+                handle_tcp_stream(
+                    tcp_stream,
+                    &thread_pool,
+                    &state_send,
+                    &state_recv,
+                    databases,
+                    shared,
+                    false,
+                );
             }
             Err(err) if err.kind() == ErrorKind::WouldBlock => {
                 thread::sleep(Duration::from_millis(10));
@@ -209,30 +494,63 @@ fn start_server<T: Storage + Send + 'static>(
                 break;
             }
         }
+        if let Some(Ok((tcp_stream, _))) = sentinel_listener.as_ref().map(TcpListener::accept) {
+            handle_tcp_stream(
+                tcp_stream,
+                &thread_pool,
+                state_send,
+                state_recv,
+                databases,
+                shared,
+                true,
+            );
+        }
 
         if stop_sig_received(&state_recv, &state_send) {
             // let's gracefully shutdown the server
             break;
         }
+
+        expire::cron(databases, shared);
+        persistence::cron(databases, shared);
+        replication::cron(databases, shared);
+        cluster::cron(shared);
+        sentinel::cron(shared);
+
+        if shared.shutdown.swap(false, Ordering::Relaxed) {
+            // SHUTDOWN closes the connections along with the listener
+            lock_then_release(&shared.clients).close_all();
+            break;
+        }
     }
 }
 
-fn handle_tcp_stream<T: Storage + Send + 'static>(
+fn handle_tcp_stream<T: Storage + Send + Sync + 'static>(
     tcp_stream: TcpStream,
     thread_pool: &ThreadPool,
     state_send: &Sender<ServerState>,
     state_recv: &Receiver<ServerState>,
-    storage: &Arc<Mutex<T>>,
+    databases: &Arc<Shards<T>>,
+    shared: &Arc<Shared>,
+    sentinel: bool,
 ) {
-    let storage = storage.clone();
+    let databases = databases.clone();
+    let shared = shared.clone();
     let state_recv = state_recv.clone();
     let state_send = state_send.clone();
 
     let _ = thread_pool.spawn(move || {
         let mut last_update = SystemTime::now();
+        let mut connection = Connection::new();
+        connection.sentinel = sentinel;
+        let _ = tcp_stream.set_read_timeout(Some(READ_TIMEOUT));
+        lock_then_release(&shared.clients).register(&connection, &tcp_stream);
+        lock_then_release(&shared.stats).connections += 1;
 
         loop {
-            let (close_connection, received_data_length) = handle_request(&storage, &tcp_stream);
+            let (close_connection, received_data_length) =
+                handle_request(&databases, &shared, &mut connection, &tcp_stream);
+            deliver_pushed(&connection, &tcp_stream);
 
             if received_data_length > 0 {
                 // reset the last time we received data
@@ -244,19 +562,21 @@ fn handle_tcp_stream<T: Storage + Send + 'static>(
 
             if stop_sig_received(&state_recv, &state_send) || close_connection {
                 // let's close the connection
-                return;
+                break;
             }
 
             if let Ok(duration) = last_update.duration_since(SystemTime::now()) {
                 if duration.as_secs() >= 300 {
                     // close the connection after 300 secs of inactivity
-                    return;
+                    break;
                 }
             }
-
-            if close_connection {
-                return;
-            }
         }
+
+        lock_then_release(&shared.pubsub).remove(&mut connection);
+        lock_then_release(&shared.tracking).disable(&connection);
+        lock_then_release(&shared.monitors).remove(&connection);
+        lock_then_release(&shared.replication).remove(&connection);
+        lock_then_release(&shared.clients).unregister(&connection);
     });
 }
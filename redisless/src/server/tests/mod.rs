@@ -1,8 +1,17 @@
 use redis::{cmd, Commands, RedisResult};
-use std::{thread::sleep, time::Duration};
+use std::{
+    collections::HashMap,
+    io::{BufRead, BufReader, Read, Write},
+    net::TcpStream,
+    thread::sleep,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
 
+use crate::server::KeyEvent;
+use crate::server::ServerBuilder;
 use crate::server::ServerState;
 use crate::storage::in_memory::InMemoryStorage;
+use crate::storage::Storage;
 use crate::Server;
 
 #[test]
@@ -139,6 +148,36 @@ fn get_set() {
     let x: String = con.get("key2").unwrap();
     assert_eq!(x, "value2");
 
+    // the keys of the other types are neither read nor overwritten
+    let _: () = con.hset("hash", "field", "value").unwrap();
+    let _: () = con.sadd("set", "member").unwrap();
+    let _: () = con.zadd("zset", "member", 1).unwrap();
+    let _: String = cmd("XADD")
+        .arg(&["stream", "*", "field", "value"])
+        .query(&mut con)
+        .unwrap();
+    for key in &["hash", "set", "zset", "stream"] {
+        let x: RedisResult<String> = con.get(key);
+        assert!(x.unwrap_err().to_string().contains("WRONGTYPE"));
+        let x: RedisResult<String> = con.getset(key, "value");
+        assert!(x.unwrap_err().to_string().contains("WRONGTYPE"));
+        let x: RedisResult<i64> = cmd("INCR").arg(key).query(&mut con);
+        assert!(x.unwrap_err().to_string().contains("WRONGTYPE"));
+    }
+    let x: u32 = cmd("HSTRLEN")
+        .arg(&["hash", "field"])
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(x, 5);
+
+    // nor is a string that is not an integer incremented
+    let _: () = con.setbit("bits", 0, true).unwrap();
+    let x: RedisResult<i64> = cmd("INCR").arg("bits").query(&mut con);
+    assert!(x.unwrap_err().to_string().contains("not an integer"));
+    let _: () = con.set("max", i64::MAX).unwrap();
+    let x: RedisResult<i64> = cmd("INCR").arg("max").query(&mut con);
+    assert!(x.unwrap_err().to_string().contains("out of range"));
+
     assert_eq!(server.stop(), Some(ServerState::Stopped));
 }
 
@@ -245,3 +284,6619 @@ fn start_and_stop_server_multiple_times() {
         assert_eq!(server.stop(), Some(ServerState::Stopped));
     }
 }
+
+#[test]
+#[serial]
+fn hstrlen() {
+    let port = 3400;
+    let server = Server::new(InMemoryStorage::new(), port);
+    assert_eq!(server.start(), Some(ServerState::Started));
+    let redis_client = redis::Client::open(format!("redis://127.0.0.1:{}/", port)).unwrap();
+    let mut con = redis_client.get_connection().unwrap();
+
+    let x: u32 = con.hset("myhash", "field", "value").unwrap();
+    assert_eq!(x, 1);
+    let x: u32 = con.hset("myhash", "field", "longer value").unwrap();
+    assert_eq!(x, 0);
+
+    let x: u32 = cmd("HSTRLEN")
+        .arg("myhash")
+        .arg("field")
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(x, 12);
+    let x: u32 = cmd("HSTRLEN")
+        .arg("myhash")
+        .arg("nofield")
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(x, 0);
+    let x: u32 = cmd("HSTRLEN")
+        .arg("nokey")
+        .arg("field")
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(x, 0);
+
+    let _: () = con.set("key", "value").unwrap();
+    let x: RedisResult<u32> = cmd("HSTRLEN").arg("key").arg("field").query(&mut con);
+    assert_eq!(x.is_err(), true);
+
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+}
+
+#[test]
+#[serial]
+fn hash_field_ttl() {
+    let port = 3401;
+    let server = Server::new(InMemoryStorage::new(), port);
+    assert_eq!(server.start(), Some(ServerState::Started));
+    let redis_client = redis::Client::open(format!("redis://127.0.0.1:{}/", port)).unwrap();
+    let mut con = redis_client.get_connection().unwrap();
+
+    let _: u32 = con.hset("myhash", "f1", "v1").unwrap();
+    let _: u32 = con.hset("myhash", "f2", "v2").unwrap();
+
+    let x: Vec<i64> = cmd("HEXPIRE")
+        .arg("myhash")
+        .arg(100)
+        .arg("FIELDS")
+        .arg(2)
+        .arg("f1")
+        .arg("nofield")
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(x, vec![1, -2]);
+
+    // NX is not met since f1 already has a ttl
+    let x: Vec<i64> = cmd("HEXPIRE")
+        .arg("myhash")
+        .arg(200)
+        .arg("NX")
+        .arg("FIELDS")
+        .arg(1)
+        .arg("f1")
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(x, vec![0]);
+
+    let x: Vec<i64> = cmd("HTTL")
+        .arg("myhash")
+        .arg("FIELDS")
+        .arg(3)
+        .arg("f1")
+        .arg("f2")
+        .arg("nofield")
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(x, vec![100, -1, -2]);
+
+    let x: Vec<i64> = cmd("HPERSIST")
+        .arg("myhash")
+        .arg("FIELDS")
+        .arg(2)
+        .arg("f1")
+        .arg("f2")
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(x, vec![1, -1]);
+
+    let x: Vec<i64> = cmd("HPEXPIRE")
+        .arg("myhash")
+        .arg(50)
+        .arg("FIELDS")
+        .arg(1)
+        .arg("f2")
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(x, vec![1]);
+    sleep(Duration::from_millis(50));
+    let x: u32 = cmd("HSTRLEN")
+        .arg("myhash")
+        .arg("f2")
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(x, 0);
+    let x: u32 = cmd("HSTRLEN")
+        .arg("myhash")
+        .arg("f1")
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(x, 2);
+
+    // a zero ttl deletes the field right away
+    let x: Vec<i64> = cmd("HEXPIRE")
+        .arg("myhash")
+        .arg(0)
+        .arg("FIELDS")
+        .arg(1)
+        .arg("f1")
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(x, vec![2]);
+    let exists: bool = con.exists("myhash").unwrap();
+    assert_eq!(exists, false);
+
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+}
+
+#[test]
+#[serial]
+fn set() {
+    let port = 3402;
+    let server = Server::new(InMemoryStorage::new(), port);
+    assert_eq!(server.start(), Some(ServerState::Started));
+    let redis_client = redis::Client::open(format!("redis://127.0.0.1:{}/", port)).unwrap();
+    let mut con = redis_client.get_connection().unwrap();
+
+    let x: u32 = con.sadd("myset", &["a", "b", "c", "a"][..]).unwrap();
+    assert_eq!(x, 3);
+    let x: u32 = con.sadd("myset", "a").unwrap();
+    assert_eq!(x, 0);
+
+    let mut members: Vec<String> = con.smembers("myset").unwrap();
+    members.sort();
+    assert_eq!(members, vec!["a", "b", "c"]);
+
+    let x: bool = con.sismember("myset", "b").unwrap();
+    assert_eq!(x, true);
+    let x: bool = con.sismember("myset", "d").unwrap();
+    assert_eq!(x, false);
+
+    let x: u32 = con.srem("myset", &["a", "b", "d"][..]).unwrap();
+    assert_eq!(x, 2);
+    let members: Vec<String> = con.smembers("myset").unwrap();
+    assert_eq!(members, vec!["c"]);
+
+    // removing the last member removes the key
+    let x: u32 = con.srem("myset", "c").unwrap();
+    assert_eq!(x, 1);
+    let exists: bool = con.exists("myset").unwrap();
+    assert_eq!(exists, false);
+    let members: Vec<String> = con.smembers("myset").unwrap();
+    assert_eq!(members.is_empty(), true);
+
+    let _: () = con.set("key", "value").unwrap();
+    let x: RedisResult<u32> = con.sadd("key", "a");
+    assert_eq!(x.is_err(), true);
+
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+}
+
+#[test]
+#[serial]
+fn set_algebra() {
+    let port = 3403;
+    let server = Server::new(InMemoryStorage::new(), port);
+    assert_eq!(server.start(), Some(ServerState::Started));
+    let redis_client = redis::Client::open(format!("redis://127.0.0.1:{}/", port)).unwrap();
+    let mut con = redis_client.get_connection().unwrap();
+
+    let _: u32 = con.sadd("set1", &["a", "b", "c", "d"][..]).unwrap();
+    let _: u32 = con.sadd("set2", &["c"][..]).unwrap();
+    let _: u32 = con.sadd("set3", &["a", "c", "e"][..]).unwrap();
+
+    let x: Vec<String> = con.sinter(&["set1", "set2", "set3"][..]).unwrap();
+    assert_eq!(x, vec!["c"]);
+    let x: Vec<String> = con.sinter(&["set1", "nokey"][..]).unwrap();
+    assert_eq!(x.is_empty(), true);
+
+    let mut x: Vec<String> = con.sunion(&["set1", "set3", "nokey"][..]).unwrap();
+    x.sort();
+    assert_eq!(x, vec!["a", "b", "c", "d", "e"]);
+
+    let mut x: Vec<String> = con.sdiff(&["set1", "set2", "set3"][..]).unwrap();
+    x.sort();
+    assert_eq!(x, vec!["b", "d"]);
+
+    let x: u32 = cmd("SINTERSTORE")
+        .arg("dst")
+        .arg(&["set1", "set3"][..])
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(x, 2);
+    let mut x: Vec<String> = con.smembers("dst").unwrap();
+    x.sort();
+    assert_eq!(x, vec!["a", "c"]);
+
+    let x: u32 = cmd("SUNIONSTORE")
+        .arg("dst")
+        .arg(&["set2", "nokey"][..])
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(x, 1);
+    let x: Vec<String> = con.smembers("dst").unwrap();
+    assert_eq!(x, vec!["c"]);
+
+    // an empty result deletes the destination
+    let x: u32 = cmd("SDIFFSTORE")
+        .arg("dst")
+        .arg(&["set2", "set1"][..])
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(x, 0);
+    let exists: bool = con.exists("dst").unwrap();
+    assert_eq!(exists, false);
+
+    let _: () = con.set("key", "value").unwrap();
+    let x: RedisResult<Vec<String>> = con.sunion(&["set1", "key"][..]);
+    assert_eq!(x.is_err(), true);
+
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+}
+
+#[test]
+#[serial]
+fn spop_and_srandmember() {
+    let port = 3404;
+    let server = Server::new(InMemoryStorage::new(), port);
+    assert_eq!(server.start(), Some(ServerState::Started));
+    let redis_client = redis::Client::open(format!("redis://127.0.0.1:{}/", port)).unwrap();
+    let mut con = redis_client.get_connection().unwrap();
+
+    let _: u32 = con.sadd("myset", &["a", "b", "c"][..]).unwrap();
+
+    let x: String = con.srandmember("myset").unwrap();
+    assert_eq!(["a", "b", "c"].contains(&x.as_str()), true);
+    let x: Vec<String> = con.srandmember_multiple("myset", 5).unwrap();
+    assert_eq!(x.len(), 3);
+    // a negative count allows the same member to be returned several times
+    let x: Vec<String> = cmd("SRANDMEMBER")
+        .arg("myset")
+        .arg(-5)
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(x.len(), 5);
+    let x: Option<String> = con.srandmember("nokey").unwrap();
+    assert_eq!(x, None);
+
+    let x: String = con.spop("myset").unwrap();
+    let members: Vec<String> = con.smembers("myset").unwrap();
+    assert_eq!(members.len(), 2);
+    assert_eq!(members.contains(&x), false);
+
+    let x: Vec<String> = cmd("SPOP").arg("myset").arg(5).query(&mut con).unwrap();
+    assert_eq!(x.len(), 2);
+    let exists: bool = con.exists("myset").unwrap();
+    assert_eq!(exists, false);
+    let x: Option<String> = con.spop("myset").unwrap();
+    assert_eq!(x, None);
+
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+}
+
+#[test]
+#[serial]
+fn scard_and_smismember() {
+    let port = 3405;
+    let server = Server::new(InMemoryStorage::new(), port);
+    assert_eq!(server.start(), Some(ServerState::Started));
+    let redis_client = redis::Client::open(format!("redis://127.0.0.1:{}/", port)).unwrap();
+    let mut con = redis_client.get_connection().unwrap();
+
+    let _: u32 = con.sadd("myset", &["a", "b", "c"][..]).unwrap();
+
+    let x: u32 = con.scard("myset").unwrap();
+    assert_eq!(x, 3);
+    let x: u32 = con.scard("nokey").unwrap();
+    assert_eq!(x, 0);
+
+    let x: Vec<u32> = cmd("SMISMEMBER")
+        .arg("myset")
+        .arg(&["a", "d", "c"][..])
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(x, vec![1, 0, 1]);
+    let x: Vec<u32> = cmd("SMISMEMBER")
+        .arg("nokey")
+        .arg(&["a", "b"][..])
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(x, vec![0, 0]);
+
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+}
+
+#[test]
+#[serial]
+fn sscan() {
+    let port = 3406;
+    let server = Server::new(InMemoryStorage::new(), port);
+    assert_eq!(server.start(), Some(ServerState::Started));
+    let redis_client = redis::Client::open(format!("redis://127.0.0.1:{}/", port)).unwrap();
+    let mut con = redis_client.get_connection().unwrap();
+
+    let members = (0..25).map(|i| format!("member:{}", i)).collect::<Vec<_>>();
+    let _: u32 = con.sadd("myset", &members).unwrap();
+    let _: u32 = con.sadd("myset", &["other:1", "other:2"][..]).unwrap();
+
+    let mut cursor = 0;
+    let mut scanned = vec![];
+    loop {
+        let (next_cursor, page): (u64, Vec<String>) = cmd("SSCAN")
+            .arg("myset")
+            .arg(cursor)
+            .arg("MATCH")
+            .arg("member:*")
+            .arg("COUNT")
+            .arg(4)
+            .query(&mut con)
+            .unwrap();
+        scanned.extend(page);
+        cursor = next_cursor;
+        if cursor == 0 {
+            break;
+        }
+    }
+    scanned.sort();
+    let mut expected = members.clone();
+    expected.sort();
+    assert_eq!(scanned, expected);
+
+    let scanned: Vec<String> = con.sscan("myset").unwrap().collect();
+    assert_eq!(scanned.len(), 27);
+
+    let (cursor, page): (u64, Vec<String>) =
+        cmd("SSCAN").arg("nokey").arg(0).query(&mut con).unwrap();
+    assert_eq!(cursor, 0);
+    assert_eq!(page.is_empty(), true);
+
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+}
+
+#[test]
+#[serial]
+fn smove() {
+    let port = 3407;
+    let server = Server::new(InMemoryStorage::new(), port);
+    assert_eq!(server.start(), Some(ServerState::Started));
+    let redis_client = redis::Client::open(format!("redis://127.0.0.1:{}/", port)).unwrap();
+    let mut con = redis_client.get_connection().unwrap();
+
+    let _: u32 = con.sadd("src", &["a", "b"][..]).unwrap();
+    let _: u32 = con.sadd("dst", &["c"][..]).unwrap();
+
+    let x: u32 = con.smove("src", "dst", "a").unwrap();
+    assert_eq!(x, 1);
+    let x: u32 = con.smove("src", "dst", "z").unwrap();
+    assert_eq!(x, 0);
+    let x: bool = con.sismember("dst", "a").unwrap();
+    assert_eq!(x, true);
+    let x: Vec<String> = con.smembers("src").unwrap();
+    assert_eq!(x, vec!["b"]);
+
+    // moving a member onto itself keeps it
+    let x: u32 = con.smove("src", "src", "b").unwrap();
+    assert_eq!(x, 1);
+    let x: Vec<String> = con.smembers("src").unwrap();
+    assert_eq!(x, vec!["b"]);
+
+    // the source is removed once empty and the destination is created
+    let x: u32 = con.smove("src", "new", "b").unwrap();
+    assert_eq!(x, 1);
+    let exists: bool = con.exists("src").unwrap();
+    assert_eq!(exists, false);
+    let x: Vec<String> = con.smembers("new").unwrap();
+    assert_eq!(x, vec!["b"]);
+
+    let _: () = con.set("key", "value").unwrap();
+    let x: RedisResult<u32> = con.smove("dst", "key", "a");
+    assert_eq!(x.is_err(), true);
+    let x: bool = con.sismember("dst", "a").unwrap();
+    assert_eq!(x, true);
+
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+}
+
+#[test]
+#[serial]
+fn sintercard() {
+    let port = 3408;
+    let server = Server::new(InMemoryStorage::new(), port);
+    assert_eq!(server.start(), Some(ServerState::Started));
+    let redis_client = redis::Client::open(format!("redis://127.0.0.1:{}/", port)).unwrap();
+    let mut con = redis_client.get_connection().unwrap();
+
+    let _: u32 = con.sadd("set1", &["a", "b", "c", "d"][..]).unwrap();
+    let _: u32 = con.sadd("set2", &["b", "c", "d", "e"][..]).unwrap();
+
+    let x: u32 = cmd("SINTERCARD")
+        .arg(2)
+        .arg("set1")
+        .arg("set2")
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(x, 3);
+    let x: u32 = cmd("SINTERCARD")
+        .arg(2)
+        .arg("set1")
+        .arg("set2")
+        .arg("LIMIT")
+        .arg(2)
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(x, 2);
+    let x: u32 = cmd("SINTERCARD")
+        .arg(2)
+        .arg("set1")
+        .arg("nokey")
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(x, 0);
+
+    let x: RedisResult<u32> = cmd("SINTERCARD")
+        .arg(3)
+        .arg("set1")
+        .arg("set2")
+        .query(&mut con);
+    assert_eq!(x.is_err(), true);
+
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+}
+
+#[test]
+#[serial]
+fn sorted_set() {
+    let port = 3409;
+    let server = Server::new(InMemoryStorage::new(), port);
+    assert_eq!(server.start(), Some(ServerState::Started));
+    let redis_client = redis::Client::open(format!("redis://127.0.0.1:{}/", port)).unwrap();
+    let mut con = redis_client.get_connection().unwrap();
+
+    let x: u32 = con
+        .zadd_multiple("leaderboard", &[(10, "alice"), (20, "bob")][..])
+        .unwrap();
+    assert_eq!(x, 2);
+    let x: u32 = con.zadd("leaderboard", "alice", 15.5).unwrap();
+    assert_eq!(x, 0);
+
+    let x: f64 = con.zscore("leaderboard", "alice").unwrap();
+    assert_eq!(x, 15.5);
+    let x: String = con.zscore("leaderboard", "bob").unwrap();
+    assert_eq!(x, "20");
+    let x: Option<f64> = con.zscore("leaderboard", "carol").unwrap();
+    assert_eq!(x, None);
+
+    let _: u32 = cmd("ZADD")
+        .arg("leaderboard")
+        .arg("-inf")
+        .arg("carol")
+        .query(&mut con)
+        .unwrap();
+    let x: String = con.zscore("leaderboard", "carol").unwrap();
+    assert_eq!(x, "-inf");
+    let x: RedisResult<u32> = con.zadd("leaderboard", "dave", "nan");
+    assert_eq!(x.is_err(), true);
+
+    let x: u32 = con.zrem("leaderboard", &["alice", "dave"][..]).unwrap();
+    assert_eq!(x, 1);
+    let x: u32 = con.zrem("leaderboard", &["bob", "carol"][..]).unwrap();
+    assert_eq!(x, 2);
+    let exists: bool = con.exists("leaderboard").unwrap();
+    assert_eq!(exists, false);
+
+    let _: () = con.set("key", "value").unwrap();
+    let x: RedisResult<u32> = con.zadd("key", "alice", 1);
+    assert_eq!(x.is_err(), true);
+
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+}
+
+#[test]
+#[serial]
+fn zrange() {
+    let port = 3410;
+    let server = Server::new(InMemoryStorage::new(), port);
+    assert_eq!(server.start(), Some(ServerState::Started));
+    let redis_client = redis::Client::open(format!("redis://127.0.0.1:{}/", port)).unwrap();
+    let mut con = redis_client.get_connection().unwrap();
+
+    let members = &[(1, "a"), (2, "b"), (3, "c"), (4, "d"), (5, "e")][..];
+    let _: u32 = con.zadd_multiple("zset", members).unwrap();
+
+    let x: Vec<String> = con.zrange("zset", 0, -1).unwrap();
+    assert_eq!(x, vec!["a", "b", "c", "d", "e"]);
+    let x: Vec<String> = con.zrange("zset", -2, 100).unwrap();
+    assert_eq!(x, vec!["d", "e"]);
+    let x: Vec<String> = con.zrange("zset", 3, 1).unwrap();
+    assert_eq!(x.is_empty(), true);
+    let x: Vec<(String, f64)> = con.zrange_withscores("zset", 0, 1).unwrap();
+    assert_eq!(x, vec![("a".to_string(), 1.0), ("b".to_string(), 2.0)]);
+    let x: Vec<String> = con.zrevrange("zset", 0, 1).unwrap();
+    assert_eq!(x, vec!["e", "d"]);
+
+    let x: Vec<String> = con.zrangebyscore("zset", "(1", 3).unwrap();
+    assert_eq!(x, vec!["b", "c"]);
+    let x: Vec<String> = con.zrangebyscore("zset", "-inf", "+inf").unwrap();
+    assert_eq!(x.len(), 5);
+    let x: Vec<String> = con.zrangebyscore_limit("zset", 2, "+inf", 1, 2).unwrap();
+    assert_eq!(x, vec!["c", "d"]);
+    let x: Vec<String> = con.zrevrangebyscore("zset", 4, "(2").unwrap();
+    assert_eq!(x, vec!["d", "c"]);
+
+    let x: Vec<String> = cmd("ZRANGE")
+        .arg("zset")
+        .arg("(5")
+        .arg(2)
+        .arg("BYSCORE")
+        .arg("REV")
+        .arg("LIMIT")
+        .arg(0)
+        .arg(2)
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(x, vec!["d", "c"]);
+    let x: Vec<String> = cmd("ZRANGE")
+        .arg("zset")
+        .arg(0)
+        .arg(1)
+        .arg("REV")
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(x, vec!["e", "d"]);
+
+    let _: u32 = con
+        .zadd_multiple("lex", &[(0, "apple"), (0, "banana"), (0, "cherry")][..])
+        .unwrap();
+    let x: Vec<String> = con.zrangebylex("lex", "[b", "+").unwrap();
+    assert_eq!(x, vec!["banana", "cherry"]);
+    let x: Vec<String> = con.zrangebylex("lex", "-", "(banana").unwrap();
+    assert_eq!(x, vec!["apple"]);
+    let x: Vec<String> = cmd("ZRANGE")
+        .arg("lex")
+        .arg("+")
+        .arg("[apple")
+        .arg("BYLEX")
+        .arg("REV")
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(x, vec!["cherry", "banana", "apple"]);
+
+    let x: RedisResult<Vec<String>> = con.zrangebylex("lex", "b", "+");
+    assert_eq!(x.is_err(), true);
+    let x: RedisResult<Vec<String>> = con.zrangebyscore("zset", "one", 2);
+    assert_eq!(x.is_err(), true);
+
+    let x: Vec<String> = con.zrange("nokey", 0, -1).unwrap();
+    assert_eq!(x.is_empty(), true);
+
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+}
+
+#[test]
+#[serial]
+fn zincrby_zcard_zcount() {
+    let port = 3411;
+    let server = Server::new(InMemoryStorage::new(), port);
+    assert_eq!(server.start(), Some(ServerState::Started));
+    let redis_client = redis::Client::open(format!("redis://127.0.0.1:{}/", port)).unwrap();
+    let mut con = redis_client.get_connection().unwrap();
+
+    let x: String = cmd("ZINCRBY")
+        .arg("zset")
+        .arg(2)
+        .arg("a")
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(x, "2");
+    let x: String = cmd("ZINCRBY")
+        .arg("zset")
+        .arg("0.5")
+        .arg("a")
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(x, "2.5");
+    let x: String = cmd("ZINCRBY")
+        .arg("zset")
+        .arg("1e20")
+        .arg("b")
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(x, "1e+20");
+    let x: String = cmd("ZINCRBY")
+        .arg("zset")
+        .arg("+inf")
+        .arg("c")
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(x, "inf");
+    let x: RedisResult<String> = cmd("ZINCRBY")
+        .arg("zset")
+        .arg("-inf")
+        .arg("c")
+        .query(&mut con);
+    assert_eq!(x.is_err(), true);
+    let x: RedisResult<String> = cmd("ZINCRBY")
+        .arg("zset")
+        .arg("one")
+        .arg("c")
+        .query(&mut con);
+    assert_eq!(x.is_err(), true);
+
+    let x: u32 = con.zcard("zset").unwrap();
+    assert_eq!(x, 3);
+    let x: u32 = con.zcard("nokey").unwrap();
+    assert_eq!(x, 0);
+
+    let x: u32 = con.zcount("zset", "-inf", "+inf").unwrap();
+    assert_eq!(x, 3);
+    let x: u32 = con.zcount("zset", "(2.5", "+inf").unwrap();
+    assert_eq!(x, 2);
+    let x: u32 = con.zcount("zset", 0, 2.5).unwrap();
+    assert_eq!(x, 1);
+    let x: RedisResult<u32> = con.zcount("zset", "a", 1);
+    assert_eq!(x.is_err(), true);
+
+    let _: () = con.set("string", "value").unwrap();
+    let x: RedisResult<u32> = con.zcard("string");
+    assert_eq!(x.is_err(), true);
+
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+}
+
+#[test]
+#[serial]
+fn zrank_zrevrank() {
+    let port = 3412;
+    let server = Server::new(InMemoryStorage::new(), port);
+    assert_eq!(server.start(), Some(ServerState::Started));
+    let redis_client = redis::Client::open(format!("redis://127.0.0.1:{}/", port)).unwrap();
+    let mut con = redis_client.get_connection().unwrap();
+
+    let members = &[(1.5, "a"), (2.0, "b"), (2.0, "c")][..];
+    let _: u32 = con.zadd_multiple("zset", members).unwrap();
+
+    let x: Option<u32> = con.zrank("zset", "a").unwrap();
+    assert_eq!(x, Some(0));
+    let x: Option<u32> = con.zrank("zset", "c").unwrap();
+    assert_eq!(x, Some(2));
+    let x: Option<u32> = con.zrevrank("zset", "c").unwrap();
+    assert_eq!(x, Some(0));
+    let x: Option<u32> = con.zrevrank("zset", "a").unwrap();
+    assert_eq!(x, Some(2));
+    let x: Option<u32> = con.zrank("zset", "z").unwrap();
+    assert_eq!(x, None);
+    let x: Option<u32> = con.zrank("nokey", "a").unwrap();
+    assert_eq!(x, None);
+
+    let x: (u32, String) = cmd("ZRANK")
+        .arg("zset")
+        .arg("a")
+        .arg("WITHSCORE")
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(x, (0, "1.5".to_string()));
+    let x: (u32, String) = cmd("ZREVRANK")
+        .arg("zset")
+        .arg("b")
+        .arg("withscore")
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(x, (1, "2".to_string()));
+    let x: Option<(u32, String)> = cmd("ZRANK")
+        .arg("zset")
+        .arg("z")
+        .arg("WITHSCORE")
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(x, None);
+    let x: RedisResult<u32> = cmd("ZRANK")
+        .arg("zset")
+        .arg("a")
+        .arg("WITHSCORES")
+        .query(&mut con);
+    assert_eq!(x.is_err(), true);
+
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+}
+
+#[test]
+#[serial]
+fn zpop_bzpop() {
+    let port = 3413;
+    let server = Server::new(InMemoryStorage::new(), port);
+    assert_eq!(server.start(), Some(ServerState::Started));
+    let redis_client = redis::Client::open(format!("redis://127.0.0.1:{}/", port)).unwrap();
+    let mut con = redis_client.get_connection().unwrap();
+
+    let members = &[(1, "a"), (2, "b"), (3, "c"), (4, "d")][..];
+    let _: u32 = con.zadd_multiple("zset", members).unwrap();
+
+    let x: Vec<String> = cmd("ZPOPMIN").arg("zset").query(&mut con).unwrap();
+    assert_eq!(x, vec!["a", "1"]);
+    let x: Vec<String> = cmd("ZPOPMAX").arg("zset").arg(2).query(&mut con).unwrap();
+    assert_eq!(x, vec!["d", "4", "c", "3"]);
+    let x: Vec<String> = cmd("ZPOPMIN").arg("zset").arg(10).query(&mut con).unwrap();
+    assert_eq!(x, vec!["b", "2"]);
+    let x: bool = con.exists("zset").unwrap();
+    assert_eq!(x, false);
+    let x: Vec<String> = cmd("ZPOPMIN").arg("zset").query(&mut con).unwrap();
+    assert_eq!(x.is_empty(), true);
+
+    // a member is already there
+    let _: u32 = con.zadd("other", "z", 26).unwrap();
+    let x: (String, String, String) = cmd("BZPOPMIN")
+        .arg("zset")
+        .arg("other")
+        .arg(1)
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(x, ("other".to_string(), "z".to_string(), "26".to_string()));
+
+    // times out
+    let x: Option<(String, String, String)> = cmd("BZPOPMAX")
+        .arg("zset")
+        .arg("0.1")
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(x, None);
+
+    // a member is added by another connection while blocked
+    let writer = std::thread::spawn(move || {
+        let mut con = redis_client.get_connection().unwrap();
+        sleep(Duration::from_millis(200));
+        let _: u32 = con
+            .zadd_multiple("zset", &[(1, "x"), (2, "y")][..])
+            .unwrap();
+    });
+    let x: (String, String, String) = cmd("BZPOPMAX").arg("zset").arg(0).query(&mut con).unwrap();
+    assert_eq!(x, ("zset".to_string(), "y".to_string(), "2".to_string()));
+    writer.join().unwrap();
+
+    let x: RedisResult<Vec<String>> = cmd("BZPOPMIN").arg("zset").arg(-1).query(&mut con);
+    assert_eq!(x.is_err(), true);
+    let x: RedisResult<Vec<String>> = cmd("BZPOPMIN").arg("zset").arg("1e20").query(&mut con);
+    assert!(x
+        .unwrap_err()
+        .to_string()
+        .contains("timeout is out of range"));
+    let x: RedisResult<Vec<String>> = cmd("BZPOPMIN").arg("zset").arg("a").query(&mut con);
+    assert_eq!(x.is_err(), true);
+
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+}
+
+#[test]
+#[serial]
+fn zunion_zinter_zdiff() {
+    let port = 3414;
+    let server = Server::new(InMemoryStorage::new(), port);
+    assert_eq!(server.start(), Some(ServerState::Started));
+    let redis_client = redis::Client::open(format!("redis://127.0.0.1:{}/", port)).unwrap();
+    let mut con = redis_client.get_connection().unwrap();
+
+    let _: u32 = con
+        .zadd_multiple("z1", &[(1, "a"), (2, "b"), (3, "c")][..])
+        .unwrap();
+    let _: u32 = con
+        .zadd_multiple("z2", &[(10, "b"), (20, "d")][..])
+        .unwrap();
+    let _: u32 = con.sadd("set", "a").unwrap();
+
+    let x: Vec<String> = cmd("ZUNION")
+        .arg(2)
+        .arg("z1")
+        .arg("z2")
+        .arg("WITHSCORES")
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(x, vec!["a", "1", "c", "3", "b", "12", "d", "20"]);
+    let x: Vec<String> = cmd("ZINTER")
+        .arg(2)
+        .arg("z1")
+        .arg("z2")
+        .arg("WEIGHTS")
+        .arg(2)
+        .arg("0.5")
+        .arg("AGGREGATE")
+        .arg("MAX")
+        .arg("WITHSCORES")
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(x, vec!["b", "5"]);
+    let x: Vec<String> = cmd("ZDIFF")
+        .arg(2)
+        .arg("z1")
+        .arg("z2")
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(x, vec!["a", "c"]);
+
+    let x: u32 = cmd("ZUNIONSTORE")
+        .arg("out")
+        .arg(3)
+        .arg("z1")
+        .arg("z2")
+        .arg("set")
+        .arg("AGGREGATE")
+        .arg("min")
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(x, 4);
+    let x: Vec<(String, f64)> = con.zrange_withscores("out", 0, -1).unwrap();
+    assert_eq!(
+        x,
+        vec![
+            ("a".to_string(), 1.0),
+            ("b".to_string(), 2.0),
+            ("c".to_string(), 3.0),
+            ("d".to_string(), 20.0)
+        ]
+    );
+
+    let x: u32 = cmd("ZINTERSTORE")
+        .arg("out")
+        .arg(2)
+        .arg("z1")
+        .arg("nokey")
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(x, 0);
+    let x: bool = con.exists("out").unwrap();
+    assert_eq!(x, false);
+
+    let x: u32 = cmd("ZDIFFSTORE")
+        .arg("out")
+        .arg(2)
+        .arg("z2")
+        .arg("z1")
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(x, 1);
+    let x: Option<f64> = con.zscore("out", "d").unwrap();
+    assert_eq!(x, Some(20.0));
+
+    let x: RedisResult<u32> = cmd("ZUNIONSTORE")
+        .arg("out")
+        .arg(2)
+        .arg("z1")
+        .arg("z2")
+        .arg("WEIGHTS")
+        .arg(1)
+        .query(&mut con);
+    assert_eq!(x.is_err(), true);
+    let x: RedisResult<u32> = cmd("ZUNIONSTORE")
+        .arg("out")
+        .arg(1)
+        .arg("z1")
+        .arg("WITHSCORES")
+        .query(&mut con);
+    assert_eq!(x.is_err(), true);
+    let _: () = con.set("string", "value").unwrap();
+    let x: RedisResult<Vec<String>> = cmd("ZINTER").arg(2).arg("z1").arg("string").query(&mut con);
+    assert_eq!(x.is_err(), true);
+
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+}
+
+#[test]
+#[serial]
+fn zscan() {
+    let port = 3415;
+    let server = Server::new(InMemoryStorage::new(), port);
+    assert_eq!(server.start(), Some(ServerState::Started));
+    let redis_client = redis::Client::open(format!("redis://127.0.0.1:{}/", port)).unwrap();
+    let mut con = redis_client.get_connection().unwrap();
+
+    let members = (0..20)
+        .map(|i| (i as f64 + 0.5, format!("m:{}", i)))
+        .collect::<Vec<_>>();
+    let _: u32 = con.zadd_multiple("zset", &members).unwrap();
+    let _: u32 = con.zadd("zset", "other", 100).unwrap();
+
+    let mut cursor = 0;
+    let mut scanned = vec![];
+    loop {
+        let (next_cursor, page): (u64, Vec<(String, f64)>) = cmd("ZSCAN")
+            .arg("zset")
+            .arg(cursor)
+            .arg("MATCH")
+            .arg("m:*")
+            .arg("COUNT")
+            .arg(4)
+            .query(&mut con)
+            .unwrap();
+        scanned.extend(page);
+        cursor = next_cursor;
+        if cursor == 0 {
+            break;
+        }
+    }
+    scanned.sort_by(|a, b| a.1.partial_cmp(&b.1).unwrap());
+    let expected = members
+        .into_iter()
+        .map(|(score, member)| (member, score))
+        .collect::<Vec<_>>();
+    assert_eq!(scanned, expected);
+
+    let (cursor, page): (u64, Vec<String>) =
+        cmd("ZSCAN").arg("nokey").arg(0).query(&mut con).unwrap();
+    assert_eq!(cursor, 0);
+    assert_eq!(page.is_empty(), true);
+
+    let _: u32 = con.sadd("set", "a").unwrap();
+    let x: RedisResult<(u64, Vec<String>)> = cmd("ZSCAN").arg("set").arg(0).query(&mut con);
+    assert_eq!(x.is_err(), true);
+
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+}
+
+#[test]
+#[serial]
+fn zrandmember() {
+    let port = 3416;
+    let server = Server::new(InMemoryStorage::new(), port);
+    assert_eq!(server.start(), Some(ServerState::Started));
+    let redis_client = redis::Client::open(format!("redis://127.0.0.1:{}/", port)).unwrap();
+    let mut con = redis_client.get_connection().unwrap();
+
+    let members = &[(1, "a"), (2, "b"), (3, "c")][..];
+    let _: u32 = con.zadd_multiple("zset", members).unwrap();
+
+    let x: String = cmd("ZRANDMEMBER").arg("zset").query(&mut con).unwrap();
+    assert_eq!(["a", "b", "c"].contains(&x.as_str()), true);
+    let x: Option<String> = cmd("ZRANDMEMBER").arg("nokey").query(&mut con).unwrap();
+    assert_eq!(x, None);
+
+    let mut x: Vec<String> = cmd("ZRANDMEMBER")
+        .arg("zset")
+        .arg(10)
+        .query(&mut con)
+        .unwrap();
+    x.sort();
+    assert_eq!(x, vec!["a", "b", "c"]);
+    let x: Vec<String> = cmd("ZRANDMEMBER")
+        .arg("zset")
+        .arg(-7)
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(x.len(), 7);
+    let x: Vec<(String, f64)> = cmd("ZRANDMEMBER")
+        .arg("zset")
+        .arg(-5)
+        .arg("WITHSCORES")
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(x.len(), 5);
+    for (member, score) in x {
+        let expected: f64 = con.zscore("zset", member).unwrap();
+        assert_eq!(score, expected);
+    }
+    let x: Vec<String> = cmd("ZRANDMEMBER")
+        .arg("nokey")
+        .arg(3)
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(x.is_empty(), true);
+
+    let x: RedisResult<Vec<String>> = cmd("ZRANDMEMBER")
+        .arg("zset")
+        .arg(1)
+        .arg("SCORES")
+        .query(&mut con);
+    assert_eq!(x.is_err(), true);
+
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+}
+
+#[test]
+#[serial]
+fn zremrange() {
+    let port = 3417;
+    let server = Server::new(InMemoryStorage::new(), port);
+    assert_eq!(server.start(), Some(ServerState::Started));
+    let redis_client = redis::Client::open(format!("redis://127.0.0.1:{}/", port)).unwrap();
+    let mut con = redis_client.get_connection().unwrap();
+
+    let members = &[(1, "a"), (2, "b"), (3, "c"), (4, "d"), (5, "e")][..];
+    let _: u32 = con.zadd_multiple("zset", members).unwrap();
+
+    // a sliding window dropping the oldest entries
+    let x: u32 = con.zrembyscore("zset", "-inf", "(2").unwrap();
+    assert_eq!(x, 1);
+    let x: Vec<String> = con.zrange("zset", 0, -1).unwrap();
+    assert_eq!(x, vec!["b", "c", "d", "e"]);
+
+    let x: u32 = con.zremrangebyrank("zset", -2, -1).unwrap();
+    assert_eq!(x, 2);
+    let x: Vec<String> = con.zrange("zset", 0, -1).unwrap();
+    assert_eq!(x, vec!["b", "c"]);
+    let x: u32 = con.zremrangebyrank("zset", 5, 10).unwrap();
+    assert_eq!(x, 0);
+
+    let _: u32 = con
+        .zadd_multiple("lex", &[(0, "a"), (0, "b"), (0, "c"), (0, "d")][..])
+        .unwrap();
+    let x: u32 = cmd("ZREMRANGEBYLEX")
+        .arg("lex")
+        .arg("(a")
+        .arg("[c")
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(x, 2);
+    let x: Vec<String> = con.zrange("lex", 0, -1).unwrap();
+    assert_eq!(x, vec!["a", "d"]);
+
+    // the key is gone once empty
+    let x: u32 = con.zrembyscore("zset", "-inf", "+inf").unwrap();
+    assert_eq!(x, 2);
+    let x: bool = con.exists("zset").unwrap();
+    assert_eq!(x, false);
+    let x: u32 = con.zrembyscore("nokey", "-inf", "+inf").unwrap();
+    assert_eq!(x, 0);
+
+    let x: RedisResult<u32> = cmd("ZREMRANGEBYLEX")
+        .arg("lex")
+        .arg("a")
+        .arg("+")
+        .query(&mut con);
+    assert_eq!(x.is_err(), true);
+
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+}
+
+#[test]
+#[serial]
+fn zadd_flags() {
+    let port = 3418;
+    let server = Server::new(InMemoryStorage::new(), port);
+    assert_eq!(server.start(), Some(ServerState::Started));
+    let redis_client = redis::Client::open(format!("redis://127.0.0.1:{}/", port)).unwrap();
+    let mut con = redis_client.get_connection().unwrap();
+
+    let _: u32 = con
+        .zadd_multiple("zset", &[(1, "a"), (2, "b")][..])
+        .unwrap();
+
+    // NX only adds
+    let x: u32 = cmd("ZADD")
+        .arg("zset")
+        .arg("NX")
+        .arg(10)
+        .arg("a")
+        .arg(3)
+        .arg("c")
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(x, 1);
+    let x: f64 = con.zscore("zset", "a").unwrap();
+    assert_eq!(x, 1.0);
+
+    // XX only updates, CH counts the updates
+    let x: u32 = cmd("ZADD")
+        .arg("zset")
+        .arg("XX")
+        .arg("CH")
+        .arg(10)
+        .arg("a")
+        .arg(4)
+        .arg("d")
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(x, 1);
+    let x: Option<f64> = con.zscore("zset", "d").unwrap();
+    assert_eq!(x, None);
+
+    // GT and LT still add new members
+    let x: u32 = cmd("ZADD")
+        .arg("zset")
+        .arg("GT")
+        .arg("CH")
+        .arg(5)
+        .arg("a")
+        .arg(5)
+        .arg("b")
+        .arg(4)
+        .arg("d")
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(x, 2);
+    let x: Vec<(String, f64)> = con.zrange_withscores("zset", 0, -1).unwrap();
+    assert_eq!(
+        x,
+        vec![
+            ("c".to_string(), 3.0),
+            ("d".to_string(), 4.0),
+            ("b".to_string(), 5.0),
+            ("a".to_string(), 10.0)
+        ]
+    );
+    let x: u32 = cmd("ZADD")
+        .arg("zset")
+        .arg("lt")
+        .arg("ch")
+        .arg(1)
+        .arg("a")
+        .arg(6)
+        .arg("b")
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(x, 1);
+
+    // INCR replies with the new score, or nil when aborted
+    let x: f64 = cmd("ZADD")
+        .arg("zset")
+        .arg("INCR")
+        .arg("1.5")
+        .arg("a")
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(x, 2.5);
+    let x: Option<f64> = cmd("ZADD")
+        .arg("zset")
+        .arg("NX")
+        .arg("INCR")
+        .arg(1)
+        .arg("a")
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(x, None);
+    let x: Option<f64> = cmd("ZADD")
+        .arg("zset")
+        .arg("GT")
+        .arg("INCR")
+        .arg(-1)
+        .arg("a")
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(x, None);
+    let x: Option<f64> = cmd("ZADD")
+        .arg("zset")
+        .arg("XX")
+        .arg("INCR")
+        .arg(1)
+        .arg("z")
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(x, None);
+    let x: bool = con.exists("nokey").unwrap();
+    assert_eq!(x, false);
+
+    let x: RedisResult<u32> = cmd("ZADD")
+        .arg("zset")
+        .arg("NX")
+        .arg("XX")
+        .arg(1)
+        .arg("a")
+        .query(&mut con);
+    assert_eq!(x.is_err(), true);
+    let x: RedisResult<u32> = cmd("ZADD")
+        .arg("zset")
+        .arg("GT")
+        .arg("LT")
+        .arg(1)
+        .arg("a")
+        .query(&mut con);
+    assert_eq!(x.is_err(), true);
+    let x: RedisResult<u32> = cmd("ZADD")
+        .arg("zset")
+        .arg("INCR")
+        .arg(1)
+        .arg("a")
+        .arg(2)
+        .arg("b")
+        .query(&mut con);
+    assert_eq!(x.is_err(), true);
+
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+}
+
+#[test]
+#[serial]
+fn zrangestore() {
+    let port = 3419;
+    let server = Server::new(InMemoryStorage::new(), port);
+    assert_eq!(server.start(), Some(ServerState::Started));
+    let redis_client = redis::Client::open(format!("redis://127.0.0.1:{}/", port)).unwrap();
+    let mut con = redis_client.get_connection().unwrap();
+
+    let members = &[(1, "a"), (2, "b"), (3, "c"), (4, "d")][..];
+    let _: u32 = con.zadd_multiple("src", members).unwrap();
+
+    let x: u32 = cmd("ZRANGESTORE")
+        .arg("dst")
+        .arg("src")
+        .arg(1)
+        .arg(-1)
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(x, 3);
+    let x: Vec<(String, f64)> = con.zrange_withscores("dst", 0, -1).unwrap();
+    assert_eq!(
+        x,
+        vec![
+            ("b".to_string(), 2.0),
+            ("c".to_string(), 3.0),
+            ("d".to_string(), 4.0)
+        ]
+    );
+
+    // the destination is overwritten
+    let x: u32 = cmd("ZRANGESTORE")
+        .arg("dst")
+        .arg("src")
+        .arg("+inf")
+        .arg(2)
+        .arg("BYSCORE")
+        .arg("REV")
+        .arg("LIMIT")
+        .arg(0)
+        .arg(2)
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(x, 2);
+    let x: Vec<String> = con.zrange("dst", 0, -1).unwrap();
+    assert_eq!(x, vec!["c", "d"]);
+
+    // an empty result deletes the destination
+    let _: () = con.set("string", "value").unwrap();
+    let x: u32 = cmd("ZRANGESTORE")
+        .arg("string")
+        .arg("src")
+        .arg(10)
+        .arg(20)
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(x, 0);
+    let x: bool = con.exists("string").unwrap();
+    assert_eq!(x, false);
+
+    let x: RedisResult<u32> = cmd("ZRANGESTORE")
+        .arg("dst")
+        .arg("src")
+        .arg(0)
+        .arg(-1)
+        .arg("WITHSCORES")
+        .query(&mut con);
+    assert_eq!(x.is_err(), true);
+
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+}
+
+#[test]
+#[serial]
+fn zmscore_zlexcount() {
+    let port = 3420;
+    let server = Server::new(InMemoryStorage::new(), port);
+    assert_eq!(server.start(), Some(ServerState::Started));
+    let redis_client = redis::Client::open(format!("redis://127.0.0.1:{}/", port)).unwrap();
+    let mut con = redis_client.get_connection().unwrap();
+
+    let _: u32 = con
+        .zadd_multiple("zset", &[(1.5, "a"), (2.0, "b")][..])
+        .unwrap();
+
+    let x: Vec<Option<String>> = cmd("ZMSCORE")
+        .arg("zset")
+        .arg("a")
+        .arg("z")
+        .arg("b")
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(
+        x,
+        vec![Some("1.5".to_string()), None, Some("2".to_string())]
+    );
+    let x: Vec<Option<String>> = cmd("ZMSCORE")
+        .arg("nokey")
+        .arg("a")
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(x, vec![None]);
+
+    let _: u32 = con
+        .zadd_multiple("lex", &[(0, "a"), (0, "b"), (0, "c"), (0, "d")][..])
+        .unwrap();
+    let x: u32 = con.zlexcount("lex", "-", "+").unwrap();
+    assert_eq!(x, 4);
+    let x: u32 = con.zlexcount("lex", "[b", "(d").unwrap();
+    assert_eq!(x, 2);
+    let x: u32 = con.zlexcount("nokey", "-", "+").unwrap();
+    assert_eq!(x, 0);
+    let x: RedisResult<u32> = con.zlexcount("lex", "b", "+");
+    assert_eq!(x.is_err(), true);
+
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+}
+
+#[test]
+#[serial]
+fn setbit_getbit() {
+    let port = 3421;
+    let server = Server::new(InMemoryStorage::new(), port);
+    assert_eq!(server.start(), Some(ServerState::Started));
+    let redis_client = redis::Client::open(format!("redis://127.0.0.1:{}/", port)).unwrap();
+    let mut con = redis_client.get_connection().unwrap();
+
+    let x: u8 = con.setbit("bitmap", 7, true).unwrap();
+    assert_eq!(x, 0);
+    let x: u8 = con.setbit("bitmap", 7, true).unwrap();
+    assert_eq!(x, 1);
+    let x: String = con.get("bitmap").unwrap();
+    assert_eq!(x.into_bytes(), vec![0b0000_0001]);
+
+    // zero-extended up to the offset
+    let x: u8 = con.setbit("bitmap", 17, true).unwrap();
+    assert_eq!(x, 0);
+    let x: String = con.get("bitmap").unwrap();
+    assert_eq!(x.into_bytes(), vec![0b0000_0001, 0, 0b0100_0000]);
+
+    // not UTF-8
+    let _: u8 = con.setbit("binary", 0, true).unwrap();
+    let x: Vec<u8> = con.get("binary").unwrap();
+    assert_eq!(x, vec![0b1000_0000]);
+    let x: u8 = con.setbit("bitmap", 7, false).unwrap();
+    assert_eq!(x, 1);
+
+    let x: bool = con.getbit("bitmap", 17).unwrap();
+    assert_eq!(x, true);
+    let x: bool = con.getbit("bitmap", 7).unwrap();
+    assert_eq!(x, false);
+    let x: bool = con.getbit("bitmap", 1000).unwrap();
+    assert_eq!(x, false);
+    let x: bool = con.getbit("nokey", 0).unwrap();
+    assert_eq!(x, false);
+
+    // a string value is a bitmap too, 'a' being 0b0110_0001
+    let _: () = con.set("string", "a").unwrap();
+    let x: bool = con.getbit("string", 1).unwrap();
+    assert_eq!(x, true);
+    let _: u8 = con.setbit("string", 6, true).unwrap();
+    let x: String = con.get("string").unwrap();
+    assert_eq!(x, "c");
+
+    let x: RedisResult<u8> = cmd("SETBIT").arg("bitmap").arg(0).arg(2).query(&mut con);
+    assert_eq!(x.is_err(), true);
+    let x: RedisResult<u8> = cmd("SETBIT").arg("bitmap").arg(-1).arg(1).query(&mut con);
+    assert_eq!(x.is_err(), true);
+    let x: RedisResult<u8> = cmd("GETBIT")
+        .arg("bitmap")
+        .arg(4294967296u64)
+        .query(&mut con);
+    assert_eq!(x.is_err(), true);
+    let _: u32 = con.sadd("set", "a").unwrap();
+    let x: RedisResult<u8> = con.setbit("set", 0, true);
+    assert_eq!(x.is_err(), true);
+
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+}
+
+#[test]
+#[serial]
+fn bitcount_bitpos() {
+    let port = 3422;
+    let server = Server::new(InMemoryStorage::new(), port);
+    assert_eq!(server.start(), Some(ServerState::Started));
+    let redis_client = redis::Client::open(format!("redis://127.0.0.1:{}/", port)).unwrap();
+    let mut con = redis_client.get_connection().unwrap();
+
+    let _: () = con.set("key", "foobar").unwrap();
+    let x: u32 = con.bitcount("key").unwrap();
+    assert_eq!(x, 26);
+    let x: u32 = con.bitcount_range("key", 1, 1).unwrap();
+    assert_eq!(x, 6);
+    let x: u32 = cmd("BITCOUNT")
+        .arg("key")
+        .arg(5)
+        .arg(30)
+        .arg("BIT")
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(x, 17);
+    let x: u32 = con.bitcount("nokey").unwrap();
+    assert_eq!(x, 0);
+
+    let _: u8 = con.setbit("bitmap", 10, true).unwrap();
+    let x: i64 = cmd("BITPOS").arg("bitmap").arg(1).query(&mut con).unwrap();
+    assert_eq!(x, 10);
+    let x: i64 = cmd("BITPOS")
+        .arg("bitmap")
+        .arg(1)
+        .arg(0)
+        .arg(0)
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(x, -1);
+    let x: i64 = cmd("BITPOS")
+        .arg("bitmap")
+        .arg(0)
+        .arg(9)
+        .arg(12)
+        .arg("BIT")
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(x, 9);
+    let x: i64 = cmd("BITPOS").arg("nokey").arg(0).query(&mut con).unwrap();
+    assert_eq!(x, 0);
+    let x: i64 = cmd("BITPOS").arg("nokey").arg(1).query(&mut con).unwrap();
+    assert_eq!(x, -1);
+
+    let x: RedisResult<i64> = cmd("BITPOS").arg("bitmap").arg(2).query(&mut con);
+    assert_eq!(x.is_err(), true);
+    let x: RedisResult<u32> = cmd("BITCOUNT").arg("key").arg(0).query(&mut con);
+    assert_eq!(x.is_err(), true);
+    let x: RedisResult<u32> = cmd("BITCOUNT")
+        .arg("key")
+        .arg(0)
+        .arg(1)
+        .arg("WORD")
+        .query(&mut con);
+    assert_eq!(x.is_err(), true);
+
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+}
+
+#[test]
+#[serial]
+fn bitop() {
+    let port = 3423;
+    let server = Server::new(InMemoryStorage::new(), port);
+    assert_eq!(server.start(), Some(ServerState::Started));
+    let redis_client = redis::Client::open(format!("redis://127.0.0.1:{}/", port)).unwrap();
+    let mut con = redis_client.get_connection().unwrap();
+
+    let _: () = con.set("a", "abc").unwrap();
+    let _: () = con.set("b", "a").unwrap();
+
+    let x: u32 = cmd("BITOP")
+        .arg("AND")
+        .arg("dest")
+        .arg(&["a", "b"][..])
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(x, 3);
+    let x: String = con.get("dest").unwrap();
+    assert_eq!(x.into_bytes(), vec![b'a', 0, 0]);
+
+    let x: u32 = cmd("BITOP")
+        .arg("OR")
+        .arg("dest")
+        .arg(&["a", "b", "nokey"][..])
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(x, 3);
+    let x: String = con.get("dest").unwrap();
+    assert_eq!(x, "abc");
+
+    let x: u32 = cmd("BITOP")
+        .arg("XOR")
+        .arg("dest")
+        .arg(&["a", "b"][..])
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(x, 3);
+    let x: String = con.get("dest").unwrap();
+    assert_eq!(x.into_bytes(), vec![0, b'b', b'c']);
+
+    let x: u32 = con.bit_not("dest", "b").unwrap();
+    assert_eq!(x, 1);
+    let x: Vec<u8> = con.get("dest").unwrap();
+    assert_eq!(x, vec![!b'a']);
+
+    // an empty result deletes the destination
+    let x: u32 = cmd("BITOP")
+        .arg("AND")
+        .arg("dest")
+        .arg(&["nokey", "other"][..])
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(x, 0);
+    let x: bool = con.exists("dest").unwrap();
+    assert_eq!(x, false);
+
+    let x: RedisResult<u32> = cmd("BITOP")
+        .arg("NOT")
+        .arg("dest")
+        .arg("a")
+        .arg("b")
+        .query(&mut con);
+    assert_eq!(x.is_err(), true);
+    let x: RedisResult<u32> = cmd("BITOP")
+        .arg("NAND")
+        .arg("dest")
+        .arg("a")
+        .query(&mut con);
+    assert_eq!(x.is_err(), true);
+    let _: u32 = con.sadd("set", "a").unwrap();
+    let x: RedisResult<u32> = cmd("BITOP")
+        .arg("OR")
+        .arg("dest")
+        .arg(&["a", "set"][..])
+        .query(&mut con);
+    assert_eq!(x.is_err(), true);
+
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+}
+
+#[test]
+#[serial]
+fn bitfield() {
+    let port = 3424;
+    let server = Server::new(InMemoryStorage::new(), port);
+    assert_eq!(server.start(), Some(ServerState::Started));
+    let redis_client = redis::Client::open(format!("redis://127.0.0.1:{}/", port)).unwrap();
+    let mut con = redis_client.get_connection().unwrap();
+
+    let x: Vec<i64> = cmd("BITFIELD")
+        .arg("counters")
+        .arg("INCRBY")
+        .arg("u8")
+        .arg("#1")
+        .arg(10)
+        .arg("SET")
+        .arg("i8")
+        .arg(0)
+        .arg(-3)
+        .arg("GET")
+        .arg("u8")
+        .arg(8)
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(x, vec![10, 0, 10]);
+    let x: Vec<u8> = con.get("counters").unwrap();
+    assert_eq!(x, vec![0xfd, 10]);
+
+    let x: Vec<Option<i64>> = cmd("BITFIELD")
+        .arg("counters")
+        .arg("OVERFLOW")
+        .arg("FAIL")
+        .arg("INCRBY")
+        .arg("u8")
+        .arg(8)
+        .arg(250)
+        .arg("OVERFLOW")
+        .arg("SAT")
+        .arg("INCRBY")
+        .arg("u8")
+        .arg(8)
+        .arg(250)
+        .arg("OVERFLOW")
+        .arg("WRAP")
+        .arg("INCRBY")
+        .arg("u8")
+        .arg(8)
+        .arg(2)
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(x, vec![None, Some(255), Some(1)]);
+
+    let x: Vec<i64> = cmd("BITFIELD_RO")
+        .arg("counters")
+        .arg("GET")
+        .arg("i8")
+        .arg(0)
+        .arg("GET")
+        .arg("u4")
+        .arg(0)
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(x, vec![-3, 15]);
+
+    // GET alone does not create the key
+    let x: Vec<i64> = cmd("BITFIELD")
+        .arg("nokey")
+        .arg("GET")
+        .arg("i64")
+        .arg(0)
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(x, vec![0]);
+    let x: bool = con.exists("nokey").unwrap();
+    assert_eq!(x, false);
+
+    let x: RedisResult<Vec<i64>> = cmd("BITFIELD_RO")
+        .arg("counters")
+        .arg("SET")
+        .arg("u8")
+        .arg(0)
+        .arg(1)
+        .query(&mut con);
+    assert_eq!(x.is_err(), true);
+    let x: RedisResult<Vec<i64>> = cmd("BITFIELD")
+        .arg("counters")
+        .arg("GET")
+        .arg("u64")
+        .arg(0)
+        .query(&mut con);
+    assert_eq!(x.is_err(), true);
+    let x: RedisResult<Vec<i64>> = cmd("BITFIELD")
+        .arg("counters")
+        .arg("OVERFLOW")
+        .arg("CLAMP")
+        .query(&mut con);
+    assert_eq!(x.is_err(), true);
+
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+}
+
+#[test]
+#[serial]
+fn hyperloglog() {
+    let port = 3425;
+    let server = Server::new(InMemoryStorage::new(), port);
+    assert_eq!(server.start(), Some(ServerState::Started));
+    let redis_client = redis::Client::open(format!("redis://127.0.0.1:{}/", port)).unwrap();
+    let mut con = redis_client.get_connection().unwrap();
+
+    let x: u32 = con.pfadd("hll1", &["a", "b", "c", "d"]).unwrap();
+    assert_eq!(x, 1);
+    let x: u32 = con.pfadd("hll1", &["a", "b"]).unwrap();
+    assert_eq!(x, 0);
+    let x: u32 = cmd("PFADD").arg("hll2").query(&mut con).unwrap();
+    assert_eq!(x, 1);
+    let x: u32 = con.pfadd("hll2", &["c", "d", "e", "f", "g"]).unwrap();
+    assert_eq!(x, 1);
+
+    let x: u64 = con.pfcount("hll1").unwrap();
+    assert_eq!(x, 4);
+    let x: u64 = con.pfcount(&["hll1", "hll2", "nokey"]).unwrap();
+    assert_eq!(x, 7);
+    let x: u64 = con.pfcount("nokey").unwrap();
+    assert_eq!(x, 0);
+
+    let _: () = cmd("PFMERGE")
+        .arg("hll3")
+        .arg("hll1")
+        .arg("hll2")
+        .query(&mut con)
+        .unwrap();
+    let x: u64 = con.pfcount("hll3").unwrap();
+    assert_eq!(x, 7);
+    let x: Vec<u8> = con.get("hll3").unwrap();
+    assert_eq!(x.starts_with(b"HYLL"), true);
+
+    let _: () = con.set("string", "value").unwrap();
+    let x: RedisResult<u32> = con.pfadd("string", "a");
+    assert_eq!(x.is_err(), true);
+    let x: RedisResult<u64> = con.pfcount(&["hll1", "string"]);
+    assert_eq!(x.is_err(), true);
+    let _: () = con.sadd("set", "a").unwrap();
+    let x: RedisResult<()> = con.pfmerge("set", "hll1");
+    assert_eq!(x.is_err(), true);
+
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+}
+
+#[test]
+#[serial]
+fn geo() {
+    let port = 3426;
+    let server = Server::new(InMemoryStorage::new(), port);
+    assert_eq!(server.start(), Some(ServerState::Started));
+    let redis_client = redis::Client::open(format!("redis://127.0.0.1:{}/", port)).unwrap();
+    let mut con = redis_client.get_connection().unwrap();
+
+    let x: u32 = cmd("GEOADD")
+        .arg("Sicily")
+        .arg(13.361389)
+        .arg(38.115556)
+        .arg("Palermo")
+        .arg(15.087269)
+        .arg(37.502669)
+        .arg("Catania")
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(x, 2);
+    let x: u32 = cmd("GEOADD")
+        .arg("Sicily")
+        .arg("NX")
+        .arg(13.0)
+        .arg(38.0)
+        .arg("Palermo")
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(x, 0);
+
+    let x: Vec<Option<(f64, f64)>> = cmd("GEOPOS")
+        .arg("Sicily")
+        .arg("Palermo")
+        .arg("Agrigento")
+        .query(&mut con)
+        .unwrap();
+    let (longitude, latitude) = x[0].unwrap();
+    assert!((longitude - 13.361389).abs() < 0.00001);
+    assert!((latitude - 38.115556).abs() < 0.00001);
+    assert_eq!(x[1], None);
+
+    let x: String = cmd("GEODIST")
+        .arg("Sicily")
+        .arg("Palermo")
+        .arg("Catania")
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(x, "166274.1516");
+    let x: String = cmd("GEODIST")
+        .arg("Sicily")
+        .arg("Palermo")
+        .arg("Catania")
+        .arg("km")
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(x, "166.2742");
+    let x: String = cmd("GEODIST")
+        .arg("Sicily")
+        .arg("Palermo")
+        .arg("Catania")
+        .arg("mi")
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(x, "103.3182");
+    let x: Option<String> = cmd("GEODIST")
+        .arg("Sicily")
+        .arg("Palermo")
+        .arg("Agrigento")
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(x, None);
+
+    let x: RedisResult<u32> = cmd("GEOADD")
+        .arg("Sicily")
+        .arg(200)
+        .arg(100)
+        .arg("Nowhere")
+        .query(&mut con);
+    assert_eq!(x.is_err(), true);
+    let x: RedisResult<String> = cmd("GEODIST")
+        .arg("Sicily")
+        .arg("Palermo")
+        .arg("Catania")
+        .arg("yd")
+        .query(&mut con);
+    assert_eq!(x.is_err(), true);
+
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+}
+
+#[test]
+#[serial]
+fn geosearch() {
+    let port = 3427;
+    let server = Server::new(InMemoryStorage::new(), port);
+    assert_eq!(server.start(), Some(ServerState::Started));
+    let redis_client = redis::Client::open(format!("redis://127.0.0.1:{}/", port)).unwrap();
+    let mut con = redis_client.get_connection().unwrap();
+
+    let _: u32 = cmd("GEOADD")
+        .arg("Sicily")
+        .arg(&[13.361389, 38.115556])
+        .arg("Palermo")
+        .arg(&[15.087269, 37.502669])
+        .arg("Catania")
+        .arg(&[12.758489, 38.788135])
+        .arg("edge1")
+        .arg(&[17.241510, 38.788135])
+        .arg("edge2")
+        .query(&mut con)
+        .unwrap();
+
+    let x: Vec<String> = cmd("GEOSEARCH")
+        .arg("Sicily")
+        .arg(&["FROMLONLAT", "15", "37", "BYRADIUS", "200", "km", "ASC"])
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(x, vec!["Catania", "Palermo"]);
+
+    let x: Vec<Vec<redis::Value>> = cmd("GEOSEARCH")
+        .arg("Sicily")
+        .arg(&["FROMLONLAT", "15", "37", "BYBOX", "400", "400", "km"])
+        .arg(&["DESC", "WITHCOORD", "WITHDIST"])
+        .query(&mut con)
+        .unwrap();
+    let x = x
+        .iter()
+        .map(|item| {
+            let member: String = redis::from_redis_value(&item[0]).unwrap();
+            let distance: String = redis::from_redis_value(&item[1]).unwrap();
+            let coordinates: Vec<f64> = redis::from_redis_value(&item[2]).unwrap();
+            assert_eq!(coordinates.len(), 2);
+            (member, distance)
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(
+        x,
+        vec![
+            ("edge1".to_string(), "279.7405".to_string()),
+            ("edge2".to_string(), "279.7403".to_string()),
+            ("Palermo".to_string(), "190.4424".to_string()),
+            ("Catania".to_string(), "56.4413".to_string()),
+        ]
+    );
+
+    let x: Vec<Vec<redis::Value>> = cmd("GEOSEARCH")
+        .arg("Sicily")
+        .arg(&["FROMMEMBER", "Palermo", "BYRADIUS", "500", "km"])
+        .arg(&["COUNT", "1", "WITHHASH"])
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(
+        x,
+        vec![vec![
+            redis::Value::Data(b"Palermo".to_vec()),
+            redis::Value::Int(3479099956230698)
+        ]]
+    );
+
+    let x: u32 = cmd("GEOSEARCHSTORE")
+        .arg("nearby")
+        .arg("Sicily")
+        .arg(&["FROMLONLAT", "15", "37", "BYRADIUS", "200", "km"])
+        .arg("STOREDIST")
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(x, 2);
+    let x: f64 = con.zscore("nearby", "Catania").unwrap();
+    assert_eq!((x * 10000.0).round() / 10000.0, 56.4413);
+
+    let x: RedisResult<Vec<String>> = cmd("GEOSEARCH")
+        .arg("Sicily")
+        .arg(&["FROMMEMBER", "Rome", "BYRADIUS", "500", "km"])
+        .query(&mut con);
+    assert_eq!(x.is_err(), true);
+    let x: RedisResult<Vec<String>> = cmd("GEOSEARCH")
+        .arg("Sicily")
+        .arg(&["BYRADIUS", "500", "km"])
+        .query(&mut con);
+    assert_eq!(x.is_err(), true);
+    let x: RedisResult<u32> = cmd("GEOSEARCHSTORE")
+        .arg("nearby")
+        .arg("Sicily")
+        .arg(&["FROMLONLAT", "15", "37", "BYRADIUS", "200", "km"])
+        .arg("WITHDIST")
+        .query(&mut con);
+    assert_eq!(x.is_err(), true);
+
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+}
+
+/// entries of a stream as ids and flattened fields and values
+fn stream_entries(entries: Vec<Vec<redis::Value>>) -> Vec<(String, Vec<String>)> {
+    entries
+        .iter()
+        .map(|entry| {
+            let id: String = redis::from_redis_value(&entry[0]).unwrap();
+            let fields: Vec<String> = redis::from_redis_value(&entry[1]).unwrap();
+            (id, fields)
+        })
+        .collect()
+}
+
+#[test]
+#[serial]
+fn stream() {
+    let port = 3428;
+    let server = Server::new(InMemoryStorage::new(), port);
+    assert_eq!(server.start(), Some(ServerState::Started));
+    let redis_client = redis::Client::open(format!("redis://127.0.0.1:{}/", port)).unwrap();
+    let mut con = redis_client.get_connection().unwrap();
+
+    let x: String = con.xadd("s", "1-1", &[("a", "1")]).unwrap();
+    assert_eq!(x, "1-1");
+    let x: String = con.xadd("s", "1-*", &[("b", "2"), ("c", "3")]).unwrap();
+    assert_eq!(x, "1-2");
+    let x: String = con.xadd("s", "3", &[("d", "4")]).unwrap();
+    assert_eq!(x, "3-0");
+    let x: String = con.xadd("s", "*", &[("e", "5")]).unwrap();
+    assert_ne!(x, "3-1");
+
+    let x: RedisResult<String> = con.xadd("s", "2-0", &[("f", "6")]);
+    assert_eq!(x.is_err(), true);
+    let x: RedisResult<String> = con.xadd("new", "0-0", &[("f", "6")]);
+    assert_eq!(x.is_err(), true);
+    let x: RedisResult<String> = con.xadd("new", "abc", &[("f", "6")]);
+    assert_eq!(x.is_err(), true);
+    let x: Option<String> = cmd("XADD")
+        .arg(&["new", "NOMKSTREAM", "*", "f", "6"])
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(x, None);
+    let x: bool = con.exists("new").unwrap();
+    assert_eq!(x, false);
+
+    let x: u32 = con.xlen("s").unwrap();
+    assert_eq!(x, 4);
+    let x: u32 = con.xlen("new").unwrap();
+    assert_eq!(x, 0);
+
+    let x: Vec<Vec<redis::Value>> = cmd("XRANGE").arg(&["s", "1", "3"]).query(&mut con).unwrap();
+    assert_eq!(
+        stream_entries(x),
+        vec![
+            ("1-1".to_string(), vec!["a".to_string(), "1".to_string()]),
+            (
+                "1-2".to_string(),
+                vec![
+                    "b".to_string(),
+                    "2".to_string(),
+                    "c".to_string(),
+                    "3".to_string()
+                ]
+            ),
+            ("3-0".to_string(), vec!["d".to_string(), "4".to_string()]),
+        ]
+    );
+    let x: Vec<Vec<redis::Value>> = cmd("XRANGE")
+        .arg(&["s", "(1-1", "+", "COUNT", "1"])
+        .query(&mut con)
+        .unwrap();
+    let x = stream_entries(x);
+    assert_eq!(x[0].0, "1-2");
+    assert_eq!(x.len(), 1);
+
+    let x: Vec<Vec<redis::Value>> = cmd("XREVRANGE")
+        .arg(&["s", "(3-0", "-"])
+        .query(&mut con)
+        .unwrap();
+    let ids = stream_entries(x)
+        .into_iter()
+        .map(|(id, _)| id)
+        .collect::<Vec<_>>();
+    assert_eq!(ids, vec!["1-2", "1-1"]);
+
+    let _: () = con.set("string", "value").unwrap();
+    let x: RedisResult<String> = con.xadd("string", "*", &[("a", "1")]);
+    assert_eq!(x.is_err(), true);
+
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+}
+
+/// streams replied by XREAD as keys and entries
+fn read_streams(streams: Vec<Vec<redis::Value>>) -> Vec<(String, Vec<(String, Vec<String>)>)> {
+    streams
+        .iter()
+        .map(|stream| {
+            let key: String = redis::from_redis_value(&stream[0]).unwrap();
+            let entries: Vec<Vec<redis::Value>> = redis::from_redis_value(&stream[1]).unwrap();
+            (key, stream_entries(entries))
+        })
+        .collect()
+}
+
+#[test]
+#[serial]
+fn xread() {
+    let port = 3429;
+    let server = Server::new(InMemoryStorage::new(), port);
+    assert_eq!(server.start(), Some(ServerState::Started));
+    let redis_client = redis::Client::open(format!("redis://127.0.0.1:{}/", port)).unwrap();
+    let mut con = redis_client.get_connection().unwrap();
+
+    let _: String = con.xadd("s1", "1-1", &[("a", "1")]).unwrap();
+    let _: String = con.xadd("s1", "2-1", &[("b", "2")]).unwrap();
+    let _: String = con.xadd("s2", "5-1", &[("c", "3")]).unwrap();
+
+    let x: Vec<Vec<redis::Value>> = cmd("XREAD")
+        .arg(&[
+            "COUNT", "1", "STREAMS", "s1", "s2", "nokey", "0", "5-1", "0",
+        ])
+        .query(&mut con)
+        .unwrap();
+    let x = read_streams(x);
+    assert_eq!(x.len(), 1);
+    assert_eq!(x[0].0, "s1");
+    assert_eq!(
+        x[0].1,
+        vec![("1-1".to_string(), vec!["a".to_string(), "1".to_string()])]
+    );
+
+    let x: Option<Vec<redis::Value>> = cmd("XREAD")
+        .arg(&["STREAMS", "s1", "$"])
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(x, None);
+    let x: Option<Vec<redis::Value>> = cmd("XREAD")
+        .arg(&["BLOCK", "100", "STREAMS", "s1", "2-1"])
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(x, None);
+
+    // an entry is added by another connection while blocked
+    let writer = std::thread::spawn(move || {
+        let mut con = redis_client.get_connection().unwrap();
+        sleep(Duration::from_millis(200));
+        let _: String = con.xadd("s2", "6-1", &[("d", "4")]).unwrap();
+    });
+    let x: Vec<Vec<redis::Value>> = cmd("XREAD")
+        .arg(&["BLOCK", "0", "STREAMS", "s1", "s2", "$", "$"])
+        .query(&mut con)
+        .unwrap();
+    let x = read_streams(x);
+    assert_eq!(x[0].0, "s2");
+    assert_eq!(
+        x[0].1,
+        vec![("6-1".to_string(), vec!["d".to_string(), "4".to_string()])]
+    );
+    writer.join().unwrap();
+
+    let x: RedisResult<Vec<redis::Value>> = cmd("XREAD")
+        .arg(&["STREAMS", "s1", "s2", "0"])
+        .query(&mut con);
+    assert_eq!(x.is_err(), true);
+    let x: RedisResult<Vec<redis::Value>> = cmd("XREAD")
+        .arg(&["BLOCK", "-1", "STREAMS", "s1", "0"])
+        .query(&mut con);
+    assert_eq!(x.is_err(), true);
+
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+}
+
+#[test]
+#[serial]
+fn stream_consumer_groups() {
+    let port = 3430;
+    let server = Server::new(InMemoryStorage::new(), port);
+    assert_eq!(server.start(), Some(ServerState::Started));
+    let redis_client = redis::Client::open(format!("redis://127.0.0.1:{}/", port)).unwrap();
+    let mut con = redis_client.get_connection().unwrap();
+
+    let x: RedisResult<String> = cmd("XGROUP")
+        .arg(&["CREATE", "s", "workers", "$"])
+        .query(&mut con);
+    assert_eq!(x.is_err(), true);
+    let x: String = cmd("XGROUP")
+        .arg(&["CREATE", "s", "workers", "$", "MKSTREAM"])
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(x, "OK");
+    let x: RedisResult<String> = cmd("XGROUP")
+        .arg(&["CREATE", "s", "workers", "0"])
+        .query(&mut con);
+    assert_eq!(x.is_err(), true);
+
+    let _: String = con.xadd("s", "1-0", &[("job", "a")]).unwrap();
+    let _: String = con.xadd("s", "2-0", &[("job", "b")]).unwrap();
+
+    let x: Vec<Vec<redis::Value>> = cmd("XREADGROUP")
+        .arg(&["GROUP", "workers", "w1", "COUNT", "1"])
+        .arg(&["STREAMS", "s", ">"])
+        .query(&mut con)
+        .unwrap();
+    let x = read_streams(x);
+    assert_eq!(
+        x,
+        vec![(
+            "s".to_string(),
+            vec![("1-0".to_string(), vec!["job".to_string(), "a".to_string()])]
+        )]
+    );
+    let x: Vec<Vec<redis::Value>> = cmd("XREADGROUP")
+        .arg(&["GROUP", "workers", "w2", "STREAMS", "s", ">"])
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(read_streams(x)[0].1[0].0, "2-0");
+    let x: Option<Vec<redis::Value>> = cmd("XREADGROUP")
+        .arg(&["GROUP", "workers", "w2", "BLOCK", "100"])
+        .arg(&["STREAMS", "s", ">"])
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(x, None);
+
+    // the pending entries of w1
+    let x: Vec<Vec<redis::Value>> = cmd("XREADGROUP")
+        .arg(&["GROUP", "workers", "w1", "STREAMS", "s", "0"])
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(read_streams(x)[0].1.len(), 1);
+
+    let x: u32 = cmd("XACK")
+        .arg(&["s", "workers", "1-0", "2-0", "3-0"])
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(x, 2);
+    let x: Vec<Vec<redis::Value>> = cmd("XREADGROUP")
+        .arg(&["GROUP", "workers", "w1", "STREAMS", "s", "0"])
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(read_streams(x)[0].1.len(), 0);
+
+    let x: String = cmd("XGROUP")
+        .arg(&["SETID", "s", "workers", "0"])
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(x, "OK");
+    let x: Vec<Vec<redis::Value>> = cmd("XREADGROUP")
+        .arg(&["GROUP", "workers", "w3", "NOACK", "STREAMS", "s", ">"])
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(read_streams(x)[0].1.len(), 2);
+    let x: u32 = cmd("XGROUP")
+        .arg(&["DELCONSUMER", "s", "workers", "w3"])
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(x, 0);
+    let x: u32 = cmd("XGROUP")
+        .arg(&["CREATECONSUMER", "s", "workers", "w4"])
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(x, 1);
+
+    let x: RedisResult<Vec<redis::Value>> = cmd("XREADGROUP")
+        .arg(&["GROUP", "nogroup", "w1", "STREAMS", "s", ">"])
+        .query(&mut con);
+    assert_eq!(x.is_err(), true);
+    let x: u32 = cmd("XGROUP")
+        .arg(&["DESTROY", "s", "workers"])
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(x, 1);
+    let x: RedisResult<u32> = cmd("XGROUP")
+        .arg(&["CREATECONSUMER", "s", "workers", "w4"])
+        .query(&mut con);
+    assert_eq!(x.is_err(), true);
+
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+}
+
+/// pending entries as ids, consumers, idle times and delivery counts
+fn pending_entries(entries: Vec<Vec<redis::Value>>) -> Vec<(String, String, u64, u32)> {
+    entries
+        .iter()
+        .map(|entry| {
+            (
+                redis::from_redis_value(&entry[0]).unwrap(),
+                redis::from_redis_value(&entry[1]).unwrap(),
+                redis::from_redis_value(&entry[2]).unwrap(),
+                redis::from_redis_value(&entry[3]).unwrap(),
+            )
+        })
+        .collect()
+}
+
+#[test]
+#[serial]
+fn stream_pending_entries() {
+    let port = 3431;
+    let server = Server::new(InMemoryStorage::new(), port);
+    assert_eq!(server.start(), Some(ServerState::Started));
+    let redis_client = redis::Client::open(format!("redis://127.0.0.1:{}/", port)).unwrap();
+    let mut con = redis_client.get_connection().unwrap();
+
+    let x: RedisResult<redis::Value> = cmd("XPENDING").arg(&["s", "workers"]).query(&mut con);
+    assert_eq!(x.is_err(), true);
+
+    let _: String = con.xadd("s", "1-0", &[("job", "a")]).unwrap();
+    let _: String = con.xadd("s", "2-0", &[("job", "b")]).unwrap();
+    let _: String = con.xadd("s", "3-0", &[("job", "c")]).unwrap();
+    let _: String = cmd("XGROUP")
+        .arg(&["CREATE", "s", "workers", "0"])
+        .query(&mut con)
+        .unwrap();
+
+    let x: (
+        u32,
+        Option<String>,
+        Option<String>,
+        Option<Vec<(String, String)>>,
+    ) = cmd("XPENDING")
+        .arg(&["s", "workers"])
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(x, (0, None, None, None));
+
+    let _: redis::Value = cmd("XREADGROUP")
+        .arg(&["GROUP", "workers", "w1", "STREAMS", "s", ">"])
+        .query(&mut con)
+        .unwrap();
+
+    let x: (u32, String, String, Vec<Vec<String>>) = cmd("XPENDING")
+        .arg(&["s", "workers"])
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(
+        x,
+        (
+            3,
+            "1-0".to_string(),
+            "3-0".to_string(),
+            vec![vec!["w1".to_string(), "3".to_string()]]
+        )
+    );
+    let x: Vec<Vec<redis::Value>> = cmd("XPENDING")
+        .arg(&["s", "workers", "-", "+", "2", "w1"])
+        .query(&mut con)
+        .unwrap();
+    let x = pending_entries(x);
+    assert_eq!(x.len(), 2);
+    assert_eq!((x[0].0.as_str(), x[0].1.as_str(), x[0].3), ("1-0", "w1", 1));
+    let x: Vec<redis::Value> = cmd("XPENDING")
+        .arg(&["s", "workers", "IDLE", "60000", "-", "+", "10"])
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(x.len(), 0);
+
+    // none has been idle for a minute
+    let x: Vec<redis::Value> = cmd("XCLAIM")
+        .arg(&["s", "workers", "w2", "60000", "1-0"])
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(x.len(), 0);
+    let x: Vec<Vec<redis::Value>> = cmd("XCLAIM")
+        .arg(&["s", "workers", "w2", "0", "1-0", "RETRYCOUNT", "5"])
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(
+        stream_entries(x),
+        vec![("1-0".to_string(), vec!["job".to_string(), "a".to_string()])]
+    );
+    let x: Vec<String> = cmd("XCLAIM")
+        .arg(&["s", "workers", "w2", "0", "2-0", "JUSTID"])
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(x, vec!["2-0".to_string()]);
+    let x: Vec<Vec<redis::Value>> = cmd("XPENDING")
+        .arg(&["s", "workers", "-", "+", "10", "w2"])
+        .query(&mut con)
+        .unwrap();
+    let x = pending_entries(x);
+    assert_eq!(
+        x.iter().map(|entry| entry.3).collect::<Vec<_>>(),
+        vec![5, 1]
+    );
+
+    let x: (String, Vec<String>, Vec<String>) = cmd("XAUTOCLAIM")
+        .arg(&["s", "workers", "w3", "0", "0", "COUNT", "2", "JUSTID"])
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(
+        x,
+        (
+            "3-0".to_string(),
+            vec!["1-0".to_string(), "2-0".to_string()],
+            vec![]
+        )
+    );
+    let x: (String, Vec<Vec<redis::Value>>, Vec<String>) = cmd("XAUTOCLAIM")
+        .arg(&["s", "workers", "w3", "0", "3-0"])
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(x.0, "0-0");
+    assert_eq!(stream_entries(x.1)[0].0, "3-0");
+
+    let x: RedisResult<redis::Value> = cmd("XAUTOCLAIM")
+        .arg(&["s", "workers", "w3", "0", "0", "COUNT", "0"])
+        .query(&mut con);
+    assert_eq!(x.is_err(), true);
+    let x: RedisResult<redis::Value> = cmd("XCLAIM")
+        .arg(&["s", "nogroup", "w3", "0", "1-0"])
+        .query(&mut con);
+    assert_eq!(x.is_err(), true);
+
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+}
+
+#[test]
+#[serial]
+fn stream_trim() {
+    let port = 3432;
+    let server = Server::new(InMemoryStorage::new(), port);
+    assert_eq!(server.start(), Some(ServerState::Started));
+    let redis_client = redis::Client::open(format!("redis://127.0.0.1:{}/", port)).unwrap();
+    let mut con = redis_client.get_connection().unwrap();
+
+    for ms in 1..=5 {
+        let _: String = con.xadd("s", format!("{}-0", ms), &[("f", "v")]).unwrap();
+    }
+
+    let x: String = cmd("XADD")
+        .arg(&["s", "MAXLEN", "=", "4", "6-0", "f", "v"])
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(x, "6-0");
+    let x: u32 = con.xlen("s").unwrap();
+    assert_eq!(x, 4);
+
+    let x: u32 = cmd("XTRIM")
+        .arg(&["s", "MAXLEN", "~", "0", "LIMIT", "1"])
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(x, 1);
+    let x: u32 = cmd("XTRIM")
+        .arg(&["s", "MINID", "5"])
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(x, 1);
+    let x: RedisResult<u32> = cmd("XTRIM")
+        .arg(&["s", "MAXLEN", "0", "LIMIT", "1"])
+        .query(&mut con);
+    assert_eq!(x.is_err(), true);
+    let x: RedisResult<u32> = cmd("XTRIM").arg(&["s", "MAXLEN"]).query(&mut con);
+    assert_eq!(x.is_err(), true);
+
+    let x: u32 = cmd("XDEL")
+        .arg(&["s", "5-0", "1-0", "5-0"])
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(x, 1);
+    let x: Vec<Vec<redis::Value>> = cmd("XRANGE").arg(&["s", "-", "+"]).query(&mut con).unwrap();
+    assert_eq!(
+        stream_entries(x),
+        vec![("6-0".to_string(), vec!["f".to_string(), "v".to_string()])]
+    );
+
+    // deleted ids cannot be reused
+    let x: RedisResult<String> = con.xadd("s", "5-1", &[("f", "v")]);
+    assert_eq!(x.is_err(), true);
+    let x: u32 = cmd("XDEL")
+        .arg(&["missing", "1-0"])
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(x, 0);
+
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+}
+
+#[test]
+#[serial]
+fn stream_info() {
+    let port = 3433;
+    let server = Server::new(InMemoryStorage::new(), port);
+    assert_eq!(server.start(), Some(ServerState::Started));
+    let redis_client = redis::Client::open(format!("redis://127.0.0.1:{}/", port)).unwrap();
+    let mut con = redis_client.get_connection().unwrap();
+
+    let x: RedisResult<redis::Value> = cmd("XINFO").arg(&["STREAM", "s"]).query(&mut con);
+    assert_eq!(x.is_err(), true);
+
+    for ms in 1..=3 {
+        let _: String = con.xadd("s", format!("{}-0", ms), &[("f", "v")]).unwrap();
+    }
+    let _: u32 = cmd("XDEL").arg(&["s", "2-0"]).query(&mut con).unwrap();
+    let _: String = cmd("XGROUP")
+        .arg(&["CREATE", "s", "workers", "0"])
+        .query(&mut con)
+        .unwrap();
+    let _: redis::Value = cmd("XREADGROUP")
+        .arg(&["GROUP", "workers", "w1", "COUNT", "1", "STREAMS", "s", ">"])
+        .query(&mut con)
+        .unwrap();
+
+    let x: Vec<redis::Value> = cmd("XINFO").arg(&["STREAM", "s"]).query(&mut con).unwrap();
+    let data = |value: &str| redis::Value::Data(value.as_bytes().to_vec());
+    assert_eq!(
+        x[..10].to_vec(),
+        vec![
+            data("length"),
+            redis::Value::Int(2),
+            data("last-generated-id"),
+            data("3-0"),
+            data("max-deleted-entry-id"),
+            data("2-0"),
+            data("entries-added"),
+            redis::Value::Int(3),
+            data("groups"),
+            redis::Value::Int(1),
+        ]
+    );
+    let first_entry: Vec<redis::Value> = redis::from_redis_value(&x[11]).unwrap();
+    let first_entry: String = redis::from_redis_value(&first_entry[0]).unwrap();
+    assert_eq!(first_entry, "1-0");
+
+    let x: Vec<Vec<redis::Value>> = cmd("XINFO").arg(&["GROUPS", "s"]).query(&mut con).unwrap();
+    assert_eq!(
+        x,
+        vec![vec![
+            data("name"),
+            data("workers"),
+            data("consumers"),
+            redis::Value::Int(1),
+            data("pending"),
+            redis::Value::Int(1),
+            data("last-delivered-id"),
+            data("1-0"),
+            data("lag"),
+            redis::Value::Int(1),
+        ]]
+    );
+
+    let x: Vec<Vec<redis::Value>> = cmd("XINFO")
+        .arg(&["CONSUMERS", "s", "workers"])
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(x.len(), 1);
+    let name: String = redis::from_redis_value(&x[0][1]).unwrap();
+    let pending: u32 = redis::from_redis_value(&x[0][3]).unwrap();
+    assert_eq!((name.as_str(), pending), ("w1", 1));
+
+    let x: Vec<redis::Value> = cmd("XINFO")
+        .arg(&["STREAM", "s", "FULL", "COUNT", "1"])
+        .query(&mut con)
+        .unwrap();
+    let entries: Vec<Vec<redis::Value>> = redis::from_redis_value(&x[9]).unwrap();
+    assert_eq!(stream_entries(entries).len(), 1);
+
+    let x: RedisResult<redis::Value> = cmd("XINFO")
+        .arg(&["CONSUMERS", "s", "nogroup"])
+        .query(&mut con);
+    assert_eq!(x.is_err(), true);
+    let _: () = con.set("string", "v").unwrap();
+    let x: RedisResult<redis::Value> = cmd("XINFO").arg(&["GROUPS", "string"]).query(&mut con);
+    assert_eq!(x.is_err(), true);
+
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+}
+
+#[test]
+#[serial]
+fn sort() {
+    let port = 3434;
+    let server = Server::new(InMemoryStorage::new(), port);
+    assert_eq!(server.start(), Some(ServerState::Started));
+    let redis_client = redis::Client::open(format!("redis://127.0.0.1:{}/", port)).unwrap();
+    let mut con = redis_client.get_connection().unwrap();
+
+    let _: u32 = con.sadd("ids", &["3", "1", "2"]).unwrap();
+    let x: Vec<String> = cmd("SORT").arg("ids").query(&mut con).unwrap();
+    assert_eq!(x, vec!["1", "2", "3"]);
+    let x: Vec<String> = cmd("SORT_RO")
+        .arg(&["ids", "DESC", "LIMIT", "0", "2"])
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(x, vec!["3", "2"]);
+
+    let _: () = con.set("weight_1", "30").unwrap();
+    let _: () = con.set("weight_2", "10").unwrap();
+    let _: () = con.set("weight_3", "20").unwrap();
+    let _: u32 = con.hset("user_1", "name", "alice").unwrap();
+    let _: u32 = con.hset("user_2", "name", "bob").unwrap();
+    let x: Vec<Option<String>> = cmd("SORT")
+        .arg(&["ids", "BY", "weight_*", "GET", "#", "GET", "user_*->name"])
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(
+        x,
+        vec![
+            Some("2".to_string()),
+            Some("bob".to_string()),
+            Some("3".to_string()),
+            None,
+            Some("1".to_string()),
+            Some("alice".to_string())
+        ]
+    );
+
+    let _: u32 = con
+        .zadd_multiple("names", &[(1, "carol"), (2, "alice"), (3, "bob")])
+        .unwrap();
+    let x: Vec<String> = cmd("SORT")
+        .arg(&["names", "ALPHA"])
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(x, vec!["alice", "bob", "carol"]);
+    // a pattern with no `*` keeps the order of the sorted set
+    let x: Vec<String> = cmd("SORT")
+        .arg(&["names", "BY", "nosort"])
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(x, vec!["carol", "alice", "bob"]);
+    let x: RedisResult<Vec<String>> = cmd("SORT").arg("names").query(&mut con);
+    assert_eq!(x.is_err(), true);
+
+    let x: Vec<String> = cmd("SORT").arg("missing").query(&mut con).unwrap();
+    assert_eq!(x.len(), 0);
+    let x: RedisResult<Vec<String>> = cmd("SORT").arg("weight_1").query(&mut con);
+    assert_eq!(x.is_err(), true);
+    let x: RedisResult<Vec<String>> = cmd("SORT_RO").arg(&["ids", "STORE", "dst"]).query(&mut con);
+    assert_eq!(x.is_err(), true);
+
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+}
+
+#[test]
+#[serial]
+fn lcs() {
+    let port = 3435;
+    let server = Server::new(InMemoryStorage::new(), port);
+    assert_eq!(server.start(), Some(ServerState::Started));
+    let redis_client = redis::Client::open(format!("redis://127.0.0.1:{}/", port)).unwrap();
+    let mut con = redis_client.get_connection().unwrap();
+
+    let _: () = con.set("key1", "ohmytext").unwrap();
+    let _: () = con.set("key2", "mynewtext").unwrap();
+
+    let x: String = cmd("LCS").arg(&["key1", "key2"]).query(&mut con).unwrap();
+    assert_eq!(x, "mytext");
+    let x: u32 = cmd("LCS")
+        .arg(&["key1", "key2", "LEN"])
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(x, 6);
+    let x: u32 = cmd("LCS")
+        .arg(&["key1", "missing", "LEN"])
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(x, 0);
+
+    let x: redis::Value = cmd("LCS")
+        .arg(&["key1", "key2", "IDX", "MINMATCHLEN", "4", "WITHMATCHLEN"])
+        .query(&mut con)
+        .unwrap();
+    let int = |i| redis::Value::Int(i);
+    assert_eq!(
+        x,
+        redis::Value::Bulk(vec![
+            redis::Value::Data(b"matches".to_vec()),
+            redis::Value::Bulk(vec![redis::Value::Bulk(vec![
+                redis::Value::Bulk(vec![int(4), int(7)]),
+                redis::Value::Bulk(vec![int(5), int(8)]),
+                int(4)
+            ])]),
+            redis::Value::Data(b"len".to_vec()),
+            int(6)
+        ])
+    );
+
+    let x: RedisResult<redis::Value> = cmd("LCS")
+        .arg(&["key1", "key2", "LEN", "IDX"])
+        .query(&mut con);
+    assert_eq!(x.is_err(), true);
+    let _: u32 = con.sadd("set", "a").unwrap();
+    let x: RedisResult<String> = cmd("LCS").arg(&["key1", "set"]).query(&mut con);
+    assert_eq!(x.is_err(), true);
+
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+}
+
+#[test]
+#[serial]
+fn publish_subscribe() {
+    let port = 3436;
+    let server = Server::new(InMemoryStorage::new(), port);
+    assert_eq!(server.start(), Some(ServerState::Started));
+    let redis_client = redis::Client::open(format!("redis://127.0.0.1:{}/", port)).unwrap();
+    let mut con = redis_client.get_connection().unwrap();
+
+    let x: u32 = con.publish("news", "nobody").unwrap();
+    assert_eq!(x, 0);
+
+    let mut subscriber = redis_client.get_connection().unwrap();
+    let mut pubsub = subscriber.as_pubsub();
+    pubsub.subscribe(&["news", "sport"]).unwrap();
+    pubsub
+        .set_read_timeout(Some(Duration::from_secs(5)))
+        .unwrap();
+
+    let x: u32 = con.publish("news", "hello").unwrap();
+    assert_eq!(x, 1);
+    let x: u32 = con.publish("weather", "sunny").unwrap();
+    assert_eq!(x, 0);
+    let x: u32 = con.publish("sport", "goal").unwrap();
+    assert_eq!(x, 1);
+
+    let message = pubsub.get_message().unwrap();
+    assert_eq!(message.get_channel_name(), "news");
+    assert_eq!(message.get_payload::<String>().unwrap(), "hello");
+    let message = pubsub.get_message().unwrap();
+    assert_eq!(message.get_channel_name(), "sport");
+    assert_eq!(message.get_payload::<String>().unwrap(), "goal");
+
+    pubsub.unsubscribe("news").unwrap();
+    let x: u32 = con.publish("news", "hello").unwrap();
+    assert_eq!(x, 0);
+
+    // a subscribed connection only runs the pub/sub commands
+    let mut other = redis_client.get_connection().unwrap();
+    let x: (String, String, u32) = cmd("SUBSCRIBE").arg("news").query(&mut other).unwrap();
+    assert_eq!(x, ("subscribe".to_string(), "news".to_string(), 1));
+    let x: RedisResult<Option<String>> = other.get("key");
+    assert_eq!(x.is_err(), true);
+    let x: (String, String) = cmd("PING").query(&mut other).unwrap();
+    assert_eq!(x, ("pong".to_string(), "".to_string()));
+    let x: (String, String) = cmd("PING").arg("token").query(&mut other).unwrap();
+    assert_eq!(x, ("pong".to_string(), "token".to_string()));
+    let x: (String, String, u32) = cmd("UNSUBSCRIBE").query(&mut other).unwrap();
+    assert_eq!(x, ("unsubscribe".to_string(), "news".to_string(), 0));
+    let x: Option<String> = other.get("key").unwrap();
+    assert_eq!(x, None);
+
+    // closing a connection drops its subscriptions
+    drop(pubsub);
+    drop(subscriber);
+    sleep(Duration::from_millis(100));
+    let x: u32 = con.publish("sport", "goal").unwrap();
+    assert_eq!(x, 0);
+
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+}
+
+#[test]
+#[serial]
+fn pattern_subscribe() {
+    let port = 3437;
+    let server = Server::new(InMemoryStorage::new(), port);
+    assert_eq!(server.start(), Some(ServerState::Started));
+    let redis_client = redis::Client::open(format!("redis://127.0.0.1:{}/", port)).unwrap();
+    let mut con = redis_client.get_connection().unwrap();
+
+    let mut subscriber = redis_client.get_connection().unwrap();
+    let mut pubsub = subscriber.as_pubsub();
+    pubsub.psubscribe("news.*").unwrap();
+    pubsub.subscribe("news.tech").unwrap();
+    pubsub
+        .set_read_timeout(Some(Duration::from_secs(5)))
+        .unwrap();
+
+    // once for the channel and once for the pattern
+    let x: u32 = con.publish("news.tech", "rust").unwrap();
+    assert_eq!(x, 2);
+    let x: u32 = con.publish("news.art", "paint").unwrap();
+    assert_eq!(x, 1);
+    let x: u32 = con.publish("sport", "goal").unwrap();
+    assert_eq!(x, 0);
+
+    let message = pubsub.get_message().unwrap();
+    assert_eq!(message.from_pattern(), false);
+    assert_eq!(message.get_payload::<String>().unwrap(), "rust");
+    let message = pubsub.get_message().unwrap();
+    assert_eq!(message.from_pattern(), true);
+    assert_eq!(message.get_pattern::<String>().unwrap(), "news.*");
+    assert_eq!(message.get_channel_name(), "news.tech");
+    let message = pubsub.get_message().unwrap();
+    assert_eq!(message.get_channel_name(), "news.art");
+    assert_eq!(message.get_payload::<String>().unwrap(), "paint");
+
+    pubsub.punsubscribe("news.*").unwrap();
+    let x: u32 = con.publish("news.art", "paint").unwrap();
+    assert_eq!(x, 0);
+
+    let mut other = redis_client.get_connection().unwrap();
+    let x: (String, Option<String>, u32) = cmd("PUNSUBSCRIBE").query(&mut other).unwrap();
+    assert_eq!(x, ("punsubscribe".to_string(), None, 0));
+
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+}
+
+#[test]
+#[serial]
+fn pubsub_introspection() {
+    let port = 3438;
+    let server = Server::new(InMemoryStorage::new(), port);
+    assert_eq!(server.start(), Some(ServerState::Started));
+    let redis_client = redis::Client::open(format!("redis://127.0.0.1:{}/", port)).unwrap();
+    let mut con = redis_client.get_connection().unwrap();
+
+    // each channel and pattern subscribed to is confirmed by a reply of
+    // its own, all of them read before the server is asked about them
+    let mut subscriber = redis_client.get_connection().unwrap();
+    let subscriptions = [
+        ("SUBSCRIBE", &["news.tech", "news.art", "sport"][..]),
+        ("PSUBSCRIBE", &["news.*", "sport.*"][..]),
+    ];
+    let mut confirmed = vec![];
+    for (command, names) in &subscriptions {
+        let command = cmd(command).arg(*names).get_packed_command();
+        subscriber.send_packed_command(&command).unwrap();
+        for _ in 0..names.len() {
+            let x: (String, String, u32) =
+                redis::from_redis_value(&subscriber.recv_response().unwrap()).unwrap();
+            confirmed.push(x.2);
+        }
+    }
+    assert_eq!(confirmed, vec![1, 2, 3, 4, 5]);
+
+    let mut x: Vec<String> = cmd("PUBSUB").arg("CHANNELS").query(&mut con).unwrap();
+    x.sort();
+    assert_eq!(x, vec!["news.art", "news.tech", "sport"]);
+    let mut x: Vec<String> = cmd("PUBSUB")
+        .arg(&["CHANNELS", "news.*"])
+        .query(&mut con)
+        .unwrap();
+    x.sort();
+    assert_eq!(x, vec!["news.art", "news.tech"]);
+
+    let x: (String, u32, String, u32) = cmd("PUBSUB")
+        .arg(&["NUMSUB", "sport", "weather"])
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(x, ("sport".to_string(), 1, "weather".to_string(), 0));
+    let x: Vec<String> = cmd("PUBSUB").arg("NUMSUB").query(&mut con).unwrap();
+    assert_eq!(x.len(), 0);
+    let x: u32 = cmd("PUBSUB").arg("NUMPAT").query(&mut con).unwrap();
+    assert_eq!(x, 2);
+    let x: Vec<String> = cmd("PUBSUB").arg("SHARDCHANNELS").query(&mut con).unwrap();
+    assert_eq!(x.len(), 0);
+
+    let x: RedisResult<u32> = cmd("PUBSUB").arg(&["NUMPAT", "x"]).query(&mut con);
+    assert_eq!(x.is_err(), true);
+
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+}
+
+#[test]
+#[serial]
+fn keyspace_notifications() {
+    let port = 3439;
+    let server = Server::new(InMemoryStorage::new(), port);
+    assert_eq!(server.start(), Some(ServerState::Started));
+    let redis_client = redis::Client::open(format!("redis://127.0.0.1:{}/", port)).unwrap();
+    let mut con = redis_client.get_connection().unwrap();
+
+    let x: Vec<String> = cmd("CONFIG")
+        .arg("GET")
+        .arg("notify-keyspace-events")
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(x, vec!["notify-keyspace-events", ""]);
+    let x: RedisResult<()> = cmd("CONFIG")
+        .arg("SET")
+        .arg("notify-keyspace-events")
+        .arg("KEQ")
+        .query(&mut con);
+    assert!(x.is_err());
+    let _: () = cmd("CONFIG")
+        .arg("SET")
+        .arg("notify-keyspace-events")
+        .arg("KEg$x")
+        .query(&mut con)
+        .unwrap();
+    let x: Vec<String> = cmd("CONFIG")
+        .arg("GET")
+        .arg("notify*")
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(x, vec!["notify-keyspace-events", "g$xKE"]);
+
+    let mut subscriber = redis_client.get_connection().unwrap();
+    let mut pubsub = subscriber.as_pubsub();
+    pubsub.psubscribe("__keyevent@0__:*").unwrap();
+    pubsub.subscribe("__keyspace@0__:session").unwrap();
+    pubsub
+        .set_read_timeout(Some(Duration::from_secs(5)))
+        .unwrap();
+
+    let _: () = con.set("session", "abc").unwrap();
+    // deleting a missing key and adding to a set fire nothing
+    let _: () = con.del("missing").unwrap();
+    let _: () = con.sadd("tags", "rust").unwrap();
+    let _: () = con.pexpire("session", 10).unwrap();
+    sleep(Duration::from_millis(50));
+    let x: Option<String> = con.get("session").unwrap();
+    assert_eq!(x, None);
+
+    let mut events = vec![];
+    for _ in 0..6 {
+        let message = pubsub.get_message().unwrap();
+        events.push((
+            message.get_channel_name().to_string(),
+            message.get_payload::<String>().unwrap(),
+        ));
+    }
+    let event = |channel: &str, payload: &str| (channel.to_string(), payload.to_string());
+    assert_eq!(
+        events,
+        vec![
+            event("__keyspace@0__:session", "set"),
+            event("__keyevent@0__:set", "session"),
+            event("__keyspace@0__:session", "expire"),
+            event("__keyevent@0__:expire", "session"),
+            event("__keyspace@0__:session", "expired"),
+            event("__keyevent@0__:expired", "session"),
+        ]
+    );
+
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+}
+
+#[test]
+#[serial]
+fn sharded_publish_subscribe() {
+    let port = 3440;
+    let server = Server::new(InMemoryStorage::new(), port);
+    assert_eq!(server.start(), Some(ServerState::Started));
+    let redis_client = redis::Client::open(format!("redis://127.0.0.1:{}/", port)).unwrap();
+    let mut con = redis_client.get_connection().unwrap();
+
+    let mut subscriber = redis_client.get_connection().unwrap();
+    let x: (String, String, u32) = cmd("SSUBSCRIBE")
+        .arg("orders")
+        .query(&mut subscriber)
+        .unwrap();
+    assert_eq!(x, ("ssubscribe".to_string(), "orders".to_string(), 1));
+    // the count of shard channels is kept apart from the other subscriptions
+    let x: (String, String, u32) = cmd("SUBSCRIBE").arg("news").query(&mut subscriber).unwrap();
+    assert_eq!(x, ("subscribe".to_string(), "news".to_string(), 1));
+    let x: RedisResult<String> = cmd("GET").arg("orders").query(&mut subscriber);
+    assert!(x.is_err());
+
+    // a plain subscriber of the same channel does not receive shard messages
+    let x: u32 = cmd("SPUBLISH")
+        .arg("orders")
+        .arg("created")
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(x, 1);
+    let x: u32 = con.publish("orders", "created").unwrap();
+    assert_eq!(x, 0);
+
+    subscriber
+        .set_read_timeout(Some(Duration::from_secs(5)))
+        .unwrap();
+    let x: Vec<String> = redis::from_redis_value(&subscriber.recv_response().unwrap()).unwrap();
+    assert_eq!(x, vec!["smessage", "orders", "created"]);
+
+    let x: Vec<String> = cmd("PUBSUB").arg("SHARDCHANNELS").query(&mut con).unwrap();
+    assert_eq!(x, vec!["orders"]);
+    let x: Vec<String> = cmd("PUBSUB").arg("CHANNELS").query(&mut con).unwrap();
+    assert_eq!(x, vec!["news"]);
+    let x: Vec<redis::Value> = cmd("PUBSUB")
+        .arg("SHARDNUMSUB")
+        .arg("orders")
+        .arg("news")
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(
+        x,
+        vec![
+            redis::Value::Data(b"orders".to_vec()),
+            redis::Value::Int(1),
+            redis::Value::Data(b"news".to_vec()),
+            redis::Value::Int(0),
+        ]
+    );
+
+    let x: (String, String, u32) = cmd("SUNSUBSCRIBE").query(&mut subscriber).unwrap();
+    assert_eq!(x, ("sunsubscribe".to_string(), "orders".to_string(), 0));
+    let x: u32 = cmd("SPUBLISH")
+        .arg("orders")
+        .arg("shipped")
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(x, 0);
+
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+}
+
+#[test]
+#[serial]
+fn transactions() {
+    let port = 3441;
+    let server = Server::new(InMemoryStorage::new(), port);
+    assert_eq!(server.start(), Some(ServerState::Started));
+    let redis_client = redis::Client::open(format!("redis://127.0.0.1:{}/", port)).unwrap();
+    let mut con = redis_client.get_connection().unwrap();
+
+    let x: String = cmd("MULTI").query(&mut con).unwrap();
+    assert_eq!(x, "OK");
+    let x: String = cmd("SET").arg("key").arg("value").query(&mut con).unwrap();
+    assert_eq!(x, "QUEUED");
+    let x: String = cmd("INCR").arg("counter").query(&mut con).unwrap();
+    assert_eq!(x, "QUEUED");
+    let x: String = cmd("GET").arg("missing").query(&mut con).unwrap();
+    assert_eq!(x, "QUEUED");
+    let x: (String, u32, Option<String>) = cmd("EXEC").query(&mut con).unwrap();
+    assert_eq!(x, ("OK".to_string(), 1, None));
+
+    let x: String = cmd("MULTI").query(&mut con).unwrap();
+    assert_eq!(x, "OK");
+    let x: RedisResult<()> = cmd("MULTI").query(&mut con);
+    assert!(x.is_err());
+    let x: String = cmd("INCR").arg("counter").query(&mut con).unwrap();
+    assert_eq!(x, "QUEUED");
+    // a blocking command gives up at once
+    let x: String = cmd("BZPOPMIN").arg("queue").arg(0).query(&mut con).unwrap();
+    assert_eq!(x, "QUEUED");
+    let x: (u32, Option<String>) = cmd("EXEC").query(&mut con).unwrap();
+    assert_eq!(x, (2, None));
+
+    let _: () = cmd("MULTI").query(&mut con).unwrap();
+    let _: () = cmd("INCR").arg("counter").query(&mut con).unwrap();
+    let x: String = cmd("DISCARD").query(&mut con).unwrap();
+    assert_eq!(x, "OK");
+    let x: u32 = con.get("counter").unwrap();
+    assert_eq!(x, 2);
+
+    // nothing runs once a command could not be queued
+    let _: () = cmd("MULTI").query(&mut con).unwrap();
+    let _: () = cmd("INCR").arg("counter").query(&mut con).unwrap();
+    let x: RedisResult<()> = cmd("INCR").query(&mut con);
+    assert!(x.is_err());
+    let x: RedisResult<()> = cmd("EXEC").query(&mut con);
+    assert_eq!(x.unwrap_err().code(), Some("EXECABORT"));
+    let x: u32 = con.get("counter").unwrap();
+    assert_eq!(x, 2);
+
+    let x: RedisResult<()> = cmd("EXEC").query(&mut con);
+    assert!(x.is_err());
+    let x: RedisResult<()> = cmd("DISCARD").query(&mut con);
+    assert!(x.is_err());
+
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+}
+
+#[test]
+#[serial]
+fn watch() {
+    let port = 3442;
+    let server = Server::new(InMemoryStorage::new(), port);
+    assert_eq!(server.start(), Some(ServerState::Started));
+    let redis_client = redis::Client::open(format!("redis://127.0.0.1:{}/", port)).unwrap();
+    let mut con = redis_client.get_connection().unwrap();
+    let mut other = redis_client.get_connection().unwrap();
+
+    let _: () = con.set("balance", 10).unwrap();
+
+    // nobody else modified the key
+    let x: String = cmd("WATCH").arg("balance").query(&mut con).unwrap();
+    assert_eq!(x, "OK");
+    let _: () = cmd("MULTI").query(&mut con).unwrap();
+    let _: () = cmd("INCR").arg("balance").query(&mut con).unwrap();
+    let x: Vec<u32> = cmd("EXEC").query(&mut con).unwrap();
+    assert_eq!(x, vec![11]);
+
+    // the key has been modified in between by another connection
+    let _: () = cmd("WATCH")
+        .arg("balance")
+        .arg("missing")
+        .query(&mut con)
+        .unwrap();
+    let _: () = other.set("missing", 1).unwrap();
+    let _: () = cmd("MULTI").query(&mut con).unwrap();
+    let x: RedisResult<()> = cmd("WATCH").arg("balance").query(&mut con);
+    assert!(x.is_err());
+    let _: () = cmd("INCR").arg("balance").query(&mut con).unwrap();
+    let x: Option<Vec<u32>> = cmd("EXEC").query(&mut con).unwrap();
+    assert_eq!(x, None);
+    let x: u32 = con.get("balance").unwrap();
+    assert_eq!(x, 11);
+
+    // EXEC forgets the watched keys
+    let _: () = other.set("missing", 2).unwrap();
+    let _: () = cmd("MULTI").query(&mut con).unwrap();
+    let _: () = cmd("INCR").arg("balance").query(&mut con).unwrap();
+    let x: Option<Vec<u32>> = cmd("EXEC").query(&mut con).unwrap();
+    assert_eq!(x, Some(vec![12]));
+
+    let _: () = cmd("WATCH").arg("balance").query(&mut con).unwrap();
+    let x: String = cmd("UNWATCH").query(&mut con).unwrap();
+    assert_eq!(x, "OK");
+    let _: () = other.set("balance", 0).unwrap();
+    let _: () = cmd("MULTI").query(&mut con).unwrap();
+    let _: () = cmd("INCR").arg("balance").query(&mut con).unwrap();
+    let x: Option<Vec<u32>> = cmd("EXEC").query(&mut con).unwrap();
+    assert_eq!(x, Some(vec![1]));
+
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+}
+
+#[cfg(feature = "scripting")]
+#[test]
+#[serial]
+fn eval() {
+    let port = 3443;
+    let server = Server::new(InMemoryStorage::new(), port);
+    assert_eq!(server.start(), Some(ServerState::Started));
+    let redis_client = redis::Client::open(format!("redis://127.0.0.1:{}/", port)).unwrap();
+    let mut con = redis_client.get_connection().unwrap();
+
+    let x: Vec<String> = cmd("EVAL")
+        .arg("return {KEYS[1], KEYS[2], ARGV[1]}")
+        .arg(2)
+        .arg("a")
+        .arg("b")
+        .arg("c")
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(x, vec!["a", "b", "c"]);
+
+    // integers lose their decimal part and the array stops at the first nil
+    let x: redis::Value = cmd("EVAL")
+        .arg("return {1, 2.9, true, 'x', nil, 'y'}")
+        .arg(0)
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(
+        x,
+        redis::Value::Bulk(vec![
+            redis::Value::Int(1),
+            redis::Value::Int(2),
+            redis::Value::Int(1),
+            redis::Value::Data(b"x".to_vec()),
+        ])
+    );
+
+    let script = "redis.call('SET', KEYS[1], ARGV[1]); return redis.call('INCR', KEYS[1])";
+    let x: u32 = cmd("EVAL")
+        .arg(script)
+        .arg(1)
+        .arg("counter")
+        .arg(41)
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(x, 42);
+    let x: Option<String> = cmd("EVAL")
+        .arg("return redis.call('GET', 'missing')")
+        .arg(0)
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(x, None);
+
+    // a script is cached once run
+    let sha = "952f49ffc8f7b098d8ab5da45d3164ca36ed18b1";
+    let x: String = cmd("EVAL")
+        .arg("return redis.sha1hex(ARGV[1])")
+        .arg(0)
+        .arg("return 'cached'")
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(x, sha);
+    let x: RedisResult<String> = cmd("EVALSHA").arg(sha).arg(0).query(&mut con);
+    assert_eq!(x.unwrap_err().code(), Some("NOSCRIPT"));
+    let _: String = cmd("EVAL")
+        .arg("return 'cached'")
+        .arg(0)
+        .query(&mut con)
+        .unwrap();
+    let x: String = cmd("EVALSHA").arg(sha).arg(0).query(&mut con).unwrap();
+    assert_eq!(x, "cached");
+
+    // errors raised by redis.call stop the script, unlike redis.pcall
+    let x: RedisResult<()> = cmd("EVAL")
+        .arg("return redis.call('HSTRLEN', KEYS[1], 'field')")
+        .arg(1)
+        .arg("counter")
+        .query(&mut con);
+    assert_eq!(x.unwrap_err().code(), Some("WRONGTYPE"));
+    let x: RedisResult<()> = cmd("EVAL")
+        .arg("local reply = redis.pcall('HSTRLEN', KEYS[1], 'field'); return redis.status_reply(reply['err'])")
+        .arg(1)
+        .arg("counter")
+        .query(&mut con);
+    assert!(x.is_ok());
+    let x: RedisResult<()> = cmd("EVAL")
+        .arg("return redis.error_reply('MYERR failed')")
+        .arg(0)
+        .query(&mut con);
+    assert_eq!(x.unwrap_err().code(), Some("MYERR"));
+    let x: RedisResult<()> = cmd("EVAL").arg("return +").arg(0).query(&mut con);
+    assert!(x.is_err());
+    let x: RedisResult<()> = cmd("EVAL")
+        .arg("return redis.call('MULTI')")
+        .arg(0)
+        .query(&mut con);
+    assert!(x.is_err());
+    let x: RedisResult<()> = cmd("EVAL").arg("return 1").arg(2).arg("a").query(&mut con);
+    assert!(x.is_err());
+
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+}
+
+#[cfg(feature = "scripting")]
+#[test]
+#[serial]
+fn script() {
+    let port = 3444;
+    let server = Server::new(InMemoryStorage::new(), port);
+    assert_eq!(server.start(), Some(ServerState::Started));
+    let redis_client = redis::Client::open(format!("redis://127.0.0.1:{}/", port)).unwrap();
+    let mut con = redis_client.get_connection().unwrap();
+
+    let sha: String = cmd("SCRIPT")
+        .arg("LOAD")
+        .arg("return ARGV[1]")
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(sha, "098e0f0d1448c0a81dafe820f66d460eb09263da");
+    let x: Vec<u8> = cmd("SCRIPT")
+        .arg("EXISTS")
+        .arg(&sha)
+        .arg("ffffffffffffffffffffffffffffffffffffffff")
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(x, vec![1, 0]);
+    let x: String = cmd("EVALSHA")
+        .arg(&sha)
+        .arg(0)
+        .arg("a")
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(x, "a");
+
+    let script = redis::Script::new("return redis.call('INCR', KEYS[1])");
+    let x: u32 = script.key("counter").invoke(&mut con).unwrap();
+    assert_eq!(x, 1);
+
+    let _: () = cmd("SCRIPT").arg("FLUSH").query(&mut con).unwrap();
+    let x: Vec<u8> = cmd("SCRIPT")
+        .arg("EXISTS")
+        .arg(&sha)
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(x, vec![0]);
+    let x: RedisResult<String> = cmd("EVALSHA").arg(&sha).arg(0).query(&mut con);
+    assert_eq!(x.unwrap_err().code(), Some("NOSCRIPT"));
+
+    let x: RedisResult<()> = cmd("SCRIPT").arg("KILL").query(&mut con);
+    assert_eq!(x.unwrap_err().code(), Some("NOTBUSY"));
+
+    // a script looping forever is killed from another connection
+    let looping = std::thread::spawn(move || {
+        let x: RedisResult<()> = cmd("EVAL").arg("while true do end").arg(0).query(&mut con);
+        x
+    });
+    sleep(Duration::from_millis(200));
+    let mut con = redis_client.get_connection().unwrap();
+    let _: () = cmd("SCRIPT").arg("KILL").query(&mut con).unwrap();
+    let x = looping.join().unwrap().unwrap_err();
+    assert!(x.to_string().contains("Script killed by user"));
+    let x: u32 = con.get("counter").unwrap();
+    assert_eq!(x, 1);
+
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+}
+
+#[cfg(feature = "scripting")]
+#[test]
+#[serial]
+fn function() {
+    let port = 3445;
+    let server = Server::new(InMemoryStorage::new(), port);
+    assert_eq!(server.start(), Some(ServerState::Started));
+    let redis_client = redis::Client::open(format!("redis://127.0.0.1:{}/", port)).unwrap();
+    let mut con = redis_client.get_connection().unwrap();
+
+    // requests are read in a 512 bytes buffer, the library must fit in it
+    let code = "#!lua name=counters
+redis.register_function('incr', function(k) return redis.call('INCR', k[1]) end)
+redis.register_function{function_name='read', flags={'no-writes'},
+  callback=function(k) return redis.call('GET', k[1]) end, description='read a counter'}
+redis.register_function{function_name='sneaky', flags={'no-writes'},
+  callback=function(k, a) return redis.call('SET', k[1], a[1]) end}";
+    let x: String = cmd("FUNCTION")
+        .arg("LOAD")
+        .arg(code)
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(x, "counters");
+    let x: RedisResult<String> = cmd("FUNCTION").arg("LOAD").arg(code).query(&mut con);
+    assert!(x.unwrap_err().to_string().contains("already exists"));
+    let x: String = cmd("FUNCTION")
+        .arg("LOAD")
+        .arg("REPLACE")
+        .arg(code)
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(x, "counters");
+
+    let x: u32 = cmd("FCALL")
+        .arg("incr")
+        .arg(1)
+        .arg("counter")
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(x, 1);
+    let x: u32 = cmd("FCALL_RO")
+        .arg("read")
+        .arg(1)
+        .arg("counter")
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(x, 1);
+    let x: RedisResult<u32> = cmd("FCALL_RO")
+        .arg("incr")
+        .arg(1)
+        .arg("counter")
+        .query(&mut con);
+    assert!(x.is_err());
+    let x: RedisResult<()> = cmd("FCALL")
+        .arg("sneaky")
+        .arg(1)
+        .arg("counter")
+        .arg(5)
+        .query(&mut con);
+    assert!(x.unwrap_err().to_string().contains("read-only scripts"));
+    let x: RedisResult<()> = cmd("FCALL").arg("missing").arg(0).query(&mut con);
+    assert!(x.unwrap_err().to_string().contains("Function not found"));
+
+    // functions cannot be registered twice, nor without the library header
+    let x: RedisResult<String> = cmd("FUNCTION")
+        .arg("LOAD")
+        .arg("#!lua name=other\nredis.register_function('incr', function() return 1 end)")
+        .query(&mut con);
+    assert!(x
+        .unwrap_err()
+        .to_string()
+        .contains("Function incr already exists"));
+    let x: RedisResult<String> = cmd("FUNCTION").arg("LOAD").arg("return 1").query(&mut con);
+    assert!(x
+        .unwrap_err()
+        .to_string()
+        .contains("Missing library metadata"));
+    let x: RedisResult<String> = cmd("FUNCTION")
+        .arg("LOAD")
+        .arg("#!lua name=empty\nlocal x = 1")
+        .query(&mut con);
+    assert!(x
+        .unwrap_err()
+        .to_string()
+        .contains("No functions registered"));
+
+    let x: redis::Value = cmd("FUNCTION")
+        .arg("LIST")
+        .arg("LIBRARYNAME")
+        .arg("count*")
+        .arg("WITHCODE")
+        .query(&mut con)
+        .unwrap();
+    let data = |s: &str| redis::Value::Data(s.as_bytes().to_vec());
+    let function = |name: &str, description: redis::Value, flags: Vec<redis::Value>| {
+        redis::Value::Bulk(vec![
+            data("name"),
+            data(name),
+            data("description"),
+            description,
+            data("flags"),
+            redis::Value::Bulk(flags),
+        ])
+    };
+    assert_eq!(
+        x,
+        redis::Value::Bulk(vec![redis::Value::Bulk(vec![
+            data("library_name"),
+            data("counters"),
+            data("engine"),
+            data("LUA"),
+            data("functions"),
+            redis::Value::Bulk(vec![
+                function("incr", redis::Value::Nil, vec![]),
+                function("read", data("read a counter"), vec![data("no-writes")]),
+                function("sneaky", redis::Value::Nil, vec![data("no-writes")]),
+            ]),
+            data("library_code"),
+            data(code),
+        ])])
+    );
+    let x: Vec<redis::Value> = cmd("FUNCTION")
+        .arg("LIST")
+        .arg("LIBRARYNAME")
+        .arg("other*")
+        .query(&mut con)
+        .unwrap();
+    assert!(x.is_empty());
+
+    let x: Vec<u8> = cmd("FUNCTION").arg("DUMP").query(&mut con).unwrap();
+    assert!(!x.is_empty());
+
+    let _: () = cmd("FUNCTION")
+        .arg("DELETE")
+        .arg("counters")
+        .query(&mut con)
+        .unwrap();
+    let x: RedisResult<()> = cmd("FUNCTION")
+        .arg("DELETE")
+        .arg("counters")
+        .query(&mut con);
+    assert!(x.is_err());
+    let x: RedisResult<()> = cmd("FCALL")
+        .arg("incr")
+        .arg(1)
+        .arg("counter")
+        .query(&mut con);
+    assert!(x.is_err());
+
+    let _: String = cmd("FUNCTION")
+        .arg("LOAD")
+        .arg(code)
+        .query(&mut con)
+        .unwrap();
+    let _: () = cmd("FUNCTION").arg("FLUSH").query(&mut con).unwrap();
+    let x: Vec<redis::Value> = cmd("FUNCTION").arg("LIST").query(&mut con).unwrap();
+    assert!(x.is_empty());
+
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+}
+
+#[test]
+#[serial]
+fn auth() {
+    let port = 3446;
+    let server = ServerBuilder::new(InMemoryStorage::new(), port)
+        .requirepass("secret")
+        .build();
+    assert_eq!(server.start(), Some(ServerState::Started));
+
+    let redis_client = redis::Client::open(format!("redis://127.0.0.1:{}/", port)).unwrap();
+    let mut con = redis_client.get_connection().unwrap();
+    let x: RedisResult<()> = con.set("key", "value");
+    assert_eq!(x.unwrap_err().code(), Some("NOAUTH"));
+    let x: RedisResult<()> = cmd("AUTH").arg("wrong").query(&mut con);
+    assert_eq!(x.unwrap_err().code(), Some("WRONGPASS"));
+    let x: RedisResult<()> = cmd("AUTH").arg("admin").arg("secret").query(&mut con);
+    assert_eq!(x.unwrap_err().code(), Some("WRONGPASS"));
+    let _: () = cmd("AUTH").arg("secret").query(&mut con).unwrap();
+    let _: () = con.set("key", "value").unwrap();
+
+    // the client authenticates with the password of the URL
+    let redis_client = redis::Client::open(format!("redis://:secret@127.0.0.1:{}/", port)).unwrap();
+    let mut con = redis_client.get_connection().unwrap();
+    let x: String = con.get("key").unwrap();
+    assert_eq!(x, "value");
+    let _: () = cmd("AUTH")
+        .arg("default")
+        .arg("secret")
+        .query(&mut con)
+        .unwrap();
+
+    let redis_client = redis::Client::open(format!("redis://:wrong@127.0.0.1:{}/", port)).unwrap();
+    assert!(redis_client.get_connection().is_err());
+
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+
+    // without a password, AUTH is an error
+    let port = 3447;
+    let server = Server::new(InMemoryStorage::new(), port);
+    assert_eq!(server.start(), Some(ServerState::Started));
+    let redis_client = redis::Client::open(format!("redis://127.0.0.1:{}/", port)).unwrap();
+    let mut con = redis_client.get_connection().unwrap();
+    let x: RedisResult<()> = cmd("AUTH").arg("secret").query(&mut con);
+    assert!(x
+        .unwrap_err()
+        .to_string()
+        .contains("without any password configured"));
+    let _: () = cmd("AUTH")
+        .arg("default")
+        .arg("any")
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+}
+
+#[test]
+#[serial]
+fn acl() {
+    let port = 3448;
+    let server = Server::new(InMemoryStorage::new(), port);
+    assert_eq!(server.start(), Some(ServerState::Started));
+    let redis_client = redis::Client::open(format!("redis://127.0.0.1:{}/", port)).unwrap();
+    let mut con = redis_client.get_connection().unwrap();
+
+    let x: String = cmd("ACL").arg("WHOAMI").query(&mut con).unwrap();
+    assert_eq!(x, "default");
+    let _: () = cmd("ACL")
+        .arg("SETUSER")
+        .arg("alice")
+        .arg("on")
+        .arg(">pass")
+        .arg("~cached:*")
+        .arg("&news")
+        .arg("-@all")
+        .arg("+get")
+        .arg("+set")
+        .arg("+eval")
+        .arg("+acl|whoami")
+        .query(&mut con)
+        .unwrap();
+    let x: RedisResult<()> = cmd("ACL")
+        .arg("SETUSER")
+        .arg("alice")
+        .arg("+nosuchcommand")
+        .query(&mut con);
+    assert!(x
+        .unwrap_err()
+        .to_string()
+        .contains("modifier '+nosuchcommand'"));
+
+    let hash = "d74ff0ee8da3b9806b18c877dbf29bbde50b5bd8e4dad7a3a725000feb82e8f1";
+    let x: redis::Value = cmd("ACL")
+        .arg("GETUSER")
+        .arg("alice")
+        .query(&mut con)
+        .unwrap();
+    let data = |s: &str| redis::Value::Data(s.as_bytes().to_vec());
+    assert_eq!(
+        x,
+        redis::Value::Bulk(vec![
+            data("flags"),
+            redis::Value::Bulk(vec![data("on")]),
+            data("passwords"),
+            redis::Value::Bulk(vec![data(hash)]),
+            data("commands"),
+            data("-@all +get +set +eval +acl|whoami"),
+            data("keys"),
+            data("~cached:*"),
+            data("channels"),
+            data("&news"),
+            data("selectors"),
+            redis::Value::Bulk(vec![]),
+        ])
+    );
+    let x: Option<String> = cmd("ACL")
+        .arg("GETUSER")
+        .arg("bob")
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(x, None);
+    let x: Vec<String> = cmd("ACL").arg("USERS").query(&mut con).unwrap();
+    assert_eq!(x, vec!["alice", "default"]);
+    let x: Vec<String> = cmd("ACL").arg("LIST").query(&mut con).unwrap();
+    assert_eq!(
+        x,
+        vec![
+            format!(
+                "user alice on #{} ~cached:* &news -@all +get +set +eval +acl|whoami",
+                hash
+            ),
+            "user default on nopass ~* &* +@all".to_string(),
+        ]
+    );
+
+    let x: Vec<String> = cmd("ACL").arg("CAT").query(&mut con).unwrap();
+    assert!(x.contains(&"hyperloglog".to_string()));
+    let x: Vec<String> = cmd("ACL")
+        .arg("CAT")
+        .arg("hyperloglog")
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(x, vec!["pfadd", "pfcount", "pfmerge"]);
+    let x: RedisResult<Vec<String>> = cmd("ACL").arg("CAT").arg("nope").query(&mut con);
+    assert!(x.is_err());
+
+    // alice may only run the commands and access the keys and channels
+    // she has been given
+    let mut alice = redis_client.get_connection().unwrap();
+    let x: RedisResult<()> = cmd("AUTH").arg("alice").arg("wrong").query(&mut alice);
+    assert_eq!(x.unwrap_err().code(), Some("WRONGPASS"));
+    let _: () = cmd("AUTH")
+        .arg("alice")
+        .arg("pass")
+        .query(&mut alice)
+        .unwrap();
+    let x: String = cmd("ACL").arg("WHOAMI").query(&mut alice).unwrap();
+    assert_eq!(x, "alice");
+    let _: () = alice.set("cached:1", 1).unwrap();
+    let x: u32 = alice.get("cached:1").unwrap();
+    assert_eq!(x, 1);
+    let x: RedisResult<()> = alice.set("other", 1);
+    assert_eq!(x.unwrap_err().code(), Some("NOPERM"));
+    let x: RedisResult<()> = cmd("INCR").arg("cached:1").query(&mut alice);
+    assert_eq!(x.unwrap_err().code(), Some("NOPERM"));
+    let x: RedisResult<()> = cmd("ACL").arg("USERS").query(&mut alice);
+    assert_eq!(x.unwrap_err().code(), Some("NOPERM"));
+    let x: RedisResult<()> = cmd("EVAL")
+        .arg("return redis.call('INCR', KEYS[1])")
+        .arg(1)
+        .arg("cached:1")
+        .query(&mut alice);
+    assert_eq!(x.unwrap_err().code(), Some("NOPERM"));
+    let x: RedisResult<()> = alice.publish("news", "hello");
+    assert_eq!(x.unwrap_err().code(), Some("NOPERM"));
+    let _: () = cmd("ACL")
+        .arg("SETUSER")
+        .arg("alice")
+        .arg("+@pubsub")
+        .query(&mut con)
+        .unwrap();
+    let _: () = alice.publish("news", "hello").unwrap();
+    let x: RedisResult<()> = alice.publish("sports", "hello");
+    assert_eq!(x.unwrap_err().code(), Some("NOPERM"));
+
+    let x: RedisResult<()> = cmd("ACL").arg("DELUSER").arg("default").query(&mut con);
+    assert!(x.is_err());
+    let x: u32 = cmd("ACL")
+        .arg("DELUSER")
+        .arg("alice")
+        .arg("bob")
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(x, 1);
+    let x: RedisResult<String> = alice.get("cached:1");
+    assert_eq!(x.unwrap_err().code(), Some("NOAUTH"));
+
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+}
+
+#[test]
+#[serial]
+fn select() {
+    let port = 3449;
+    let server = ServerBuilder::new(InMemoryStorage::new(), port)
+        .databases(4)
+        .build();
+    assert_eq!(server.start(), Some(ServerState::Started));
+    let redis_client = redis::Client::open(format!("redis://127.0.0.1:{}/", port)).unwrap();
+    let mut con = redis_client.get_connection().unwrap();
+    let db_client = redis::Client::open(format!("redis://127.0.0.1:{}/1", port)).unwrap();
+    let mut db_con = db_client.get_connection().unwrap();
+
+    // each database has its own keys
+    let _: () = con.set("key", "zero").unwrap();
+    let x: RedisResult<String> = db_con.get("key");
+    assert!(x.is_err());
+    let _: () = db_con.set("key", "one").unwrap();
+    let x: String = con.get("key").unwrap();
+    assert_eq!(x, "zero");
+    let _: () = cmd("SELECT").arg(1).query(&mut con).unwrap();
+    let x: String = con.get("key").unwrap();
+    assert_eq!(x, "one");
+
+    let x: RedisResult<()> = cmd("SELECT").arg(4).query(&mut con);
+    assert!(x.is_err());
+    let x: RedisResult<()> = cmd("SELECT").arg(-1).query(&mut con);
+    assert!(x.is_err());
+    let x: RedisResult<()> = cmd("SELECT").arg("one").query(&mut con);
+    assert!(x.is_err());
+
+    // SELECT queued in a transaction switches the database of the commands
+    // queued after it
+    let _: () = cmd("MULTI").query(&mut con).unwrap();
+    let _: () = cmd("SELECT").arg(2).query(&mut con).unwrap();
+    let _: () = con.set("key", "two").unwrap();
+    let x: (String, String) = cmd("EXEC").query(&mut con).unwrap();
+    assert_eq!(x, ("OK".to_string(), "OK".to_string()));
+    let x: String = con.get("key").unwrap();
+    assert_eq!(x, "two");
+    let x: String = db_con.get("key").unwrap();
+    assert_eq!(x, "one");
+
+    // a key is watched in the database it was watched from
+    let _: () = cmd("WATCH").arg("key").query(&mut con).unwrap();
+    let _: () = db_con.set("key", "changed").unwrap();
+    let _: () = cmd("MULTI").query(&mut con).unwrap();
+    let _: () = con.set("key", "watched").unwrap();
+    let x: (String,) = cmd("EXEC").query(&mut con).unwrap();
+    assert_eq!(x, ("OK".to_string(),));
+
+    // a script switching database leaves the connection's one as it was
+    #[cfg(feature = "scripting")]
+    {
+        let x: String = cmd("EVAL")
+            .arg("redis.call('SELECT', 0) return redis.call('GET', KEYS[1])")
+            .arg(1)
+            .arg("key")
+            .query(&mut con)
+            .unwrap();
+        assert_eq!(x, "zero");
+        let x: String = con.get("key").unwrap();
+        assert_eq!(x, "watched");
+    }
+
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+}
+
+#[test]
+#[serial]
+fn swapdb() {
+    let port = 3450;
+    let server = Server::new(InMemoryStorage::new(), port);
+    assert_eq!(server.start(), Some(ServerState::Started));
+    let redis_client = redis::Client::open(format!("redis://127.0.0.1:{}/", port)).unwrap();
+    let mut con = redis_client.get_connection().unwrap();
+    let db_client = redis::Client::open(format!("redis://127.0.0.1:{}/1", port)).unwrap();
+    let mut db_con = db_client.get_connection().unwrap();
+
+    let _: () = con.set("key", "blue").unwrap();
+    let _: () = db_con.set("key", "green").unwrap();
+    let _: () = db_con.set("warm", "1").unwrap();
+
+    // a transaction watching a key of a swapped database is discarded
+    let _: () = cmd("WATCH").arg("key").query(&mut con).unwrap();
+    let _: () = cmd("SWAPDB").arg(0).arg(1).query(&mut db_con).unwrap();
+    let _: () = cmd("MULTI").query(&mut con).unwrap();
+    let _: () = con.set("key", "other").unwrap();
+    let x: Option<(String,)> = cmd("EXEC").query(&mut con).unwrap();
+    assert_eq!(x, None);
+
+    let x: String = con.get("key").unwrap();
+    assert_eq!(x, "green");
+    let x: bool = con.exists("warm").unwrap();
+    assert!(x);
+    let x: String = db_con.get("key").unwrap();
+    assert_eq!(x, "blue");
+    let x: bool = db_con.exists("warm").unwrap();
+    assert!(!x);
+
+    let _: () = cmd("SWAPDB").arg(1).arg(1).query(&mut con).unwrap();
+    let x: RedisResult<()> = cmd("SWAPDB").arg(0).arg(16).query(&mut con);
+    assert!(x.is_err());
+    let x: RedisResult<()> = cmd("SWAPDB").arg("first").arg(1).query(&mut con);
+    assert!(x.is_err());
+
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+}
+
+#[test]
+#[serial]
+fn move_key() {
+    let port = 3451;
+    let server = Server::new(InMemoryStorage::new(), port);
+    assert_eq!(server.start(), Some(ServerState::Started));
+    let redis_client = redis::Client::open(format!("redis://127.0.0.1:{}/", port)).unwrap();
+    let mut con = redis_client.get_connection().unwrap();
+    let db_client = redis::Client::open(format!("redis://127.0.0.1:{}/1", port)).unwrap();
+    let mut db_con = db_client.get_connection().unwrap();
+
+    let _: () = con.sadd("set", "member").unwrap();
+    let _: () = con.set("key", "zero").unwrap();
+    let _: () = db_con.set("key", "one").unwrap();
+
+    let x: u32 = cmd("MOVE").arg("set").arg(1).query(&mut con).unwrap();
+    assert_eq!(x, 1);
+    let x: bool = con.exists("set").unwrap();
+    assert!(!x);
+    let x: Vec<String> = db_con.smembers("set").unwrap();
+    assert_eq!(x, vec!["member"]);
+    let x: u32 = cmd("MOVE").arg("set").arg(1).query(&mut con).unwrap();
+    assert_eq!(x, 0);
+
+    // the destination already has the key
+    let x: u32 = cmd("MOVE").arg("key").arg(1).query(&mut con).unwrap();
+    assert_eq!(x, 0);
+    let x: String = con.get("key").unwrap();
+    assert_eq!(x, "zero");
+    let x: String = db_con.get("key").unwrap();
+    assert_eq!(x, "one");
+
+    let x: RedisResult<u32> = cmd("MOVE").arg("key").arg(0).query(&mut con);
+    assert!(x.is_err());
+    let x: RedisResult<u32> = cmd("MOVE").arg("key").arg(16).query(&mut con);
+    assert!(x.is_err());
+
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+}
+
+#[test]
+#[serial]
+fn client() {
+    let port = 3452;
+    let server = Server::new(InMemoryStorage::new(), port);
+    assert_eq!(server.start(), Some(ServerState::Started));
+    let redis_client = redis::Client::open(format!("redis://127.0.0.1:{}/", port)).unwrap();
+    let mut con = redis_client.get_connection().unwrap();
+    let mut other = redis_client.get_connection().unwrap();
+
+    let id: u64 = cmd("CLIENT").arg("ID").query(&mut con).unwrap();
+    let other_id: u64 = cmd("CLIENT").arg("ID").query(&mut other).unwrap();
+    assert_ne!(id, other_id);
+
+    let x: Option<String> = cmd("CLIENT").arg("GETNAME").query(&mut con).unwrap();
+    assert_eq!(x, None);
+    let _: () = cmd("CLIENT")
+        .arg("SETNAME")
+        .arg("pool-1")
+        .query(&mut con)
+        .unwrap();
+    let x: Option<String> = cmd("CLIENT").arg("GETNAME").query(&mut con).unwrap();
+    assert_eq!(x, Some("pool-1".to_string()));
+    let x: RedisResult<()> = cmd("CLIENT").arg("SETNAME").arg("pool 1").query(&mut con);
+    assert!(x.is_err());
+
+    let _: () = other.set("key", "value").unwrap();
+    let x: String = cmd("CLIENT").arg("LIST").query(&mut con).unwrap();
+    assert_eq!(x.lines().count(), 2);
+    let line = |id: u64| {
+        x.lines()
+            .find(|line| line.starts_with(&format!("id={} ", id)))
+            .unwrap()
+    };
+    assert!(line(id).contains(" addr=127.0.0.1:"));
+    assert!(line(id).contains(&format!(" laddr=127.0.0.1:{} name=pool-1 ", port)));
+    assert!(line(id).contains(" cmd=client|list "));
+    assert!(line(other_id).contains(" name= "));
+    assert!(line(other_id).contains(" db=0 "));
+    assert!(line(other_id).contains(" cmd=set "));
+
+    let x: String = cmd("CLIENT")
+        .arg("LIST")
+        .arg("ID")
+        .arg(other_id)
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(x.lines().count(), 1);
+    let x: String = cmd("CLIENT")
+        .arg("LIST")
+        .arg("TYPE")
+        .arg("pubsub")
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(x, "");
+    let x: RedisResult<String> = cmd("CLIENT")
+        .arg("LIST")
+        .arg("TYPE")
+        .arg("x")
+        .query(&mut con);
+    assert!(x.is_err());
+
+    let _: () = cmd("SELECT").arg(2).query(&mut con).unwrap();
+    let x: String = cmd("CLIENT").arg("INFO").query(&mut con).unwrap();
+    assert!(x.starts_with(&format!("id={} ", id)));
+    assert!(x.contains(" db=2 "));
+    assert!(x.contains(" cmd=client|info "));
+    assert!(x.ends_with('\n'));
+
+    // a closed connection is no longer listed
+    drop(other);
+    sleep(Duration::from_millis(100));
+    let x: String = cmd("CLIENT").arg("LIST").query(&mut con).unwrap();
+    assert_eq!(x.lines().count(), 1);
+
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+}
+
+#[test]
+#[serial]
+fn client_kill() {
+    let port = 3453;
+    let server = Server::new(InMemoryStorage::new(), port);
+    assert_eq!(server.start(), Some(ServerState::Started));
+    let redis_client = redis::Client::open(format!("redis://127.0.0.1:{}/", port)).unwrap();
+    let mut con = redis_client.get_connection().unwrap();
+    let mut other = redis_client.get_connection().unwrap();
+    let mut another = redis_client.get_connection().unwrap();
+
+    let other_id: u64 = cmd("CLIENT").arg("ID").query(&mut other).unwrap();
+    let x: u32 = cmd("CLIENT")
+        .arg("KILL")
+        .arg("ID")
+        .arg(other_id)
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(x, 1);
+    let x: RedisResult<String> = other.get("key");
+    assert!(x.is_err());
+    let x: u32 = cmd("CLIENT")
+        .arg("KILL")
+        .arg("ID")
+        .arg(other_id)
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(x, 0);
+
+    // the old form takes the address of the connection
+    let info: String = cmd("CLIENT").arg("INFO").query(&mut another).unwrap();
+    let addr = info
+        .split(' ')
+        .find_map(|field| field.strip_prefix("addr="))
+        .unwrap()
+        .to_string();
+    let _: () = cmd("CLIENT")
+        .arg("KILL")
+        .arg(&addr)
+        .query(&mut con)
+        .unwrap();
+    let x: RedisResult<String> = another.get("key");
+    assert!(x.is_err());
+    let x: RedisResult<()> = cmd("CLIENT").arg("KILL").arg(&addr).query(&mut con);
+    assert!(x.is_err());
+
+    // the caller is skipped unless SKIPME no is given
+    let laddr = format!("127.0.0.1:{}", port);
+    let x: u32 = cmd("CLIENT")
+        .arg("KILL")
+        .arg("LADDR")
+        .arg(&laddr)
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(x, 0);
+    let x: RedisResult<()> = cmd("CLIENT")
+        .arg("KILL")
+        .arg("LADDR")
+        .arg(&laddr)
+        .arg("SKIPME")
+        .arg("maybe")
+        .query(&mut con);
+    assert!(x.is_err());
+    let x: u32 = cmd("CLIENT")
+        .arg("KILL")
+        .arg("USER")
+        .arg("default")
+        .arg("SKIPME")
+        .arg("no")
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(x, 1);
+    let x: RedisResult<String> = con.get("key");
+    assert!(x.is_err());
+
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+}
+
+#[test]
+#[serial]
+fn client_pause() {
+    let port = 3454;
+    let server = Server::new(InMemoryStorage::new(), port);
+    assert_eq!(server.start(), Some(ServerState::Started));
+    let redis_client = redis::Client::open(format!("redis://127.0.0.1:{}/", port)).unwrap();
+    let mut con = redis_client.get_connection().unwrap();
+    let mut other = redis_client.get_connection().unwrap();
+    let _: () = con.set("key", "value").unwrap();
+
+    // only the writes are held back
+    let _: () = cmd("CLIENT")
+        .arg("PAUSE")
+        .arg(300)
+        .arg("WRITE")
+        .query(&mut con)
+        .unwrap();
+    let start = Instant::now();
+    let x: String = other.get("key").unwrap();
+    assert_eq!(x, "value");
+    assert!(start.elapsed() < Duration::from_millis(200));
+    let _: () = other.set("key", "paused").unwrap();
+    assert!(start.elapsed() >= Duration::from_millis(250));
+
+    // every command is held back until CLIENT UNPAUSE
+    let _: () = cmd("CLIENT")
+        .arg("PAUSE")
+        .arg(10000)
+        .query(&mut con)
+        .unwrap();
+    let start = Instant::now();
+    let get = std::thread::spawn(move || {
+        let x: String = other.get("key").unwrap();
+        x
+    });
+    sleep(Duration::from_millis(200));
+    let _: () = cmd("CLIENT").arg("UNPAUSE").query(&mut con).unwrap();
+    assert_eq!(get.join().unwrap(), "paused");
+    assert!(start.elapsed() >= Duration::from_millis(200));
+    assert!(start.elapsed() < Duration::from_secs(5));
+
+    let x: RedisResult<()> = cmd("CLIENT")
+        .arg("PAUSE")
+        .arg(100)
+        .arg("READ")
+        .query(&mut con);
+    assert!(x.is_err());
+
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+}
+
+#[test]
+#[serial]
+fn client_tracking() {
+    let port = 3455;
+    let server = Server::new(InMemoryStorage::new(), port);
+    assert_eq!(server.start(), Some(ServerState::Started));
+    let redis_client = redis::Client::open(format!("redis://127.0.0.1:{}/", port)).unwrap();
+    let mut con = redis_client.get_connection().unwrap();
+    let mut tracker = redis_client.get_connection().unwrap();
+
+    // the invalidations are redirected to a subscribed connection
+    let mut subscriber = redis_client.get_connection().unwrap();
+    let id: u64 = cmd("CLIENT").arg("ID").query(&mut subscriber).unwrap();
+    let mut pubsub = subscriber.as_pubsub();
+    pubsub.subscribe("__redis__:invalidate").unwrap();
+    pubsub
+        .set_read_timeout(Some(Duration::from_secs(5)))
+        .unwrap();
+
+    let _: () = cmd("CLIENT")
+        .arg(&["TRACKING", "ON", "REDIRECT"])
+        .arg(id)
+        .query(&mut tracker)
+        .unwrap();
+    let x: Option<String> = tracker.get("cached").unwrap();
+    assert_eq!(x, None);
+    let _: () = con.set("cached", "1").unwrap();
+    // a key is forgotten once its readers are told
+    let _: () = con.set("cached", "2").unwrap();
+
+    let message = pubsub.get_message().unwrap();
+    assert_eq!(message.get_channel_name(), "__redis__:invalidate");
+    let x: Vec<String> = message.get_payload().unwrap();
+    assert_eq!(x, vec!["cached"]);
+
+    // in BCAST mode, the connection is told about every key with a prefix
+    let _: () = cmd("CLIENT")
+        .arg(&["TRACKING", "ON", "REDIRECT"])
+        .arg(id)
+        .arg(&["BCAST", "PREFIX", "user:"])
+        .query(&mut tracker)
+        .unwrap();
+    let _: () = con.set("other", "1").unwrap();
+    let _: () = con.set("user:1", "alice").unwrap();
+    let x: Vec<String> = pubsub.get_message().unwrap().get_payload().unwrap();
+    assert_eq!(x, vec!["user:1"]);
+
+    // every key is gone once databases are swapped
+    let _: () = cmd("SWAPDB").arg(0).arg(1).query(&mut con).unwrap();
+    let x: Option<Vec<String>> = pubsub.get_message().unwrap().get_payload().unwrap();
+    assert_eq!(x, None);
+
+    let x: RedisResult<()> = cmd("CLIENT")
+        .arg(&["TRACKING", "ON", "PREFIX", "user:"])
+        .query(&mut tracker);
+    assert!(x.is_err());
+    let x: RedisResult<()> = cmd("CLIENT")
+        .arg(&["TRACKING", "ON", "REDIRECT", "12345"])
+        .query(&mut tracker);
+    assert!(x.is_err());
+
+    let _: () = cmd("CLIENT")
+        .arg(&["TRACKING", "OFF"])
+        .query(&mut tracker)
+        .unwrap();
+    let _: () = con.set("user:2", "bob").unwrap();
+    pubsub
+        .set_read_timeout(Some(Duration::from_millis(300)))
+        .unwrap();
+    assert!(pubsub.get_message().is_err());
+
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+}
+
+#[test]
+#[serial]
+fn command() {
+    let port = 3456;
+    let server = Server::new(InMemoryStorage::new(), port);
+    assert_eq!(server.start(), Some(ServerState::Started));
+    let redis_client = redis::Client::open(format!("redis://127.0.0.1:{}/", port)).unwrap();
+    let mut con = redis_client.get_connection().unwrap();
+
+    let count: usize = cmd("COMMAND").arg("COUNT").query(&mut con).unwrap();
+    let x: Vec<redis::Value> = cmd("COMMAND").query(&mut con).unwrap();
+    assert_eq!(x.len(), count);
+
+    let x: Vec<redis::Value> = cmd("COMMAND")
+        .arg(&["INFO", "mset", "unknown", "XGROUP"])
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(x.len(), 3);
+    let (name, arity, flags, first, last, step, categories): (
+        String,
+        i64,
+        Vec<String>,
+        i64,
+        i64,
+        i64,
+        Vec<String>,
+    ) = match &x[0] {
+        redis::Value::Bulk(info) => {
+            redis::from_redis_value(&redis::Value::Bulk(info[..7].to_vec())).unwrap()
+        }
+        _ => panic!("COMMAND INFO should describe MSET"),
+    };
+    assert_eq!(name, "mset");
+    assert_eq!(arity, -3);
+    assert_eq!(flags, vec!["write", "denyoom"]);
+    assert_eq!((first, last, step), (1, -1, 2));
+    assert_eq!(categories, vec!["@write", "@string", "@slow"]);
+    assert_eq!(x[1], redis::Value::Nil);
+
+    // a container lists its subcommands, with the positions of their keys
+    let subcommands: Vec<(String, i64, Vec<String>, i64, i64, i64)> = match &x[2] {
+        redis::Value::Bulk(info) => match &info[9] {
+            redis::Value::Bulk(subcommands) => subcommands
+                .iter()
+                .map(|subcommand| match subcommand {
+                    redis::Value::Bulk(info) => {
+                        redis::from_redis_value(&redis::Value::Bulk(info[..6].to_vec())).unwrap()
+                    }
+                    _ => panic!("subcommands should be described"),
+                })
+                .collect(),
+            _ => panic!("XGROUP should list its subcommands"),
+        },
+        _ => panic!("COMMAND INFO should describe XGROUP"),
+    };
+    assert_eq!(subcommands.len(), 5);
+    assert_eq!(
+        subcommands[0],
+        (
+            "xgroup|create".to_string(),
+            -5,
+            vec!["write".to_string(), "denyoom".to_string()],
+            2,
+            2,
+            1
+        )
+    );
+
+    let x: Vec<redis::Value> = cmd("COMMAND")
+        .arg(&["DOCS", "zadd", "unknown"])
+        .query(&mut con)
+        .unwrap();
+    let (name, docs): (String, Vec<String>) =
+        redis::from_redis_value(&redis::Value::Bulk(x)).unwrap();
+    assert_eq!(name, "zadd");
+    assert_eq!(docs, vec!["group", "sorted-set"]);
+
+    let x: RedisResult<()> = cmd("COMMAND").arg("UNKNOWN").query(&mut con);
+    assert!(x.is_err());
+
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+}
+
+#[test]
+#[serial]
+fn command_getkeys() {
+    let port = 3457;
+    let server = Server::new(InMemoryStorage::new(), port);
+    assert_eq!(server.start(), Some(ServerState::Started));
+    let redis_client = redis::Client::open(format!("redis://127.0.0.1:{}/", port)).unwrap();
+    let mut con = redis_client.get_connection().unwrap();
+
+    let getkeys = |con: &mut redis::Connection, args: &[&str]| -> RedisResult<Vec<String>> {
+        cmd("COMMAND").arg("GETKEYS").arg(args).query(con)
+    };
+
+    // keys at fixed positions
+    let x = getkeys(&mut con, &["MSET", "a", "1", "b", "2"]).unwrap();
+    assert_eq!(x, vec!["a", "b"]);
+    let x = getkeys(&mut con, &["del", "a", "b", "c"]).unwrap();
+    assert_eq!(x, vec!["a", "b", "c"]);
+    let x = getkeys(&mut con, &["BZPOPMIN", "a", "b", "0"]).unwrap();
+    assert_eq!(x, vec!["a", "b"]);
+    let x = getkeys(&mut con, &["XGROUP", "CREATE", "stream", "group", "$"]).unwrap();
+    assert_eq!(x, vec!["stream"]);
+
+    // keys found by parsing the command
+    let x = getkeys(
+        &mut con,
+        &["ZUNIONSTORE", "dest", "2", "a", "b", "WEIGHTS", "1", "2"],
+    )
+    .unwrap();
+    assert_eq!(x, vec!["dest", "a", "b"]);
+    let x = getkeys(&mut con, &["EVAL", "return 1", "1", "a", "arg"]).unwrap();
+    assert_eq!(x, vec!["a"]);
+    let x = getkeys(
+        &mut con,
+        &["XREAD", "COUNT", "1", "STREAMS", "a", "b", "0", "0"],
+    )
+    .unwrap();
+    assert_eq!(x, vec!["a", "b"]);
+
+    assert!(getkeys(&mut con, &["PING"]).is_err());
+    assert!(getkeys(&mut con, &["UNKNOWN", "a"]).is_err());
+    assert!(getkeys(&mut con, &["GET"]).is_err());
+    assert!(getkeys(&mut con, &["ZUNION", "two", "a"]).is_err());
+
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+}
+
+#[test]
+#[serial]
+fn config() {
+    let port = 3458;
+    let server = ServerBuilder::new(InMemoryStorage::new(), port)
+        .databases(4)
+        .build();
+    assert_eq!(server.start(), Some(ServerState::Started));
+    let redis_client = redis::Client::open(format!("redis://127.0.0.1:{}/", port)).unwrap();
+    let mut con = redis_client.get_connection().unwrap();
+
+    let x: Vec<String> = cmd("CONFIG")
+        .arg(&["GET", "maxmemory*", "DATABASES", "port"])
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(
+        x,
+        vec![
+            "databases",
+            "4",
+            "maxmemory",
+            "0",
+            "maxmemory-policy",
+            "noeviction",
+            "maxmemory-samples",
+            "5",
+            "port",
+            "3458",
+        ]
+    );
+
+    // the append only file is written to a directory of its own
+    let dir = std::env::temp_dir().join(format!("redisless-config-{}", port));
+    std::fs::create_dir_all(&dir).unwrap();
+    let _: () = cmd("CONFIG")
+        .arg(&[
+            "SET",
+            "dir",
+            dir.to_str().unwrap(),
+            "maxmemory",
+            "100mb",
+            "timeout",
+            "30",
+            "appendonly",
+            "YES",
+            "save",
+            "900 1  300 10",
+        ])
+        .query(&mut con)
+        .unwrap();
+    let x: Vec<String> = cmd("CONFIG")
+        .arg(&["GET", "maxmemory", "timeout", "appendonly", "save"])
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(
+        x,
+        vec![
+            "appendonly",
+            "yes",
+            "maxmemory",
+            "104857600",
+            "save",
+            "900 1 300 10",
+            "timeout",
+            "30",
+        ]
+    );
+
+    // nothing is set unless every parameter is valid
+    let invalid: &[&[&str]] = &[
+        &["timeout", "-1"],
+        &["timeout", "forever"],
+        &["maxmemory", "100zb"],
+        &["appendonly", "maybe"],
+        &["maxmemory-policy", "allkeys-fifo"],
+        &["save", "900"],
+        &["databases", "8"],
+        &["unknown", "1"],
+        &["timeout", "10", "timeout", "20"],
+    ];
+    for parameters in invalid {
+        let x: RedisResult<()> = cmd("CONFIG")
+            .arg("SET")
+            .arg(&["hz", "20"])
+            .arg(*parameters)
+            .query(&mut con);
+        assert!(x.is_err());
+    }
+    let x: Vec<String> = cmd("CONFIG")
+        .arg(&["GET", "hz", "timeout"])
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(x, vec!["hz", "10", "timeout", "30"]);
+
+    // turning the append only file on rewrote it in the background
+    loop {
+        let x: redis::InfoDict = cmd("INFO").arg("persistence").query(&mut con).unwrap();
+        if x.get::<i64>("aof_rewrite_in_progress") == Some(0) {
+            break;
+        }
+        sleep(Duration::from_millis(10));
+    }
+    assert!(dir.join("appendonly.aof").exists());
+    std::fs::remove_dir_all(&dir).unwrap();
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+}
+
+#[test]
+#[serial]
+fn config_file() {
+    let port = 3459;
+    let dir = std::env::temp_dir();
+    let included = dir.join("redisless-included.conf");
+    std::fs::write(&included, "maxmemory 2mb\nmaxmemory-policy allkeys-lru\n").unwrap();
+    let path = dir.join("redisless.conf");
+    std::fs::write(
+        &path,
+        format!(
+            "# a redis.conf for local development\n\
+             port {}\n\
+             databases 2\n\
+             \n\
+             save 900 1\n\
+             save 300 10\n\
+             appendonly yes\n\
+             dir {}\n\
+             dbfilename \"local dump.rdb\"\n\
+             notify-keyspace-events Ex\n\
+             requirepass secret\n\
+             user reader on >reader ~* +get\n\
+             supervised no\n\
+             include {}\n",
+            port,
+            dir.display(),
+            included.display()
+        ),
+    )
+    .unwrap();
+
+    let server = ServerBuilder::new(InMemoryStorage::new(), 6379)
+        .config_file(&path)
+        .unwrap()
+        .build();
+    assert_eq!(server.start(), Some(ServerState::Started));
+    let redis_client = redis::Client::open(format!("redis://127.0.0.1:{}/", port)).unwrap();
+    let mut con = redis_client.get_connection().unwrap();
+
+    let x: RedisResult<String> = con.get("key");
+    assert!(x.is_err());
+    let _: () = cmd("AUTH").arg("secret").query(&mut con).unwrap();
+    let x: RedisResult<()> = cmd("SELECT").arg(2).query(&mut con);
+    assert!(x.is_err());
+
+    let x: Vec<String> = cmd("CONFIG")
+        .arg(&[
+            "GET",
+            "save",
+            "appendonly",
+            "dbfilename",
+            "notify-keyspace-events",
+            "maxmemory*",
+        ])
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(
+        x,
+        vec![
+            "appendonly",
+            "yes",
+            "dbfilename",
+            "local dump.rdb",
+            "maxmemory",
+            "2097152",
+            "maxmemory-policy",
+            "allkeys-lru",
+            "maxmemory-samples",
+            "5",
+            "notify-keyspace-events",
+            "xE",
+            "save",
+            "900 1 300 10",
+        ]
+    );
+
+    let mut reader = redis_client.get_connection().unwrap();
+    let _: () = cmd("AUTH")
+        .arg(&["reader", "reader"])
+        .query(&mut reader)
+        .unwrap();
+    let x: RedisResult<()> = reader.set("key", "value");
+    assert!(x.is_err());
+
+    // a value the parameter does not accept, or an unclosed quote
+    std::fs::write(&path, "port 3459\nappendonly maybe\n").unwrap();
+    assert!(ServerBuilder::new(InMemoryStorage::new(), port)
+        .config_file(&path)
+        .is_err());
+    std::fs::write(&path, "dbfilename \"dump.rdb\n").unwrap();
+    assert!(ServerBuilder::new(InMemoryStorage::new(), port)
+        .config_file(&path)
+        .is_err());
+
+    let _ = std::fs::remove_file(&path);
+    let _ = std::fs::remove_file(&included);
+    let _ = std::fs::remove_file(dir.join("appendonly.aof"));
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+}
+
+#[test]
+#[serial]
+fn monitor() {
+    let port = 3460;
+    let server = Server::new(InMemoryStorage::new(), port);
+    assert_eq!(server.start(), Some(ServerState::Started));
+    let redis_client = redis::Client::open(format!("redis://127.0.0.1:{}/", port)).unwrap();
+    let mut con = redis_client.get_connection().unwrap();
+
+    let mut monitor = TcpStream::connect(("127.0.0.1", port)).unwrap();
+    monitor
+        .set_read_timeout(Some(Duration::from_secs(5)))
+        .unwrap();
+    let mut reader = BufReader::new(monitor.try_clone().unwrap());
+    monitor.write_all(b"*1\r\n$7\r\nMONITOR\r\n").unwrap();
+    let mut line = String::new();
+    reader.read_line(&mut line).unwrap();
+    assert_eq!(line, "+OK\r\n");
+
+    let _: () = cmd("SELECT").arg(1).query(&mut con).unwrap();
+    let _: () = con.set("key", "a \"quoted\"\nvalue").unwrap();
+    let _: () = cmd("AUTH").arg("secret").query(&mut con).unwrap_or(());
+    let _: () = cmd("CONFIG")
+        .arg(&["SET", "timeout", "0"])
+        .query(&mut con)
+        .unwrap();
+    let mut lines = vec![];
+    for _ in 0..3 {
+        let mut line = String::new();
+        reader.read_line(&mut line).unwrap();
+        // the timestamp is left out
+        let (timestamp, line) = line.split_at(line.find(' ').unwrap());
+        assert!(timestamp.starts_with('+'));
+        lines.push(line.trim_end().to_string());
+    }
+    let addr = lines[0][4..lines[0].find(']').unwrap()].to_string();
+    assert_eq!(
+        lines,
+        vec![
+            format!(" [0 {}] \"SELECT\" \"1\"", addr),
+            format!(" [1 {}] \"SET\" \"key\" \"a \\\"quoted\\\"\\nvalue\"", addr),
+            format!(" [1 {}] \"AUTH\" \"(redacted)\"", addr),
+        ]
+    );
+
+    // the commands run by a script follow it
+    #[cfg(feature = "scripting")]
+    {
+        let _: () = cmd("EVAL")
+            .arg(&["return redis.call('DEL', KEYS[1])", "1", "key"])
+            .query(&mut con)
+            .unwrap_or(());
+        let mut lines = vec![];
+        for _ in 0..2 {
+            let mut line = String::new();
+            reader.read_line(&mut line).unwrap();
+            lines.push(line[line.find(' ').unwrap()..].trim_end().to_string());
+        }
+        assert_eq!(
+            lines,
+            vec![
+                format!(
+                    " [1 {}] \"EVAL\" \"return redis.call('DEL', KEYS[1])\" \"1\" \"key\"",
+                    addr
+                ),
+                " [1 lua] \"DEL\" \"key\"".to_string(),
+            ]
+        );
+    }
+
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+}
+
+#[test]
+#[serial]
+fn slowlog() {
+    let port = 3461;
+    let server = Server::new(InMemoryStorage::new(), port);
+    assert_eq!(server.start(), Some(ServerState::Started));
+    let redis_client = redis::Client::open(format!("redis://127.0.0.1:{}/", port)).unwrap();
+    let mut con = redis_client.get_connection().unwrap();
+
+    let _: () = con.set("key", "value").unwrap();
+    let x: usize = cmd("SLOWLOG").arg("LEN").query(&mut con).unwrap();
+    assert_eq!(x, 0);
+
+    // every command is slow from now on
+    let _: () = cmd("CONFIG")
+        .arg(&["SET", "slowlog-log-slower-than", "0"])
+        .query(&mut con)
+        .unwrap();
+    let _: () = cmd("CLIENT")
+        .arg(&["SETNAME", "perf"])
+        .query(&mut con)
+        .unwrap();
+    let _: () = con.set("key", "x".repeat(200)).unwrap();
+    let args = (0..40).map(|i| i.to_string()).collect::<Vec<_>>();
+    let _: usize = con.sadd("set", &args).unwrap();
+
+    // SLOWLOG LEN itself is recorded once it replied
+    let x: usize = cmd("SLOWLOG").arg("LEN").query(&mut con).unwrap();
+    assert_eq!(x, 4);
+    let x = slowlog_entries(cmd("SLOWLOG").arg("GET").arg(3).query(&mut con).unwrap());
+    assert_eq!(x.len(), 3);
+    assert_eq!(x[0].3, vec!["SLOWLOG", "LEN"]);
+    let (id, _, _, sadd, _, name) = &x[1];
+    assert_eq!(*id, 3);
+    assert_eq!(sadd.len(), 32);
+    assert_eq!(sadd[..3], ["SADD", "set", "0"]);
+    assert_eq!(sadd[31], "... (11 more arguments)");
+    assert_eq!(name, "perf");
+    let (id, _, _, set, _, _) = &x[2];
+    assert_eq!(*id, 2);
+    assert_eq!(set[2], format!("{}... (72 more bytes)", "x".repeat(128)));
+
+    // the oldest entries are dropped past the maximum length
+    let _: () = cmd("CONFIG")
+        .arg(&["SET", "slowlog-max-len", "2"])
+        .query(&mut con)
+        .unwrap();
+    let x = slowlog_entries(cmd("SLOWLOG").arg("GET").arg(-1).query(&mut con).unwrap());
+    assert_eq!(x.len(), 2);
+    assert_eq!(x[0].3, vec!["CONFIG", "SET", "slowlog-max-len", "2"]);
+
+    let _: () = cmd("SLOWLOG").arg("RESET").query(&mut con).unwrap();
+    let _: () = cmd("CONFIG")
+        .arg(&["SET", "slowlog-log-slower-than", "-1"])
+        .query(&mut con)
+        .unwrap();
+    let _: () = con.set("key", "value").unwrap();
+    let x: usize = cmd("SLOWLOG").arg("LEN").query(&mut con).unwrap();
+    assert_eq!(x, 1);
+
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+}
+
+type SlowLogEntry = (u64, u64, u64, Vec<String>, String, String);
+
+fn slowlog_entries(entries: Vec<redis::Value>) -> Vec<SlowLogEntry> {
+    entries
+        .iter()
+        .map(|entry| redis::from_redis_value(entry).unwrap())
+        .collect()
+}
+
+#[test]
+#[serial]
+fn debug() {
+    let port = 3462;
+    let server = Server::new(InMemoryStorage::new(), port);
+    assert_eq!(server.start(), Some(ServerState::Started));
+    let redis_client = redis::Client::open(format!("redis://127.0.0.1:{}/", port)).unwrap();
+    let mut con = redis_client.get_connection().unwrap();
+
+    let started = Instant::now();
+    let _: () = cmd("DEBUG").arg(&["SLEEP", "0.2"]).query(&mut con).unwrap();
+    assert!(started.elapsed() >= Duration::from_millis(200));
+    let x: RedisResult<()> = cmd("DEBUG").arg(&["SLEEP", "soon"]).query(&mut con);
+    assert!(x.is_err());
+    for seconds in &["inf", "1e30"] {
+        let x: RedisResult<()> = cmd("DEBUG").arg(&["SLEEP", seconds]).query(&mut con);
+        assert!(x
+            .unwrap_err()
+            .to_string()
+            .contains("timeout is out of range"));
+    }
+
+    let _: () = con.set("counter", 12).unwrap();
+    let _: () = con.set("short", "value").unwrap();
+    let _: () = con.set("long", "x".repeat(50)).unwrap();
+    let _: usize = con.sadd("integers", &[1, 2, 3]).unwrap();
+    let _: usize = con.sadd("members", &["a", "b"]).unwrap();
+    let _: usize = con.zadd("ranking", "player", 1).unwrap();
+    let _: usize = con.hset("hash", "field", "x".repeat(65)).unwrap();
+
+    let encoding = |con: &mut redis::Connection, k: &str| {
+        let x: String = cmd("DEBUG").arg("OBJECT").arg(k).query(con).unwrap();
+        assert!(x.starts_with("Value at:"));
+        x.split(' ')
+            .find_map(|field| field.strip_prefix("encoding:"))
+            .unwrap()
+            .to_string()
+    };
+    assert_eq!(encoding(&mut con, "counter"), "int");
+    assert_eq!(encoding(&mut con, "short"), "embstr");
+    assert_eq!(encoding(&mut con, "long"), "raw");
+    assert_eq!(encoding(&mut con, "integers"), "intset");
+    assert_eq!(encoding(&mut con, "members"), "listpack");
+    assert_eq!(encoding(&mut con, "ranking"), "listpack");
+    assert_eq!(encoding(&mut con, "hash"), "hashtable");
+    let x: RedisResult<String> = cmd("DEBUG").arg(&["OBJECT", "missing"]).query(&mut con);
+    assert!(x.is_err());
+
+    let _: () = cmd("DEBUG")
+        .arg(&["SET-ACTIVE-EXPIRE", "0"])
+        .query(&mut con)
+        .unwrap();
+    let x: RedisResult<()> = cmd("DEBUG")
+        .arg(&["SET-ACTIVE-EXPIRE", "2"])
+        .query(&mut con);
+    assert!(x.is_err());
+    let _: () = cmd("DEBUG").arg("JMAP").query(&mut con).unwrap();
+
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+}
+
+#[test]
+#[serial]
+fn shutdown() {
+    let port = 3463;
+    let server = Server::new(InMemoryStorage::new(), port);
+    assert_eq!(server.start(), Some(ServerState::Started));
+    let redis_client = redis::Client::open(format!("redis://127.0.0.1:{}/", port)).unwrap();
+    let mut con = redis_client.get_connection().unwrap();
+    let mut other = redis_client.get_connection().unwrap();
+
+    // the connection is closed without a reply
+    let x: Result<(), _> = cmd("SHUTDOWN").arg("NOSAVE").query(&mut con);
+    assert!(x.is_err());
+    sleep(Duration::from_millis(100));
+
+    // the other connections are closed and no new one is accepted
+    let x: Result<String, _> = cmd("PING").query(&mut other);
+    assert!(x.is_err());
+    assert!(TcpStream::connect(("127.0.0.1", port)).is_err());
+
+    // the server can be started again by the embedder
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+    assert_eq!(server.start(), Some(ServerState::Started));
+    let mut con = redis_client.get_connection().unwrap();
+    let x: String = cmd("PING").query(&mut con).unwrap();
+    assert_eq!(x, "PONG");
+
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+}
+
+#[test]
+#[serial]
+fn latency() {
+    let port = 3464;
+    let server = Server::new(InMemoryStorage::new(), port);
+    assert_eq!(server.start(), Some(ServerState::Started));
+    let redis_client = redis::Client::open(format!("redis://127.0.0.1:{}/", port)).unwrap();
+    let mut con = redis_client.get_connection().unwrap();
+
+    let x: String = cmd("LATENCY").arg("DOCTOR").query(&mut con).unwrap();
+    assert!(x.contains("Latency monitoring is disabled"));
+    let _: () = cmd("DEBUG")
+        .arg(&["SLEEP", "0.02"])
+        .query(&mut con)
+        .unwrap();
+    let x: Vec<redis::Value> = cmd("LATENCY").arg("LATEST").query(&mut con).unwrap();
+    assert!(x.is_empty());
+
+    let _: () = cmd("CONFIG")
+        .arg(&["SET", "latency-monitor-threshold", "10"])
+        .query(&mut con)
+        .unwrap();
+    let _: () = cmd("DEBUG")
+        .arg(&["SLEEP", "0.02"])
+        .query(&mut con)
+        .unwrap();
+    let _: () = con.set("key", "value").unwrap();
+    let x: Vec<redis::Value> = cmd("LATENCY").arg("LATEST").query(&mut con).unwrap();
+    assert_eq!(x.len(), 1);
+    let (event, time, latest, max): (String, u64, u64, u64) =
+        redis::from_redis_value(&x[0]).unwrap();
+    assert_eq!(event, "command");
+    assert!(latest >= 20);
+    assert_eq!(latest, max);
+    let x: Vec<Vec<u64>> = cmd("LATENCY")
+        .arg(&["HISTORY", "command"])
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(x, vec![vec![time, latest]]);
+    let x: Vec<Vec<u64>> = cmd("LATENCY")
+        .arg(&["HISTORY", "fast-command"])
+        .query(&mut con)
+        .unwrap();
+    assert!(x.is_empty());
+    let x: String = cmd("LATENCY").arg("DOCTOR").query(&mut con).unwrap();
+    assert!(x.contains("1. command: 1 latency spikes"));
+
+    let x: usize = cmd("LATENCY")
+        .arg(&["RESET", "command", "unknown"])
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(x, 1);
+    let x: Vec<redis::Value> = cmd("LATENCY").arg("LATEST").query(&mut con).unwrap();
+    assert!(x.is_empty());
+    let x: String = cmd("LATENCY").arg("DOCTOR").query(&mut con).unwrap();
+    assert!(x.contains("no latency spike was observed"));
+
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+}
+
+#[test]
+#[serial]
+fn memory() {
+    let port = 3465;
+    let server = Server::new(InMemoryStorage::new(), port);
+    assert_eq!(server.start(), Some(ServerState::Started));
+    let redis_client = redis::Client::open(format!("redis://127.0.0.1:{}/", port)).unwrap();
+    let mut con = redis_client.get_connection().unwrap();
+
+    let x: Option<usize> = cmd("MEMORY")
+        .arg(&["USAGE", "key"])
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(x, None);
+    let _: () = con.set("key", "x".repeat(300)).unwrap();
+    let x: usize = cmd("MEMORY")
+        .arg(&["USAGE", "key", "SAMPLES", "0"])
+        .query(&mut con)
+        .unwrap();
+    assert!(x > 300);
+    let x: Result<usize, _> = cmd("MEMORY")
+        .arg(&["USAGE", "key", "SAMPLES", "many"])
+        .query(&mut con);
+    assert!(x.is_err());
+
+    let _: () = cmd("SELECT").arg(2).query(&mut con).unwrap();
+    let _: usize = con.sadd("set", &["a", "b", "c"]).unwrap();
+    let x: HashMap<String, redis::Value> = cmd("MEMORY").arg("STATS").query(&mut con).unwrap();
+    assert_eq!(x["keys.count"], redis::Value::Int(2));
+    assert!(x.contains_key("db.0"));
+    assert!(x.contains_key("db.2"));
+    assert!(!x.contains_key("db.1"));
+    let total: usize = redis::from_redis_value(&x["total.allocated"]).unwrap();
+    let overhead: usize = redis::from_redis_value(&x["overhead.total"]).unwrap();
+    let dataset: usize = redis::from_redis_value(&x["dataset.bytes"]).unwrap();
+    assert_eq!(total, overhead + dataset);
+
+    let x: String = cmd("MEMORY").arg("DOCTOR").query(&mut con).unwrap();
+    assert!(x.contains("using very little memory"));
+
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+}
+
+#[test]
+#[serial]
+fn lolwut() {
+    let port = 3466;
+    let server = Server::new(InMemoryStorage::new(), port);
+    assert_eq!(server.start(), Some(ServerState::Started));
+    let redis_client = redis::Client::open(format!("redis://127.0.0.1:{}/", port)).unwrap();
+    let mut con = redis_client.get_connection().unwrap();
+
+    let x: String = cmd("LOLWUT").query(&mut con).unwrap();
+    assert!(x.ends_with(&format!("ver. {}\n", env!("CARGO_PKG_VERSION"))));
+    let y: String = cmd("LOLWUT")
+        .arg(&["VERSION", "5"])
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(x, y);
+    let x: Result<String, _> = cmd("LOLWUT").arg(&["VERSION", "five"]).query(&mut con);
+    assert!(x.is_err());
+    let x: Vec<Vec<redis::Value>> = cmd("COMMAND")
+        .arg(&["INFO", "lolwut"])
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(x[0][0], redis::Value::Data(b"lolwut".to_vec()));
+
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+}
+
+#[test]
+#[serial]
+fn time() {
+    let port = 3467;
+    let server = Server::new(InMemoryStorage::new(), port);
+    assert_eq!(server.start(), Some(ServerState::Started));
+    let redis_client = redis::Client::open(format!("redis://127.0.0.1:{}/", port)).unwrap();
+    let mut con = redis_client.get_connection().unwrap();
+
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
+    let (secs, micros): (u64, u64) = cmd("TIME").query(&mut con).unwrap();
+    assert!(secs.max(now.as_secs()) - secs.min(now.as_secs()) <= 1);
+    assert!(micros < 1_000_000);
+    let x: Result<(u64, u64), _> = cmd("TIME").arg("now").query(&mut con);
+    assert!(x.is_err());
+
+    #[cfg(feature = "scripting")]
+    {
+        let x: Vec<u64> = cmd("EVAL")
+            .arg("return redis.call('TIME')")
+            .arg(0)
+            .query(&mut con)
+            .unwrap();
+        assert_eq!(x.len(), 2);
+        assert!(x[0] >= secs);
+    }
+
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+}
+
+#[test]
+#[serial]
+fn ping_and_echo() {
+    let port = 3468;
+    let server = Server::new(InMemoryStorage::new(), port);
+    assert_eq!(server.start(), Some(ServerState::Started));
+    let redis_client = redis::Client::open(format!("redis://127.0.0.1:{}/", port)).unwrap();
+    let mut con = redis_client.get_connection().unwrap();
+
+    let x: String = cmd("PING").query(&mut con).unwrap();
+    assert_eq!(x, "PONG");
+    let x: String = cmd("PING")
+        .arg("healthcheck-token")
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(x, "healthcheck-token");
+    let x: Result<String, _> = cmd("PING").arg(&["a", "b"]).query(&mut con);
+    assert!(x.is_err());
+
+    let x: String = cmd("ECHO").arg("hello world").query(&mut con).unwrap();
+    assert_eq!(x, "hello world");
+    let x: Vec<u8> = cmd("ECHO").arg(&[0u8, 255][..]).query(&mut con).unwrap();
+    assert_eq!(x, vec![0, 255]);
+    let x: Result<String, _> = cmd("ECHO").query(&mut con);
+    assert!(x.is_err());
+
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+}
+
+#[test]
+#[serial]
+fn hello() {
+    let port = 3469;
+    let server = ServerBuilder::new(InMemoryStorage::new(), port)
+        .requirepass("secret")
+        .build();
+    assert_eq!(server.start(), Some(ServerState::Started));
+    let redis_client = redis::Client::open(format!("redis://:secret@127.0.0.1:{}/", port)).unwrap();
+    let mut con = redis_client.get_connection().unwrap();
+
+    // the server is described by a map, flattened in RESP2
+    let x: HashMap<String, redis::Value> = cmd("HELLO").query(&mut con).unwrap();
+    assert_eq!(x["server"], redis::Value::Data(b"redis".to_vec()));
+    assert_eq!(x["proto"], redis::Value::Int(2));
+    assert_eq!(x["mode"], redis::Value::Data(b"standalone".to_vec()));
+    let x: Result<redis::Value, _> = cmd("HELLO").arg(4).query(&mut con);
+    assert_eq!(x.unwrap_err().code(), Some("NOPROTO"));
+
+    let mut stream = TcpStream::connect(("127.0.0.1", port)).unwrap();
+    stream
+        .set_read_timeout(Some(Duration::from_secs(5)))
+        .unwrap();
+    let mut request = |args: &[&str]| resp_request(&mut stream, args);
+
+    assert!(request(&["HELLO", "3"]).starts_with("-NOAUTH HELLO must be called"));
+    assert!(request(&["HELLO", "3", "AUTH", "default", "wrong"]).starts_with("-WRONGPASS"));
+    let x = request(&[
+        "HELLO", "3", "AUTH", "default", "secret", "SETNAME", "resp3",
+    ]);
+    assert!(x.starts_with("%7\r\n$6\r\nserver\r\n$5\r\nredis\r\n"));
+    assert!(x.contains("$5\r\nproto\r\n:3\r\n"));
+    assert_eq!(request(&["CLIENT", "GETNAME"]), "$5\r\nresp3\r\n");
+    assert_eq!(request(&["GET", "missing"]), "_\r\n");
+    assert!(request(&["CLIENT", "INFO"]).contains(" resp=3"));
+
+    // messages are pushed, and any command runs while subscribed
+    assert_eq!(
+        request(&["SUBSCRIBE", "news"]),
+        ">3\r\n$9\r\nsubscribe\r\n$4\r\nnews\r\n:1\r\n"
+    );
+    assert_eq!(request(&["SET", "key", "value"]), "+OK\r\n");
+    assert_eq!(request(&["PING"]), "+PONG\r\n");
+    let _: usize = con.publish("news", "hello").unwrap();
+    assert_eq!(
+        resp_read(&mut stream),
+        ">3\r\n$7\r\nmessage\r\n$4\r\nnews\r\n$5\r\nhello\r\n"
+    );
+
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+}
+
+#[test]
+#[serial]
+fn reset() {
+    let port = 3470;
+    let server = ServerBuilder::new(InMemoryStorage::new(), port)
+        .requirepass("secret")
+        .build();
+    assert_eq!(server.start(), Some(ServerState::Started));
+    let redis_client = redis::Client::open(format!("redis://:secret@127.0.0.1:{}/", port)).unwrap();
+    let mut con = redis_client.get_connection().unwrap();
+
+    let mut stream = TcpStream::connect(("127.0.0.1", port)).unwrap();
+    stream
+        .set_read_timeout(Some(Duration::from_secs(5)))
+        .unwrap();
+    let mut request = |args: &[&str]| resp_request(&mut stream, args);
+
+    assert!(request(&["HELLO", "3", "AUTH", "default", "secret"]).starts_with("%7\r\n"));
+    assert_eq!(request(&["SELECT", "1"]), "+OK\r\n");
+    assert_eq!(request(&["CLIENT", "SETNAME", "before"]), "+OK\r\n");
+    assert_eq!(request(&["MULTI"]), "+OK\r\n");
+    assert_eq!(request(&["SET", "key", "value"]), "+QUEUED\r\n");
+
+    // the transaction is discarded rather than queuing RESET
+    assert_eq!(request(&["RESET"]), "+RESET\r\n");
+    assert!(request(&["GET", "key"]).starts_with("-NOAUTH"));
+    assert_eq!(request(&["AUTH", "secret"]), "+OK\r\n");
+    assert_eq!(request(&["EXEC"]), "-ERR EXEC without MULTI\r\n");
+    assert_eq!(request(&["GET", "missing"]), "$-1\r\n");
+    assert_eq!(request(&["CLIENT", "GETNAME"]), "$-1\r\n");
+    assert!(request(&["CLIENT", "INFO"]).contains(" db=0 "));
+
+    // the subscriptions are dropped, so the other commands run again
+    assert_eq!(
+        request(&["SUBSCRIBE", "news"]),
+        "*3\r\n$9\r\nsubscribe\r\n$4\r\nnews\r\n:1\r\n"
+    );
+    assert!(request(&["GET", "key"]).starts_with("-ERR Can't execute 'get'"));
+    assert_eq!(request(&["RESET"]), "+RESET\r\n");
+    assert_eq!(request(&["AUTH", "secret"]), "+OK\r\n");
+    assert_eq!(request(&["GET", "key"]), "$-1\r\n");
+    let x: usize = con.publish("news", "hello").unwrap();
+    assert_eq!(x, 0);
+
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+}
+
+#[test]
+#[serial]
+fn wait() {
+    let port = 3471;
+    let server = ServerBuilder::new(InMemoryStorage::new(), port).build();
+    assert_eq!(server.start(), Some(ServerState::Started));
+    let redis_client = redis::Client::open(format!("redis://127.0.0.1:{}/", port)).unwrap();
+    let mut con = redis_client.get_connection().unwrap();
+
+    let _: () = con.set("key", "value").unwrap();
+    let x: usize = cmd("WAIT").arg(1).arg(100).query(&mut con).unwrap();
+    assert_eq!(x, 0);
+    // a standalone server does not block, even with no timeout
+    let x: usize = cmd("WAIT").arg(0).arg(0).query(&mut con).unwrap();
+    assert_eq!(x, 0);
+    let x: Result<usize, _> = cmd("WAIT").arg(1).arg(-1).query(&mut con);
+    assert!(x.unwrap_err().to_string().contains("timeout is negative"));
+
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+}
+
+#[test]
+#[serial]
+fn info() {
+    let port = 3472;
+    let server = Server::new(InMemoryStorage::new(), port);
+    assert_eq!(server.start(), Some(ServerState::Started));
+    let redis_client = redis::Client::open(format!("redis://127.0.0.1:{}/", port)).unwrap();
+    let mut con = redis_client.get_connection().unwrap();
+
+    let _: () = con.set("key", "value").unwrap();
+    let _: () = con.set_ex("volatile", "value", 100).unwrap();
+    let _: () = con.pset_ex("expiring", "value", 1).unwrap();
+    sleep(Duration::from_millis(2));
+    let x: Option<String> = con.get("expiring").unwrap();
+    assert_eq!(x, None);
+
+    let x: redis::InfoDict = cmd("INFO").query(&mut con).unwrap();
+    assert_eq!(x.get("redis_version"), Some("7.4.0".to_string()));
+    assert_eq!(x.get("tcp_port"), Some(port));
+    assert_eq!(x.get("connected_clients"), Some(1));
+    assert!(x.get::<usize>("used_memory").unwrap() > 0);
+    assert!(x.get::<usize>("total_commands_processed").unwrap() >= 4);
+    assert_eq!(x.get("expired_keys"), Some(1));
+    assert_eq!(x.get("keyspace_hits"), Some(0));
+    assert_eq!(x.get("keyspace_misses"), Some(1));
+    let db0: String = x.get("db0").unwrap();
+    assert!(db0.starts_with("keys=2,expires=1,avg_ttl="));
+    assert_eq!(x.get::<String>("db1"), None);
+
+    // only the sections asked for are replied
+    let x: String = cmd("INFO").arg("keyspace").query(&mut con).unwrap();
+    assert!(x.starts_with("# Keyspace\r\ndb0:keys=2,"));
+
+    // the keys read count as hits or misses, the keys written do not
+    let _: Vec<Option<String>> = con.get(&["key", "volatile", "missing"]).unwrap();
+    let _: usize = cmd("HSTRLEN")
+        .arg("missing")
+        .arg("field")
+        .query(&mut con)
+        .unwrap();
+    let _: () = con.set("missing", "value").unwrap();
+    let x: redis::InfoDict = cmd("INFO").arg("stats").query(&mut con).unwrap();
+    assert_eq!(x.get("keyspace_hits"), Some(2));
+    assert_eq!(x.get("keyspace_misses"), Some(3));
+    let x: String = cmd("INFO")
+        .arg("CLIENTS")
+        .arg("memory")
+        .query(&mut con)
+        .unwrap();
+    assert!(x.starts_with("# Clients\r\n"));
+    assert!(x.contains("\r\n\r\n# Memory\r\n"));
+    assert!(!x.contains("# Server"));
+    let x: String = cmd("INFO").arg("unknown").query(&mut con).unwrap();
+    assert_eq!(x, "");
+
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+}
+
+#[test]
+#[serial]
+fn info_commandstats() {
+    let port = 3473;
+    let server = Server::new(InMemoryStorage::new(), port);
+    assert_eq!(server.start(), Some(ServerState::Started));
+    let redis_client = redis::Client::open(format!("redis://127.0.0.1:{}/", port)).unwrap();
+    let mut con = redis_client.get_connection().unwrap();
+
+    let _: () = con.set("key", "value").unwrap();
+    let _: String = con.get("key").unwrap();
+    let x: Result<(), _> = cmd("SELECT").arg(100).query(&mut con);
+    assert!(x.is_err());
+    let _: () = cmd("MULTI").query(&mut con).unwrap();
+    let _: () = cmd("SET").arg("key").arg("other").query(&mut con).unwrap();
+    let _: () = cmd("EXEC").query(&mut con).unwrap();
+
+    let mut stream = TcpStream::connect(("127.0.0.1", port)).unwrap();
+    stream
+        .set_read_timeout(Some(Duration::from_secs(5)))
+        .unwrap();
+    resp_request(&mut stream, &["SUBSCRIBE", "news"]);
+    assert!(resp_request(&mut stream, &["GET", "key"]).starts_with("-ERR Can't execute"));
+
+    let x: redis::InfoDict = cmd("INFO").arg("commandstats").query(&mut con).unwrap();
+    let stats = |name: &str| {
+        let stats: String = x.get(&format!("cmdstat_{}", name)).unwrap();
+        stats
+            .split(',')
+            .map(|field| {
+                let (name, value) = field.split_at(field.find('=').unwrap());
+                (name.to_string(), value[1..].to_string())
+            })
+            .collect::<HashMap<_, _>>()
+    };
+    // the commands of the transaction are counted once run by EXEC
+    assert_eq!(stats("set")["calls"], "2");
+    assert_eq!(stats("get")["calls"], "1");
+    assert_eq!(stats("get")["rejected_calls"], "1");
+    assert_eq!(stats("select")["failed_calls"], "1");
+    assert_eq!(stats("multi")["calls"], "1");
+    assert_eq!(stats("exec")["calls"], "1");
+    assert!(stats("set")["usec_per_call"].contains('.'));
+    assert_eq!(x.get::<String>("cmdstat_info"), None);
+
+    let x: String = cmd("INFO").arg("latencystats").query(&mut con).unwrap();
+    assert!(x.starts_with("# Latencystats\r\n"));
+    assert!(x.contains("\r\nlatency_percentiles_usec_set:p50="));
+    assert!(x.contains(",p99.9="));
+    // they are not replied by default
+    let x: String = cmd("INFO").query(&mut con).unwrap();
+    assert!(!x.contains("# Commandstats"));
+    let x: String = cmd("INFO").arg("all").query(&mut con).unwrap();
+    assert!(x.contains("# Commandstats\r\ncmdstat_"));
+
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+}
+
+#[test]
+#[serial]
+fn save() {
+    let port = 3474;
+    let server = Server::new(InMemoryStorage::new(), port);
+    assert_eq!(server.start(), Some(ServerState::Started));
+    let redis_client = redis::Client::open(format!("redis://127.0.0.1:{}/", port)).unwrap();
+    let mut con = redis_client.get_connection().unwrap();
+
+    let dir = std::env::temp_dir().join(format!("redisless-save-{}", port));
+    std::fs::create_dir_all(&dir).unwrap();
+    let _: () = cmd("CONFIG")
+        .arg("SET")
+        .arg("dir")
+        .arg(dir.to_str().unwrap())
+        .query(&mut con)
+        .unwrap();
+    let started: i64 = cmd("LASTSAVE").query(&mut con).unwrap();
+    assert!(started > 0);
+
+    let _: () = con.set("key", "value").unwrap();
+    let _: () = cmd("SELECT").arg(2).query(&mut con).unwrap();
+    let _: () = con.sadd("set", "member").unwrap();
+    let x: String = cmd("SAVE").query(&mut con).unwrap();
+    assert_eq!(x, "OK");
+    let x: i64 = cmd("LASTSAVE").query(&mut con).unwrap();
+    assert!(x >= started);
+    let file = std::fs::File::open(dir.join("dump.rdb")).unwrap();
+    let databases = crate::storage::rdb::read(&mut BufReader::new(file)).unwrap();
+    assert_eq!(databases[0][0].0, b"key");
+    assert_eq!(databases[2][0].0, b"set");
+
+    let _: () = con.set("other", "value").unwrap();
+    let x: String = cmd("BGSAVE").query(&mut con).unwrap();
+    assert_eq!(x, "Background saving started");
+    let mut persistence: redis::InfoDict;
+    loop {
+        persistence = cmd("INFO").arg("persistence").query(&mut con).unwrap();
+        if persistence.get::<i64>("rdb_bgsave_in_progress") == Some(0) {
+            break;
+        }
+        sleep(Duration::from_millis(10));
+    }
+    assert_eq!(
+        persistence.get::<String>("rdb_last_bgsave_status"),
+        Some("ok".to_string())
+    );
+    let file = std::fs::File::open(dir.join("dump.rdb")).unwrap();
+    let databases = crate::storage::rdb::read(&mut BufReader::new(file)).unwrap();
+    assert_eq!(databases[2].len(), 2);
+
+    // the server keeps running when the dataset can't be saved
+    let missing = dir.join("missing");
+    let _: () = cmd("CONFIG")
+        .arg("SET")
+        .arg("dir")
+        .arg(missing.to_str().unwrap())
+        .query(&mut con)
+        .unwrap();
+    let x: Result<(), _> = cmd("SAVE").query(&mut con);
+    assert!(x.is_err());
+    let x: Result<(), _> = cmd("SHUTDOWN").query(&mut con);
+    assert!(x
+        .unwrap_err()
+        .to_string()
+        .contains("Errors trying to SHUTDOWN"));
+    let x: String = cmd("PING").query(&mut con).unwrap();
+    assert_eq!(x, "PONG");
+
+    std::fs::remove_dir_all(&dir).unwrap();
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+}
+
+#[test]
+#[serial]
+fn load_snapshot() {
+    let port = 3475;
+    let dir = std::env::temp_dir().join(format!("redisless-load-{}", port));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("redisless.conf");
+    std::fs::write(
+        &path,
+        format!("dir {}\ndbfilename saved.rdb\n", dir.display()),
+    )
+    .unwrap();
+    let build = || {
+        ServerBuilder::new(InMemoryStorage::new(), port)
+            .config_file(&path)
+            .unwrap()
+            .build()
+    };
+
+    let server = build();
+    assert_eq!(server.start(), Some(ServerState::Started));
+    let redis_client = redis::Client::open(format!("redis://127.0.0.1:{}/", port)).unwrap();
+    let mut con = redis_client.get_connection().unwrap();
+    let _: () = con.set_ex("key", "value", 100).unwrap();
+    let _: () = cmd("SELECT").arg(1).query(&mut con).unwrap();
+    let _: () = con.zadd("zset", "member", 2).unwrap();
+    let _: () = cmd("SAVE").query(&mut con).unwrap();
+    drop(con);
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+
+    // the next server resumes from what was saved
+    let server = build();
+    assert_eq!(server.start(), Some(ServerState::Started));
+    let mut con = redis_client.get_connection().unwrap();
+    let x: String = con.get("key").unwrap();
+    assert_eq!(x, "value");
+    let x: redis::InfoDict = cmd("INFO").arg("keyspace").query(&mut con).unwrap();
+    assert!(x.get::<String>("db0").unwrap().contains("expires=1"));
+    let _: () = cmd("SELECT").arg(1).query(&mut con).unwrap();
+    let x: f64 = con.zscore("zset", "member").unwrap();
+    assert_eq!(x, 2.0);
+    drop(con);
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+
+    // a file that is not a snapshot keeps the server from starting
+    std::fs::write(dir.join("saved.rdb"), "not a snapshot").unwrap();
+    let server = build();
+    assert!(matches!(server.start(), Some(ServerState::Error(_))));
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+#[serial]
+fn save_points() {
+    let port = 3476;
+    let server = Server::new(InMemoryStorage::new(), port);
+    assert_eq!(server.start(), Some(ServerState::Started));
+    let redis_client = redis::Client::open(format!("redis://127.0.0.1:{}/", port)).unwrap();
+    let mut con = redis_client.get_connection().unwrap();
+
+    let dir = std::env::temp_dir().join(format!("redisless-save-points-{}", port));
+    std::fs::create_dir_all(&dir).unwrap();
+    let _: () = cmd("CONFIG")
+        .arg("SET")
+        .arg("dir")
+        .arg(dir.to_str().unwrap())
+        .arg("save")
+        .arg("1 2")
+        .query(&mut con)
+        .unwrap();
+    let changes = |con: &mut redis::Connection| {
+        let x: redis::InfoDict = cmd("INFO").arg("persistence").query(con).unwrap();
+        x.get::<u64>("rdb_changes_since_last_save").unwrap()
+    };
+
+    // a single change is not enough to save
+    let _: () = con.set("key", "value").unwrap();
+    sleep(Duration::from_millis(2100));
+    assert_eq!(changes(&mut con), 1);
+    assert!(!dir.join("dump.rdb").exists());
+
+    let _: () = con.sadd("set", "member").unwrap();
+    let start = Instant::now();
+    while changes(&mut con) > 0 {
+        assert!(start.elapsed() < Duration::from_secs(5));
+        sleep(Duration::from_millis(10));
+    }
+    assert!(dir.join("dump.rdb").exists());
+
+    std::fs::remove_dir_all(&dir).unwrap();
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+}
+
+#[test]
+#[serial]
+fn append_only() {
+    let port = 3477;
+    let dir = std::env::temp_dir().join(format!("redisless-aof-{}", port));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("redisless.conf");
+    std::fs::write(
+        &path,
+        format!("dir {}\nappendonly yes\nsave \"\"\n", dir.display()),
+    )
+    .unwrap();
+    let build = || {
+        ServerBuilder::new(InMemoryStorage::new(), port)
+            .config_file(&path)
+            .unwrap()
+            .build()
+    };
+    let rewrite_done = |con: &mut redis::Connection| loop {
+        let x: redis::InfoDict = cmd("INFO").arg("persistence").query(con).unwrap();
+        if x.get::<i64>("aof_rewrite_in_progress") == Some(0) {
+            return x;
+        }
+        sleep(Duration::from_millis(10));
+    };
+
+    let server = build();
+    assert_eq!(server.start(), Some(ServerState::Started));
+    let redis_client = redis::Client::open(format!("redis://127.0.0.1:{}/", port)).unwrap();
+    let mut con = redis_client.get_connection().unwrap();
+    let x: redis::InfoDict = cmd("INFO").arg("persistence").query(&mut con).unwrap();
+    assert_eq!(x.get::<i64>("aof_enabled"), Some(1));
+    let _: () = con.set("key", "value").unwrap();
+    let _: () = con.set("removed", "value").unwrap();
+    let _: () = con.del("removed").unwrap();
+    let _: () = cmd("MULTI").query(&mut con).unwrap();
+    let _: () = cmd("SADD").arg("set").arg("a").query(&mut con).unwrap();
+    let _: () = cmd("SADD").arg("set").arg("b").query(&mut con).unwrap();
+    let _: () = cmd("EXEC").query(&mut con).unwrap();
+    let _: () = cmd("SWAPDB").arg(0).arg(1).query(&mut con).unwrap();
+    drop(con);
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+
+    // the changes are replayed by the next server
+    let server = build();
+    assert_eq!(server.start(), Some(ServerState::Started));
+    let mut con = redis_client.get_connection().unwrap();
+    let _: () = cmd("SELECT").arg(1).query(&mut con).unwrap();
+    let x: String = con.get("key").unwrap();
+    assert_eq!(x, "value");
+    let x: bool = con.exists("removed").unwrap();
+    assert!(!x);
+    let x: usize = con.scard("set").unwrap();
+    assert_eq!(x, 2);
+
+    // the changes made during a rewrite are kept
+    let _: () = cmd("BGREWRITEAOF").query(&mut con).unwrap();
+    let _: () = con.set("other", "value").unwrap();
+    let x = rewrite_done(&mut con);
+    assert_eq!(
+        x.get::<String>("aof_last_bgrewrite_status"),
+        Some("ok".to_string())
+    );
+    let _: () = con.set("last", "value").unwrap();
+    drop(con);
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+
+    let server = build();
+    assert_eq!(server.start(), Some(ServerState::Started));
+    let mut con = redis_client.get_connection().unwrap();
+    let _: () = cmd("SELECT").arg(1).query(&mut con).unwrap();
+    let x: Vec<Option<String>> = con.get(&["key", "other", "last"]).unwrap();
+    assert_eq!(x, vec![Some("value".to_string()); 3]);
+
+    let _: () = cmd("CONFIG")
+        .arg("SET")
+        .arg("appendonly")
+        .arg("no")
+        .query(&mut con)
+        .unwrap();
+    let x: redis::InfoDict = cmd("INFO").arg("persistence").query(&mut con).unwrap();
+    assert_eq!(x.get::<i64>("aof_enabled"), Some(0));
+    drop(con);
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+#[serial]
+fn append_fsync() {
+    let port = 3478;
+    let dir = std::env::temp_dir().join(format!("redisless-fsync-{}", port));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("redisless.conf");
+    std::fs::write(
+        &path,
+        format!(
+            "dir {}\nappendonly yes\nappendfsync always\n",
+            dir.display()
+        ),
+    )
+    .unwrap();
+    let server = ServerBuilder::new(InMemoryStorage::new(), port)
+        .config_file(&path)
+        .unwrap()
+        .build();
+    assert_eq!(server.start(), Some(ServerState::Started));
+    let redis_client = redis::Client::open(format!("redis://127.0.0.1:{}/", port)).unwrap();
+    let mut con = redis_client.get_connection().unwrap();
+    let changes = || {
+        let file = std::fs::File::open(dir.join("appendonly.aof")).unwrap();
+        let mut reader = BufReader::new(file);
+        crate::storage::snapshot::read(&mut reader).unwrap();
+        crate::storage::snapshot::read_changes(&mut reader).unwrap()
+    };
+
+    // the changes are in the file once replied, whatever the policy
+    let _: () = con.set("key", "value").unwrap();
+    assert_eq!(changes().len(), 1);
+    for policy in &["everysec", "no"] {
+        let _: () = cmd("CONFIG")
+            .arg("SET")
+            .arg("appendfsync")
+            .arg(policy)
+            .query(&mut con)
+            .unwrap();
+        let _: () = con.set("key", *policy).unwrap();
+    }
+    assert_eq!(changes().len(), 3);
+    let x: Result<(), _> = cmd("CONFIG")
+        .arg("SET")
+        .arg("appendfsync")
+        .arg("sometimes")
+        .query(&mut con);
+    assert!(x.is_err());
+
+    std::fs::remove_dir_all(&dir).unwrap();
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+}
+
+#[test]
+#[serial]
+fn replicaof() {
+    let (master_port, replica_port) = (3479, 3480);
+    let master = Server::new(InMemoryStorage::new(), master_port);
+    assert_eq!(master.start(), Some(ServerState::Started));
+    let replica = Server::new(InMemoryStorage::new(), replica_port);
+    assert_eq!(replica.start(), Some(ServerState::Started));
+    let master_client = redis::Client::open(format!("redis://127.0.0.1:{}/", master_port)).unwrap();
+    let mut master_con = master_client.get_connection().unwrap();
+    let replica_client =
+        redis::Client::open(format!("redis://127.0.0.1:{}/", replica_port)).unwrap();
+    let mut con = replica_client.get_connection().unwrap();
+    let mut db1_con = replica_client.get_connection().unwrap();
+    let _: () = cmd("SELECT").arg(1).query(&mut db1_con).unwrap();
+    // waits for the replica to catch up
+    let eventually = |con: &mut redis::Connection, key: &str, expected: Option<&str>| {
+        let started = Instant::now();
+        loop {
+            let value: Option<String> = con.get(key).unwrap();
+            if value.as_deref() == expected || started.elapsed() > Duration::from_secs(5) {
+                return value;
+            }
+            sleep(Duration::from_millis(20));
+        }
+    };
+
+    let _: () = master_con.set("key", "value").unwrap();
+    let _: () = con.set("stale", "value").unwrap();
+    let x: String = cmd("REPLICAOF")
+        .arg("127.0.0.1")
+        .arg(master_port)
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(x, "OK");
+    let x: String = cmd("SLAVEOF")
+        .arg("127.0.0.1")
+        .arg(master_port)
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(x, "OK Already connected to specified master");
+    let x: Result<(), _> = cmd("REPLICAOF")
+        .arg("127.0.0.1")
+        .arg("port")
+        .query(&mut con);
+    assert!(x.unwrap_err().to_string().contains("Invalid master port"));
+
+    // the dataset of the master replaces the one of the replica
+    assert_eq!(
+        eventually(&mut con, "key", Some("value")),
+        Some("value".to_string())
+    );
+    let x: bool = con.exists("stale").unwrap();
+    assert!(!x);
+
+    // then the changes made to it follow
+    let _: () = master_con.set("key", "other value").unwrap();
+    let _: () = cmd("INCR").arg("counter").query(&mut master_con).unwrap();
+    let _: () = cmd("SELECT").arg(1).query(&mut master_con).unwrap();
+    let _: () = master_con.set("in db 1", "value").unwrap();
+    let _: () = cmd("SWAPDB").arg(0).arg(1).query(&mut master_con).unwrap();
+    let _: () = master_con.del("key").unwrap();
+    assert_eq!(
+        eventually(&mut db1_con, "counter", Some("1")),
+        Some("1".to_string())
+    );
+    assert_eq!(eventually(&mut db1_con, "key", None), None);
+    assert_eq!(con.get::<_, String>("in db 1").unwrap(), "value");
+
+    // the clients of a replica only read, unless told otherwise
+    let x: Result<(), _> = con.set("key", "value");
+    assert!(x
+        .unwrap_err()
+        .to_string()
+        .contains("You can't write against a read only replica."));
+    let _: () = cmd("MULTI").query(&mut con).unwrap();
+    let x: Result<(), _> = con.set("key", "value");
+    assert!(x.is_err());
+    let x: Result<(), _> = cmd("EXEC").query(&mut con);
+    assert_eq!(x.unwrap_err().code(), Some("EXECABORT"));
+    #[cfg(feature = "scripting")]
+    {
+        let x: Result<(), _> = cmd("EVAL")
+            .arg("return redis.call('SET', KEYS[1], 'value')")
+            .arg(1)
+            .arg("key")
+            .query(&mut con);
+        assert!(x.unwrap_err().to_string().contains("read only replica"));
+    }
+    let x: String = cmd("READONLY").query(&mut con).unwrap();
+    assert_eq!(x, "OK");
+    let x: String = cmd("READWRITE").query(&mut con).unwrap();
+    assert_eq!(x, "OK");
+    let _: () = cmd("CONFIG")
+        .arg(&["SET", "replica-read-only", "no"])
+        .query(&mut con)
+        .unwrap();
+    let _: () = con.set("written", "value").unwrap();
+    let _: () = cmd("CONFIG")
+        .arg(&["SET", "replica-read-only", "yes"])
+        .query(&mut con)
+        .unwrap();
+
+    // a replica no more keeps the dataset as it was
+    let x: String = cmd("REPLICAOF")
+        .arg("NO")
+        .arg("ONE")
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(x, "OK");
+    let _: () = master_con.set("after", "value").unwrap();
+    sleep(Duration::from_millis(100));
+    let x: bool = con.exists("after").unwrap();
+    assert!(!x);
+    assert_eq!(con.get::<_, String>("in db 1").unwrap(), "value");
+    let _: () = con.set("key", "value").unwrap();
+
+    assert_eq!(replica.stop(), Some(ServerState::Stopped));
+    assert_eq!(master.stop(), Some(ServerState::Stopped));
+}
+
+#[test]
+#[serial]
+fn psync() {
+    use crate::storage::snapshot::{self, Change};
+
+    let (master_port, replica_port) = (3481, 3482);
+    let master = Server::new(InMemoryStorage::new(), master_port);
+    assert_eq!(master.start(), Some(ServerState::Started));
+    let replica = Server::new(InMemoryStorage::new(), replica_port);
+    assert_eq!(replica.start(), Some(ServerState::Started));
+    let redis_client = redis::Client::open(format!("redis://127.0.0.1:{}/", master_port)).unwrap();
+    let mut con = redis_client.get_connection().unwrap();
+    let replica_client =
+        redis::Client::open(format!("redis://127.0.0.1:{}/", replica_port)).unwrap();
+    let mut replica_con = replica_client.get_connection().unwrap();
+    // what the server replies to PSYNC, read up to the first line
+    let psync = |port: u16, replid: &str, offset: &str| {
+        let mut stream = TcpStream::connect(("127.0.0.1", port)).unwrap();
+        stream
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+        let mut reader = BufReader::new(stream.try_clone().unwrap());
+        let request = format!(
+            "*3\r\n$5\r\nPSYNC\r\n${}\r\n{}\r\n${}\r\n{}\r\n",
+            replid.len(),
+            replid,
+            offset.len(),
+            offset
+        );
+        stream.write_all(request.as_bytes()).unwrap();
+        let mut line = String::new();
+        reader.read_line(&mut line).unwrap();
+        (line.trim_end().to_string(), reader)
+    };
+
+    // a replica that has nothing is sent the whole dataset
+    let _: () = con.set("key", "value").unwrap();
+    let (line, mut reader) = psync(master_port, "?", "-1");
+    let fields = line.split(' ').collect::<Vec<_>>();
+    assert_eq!(fields[0], "+FULLRESYNC");
+    let (replid, offset) = (fields[1].to_string(), fields[2].parse::<u64>().unwrap());
+    assert_eq!(replid.len(), 40);
+    let mut line = String::new();
+    reader.read_line(&mut line).unwrap();
+    let mut dump = vec![0; line.trim_end()[1..].parse().unwrap()];
+    reader.read_exact(&mut dump).unwrap();
+    let databases = crate::storage::rdb::read(&mut dump.as_slice()).unwrap();
+    assert_eq!(databases[0][0].0, b"key");
+
+    // then the changes made to it
+    let _: () = con.set("other", "value").unwrap();
+    let change = snapshot::read_change(&mut reader).unwrap();
+    assert!(matches!(&change, Change::Put(0, key, _) if key == b"other"));
+    let mut bytes = vec![];
+    snapshot::write_change(&mut bytes, &change).unwrap();
+    let after = offset + bytes.len() as u64;
+
+    // a replica that lost its link resumes from the backlog
+    let (line, mut reader) = psync(master_port, &replid, &(offset + 1).to_string());
+    assert_eq!(line, format!("+CONTINUE {}", replid));
+    assert_eq!(snapshot::read_change(&mut reader).unwrap(), change);
+    let (line, _) = psync(master_port, &replid, &(after + 1).to_string());
+    assert_eq!(line, format!("+CONTINUE {}", replid));
+    // unless the backlog doesn't have what it lacks
+    let (line, _) = psync(master_port, &replid, &(after + 10).to_string());
+    assert!(line.starts_with("+FULLRESYNC"));
+    let (line, _) = psync(master_port, &"0".repeat(40), "1");
+    assert!(line.starts_with("+FULLRESYNC"));
+
+    // the replicas of a master resume from the replica that replaced it
+    let _: () = cmd("REPLICAOF")
+        .arg("127.0.0.1")
+        .arg(master_port)
+        .query(&mut replica_con)
+        .unwrap();
+    let started = Instant::now();
+    while !replica_con.exists::<_, bool>("other").unwrap()
+        && started.elapsed() < Duration::from_secs(5)
+    {
+        sleep(Duration::from_millis(20));
+    }
+    let _: () = cmd("REPLICAOF")
+        .arg("NO")
+        .arg("ONE")
+        .query(&mut replica_con)
+        .unwrap();
+    let (line, _) = psync(replica_port, &replid, &(after + 1).to_string());
+    let fields = line.split(' ').collect::<Vec<_>>();
+    assert_eq!(fields[0], "+CONTINUE");
+    assert_ne!(fields[1], replid);
+
+    assert_eq!(replica.stop(), Some(ServerState::Stopped));
+    assert_eq!(master.stop(), Some(ServerState::Stopped));
+}
+
+#[test]
+#[serial]
+fn info_replication() {
+    let (master_port, replica_port) = (3483, 3484);
+    let master = Server::new(InMemoryStorage::new(), master_port);
+    assert_eq!(master.start(), Some(ServerState::Started));
+    let replica = Server::new(InMemoryStorage::new(), replica_port);
+    assert_eq!(replica.start(), Some(ServerState::Started));
+    let redis_client = redis::Client::open(format!("redis://127.0.0.1:{}/", master_port)).unwrap();
+    let mut con = redis_client.get_connection().unwrap();
+    let replica_client =
+        redis::Client::open(format!("redis://127.0.0.1:{}/", replica_port)).unwrap();
+    let mut replica_con = replica_client.get_connection().unwrap();
+    let info = |con: &mut redis::Connection| -> redis::InfoDict {
+        cmd("INFO").arg("replication").query(con).unwrap()
+    };
+
+    let x = info(&mut con);
+    assert_eq!(x.get("role"), Some("master".to_string()));
+    assert_eq!(x.get("connected_slaves"), Some(0));
+    assert_eq!(x.get("master_repl_offset"), Some(0));
+    assert!(x.get::<String>("master_link_status").is_none());
+
+    let _: () = cmd("REPLICAOF")
+        .arg("127.0.0.1")
+        .arg(master_port)
+        .query(&mut replica_con)
+        .unwrap();
+    let x = info(&mut replica_con);
+    assert_eq!(x.get("role"), Some("slave".to_string()));
+    assert_eq!(x.get("master_host"), Some("127.0.0.1".to_string()));
+    assert_eq!(x.get("master_port"), Some(master_port));
+    assert_eq!(x.get("slave_read_only"), Some(1));
+    let started = Instant::now();
+    while info(&mut replica_con).get::<String>("master_link_status") != Some("up".to_string())
+        && started.elapsed() < Duration::from_secs(5)
+    {
+        sleep(Duration::from_millis(20));
+    }
+    assert!(info(&mut replica_con)
+        .get::<String>("master_link_down_since_seconds")
+        .is_none());
+
+    // the replica catches up with the offset of the master, and says so
+    let _: () = con.set("key", "value").unwrap();
+    let offset: u64 = info(&mut con).get("master_repl_offset").unwrap();
+    assert!(offset > 0);
+    let slave0 = format!(
+        "ip=127.0.0.1,port={},state=online,offset={},lag=",
+        replica_port, offset
+    );
+    let started = Instant::now();
+    while !info(&mut con)
+        .get::<String>("slave0")
+        .unwrap()
+        .starts_with(&slave0)
+        && started.elapsed() < Duration::from_secs(5)
+    {
+        sleep(Duration::from_millis(20));
+    }
+    let x = info(&mut con);
+    assert_eq!(x.get("connected_slaves"), Some(1));
+    assert!(x.get::<String>("slave0").unwrap().starts_with(&slave0));
+    let x = info(&mut replica_con);
+    assert_eq!(x.get("slave_repl_offset"), Some(offset));
+    assert_eq!(x.get("master_repl_offset"), Some(offset));
+    assert_eq!(x.get("master_sync_in_progress"), Some(0));
+
+    let _: () = cmd("REPLICAOF")
+        .arg("NO")
+        .arg("ONE")
+        .query(&mut replica_con)
+        .unwrap();
+    let x = info(&mut replica_con);
+    assert_eq!(x.get("role"), Some("master".to_string()));
+    assert_eq!(x.get("second_repl_offset"), Some(offset + 1));
+
+    assert_eq!(replica.stop(), Some(ServerState::Stopped));
+    assert_eq!(master.stop(), Some(ServerState::Stopped));
+}
+
+#[test]
+#[serial]
+fn failover() {
+    let (master_port, replica_port) = (3489, 3490);
+    let master = Server::new(InMemoryStorage::new(), master_port);
+    assert_eq!(master.start(), Some(ServerState::Started));
+    let replica = Server::new(InMemoryStorage::new(), replica_port);
+    assert_eq!(replica.start(), Some(ServerState::Started));
+    let redis_client = redis::Client::open(format!("redis://127.0.0.1:{}/", master_port)).unwrap();
+    let mut con = redis_client.get_connection().unwrap();
+    let replica_client =
+        redis::Client::open(format!("redis://127.0.0.1:{}/", replica_port)).unwrap();
+    let mut replica_con = replica_client.get_connection().unwrap();
+    let info = |con: &mut redis::Connection| -> redis::InfoDict {
+        cmd("INFO").arg("replication").query(con).unwrap()
+    };
+    let failover = |con: &mut redis::Connection, args: &[&str]| -> redis::RedisResult<()> {
+        cmd("FAILOVER").arg(args).query(con)
+    };
+
+    let x = failover(&mut con, &[]).unwrap_err();
+    assert_eq!(x.detail(), Some("FAILOVER requires connected replicas."));
+    let x = failover(&mut con, &["ABORT"]).unwrap_err();
+    assert_eq!(x.detail(), Some("No failover in progress."));
+    let x = failover(&mut con, &["TIMEOUT", "0"]).unwrap_err();
+    assert_eq!(x.detail(), Some("FAILOVER timeout must be greater than 0"));
+    let x = failover(&mut con, &["TO", "127.0.0.1", "3490", "FORCE"]).unwrap_err();
+    assert_eq!(
+        x.detail(),
+        Some("FAILOVER with force option requires both a timeout and target HOST and IP.")
+    );
+
+    let _: () = cmd("REPLICAOF")
+        .arg("127.0.0.1")
+        .arg(master_port)
+        .query(&mut replica_con)
+        .unwrap();
+    let started = Instant::now();
+    while info(&mut replica_con).get::<String>("master_link_status") != Some("up".to_string())
+        && started.elapsed() < Duration::from_secs(5)
+    {
+        sleep(Duration::from_millis(20));
+    }
+    let _: () = con.set("key", "value").unwrap();
+    let x = failover(&mut replica_con, &[]).unwrap_err();
+    assert_eq!(
+        x.detail(),
+        Some("FAILOVER is not valid when server is a replica.")
+    );
+    let x = failover(&mut con, &["TO", "127.0.0.1", "3491"]).unwrap_err();
+    assert_eq!(
+        x.detail(),
+        Some("FAILOVER target HOST and PORT is not a replica.")
+    );
+
+    // the writes are held back until the replica caught up and is promoted,
+    // the master replicating it from then on
+    let _: () = failover(&mut con, &["TO", "127.0.0.1", "3490"]).unwrap();
+    let x = failover(&mut con, &[]).unwrap_err();
+    assert_eq!(x.detail(), Some("FAILOVER already in progress."));
+    assert_ne!(
+        info(&mut con).get::<String>("master_failover_state"),
+        Some("no-failover".to_string())
+    );
+    let x: redis::RedisResult<()> = con.set("key", "other");
+    assert_eq!(x.unwrap_err().code(), Some("READONLY"));
+    let x = info(&mut con);
+    assert_eq!(x.get("role"), Some("slave".to_string()));
+    assert_eq!(x.get("master_port"), Some(replica_port));
+    assert_eq!(
+        x.get("master_failover_state"),
+        Some("no-failover".to_string())
+    );
+    assert_eq!(
+        info(&mut replica_con).get("role"),
+        Some("master".to_string())
+    );
+    let x: String = replica_con.get("key").unwrap();
+    assert_eq!(x, "value");
+
+    let _: () = replica_con.set("key", "other").unwrap();
+    let started = Instant::now();
+    while con.get::<_, String>("key").unwrap() != "other"
+        && started.elapsed() < Duration::from_secs(5)
+    {
+        sleep(Duration::from_millis(20));
+    }
+    let x: String = con.get("key").unwrap();
+    assert_eq!(x, "other");
+
+    // an aborted failover lets the writes go on, the replica being left
+    // as it is
+    let started = Instant::now();
+    while info(&mut replica_con).get("connected_slaves") != Some(1)
+        && started.elapsed() < Duration::from_secs(5)
+    {
+        sleep(Duration::from_millis(20));
+    }
+    let _: () = replica_con.set("key", "value").unwrap();
+    let _: () = failover(&mut replica_con, &["TIMEOUT", "5000"]).unwrap();
+    let _: () = failover(&mut replica_con, &["ABORT"]).unwrap();
+    let _: () = replica_con.set("key", "again").unwrap();
+    let x = info(&mut replica_con);
+    assert_eq!(x.get("role"), Some("master".to_string()));
+    assert_eq!(
+        x.get("master_failover_state"),
+        Some("no-failover".to_string())
+    );
+
+    assert_eq!(replica.stop(), Some(ServerState::Stopped));
+    assert_eq!(master.stop(), Some(ServerState::Stopped));
+}
+
+#[test]
+#[serial]
+fn sentinel() {
+    let (master_port, sentinel_port, replica_port) = (3491, 3492, 3493);
+    let master = ServerBuilder::new(InMemoryStorage::new(), master_port)
+        .sentinel(sentinel_port, "mymaster")
+        .build();
+    assert_eq!(master.start(), Some(ServerState::Started));
+    let replica = Server::new(InMemoryStorage::new(), replica_port);
+    assert_eq!(replica.start(), Some(ServerState::Started));
+    let redis_client = redis::Client::open(format!("redis://127.0.0.1:{}/", master_port)).unwrap();
+    let mut con = redis_client.get_connection().unwrap();
+    let sentinel_client =
+        redis::Client::open(format!("redis://127.0.0.1:{}/", sentinel_port)).unwrap();
+    let mut sentinel_con = sentinel_client.get_connection().unwrap();
+    let replica_client =
+        redis::Client::open(format!("redis://127.0.0.1:{}/", replica_port)).unwrap();
+    let mut replica_con = replica_client.get_connection().unwrap();
+    let master_addr = |con: &mut redis::Connection| -> Option<(String, u16)> {
+        cmd("SENTINEL")
+            .arg("get-master-addr-by-name")
+            .arg("mymaster")
+            .query(con)
+            .unwrap()
+    };
+
+    assert_eq!(
+        master_addr(&mut sentinel_con),
+        Some(("127.0.0.1".to_string(), master_port))
+    );
+    let x: Option<(String, u16)> = cmd("SENTINEL")
+        .arg("get-master-addr-by-name")
+        .arg("other")
+        .query(&mut sentinel_con)
+        .unwrap();
+    assert_eq!(x, None);
+    let x: Vec<HashMap<String, String>> = cmd("SENTINEL")
+        .arg("MASTERS")
+        .query(&mut sentinel_con)
+        .unwrap();
+    assert_eq!(x.len(), 1);
+    assert_eq!(x[0]["name"], "mymaster");
+    assert_eq!(x[0]["ip"], "127.0.0.1");
+    assert_eq!(x[0]["port"], master_port.to_string());
+    assert_eq!(x[0]["flags"], "master");
+    assert_eq!(x[0]["num-slaves"], "0");
+    let x: HashMap<String, String> = cmd("SENTINEL")
+        .arg("MASTER")
+        .arg("mymaster")
+        .query(&mut sentinel_con)
+        .unwrap();
+    assert_eq!(x["port"], master_port.to_string());
+    let x: redis::RedisResult<()> = cmd("SENTINEL")
+        .arg("MASTER")
+        .arg("other")
+        .query(&mut sentinel_con);
+    assert_eq!(
+        x.unwrap_err().detail(),
+        Some("No such master with that name")
+    );
+
+    // the sentinel listener answers the commands of a sentinel alone, which
+    // the server doesn't know about
+    let x: redis::RedisResult<()> = sentinel_con.set("key", "value");
+    assert_eq!(x.unwrap_err().detail(), Some("unknown command 'set'"));
+    let x: String = cmd("PING").query(&mut sentinel_con).unwrap();
+    assert_eq!(x, "PONG");
+    let x: redis::RedisResult<()> = cmd("SENTINEL").arg("MASTERS").query(&mut con);
+    assert_eq!(x.unwrap_err().detail(), Some("unknown command 'sentinel'"));
+
+    // the applications are told once the master changes
+    let mut subscriber = sentinel_client.get_connection().unwrap();
+    let mut pubsub = subscriber.as_pubsub();
+    pubsub.subscribe("+switch-master").unwrap();
+    pubsub
+        .set_read_timeout(Some(Duration::from_secs(10)))
+        .unwrap();
+    let _: () = cmd("REPLICAOF")
+        .arg("127.0.0.1")
+        .arg(master_port)
+        .query(&mut replica_con)
+        .unwrap();
+    let started = Instant::now();
+    while cmd("INFO")
+        .arg("replication")
+        .query::<redis::InfoDict>(&mut replica_con)
+        .unwrap()
+        .get::<String>("master_link_status")
+        != Some("up".to_string())
+        && started.elapsed() < Duration::from_secs(5)
+    {
+        sleep(Duration::from_millis(20));
+    }
+    let _: () = cmd("FAILOVER").query(&mut con).unwrap();
+
+    let message = pubsub.get_message().unwrap();
+    assert_eq!(message.get_channel_name(), "+switch-master");
+    assert_eq!(
+        message.get_payload::<String>().unwrap(),
+        format!(
+            "mymaster 127.0.0.1 {} 127.0.0.1 {}",
+            master_port, replica_port
+        )
+    );
+    assert_eq!(
+        master_addr(&mut sentinel_con),
+        Some(("127.0.0.1".to_string(), replica_port))
+    );
+    let x: HashMap<String, String> = cmd("SENTINEL")
+        .arg("MASTER")
+        .arg("mymaster")
+        .query(&mut sentinel_con)
+        .unwrap();
+    assert_eq!(x["config-epoch"], "1");
+
+    assert_eq!(replica.stop(), Some(ServerState::Stopped));
+    assert_eq!(master.stop(), Some(ServerState::Stopped));
+}
+
+#[test]
+#[serial]
+fn active_expire() {
+    let port = 3494;
+    let server = Server::new(InMemoryStorage::new(), port);
+    assert_eq!(server.start(), Some(ServerState::Started));
+    let redis_client = redis::Client::open(format!("redis://127.0.0.1:{}/", port)).unwrap();
+    let mut con = redis_client.get_connection().unwrap();
+    let expired_keys = |con: &mut redis::Connection| -> u64 {
+        let x: redis::InfoDict = cmd("INFO").arg("stats").query(con).unwrap();
+        x.get("expired_keys").unwrap()
+    };
+    let _: () = cmd("CONFIG")
+        .arg(&["SET", "notify-keyspace-events", "Ex"])
+        .query(&mut con)
+        .unwrap();
+    let mut subscriber = redis_client.get_connection().unwrap();
+    let mut pubsub = subscriber.as_pubsub();
+    pubsub.subscribe("__keyevent@0__:expired").unwrap();
+    pubsub
+        .set_read_timeout(Some(Duration::from_secs(5)))
+        .unwrap();
+
+    // the keys that expired are removed without being read
+    let _: () = con.set("key", "value").unwrap();
+    for i in 0..50 {
+        let _: () = con.pset_ex(format!("expiring{}", i), "value", 10).unwrap();
+    }
+    let started = Instant::now();
+    while expired_keys(&mut con) < 50 && started.elapsed() < Duration::from_secs(5) {
+        sleep(Duration::from_millis(20));
+    }
+    assert_eq!(expired_keys(&mut con), 50);
+    let message = pubsub.get_message().unwrap();
+    assert!(message
+        .get_payload::<String>()
+        .unwrap()
+        .starts_with("expiring"));
+    let x: redis::InfoDict = cmd("INFO").arg("keyspace").query(&mut con).unwrap();
+    assert!(x
+        .get::<String>("db0")
+        .unwrap()
+        .starts_with("keys=1,expires=0"));
+
+    // unless the active expiration is off
+    let _: () = cmd("DEBUG")
+        .arg(&["SET-ACTIVE-EXPIRE", "0"])
+        .query(&mut con)
+        .unwrap();
+    let _: () = cmd("CONFIG")
+        .arg(&["SET", "active-expire-effort", "10"])
+        .query(&mut con)
+        .unwrap();
+    let _: () = con.pset_ex("expiring", "value", 10).unwrap();
+    sleep(Duration::from_millis(300));
+    assert_eq!(expired_keys(&mut con), 50);
+    let _: () = cmd("DEBUG")
+        .arg(&["SET-ACTIVE-EXPIRE", "1"])
+        .query(&mut con)
+        .unwrap();
+    let started = Instant::now();
+    while expired_keys(&mut con) < 51 && started.elapsed() < Duration::from_secs(5) {
+        sleep(Duration::from_millis(20));
+    }
+    assert_eq!(expired_keys(&mut con), 51);
+
+    let x: RedisResult<()> = cmd("CONFIG")
+        .arg(&["SET", "active-expire-effort", "11"])
+        .query(&mut con);
+    assert!(x.is_err());
+
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+}
+
+#[test]
+#[serial]
+fn cluster() {
+    let port = 3485;
+    let server = Server::new(InMemoryStorage::new(), port);
+    assert_eq!(server.start(), Some(ServerState::Started));
+    let redis_client = redis::Client::open(format!("redis://127.0.0.1:{}/", port)).unwrap();
+    let mut con = redis_client.get_connection().unwrap();
+
+    let myid: String = cmd("CLUSTER").arg("MYID").query(&mut con).unwrap();
+    assert_eq!(myid.len(), 40);
+    let x: String = cmd("CLUSTER").arg("MYID").query(&mut con).unwrap();
+    assert_eq!(x, myid);
+
+    let x: redis::InfoDict = cmd("CLUSTER").arg("INFO").query(&mut con).unwrap();
+    assert_eq!(x.get("cluster_enabled"), Some(0));
+    assert_eq!(x.get("cluster_state"), Some("ok".to_string()));
+    assert_eq!(x.get("cluster_slots_assigned"), Some(16384));
+    assert_eq!(x.get("cluster_known_nodes"), Some(1));
+    let x: redis::InfoDict = cmd("INFO").arg("cluster").query(&mut con).unwrap();
+    assert_eq!(x.get("cluster_enabled"), Some(0));
+
+    // a single node owns every slot
+    let x: Vec<redis::Value> = cmd("CLUSTER").arg("SLOTS").query(&mut con).unwrap();
+    assert_eq!(x.len(), 1);
+    let x: (i64, i64, (String, u16, String)) = redis::from_redis_value(&x[0]).unwrap();
+    assert_eq!(x, (0, 16383, ("127.0.0.1".to_string(), port, myid.clone())));
+    let x: Vec<HashMap<String, redis::Value>> =
+        cmd("CLUSTER").arg("SHARDS").query(&mut con).unwrap();
+    assert_eq!(x.len(), 1);
+    let slots: Vec<i64> = redis::from_redis_value(&x[0]["slots"]).unwrap();
+    assert_eq!(slots, vec![0, 16383]);
+    let nodes: Vec<HashMap<String, redis::Value>> =
+        redis::from_redis_value(&x[0]["nodes"]).unwrap();
+    assert_eq!(nodes.len(), 1);
+    let id: String = redis::from_redis_value(&nodes[0]["id"]).unwrap();
+    assert_eq!(id, myid);
+    let role: String = redis::from_redis_value(&nodes[0]["role"]).unwrap();
+    assert_eq!(role, "master");
+
+    let x: String = cmd("CLUSTER").arg("NODES").query(&mut con).unwrap();
+    assert!(x.starts_with(&format!("{} 127.0.0.1:{}@", myid, port)));
+    assert!(x.ends_with(" myself,master - 0 0 0 connected 0-16383\n"));
+
+    // the slot of a key is the one of its hash tag, if any
+    let keyslot = |con: &mut redis::Connection, key: &str| -> u16 {
+        cmd("CLUSTER").arg("KEYSLOT").arg(key).query(con).unwrap()
+    };
+    assert_eq!(keyslot(&mut con, "123456789"), 12739);
+    assert_eq!(keyslot(&mut con, "somekey"), 11058);
+    assert_eq!(keyslot(&mut con, "foo"), 12182);
+    assert_eq!(
+        keyslot(&mut con, "{user1000}.following"),
+        keyslot(&mut con, "{user1000}.followers")
+    );
+    assert_eq!(keyslot(&mut con, "foo{bar}{zap}"), keyslot(&mut con, "bar"));
+    assert_eq!(
+        keyslot(&mut con, "foo{{bar}}zap"),
+        keyslot(&mut con, "{bar")
+    );
+    assert_ne!(keyslot(&mut con, "foo{}{bar}"), keyslot(&mut con, "bar"));
+    // keys in different slots are fine out of cluster mode
+    let _: () = con.set_multiple(&[("foo", "1"), ("bar", "2")]).unwrap();
+
+    let x: redis::RedisResult<()> = cmd("CLUSTER").arg("NODE").query(&mut con);
+    assert!(x.is_err());
+    // the nodes of a cluster are only met in cluster mode
+    let x: redis::RedisResult<()> = cmd("CLUSTER")
+        .arg("MEET")
+        .arg("127.0.0.1")
+        .arg(port)
+        .query(&mut con);
+    assert!(x.is_err());
+
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+}
+
+#[test]
+#[serial]
+fn cluster_mode() {
+    let ports = [3486, 3487, 3488];
+    let servers = ports
+        .iter()
+        .map(|port| {
+            ServerBuilder::new(InMemoryStorage::new(), *port)
+                .cluster_enabled(true)
+                .build()
+        })
+        .collect::<Vec<_>>();
+    let mut cons = vec![];
+    for (server, port) in servers.iter().zip(&ports) {
+        assert_eq!(server.start(), Some(ServerState::Started));
+        let redis_client = redis::Client::open(format!("redis://127.0.0.1:{}/", port)).unwrap();
+        cons.push(redis_client.get_connection().unwrap());
+    }
+    let ids = cons
+        .iter_mut()
+        .map(|con| cmd("CLUSTER").arg("MYID").query(con).unwrap())
+        .collect::<Vec<String>>();
+    let info = |con: &mut redis::Connection| -> redis::InfoDict {
+        cmd("CLUSTER").arg("INFO").query(con).unwrap()
+    };
+
+    // no slot is served until assigned
+    let x = info(&mut cons[0]);
+    assert_eq!(x.get("cluster_enabled"), Some(1));
+    assert_eq!(x.get("cluster_state"), Some("fail".to_string()));
+    assert_eq!(x.get("cluster_slots_assigned"), Some(0));
+    let x: Vec<redis::Value> = cmd("CLUSTER").arg("SLOTS").query(&mut cons[0]).unwrap();
+    assert!(x.is_empty());
+
+    let ranges = [(0, 5460), (5461, 10922), (10923, 16383)];
+    for (con, (start, end)) in cons.iter_mut().zip(&ranges) {
+        let _: () = cmd("CLUSTER")
+            .arg("ADDSLOTSRANGE")
+            .arg(*start)
+            .arg(*end)
+            .query(con)
+            .unwrap();
+    }
+    let x: redis::RedisResult<()> = cmd("CLUSTER").arg("ADDSLOTS").arg(0).query(&mut cons[0]);
+    assert_eq!(
+        x.unwrap_err().to_string(),
+        "An error was signalled by the server: Slot 0 is already busy"
+    );
+    let x: redis::RedisResult<()> = cmd("CLUSTER")
+        .arg("ADDSLOTS")
+        .arg(16384)
+        .query(&mut cons[0]);
+    assert!(x.is_err());
+
+    // the first node meets the others, which get to know one another from it
+    for port in &ports[1..] {
+        let _: () = cmd("CLUSTER")
+            .arg("MEET")
+            .arg("127.0.0.1")
+            .arg(*port)
+            .query(&mut cons[0])
+            .unwrap();
+    }
+    let started = Instant::now();
+    while cons
+        .iter_mut()
+        .any(|con| info(con).get::<String>("cluster_state") != Some("ok".to_string()))
+        && started.elapsed() < Duration::from_secs(5)
+    {
+        sleep(Duration::from_millis(20));
+    }
+    for con in cons.iter_mut() {
+        let x = info(con);
+        assert_eq!(x.get("cluster_state"), Some("ok".to_string()));
+        assert_eq!(x.get("cluster_known_nodes"), Some(3));
+        assert_eq!(x.get("cluster_size"), Some(3));
+
+        // every node tells which one serves each range of slots
+        let x: Vec<redis::Value> = cmd("CLUSTER").arg("SLOTS").query(con).unwrap();
+        let x = x
+            .iter()
+            .map(|range| redis::from_redis_value(range).unwrap())
+            .collect::<Vec<(u16, u16, (String, u16, String))>>();
+        let expected = ranges
+            .iter()
+            .zip(ports.iter().zip(&ids))
+            .map(|((start, end), (port, id))| {
+                (*start, *end, ("127.0.0.1".to_string(), *port, id.clone()))
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(x, expected);
+        let x: String = cmd("CLUSTER").arg("NODES").query(con).unwrap();
+        assert_eq!(x.lines().count(), 3);
+        assert_eq!(x.matches("myself").count(), 1);
+    }
+
+    // a node only runs the commands whose keys are in a slot it serves,
+    // the keys of a command being in the same slot
+    let x: redis::RedisResult<()> = cons[0].set("foo", "value");
+    assert_eq!(x.unwrap_err().code(), Some("MOVED"));
+    let x: String = cmd("SET")
+        .arg("foo")
+        .arg("value")
+        .query(&mut cons[0])
+        .map(|()| String::new())
+        .unwrap_or_else(|error| error.to_string());
+    assert!(x.contains("12182 127.0.0.1:3488"), "{}", x);
+    let _: () = cons[2].set("foo", "value").unwrap();
+    let x: redis::RedisResult<()> = cons[2].set_multiple(&[("foo", "1"), ("bar", "2")]);
+    assert_eq!(x.unwrap_err().code(), Some("CROSSSLOT"));
+    let _: () = cons[2]
+        .set_multiple(&[("{foo}1", "1"), ("{foo}2", "2")])
+        .unwrap();
+    let x: Vec<String> = cons[2].get(&["{foo}1", "{foo}2"]).unwrap();
+    assert_eq!(x, vec!["1", "2"]);
+    // as the commands of a transaction are
+    let _: () = cmd("MULTI").query(&mut cons[2]).unwrap();
+    let _: () = cons[2].set("foo", "1").unwrap();
+    let x: redis::RedisResult<()> = cons[2].set("bar", "2");
+    assert_eq!(x.unwrap_err().code(), Some("MOVED"));
+    let x: redis::RedisResult<()> = cmd("EXEC").query(&mut cons[2]);
+    assert_eq!(x.unwrap_err().code(), Some("EXECABORT"));
+    assert_eq!(cons[2].get::<_, String>("foo").unwrap(), "value");
+
+    // a node that stopped serving its slots leaves them unserved
+    let _: () = cmd("CLUSTER")
+        .arg("DELSLOTSRANGE")
+        .arg(10923)
+        .arg(16383)
+        .query(&mut cons[2])
+        .unwrap();
+    let started = Instant::now();
+    while info(&mut cons[0]).get::<String>("cluster_state") != Some("fail".to_string())
+        && started.elapsed() < Duration::from_secs(5)
+    {
+        sleep(Duration::from_millis(20));
+    }
+    let x = info(&mut cons[0]);
+    assert_eq!(x.get("cluster_slots_assigned"), Some(10923));
+    assert_eq!(x.get("cluster_size"), Some(2));
+    let x: redis::RedisResult<Option<String>> = cons[0].get("foo");
+    assert_eq!(x.unwrap_err().code(), Some("CLUSTERDOWN"));
+
+    for server in &servers {
+        assert_eq!(server.stop(), Some(ServerState::Stopped));
+    }
+}
+
+#[test]
+#[serial]
+fn maxmemory() {
+    let port = 3495;
+    let server = Server::new(InMemoryStorage::new(), port);
+    assert_eq!(server.start(), Some(ServerState::Started));
+    let redis_client = redis::Client::open(format!("redis://127.0.0.1:{}/", port)).unwrap();
+    let mut con = redis_client.get_connection().unwrap();
+    let config_set = |con: &mut redis::Connection, parameter: &str, value: &str| {
+        let _: () = cmd("CONFIG")
+            .arg(&["SET", parameter, value])
+            .query(con)
+            .unwrap();
+    };
+    let info = |con: &mut redis::Connection| -> redis::InfoDict { cmd("INFO").query(con).unwrap() };
+
+    // the commands that may take more memory are refused under noeviction,
+    // the others being run
+    for i in 0..10 {
+        let _: () = con.set(format!("key{}", i), "x".repeat(100)).unwrap();
+    }
+    let used = info(&mut con).get::<usize>("used_memory").unwrap();
+    config_set(&mut con, "maxmemory", &(used - 1).to_string());
+    let x: RedisResult<()> = con.set("key", "value");
+    assert_eq!(x.unwrap_err().code(), Some("OOM"));
+    let x: String = con.get("key0").unwrap();
+    assert_eq!(x.len(), 100);
+    let _: () = cmd("MULTI").query(&mut con).unwrap();
+    let x: RedisResult<()> = con.set("key", "value");
+    assert_eq!(x.unwrap_err().code(), Some("OOM"));
+    let x: RedisResult<()> = cmd("EXEC").query(&mut con);
+    assert_eq!(x.unwrap_err().code(), Some("EXECABORT"));
+    let x: u32 = con.del("key9").unwrap();
+    assert_eq!(x, 1);
+
+    // keys are evicted to make room, the ones used the least first
+    config_set(&mut con, "maxmemory", &used.to_string());
+    config_set(&mut con, "maxmemory-policy", "allkeys-lru");
+    config_set(&mut con, "maxmemory-samples", "64");
+    // key0 being read before each key is written, it is never the one used
+    // the least
+    for i in 10..20 {
+        let _: String = con.get("key0").unwrap();
+        let _: () = con.set(format!("key{}", i), "x".repeat(100)).unwrap();
+    }
+    let x = info(&mut con);
+    assert!(x.get::<usize>("used_memory").unwrap() <= used + 200);
+    assert!(x.get::<u64>("evicted_keys").unwrap() > 0);
+    let x: bool = con.exists("key0").unwrap();
+    assert_eq!(x, true);
+    let x: bool = con.exists("key19").unwrap();
+    assert_eq!(x, true);
+    let x: bool = con.exists("key1").unwrap();
+    assert_eq!(x, false);
+
+    // with no key having a time to live, the volatile policies leave none
+    // to evict
+    config_set(&mut con, "maxmemory-policy", "volatile-lru");
+    config_set(&mut con, "maxmemory", "1");
+    let x: RedisResult<()> = con.set("key", "value");
+    assert_eq!(x.unwrap_err().code(), Some("OOM"));
+
+    config_set(&mut con, "maxmemory", "0");
+    let _: () = con.set("key", "value").unwrap();
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+}
+
+#[test]
+#[serial]
+fn key_events() {
+    let port = 3496;
+    let server = Server::new(InMemoryStorage::new(), port);
+    let events = server.key_events();
+    assert_eq!(server.start(), Some(ServerState::Started));
+    let redis_client = redis::Client::open(format!("redis://127.0.0.1:{}/", port)).unwrap();
+    let mut con = redis_client.get_connection().unwrap();
+    let timeout = Duration::from_secs(1);
+
+    // the keys that expire are told whether removed in the background or
+    // as they are read
+    let _: () = con.pset_ex("key", "value", 10).unwrap();
+    assert_eq!(
+        events.recv_timeout(timeout),
+        Ok(KeyEvent::Expired(0, b"key".to_vec()))
+    );
+    let _: () = cmd("DEBUG")
+        .arg(&["SET-ACTIVE-EXPIRE", "0"])
+        .query(&mut con)
+        .unwrap();
+    let _: () = cmd("SELECT").arg(1).query(&mut con).unwrap();
+    let _: () = con.pset_ex("key", "value", 10).unwrap();
+    sleep(Duration::from_millis(20));
+    assert!(events.try_recv().is_err());
+    let x: Option<String> = con.get("key").unwrap();
+    assert_eq!(x, None);
+    assert_eq!(events.try_recv(), Ok(KeyEvent::Expired(1, b"key".to_vec())));
+
+    // as are the keys evicted
+    let _: () = con.set("evicted", "value").unwrap();
+    let _: () = cmd("CONFIG")
+        .arg(&["SET", "maxmemory-policy", "allkeys-random"])
+        .query(&mut con)
+        .unwrap();
+    let _: () = cmd("CONFIG")
+        .arg(&["SET", "maxmemory", "1"])
+        .query(&mut con)
+        .unwrap();
+    let _: () = con.set("key", "value").unwrap();
+    assert_eq!(
+        events.recv_timeout(timeout),
+        Ok(KeyEvent::Evicted(1, b"evicted".to_vec()))
+    );
+
+    // the channels whose receiver was dropped are not sent to anymore
+    drop(events);
+    let _: () = cmd("CONFIG")
+        .arg(&["SET", "maxmemory", "0"])
+        .query(&mut con)
+        .unwrap();
+    let _: () = con.pset_ex("key", "value", 10).unwrap();
+    sleep(Duration::from_millis(20));
+    let x: Option<String> = con.get("key").unwrap();
+    assert_eq!(x, None);
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+}
+
+#[test]
+#[serial]
+fn shards() {
+    let port = 3497;
+    let mut storage = InMemoryStorage::new();
+    storage.write(b"key2", b"given");
+    storage.write(b"key3", b"given");
+    let server = ServerBuilder::new(storage, port).shards(4).build();
+    assert_eq!(server.start(), Some(ServerState::Started));
+    let redis_client = redis::Client::open(format!("redis://127.0.0.1:{}/", port)).unwrap();
+    let mut con = redis_client.get_connection().unwrap();
+
+    // the keys of the storage given are moved to their shard
+    let x: Vec<String> = cmd("MGET").arg(&["key2", "key3"]).query(&mut con).unwrap();
+    assert_eq!(x, vec!["given", "given"]);
+    let x: String = con.get("key2").unwrap();
+    assert_eq!(x, "given");
+
+    // the connections writing keys in different shards run side by side
+    let writers = (0..8)
+        .map(|i| {
+            let redis_client = redis_client.clone();
+            std::thread::spawn(move || {
+                let mut con = redis_client.get_connection().unwrap();
+                for _ in 0..100 {
+                    let _: u32 = cmd("INCR")
+                        .arg(format!("counter{}", i))
+                        .query(&mut con)
+                        .unwrap();
+                    let _: u32 = cmd("INCR").arg("counter").query(&mut con).unwrap();
+                }
+            })
+        })
+        .collect::<Vec<_>>();
+    writers
+        .into_iter()
+        .for_each(|writer| writer.join().unwrap());
+    let x: u32 = con.get("counter").unwrap();
+    assert_eq!(x, 800);
+    let keys = (0..8).map(|i| format!("counter{}", i)).collect::<Vec<_>>();
+    let x: Vec<u32> = cmd("MGET").arg(&keys).query(&mut con).unwrap();
+    assert_eq!(x, vec![100; 8]);
+
+    // the commands whose keys are in several shards see all of them, as
+    // the transactions do
+    let _: () = con.sadd("set1", &["a", "b", "c"]).unwrap();
+    let _: () = con.sadd("set2", &["b", "c", "d"]).unwrap();
+    let _: () = con.sadd("set3", &["c", "d", "e"]).unwrap();
+    let x: u32 = cmd("SINTERCARD")
+        .arg(&["3", "set1", "set2", "set3"])
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(x, 1);
+    let _: () = con
+        .set_multiple(&[("key1", "1"), ("key2", "2"), ("key3", "3")])
+        .unwrap();
+    let _: () = cmd("MULTI").query(&mut con).unwrap();
+    let _: () = cmd("INCR").arg("key1").query(&mut con).unwrap();
+    let _: () = cmd("MGET")
+        .arg(&["key1", "key2", "key3"])
+        .query(&mut con)
+        .unwrap();
+    let (x, y): (u32, Vec<String>) = cmd("EXEC").query(&mut con).unwrap();
+    assert_eq!(x, 2);
+    assert_eq!(y, vec!["2", "2", "3"]);
+    let x: redis::InfoDict = cmd("INFO").arg("keyspace").query(&mut con).unwrap();
+    assert!(x.get::<String>("db0").unwrap().starts_with("keys=15,"));
+
+    // SWAPDB swaps the databases of every shard
+    let _: () = cmd("SWAPDB").arg(&["0", "1"]).query(&mut con).unwrap();
+    let x: Option<String> = con.get("key1").unwrap();
+    assert_eq!(x, None);
+    let _: () = cmd("SELECT").arg(1).query(&mut con).unwrap();
+    let x: Vec<String> = cmd("MGET")
+        .arg(&["key1", "key2", "key3"])
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(x, vec!["2", "2", "3"]);
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+}
+
+/// run the command on the databases from another thread, the receiver
+/// given back telling its reply once it ran
+fn run_apart(
+    databases: &std::sync::Arc<super::util::shards::Shards<InMemoryStorage>>,
+    shared: &std::sync::Arc<super::util::shared::Shared>,
+    args: &[&str],
+) -> std::sync::mpsc::Receiver<String> {
+    use super::util::connection::Connection;
+    use super::util::replication::request;
+    use super::util::run_command_and_get_response;
+
+    let (databases, shared) = (databases.clone(), shared.clone());
+    let mut bytes = [0; 512];
+    let args = args.iter().map(|arg| arg.as_bytes()).collect::<Vec<_>>();
+    let packed = request(&args);
+    bytes[..packed.len()].copy_from_slice(&packed);
+    let (sender, receiver) = std::sync::mpsc::channel();
+    std::thread::spawn(move || {
+        let mut connection = Connection::new();
+        let (_, response) =
+            run_command_and_get_response(&databases, &shared, &mut connection, &bytes);
+        let _ = sender.send(String::from_utf8(response).unwrap());
+    });
+    receiver
+}
+
+#[test]
+fn shards_run_apart() {
+    use super::util::cluster::key_slot;
+    use super::util::lock_then_release;
+    use super::util::shards::Shards;
+    use super::util::shared::Shared;
+    use crate::command::Command;
+    use std::sync::Arc;
+
+    let databases = Arc::new(Shards::new(InMemoryStorage::new(), 1, 2).unwrap());
+    let shared = Arc::new(Shared::new());
+    let run = |args: &[&str]| run_apart(&databases, &shared, args);
+    let shard = |key: &str| key_slot(key.as_bytes()) % 2;
+    let held = "key0";
+    let free = (1..)
+        .map(|i| format!("key{}", i))
+        .find(|key| shard(key) != shard(held))
+        .unwrap();
+    let timeout = Duration::from_secs(5);
+
+    // a command on a key of a shard held by another one waits for it, the
+    // commands on the other shard running meanwhile
+    let shard = databases.shard_of(&Command::Get(held.into())).unwrap();
+    let guard = lock_then_release(&shard);
+    let waiting = run(&["SET", held, "value"]);
+    assert_eq!(
+        run(&["SET", &free, "value"]).recv_timeout(timeout),
+        Ok("+OK\r\n".to_string())
+    );
+    assert_eq!(
+        run(&["GET", &free]).recv_timeout(timeout),
+        Ok("+value\r\n".to_string())
+    );
+    assert!(waiting.recv_timeout(Duration::from_millis(50)).is_err());
+    drop(guard);
+    assert_eq!(waiting.recv_timeout(timeout), Ok("+OK\r\n".to_string()));
+
+    // as the commands with no key run while every shard is held, unlike the
+    // ones on keys of several shards
+    let guard = lock_then_release(&*databases);
+    assert_eq!(
+        run(&["PING"]).recv_timeout(timeout),
+        Ok("+PONG\r\n".to_string())
+    );
+    let waiting = run(&["MSET", held, "other", &free, "other"]);
+    assert!(waiting.recv_timeout(Duration::from_millis(50)).is_err());
+    drop(guard);
+    assert_eq!(waiting.recv_timeout(timeout), Ok("+OK\r\n".to_string()));
+}
+
+#[test]
+fn shared_misses() {
+    use super::util::lock_then_release;
+    use super::util::shards::Shards;
+    use super::util::shared::Shared;
+    use crate::command::Command;
+    use std::sync::Arc;
+
+    let databases = Arc::new(Shards::new(InMemoryStorage::new(), 1, 1).unwrap());
+    let shared = Arc::new(Shared::new());
+    let run = |args: &[&str]| run_apart(&databases, &shared, args);
+    let timeout = Duration::from_secs(5);
+    assert_eq!(
+        run(&["SET", "key", "value"]).recv_timeout(timeout),
+        Ok("+OK\r\n".to_string())
+    );
+    assert_eq!(
+        run(&["PSETEX", "volatile", "10", "value"]).recv_timeout(timeout),
+        Ok("+OK\r\n".to_string())
+    );
+    assert_eq!(
+        run(&["HSET", "hash", "field", "value"]).recv_timeout(timeout),
+        Ok(":1\r\n".to_string())
+    );
+
+    // the keys missing are read alongside the other readers as the ones
+    // found, each being counted as a miss, as the keys of another type MGET
+    // has no value for
+    let shard = databases.shard_of(&Command::Get(b"key".to_vec())).unwrap();
+    let guard = shard.read();
+    assert_eq!(
+        run(&["GET", "missing"]).recv_timeout(timeout),
+        Ok("$-1\r\n".to_string())
+    );
+    assert_eq!(
+        run(&["MGET", "key", "missing", "hash"]).recv_timeout(timeout),
+        Ok("*3\r\n+value\r\n$-1\r\n$-1\r\n".to_string())
+    );
+    assert_eq!(
+        run(&["EXISTS", "missing"]).recv_timeout(timeout),
+        Ok(":0\r\n".to_string())
+    );
+    {
+        let stats = lock_then_release(&shared.stats);
+        assert_eq!((stats.keyspace_hits, stats.keyspace_misses), (1, 4));
+    }
+
+    // unlike a key that expired, which waits for the shard to be removed,
+    // or GET on a key of another type, which waits to reply WRONGTYPE
+    sleep(Duration::from_millis(20));
+    let expired = run(&["GET", "volatile"]);
+    let wrong_type = run(&["GET", "hash"]);
+    assert!(expired.recv_timeout(Duration::from_millis(50)).is_err());
+    assert!(wrong_type.recv_timeout(Duration::from_millis(50)).is_err());
+    drop(guard);
+    assert_eq!(expired.recv_timeout(timeout), Ok("$-1\r\n".to_string()));
+    assert!(wrong_type
+        .recv_timeout(timeout)
+        .unwrap()
+        .starts_with("-WRONGTYPE"));
+    let stats = lock_then_release(&shared.stats);
+    assert_eq!((stats.expired_keys, stats.keyspace_misses), (1, 5));
+}
+
+#[test]
+#[serial]
+fn shared_reads() {
+    let port = 3498;
+    let server = Server::new(InMemoryStorage::new(), port);
+    let events = server.key_events();
+    assert_eq!(server.start(), Some(ServerState::Started));
+    let redis_client = redis::Client::open(format!("redis://127.0.0.1:{}/", port)).unwrap();
+    let mut con = redis_client.get_connection().unwrap();
+    let _: () = cmd("DEBUG")
+        .arg(&["SET-ACTIVE-EXPIRE", "0"])
+        .query(&mut con)
+        .unwrap();
+    let _: () = con.set("key", "value").unwrap();
+    let _: () = con.pset_ex("volatile", "value", 10).unwrap();
+
+    // the connections reading the same keys read them side by side, each
+    // read being counted
+    let readers = (0..8)
+        .map(|_| {
+            let redis_client = redis_client.clone();
+            std::thread::spawn(move || {
+                let mut con = redis_client.get_connection().unwrap();
+                for _ in 0..100 {
+                    let x: String = con.get("key").unwrap();
+                    assert_eq!(x, "value");
+                    let x: bool = con.exists("key").unwrap();
+                    assert!(x);
+                }
+            })
+        })
+        .collect::<Vec<_>>();
+    readers
+        .into_iter()
+        .for_each(|reader| reader.join().unwrap());
+    let x: redis::InfoDict = cmd("INFO").arg("stats").query(&mut con).unwrap();
+    assert_eq!(x.get("keyspace_hits"), Some(1600));
+
+    // a key read once expired is removed and told as any other
+    sleep(Duration::from_millis(20));
+    let x: Option<String> = con.get("volatile").unwrap();
+    assert_eq!(x, None);
+    assert_eq!(
+        events.try_recv(),
+        Ok(KeyEvent::Expired(0, b"volatile".to_vec()))
+    );
+    let x: redis::InfoDict = cmd("INFO").arg("stats").query(&mut con).unwrap();
+    assert_eq!(x.get("expired_keys"), Some(1));
+    assert_eq!(x.get("keyspace_misses"), Some(1));
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+}
+
+#[test]
+#[serial]
+fn cluster_readonly() {
+    let (master_port, replica_port) = (3499, 3500);
+    let master = ServerBuilder::new(InMemoryStorage::new(), master_port)
+        .cluster_enabled(true)
+        .build();
+    let replica = ServerBuilder::new(InMemoryStorage::new(), replica_port)
+        .cluster_enabled(true)
+        .build();
+    assert_eq!(master.start(), Some(ServerState::Started));
+    assert_eq!(replica.start(), Some(ServerState::Started));
+    let redis_client = redis::Client::open(format!("redis://127.0.0.1:{}/", master_port)).unwrap();
+    let mut master_con = redis_client.get_connection().unwrap();
+    let redis_client = redis::Client::open(format!("redis://127.0.0.1:{}/", replica_port)).unwrap();
+    let mut replica_con = redis_client.get_connection().unwrap();
+
+    // the master serves every slot, the replica getting to know it
+    let _: () = cmd("CLUSTER")
+        .arg(&["ADDSLOTSRANGE", "0", "16383"])
+        .query(&mut master_con)
+        .unwrap();
+    let _: () = master_con.set("foo", "value").unwrap();
+    let _: () = cmd("CLUSTER")
+        .arg("MEET")
+        .arg("127.0.0.1")
+        .arg(master_port)
+        .query(&mut replica_con)
+        .unwrap();
+    let _: () = cmd("REPLICAOF")
+        .arg("127.0.0.1")
+        .arg(master_port)
+        .query(&mut replica_con)
+        .unwrap();
+    let started = Instant::now();
+    loop {
+        let x: redis::InfoDict = cmd("CLUSTER").arg("INFO").query(&mut replica_con).unwrap();
+        let y: redis::InfoDict = cmd("INFO")
+            .arg("replication")
+            .query(&mut replica_con)
+            .unwrap();
+        let ready = x.get("cluster_state") == Some("ok".to_string())
+            && y.get("master_link_status") == Some("up".to_string());
+        if ready || started.elapsed() > Duration::from_secs(5) {
+            break;
+        }
+        sleep(Duration::from_millis(20));
+    }
+
+    // the keys are read from the master unless the connection is READONLY
+    let x: RedisResult<String> = replica_con.get("foo");
+    assert_eq!(x.unwrap_err().code(), Some("MOVED"));
+    let _: () = cmd("READONLY").query(&mut replica_con).unwrap();
+    let x: String = replica_con.get("foo").unwrap();
+    assert_eq!(x, "value");
+    let x: RedisResult<()> = replica_con.set("foo", "other");
+    assert_eq!(x.unwrap_err().code(), Some("MOVED"));
+    let _: () = cmd("READWRITE").query(&mut replica_con).unwrap();
+    let x: RedisResult<String> = replica_con.get("foo");
+    assert_eq!(x.unwrap_err().code(), Some("MOVED"));
+
+    assert_eq!(replica.stop(), Some(ServerState::Stopped));
+    assert_eq!(master.stop(), Some(ServerState::Stopped));
+}
+
+#[test]
+#[serial]
+fn durable_siblings() {
+    use crate::storage::log_file::LogFileStorage;
+
+    let port = 3501;
+    let dir = std::env::temp_dir().join(format!("redisless-siblings-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    // no dump is read, the keys being read back from the logs alone
+    let config = dir.join("redis.conf");
+    std::fs::write(&config, format!("dir {}\n", dir.display())).unwrap();
+    let path = dir.join("keys.log");
+    let start = |shards: Option<usize>| {
+        let builder = ServerBuilder::new(LogFileStorage::open(&path).unwrap(), port)
+            .config_file(&config)
+            .unwrap();
+        let server = match shards {
+            Some(shards) => builder.shards(shards),
+            None => builder,
+        }
+        .build();
+        assert_eq!(server.start(), Some(ServerState::Started));
+        let redis_client = redis::Client::open(format!("redis://127.0.0.1:{}/", port)).unwrap();
+        (server, redis_client.get_connection().unwrap())
+    };
+    let logs = || {
+        std::fs::read_dir(&dir)
+            .unwrap()
+            .filter(|entry| {
+                let name = entry.as_ref().unwrap().file_name();
+                name.to_string_lossy().starts_with("keys.log")
+            })
+            .count()
+    };
+    let read_back = |con: &mut redis::Connection| {
+        for i in 0..10 {
+            let x: u32 = con.get(format!("key{}", i)).unwrap();
+            assert_eq!(x, i);
+        }
+        let _: () = cmd("SELECT").arg(1).query(con).unwrap();
+        let x: String = con.get("other").unwrap();
+        assert_eq!(x, "value");
+    };
+
+    // a storage on disk is a single shard unless told, with a log for each
+    // database next to the first one
+    let (server, mut con) = start(None);
+    for i in 0..10 {
+        let _: () = con.set(format!("key{}", i), i).unwrap();
+    }
+    let _: () = cmd("SELECT").arg(1).query(&mut con).unwrap();
+    let _: () = con.set("other", "value").unwrap();
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+    assert_eq!(logs(), 16);
+
+    // split into shards, the keys read back are moved to the shard they
+    // belong to, each shard of each database having a log of its own
+    let (server, mut con) = start(Some(4));
+    read_back(&mut con);
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+    assert_eq!(logs(), 16 * 4);
+
+    // and read back from there once the server starts again
+    let (server, mut con) = start(Some(4));
+    read_back(&mut con);
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+/// send a command over RESP and read what is replied
+fn resp_request(stream: &mut TcpStream, args: &[&str]) -> String {
+    let mut bytes = format!("*{}\r\n", args.len());
+    for arg in args {
+        bytes.push_str(&format!("${}\r\n{}\r\n", arg.len(), arg));
+    }
+    stream.write_all(bytes.as_bytes()).unwrap();
+    resp_read(stream)
+}
+
+/// what was written to the stream, read at once
+fn resp_read(stream: &mut TcpStream) -> String {
+    sleep(Duration::from_millis(50));
+    let mut buf = [0; 512];
+    let length = stream.read(&mut buf).unwrap();
+    String::from_utf8_lossy(&buf[..length]).to_string()
+}
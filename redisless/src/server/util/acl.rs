@@ -0,0 +1,427 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use super::connection::Connection;
+use crate::command::table::{self, CATEGORIES, COMMANDS};
+use crate::command::Command;
+use crate::glob::glob_match;
+use crate::protocol::response::RedisResponse;
+
+/// the users connections authenticate as, by name
+pub struct Acl {
+    users: BTreeMap<String, User>,
+}
+
+/// what a user is allowed to run, the keys and channels it may access
+#[derive(Clone)]
+pub struct User {
+    enabled: bool,
+    // any password is accepted
+    nopass: bool,
+    // SHA256 of the passwords, in hexadecimal
+    passwords: BTreeSet<String>,
+    key_patterns: Vec<Vec<u8>>,
+    channel_patterns: Vec<Vec<u8>>,
+    // names of the commands and subcommands allowed, a container allowing
+    // all of its subcommands
+    commands: BTreeSet<&'static str>,
+    // the command rules as given since the last +@all or -@all
+    command_rules: Vec<String>,
+}
+
+impl User {
+    /// a user that is disabled and allowed nothing
+    fn new() -> Self {
+        User {
+            enabled: false,
+            nopass: false,
+            passwords: BTreeSet::new(),
+            key_patterns: vec![],
+            channel_patterns: vec![],
+            commands: BTreeSet::new(),
+            command_rules: vec!["-@all".to_string()],
+        }
+    }
+
+    fn apply(&mut self, rule: &str) -> Result<(), &'static str> {
+        match rule.to_ascii_lowercase().as_str() {
+            "on" => self.enabled = true,
+            "off" => self.enabled = false,
+            "nopass" => {
+                self.nopass = true;
+                self.passwords.clear();
+            }
+            "resetpass" => {
+                self.nopass = false;
+                self.passwords.clear();
+            }
+            "allkeys" => self.key_patterns = vec![b"*".to_vec()],
+            "resetkeys" => self.key_patterns.clear(),
+            "allchannels" => self.channel_patterns = vec![b"*".to_vec()],
+            "resetchannels" => self.channel_patterns.clear(),
+            "allcommands" => self.apply("+@all")?,
+            "nocommands" => self.apply("-@all")?,
+            "reset" => *self = User::new(),
+            _ => match rule.split_at(rule.chars().next().map_or(0, |c| c.len_utf8())) {
+                (">", password) => {
+                    self.nopass = false;
+                    self.passwords.insert(sha256_hex(password.as_bytes()));
+                }
+                ("<", password) => {
+                    if !self.passwords.remove(&sha256_hex(password.as_bytes())) {
+                        return Err("no such password");
+                    }
+                }
+                ("#", hash) => {
+                    let is_hex = hash.bytes().all(|c| matches!(c, b'0'..=b'9' | b'a'..=b'f'));
+                    if hash.len() != 64 || !is_hex {
+                        return Err("The password hash must be exactly 64 characters and contain only lowercase hexadecimal characters");
+                    }
+                    self.nopass = false;
+                    self.passwords.insert(hash.to_string());
+                }
+                ("!", hash) => {
+                    if !self.passwords.remove(hash) {
+                        return Err("no such password");
+                    }
+                }
+                ("~", pattern) => self.key_patterns.push(pattern.as_bytes().to_vec()),
+                ("&", pattern) => self.channel_patterns.push(pattern.as_bytes().to_vec()),
+                (sign @ "+", name) | (sign @ "-", name) => {
+                    let name = name.to_ascii_lowercase();
+                    let names = match name.strip_prefix('@') {
+                        Some("all") => {
+                            self.command_rules.clear();
+                            COMMANDS.iter().map(|info| info.name).collect()
+                        }
+                        Some(category) if CATEGORIES.contains(&category) => COMMANDS
+                            .iter()
+                            .filter(|info| info.categories.contains(&category))
+                            .map(|info| info.name)
+                            .collect(),
+                        Some(_) => return Err("Unknown command or category name in ACL"),
+                        None => match COMMANDS.iter().find(|info| info.name == name) {
+                            // removing a container removes its subcommands
+                            Some(info) if sign == "-" => COMMANDS
+                                .iter()
+                                .filter(|other| other.name.split('|').next() == Some(info.name))
+                                .map(|other| other.name)
+                                .collect(),
+                            Some(info) => vec![info.name],
+                            None => return Err("Unknown command or category name in ACL"),
+                        },
+                    };
+
+                    // removing a subcommand of an allowed container keeps
+                    // the other subcommands
+                    let container = name.split('|').next().unwrap_or(&name);
+                    if sign == "-" && name.contains('|') && self.commands.remove(container) {
+                        let subcommands = COMMANDS
+                            .iter()
+                            .filter(|info| info.name.split('|').next() == Some(container))
+                            .filter(|info| info.name.contains('|'));
+                        self.commands.extend(subcommands.map(|info| info.name));
+                    }
+
+                    for name in names {
+                        match sign {
+                            "+" => self.commands.insert(name),
+                            _ => self.commands.remove(name),
+                        };
+                    }
+                    self.command_rules.push(format!("{}{}", sign, name));
+                }
+                _ => return Err("Syntax error"),
+            },
+        }
+
+        Ok(())
+    }
+
+    fn is_allowed(&self, full_name: &str) -> bool {
+        let container = full_name.split('|').next().unwrap_or(full_name);
+        self.commands.contains(full_name) || self.commands.contains(container)
+    }
+
+    /// the user as ACL LIST describes it
+    fn describe(&self, name: &str) -> String {
+        let mut rules = vec![
+            "user".to_string(),
+            name.to_string(),
+            if self.enabled { "on" } else { "off" }.to_string(),
+        ];
+        if self.nopass {
+            rules.push("nopass".to_string());
+        }
+        rules.extend(self.passwords.iter().map(|hash| format!("#{}", hash)));
+        rules.extend(
+            self.key_patterns
+                .iter()
+                .map(|pattern| format!("~{}", String::from_utf8_lossy(pattern))),
+        );
+        if self.channel_patterns.is_empty() {
+            rules.push("resetchannels".to_string());
+        }
+        rules.extend(
+            self.channel_patterns
+                .iter()
+                .map(|pattern| format!("&{}", String::from_utf8_lossy(pattern))),
+        );
+        rules.extend(self.command_rules.iter().cloned());
+        rules.join(" ")
+    }
+}
+
+impl Acl {
+    /// only the default user, allowed everything and needing the password if
+    /// any
+    pub fn new(requirepass: Option<String>) -> Self {
+        let mut user = User::new();
+        for rule in ["on", "allkeys", "allchannels", "+@all"].iter() {
+            let _ = user.apply(rule);
+        }
+        if let Some(password) = requirepass {
+            user.passwords.insert(sha256_hex(password.as_bytes()));
+        }
+        user.nopass = user.passwords.is_empty();
+
+        let mut users = BTreeMap::new();
+        users.insert("default".to_string(), user);
+        Acl { users }
+    }
+
+    /// the user the connection runs commands as, none until it
+    /// authenticates unless the default user needs no password
+    pub fn username<'a>(&'a self, connection: &'a Connection) -> Option<&'a str> {
+        let name = connection.user.as_deref().unwrap_or("default");
+        match self.users.get(name) {
+            Some(user) if !user.enabled => None,
+            Some(user) if connection.user.is_some() || user.nopass => Some(name),
+            _ => None,
+        }
+    }
+
+    pub fn default_user_has_password(&self) -> bool {
+        match self.users.get("default") {
+            Some(user) => !user.nopass,
+            None => false,
+        }
+    }
+
+    pub fn authenticate(&self, username: &str, password: &[u8]) -> bool {
+        match self.users.get(username) {
+            Some(user) if user.enabled => {
+                user.nopass || user.passwords.contains(&sha256_hex(password))
+            }
+            _ => false,
+        }
+    }
+
+    /// whether the connection may run the command given its name and
+    /// subcommand, failing with the error reply
+    pub fn check(
+        &self,
+        connection: &Connection,
+        command: &Command,
+        name: &str,
+        subcommand: Option<&str>,
+    ) -> Result<(), String> {
+        // HELLO refuses to run unless authenticated once it is given AUTH
+        if matches!(
+            command,
+            Command::Auth(..) | Command::Hello(..) | Command::Quit | Command::Reset
+        ) {
+            return Ok(());
+        }
+        let (username, user) = match self.username(connection) {
+            Some(username) => (username, &self.users[username]),
+            None => return Err("NOAUTH Authentication required.".to_string()),
+        };
+
+        let full_name = table::lookup(name, subcommand).map_or(name, |info| info.name);
+        if !user.is_allowed(full_name) {
+            return Err(format!(
+                "NOPERM User {} has no permissions to run the '{}' command",
+                username, full_name
+            ));
+        }
+
+        let key_allowed = |key: &[u8]| {
+            user.key_patterns
+                .iter()
+                .any(|pattern| glob_match(pattern, key))
+        };
+        if !command.keys().into_iter().all(key_allowed) {
+            return Err("NOPERM No permissions to access a key".to_string());
+        }
+
+        // patterns subscribed to have to be allowed as they are
+        let channel_allowed = |channel: &[u8]| {
+            user.channel_patterns.iter().any(|pattern| match command {
+                Command::PSubscribe(_) => pattern == b"*" || pattern == channel,
+                _ => glob_match(pattern, channel),
+            })
+        };
+        if !command.channels().into_iter().all(channel_allowed) {
+            return Err("NOPERM No permissions to access a channel".to_string());
+        }
+
+        Ok(())
+    }
+
+    /// create the user if needed then apply the rules, none being applied if
+    /// one is invalid
+    pub fn set_user(&mut self, username: &str, rules: &[Vec<u8>]) -> Result<(), String> {
+        let mut user = self.users.get(username).cloned().unwrap_or_else(User::new);
+        for rule in rules {
+            let rule = String::from_utf8_lossy(rule);
+            if let Err(reason) = user.apply(&rule) {
+                return Err(format!(
+                    "ERR Error in ACL SETUSER modifier '{}': {}",
+                    rule, reason
+                ));
+            }
+        }
+
+        self.users.insert(username.to_string(), user);
+        Ok(())
+    }
+
+    /// the user as ACL GETUSER replies it
+    pub fn get_user(&self, username: &str) -> RedisResponse {
+        let user = match self.users.get(username) {
+            Some(user) => user,
+            None => return RedisResponse::Nil,
+        };
+        let bulk = |s: &str| RedisResponse::BulkString(s.as_bytes().to_vec());
+        let patterns = |prefix: &str, patterns: &[Vec<u8>]| {
+            let patterns = patterns
+                .iter()
+                .map(|pattern| format!("{}{}", prefix, String::from_utf8_lossy(pattern)))
+                .collect::<Vec<_>>();
+            bulk(&patterns.join(" "))
+        };
+
+        let mut flags = vec![bulk(if user.enabled { "on" } else { "off" })];
+        if user.nopass {
+            flags.push(bulk("nopass"));
+        }
+        RedisResponse::Array(vec![
+            bulk("flags"),
+            RedisResponse::Array(flags),
+            bulk("passwords"),
+            RedisResponse::Array(user.passwords.iter().map(|hash| bulk(hash)).collect()),
+            bulk("commands"),
+            bulk(&user.command_rules.join(" ")),
+            bulk("keys"),
+            patterns("~", &user.key_patterns),
+            bulk("channels"),
+            patterns("&", &user.channel_patterns),
+            bulk("selectors"),
+            RedisResponse::Array(vec![]),
+        ])
+    }
+
+    /// returns how many users were deleted
+    pub fn del_users(&mut self, usernames: &[Vec<u8>]) -> Result<usize, String> {
+        if usernames.iter().any(|username| username == b"default") {
+            return Err("ERR The 'default' user cannot be removed".to_string());
+        }
+
+        Ok(usernames
+            .iter()
+            .filter(|username| {
+                let username = String::from_utf8_lossy(username);
+                self.users.remove(username.as_ref()).is_some()
+            })
+            .count())
+    }
+
+    pub fn list(&self) -> Vec<String> {
+        self.users
+            .iter()
+            .map(|(name, user)| user.describe(name))
+            .collect()
+    }
+
+    pub fn usernames(&self) -> Vec<String> {
+        self.users.keys().cloned().collect()
+    }
+}
+
+impl Default for Acl {
+    fn default() -> Self {
+        Acl::new(None)
+    }
+}
+
+/// the SHA256 digest of the data in hexadecimal, as passwords are kept
+fn sha256_hex(data: &[u8]) -> String {
+    const K: [u32; 64] = [
+        0x428a2f98, 0x71374491, 0xb5c0fbcf, 0xe9b5dba5, 0x3956c25b, 0x59f111f1, 0x923f82a4,
+        0xab1c5ed5, 0xd807aa98, 0x12835b01, 0x243185be, 0x550c7dc3, 0x72be5d74, 0x80deb1fe,
+        0x9bdc06a7, 0xc19bf174, 0xe49b69c1, 0xefbe4786, 0x0fc19dc6, 0x240ca1cc, 0x2de92c6f,
+        0x4a7484aa, 0x5cb0a9dc, 0x76f988da, 0x983e5152, 0xa831c66d, 0xb00327c8, 0xbf597fc7,
+        0xc6e00bf3, 0xd5a79147, 0x06ca6351, 0x14292967, 0x27b70a85, 0x2e1b2138, 0x4d2c6dfc,
+        0x53380d13, 0x650a7354, 0x766a0abb, 0x81c2c92e, 0x92722c85, 0xa2bfe8a1, 0xa81a664b,
+        0xc24b8b70, 0xc76c51a3, 0xd192e819, 0xd6990624, 0xf40e3585, 0x106aa070, 0x19a4c116,
+        0x1e376c08, 0x2748774c, 0x34b0bcb5, 0x391c0cb3, 0x4ed8aa4a, 0x5b9cca4f, 0x682e6ff3,
+        0x748f82ee, 0x78a5636f, 0x84c87814, 0x8cc70208, 0x90befffa, 0xa4506ceb, 0xbef9a3f7,
+        0xc67178f2,
+    ];
+    let mut h: [u32; 8] = [
+        0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a, 0x510e527f, 0x9b05688c, 0x1f83d9ab,
+        0x5be0cd19,
+    ];
+
+    // padded with a 1 bit, zeros, then the length in bits
+    let mut message = data.to_vec();
+    message.push(0x80);
+    while message.len() % 64 != 56 {
+        message.push(0);
+    }
+    message.extend_from_slice(&((data.len() as u64) * 8).to_be_bytes());
+
+    for block in message.chunks(64) {
+        let mut w = [0u32; 64];
+        for (i, word) in block.chunks(4).enumerate() {
+            w[i] = u32::from_be_bytes([word[0], word[1], word[2], word[3]]);
+        }
+        for i in 16..64 {
+            let s0 = w[i - 15].rotate_right(7) ^ w[i - 15].rotate_right(18) ^ (w[i - 15] >> 3);
+            let s1 = w[i - 2].rotate_right(17) ^ w[i - 2].rotate_right(19) ^ (w[i - 2] >> 10);
+            w[i] = w[i - 16]
+                .wrapping_add(s0)
+                .wrapping_add(w[i - 7])
+                .wrapping_add(s1);
+        }
+
+        let [mut a, mut b, mut c, mut d, mut e, mut f, mut g, mut hh] = h;
+        for i in 0..64 {
+            let s1 = e.rotate_right(6) ^ e.rotate_right(11) ^ e.rotate_right(25);
+            let ch = (e & f) ^ (!e & g);
+            let t1 = hh
+                .wrapping_add(s1)
+                .wrapping_add(ch)
+                .wrapping_add(K[i])
+                .wrapping_add(w[i]);
+            let s0 = a.rotate_right(2) ^ a.rotate_right(13) ^ a.rotate_right(22);
+            let maj = (a & b) ^ (a & c) ^ (b & c);
+            let t2 = s0.wrapping_add(maj);
+
+            hh = g;
+            g = f;
+            f = e;
+            e = d.wrapping_add(t1);
+            d = c;
+            c = b;
+            b = a;
+            a = t1.wrapping_add(t2);
+        }
+
+        for (h, v) in h.iter_mut().zip([a, b, c, d, e, f, g, hh].iter()) {
+            *h = h.wrapping_add(*v);
+        }
+    }
+
+    h.iter().map(|word| format!("{:08x}", word)).collect()
+}
@@ -0,0 +1,288 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufReader, BufWriter, ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::{Arc, Weak};
+use std::thread;
+use std::time::Duration;
+
+use super::config::Config;
+use super::lock_then_release;
+use super::shared::Shared;
+use crate::storage::models::StoredValue;
+use crate::storage::snapshot::{self, Change};
+use crate::storage::Storage;
+
+// how often the changes are flushed to the disk when they are every second
+const FSYNC_INTERVAL: Duration = Duration::from_secs(1);
+
+/// when the changes appended are flushed to the disk, rather than left to
+/// the operating system
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Fsync {
+    // before the command is replied
+    Always,
+    // by another thread, once a second
+    EverySec,
+    No,
+}
+
+impl Fsync {
+    /// the policy of the `appendfsync` parameter
+    pub fn parse(value: &[u8]) -> Self {
+        match value {
+            b"always" => Fsync::Always,
+            b"no" => Fsync::No,
+            _ => Fsync::EverySec,
+        }
+    }
+}
+
+/// the append only file, made of a snapshot of the dataset followed by the
+/// changes made to it since, rewritten as a snapshot alone to be compacted
+pub struct Aof {
+    // whether the changes are appended, the file being opened once
+    // written the first time
+    enabled: bool,
+    file: Option<File>,
+    // the changes made while the file is rewritten, appended to the new
+    // file once written
+    rewrite_buffer: Option<Vec<u8>>,
+    pub fsync: Fsync,
+    // whether changes were written since the file was last flushed
+    unsynced: bool,
+    pub last_write_ok: bool,
+    pub last_rewrite_ok: bool,
+}
+
+impl Aof {
+    pub fn new() -> Self {
+        Aof {
+            enabled: false,
+            file: None,
+            rewrite_buffer: None,
+            fsync: Fsync::EverySec,
+            unsynced: false,
+            last_write_ok: true,
+            last_rewrite_ok: true,
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub fn is_rewriting(&self) -> bool {
+        self.rewrite_buffer.is_some()
+    }
+
+    /// whether the changes made to the dataset are to be appended
+    pub fn is_logging(&self) -> bool {
+        self.enabled || self.is_rewriting()
+    }
+
+    /// append a change to the file, and to the ones made during a rewrite
+    pub fn append(&mut self, change: &Change) {
+        let mut bytes = vec![];
+        let _ = snapshot::write_change(&mut bytes, change);
+        if let Some(buffer) = &mut self.rewrite_buffer {
+            buffer.extend_from_slice(&bytes);
+        }
+        let fsync = self.fsync;
+        if let Some(file) = &mut self.file {
+            let written = file.write_all(&bytes).and_then(|()| match fsync {
+                Fsync::Always => file.sync_data(),
+                _ => Ok(()),
+            });
+            self.last_write_ok = written.is_ok();
+            self.unsynced = fsync == Fsync::EverySec;
+        }
+    }
+}
+
+impl Default for Aof {
+    fn default() -> Self {
+        Aof::new()
+    }
+}
+
+/// the append only file, as configured
+pub fn path(config: &Config) -> PathBuf {
+    Path::new(&*String::from_utf8_lossy(config.value("dir")))
+        .join(&*String::from_utf8_lossy(config.value("appendfilename")))
+}
+
+/// load the dataset from the append only file into the databases, false if
+/// there is no such file
+pub fn load<T: Storage>(databases: &mut [T], config: &Config) -> io::Result<bool> {
+    let file = match File::open(path(config)) {
+        Ok(file) => file,
+        Err(error) if error.kind() == ErrorKind::NotFound => return Ok(false),
+        Err(error) => return Err(error),
+    };
+
+    let mut reader = BufReader::new(file);
+    let saved = snapshot::read(&mut reader)?;
+    let changes = snapshot::read_changes(&mut reader)?;
+    let out_of_range = |db: usize| {
+        io::Error::new(
+            ErrorKind::InvalidData,
+            format!("the append only file changes database {}, out of range", db),
+        )
+    };
+
+    if saved.len() > databases.len() {
+        return Err(out_of_range(saved.len() - 1));
+    }
+    for (storage, keys) in databases.iter_mut().zip(saved) {
+        for (key, value) in keys {
+            storage.put(&key, value);
+        }
+    }
+    for change in changes {
+        match change {
+            Change::Put(db, key, value) => match databases.get_mut(db) {
+                Some(storage) => storage.put(&key, value),
+                None => return Err(out_of_range(db)),
+            },
+            Change::Remove(db, key) => match databases.get_mut(db) {
+                Some(storage) => {
+                    storage.remove(&key);
+                }
+                None => return Err(out_of_range(db)),
+            },
+            Change::SwapDb(first, second) => match first.max(second) < databases.len() {
+                true => databases.swap(first, second),
+                false => return Err(out_of_range(first.max(second))),
+            },
+        }
+    }
+
+    Ok(true)
+}
+
+/// rewrite the append only file from the databases then append the changes
+/// to it, as when the server starts
+pub fn rewrite<T: Storage>(databases: &mut [T], shared: &Shared) -> io::Result<()> {
+    let keys = databases
+        .iter_mut()
+        .map(|storage| storage.snapshot())
+        .collect::<Vec<_>>();
+    let path = path(&lock_then_release(&shared.config));
+    let temporary = temporary(&path);
+    if let Err(error) = write(&temporary, &keys).and_then(|()| fs::rename(&temporary, &path)) {
+        let _ = fs::remove_file(&temporary);
+        return Err(error);
+    }
+
+    let mut aof = lock_then_release(&shared.aof);
+    aof.enabled = true;
+    aof.file = Some(OpenOptions::new().append(true).open(&path)?);
+    Ok(())
+}
+
+/// rewrite the append only file from the databases as they are now on
+/// another thread, the changes made meanwhile being appended to the new
+/// file once written
+pub fn bgrewrite<T: Storage>(databases: &mut [T], shared: &Arc<Shared>) {
+    let keys = databases
+        .iter_mut()
+        .map(|storage| storage.snapshot())
+        .collect::<Vec<_>>();
+    let path = path(&lock_then_release(&shared.config));
+    lock_then_release(&shared.aof).rewrite_buffer = Some(vec![]);
+
+    let shared = Arc::clone(shared);
+    thread::spawn(move || {
+        let temporary = temporary(&path);
+        let written = write(&temporary, &keys);
+
+        // the changes made meanwhile are appended while no other can be,
+        // so that none is lost before the new file replaces the old one
+        let mut aof = lock_then_release(&shared.aof);
+        let buffer = aof.rewrite_buffer.take().unwrap_or_default();
+        let rewritten = written
+            .and_then(|()| OpenOptions::new().append(true).open(&temporary))
+            .and_then(|mut file| file.write_all(&buffer))
+            .and_then(|()| fs::rename(&temporary, &path))
+            .and_then(|()| match aof.enabled {
+                true => OpenOptions::new().append(true).open(&path).map(Some),
+                false => Ok(None),
+            });
+
+        aof.last_rewrite_ok = rewritten.is_ok();
+        match rewritten {
+            Ok(file) => aof.file = file,
+            Err(_) => {
+                let _ = fs::remove_file(&temporary);
+            }
+        }
+    });
+}
+
+/// flush the changes appended to the disk once a second when asked to, on
+/// a thread of its own so that the commands don't wait for the disk, until
+/// the server is dropped
+pub fn start_fsync_thread(shared: &Arc<Shared>) {
+    let shared: Weak<Shared> = Arc::downgrade(shared);
+    thread::spawn(move || loop {
+        thread::sleep(FSYNC_INTERVAL);
+        let shared = match shared.upgrade() {
+            Some(shared) => shared,
+            None => return,
+        };
+
+        // the file is flushed through a handle of its own, so that the
+        // changes keep being appended meanwhile
+        let file = {
+            let mut aof = lock_then_release(&shared.aof);
+            match (aof.fsync, aof.unsynced) {
+                (Fsync::EverySec, true) => {
+                    aof.unsynced = false;
+                    aof.file.as_ref().and_then(|file| file.try_clone().ok())
+                }
+                _ => None,
+            }
+        };
+        if let Some(file) = file {
+            let _ = file.sync_data();
+        }
+    });
+}
+
+/// append the changes from now on, once the file is rewritten so that it
+/// starts with the dataset as it is now
+pub fn enable<T: Storage>(databases: &mut [T], shared: &Arc<Shared>) {
+    let mut aof = lock_then_release(&shared.aof);
+    if aof.enabled {
+        return;
+    }
+    aof.enabled = true;
+    let is_rewriting = aof.is_rewriting();
+    drop(aof);
+
+    // a rewrite in progress opens the file once done
+    if !is_rewriting {
+        bgrewrite(databases, shared);
+    }
+}
+
+/// stop appending the changes, the file being left as it is
+pub fn disable(shared: &Shared) {
+    let mut aof = lock_then_release(&shared.aof);
+    aof.enabled = false;
+    aof.file = None;
+}
+
+/// the file a rewrite is written to before it replaces the given one, so
+/// that a rewrite failing halfway leaves the previous file whole
+fn temporary(path: &Path) -> PathBuf {
+    path.with_file_name(format!("temp-rewriteaof-{}.aof", process::id()))
+}
+
+fn write(path: &Path, keys: &[Vec<(Vec<u8>, StoredValue)>]) -> io::Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    snapshot::write(&mut writer, keys)?;
+    writer.flush()?;
+    writer.get_ref().sync_all()
+}
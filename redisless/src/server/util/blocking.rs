@@ -0,0 +1,30 @@
+use std::{
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::server::CommandResponse;
+
+// how often a blocked command tries again
+const RETRY_INTERVAL: Duration = Duration::from_millis(10);
+
+/// run the attempt until it gives a response or the timeout elapses, a
+/// None timeout blocking forever. The attempt is expected to lock the
+/// storage itself so that other connections can write in between.
+pub fn block_on<F>(timeout: Option<Duration>, mut attempt: F) -> Option<CommandResponse>
+where
+    F: FnMut() -> Option<CommandResponse>,
+{
+    let deadline = timeout.map(|timeout| Instant::now() + timeout);
+
+    loop {
+        if let Some(response) = attempt() {
+            return Some(response);
+        }
+
+        match deadline {
+            Some(deadline) if Instant::now() >= deadline => return None,
+            _ => thread::sleep(RETRY_INTERVAL),
+        }
+    }
+}
@@ -0,0 +1,239 @@
+use std::collections::BTreeMap;
+use std::io;
+use std::net::{Shutdown, SocketAddr, TcpStream};
+use std::time::{Duration, Instant};
+
+use super::connection::Connection;
+use crate::command::client::ClientKillFilter;
+
+/// what CLIENT LIST and CLIENT INFO tell about a connection, refreshed
+/// every time it runs a command
+pub struct Client {
+    pub addr: String,
+    pub laddr: String,
+    pub name: Option<Vec<u8>>,
+    created: Instant,
+    last_interaction: Instant,
+    last_command: String,
+    db: usize,
+    user: String,
+    subscriptions: (usize, usize, usize),
+    monitoring: bool,
+    // version of RESP the connection speaks
+    resp: u8,
+    // commands queued since MULTI, if in a transaction
+    queued: Option<usize>,
+    // shut down to close the connection from another one
+    stream: Option<TcpStream>,
+}
+
+impl Client {
+    fn new(stream: &TcpStream) -> Self {
+        let now = Instant::now();
+
+        Client {
+            addr: address(stream.peer_addr()),
+            laddr: address(stream.local_addr()),
+            name: None,
+            created: now,
+            last_interaction: now,
+            last_command: "NULL".to_string(),
+            db: 0,
+            user: "default".to_string(),
+            subscriptions: (0, 0, 0),
+            monitoring: false,
+            resp: 2,
+            queued: None,
+            stream: stream.try_clone().ok(),
+        }
+    }
+
+    /// the connection as a line of CLIENT LIST
+    pub fn describe(&self, id: u64) -> String {
+        let (sub, psub, ssub) = self.subscriptions;
+        let flags = match (self.queued, sub + psub + ssub, self.monitoring) {
+            (Some(_), _, _) => "x",
+            (None, _, true) => "O",
+            (None, 0, false) => "N",
+            (None, _, false) => "P",
+        };
+        let multi = match self.queued {
+            Some(queued) => queued as i64,
+            None => -1,
+        };
+
+        format!(
+            "id={} addr={} laddr={} name={} age={} idle={} flags={} db={} sub={} psub={} ssub={} multi={} cmd={} user={} resp={}",
+            id,
+            self.addr,
+            self.laddr,
+            String::from_utf8_lossy(self.name.as_deref().unwrap_or_default()),
+            self.created.elapsed().as_secs(),
+            self.last_interaction.elapsed().as_secs(),
+            flags,
+            self.db,
+            sub,
+            psub,
+            ssub,
+            multi,
+            self.last_command,
+            self.user,
+            self.resp,
+        )
+    }
+
+    pub fn is_pubsub(&self) -> bool {
+        let (sub, psub, ssub) = self.subscriptions;
+        sub + psub + ssub > 0
+    }
+
+    /// whether the connection is of the type CLIENT LIST or CLIENT KILL
+    /// filter on, there being no replication
+    pub fn is_of_type(&self, client_type: &[u8]) -> bool {
+        match client_type {
+            b"pubsub" => self.is_pubsub(),
+            b"normal" => !self.is_pubsub(),
+            _ => false,
+        }
+    }
+
+    fn matches(&self, id: u64, filter: &ClientKillFilter) -> bool {
+        (filter.ids.is_empty() || filter.ids.contains(&id))
+            && filter.addr.iter().all(|addr| *addr == self.addr)
+            && filter.laddr.iter().all(|laddr| *laddr == self.laddr)
+            && filter.user.iter().all(|user| *user == self.user)
+            && filter
+                .client_type
+                .iter()
+                .all(|client_type| self.is_of_type(client_type))
+    }
+}
+
+/// the connections of a server by id, shared by all of them
+#[derive(Default)]
+pub struct Clients {
+    clients: BTreeMap<u64, Client>,
+    // end of CLIENT PAUSE, and whether only the writes are held back
+    pause: Option<(Instant, bool)>,
+}
+
+impl Clients {
+    pub fn new() -> Self {
+        Clients {
+            clients: BTreeMap::new(),
+            pause: None,
+        }
+    }
+
+    /// hold the commands back until the timeout, a pause in progress being
+    /// extended rather than shortened, and holding back every command if
+    /// any of the two does
+    pub fn pause(&mut self, timeout: Duration, write_only: bool) {
+        let end = Instant::now() + timeout;
+        self.pause = match self.pause {
+            Some((until, only_writes)) if until > Instant::now() => {
+                Some((until.max(end), write_only && only_writes))
+            }
+            _ => Some((end, write_only)),
+        };
+    }
+
+    pub fn unpause(&mut self) {
+        self.pause = None;
+    }
+
+    /// whether a command is held back, given whether it may write
+    pub fn is_paused(&mut self, may_write: bool) -> bool {
+        match self.pause {
+            Some((until, _)) if until <= Instant::now() => {
+                self.pause = None;
+                false
+            }
+            Some((_, write_only)) => !write_only || may_write,
+            None => false,
+        }
+    }
+
+    pub fn register(&mut self, connection: &Connection, stream: &TcpStream) {
+        self.clients.insert(connection.id, Client::new(stream));
+    }
+
+    pub fn unregister(&mut self, connection: &Connection) {
+        self.clients.remove(&connection.id);
+    }
+
+    /// record the command the connection ran, along with its state
+    pub fn update(&mut self, connection: &Connection, command: &str) {
+        if let Some(client) = self.clients.get_mut(&connection.id) {
+            client.last_interaction = Instant::now();
+            client.last_command = command.to_string();
+            client.db = connection.db;
+            client.user = connection
+                .user
+                .clone()
+                .unwrap_or_else(|| "default".to_string());
+            client.subscriptions = (
+                connection.channels.len(),
+                connection.patterns.len(),
+                connection.shard_channels.len(),
+            );
+            client.monitoring = connection.monitoring;
+            client.resp = connection.protocol;
+            client.queued = connection
+                .transaction
+                .as_ref()
+                .map(|transaction| transaction.commands.len());
+        }
+    }
+
+    pub fn get(&self, id: u64) -> Option<&Client> {
+        self.clients.get(&id)
+    }
+
+    pub fn get_mut(&mut self, id: u64) -> Option<&mut Client> {
+        self.clients.get_mut(&id)
+    }
+
+    /// close the connections matching the filter, returns their ids
+    pub fn kill(&mut self, filter: &ClientKillFilter, caller: u64) -> Vec<u64> {
+        let killed = self
+            .clients
+            .iter()
+            .filter(|(id, client)| client.matches(**id, filter))
+            .filter(|(id, _)| !filter.skip_me || **id != caller)
+            .map(|(id, _)| *id)
+            .collect::<Vec<_>>();
+
+        // the caller is closed once it got the response
+        for id in &killed {
+            if let (Some(client), false) = (self.clients.remove(id), *id == caller) {
+                if let Some(stream) = client.stream {
+                    let _ = stream.shutdown(Shutdown::Both);
+                }
+            }
+        }
+
+        killed
+    }
+
+    /// close every connection, as the server stops
+    pub fn close_all(&self) {
+        for client in self.clients.values() {
+            if let Some(stream) = &client.stream {
+                let _ = stream.shutdown(Shutdown::Both);
+            }
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&u64, &Client)> {
+        self.clients.iter()
+    }
+}
+
+/// an address as CLIENT LIST shows it, empty if unknown
+fn address(addr: io::Result<SocketAddr>) -> String {
+    match addr {
+        Ok(addr) => addr.to_string(),
+        Err(_) => String::new(),
+    }
+}
@@ -0,0 +1,598 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::io::{self, BufRead, BufReader, ErrorKind, Read, Write};
+use std::net::{SocketAddr, TcpStream, ToSocketAddrs};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use super::lock_then_release;
+use super::replication::{self, read_line, request};
+use super::shared::Shared;
+use crate::protocol::response::RedisResponse;
+
+// the hash slots the keys are spread over
+pub const SLOTS: u16 = 16384;
+// what the commands whose keys don't hash to the same slot reply
+pub const CROSSSLOT_ERROR: &str = "CROSSSLOT Keys in request don't hash to the same slot";
+// what the cluster commands other than the ones telling about the server
+// reply when it doesn't run in cluster mode
+pub const DISABLED_ERROR: &str = "ERR This instance has cluster support disabled";
+// how often the other nodes are asked about the slots they serve and the
+// nodes they know
+const GOSSIP_INTERVAL: Duration = Duration::from_millis(100);
+// how long a node has to reply before being asked again
+const GOSSIP_TIMEOUT: Duration = Duration::from_secs(1);
+// the port of the cluster bus is the one of the clients plus this, as the
+// nodes of Redis have it, told about although the nodes talk over the port
+// of the clients
+const BUS_PORT_OFFSET: u32 = 10000;
+
+/// the cluster the server is part of, the nodes it met and the slots each
+/// serves, the server alone serving every slot when it doesn't run in
+/// cluster mode, for the clients that ask to be told so
+pub struct Cluster {
+    // the id of the node, as CLUSTER MYID replies it
+    pub myid: String,
+    // whether the server runs in cluster mode, set once it is built
+    pub enabled: bool,
+    // the slots the node serves, once assigned with CLUSTER ADDSLOTS
+    slots: BTreeSet<u16>,
+    // the other nodes, by id, as each of them tells about itself
+    nodes: BTreeMap<String, Node>,
+    // the addresses of the nodes met whose id is not known yet
+    met: BTreeSet<(String, u16)>,
+    // whether the thread gossiping with the other nodes was started
+    gossiping: bool,
+}
+
+/// another node of the cluster
+pub struct Node {
+    pub ip: String,
+    pub port: u16,
+    pub slots: BTreeSet<u16>,
+    // the unix time in milliseconds it last replied at
+    pub pong: u64,
+    // whether it replied the last time it was asked
+    pub connected: bool,
+}
+
+impl Cluster {
+    pub fn new() -> Self {
+        Cluster {
+            myid: replication::new_id(),
+            enabled: false,
+            slots: BTreeSet::new(),
+            nodes: BTreeMap::new(),
+            met: BTreeSet::new(),
+            gossiping: false,
+        }
+    }
+
+    /// get to know the node at the address, asked about the nodes it knows
+    /// from then on
+    pub fn meet(&mut self, ip: &str, port: u16) {
+        let known = self
+            .nodes
+            .values()
+            .any(|node| node.ip == ip && node.port == port);
+        if !known {
+            self.met.insert((ip.to_string(), port));
+        }
+    }
+
+    /// serve the slots, none of which may be served already
+    pub fn add_slots(&mut self, slots: &[u16]) -> Result<(), String> {
+        let mut added = BTreeSet::new();
+        for slot in slots {
+            if self.owner(*slot).is_some() {
+                return Err(format!("ERR Slot {} is already busy", slot));
+            }
+            if !added.insert(*slot) {
+                return Err(format!("ERR Slot {} specified multiple times", slot));
+            }
+        }
+
+        self.slots.extend(added);
+        Ok(())
+    }
+
+    /// stop serving the slots, each of which the node has to serve
+    pub fn del_slots(&mut self, slots: &[u16]) -> Result<(), String> {
+        let mut deleted = BTreeSet::new();
+        for slot in slots {
+            if !self.slots.contains(slot) {
+                return Err(format!("ERR Slot {} is already unassigned", slot));
+            }
+            if !deleted.insert(*slot) {
+                return Err(format!("ERR Slot {} specified multiple times", slot));
+            }
+        }
+
+        self.slots.retain(|slot| !deleted.contains(slot));
+        Ok(())
+    }
+
+    /// the slots the node serves, every one of them out of cluster mode
+    fn served(&self) -> Vec<(u16, u16)> {
+        match self.enabled {
+            true => ranges(&self.slots),
+            false => vec![(0, SLOTS - 1)],
+        }
+    }
+
+    /// the id of the node serving the slot, None if none does
+    pub fn owner(&self, slot: u16) -> Option<&str> {
+        if !self.enabled || self.slots.contains(&slot) {
+            return Some(&self.myid);
+        }
+        self.nodes
+            .iter()
+            .find(|(_, node)| node.slots.contains(&slot))
+            .map(|(id, _)| id.as_str())
+    }
+
+    /// the error a command working on the keys is replied in cluster mode,
+    /// the keys having to hash to a slot the node serves, or to one its
+    /// master serves when read from a READONLY connection, None if it may run
+    pub fn redirect(&self, keys: &[&[u8]], master: Option<&(String, u16)>) -> Option<String> {
+        if !self.enabled {
+            return None;
+        }
+        let slot = key_slot(keys.first()?);
+        if keys.iter().any(|key| key_slot(key) != slot) {
+            return Some(CROSSSLOT_ERROR.to_string());
+        }
+
+        match self.owner(slot) {
+            Some(id) if id == self.myid => None,
+            Some(id) => {
+                let node = &self.nodes[id];
+                if matches!(master, Some((ip, port)) if *ip == node.ip && *port == node.port) {
+                    return None;
+                }
+                Some(format!("MOVED {} {}:{}", slot, node.ip, node.port))
+            }
+            None => Some("CLUSTERDOWN Hash slot not served".to_string()),
+        }
+    }
+
+    /// whether every slot is served by a node that replies
+    fn is_ok(&self) -> bool {
+        let mut served = self.slots.len();
+        for node in self.nodes.values().filter(|node| node.connected) {
+            served += node.slots.len();
+        }
+        !self.enabled || served >= SLOTS as usize
+    }
+
+    /// what CLUSTER INFO replies
+    pub fn info(&self) -> String {
+        let state = match self.is_ok() {
+            true => "ok",
+            false => "fail",
+        };
+        let (assigned, ok, failing) = match self.enabled {
+            true => {
+                let assigned = self.slots.len()
+                    + self
+                        .nodes
+                        .values()
+                        .map(|node| node.slots.len())
+                        .sum::<usize>();
+                let failing = self
+                    .nodes
+                    .values()
+                    .filter(|node| !node.connected)
+                    .map(|node| node.slots.len())
+                    .sum::<usize>();
+                (assigned, assigned - failing, failing)
+            }
+            false => (SLOTS as usize, SLOTS as usize, 0),
+        };
+        let size = (!self.served().is_empty()) as usize
+            + self
+                .nodes
+                .values()
+                .filter(|node| !node.slots.is_empty())
+                .count();
+        let fields = [
+            ("cluster_enabled", (self.enabled as u8).to_string()),
+            ("cluster_state", state.to_string()),
+            ("cluster_slots_assigned", assigned.to_string()),
+            ("cluster_slots_ok", ok.to_string()),
+            ("cluster_slots_pfail", "0".to_string()),
+            ("cluster_slots_fail", failing.to_string()),
+            ("cluster_known_nodes", (self.nodes.len() + 1).to_string()),
+            ("cluster_size", size.to_string()),
+            ("cluster_current_epoch", "0".to_string()),
+            ("cluster_my_epoch", "0".to_string()),
+        ];
+        fields
+            .iter()
+            .map(|(field, value)| format!("{}:{}\r\n", field, value))
+            .collect()
+    }
+
+    /// what CLUSTER NODES replies, a line for each node, the node being at
+    /// the ip and the port the client connected to
+    pub fn nodes(&self, ip: &str, port: u16) -> String {
+        let mut lines = vec![node_line(
+            &self.myid,
+            ip,
+            port,
+            "myself,master",
+            0,
+            true,
+            &self.served(),
+        )];
+        for (id, node) in &self.nodes {
+            lines.push(node_line(
+                id,
+                &node.ip,
+                node.port,
+                "master",
+                node.pong,
+                node.connected,
+                &ranges(&node.slots),
+            ));
+        }
+
+        lines.concat()
+    }
+
+    /// what CLUSTER SLOTS replies, a range of slots along with the node
+    /// serving them, the node being at the ip and the port the client
+    /// connected to
+    pub fn slots(&self, ip: &str, port: u16) -> RedisResponse {
+        let mut served = self
+            .served()
+            .into_iter()
+            .map(|range| (range, ip, port, &self.myid))
+            .collect::<Vec<_>>();
+        for (id, node) in &self.nodes {
+            for range in ranges(&node.slots) {
+                served.push((range, &node.ip, node.port, id));
+            }
+        }
+        served.sort_by_key(|((start, _), ..)| *start);
+
+        RedisResponse::Array(
+            served
+                .into_iter()
+                .map(|((start, end), ip, port, id)| {
+                    RedisResponse::Array(vec![
+                        RedisResponse::Integer(start as i64),
+                        RedisResponse::Integer(end as i64),
+                        RedisResponse::Array(vec![
+                            RedisResponse::BulkString(ip.as_bytes().to_vec()),
+                            RedisResponse::Integer(port as i64),
+                            RedisResponse::BulkString(id.as_bytes().to_vec()),
+                        ]),
+                    ])
+                })
+                .collect(),
+        )
+    }
+
+    /// what CLUSTER SHARDS replies, a shard for each node along with the
+    /// slots it serves, the replication offset being the one of this node
+    pub fn shards(&self, ip: &str, port: u16, offset: u64) -> RedisResponse {
+        let mut shards = vec![shard(&self.myid, ip, port, &self.served(), offset, true)];
+        for (id, node) in &self.nodes {
+            shards.push(shard(
+                id,
+                &node.ip,
+                node.port,
+                &ranges(&node.slots),
+                0,
+                node.connected,
+            ));
+        }
+
+        RedisResponse::Array(shards)
+    }
+
+    /// the addresses of the nodes to ask about the cluster
+    fn addresses(&self) -> Vec<(String, u16)> {
+        let known = self.nodes.values().map(|node| (node.ip.clone(), node.port));
+        known.chain(self.met.iter().cloned()).collect()
+    }
+
+    /// what a node replied about itself and the nodes it knows, the node
+    /// being the one at the address asked
+    fn learn(&mut self, ip: &str, port: u16, nodes: Vec<Gossip>) {
+        self.met.remove(&(ip.to_string(), port));
+        for gossip in nodes {
+            if gossip.id == self.myid {
+                continue;
+            }
+            if gossip.myself {
+                self.nodes.insert(
+                    gossip.id,
+                    Node {
+                        ip: ip.to_string(),
+                        port,
+                        slots: gossip.slots,
+                        pong: now_millis(),
+                        connected: true,
+                    },
+                );
+            } else if !self.nodes.contains_key(&gossip.id) {
+                self.meet(&gossip.ip, gossip.port);
+            }
+        }
+    }
+
+    /// the node at the address didn't reply
+    fn lost(&mut self, ip: &str, port: u16) {
+        for node in self.nodes.values_mut() {
+            if node.ip == ip && node.port == port {
+                node.connected = false;
+            }
+        }
+    }
+}
+
+impl Default for Cluster {
+    fn default() -> Self {
+        Cluster::new()
+    }
+}
+
+/// a node as another one tells about it
+struct Gossip {
+    id: String,
+    ip: String,
+    port: u16,
+    // whether the node telling about it is the node itself
+    myself: bool,
+    slots: BTreeSet<u16>,
+}
+
+/// start gossiping with the other nodes once the server runs in cluster
+/// mode, each of them being asked about the cluster over and over on a
+/// thread of its own, called over and over while the server runs
+pub fn cron(shared: &Arc<Shared>) {
+    let mut cluster = lock_then_release(&shared.cluster);
+    if !cluster.enabled || cluster.gossiping {
+        return;
+    }
+    cluster.gossiping = true;
+    drop(cluster);
+
+    let shared = Arc::clone(shared);
+    thread::spawn(move || {
+        // the connections to the nodes, kept from one time to the next
+        let mut links = HashMap::new();
+        loop {
+            let addresses = lock_then_release(&shared.cluster).addresses();
+            links.retain(|address, _| addresses.contains(address));
+            for address in addresses {
+                let (ip, port) = &address;
+                let link = match links.remove(&address) {
+                    Some(link) => Ok(link),
+                    None => connect(&shared, ip, *port),
+                };
+                match link.and_then(|link| gossip(&shared, &link).map(|nodes| (link, nodes))) {
+                    Ok((link, nodes)) => {
+                        lock_then_release(&shared.cluster).learn(ip, *port, nodes);
+                        links.insert(address, link);
+                    }
+                    Err(_) => lock_then_release(&shared.cluster).lost(ip, *port),
+                }
+            }
+            thread::sleep(GOSSIP_INTERVAL);
+        }
+    });
+}
+
+/// connect to the node at the address, authenticated as replicas are
+fn connect(shared: &Shared, ip: &str, port: u16) -> io::Result<TcpStream> {
+    let address = (ip, port)
+        .to_socket_addrs()?
+        .next()
+        .ok_or_else(|| io::Error::from(ErrorKind::AddrNotAvailable))?;
+    let stream = TcpStream::connect_timeout(&address, GOSSIP_TIMEOUT)?;
+    stream.set_read_timeout(Some(GOSSIP_TIMEOUT))?;
+
+    let masterauth = lock_then_release(&shared.config)
+        .value("masterauth")
+        .to_vec();
+    if !masterauth.is_empty() {
+        (&stream).write_all(&request(&[b"AUTH", &masterauth]))?;
+        expect_ok(&mut BufReader::new(&stream))?;
+    }
+    Ok(stream)
+}
+
+/// tell the node about this one, then ask it about the nodes it knows, as
+/// CLUSTER NODES replies
+fn gossip(shared: &Shared, stream: &TcpStream) -> io::Result<Vec<Gossip>> {
+    let myip = stream.local_addr()?.ip().to_string();
+    let myport = lock_then_release(&shared.config)
+        .integer("port")
+        .to_string();
+    let mut writer = stream;
+    let mut reader = BufReader::new(stream);
+    writer.write_all(&request(&[
+        b"CLUSTER",
+        b"MEET",
+        myip.as_bytes(),
+        myport.as_bytes(),
+    ]))?;
+    expect_ok(&mut reader)?;
+    writer.write_all(&request(&[b"CLUSTER", b"NODES"]))?;
+    let length = match read_line(&mut reader)?.strip_prefix('$') {
+        Some(length) => length.parse::<u64>().map_err(|_| invalid(length))?,
+        None => return Err(invalid("no nodes")),
+    };
+    let mut nodes = String::new();
+    (&mut reader).take(length).read_to_string(&mut nodes)?;
+    // the line ending after them
+    read_line(&mut reader)?;
+
+    nodes.lines().map(parse_node_line).collect()
+}
+
+/// a line of CLUSTER NODES, the slots being migrated left out
+fn parse_node_line(line: &str) -> io::Result<Gossip> {
+    let fields = line.split(' ').collect::<Vec<_>>();
+    if fields.len() < 8 {
+        return Err(invalid(line));
+    }
+    // ip:port@cport, followed by the hostname if any
+    let address = fields[1].split(&['@', ','][..]).next();
+    let address = address
+        .and_then(|address| address.parse::<SocketAddr>().ok())
+        .ok_or_else(|| invalid(line))?;
+
+    let mut slots = BTreeSet::new();
+    for range in fields[8..].iter().filter(|range| !range.starts_with('[')) {
+        let (start, end) = range.split_once('-').unwrap_or((range, range));
+        match (start.parse::<u16>(), end.parse::<u16>()) {
+            (Ok(start), Ok(end)) if start <= end && end < SLOTS => slots.extend(start..=end),
+            _ => return Err(invalid(line)),
+        }
+    }
+
+    Ok(Gossip {
+        id: fields[0].to_string(),
+        ip: address.ip().to_string(),
+        port: address.port(),
+        myself: fields[2].split(',').any(|flag| flag == "myself"),
+        slots,
+    })
+}
+
+/// a line of CLUSTER NODES
+fn node_line(
+    id: &str,
+    ip: &str,
+    port: u16,
+    flags: &str,
+    pong: u64,
+    connected: bool,
+    slots: &[(u16, u16)],
+) -> String {
+    let link = match connected {
+        true => "connected",
+        false => "disconnected",
+    };
+    let mut line = format!(
+        "{} {}:{}@{} {} - 0 {} 0 {}",
+        id,
+        ip,
+        port,
+        port as u32 + BUS_PORT_OFFSET,
+        flags,
+        pong,
+        link
+    );
+    for (start, end) in slots {
+        match start == end {
+            true => line.push_str(&format!(" {}", start)),
+            false => line.push_str(&format!(" {}-{}", start, end)),
+        }
+    }
+    line.push('\n');
+    line
+}
+
+/// a shard as CLUSTER SHARDS tells about it, a node alone
+fn shard(
+    id: &str,
+    ip: &str,
+    port: u16,
+    slots: &[(u16, u16)],
+    offset: u64,
+    online: bool,
+) -> RedisResponse {
+    let field = |name: &str| RedisResponse::BulkString(name.as_bytes().to_vec());
+    let health = match online {
+        true => "online",
+        false => "fail",
+    };
+    let node = RedisResponse::Map(vec![
+        (field("id"), field(id)),
+        (field("port"), RedisResponse::Integer(port as i64)),
+        (field("ip"), field(ip)),
+        (field("endpoint"), field(ip)),
+        (field("role"), field("master")),
+        (
+            field("replication-offset"),
+            RedisResponse::Integer(offset as i64),
+        ),
+        (field("health"), field(health)),
+    ]);
+    let slots = slots
+        .iter()
+        .flat_map(|(start, end)| vec![*start, *end])
+        .map(|slot| RedisResponse::Integer(slot as i64))
+        .collect();
+
+    RedisResponse::Map(vec![
+        (field("slots"), RedisResponse::Array(slots)),
+        (field("nodes"), RedisResponse::Array(vec![node])),
+    ])
+}
+
+/// the slot the key hashes to, only the part of the key between the first
+/// braces being hashed if they hold anything, so that keys sharing it are
+/// in the same slot
+pub fn key_slot(key: &[u8]) -> u16 {
+    let tag = key.iter().position(|byte| *byte == b'{').and_then(|start| {
+        let length = key[start + 1..].iter().position(|byte| *byte == b'}')?;
+        match length {
+            0 => None,
+            length => Some(&key[start + 1..start + 1 + length]),
+        }
+    });
+    crc16(tag.unwrap_or(key)) % SLOTS
+}
+
+/// the CRC16 of Redis Cluster, the XMODEM one
+fn crc16(bytes: &[u8]) -> u16 {
+    let mut crc = 0u16;
+    for byte in bytes {
+        crc ^= (*byte as u16) << 8;
+        for _ in 0..8 {
+            crc = match crc & 0x8000 {
+                0 => crc << 1,
+                _ => (crc << 1) ^ 0x1021,
+            };
+        }
+    }
+    crc
+}
+
+/// the slots as ranges of consecutive ones
+fn ranges(slots: &BTreeSet<u16>) -> Vec<(u16, u16)> {
+    let mut ranges: Vec<(u16, u16)> = vec![];
+    for slot in slots {
+        match ranges.last_mut() {
+            Some((_, end)) if *end + 1 == *slot => *end = *slot,
+            _ => ranges.push((*slot, *slot)),
+        }
+    }
+    ranges
+}
+
+fn expect_ok<R: BufRead>(reader: &mut R) -> io::Result<()> {
+    match read_line(reader)?.as_str() {
+        "+OK" => Ok(()),
+        line => Err(invalid(line)),
+    }
+}
+
+fn invalid(reason: &str) -> io::Error {
+    io::Error::new(
+        ErrorKind::InvalidData,
+        format!("unexpected reply from the node: {}", reason),
+    )
+}
+
+fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_millis() as u64)
+}
@@ -0,0 +1,387 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{self, ErrorKind};
+use std::path::Path;
+
+use super::notify::KeyspaceEvents;
+use crate::glob::glob_match;
+
+/// the values a parameter accepts
+enum Kind {
+    // inclusive bounds
+    Integer(i64, i64),
+    // bytes, given with or without a unit such as `100mb`
+    Memory,
+    Bool,
+    Enum(&'static [&'static str]),
+    // pairs of seconds and changes after which the dataset is saved
+    Save,
+    KeyspaceEvents,
+    String,
+}
+
+/// a parameter of the configuration, an immutable one being set only when
+/// the server starts
+struct Parameter {
+    name: &'static str,
+    kind: Kind,
+    default: &'static str,
+    immutable: bool,
+}
+
+const MAX_INT: i64 = i32::MAX as i64;
+const MAXMEMORY_POLICIES: &[&str] = &[
+    "volatile-lru",
+    "volatile-lfu",
+    "volatile-random",
+    "volatile-ttl",
+    "allkeys-lru",
+    "allkeys-lfu",
+    "allkeys-random",
+    "noeviction",
+];
+
+macro_rules! parameters {
+    ($($name:literal => $kind:expr, $default:literal $(, $immutable:ident)?;)*) => {
+        &[$(Parameter {
+            name: $name,
+            kind: $kind,
+            default: $default,
+            immutable: parameters!(@immutable $($immutable)?),
+        },)*]
+    };
+    (@immutable immutable) => { true };
+    (@immutable) => { false };
+}
+
+/// every parameter known, along with its default value
+const PARAMETERS: &[Parameter] = parameters![
+    "port" => Kind::Integer(0, 65535), "6379", immutable;
+    "bind" => Kind::String, "* -::*", immutable;
+    "databases" => Kind::Integer(1, MAX_INT), "16", immutable;
+    "daemonize" => Kind::Bool, "no", immutable;
+    "logfile" => Kind::String, "", immutable;
+    "loglevel" => Kind::Enum(&["debug", "verbose", "notice", "warning", "nothing"]), "notice";
+    "protected-mode" => Kind::Bool, "yes";
+    "timeout" => Kind::Integer(0, MAX_INT), "0";
+    "tcp-keepalive" => Kind::Integer(0, MAX_INT), "300";
+    "maxclients" => Kind::Integer(1, MAX_INT), "10000";
+    "hz" => Kind::Integer(1, 500), "10";
+    "active-expire-effort" => Kind::Integer(1, 10), "1";
+    "maxmemory" => Kind::Memory, "0";
+    "maxmemory-policy" => Kind::Enum(MAXMEMORY_POLICIES), "noeviction";
+    "maxmemory-samples" => Kind::Integer(1, 64), "5";
+    "save" => Kind::Save, "3600 1 300 100 60 10000";
+    "dbfilename" => Kind::String, "dump.rdb";
+    "dir" => Kind::String, ".";
+    "appendonly" => Kind::Bool, "no";
+    "appendfilename" => Kind::String, "appendonly.aof", immutable;
+    "appendfsync" => Kind::Enum(&["always", "everysec", "no"]), "everysec";
+    "notify-keyspace-events" => Kind::KeyspaceEvents, "";
+    "slowlog-log-slower-than" => Kind::Integer(-1, i64::MAX), "10000";
+    "slowlog-max-len" => Kind::Integer(0, i64::MAX), "128";
+    "latency-monitor-threshold" => Kind::Integer(0, i64::MAX), "0";
+    "busy-reply-threshold" => Kind::Integer(0, i64::MAX), "5000";
+    "tracking-table-max-keys" => Kind::Integer(0, i64::MAX), "1000000";
+    "proto-max-bulk-len" => Kind::Memory, "536870912";
+    "hash-max-listpack-entries" => Kind::Integer(0, i64::MAX), "128";
+    "hash-max-listpack-value" => Kind::Integer(0, i64::MAX), "64";
+    "set-max-intset-entries" => Kind::Integer(0, i64::MAX), "512";
+    "set-max-listpack-entries" => Kind::Integer(0, i64::MAX), "128";
+    "set-max-listpack-value" => Kind::Integer(0, i64::MAX), "64";
+    "zset-max-listpack-entries" => Kind::Integer(0, i64::MAX), "128";
+    "zset-max-listpack-value" => Kind::Integer(0, i64::MAX), "64";
+    "stream-node-max-bytes" => Kind::Memory, "4096";
+    "stream-node-max-entries" => Kind::Integer(0, i64::MAX), "100";
+    "activerehashing" => Kind::Bool, "yes";
+    "lazyfree-lazy-eviction" => Kind::Bool, "no";
+    "replica-read-only" => Kind::Bool, "yes";
+    "masterauth" => Kind::String, "";
+    "repl-backlog-size" => Kind::Memory, "1048576";
+    "cluster-enabled" => Kind::Bool, "no", immutable;
+    "sentinel-port" => Kind::Integer(0, 65535), "0", immutable;
+    "sentinel-master-name" => Kind::String, "mymaster", immutable;
+];
+
+/// the values of the parameters, read by CONFIG GET and changed by CONFIG
+/// SET, shared by every connection of a server
+pub struct Config {
+    values: BTreeMap<&'static str, Vec<u8>>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config::new()
+    }
+}
+
+impl Config {
+    pub fn new() -> Self {
+        Config {
+            values: PARAMETERS
+                .iter()
+                .map(|parameter| (parameter.name, parameter.default.as_bytes().to_vec()))
+                .collect(),
+        }
+    }
+
+    /// the parameters matching any of the glob patterns, with their values
+    pub fn get(&self, patterns: &[Vec<u8>]) -> Vec<(Vec<u8>, Vec<u8>)> {
+        let patterns = patterns
+            .iter()
+            .map(|pattern| pattern.to_ascii_lowercase())
+            .collect::<Vec<_>>();
+
+        self.values
+            .iter()
+            .filter(|(name, _)| {
+                patterns
+                    .iter()
+                    .any(|pattern| glob_match(pattern, name.as_bytes()))
+            })
+            .map(|(name, value)| (name.as_bytes().to_vec(), value.clone()))
+            .collect()
+    }
+
+    /// the value of a parameter known to exist
+    pub fn value(&self, name: &str) -> &[u8] {
+        &self.values[name]
+    }
+
+    /// the value of an integer parameter known to exist
+    pub fn integer(&self, name: &str) -> i64 {
+        String::from_utf8_lossy(self.value(name))
+            .parse()
+            .unwrap_or_default()
+    }
+
+    /// set the parameters as CONFIG SET does, none of them being set unless
+    /// all of them are valid and mutable
+    pub fn set(&mut self, items: &[(Vec<u8>, Vec<u8>)]) -> Result<(), String> {
+        let mut values = Vec::with_capacity(items.len());
+        for (name, value) in items {
+            let parameter = find(name).ok_or_else(|| {
+                format!(
+                    "ERR Unknown option or number of arguments for CONFIG SET - '{}'",
+                    String::from_utf8_lossy(name)
+                )
+            })?;
+            let failed = |reason: &str| {
+                format!(
+                    "ERR CONFIG SET failed (possibly related to argument '{}') - {}",
+                    parameter.name, reason
+                )
+            };
+
+            if parameter.immutable {
+                return Err(failed("can't set immutable config"));
+            }
+            if values.iter().any(|(name, _)| *name == parameter.name) {
+                return Err(failed("duplicate parameter"));
+            }
+            let value = parameter
+                .kind
+                .parse(value)
+                .map_err(|reason| failed(&reason))?;
+            values.push((parameter.name, value));
+        }
+
+        self.values.extend(values);
+        Ok(())
+    }
+
+    /// set a parameter as the server starts, immutable ones included
+    pub fn init(&mut self, name: &[u8], value: &[u8]) -> Result<(), String> {
+        let parameter = find(name).ok_or_else(|| {
+            format!(
+                "Bad directive or wrong number of arguments - '{}'",
+                String::from_utf8_lossy(name)
+            )
+        })?;
+        let value = parameter
+            .kind
+            .parse(value)
+            .map_err(|reason| format!("argument '{}' is invalid - {}", parameter.name, reason))?;
+
+        self.values.insert(parameter.name, value);
+        Ok(())
+    }
+
+    /// whether the parameter is one of the configuration
+    pub fn knows(name: &[u8]) -> bool {
+        find(name).is_some()
+    }
+}
+
+/// a line of a redis.conf file, its arguments unquoted
+pub struct Directive {
+    pub line: usize,
+    pub name: Vec<u8>,
+    pub args: Vec<Vec<u8>>,
+}
+
+/// the directives of a redis.conf file, the ones of the files it includes
+/// taking their place
+pub fn read_file(path: &Path) -> io::Result<Vec<Directive>> {
+    let contents = fs::read_to_string(path)?;
+    let invalid = |line: usize, reason: &str| {
+        io::Error::new(
+            ErrorKind::InvalidData,
+            format!("{}:{}: {}", path.display(), line, reason),
+        )
+    };
+
+    let mut directives = vec![];
+    for (index, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let mut args = split_args(line).ok_or_else(|| invalid(index + 1, "Unbalanced quotes"))?;
+        let name = args.remove(0).to_ascii_lowercase();
+        match (name.as_slice(), args.as_slice()) {
+            (b"include", [included]) => {
+                let included = String::from_utf8_lossy(included).to_string();
+                directives.extend(read_file(Path::new(&included))?);
+            }
+            (b"include", _) => return Err(invalid(index + 1, "wrong number of arguments")),
+            _ => directives.push(Directive {
+                line: index + 1,
+                name,
+                args,
+            }),
+        }
+    }
+
+    Ok(directives)
+}
+
+/// the arguments of a line, split on spaces unless quoted, escape sequences
+/// being allowed between double quotes, None if a quote is not closed
+fn split_args(line: &str) -> Option<Vec<Vec<u8>>> {
+    let mut args = vec![];
+    let mut bytes = line.bytes().peekable();
+
+    loop {
+        while matches!(bytes.peek(), Some(byte) if byte.is_ascii_whitespace()) {
+            bytes.next();
+        }
+        let quote = match bytes.peek() {
+            None => return Some(args),
+            Some(b'"') | Some(b'\'') => bytes.next(),
+            Some(_) => None,
+        };
+
+        let mut arg = vec![];
+        loop {
+            match (quote, bytes.next()) {
+                (None, None) => break,
+                (None, Some(byte)) if byte.is_ascii_whitespace() => break,
+                (Some(_), None) => return None,
+                (Some(b'"'), Some(b'\\')) => arg.push(match bytes.next()? {
+                    b'n' => b'\n',
+                    b'r' => b'\r',
+                    b't' => b'\t',
+                    b'b' => 8,
+                    b'a' => 7,
+                    byte => byte,
+                }),
+                (Some(b'\''), Some(b'\\')) if bytes.peek() == Some(&b'\'') => {
+                    arg.push(b'\'');
+                    bytes.next();
+                }
+                // a closing quote is followed by a space or ends the line
+                (Some(quote), Some(byte)) if byte == quote => match bytes.peek() {
+                    Some(next) if !next.is_ascii_whitespace() => return None,
+                    _ => break,
+                },
+                (_, Some(byte)) => arg.push(byte),
+            }
+        }
+        args.push(arg);
+    }
+}
+
+fn find(name: &[u8]) -> Option<&'static Parameter> {
+    let name = name.to_ascii_lowercase();
+    PARAMETERS
+        .iter()
+        .find(|parameter| parameter.name.as_bytes() == name.as_slice())
+}
+
+impl Kind {
+    /// the value as CONFIG GET gives it back, or why it is invalid
+    fn parse(&self, value: &[u8]) -> Result<Vec<u8>, String> {
+        let text = std::str::from_utf8(value).map_err(|_| "argument must be a string")?;
+
+        match self {
+            Kind::Integer(min, max) => {
+                let integer = text
+                    .parse::<i64>()
+                    .map_err(|_| "argument couldn't be parsed into an integer")?;
+                match (*min..=*max).contains(&integer) {
+                    true => Ok(integer.to_string().into_bytes()),
+                    false => Err(format!(
+                        "argument must be between {} and {} inclusive",
+                        min, max
+                    )),
+                }
+            }
+            Kind::Memory => match parse_memory(text) {
+                Some(bytes) => Ok(bytes.to_string().into_bytes()),
+                None => Err("argument must be a memory value".to_string()),
+            },
+            Kind::Bool => match text.to_ascii_lowercase().as_str() {
+                "yes" => Ok(b"yes".to_vec()),
+                "no" => Ok(b"no".to_vec()),
+                _ => Err("argument must be 'yes' or 'no'".to_string()),
+            },
+            Kind::Enum(values) => {
+                let text = text.to_ascii_lowercase();
+                match values.contains(&text.as_str()) {
+                    true => Ok(text.into_bytes()),
+                    false => Err(format!(
+                        "argument(s) must be one of the following: {}",
+                        values.join(", ")
+                    )),
+                }
+            }
+            Kind::Save => {
+                let fields = text.split_whitespace().collect::<Vec<_>>();
+                let valid = fields.len() % 2 == 0
+                    && fields.iter().all(|field| field.parse::<u64>().is_ok());
+                match valid {
+                    true => Ok(fields.join(" ").into_bytes()),
+                    false => Err("Invalid save parameters".to_string()),
+                }
+            }
+            Kind::KeyspaceEvents => match KeyspaceEvents::parse(value) {
+                Some(keyspace_events) => Ok(keyspace_events.to_flags()),
+                None => Err("Invalid event class character. Use 'Ag$lshzxeKEtmdn'.".to_string()),
+            },
+            Kind::String => Ok(value.to_vec()),
+        }
+    }
+}
+
+/// bytes given as a number followed by an optional unit, `k` being 1000
+/// bytes and `kb` 1024 bytes
+fn parse_memory(text: &str) -> Option<u64> {
+    let text = text.to_ascii_lowercase();
+    let digits = text
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(text.len());
+    let unit = match &text[digits..] {
+        "" | "b" => 1,
+        "k" => 1000,
+        "kb" => 1024,
+        "m" => 1000 * 1000,
+        "mb" => 1024 * 1024,
+        "g" => 1000 * 1000 * 1000,
+        "gb" => 1024 * 1024 * 1024,
+        _ => return None,
+    };
+
+    text[..digits].parse::<u64>().ok()?.checked_mul(unit)
+}
@@ -0,0 +1,108 @@
+use std::collections::{BTreeSet, HashMap};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crossbeam_channel::{unbounded, Receiver, Sender};
+
+use crate::command::Command;
+use crate::server::CommandResponse;
+
+static NEXT_CONNECTION_ID: AtomicU64 = AtomicU64::new(1);
+
+/// commands queued after MULTI until EXEC or DISCARD
+#[derive(Default)]
+pub struct Transaction {
+    // along with their name, as the command stats know it
+    pub commands: Vec<(String, Command)>,
+    // a command failed to be parsed, EXEC discarding the transaction
+    pub aborted: bool,
+}
+
+/// state of a client connection kept from one command to the next
+pub struct Connection {
+    pub id: u64,
+    // the user AUTH succeeded as, the default user being used until then if
+    // it needs no password
+    pub user: Option<String>,
+    // database the commands run against, switched by SELECT
+    pub db: usize,
+    // version of RESP the connection speaks
+    pub protocol: u8,
+    // channels and glob patterns the connection is subscribed to
+    pub channels: BTreeSet<Vec<u8>>,
+    pub patterns: BTreeSet<Vec<u8>>,
+    pub shard_channels: BTreeSet<Vec<u8>>,
+    // commands queued since MULTI
+    pub transaction: Option<Transaction>,
+    // keys watched by the next transaction, along with their database, and
+    // their version
+    pub watched: HashMap<(usize, Vec<u8>), u64>,
+    // closed once the response is written, e.g. killed by CLIENT KILL
+    pub closing: bool,
+    // told about every command run since it issued MONITOR
+    pub monitoring: bool,
+    // reads the keys of a replica rather than being redirected to its
+    // master, as cluster clients ask with READONLY
+    pub readonly: bool,
+    // the port a replica connected as listens on, as it told with REPLCONF
+    pub listening_port: Option<u16>,
+    // connected to the sentinel listener, running the commands of a
+    // sentinel alone
+    pub sentinel: bool,
+    // messages pushed to the connection, written to it between two commands
+    sender: Sender<CommandResponse>,
+    receiver: Receiver<CommandResponse>,
+}
+
+impl Connection {
+    pub fn new() -> Self {
+        let (sender, receiver) = unbounded();
+
+        Connection {
+            id: NEXT_CONNECTION_ID.fetch_add(1, Ordering::Relaxed),
+            user: None,
+            db: 0,
+            protocol: 2,
+            channels: BTreeSet::new(),
+            patterns: BTreeSet::new(),
+            shard_channels: BTreeSet::new(),
+            transaction: None,
+            watched: HashMap::new(),
+            closing: false,
+            monitoring: false,
+            readonly: false,
+            listening_port: None,
+            sentinel: false,
+            sender,
+            receiver,
+        }
+    }
+
+    /// where to push messages to the connection
+    pub fn sender(&self) -> Sender<CommandResponse> {
+        self.sender.clone()
+    }
+
+    /// the messages pushed since the last call
+    pub fn pushed(&self) -> impl Iterator<Item = CommandResponse> + '_ {
+        self.receiver.try_iter()
+    }
+
+    pub fn subscriptions(&self) -> usize {
+        self.channels.len() + self.patterns.len()
+    }
+
+    pub fn shard_subscriptions(&self) -> usize {
+        self.shard_channels.len()
+    }
+
+    /// a subscribed connection only accepts the pub/sub commands
+    pub fn is_subscribed(&self) -> bool {
+        self.subscriptions() + self.shard_subscriptions() > 0
+    }
+}
+
+impl Default for Connection {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,42 @@
+use std::sync::{Arc, Mutex};
+
+use crossbeam_channel::{unbounded, Receiver, Sender};
+
+use super::lock_then_release;
+
+/// a key removed without a command asking for it, told to the application
+/// embedding the server rather than to the clients
+#[derive(Debug, Clone, PartialEq)]
+pub enum KeyEvent {
+    // the database and the key
+    Expired(usize, Vec<u8>),
+    Evicted(usize, Vec<u8>),
+}
+
+/// the channels the key events are sent to, shared by the server and the
+/// connections
+#[derive(Default, Clone)]
+pub struct Events {
+    senders: Arc<Mutex<Vec<Sender<KeyEvent>>>>,
+}
+
+impl Events {
+    pub fn new() -> Self {
+        Events {
+            senders: Arc::new(Mutex::new(vec![])),
+        }
+    }
+
+    /// a channel receiving the events sent from now on
+    pub fn subscribe(&self) -> Receiver<KeyEvent> {
+        let (sender, receiver) = unbounded();
+        lock_then_release(&self.senders).push(sender);
+        receiver
+    }
+
+    /// send the event to every channel, the ones whose receiver was dropped
+    /// being forgotten
+    pub fn send(&self, event: KeyEvent) {
+        lock_then_release(&self.senders).retain(|sender| sender.send(event.clone()).is_ok());
+    }
+}
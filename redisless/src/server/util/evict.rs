@@ -0,0 +1,95 @@
+use super::config::Config;
+use super::events::KeyEvent;
+use super::expire;
+use super::lock_then_release;
+use super::notify;
+use super::shards::Shards;
+use super::shared::Shared;
+use crate::storage::{EvictionPolicy, Storage};
+
+/// what the commands that may take more memory are refused with, once the
+/// keys take more than maxmemory with no key left to evict
+pub const OOM_ERROR: &str = "OOM command not allowed when used memory > 'maxmemory'.";
+
+/// how much memory the keys may take and the keys evicted once they take
+/// more, as the configuration tells
+#[derive(Clone, Copy)]
+pub struct MaxMemory {
+    pub bytes: usize,
+    pub policy: Option<EvictionPolicy>,
+    pub samples: usize,
+}
+
+impl MaxMemory {
+    pub fn new(config: &Config) -> Self {
+        MaxMemory {
+            bytes: config.integer("maxmemory") as usize,
+            policy: EvictionPolicy::parse(config.value("maxmemory-policy")),
+            samples: config.integer("maxmemory-samples") as usize,
+        }
+    }
+}
+
+/// evict keys until they take no more than `maxmemory`, as Redis does
+/// before running a command that may take more, the key evicted each time
+/// being the one `maxmemory-policy` evicts first among `maxmemory-samples`
+/// keys of each database, returns false if they still take more, so that
+/// the command is refused
+pub fn make_room<T: Storage>(databases: &Shards<T>, shared: &Shared, maxmemory: MaxMemory) -> bool {
+    // a replica waits for its master to tell it the keys evicted
+    if maxmemory.bytes == 0 || lock_then_release(&shared.replication).master.is_some() {
+        return true;
+    }
+
+    // each shard is locked in turn, the commands on the others going on
+    loop {
+        let used = databases
+            .each()
+            .map(|shard| {
+                let mut databases = lock_then_release(&shard);
+                databases
+                    .iter_mut()
+                    .map(|storage| storage.used_memory())
+                    .sum::<usize>()
+            })
+            .sum::<usize>();
+        if used <= maxmemory.bytes {
+            return true;
+        }
+
+        let policy = match maxmemory.policy {
+            Some(policy) => policy,
+            None => return false,
+        };
+        let candidate = databases
+            .each()
+            .filter_map(|shard| {
+                let candidate = lock_then_release(&shard)
+                    .iter_mut()
+                    .enumerate()
+                    .filter_map(|(db, storage)| {
+                        let (key, score) = storage.eviction_candidate(policy, maxmemory.samples)?;
+                        Some((db, key, score))
+                    })
+                    .max_by_key(|(_, _, score)| *score)?;
+                Some((shard, candidate))
+            })
+            .max_by_key(|(_, (_, _, score))| *score);
+        let (shard, (db, key, _)) = match candidate {
+            Some(candidate) => candidate,
+            None => return false,
+        };
+
+        let mut databases = lock_then_release(&shard);
+        databases[db].remove(&key);
+        lock_then_release(&shared.stats).evicted_keys += 1;
+        expire::tell_removed(
+            databases,
+            shared,
+            vec![(db, key)],
+            notify::EVICTED,
+            "evicted",
+            KeyEvent::Evicted,
+        );
+    }
+}
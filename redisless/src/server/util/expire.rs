@@ -0,0 +1,139 @@
+use std::sync::atomic::Ordering;
+use std::time::{Duration, Instant};
+
+use super::events::KeyEvent;
+use super::lock_then_release;
+use super::notify;
+use super::shards::Shards;
+use super::shared::Shared;
+use crate::storage::snapshot::Change;
+use crate::storage::Storage;
+
+/// when the keys were last sampled for the ones that expired, so that they
+/// are `hz` times a second
+pub struct Expire {
+    last_cycle: Instant,
+}
+
+impl Expire {
+    pub fn new() -> Self {
+        Expire {
+            last_cycle: Instant::now(),
+        }
+    }
+}
+
+impl Default for Expire {
+    fn default() -> Self {
+        Expire::new()
+    }
+}
+
+/// remove the keys that expired without waiting for them to be read, as
+/// Redis does in its cron, each database being sampled over and over while
+/// enough of the keys sampled expired, the more so that
+/// `active-expire-effort` is high, called over and over while the server
+/// runs
+pub fn cron<T: Storage>(databases: &Shards<T>, shared: &Shared) {
+    // a replica waits for its master to tell it the keys that expired
+    if !shared.active_expire.load(Ordering::Relaxed)
+        || lock_then_release(&shared.replication).master.is_some()
+    {
+        return;
+    }
+    let (hz, effort) = {
+        let config = lock_then_release(&shared.config);
+        (
+            config.integer("hz") as u64,
+            config.integer("active-expire-effort") as usize - 1,
+        )
+    };
+    let period = Duration::from_millis(1000 / hz);
+    let mut expire = lock_then_release(&shared.expire);
+    if expire.last_cycle.elapsed() < period {
+        return;
+    }
+    expire.last_cycle = Instant::now();
+    drop(expire);
+
+    // the keys looked at each time, the share of them that has to have
+    // expired for a database to be sampled again, in percent, and the share
+    // of the period the cycle may take
+    let keys_per_sample = 20 + 20 / 4 * effort;
+    let acceptable_stale = 10 - effort;
+    let time_limit = period * (25 + 2 * effort as u32) / 100;
+
+    // each shard is locked in turn, the commands on the others going on,
+    // and a shard held by a command, such as a script that runs for long,
+    // is sampled next time rather than keeping the listener waiting
+    let started = Instant::now();
+    for shard in databases.each() {
+        let mut databases = match shard.try_acquire() {
+            Some(databases) => databases,
+            None => continue,
+        };
+        let mut expired = vec![];
+        for (db, storage) in databases.iter_mut().enumerate() {
+            loop {
+                let (sampled, removed) = storage.sample_expired(keys_per_sample);
+                if sampled == 0
+                    || removed * 100 <= sampled * acceptable_stale
+                    || started.elapsed() > time_limit
+                {
+                    break;
+                }
+            }
+            expired.extend(storage.take_expired().into_iter().map(|key| (db, key)));
+        }
+        if expired.is_empty() {
+            continue;
+        }
+
+        lock_then_release(&shared.stats).expired_keys += expired.len() as u64;
+        tell_removed(
+            databases,
+            shared,
+            expired,
+            notify::EXPIRED,
+            "expired",
+            KeyEvent::Expired,
+        );
+    }
+}
+
+/// tell the keys removed while no command asked for it to the append only
+/// file and to the replicas, the guard of the databases being dropped after,
+/// then to the subscribers to the event, to the application embedding the
+/// server and to the connections tracking them
+pub fn tell_removed<D>(
+    databases: D,
+    shared: &Shared,
+    removed: Vec<(usize, Vec<u8>)>,
+    class: u16,
+    event: &str,
+    key_event: fn(usize, Vec<u8>) -> KeyEvent,
+) {
+    let mut aof = lock_then_release(&shared.aof);
+    let mut replication = lock_then_release(&shared.replication);
+    for (db, key) in &removed {
+        let change = Change::Remove(*db, key.clone());
+        if aof.is_logging() {
+            aof.append(&change);
+        }
+        if replication.backlog_active {
+            replication.feed(&change);
+        }
+    }
+    drop(replication);
+    drop(aof);
+    drop(databases);
+
+    lock_then_release(&shared.persistence).dirty += removed.len() as u64;
+    let pubsub = lock_then_release(&shared.pubsub);
+    for (db, key) in &removed {
+        pubsub.notify(*db, class, event, key);
+        shared.events.send(key_event(*db, key.clone()));
+    }
+    let keys = removed.into_iter().map(|(_, key)| key).collect::<Vec<_>>();
+    lock_then_release(&shared.tracking).invalidate(&keys, 0, &pubsub);
+}
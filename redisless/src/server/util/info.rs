@@ -0,0 +1,361 @@
+use std::fmt::Write;
+use std::net::SocketAddr;
+use std::process;
+use std::time::Instant;
+
+use super::lock_then_release;
+use super::shared::Shared;
+use super::REDIS_VERSION;
+use crate::storage::Storage;
+
+// the sections INFO replies when not asked for any
+const DEFAULT_SECTIONS: &[&str] = &[
+    "server",
+    "clients",
+    "memory",
+    "persistence",
+    "stats",
+    "replication",
+    "cluster",
+    "keyspace",
+];
+
+/// what INFO replies, the sections asked for in the order Redis gives them,
+/// the unknown ones being left out
+pub fn info<T: Storage>(databases: &mut [T], shared: &Shared, sections: &[Vec<u8>]) -> String {
+    let asked = sections
+        .iter()
+        .map(|section| String::from_utf8_lossy(section).to_ascii_lowercase())
+        .collect::<Vec<_>>();
+    let is_asked = |section: &str| match asked.is_empty() {
+        true => DEFAULT_SECTIONS.contains(&section),
+        false => asked.iter().any(|asked| match asked.as_str() {
+            "all" | "everything" => true,
+            "default" => DEFAULT_SECTIONS.contains(&section),
+            asked => asked == section,
+        }),
+    };
+
+    let mut replied = vec![];
+    if is_asked("server") {
+        replied.push(server(shared));
+    }
+    if is_asked("clients") {
+        replied.push(clients(shared));
+    }
+    if is_asked("memory") {
+        replied.push(memory(databases, shared));
+    }
+    if is_asked("persistence") {
+        replied.push(persistence(shared));
+    }
+    if is_asked("stats") {
+        replied.push(stats(shared));
+    }
+    if is_asked("replication") {
+        replied.push(replication(shared));
+    }
+    if is_asked("commandstats") {
+        replied.push(commandstats(shared));
+    }
+    if is_asked("latencystats") {
+        replied.push(latencystats(shared));
+    }
+    if is_asked("cluster") {
+        let enabled = lock_then_release(&shared.cluster).enabled;
+        replied.push(section(
+            "Cluster",
+            vec![("cluster_enabled", (enabled as u8).to_string())],
+        ));
+    }
+    if is_asked("keyspace") {
+        replied.push(keyspace(databases));
+    }
+
+    replied.join("\r\n")
+}
+
+/// a section of INFO, a line for each field
+fn section(name: &str, fields: Vec<(&str, String)>) -> String {
+    let mut section = format!("# {}\r\n", name);
+    for (field, value) in fields {
+        let _ = write!(section, "{}:{}\r\n", field, value);
+    }
+
+    section
+}
+
+fn server(shared: &Shared) -> String {
+    let port = lock_then_release(&shared.config).integer("port");
+    let uptime = lock_then_release(&shared.stats).uptime().as_secs();
+
+    section(
+        "Server",
+        vec![
+            ("redis_version", REDIS_VERSION.to_string()),
+            ("redis_mode", "standalone".to_string()),
+            ("process_id", process::id().to_string()),
+            ("tcp_port", port.to_string()),
+            ("uptime_in_seconds", uptime.to_string()),
+            ("uptime_in_days", (uptime / 86400).to_string()),
+        ],
+    )
+}
+
+fn clients(shared: &Shared) -> String {
+    let clients = lock_then_release(&shared.clients);
+    let connected = clients.iter().count();
+    let pubsub = clients
+        .iter()
+        .filter(|(_, client)| client.is_pubsub())
+        .count();
+
+    section(
+        "Clients",
+        vec![
+            ("connected_clients", connected.to_string()),
+            ("pubsub_clients", pubsub.to_string()),
+        ],
+    )
+}
+
+fn memory<T: Storage>(databases: &mut [T], shared: &Shared) -> String {
+    let (used, dataset) = databases
+        .iter_mut()
+        .map(|storage| storage.memory_stats())
+        .fold((0, 0), |(used, dataset), stats| {
+            (
+                used + stats.overhead + stats.dataset,
+                dataset + stats.dataset,
+            )
+        });
+    let (maxmemory, policy) = {
+        let config = lock_then_release(&shared.config);
+        (
+            config.integer("maxmemory") as usize,
+            String::from_utf8_lossy(config.value("maxmemory-policy")).to_string(),
+        )
+    };
+
+    section(
+        "Memory",
+        vec![
+            ("used_memory", used.to_string()),
+            ("used_memory_human", human(used)),
+            ("used_memory_dataset", dataset.to_string()),
+            ("maxmemory", maxmemory.to_string()),
+            ("maxmemory_human", human(maxmemory)),
+            ("maxmemory_policy", policy),
+        ],
+    )
+}
+
+fn persistence(shared: &Shared) -> String {
+    let persistence = lock_then_release(&shared.persistence);
+    let aof = lock_then_release(&shared.aof);
+    let status = |ok: bool| match ok {
+        true => "ok".to_string(),
+        false => "err".to_string(),
+    };
+
+    section(
+        "Persistence",
+        vec![
+            ("loading", "0".to_string()),
+            ("rdb_changes_since_last_save", persistence.dirty.to_string()),
+            (
+                "rdb_bgsave_in_progress",
+                (persistence.bgsave_in_progress as u8).to_string(),
+            ),
+            ("rdb_last_save_time", persistence.last_save.to_string()),
+            ("rdb_last_bgsave_status", status(persistence.last_bgsave_ok)),
+            ("aof_enabled", (aof.is_enabled() as u8).to_string()),
+            (
+                "aof_rewrite_in_progress",
+                (aof.is_rewriting() as u8).to_string(),
+            ),
+            ("aof_last_bgrewrite_status", status(aof.last_rewrite_ok)),
+            ("aof_last_write_status", status(aof.last_write_ok)),
+        ],
+    )
+}
+
+fn stats(shared: &Shared) -> String {
+    let stats = lock_then_release(&shared.stats);
+
+    section(
+        "Stats",
+        vec![
+            ("total_connections_received", stats.connections.to_string()),
+            ("total_commands_processed", stats.commands.to_string()),
+            ("expired_keys", stats.expired_keys.to_string()),
+            ("evicted_keys", stats.evicted_keys.to_string()),
+            ("keyspace_hits", stats.keyspace_hits.to_string()),
+            ("keyspace_misses", stats.keyspace_misses.to_string()),
+        ],
+    )
+}
+
+/// the role of the server, along with the replicas it feeds or the master
+/// it replicates, and how far each is in the changes
+fn replication(shared: &Shared) -> String {
+    let read_only = lock_then_release(&shared.config).value("replica-read-only") == b"yes";
+    let replication = lock_then_release(&shared.replication);
+    let seconds_since = |time: Option<Instant>| match time {
+        Some(time) => time.elapsed().as_secs().to_string(),
+        None => "-1".to_string(),
+    };
+
+    let mut role = vec![];
+    match &replication.master {
+        Some((host, port)) => {
+            role.push(("role", "slave".to_string()));
+            role.push(("master_host", host.clone()));
+            role.push(("master_port", port.to_string()));
+            let status = match replication.link_up {
+                true => "up",
+                false => "down",
+            };
+            role.push(("master_link_status", status.to_string()));
+            role.push((
+                "master_last_io_seconds_ago",
+                seconds_since(replication.last_io),
+            ));
+            role.push((
+                "master_sync_in_progress",
+                (replication.syncing as u8).to_string(),
+            ));
+            role.push(("slave_read_repl_offset", replication.offset.to_string()));
+            role.push(("slave_repl_offset", replication.offset.to_string()));
+            if !replication.link_up {
+                role.push((
+                    "master_link_down_since_seconds",
+                    seconds_since(replication.link_down_since),
+                ));
+            }
+            role.push(("slave_read_only", (read_only as u8).to_string()));
+        }
+        None => role.push(("role", "master".to_string())),
+    }
+    let replicas = replication.replicas();
+    role.push(("connected_slaves", replicas.len().to_string()));
+    let mut section = section("Replication", role);
+
+    let clients = lock_then_release(&shared.clients);
+    for (index, (id, replica)) in replicas.iter().enumerate() {
+        let ip = clients
+            .get(*id)
+            .and_then(|client| client.addr.parse::<SocketAddr>().ok())
+            .map_or(String::new(), |addr| addr.ip().to_string());
+        let _ = write!(
+            section,
+            "slave{}:ip={},port={},state=online,offset={},lag={}\r\n",
+            index,
+            ip,
+            replica.port,
+            replica.ack_offset,
+            replica.ack_time.elapsed().as_secs()
+        );
+    }
+    drop(clients);
+
+    let history = vec![
+        (
+            "master_failover_state",
+            replication.failover_state().to_string(),
+        ),
+        ("master_replid", replication.replid.clone()),
+        ("master_replid2", replication.replid2.clone()),
+        ("master_repl_offset", replication.offset.to_string()),
+        (
+            "second_repl_offset",
+            replication.second_replid_offset.to_string(),
+        ),
+        (
+            "repl_backlog_active",
+            (replication.backlog_active as u8).to_string(),
+        ),
+        ("repl_backlog_size", replication.backlog_size.to_string()),
+        (
+            "repl_backlog_first_byte_offset",
+            replication.backlog_first_byte_offset().to_string(),
+        ),
+        (
+            "repl_backlog_histlen",
+            replication.backlog_histlen().to_string(),
+        ),
+    ];
+    for (field, value) in history {
+        let _ = write!(section, "{}:{}\r\n", field, value);
+    }
+
+    section
+}
+
+/// a line for each command called so far
+fn commandstats(shared: &Shared) -> String {
+    let stats = lock_then_release(&shared.stats);
+    let mut section = "# Commandstats\r\n".to_string();
+    for (name, command) in stats.per_command() {
+        let usec_per_call = match command.calls {
+            0 => 0.0,
+            calls => command.usec as f64 / calls as f64,
+        };
+        let _ = write!(
+            section,
+            "cmdstat_{}:calls={},usec={},usec_per_call={:.2},rejected_calls={},failed_calls={}\r\n",
+            name,
+            command.calls,
+            command.usec,
+            usec_per_call,
+            command.rejected_calls,
+            command.failed_calls
+        );
+    }
+
+    section
+}
+
+/// the percentiles of the latency of each command run so far
+fn latencystats(shared: &Shared) -> String {
+    let stats = lock_then_release(&shared.stats);
+    let mut section = "# Latencystats\r\n".to_string();
+    for (name, command) in stats.per_command().filter(|(_, command)| command.calls > 0) {
+        let _ = write!(
+            section,
+            "latency_percentiles_usec_{}:p50={:.3},p99={:.3},p99.9={:.3}\r\n",
+            name,
+            command.percentile(50.0) as f64,
+            command.percentile(99.0) as f64,
+            command.percentile(99.9) as f64
+        );
+    }
+
+    section
+}
+
+/// a line for each database holding keys
+fn keyspace<T: Storage>(databases: &mut [T]) -> String {
+    let mut section = "# Keyspace\r\n".to_string();
+    for (db, storage) in databases.iter_mut().enumerate() {
+        let stats = storage.keyspace_stats();
+        if stats.keys > 0 {
+            let _ = write!(
+                section,
+                "db{}:keys={},expires={},avg_ttl={}\r\n",
+                db, stats.keys, stats.expires, stats.avg_ttl
+            );
+        }
+    }
+
+    section
+}
+
+/// bytes as Redis writes them for a human to read, such as 1.50K
+fn human(bytes: usize) -> String {
+    let units = [("G", 1 << 30), ("M", 1 << 20), ("K", 1 << 10)];
+    match units.iter().find(|(_, size)| bytes >= *size) {
+        Some((unit, size)) => format!("{:.2}{}", bytes as f64 / *size as f64, unit),
+        None => format!("{}B", bytes),
+    }
+}
@@ -0,0 +1,168 @@
+use std::collections::{BTreeMap, VecDeque};
+use std::fmt::Write;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::protocol::response::RedisResponse;
+
+// samples kept by an event, one per second at most
+const MAX_SAMPLES: usize = 160;
+
+/// the latency spikes of an event, the oldest first
+#[derive(Default)]
+struct Event {
+    // seconds since the epoch, and milliseconds
+    samples: VecDeque<(u64, u64)>,
+    // the worst spike since the event was reset, even if no longer sampled
+    max: u64,
+}
+
+/// the events that took longer than the threshold of the latency monitor,
+/// read by LATENCY
+#[derive(Default)]
+pub struct Latency {
+    events: BTreeMap<String, Event>,
+}
+
+impl Latency {
+    pub fn new() -> Self {
+        Latency {
+            events: BTreeMap::new(),
+        }
+    }
+
+    /// record a spike of an event, only the worst one of each second being
+    /// kept
+    pub fn record(&mut self, event: &str, millis: u64) {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        let event = self.events.entry(event.to_string()).or_default();
+
+        match event.samples.back_mut() {
+            Some((time, latency)) if *time == now => *latency = millis.max(*latency),
+            _ => event.samples.push_back((now, millis)),
+        }
+        if event.samples.len() > MAX_SAMPLES {
+            event.samples.pop_front();
+        }
+        event.max = millis.max(event.max);
+    }
+
+    /// the name, the time and the latency of the latest spike of every
+    /// event, along with its worst spike
+    pub fn latest(&self) -> RedisResponse {
+        RedisResponse::Array(
+            self.events
+                .iter()
+                .filter_map(|(name, event)| {
+                    let (time, latency) = event.samples.back()?;
+                    Some(RedisResponse::Array(vec![
+                        RedisResponse::BulkString(name.as_bytes().to_vec()),
+                        RedisResponse::Integer(*time as i64),
+                        RedisResponse::Integer(*latency as i64),
+                        RedisResponse::Integer(event.max as i64),
+                    ]))
+                })
+                .collect(),
+        )
+    }
+
+    /// the time and the latency of the spikes of an event, the oldest first
+    pub fn history(&self, event: &str) -> RedisResponse {
+        let samples = match self.events.get(event) {
+            Some(event) => event.samples.iter().collect(),
+            None => vec![],
+        };
+        RedisResponse::Array(
+            samples
+                .into_iter()
+                .map(|(time, latency)| {
+                    RedisResponse::Array(vec![
+                        RedisResponse::Integer(*time as i64),
+                        RedisResponse::Integer(*latency as i64),
+                    ])
+                })
+                .collect(),
+        )
+    }
+
+    /// forget the spikes of the events given, or of all of them, returns
+    /// the number of events forgotten
+    pub fn reset(&mut self, events: &[String]) -> usize {
+        match events.is_empty() {
+            true => {
+                let count = self.events.len();
+                self.events.clear();
+                count
+            }
+            false => events
+                .iter()
+                .filter(|event| self.events.remove(*event).is_some())
+                .count(),
+        }
+    }
+
+    /// a report of the spikes for a human to read, as LATENCY DOCTOR
+    /// replies it
+    pub fn doctor(&self, threshold: i64) -> String {
+        if self.events.is_empty() {
+            return match threshold {
+                0 => "I'm sorry, Dave, I can't do that. Latency monitoring is disabled in this \
+                      Redis instance. You may use \"CONFIG SET latency-monitor-threshold \
+                      <milliseconds>.\" in order to enable it.\n"
+                    .to_string(),
+                _ => "Dave, no latency spike was observed during the lifetime of this Redis \
+                      instance, not in the slightest bit. I honestly think you ought to sleep \
+                      tonight.\n"
+                    .to_string(),
+            };
+        }
+
+        let mut report = String::from(
+            "Dave, I have observed latency spikes in this Redis instance. You don't mind \
+             talking about it, do you Dave?\n\n",
+        );
+        for (index, (name, event)) in self.events.iter().enumerate() {
+            // an event has a sample at least, as resetting it removes it
+            let count = event.samples.len() as u64;
+            let average = event
+                .samples
+                .iter()
+                .map(|(_, latency)| latency)
+                .sum::<u64>()
+                / count;
+            let deviation = event
+                .samples
+                .iter()
+                .map(|(_, latency)| (*latency as i64 - average as i64).unsigned_abs())
+                .sum::<u64>()
+                / count;
+            let period = match (event.samples.front(), event.samples.back()) {
+                (Some((first, _)), Some((last, _))) if count > 1 => {
+                    (last - first) as f64 / (count - 1) as f64
+                }
+                _ => 0.0,
+            };
+            let _ = writeln!(
+                report,
+                "{}. {}: {} latency spikes (average {}ms, mean deviation {}ms, period {:.2} sec). Worst all time event {}ms.",
+                index + 1,
+                name,
+                count,
+                average,
+                deviation,
+                period,
+                event.max
+            );
+        }
+        report.push_str(
+            "\nI have a few advices for you:\n\n\
+             - Check the slow log with SLOWLOG GET for the commands that took the longest.\n\
+             - Commands running in O(N) over large values, such as KEYS or SMEMBERS, block the \
+             other clients while they run.\n",
+        );
+
+        report
+    }
+}
@@ -1,4 +1,30 @@
+pub mod acl;
+pub mod aof;
+mod blocking;
+pub mod clients;
+pub mod cluster;
+pub mod config;
+pub mod connection;
+pub mod events;
+pub mod evict;
+pub mod expire;
+mod info;
+pub mod latency;
+pub mod monitor;
+pub mod notify;
+pub mod persistence;
+pub mod pubsub;
+pub mod replication;
 mod run_command;
+mod scan;
+#[cfg(feature = "scripting")]
+pub mod scripting;
+pub mod sentinel;
+pub mod shards;
+pub mod shared;
+pub mod slowlog;
+pub mod stats;
+pub mod tracking;
 // re-export run_command
 use crossbeam_channel::{Receiver, Sender};
 pub use run_command::*;
@@ -6,9 +32,11 @@ pub use run_command::*;
 use crate::server::ServerState;
 
 use std::{
+    cell::{Cell, RefCell, RefMut},
     io::{BufReader, Read, Write},
     net::TcpStream,
-    sync::{Arc, Mutex, MutexGuard},
+    ops::{Deref, DerefMut},
+    sync::{Arc, Mutex, MutexGuard, RwLock, RwLockWriteGuard},
     thread,
     time::Duration,
 };
@@ -20,20 +48,169 @@ use crate::{
 };
 
 use super::{CloseConnection, CommandResponse, ReceivedDataLength};
+use connection::Connection;
+use shards::Shards;
+use shared::Shared;
 
-pub fn lock_then_release<T: Storage>(storage: &Arc<Mutex<T>>) -> MutexGuard<T> {
-    loop {
-        match storage.lock() {
-            Ok(storage) => {
-                return storage;
+/// access to what was locked, released once dropped
+pub enum Guard<'a, T> {
+    Mutex(MutexGuard<'a, T>),
+    Transaction(RefMut<'a, T>),
+    Database(MutexGuard<'a, Vec<T>>, usize),
+    RwLock(RwLockWriteGuard<'a, T>),
+    RwLockDatabase(RwLockWriteGuard<'a, Vec<T>>, usize),
+    // every shard, their storages being put back once dropped
+    Shards(Box<dyn DerefMut<Target = T> + 'a>),
+    ShardsDatabase(Box<dyn DerefMut<Target = Vec<T>> + 'a>, usize),
+}
+
+impl<'a, T> Guard<'a, Vec<T>> {
+    /// narrow the guard of all the databases down to one of them
+    pub fn select(self, db: usize) -> Guard<'a, T> {
+        match self {
+            Guard::Mutex(guard) => Guard::Database(guard, db),
+            Guard::RwLock(guard) => Guard::RwLockDatabase(guard, db),
+            Guard::Transaction(guard) => Guard::Transaction(RefMut::map(guard, |dbs| &mut dbs[db])),
+            Guard::Shards(guard) => Guard::ShardsDatabase(guard, db),
+            Guard::Database(..) | Guard::RwLockDatabase(..) | Guard::ShardsDatabase(..) => {
+                unreachable!("databases are never nested")
+            }
+        }
+    }
+}
+
+impl<T> Deref for Guard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        match self {
+            Guard::Mutex(guard) => guard,
+            Guard::Transaction(guard) => guard,
+            Guard::Database(guard, db) => &guard[*db],
+            Guard::RwLock(guard) => guard,
+            Guard::RwLockDatabase(guard, db) => &guard[*db],
+            Guard::Shards(guard) => guard,
+            Guard::ShardsDatabase(guard, db) => &guard[*db],
+        }
+    }
+}
+
+impl<T> DerefMut for Guard<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        match self {
+            Guard::Mutex(guard) => guard,
+            Guard::Transaction(guard) => guard,
+            Guard::Database(guard, db) => &mut guard[*db],
+            Guard::RwLock(guard) => guard,
+            Guard::RwLockDatabase(guard, db) => &mut guard[*db],
+            Guard::Shards(guard) => guard,
+            Guard::ShardsDatabase(guard, db) => &mut guard[*db],
+        }
+    }
+}
+
+/// what the commands lock to reach the storage or the pub/sub registry,
+/// either a mutex shared by the connections or a storage already locked
+/// for a whole transaction
+pub trait Lock<T> {
+    fn acquire(&self) -> Guard<'_, T>;
+}
+
+impl<T> Lock<T> for Arc<Mutex<T>> {
+    fn acquire(&self) -> Guard<'_, T> {
+        (**self).acquire()
+    }
+}
+
+impl<T> Lock<T> for Mutex<T> {
+    fn acquire(&self) -> Guard<'_, T> {
+        loop {
+            match self.lock() {
+                Ok(guard) => {
+                    return Guard::Mutex(guard);
+                }
+                Err(_) => {
+                    thread::sleep(Duration::from_millis(10));
+                }
             }
-            Err(_) => {
-                thread::sleep(Duration::from_millis(10));
+        }
+    }
+}
+
+/// locked for writing, the readers locking it themselves
+impl<T> Lock<T> for RwLock<T> {
+    fn acquire(&self) -> Guard<'_, T> {
+        loop {
+            match self.write() {
+                Ok(guard) => {
+                    return Guard::RwLock(guard);
+                }
+                Err(_) => {
+                    thread::sleep(Duration::from_millis(10));
+                }
             }
         }
     }
 }
 
+impl<T> Lock<T> for RefCell<&mut T> {
+    fn acquire(&self) -> Guard<'_, T> {
+        Guard::Transaction(RefMut::map(self.borrow_mut(), |storage| &mut **storage))
+    }
+}
+
+/// the database a connection selected among all the databases, either
+/// shared by the connections or already locked for a whole transaction or
+/// script
+pub struct Selected<'a, L> {
+    pub databases: &'a L,
+    pub db: usize,
+}
+
+impl<'a, L> Selected<'a, L> {
+    pub fn new(databases: &'a L, db: usize) -> Self {
+        Selected { databases, db }
+    }
+}
+
+impl<T, L: Lock<Vec<T>>> Lock<T> for Selected<'_, L> {
+    fn acquire(&self) -> Guard<'_, T> {
+        self.databases.acquire().select(self.db)
+    }
+}
+
+/// the databases, telling whether a command locked them, for the commands
+/// with no key to do what follows a change of the keys only if they went
+/// through them
+pub struct Touched<'a, L> {
+    databases: &'a L,
+    locked: Cell<bool>,
+}
+
+impl<'a, L> Touched<'a, L> {
+    pub fn new(databases: &'a L) -> Self {
+        Touched {
+            databases,
+            locked: Cell::new(false),
+        }
+    }
+
+    pub fn locked(&self) -> bool {
+        self.locked.get()
+    }
+}
+
+impl<T, L: Lock<T>> Lock<T> for Touched<'_, L> {
+    fn acquire(&self) -> Guard<'_, T> {
+        self.locked.set(true);
+        self.databases.acquire()
+    }
+}
+
+pub fn lock_then_release<T, L: Lock<T>>(lock: &L) -> Guard<'_, T> {
+    lock.acquire()
+}
+
 pub fn stop_sig_received(recv: &Receiver<ServerState>, sender: &Sender<ServerState>) -> bool {
     if let Ok(recv_state) = recv.try_recv() {
         if recv_state == ServerState::Stop {
@@ -57,12 +234,57 @@ pub fn get_command(bytes: &[u8; 512]) -> Result<Command, RedisCommandError> {
     }
 }
 
-fn get_bytes_from_request(stream: &TcpStream) -> ([u8; 512], usize) {
+/// the name of the command sent, in lowercase
+pub fn get_command_name(bytes: &[u8; 512]) -> String {
+    match RedisProtocolParser::parse(bytes) {
+        Ok((Resp::Array(v), _)) => match v.first() {
+            Some(Resp::BulkString(name)) | Some(Resp::String(name)) => {
+                String::from_utf8_lossy(name).to_lowercase()
+            }
+            _ => String::new(),
+        },
+        _ => String::new(),
+    }
+}
+
+/// the arguments of the command sent, its name included
+pub fn get_command_args(bytes: &[u8; 512]) -> Vec<Vec<u8>> {
+    match RedisProtocolParser::parse(bytes) {
+        Ok((Resp::Array(v), _)) => v
+            .iter()
+            .filter_map(|arg| match arg {
+                Resp::BulkString(arg) | Resp::String(arg) => Some(arg.to_vec()),
+                _ => None,
+            })
+            .collect(),
+        _ => vec![],
+    }
+}
+
+/// the second argument of the command sent, in lowercase, naming the
+/// subcommand of a container
+pub fn get_subcommand_name(bytes: &[u8; 512]) -> Option<String> {
+    match RedisProtocolParser::parse(bytes) {
+        Ok((Resp::Array(v), _)) => match v.get(1) {
+            Some(Resp::BulkString(name)) | Some(Resp::String(name)) => {
+                Some(String::from_utf8_lossy(name).to_lowercase())
+            }
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+// the bytes received, and whether the peer has closed the connection
+fn get_bytes_from_request(stream: &TcpStream) -> ([u8; 512], usize, CloseConnection) {
     let mut buf_reader = BufReader::new(stream);
     let mut buf = [0; 512];
     let mut buf_length = 0_usize;
+    let mut closed = false;
 
+    // reading times out when nothing has been received
     while let Ok(s) = buf_reader.read(&mut buf) {
+        closed = s == 0 && buf_length == 0;
         buf_length += s;
 
         if s < 512 {
@@ -70,28 +292,38 @@ fn get_bytes_from_request(stream: &TcpStream) -> ([u8; 512], usize) {
         }
     }
 
-    (buf, buf_length)
+    (buf, buf_length, closed)
 }
 
 pub fn handle_request<T: Storage>(
-    storage: &Arc<Mutex<T>>,
+    databases: &Shards<T>,
+    shared: &Arc<Shared>,
+    connection: &mut Connection,
     mut stream: &TcpStream,
 ) -> (CloseConnection, ReceivedDataLength) {
-    let (buf, buf_length) = get_bytes_from_request(stream);
+    let (buf, buf_length, closed) = get_bytes_from_request(stream);
 
     match buf.get(0) {
         Some(x) if *x == 0 => {
-            return (false, buf_length);
+            return (closed, buf_length);
         }
         _ => {}
     }
 
-    let (command, res) = run_command_and_get_response(storage, &buf);
+    let (command, res) = run_command_and_get_response(databases, shared, connection, &buf);
 
     let _ = stream.write(res.as_slice());
 
     match command {
         Some(command) if command == Command::Quit => (true, buf_length),
-        _ => (false, buf_length),
+        _ => (connection.closing, buf_length),
+    }
+}
+
+/// write the messages pushed to the connection, such as the ones published
+/// to the channels it is subscribed to
+pub fn deliver_pushed(connection: &Connection, mut stream: &TcpStream) {
+    for message in connection.pushed() {
+        let _ = stream.write(message.as_slice());
     }
 }
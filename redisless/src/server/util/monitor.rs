@@ -0,0 +1,85 @@
+use std::collections::HashMap;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crossbeam_channel::Sender;
+
+use super::connection::Connection;
+use crate::server::CommandResponse;
+
+/// the connections that issued MONITOR, told about every command run by
+/// the others
+#[derive(Default)]
+pub struct Monitors {
+    monitors: HashMap<u64, Sender<CommandResponse>>,
+}
+
+impl Monitors {
+    pub fn new() -> Self {
+        Monitors {
+            monitors: HashMap::new(),
+        }
+    }
+
+    pub fn add(&mut self, connection: &Connection) {
+        self.monitors.insert(connection.id, connection.sender());
+    }
+
+    pub fn remove(&mut self, connection: &Connection) {
+        self.monitors.remove(&connection.id);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.monitors.is_empty()
+    }
+
+    /// tell every monitor about a command as it is run, along with the
+    /// database and the address of the client or `lua` if run by a script,
+    /// the password given to AUTH being left out
+    pub fn feed(&self, db: usize, origin: &str, args: &[Vec<u8>]) {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        let mut line = format!(
+            "+{}.{:06} [{} {}]",
+            now.as_secs(),
+            now.subsec_micros(),
+            db,
+            origin
+        );
+
+        let redacted = matches!(args.first(), Some(name) if name.eq_ignore_ascii_case(b"AUTH"));
+        for (index, arg) in args.iter().enumerate() {
+            line.push(' ');
+            match redacted && index > 0 {
+                true => line.push_str("\"(redacted)\""),
+                false => line.push_str(&quote(arg)),
+            }
+        }
+        line.push_str("\r\n");
+
+        for sender in self.monitors.values() {
+            let _ = sender.send(line.clone().into_bytes());
+        }
+    }
+}
+
+/// an argument between double quotes, the bytes that are not printable
+/// being escaped
+fn quote(arg: &[u8]) -> String {
+    let mut quoted = String::from("\"");
+    for byte in arg {
+        match byte {
+            b'\\' => quoted.push_str("\\\\"),
+            b'"' => quoted.push_str("\\\""),
+            b'\n' => quoted.push_str("\\n"),
+            b'\r' => quoted.push_str("\\r"),
+            b'\t' => quoted.push_str("\\t"),
+            7 => quoted.push_str("\\a"),
+            8 => quoted.push_str("\\b"),
+            byte if byte.is_ascii_graphic() || *byte == b' ' => quoted.push(*byte as char),
+            byte => quoted.push_str(&format!("\\x{:02x}", byte)),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
@@ -0,0 +1,168 @@
+use crate::command::Command;
+use crate::protocol;
+use crate::storage::models::redis_sorted_set::RangeBy;
+
+// classes of the keyspace events, as set by notify-keyspace-events
+pub const KEYSPACE: u16 = 1;
+pub const KEYEVENT: u16 = 1 << 1;
+pub const GENERIC: u16 = 1 << 2;
+pub const STRING: u16 = 1 << 3;
+pub const LIST: u16 = 1 << 4;
+pub const SET: u16 = 1 << 5;
+pub const HASH: u16 = 1 << 6;
+pub const ZSET: u16 = 1 << 7;
+pub const EXPIRED: u16 = 1 << 8;
+pub const EVICTED: u16 = 1 << 9;
+pub const STREAM: u16 = 1 << 10;
+pub const KEY_MISS: u16 = 1 << 11;
+pub const MODULE: u16 = 1 << 12;
+pub const NEW: u16 = 1 << 13;
+// what `A` stands for
+pub const ALL: u16 =
+    GENERIC | STRING | LIST | SET | HASH | ZSET | EXPIRED | EVICTED | STREAM | MODULE;
+
+const CLASSES: [(u8, u16); 14] = [
+    (b'g', GENERIC),
+    (b'$', STRING),
+    (b'l', LIST),
+    (b's', SET),
+    (b'h', HASH),
+    (b'z', ZSET),
+    (b'x', EXPIRED),
+    (b'e', EVICTED),
+    (b't', STREAM),
+    (b'd', MODULE),
+    (b'K', KEYSPACE),
+    (b'E', KEYEVENT),
+    (b'm', KEY_MISS),
+    (b'n', NEW),
+];
+
+/// the classes of keyspace events to publish, none by default
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct KeyspaceEvents(pub u16);
+
+impl KeyspaceEvents {
+    /// parse flags such as `KEA` or `Kx`, nothing being published unless
+    /// `K` or `E` is given
+    pub fn parse(flags: &[u8]) -> Option<Self> {
+        let mut classes = 0;
+        for flag in flags {
+            classes |= match flag {
+                b'A' => ALL,
+                _ => CLASSES.iter().find(|(c, _)| c == flag)?.1,
+            };
+        }
+
+        match classes & (KEYSPACE | KEYEVENT) {
+            0 => Some(KeyspaceEvents(0)),
+            _ => Some(KeyspaceEvents(classes)),
+        }
+    }
+
+    pub fn contains(&self, class: u16) -> bool {
+        self.0 & class == class
+    }
+
+    /// the flags back, `A` standing for every class it covers
+    pub fn to_flags(self) -> Vec<u8> {
+        let mut flags = vec![];
+        if self.contains(ALL) {
+            flags.push(b'A');
+        }
+        for (flag, class) in CLASSES.iter() {
+            let covered = ALL & class != 0 && self.contains(ALL);
+            if self.contains(*class) && !covered {
+                flags.push(*flag);
+            }
+        }
+        flags
+    }
+}
+
+/// the class, name and key of the events fired by a command given its
+/// response, none when it failed or changed nothing
+pub fn keyspace_events(command: &Command, response: &[u8]) -> Vec<(u16, &'static str, Vec<u8>)> {
+    use Command::*;
+
+    let failed = response.starts_with(b"-") || response == protocol::NIL;
+    let unchanged = failed || response == b":0\r\n" || response == b"*0\r\n";
+
+    // the events fired whatever the response, and those fired only if
+    // something changed
+    let (events, when_changed) = match command {
+        Set(k, _) | GetSet(k, _) => (vec![(STRING, "set", k)], false),
+        Setnx(k, _) => (vec![(STRING, "set", k)], true),
+        Setex(k, _, _) | PSetex(k, _, _) => {
+            (vec![(STRING, "set", k), (GENERIC, "expire", k)], false)
+        }
+        MSet(items) => (
+            items.iter().map(|(k, _)| (STRING, "set", k)).collect(),
+            false,
+        ),
+        MSetnx(items) => (
+            items.iter().map(|(k, _)| (STRING, "set", k)).collect(),
+            true,
+        ),
+        Expire(k, _) | PExpire(k, _) => (vec![(GENERIC, "expire", k)], true),
+        Del(k) => (vec![(GENERIC, "del", k)], true),
+        // `move_to` is fired in the destination database
+        Move(k, _) => (vec![(GENERIC, "move_from", k)], true),
+        Incr(k) => (vec![(STRING, "incrby", k)], false),
+        SetBit(k, _, _) => (vec![(STRING, "setbit", k)], false),
+        BitField(k, operations) if operations.iter().any(|op| !op.is_read_only()) => {
+            (vec![(STRING, "setbit", k)], false)
+        }
+        BitOp(_, k, _) => (vec![(STRING, "set", k)], false),
+        PfAdd(k, _) => (vec![(STRING, "pfadd", k)], true),
+        PfMerge(k, _) => (vec![(STRING, "pfadd", k)], false),
+        HSet(k, _) => (vec![(HASH, "hset", k)], false),
+        SAdd(k, _) => (vec![(SET, "sadd", k)], true),
+        SRem(k, _) => (vec![(SET, "srem", k)], true),
+        SPop(k, _) => (vec![(SET, "spop", k)], true),
+        SMove(source, destination, _) => (
+            vec![(SET, "srem", source), (SET, "sadd", destination)],
+            true,
+        ),
+        SInterStore(k, _) => (vec![(SET, "sinterstore", k)], true),
+        SUnionStore(k, _) => (vec![(SET, "sunionstore", k)], true),
+        SDiffStore(k, _) => (vec![(SET, "sdiffstore", k)], true),
+        ZAdd(k, _, _) => (vec![(ZSET, "zadd", k)], false),
+        ZIncrBy(k, _, _) => (vec![(ZSET, "zincr", k)], false),
+        ZRem(k, _) => (vec![(ZSET, "zrem", k)], true),
+        ZRemRange(k, range) => {
+            let event = match range {
+                RangeBy::Rank(_, _) => "zremrangebyrank",
+                RangeBy::Score(_, _) => "zremrangebyscore",
+                RangeBy::Lex(_, _) => "zremrangebylex",
+            };
+            (vec![(ZSET, event, k)], true)
+        }
+        ZPop(k, _, max) => {
+            let event = if *max { "zpopmax" } else { "zpopmin" };
+            (vec![(ZSET, event, k)], true)
+        }
+        ZUnionStore(k, _, _, _) => (vec![(ZSET, "zunionstore", k)], true),
+        ZInterStore(k, _, _, _) => (vec![(ZSET, "zinterstore", k)], true),
+        ZDiffStore(k, _) => (vec![(ZSET, "zdiffstore", k)], true),
+        ZRangeStore(k, _, _) => (vec![(ZSET, "zrangestore", k)], true),
+        GeoSearchStore(k, _, _, _) => (vec![(ZSET, "geosearchstore", k)], true),
+        XAdd(k, _, _, _, _) => (vec![(STREAM, "xadd", k)], false),
+        XTrim(k, _) => (vec![(STREAM, "xtrim", k)], true),
+        XDel(k, _) => (vec![(STREAM, "xdel", k)], true),
+        XGroupCreate(k, _, _, _) => (vec![(STREAM, "xgroup-create", k)], false),
+        XGroupSetId(k, _, _) => (vec![(STREAM, "xgroup-setid", k)], false),
+        XGroupDestroy(k, _) => (vec![(STREAM, "xgroup-destroy", k)], true),
+        XGroupCreateConsumer(k, _, _) => (vec![(STREAM, "xgroup-createconsumer", k)], true),
+        XGroupDelConsumer(k, _, _) => (vec![(STREAM, "xgroup-delconsumer", k)], false),
+        _ => (vec![], false),
+    };
+
+    match (failed, when_changed && unchanged) {
+        (false, false) => events
+            .into_iter()
+            .map(|(class, event, k)| (class, event, k.clone()))
+            .collect(),
+        _ => vec![],
+    }
+}
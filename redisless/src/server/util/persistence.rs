@@ -0,0 +1,203 @@
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, BufWriter, ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::Arc;
+use std::thread;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use super::config::Config;
+use super::lock_then_release;
+use super::shards::Shards;
+use super::shared::Shared;
+use crate::storage::models::StoredValue;
+use crate::storage::Storage;
+use crate::storage::{rdb, snapshot};
+
+// seconds before a background save that failed is tried again, however
+// many changes there are
+const BGSAVE_RETRY_DELAY: u64 = 5;
+
+/// how the snapshots of the dataset went, reported by LASTSAVE and INFO
+pub struct Persistence {
+    // seconds since the epoch of the last successful save, or of when the
+    // server started if there was none
+    pub last_save: u64,
+    // keys changed since the last save
+    pub dirty: u64,
+    // the changes the background save in progress covers
+    dirty_before_bgsave: u64,
+    pub bgsave_in_progress: bool,
+    pub last_bgsave_ok: bool,
+    // seconds since the epoch of when the last background save started
+    last_bgsave_try: u64,
+}
+
+impl Persistence {
+    pub fn new() -> Self {
+        Persistence {
+            last_save: now(),
+            dirty: 0,
+            dirty_before_bgsave: 0,
+            bgsave_in_progress: false,
+            last_bgsave_ok: true,
+            last_bgsave_try: 0,
+        }
+    }
+}
+
+impl Default for Persistence {
+    fn default() -> Self {
+        Persistence::new()
+    }
+}
+
+/// the file the dataset is saved to, as configured
+pub fn path(config: &Config) -> PathBuf {
+    Path::new(&*String::from_utf8_lossy(config.value("dir")))
+        .join(&*String::from_utf8_lossy(config.value("dbfilename")))
+}
+
+/// load the dataset saved to the configured file into the databases, if
+/// there is such a file, be it saved by RedisLess or dumped by Redis
+pub fn load<T: Storage>(databases: &mut [T], config: &Config) -> io::Result<()> {
+    let file = match File::open(path(config)) {
+        Ok(file) => file,
+        Err(error) if error.kind() == ErrorKind::NotFound => return Ok(()),
+        Err(error) => return Err(error),
+    };
+
+    let mut reader = BufReader::new(file);
+    let saved = match reader.fill_buf()?.starts_with(snapshot::MAGIC) {
+        true => snapshot::read(&mut reader)?,
+        false => rdb::read(&mut reader)?,
+    };
+    if saved.len() > databases.len() {
+        return Err(io::Error::new(
+            ErrorKind::InvalidData,
+            format!(
+                "the saved dataset has {} databases, more than the {} configured",
+                saved.len(),
+                databases.len()
+            ),
+        ));
+    }
+    for (storage, keys) in databases.iter_mut().zip(saved) {
+        for (key, value) in keys {
+            storage.put(&key, value);
+        }
+    }
+
+    Ok(())
+}
+
+/// save the dataset, the databases being held until it is written
+pub fn save<T: Storage>(databases: &mut [T], shared: &Shared) -> io::Result<()> {
+    let keys = databases
+        .iter_mut()
+        .map(|storage| storage.snapshot())
+        .collect::<Vec<_>>();
+    write(&path(&lock_then_release(&shared.config)), &keys)?;
+    let mut persistence = lock_then_release(&shared.persistence);
+    persistence.last_save = now();
+    persistence.dirty = 0;
+
+    Ok(())
+}
+
+/// save the dataset as it is now on another thread, the databases being
+/// held only while they are copied
+pub fn bgsave<T: Storage>(databases: &mut [T], shared: &Arc<Shared>) {
+    let keys = databases
+        .iter_mut()
+        .map(|storage| storage.snapshot())
+        .collect::<Vec<_>>();
+    let path = path(&lock_then_release(&shared.config));
+    let mut persistence = lock_then_release(&shared.persistence);
+    persistence.bgsave_in_progress = true;
+    persistence.dirty_before_bgsave = persistence.dirty;
+    persistence.last_bgsave_try = now();
+    drop(persistence);
+
+    let shared = Arc::clone(shared);
+    thread::spawn(move || {
+        let saved = write(&path, &keys);
+        let mut persistence = lock_then_release(&shared.persistence);
+        persistence.bgsave_in_progress = false;
+        persistence.last_bgsave_ok = saved.is_ok();
+        if saved.is_ok() {
+            persistence.last_save = now();
+            // the changes made meanwhile are left for the next save
+            persistence.dirty -= persistence.dirty_before_bgsave.min(persistence.dirty);
+        }
+    });
+}
+
+/// start a background save once a save point is reached, that is once at
+/// least as many keys as it says changed within as many seconds since the
+/// last save, called over and over while the server runs
+pub fn cron<T: Storage + Send + Sync + 'static>(databases: &Shards<T>, shared: &Arc<Shared>) {
+    let points = save_points(&lock_then_release(&shared.config));
+    let now = now();
+    let reached = {
+        let persistence = lock_then_release(&shared.persistence);
+        let retry = persistence.last_bgsave_ok
+            || now.saturating_sub(persistence.last_bgsave_try) > BGSAVE_RETRY_DELAY;
+        !persistence.bgsave_in_progress
+            && retry
+            && points.iter().any(|(seconds, changes)| {
+                persistence.dirty >= *changes
+                    && now.saturating_sub(persistence.last_save) > *seconds
+            })
+    };
+    if !reached {
+        return;
+    }
+
+    // the databases are locked before checking again, as SAVE and BGSAVE
+    // do, so that two saves never run at once
+    let mut databases = lock_then_release(databases);
+    if !lock_then_release(&shared.persistence).bgsave_in_progress {
+        bgsave(&mut databases, shared);
+    }
+}
+
+/// the pairs of seconds and changes of the `save` parameter
+fn save_points(config: &Config) -> Vec<(u64, u64)> {
+    let text = String::from_utf8_lossy(config.value("save")).to_string();
+    let fields = text
+        .split_whitespace()
+        .filter_map(|field| field.parse().ok())
+        .collect::<Vec<u64>>();
+    fields
+        .chunks_exact(2)
+        .map(|point| (point[0], point[1]))
+        .collect()
+}
+
+/// write the keys as Redis does to a temporary file then move it over the
+/// given one, so that a save failing halfway leaves the previous one whole
+fn write(path: &Path, keys: &[Vec<(Vec<u8>, StoredValue)>]) -> io::Result<()> {
+    let temporary = path.with_file_name(format!("temp-{}.rdb", process::id()));
+    let written = File::create(&temporary).and_then(|file| {
+        let mut writer = BufWriter::new(file);
+        rdb::write(&mut writer, keys)?;
+        writer.flush()?;
+        writer.get_ref().sync_all()
+    });
+
+    match written {
+        Ok(()) => fs::rename(&temporary, path),
+        Err(error) => {
+            let _ = fs::remove_file(&temporary);
+            Err(error)
+        }
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
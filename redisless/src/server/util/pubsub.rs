@@ -0,0 +1,251 @@
+use std::collections::HashMap;
+
+use crossbeam_channel::Sender;
+
+use super::connection::Connection;
+use super::notify::{KeyspaceEvents, KEYEVENT, KEYSPACE};
+use crate::glob::glob_match;
+use crate::protocol::response::RedisResponse;
+use crate::server::CommandResponse;
+
+type Subscribers = HashMap<u64, Subscriber>;
+
+/// a connection subscribed to a channel or a pattern, pushed messages in
+/// the version of RESP it speaks
+pub struct Subscriber {
+    sender: Sender<CommandResponse>,
+    resp3: bool,
+}
+
+impl Subscriber {
+    fn new(connection: &Connection) -> Self {
+        Subscriber {
+            sender: connection.sender(),
+            resp3: connection.protocol == 3,
+        }
+    }
+
+    /// push a message, as an array in RESP2, returns false if the
+    /// connection is gone
+    pub fn push(&self, message: Vec<RedisResponse>) -> bool {
+        let message = match self.resp3 {
+            true => RedisResponse::Push(message),
+            false => RedisResponse::Array(message),
+        };
+        self.sender.send(message.to_vec()).is_ok()
+    }
+}
+
+/// channels, glob patterns and shard channels along with the connections
+/// subscribed to them, shared by every connection of a server
+#[derive(Default)]
+pub struct PubSub {
+    channels: HashMap<Vec<u8>, Subscribers>,
+    patterns: HashMap<Vec<u8>, Subscribers>,
+    // a standalone server holding every slot, shard channels are only kept
+    // apart from the others
+    shard_channels: HashMap<Vec<u8>, Subscribers>,
+    pub keyspace_events: KeyspaceEvents,
+}
+
+impl PubSub {
+    pub fn new() -> Self {
+        PubSub {
+            channels: HashMap::new(),
+            patterns: HashMap::new(),
+            shard_channels: HashMap::new(),
+            keyspace_events: KeyspaceEvents::default(),
+        }
+    }
+
+    /// returns true if the connection was not subscribed to the channel yet
+    pub fn subscribe(&mut self, channel: &[u8], connection: &mut Connection) -> bool {
+        self.channels
+            .entry(channel.to_vec())
+            .or_default()
+            .insert(connection.id, Subscriber::new(connection));
+
+        connection.channels.insert(channel.to_vec())
+    }
+
+    /// returns true if the connection was subscribed to the channel
+    pub fn unsubscribe(&mut self, channel: &[u8], connection: &mut Connection) -> bool {
+        remove_subscriber(&mut self.channels, channel, connection.id);
+        connection.channels.remove(channel)
+    }
+
+    /// returns true if the connection was not subscribed to the pattern yet
+    pub fn psubscribe(&mut self, pattern: &[u8], connection: &mut Connection) -> bool {
+        self.patterns
+            .entry(pattern.to_vec())
+            .or_default()
+            .insert(connection.id, Subscriber::new(connection));
+
+        connection.patterns.insert(pattern.to_vec())
+    }
+
+    /// returns true if the connection was subscribed to the pattern
+    pub fn punsubscribe(&mut self, pattern: &[u8], connection: &mut Connection) -> bool {
+        remove_subscriber(&mut self.patterns, pattern, connection.id);
+        connection.patterns.remove(pattern)
+    }
+
+    /// returns true if the connection was not subscribed to the shard channel yet
+    pub fn ssubscribe(&mut self, channel: &[u8], connection: &mut Connection) -> bool {
+        self.shard_channels
+            .entry(channel.to_vec())
+            .or_default()
+            .insert(connection.id, Subscriber::new(connection));
+
+        connection.shard_channels.insert(channel.to_vec())
+    }
+
+    /// returns true if the connection was subscribed to the shard channel
+    pub fn sunsubscribe(&mut self, channel: &[u8], connection: &mut Connection) -> bool {
+        remove_subscriber(&mut self.shard_channels, channel, connection.id);
+        connection.shard_channels.remove(channel)
+    }
+
+    /// forget every subscription of a connection being closed
+    pub fn remove(&mut self, connection: &mut Connection) {
+        for channel in connection.channels.clone() {
+            self.unsubscribe(&channel, connection);
+        }
+        for pattern in connection.patterns.clone() {
+            self.punsubscribe(&pattern, connection);
+        }
+        for channel in connection.shard_channels.clone() {
+            self.sunsubscribe(&channel, connection);
+        }
+    }
+
+    /// a connection subscribed to the channel
+    pub fn subscriber(&self, channel: &[u8], id: u64) -> Option<&Subscriber> {
+        self.channels.get(channel)?.get(&id)
+    }
+
+    /// the channels with at least one subscriber, matching the pattern if any
+    pub fn channels(&self, pattern: Option<&[u8]>) -> Vec<Vec<u8>> {
+        matching_names(&self.channels, pattern)
+    }
+
+    /// number of connections subscribed to the channel, the patterns aside
+    pub fn subscribers(&self, channel: &[u8]) -> usize {
+        self.channels
+            .get(channel)
+            .map_or(0, |subscribers| subscribers.len())
+    }
+
+    /// the shard channels with at least one subscriber, matching the pattern if any
+    pub fn shard_channels(&self, pattern: Option<&[u8]>) -> Vec<Vec<u8>> {
+        matching_names(&self.shard_channels, pattern)
+    }
+
+    pub fn shard_subscribers(&self, channel: &[u8]) -> usize {
+        self.shard_channels
+            .get(channel)
+            .map_or(0, |subscribers| subscribers.len())
+    }
+
+    /// number of patterns with at least one subscriber
+    pub fn patterns(&self) -> usize {
+        self.patterns.len()
+    }
+
+    /// push the message to the subscribers of the channel and of the
+    /// patterns matching it, returns how many received it, a connection
+    /// receiving it once per matching subscription
+    pub fn publish(&self, channel: &[u8], message: &[u8]) -> usize {
+        let mut receivers = 0;
+
+        if let Some(subscribers) = self.channels.get(channel) {
+            let push = vec![
+                RedisResponse::BulkString(b"message".to_vec()),
+                RedisResponse::BulkString(channel.to_vec()),
+                RedisResponse::BulkString(message.to_vec()),
+            ];
+            receivers += send(subscribers, &push);
+        }
+
+        for (pattern, subscribers) in &self.patterns {
+            if glob_match(pattern, channel) {
+                let push = vec![
+                    RedisResponse::BulkString(b"pmessage".to_vec()),
+                    RedisResponse::BulkString(pattern.clone()),
+                    RedisResponse::BulkString(channel.to_vec()),
+                    RedisResponse::BulkString(message.to_vec()),
+                ];
+                receivers += send(subscribers, &push);
+            }
+        }
+
+        receivers
+    }
+
+    /// push the message to the subscribers of the shard channel, returns
+    /// how many received it
+    pub fn spublish(&self, channel: &[u8], message: &[u8]) -> usize {
+        match self.shard_channels.get(channel) {
+            Some(subscribers) => {
+                let push = vec![
+                    RedisResponse::BulkString(b"smessage".to_vec()),
+                    RedisResponse::BulkString(channel.to_vec()),
+                    RedisResponse::BulkString(message.to_vec()),
+                ];
+                send(subscribers, &push)
+            }
+            None => 0,
+        }
+    }
+
+    /// publish an event on a key of a database to `__keyspace@<db>__:<key>`
+    /// and `__keyevent@<db>__:<event>` if its class is enabled
+    pub fn notify(&self, db: usize, class: u16, event: &str, key: &[u8]) {
+        if !self.keyspace_events.contains(class) {
+            return;
+        }
+
+        if self.keyspace_events.contains(KEYSPACE) {
+            let channel = [format!("__keyspace@{}__:", db).as_bytes(), key].concat();
+            self.publish(&channel, event.as_bytes());
+        }
+        if self.keyspace_events.contains(KEYEVENT) {
+            let channel = format!("__keyevent@{}__:{}", db, event).into_bytes();
+            self.publish(&channel, key);
+        }
+    }
+}
+
+/// remove the connection from the subscribers of a channel or pattern, which
+/// is dropped once nobody is subscribed to it
+fn remove_subscriber(subscriptions: &mut HashMap<Vec<u8>, Subscribers>, name: &[u8], id: u64) {
+    if let Some(subscribers) = subscriptions.get_mut(name) {
+        subscribers.remove(&id);
+        if subscribers.is_empty() {
+            subscriptions.remove(name);
+        }
+    }
+}
+
+/// the names subscribed to, matching the pattern if any
+fn matching_names(
+    subscriptions: &HashMap<Vec<u8>, Subscribers>,
+    pattern: Option<&[u8]>,
+) -> Vec<Vec<u8>> {
+    subscriptions
+        .keys()
+        .filter(|name| match pattern {
+            Some(pattern) => glob_match(pattern, name),
+            None => true,
+        })
+        .cloned()
+        .collect()
+}
+
+/// returns how many subscribers the push was sent to
+fn send(subscribers: &Subscribers, push: &[RedisResponse]) -> usize {
+    subscribers
+        .values()
+        .filter(|subscriber| subscriber.push(push.to_vec()))
+        .count()
+}
@@ -0,0 +1,677 @@
+use std::collections::{HashMap, VecDeque};
+use std::convert::TryFrom;
+use std::io::{self, BufRead, BufReader, ErrorKind, Read, Write};
+use std::net::{Shutdown, TcpStream};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crossbeam_channel::Sender;
+use uuid::Uuid;
+
+use super::connection::Connection;
+use super::lock_then_release;
+use super::shards::Shards;
+use super::shared::Shared;
+use crate::protocol::response::RedisResponse;
+use crate::server::CommandResponse;
+use crate::storage::snapshot::{self, Change};
+use crate::storage::{rdb, Storage};
+
+// how long a replica waits before connecting again to a master it lost
+const RECONNECT_DELAY: Duration = Duration::from_secs(1);
+// how often a replica tells its master the offset it is at
+const ACK_INTERVAL: Duration = Duration::from_secs(1);
+// how long a failover holds the writes back at most, until it ends anyway
+pub const FAILOVER_PAUSE: Duration = Duration::from_secs(60 * 60 * 24 * 365);
+// the history a replication id that was never replaced stands for
+const NO_REPLID: &str = "0000000000000000000000000000000000000000";
+// what the writes of the clients of a read-only replica are replied
+pub const READONLY_ERROR: &str = "READONLY You can't write against a read only replica.";
+
+/// whether the server replicates a master, and the replicas it feeds with
+/// the changes made to its dataset
+pub struct Replication {
+    // the history of the dataset the offset counts the changes of, the
+    // master's one on a replica
+    pub replid: String,
+    // bytes of changes fed to the replicas, or received from the master
+    pub offset: u64,
+    // the history the replication id replaced, shared with the current one
+    // up to the offset of the first byte that is not, -1 if none, so that
+    // the replicas of a former master resume from it once it is replaced by
+    // one of them
+    pub replid2: String,
+    pub second_replid_offset: i64,
+    // the last changes fed, which a replica that lost its link resumes
+    // from rather than being sent the whole dataset
+    backlog: VecDeque<u8>,
+    pub backlog_size: usize,
+    // the changes are counted and kept in the backlog once a replica
+    // connected, or once the server replicated a master, for the replicas
+    // to come to resume from it
+    pub backlog_active: bool,
+    // the host and the port of the master replicated, none for a master
+    pub master: Option<(String, u16)>,
+    // whether the dataset of the master was received and its changes are
+    // being applied
+    pub link_up: bool,
+    // the dataset of the master is being received
+    pub syncing: bool,
+    // when the master was last heard from, and when the link was lost, none
+    // if it never was up
+    pub last_io: Option<Instant>,
+    pub link_down_since: Option<Instant>,
+    // bumped every time the master changes, for the link to the previous
+    // one to end
+    generation: u64,
+    // the generation a link was started for
+    linked: Option<u64>,
+    // the connection to the master, shut down to end the link
+    link: Option<TcpStream>,
+    // the replicas, by the id of their connection
+    replicas: HashMap<u64, Replica>,
+    // the failover FAILOVER started, if any
+    pub failover: Option<Failover>,
+}
+
+/// a replica fed with the changes, along with the offset it acknowledged
+pub struct Replica {
+    sender: Sender<CommandResponse>,
+    // the port it listens on, as it told with REPLCONF, 0 if it didn't
+    pub port: u16,
+    // the offset it received the changes up to, and when it said so
+    pub ack_offset: u64,
+    pub ack_time: Instant,
+}
+
+/// a replica the master role is handed over to once it caught up, the
+/// writes being held back until then
+pub struct Failover {
+    // the connection of the replica, and the address it listens on
+    replica: u64,
+    host: String,
+    port: u16,
+    // when the failover is aborted, or the replica promoted anyway if
+    // forced to
+    deadline: Option<Instant>,
+    force: bool,
+    // the replica caught up and is being told to become a master
+    promoting: bool,
+}
+
+impl Replication {
+    pub fn new() -> Self {
+        Replication {
+            replid: new_id(),
+            offset: 0,
+            replid2: NO_REPLID.to_string(),
+            second_replid_offset: -1,
+            backlog: VecDeque::new(),
+            backlog_size: 1024 * 1024,
+            backlog_active: false,
+            master: None,
+            link_up: false,
+            syncing: false,
+            last_io: None,
+            link_down_since: None,
+            generation: 0,
+            linked: None,
+            link: None,
+            replicas: HashMap::new(),
+            failover: None,
+        }
+    }
+
+    /// replicate another master, or none to be a master again, the dataset
+    /// being kept as it is
+    pub fn replicate(&mut self, master: Option<(String, u16)>) {
+        if master.is_none() && self.master.is_some() {
+            // the dataset now has a history of its own, the one of the
+            // master being kept for its other replicas to resume from
+            self.replid2 = std::mem::replace(&mut self.replid, new_id());
+            self.second_replid_offset = self.offset as i64 + 1;
+        }
+        self.master = master;
+        self.link_up = false;
+        self.syncing = false;
+        self.last_io = None;
+        self.link_down_since = None;
+        self.generation += 1;
+        if let Some(link) = self.link.take() {
+            let _ = link.shutdown(Shutdown::Both);
+        }
+    }
+
+    /// the dataset of the master was received, its changes being applied
+    fn up(&mut self) {
+        self.link_up = true;
+        self.syncing = false;
+        self.last_io = Some(Instant::now());
+        self.link_down_since = None;
+        self.backlog_active = true;
+    }
+
+    /// feed the replica with the changes from now on
+    pub fn add(&mut self, connection: &Connection) {
+        let replica = Replica {
+            sender: connection.sender(),
+            port: connection.listening_port.unwrap_or(0),
+            ack_offset: self.offset,
+            ack_time: Instant::now(),
+        };
+        self.replicas.insert(connection.id, replica);
+        self.backlog_active = true;
+    }
+
+    pub fn remove(&mut self, connection: &Connection) {
+        self.replicas.remove(&connection.id);
+    }
+
+    /// the offset a replica says it received the changes up to
+    pub fn ack(&mut self, connection: &Connection, offset: u64) {
+        if let Some(replica) = self.replicas.get_mut(&connection.id) {
+            replica.ack_offset = offset;
+            replica.ack_time = Instant::now();
+        }
+    }
+
+    /// the replicas, in the order they connected
+    pub fn replicas(&self) -> Vec<(u64, &Replica)> {
+        let mut replicas = self
+            .replicas
+            .iter()
+            .map(|(id, replica)| (*id, replica))
+            .collect::<Vec<_>>();
+        replicas.sort_by_key(|(id, _)| *id);
+        replicas
+    }
+
+    /// hand the master role over to the replica listening at the address
+    /// given, or the one furthest in the changes, the address each replica
+    /// connected from being given by the id of its connection
+    pub fn start_failover(
+        &mut self,
+        ips: &HashMap<u64, String>,
+        target: Option<(String, u16)>,
+        force: bool,
+        timeout: Option<Duration>,
+    ) -> Result<(), &'static str> {
+        if self.master.is_some() {
+            return Err("ERR FAILOVER is not valid when server is a replica.");
+        }
+        if self.failover.is_some() {
+            return Err("ERR FAILOVER already in progress.");
+        }
+        if self.replicas.is_empty() {
+            return Err("ERR FAILOVER requires connected replicas.");
+        }
+
+        let ip = |id: &u64| ips.get(id).cloned().unwrap_or_default();
+        let replica = match target {
+            Some((host, port)) => self
+                .replicas()
+                .into_iter()
+                .find(|(id, replica)| replica.port == port && ip(id) == host),
+            None => self
+                .replicas()
+                .into_iter()
+                .max_by_key(|(_, replica)| replica.ack_offset),
+        };
+        let (id, port) = match replica {
+            Some((id, replica)) => (id, replica.port),
+            None => return Err("ERR FAILOVER target HOST and PORT is not a replica."),
+        };
+        self.failover = Some(Failover {
+            replica: id,
+            host: ip(&id),
+            port,
+            deadline: timeout.map(|timeout| Instant::now() + timeout),
+            force,
+            promoting: false,
+        });
+        Ok(())
+    }
+
+    /// what INFO tells about the failover in progress
+    pub fn failover_state(&self) -> &'static str {
+        match &self.failover {
+            None => "no-failover",
+            Some(failover) if failover.promoting => "failover-in-progress",
+            Some(_) => "waiting-for-sync",
+        }
+    }
+
+    /// the offset of the first byte kept in the backlog, as Redis counts
+    /// them
+    pub fn backlog_first_byte_offset(&self) -> u64 {
+        self.offset - self.backlog.len() as u64 + 1
+    }
+
+    pub fn backlog_histlen(&self) -> usize {
+        self.backlog.len()
+    }
+
+    /// feed every replica with a change, counted by the offset and kept in
+    /// the backlog
+    pub fn feed(&mut self, change: &Change) {
+        let mut bytes = vec![];
+        let _ = snapshot::write_change(&mut bytes, change);
+        self.offset += bytes.len() as u64;
+        self.backlog.extend(&bytes);
+        let excess = self.backlog.len().saturating_sub(self.backlog_size);
+        self.backlog.drain(..excess);
+        for replica in self.replicas.values() {
+            let _ = replica.sender.send(bytes.clone());
+        }
+    }
+
+    /// what PSYNC replies when the replica can resume from the backlog, the
+    /// changes it lacks, None if it has to be sent the whole dataset, the
+    /// offset being the one of the first byte it lacks as Redis counts them
+    pub fn partial_sync(
+        &mut self,
+        connection: &Connection,
+        replid: &[u8],
+        offset: i64,
+    ) -> Option<CommandResponse> {
+        let received = u64::try_from(offset - 1).ok()?;
+        let same_history = replid == self.replid.as_bytes()
+            || (replid == self.replid2.as_bytes() && offset <= self.second_replid_offset);
+        let backlog_start = self.offset - self.backlog.len() as u64;
+        if !same_history || received < backlog_start || received > self.offset {
+            return None;
+        }
+
+        self.add(connection);
+        let mut response = format!("+CONTINUE {}\r\n", self.replid).into_bytes();
+        response.extend(
+            self.backlog
+                .iter()
+                .skip((received - backlog_start) as usize),
+        );
+        Some(response)
+    }
+}
+
+impl Default for Replication {
+    fn default() -> Self {
+        Replication::new()
+    }
+}
+
+/// whether the writes of the clients are refused, the server replicating a
+/// master with `replica-read-only` on, the changes coming from the master
+/// alone
+pub fn refuses_writes(shared: &Shared) -> bool {
+    let replicating = lock_then_release(&shared.replication).master.is_some();
+    replicating && lock_then_release(&shared.config).value("replica-read-only") == b"yes"
+}
+
+/// what SYNC and PSYNC reply, the dataset as Redis dumps it, the replica
+/// being fed with the changes made from then on
+pub fn full_sync<T: Storage>(
+    databases: &mut [T],
+    shared: &Shared,
+    connection: &Connection,
+) -> CommandResponse {
+    let keys = databases
+        .iter_mut()
+        .map(|storage| storage.snapshot())
+        .collect::<Vec<_>>();
+    let mut dump = vec![];
+    if let Err(error) = rdb::write(&mut dump, &keys) {
+        return RedisResponse::Error(format!("ERR {}", error)).to_vec();
+    }
+
+    // the databases are still held, so that no change is fed before the
+    // dataset it applies to
+    let mut replication = lock_then_release(&shared.replication);
+    replication.add(connection);
+    let mut response = format!(
+        "+FULLRESYNC {} {}\r\n${}\r\n",
+        replication.replid,
+        replication.offset,
+        dump.len()
+    )
+    .into_bytes();
+    response.extend(dump);
+    response
+}
+
+/// connect to the master once REPLICAOF asks to, the link being started on
+/// a thread of its own, called over and over while the server runs
+pub fn cron<T: Storage + Send + Sync + 'static>(databases: &Arc<Shards<T>>, shared: &Arc<Shared>) {
+    failover_cron(shared);
+
+    let mut replication = lock_then_release(&shared.replication);
+    let (host, port) = match &replication.master {
+        Some(master) if replication.linked != Some(replication.generation) => master.clone(),
+        _ => return,
+    };
+    let generation = replication.generation;
+    replication.linked = Some(generation);
+    drop(replication);
+
+    let databases = Arc::clone(databases);
+    let shared = Arc::clone(shared);
+    thread::spawn(move || loop {
+        let _ = link(&databases, &shared, &host, port, generation);
+        let mut replication = lock_then_release(&shared.replication);
+        if replication.generation != generation {
+            return;
+        }
+        if replication.link_up {
+            replication.link_down_since = Some(Instant::now());
+        }
+        replication.link_up = false;
+        replication.syncing = false;
+        // for the acknowledgements to the lost master to end
+        if let Some(link) = replication.link.take() {
+            let _ = link.shutdown(Shutdown::Both);
+        }
+        drop(replication);
+        thread::sleep(RECONNECT_DELAY);
+    });
+}
+
+/// promote the replica of the failover in progress once it acknowledged
+/// every change, or once the timeout is reached if forced to, the failover
+/// being aborted if the replica is lost or the timeout reached otherwise
+fn failover_cron(shared: &Arc<Shared>) {
+    let mut replication = lock_then_release(&shared.replication);
+    let offset = replication.offset;
+    let (caught_up, connected) = match &replication.failover {
+        Some(failover) if !failover.promoting => {
+            match replication.replicas.get(&failover.replica) {
+                Some(replica) => (replica.ack_offset >= offset, true),
+                None => (false, false),
+            }
+        }
+        _ => return,
+    };
+    let failover = match &mut replication.failover {
+        Some(failover) => failover,
+        None => return,
+    };
+    let timed_out = matches!(failover.deadline, Some(deadline) if deadline <= Instant::now());
+    if connected && !caught_up && !timed_out {
+        return;
+    }
+    if !connected || (!caught_up && !failover.force) {
+        replication.failover = None;
+        drop(replication);
+        lock_then_release(&shared.clients).unpause();
+        return;
+    }
+
+    failover.promoting = true;
+    let (host, port) = (failover.host.clone(), failover.port);
+    drop(replication);
+    let shared = Arc::clone(shared);
+    thread::spawn(move || {
+        // the former master replicates the new one, resuming from where it
+        // is as the new one keeps its history
+        let promoted = promote(&shared, &host, port);
+        let mut replication = lock_then_release(&shared.replication);
+        if promoted.is_ok() {
+            replication.replicate(Some((host, port)));
+        }
+        replication.failover = None;
+        drop(replication);
+        lock_then_release(&shared.clients).unpause();
+    });
+}
+
+/// tell the replica at the address to become a master
+fn promote(shared: &Shared, host: &str, port: u16) -> io::Result<()> {
+    let stream = TcpStream::connect((host, port))?;
+    let masterauth = lock_then_release(&shared.config)
+        .value("masterauth")
+        .to_vec();
+    let mut writer = &stream;
+    let mut reader = BufReader::new(&stream);
+    if !masterauth.is_empty() {
+        writer.write_all(&request(&[b"AUTH", &masterauth]))?;
+        expect_ok(&mut reader)?;
+    }
+    writer.write_all(&request(&[b"REPLICAOF", b"NO", b"ONE"]))?;
+    expect_ok(&mut reader)
+}
+
+/// connect to the master, load its dataset then apply the changes it feeds
+/// until the connection is lost or the master changes
+fn link<T: Storage>(
+    databases: &Shards<T>,
+    shared: &Arc<Shared>,
+    host: &str,
+    port: u16,
+    generation: u64,
+) -> io::Result<()> {
+    let stream = TcpStream::connect((host, port))?;
+    // the dataset is resumed from where it is, be it as it was received
+    // from the master before or as it was on a master
+    let (replid, offset) = {
+        let mut replication = lock_then_release(&shared.replication);
+        if replication.generation != generation {
+            return Ok(());
+        }
+        replication.link = Some(stream.try_clone()?);
+        replication.syncing = true;
+        (replication.replid.clone(), replication.offset)
+    };
+
+    let listening_port = lock_then_release(&shared.config)
+        .integer("port")
+        .to_string();
+    let masterauth = lock_then_release(&shared.config)
+        .value("masterauth")
+        .to_vec();
+    let mut writer = &stream;
+    let mut reader = BufReader::new(&stream);
+    if !masterauth.is_empty() {
+        writer.write_all(&request(&[b"AUTH", &masterauth]))?;
+        expect_ok(&mut reader)?;
+    }
+    writer.write_all(&request(&[
+        b"REPLCONF",
+        b"listening-port",
+        listening_port.as_bytes(),
+    ]))?;
+    expect_ok(&mut reader)?;
+    writer.write_all(&request(&[
+        b"PSYNC",
+        replid.as_bytes(),
+        (offset + 1).to_string().as_bytes(),
+    ]))?;
+
+    let line = read_line(&mut reader)?;
+    if let Some(rest) = line.strip_prefix("+CONTINUE") {
+        resume(shared, rest.trim(), generation);
+    } else {
+        full_sync_from(databases, shared, &mut reader, &line, generation)?;
+    }
+
+    let acks = stream.try_clone()?;
+    let acknowledging = Arc::clone(shared);
+    thread::spawn(move || acknowledge(&acknowledging, acks, generation));
+    loop {
+        let change = snapshot::read_change(&mut reader)?;
+        apply(databases, shared, change, generation);
+    }
+}
+
+/// go on receiving the changes from where the replica is, the master
+/// having a replication id of its own once it replaced the one the replica
+/// knew
+fn resume(shared: &Shared, new_replid: &str, generation: u64) {
+    let mut replication = lock_then_release(&shared.replication);
+    if replication.generation != generation {
+        return;
+    }
+    if !new_replid.is_empty() && new_replid != replication.replid {
+        replication.replid2 = std::mem::replace(&mut replication.replid, new_replid.to_string());
+        replication.second_replid_offset = replication.offset as i64 + 1;
+    }
+    replication.up();
+}
+
+/// load the dataset of the master, which replaces the one there was
+fn full_sync_from<T: Storage, R: BufRead>(
+    databases: &Shards<T>,
+    shared: &Shared,
+    mut reader: R,
+    line: &str,
+    generation: u64,
+) -> io::Result<()> {
+    let (replid, offset) = match line.strip_prefix("+FULLRESYNC ") {
+        Some(rest) => match rest.split_once(' ') {
+            Some((replid, offset)) => (replid.to_string(), offset.parse().unwrap_or_default()),
+            None => return Err(invalid(line)),
+        },
+        None => return Err(invalid(line)),
+    };
+    let length = match read_line(&mut reader)?.strip_prefix('$') {
+        Some(length) => length
+            .parse::<u64>()
+            .map_err(|_| invalid("bad dump length"))?,
+        None => return Err(invalid("no dump")),
+    };
+    let mut dump = vec![];
+    (&mut reader).take(length).read_to_end(&mut dump)?;
+    if (dump.len() as u64) < length {
+        return Err(ErrorKind::UnexpectedEof.into());
+    }
+    let saved = rdb::read(&mut dump.as_slice())?;
+
+    {
+        let mut databases = lock_then_release(databases);
+        let mut replication = lock_then_release(&shared.replication);
+        if replication.generation != generation {
+            return Ok(());
+        }
+        for storage in databases.iter_mut() {
+            for (key, _) in storage.snapshot() {
+                storage.remove(&key);
+            }
+        }
+        for (storage, keys) in databases.iter_mut().zip(saved) {
+            for (key, value) in keys {
+                storage.put(&key, value);
+            }
+        }
+        replication.replid = replid;
+        replication.offset = offset;
+        replication.replid2 = NO_REPLID.to_string();
+        replication.second_replid_offset = -1;
+        replication.backlog.clear();
+        replication.up();
+    }
+    lock_then_release(&shared.persistence).dirty += 1;
+    Ok(())
+}
+
+/// apply a change fed by the master as the commands do theirs, the change
+/// being appended to the append only file and fed to the replicas of the
+/// replica in turn
+fn apply<T: Storage>(databases: &Shards<T>, shared: &Shared, change: Change, generation: u64) {
+    let mut databases = lock_then_release(databases);
+    if lock_then_release(&shared.replication).generation != generation {
+        return;
+    }
+    let count = databases.len();
+    let changed = match &change {
+        Change::Put(db, key, value) if *db < count => {
+            databases[*db].put(key, value.clone());
+            vec![key.clone()]
+        }
+        Change::Remove(db, key) if *db < count => {
+            databases[*db].remove(key);
+            vec![key.clone()]
+        }
+        Change::SwapDb(first, second) if *first < count && *second < count => {
+            databases.swap(*first, *second);
+            vec![]
+        }
+        // a database the replica doesn't have
+        _ => return,
+    };
+
+    lock_then_release(&shared.persistence).dirty += 1;
+    let mut aof = lock_then_release(&shared.aof);
+    if aof.is_logging() {
+        aof.append(&change);
+    }
+    drop(aof);
+    let mut replication = lock_then_release(&shared.replication);
+    replication.last_io = Some(Instant::now());
+    replication.feed(&change);
+    drop(replication);
+
+    let pubsub = lock_then_release(&shared.pubsub);
+    let mut tracking = lock_then_release(&shared.tracking);
+    match change {
+        Change::SwapDb(..) => tracking.invalidate_all(&pubsub),
+        _ => tracking.invalidate(&changed, 0, &pubsub),
+    }
+}
+
+/// tell the master the offset the replica received the changes up to, over
+/// and over until the link ends
+fn acknowledge(shared: &Shared, mut stream: TcpStream, generation: u64) {
+    loop {
+        thread::sleep(ACK_INTERVAL);
+        let offset = {
+            let replication = lock_then_release(&shared.replication);
+            if replication.generation != generation || !replication.link_up {
+                return;
+            }
+            replication.offset
+        };
+        let ack = request(&[b"REPLCONF", b"ACK", offset.to_string().as_bytes()]);
+        if stream.write_all(&ack).is_err() {
+            return;
+        }
+    }
+}
+
+/// a command as a client sends it
+pub fn request(args: &[&[u8]]) -> Vec<u8> {
+    let mut request = format!("*{}\r\n", args.len()).into_bytes();
+    for arg in args {
+        request.extend(format!("${}\r\n", arg.len()).into_bytes());
+        request.extend_from_slice(arg);
+        request.extend_from_slice(b"\r\n");
+    }
+    request
+}
+
+pub fn read_line<R: BufRead>(reader: &mut R) -> io::Result<String> {
+    let mut line = String::new();
+    if reader.read_line(&mut line)? == 0 {
+        return Err(ErrorKind::UnexpectedEof.into());
+    }
+    Ok(line.trim_end().to_string())
+}
+
+fn expect_ok<R: BufRead>(reader: &mut R) -> io::Result<()> {
+    match read_line(reader)?.as_str() {
+        "+OK" => Ok(()),
+        line => Err(invalid(line)),
+    }
+}
+
+fn invalid(reason: &str) -> io::Error {
+    io::Error::new(
+        ErrorKind::InvalidData,
+        format!("unexpected reply from the master: {}", reason),
+    )
+}
+
+/// 40 hexadecimal characters, as Redis has for the replication ids and the
+/// ids of the cluster nodes
+pub fn new_id() -> String {
+    let mut replid = Uuid::new_v4().to_simple().to_string();
+    replid.push_str(&Uuid::new_v4().to_simple().to_string()[..8]);
+    replid
+}
@@ -1,142 +1,3936 @@
-use std::sync::{Arc, Mutex};
+use std::{
+    cell::RefCell,
+    collections::HashSet,
+    convert::TryFrom,
+    net::SocketAddr,
+    sync::{atomic::Ordering, Arc},
+    time::{Instant, SystemTime, UNIX_EPOCH},
+};
 
-use crate::{command::Command, storage::Storage};
+use crate::{
+    command::{
+        table::{self, CommandInfo},
+        Command,
+    },
+    protocol::response::RedisResponse,
+    storage::{
+        models::{
+            geo::{self, GeoOrigin, GeoQuery, GeoReplyOptions},
+            hyperloglog::HyperLogLog,
+            redis_set,
+            redis_sorted_set::{self, format_score, RangeQuery, Scores},
+            redis_stream::{
+                now_millis, ConsumerGroup, RedisStream, StreamFields, StreamId, XReadGroupId,
+                XReadId,
+            },
+            redis_value::{bit_count, bit_operation, bit_position, get_bit, lcs},
+            sort::SortQuery,
+            DataType, RedisSortedSet,
+        },
+        snapshot::Change,
+        MemoryStats, Storage,
+    },
+};
 
+use super::blocking::block_on;
+use super::config::Config;
+use super::connection::{Connection, Transaction};
+use super::events::KeyEvent;
+use super::info;
+use super::notify::{self, KeyspaceEvents};
+use super::scan::scan;
+#[cfg(feature = "scripting")]
+use super::scripting;
+use super::shards::{Shard, Shards};
+use super::shared::Shared;
 use super::*;
 
+// how often a command held back by CLIENT PAUSE checks whether it ended
+const PAUSE_CHECK_INTERVAL: Duration = Duration::from_millis(10);
+// entries SLOWLOG GET replies when not given a count
+const DEFAULT_SLOWLOG_COUNT: usize = 10;
+// the version of Redis whose commands the server answers, as told to the
+// clients
+pub(super) const REDIS_VERSION: &str = "7.4.0";
+// what LOLWUT replies above the version
+const LOLWUT_BANNER: &str = r" ____          _ _     _
+|  _ \ ___  __| (_)___| |    ___  ___ ___
+| |_) / _ \/ _` | / __| |   / _ \/ __/ __|
+|  _ <  __/ (_| | \__ \ |__|  __/\__ \__ \
+|_| \_\___|\__,_|_|___/_____\___||___/___/
+";
+// bytes below which MEMORY DOCTOR has too little to look at
+const MEMORY_DOCTOR_MIN_BYTES: usize = 5 * 1024 * 1024;
+
+/// the parameters of the configuration a command looks at on its way, read
+/// at once as it starts rather than locking the configuration each time
+struct Parameters {
+    maxmemory: evict::MaxMemory,
+    // slowlog-log-slower-than and slowlog-max-len
+    slowlog: (i64, i64),
+    latency_threshold: i64,
+}
+
+impl Parameters {
+    fn new(config: &Config) -> Self {
+        Parameters {
+            maxmemory: evict::MaxMemory::new(config),
+            slowlog: (
+                config.integer("slowlog-log-slower-than"),
+                config.integer("slowlog-max-len"),
+            ),
+            latency_threshold: config.integer("latency-monitor-threshold"),
+        }
+    }
+
+    /// whether the keys read are counted as used, for the eviction policies
+    fn evicts(&self) -> bool {
+        self.maxmemory.bytes > 0
+    }
+}
+
 pub fn run_command_and_get_response<T: Storage>(
-    storage: &Arc<Mutex<T>>,
+    databases: &Shards<T>,
+    shared: &Arc<Shared>,
+    connection: &mut Connection,
     bytes: &[u8; 512],
 ) -> (Option<Command>, CommandResponse) {
-    let command = get_command(bytes);
+    let command = match get_command(bytes) {
+        Ok(command) => command,
+        Err(err) => {
+            // EXEC is refused once a command could not be queued
+            if let Some(transaction) = &mut connection.transaction {
+                transaction.aborted = true;
+            }
+            return (None, format!("-ERR {}\r\n", err).as_bytes().to_vec());
+        }
+    };
+
+    let name = get_command_name(bytes);
+    let subcommand = get_subcommand_name(bytes);
+    let info = table::lookup(&name, subcommand.as_deref());
+    let full_name = match info {
+        Some(info) => info.name.to_string(),
+        None => name.clone(),
+    };
+    // the sentinel listener runs the commands of a sentinel alone, which
+    // the server doesn't know about
+    let unknown = match connection.sentinel {
+        true => !command.is_allowed_in_sentinel(),
+        false => command.is_sentinel(),
+    };
+    if unknown {
+        if let Some(transaction) = &mut connection.transaction {
+            transaction.aborted = true;
+        }
+        let error = format!("ERR unknown command '{}'", name);
+        return (None, RedisResponse::Error(error).to_vec());
+    }
+
+    let allowed =
+        lock_then_release(&shared.acl).check(connection, &command, &name, subcommand.as_deref());
+    if let Err(error) = allowed {
+        if let Some(transaction) = &mut connection.transaction {
+            transaction.aborted = true;
+        }
+        lock_then_release(&shared.stats).reject(&full_name);
+        return (None, RedisResponse::Error(error).to_vec());
+    }
+
+    // a RESP3 connection tells the messages pushed apart from the replies,
+    // so it may run any command while subscribed
+    if connection.protocol == 2
+        && connection.is_subscribed()
+        && !command.is_allowed_when_subscribed()
+    {
+        let error = format!(
+            "ERR Can't execute '{}': only (P|S)SUBSCRIBE / (P|S)UNSUBSCRIBE / PING / QUIT / RESET are allowed in this context",
+            name
+        );
+        lock_then_release(&shared.stats).reject(&full_name);
+        return (Some(command), RedisResponse::Error(error).to_vec());
+    }
+
+    // the commands that may write, held back by CLIENT PAUSE WRITE and
+    // never read from a replica, a transaction as a whole
+    let may_write = match (&command, &connection.transaction) {
+        (Command::Exec, Some(transaction)) => transaction
+            .commands
+            .iter()
+            .any(|(_, command)| command.may_replicate()),
+        (command, _) => command.may_replicate(),
+    };
+
+    // in cluster mode the keys have to be in a slot the node serves, the
+    // ones of a transaction being in the same slot, a READONLY connection
+    // of a replica reading the keys of the slots its master serves
+    let keys = match (&command, &connection.transaction) {
+        (Command::Exec, Some(transaction)) => transaction
+            .commands
+            .iter()
+            .flat_map(|(_, command)| command.keys())
+            .collect(),
+        (command, _) => command.keys(),
+    };
+    let master = match connection.readonly && !may_write {
+        true => lock_then_release(&shared.replication).master.clone(),
+        false => None,
+    };
+    let redirect = match keys.is_empty() {
+        true => None,
+        false => lock_then_release(&shared.cluster).redirect(&keys, master.as_ref()),
+    };
+    if let Some(error) = redirect {
+        if let Some(transaction) = &mut connection.transaction {
+            transaction.aborted = true;
+        }
+        lock_then_release(&shared.stats).reject(&full_name);
+        return (Some(command), RedisResponse::Error(error).to_vec());
+    }
+
+    // CLIENT PAUSE holds the commands back until it ends, the transactions
+    // being held back as a whole
+    let holds_back = !matches!(
+        command,
+        Command::ClientPause(..) | Command::ClientUnpause | Command::Quit
+    );
+    while holds_back && lock_then_release(&shared.clients).is_paused(may_write) {
+        thread::sleep(PAUSE_CHECK_INTERVAL);
+    }
+
+    // a read-only replica refuses the writes before they are queued, and
+    // the transactions that queued some before it became one, the writes
+    // held back by a failover being refused once it is over
+    let writes = match (&command, &connection.transaction) {
+        (Command::Exec, Some(transaction)) => transaction
+            .commands
+            .iter()
+            .any(|(_, command)| command.is_write()),
+        (command, _) => command.is_write(),
+    };
+    if writes && replication::refuses_writes(shared) {
+        if let Some(transaction) = &mut connection.transaction {
+            transaction.aborted = true;
+        }
+        lock_then_release(&shared.stats).reject(&full_name);
+        let error = RedisResponse::Error(replication::READONLY_ERROR.to_string());
+        return (Some(command), error.to_vec());
+    }
 
+    let has_flag = |flag| match info {
+        Some(info) => info.flags.contains(&flag),
+        None => false,
+    };
+    let parameters = Parameters::new(&lock_then_release(&shared.config));
+
+    // keys are evicted before the commands that may take more memory once
+    // the keys take more than maxmemory, such a command being refused if
+    // there is no key left to evict, as the transactions that queued one
+    let denies_oom = match (&command, &connection.transaction) {
+        (Command::Exec, Some(transaction)) => transaction.commands.iter().any(
+            |(name, _)| matches!(table::find(name), Some(info) if info.flags.contains(&"denyoom")),
+        ),
+        _ => has_flag("denyoom"),
+    };
+    if denies_oom && !evict::make_room(databases, shared, parameters.maxmemory) {
+        if let Some(transaction) = &mut connection.transaction {
+            transaction.aborted = true;
+        }
+        lock_then_release(&shared.stats).reject(&full_name);
+        let error = RedisResponse::Error(evict::OOM_ERROR.to_string());
+        return (Some(command), error.to_vec());
+    }
+
+    // admin commands, MONITOR included, are not told to the monitors
+    let is_admin = has_flag("admin");
+    let monitors = lock_then_release(&shared.monitors);
+    if !monitors.is_empty() && !is_admin {
+        let addr = match lock_then_release(&shared.clients).get(connection.id) {
+            Some(client) => client.addr.clone(),
+            None => String::new(),
+        };
+        monitors.feed(connection.db, &addr, &get_command_args(bytes));
+    }
+    drop(monitors);
+
+    // QUIT closes the connection right away, even in a transaction, and
+    // RESET discards it
+    let is_queued = !matches!(
+        command,
+        Command::Multi
+            | Command::Exec
+            | Command::Discard
+            | Command::Watch(_)
+            | Command::Quit
+            | Command::Reset
+    );
+    if let (Some(transaction), true) = (&mut connection.transaction, is_queued) {
+        transaction
+            .commands
+            .push((full_name, command.without_blocking()));
+        return (None, RedisResponse::Status("QUEUED".to_string()).to_vec());
+    }
+
+    let started = Instant::now();
     let response = match &command {
-        Ok(command) => match command {
-            Command::Set(k, v) => {
-                lock_then_release(storage).write(k.as_slice(), v.as_slice());
+        Command::Multi => match connection.transaction {
+            Some(_) => {
+                RedisResponse::Error("ERR MULTI calls can not be nested".to_string()).to_vec()
+            }
+            None => {
+                connection.transaction = Some(Transaction::default());
                 protocol::OK.to_vec()
             }
-            Command::Setex(k, expiry, v) | Command::PSetex(k, expiry, v) => {
-                let mut storage = lock_then_release(storage);
+        },
+        Command::Exec => match connection.transaction.take() {
+            Some(transaction) if transaction.aborted => {
+                connection.watched.clear();
+                RedisResponse::Error(
+                    "EXECABORT Transaction discarded because of previous errors.".to_string(),
+                )
+                .to_vec()
+            }
+            Some(transaction) => exec(
+                databases,
+                shared,
+                connection,
+                transaction.commands,
+                parameters.evicts(),
+            ),
+            None => RedisResponse::Error("ERR EXEC without MULTI".to_string()).to_vec(),
+        },
+        Command::Discard => match connection.transaction.take() {
+            Some(_) => {
+                connection.watched.clear();
+                protocol::OK.to_vec()
+            }
+            None => RedisResponse::Error("ERR DISCARD without MULTI".to_string()).to_vec(),
+        },
+        Command::Watch(_) if connection.transaction.is_some() => {
+            RedisResponse::Error("ERR WATCH inside MULTI is not allowed".to_string()).to_vec()
+        }
+        Command::Watch(keys) => {
+            let db = connection.db;
+            let mut databases = lock_then_release(databases);
 
-                storage.write(k.as_slice(), v.as_slice());
-                storage.expire(k.as_slice(), *expiry);
+            // a key watched twice keeps the version it had the first time
+            for k in keys {
+                connection
+                    .watched
+                    .entry((db, k.clone()))
+                    .or_insert_with(|| databases[db].version(k));
+            }
+            protocol::OK.to_vec()
+        }
+        // a command whose keys are all in one shard locks that shard alone
+        command => {
+            let db = connection.db;
+            let evicts = parameters.evicts();
+            match databases.shard_of(command) {
+                Some(shard) => match read_shared(&shard, shared, connection, command, evicts) {
+                    Some(response) => response,
+                    None => {
+                        let response = execute(&shard, shared, connection, command);
+                        publish_keyspace_events(
+                            &shard, db, shared, connection, command, &response, evicts,
+                        );
+                        response
+                    }
+                },
+                // one with no key, such as PING, locks the databases only if
+                // it goes through the keys, as KEYS does
+                None => {
+                    let databases = Touched::new(databases);
+                    let response = execute(&databases, shared, connection, command);
+                    if databases.locked() {
+                        publish_keyspace_events(
+                            &databases, db, shared, connection, command, &response, evicts,
+                        );
+                    }
+                    response
+                }
+            }
+        }
+    };
+    // RESP3 has a type of its own for the replies that are nil, the nils
+    // within arrays being left as they are
+    let response = match connection.protocol == 3
+        && (response == protocol::NIL || response == protocol::NIL_ARRAY)
+    {
+        true => protocol::RESP3_NULL.to_vec(),
+        false => response,
+    };
 
-                protocol::OK.to_vec()
+    // CONFIG SET is told about as the parameters it set say
+    let parameters = match command {
+        Command::ConfigSet(..) => Parameters::new(&lock_then_release(&shared.config)),
+        _ => parameters,
+    };
+
+    // the time spent blocked by a blocking command is not counted
+    let duration = started.elapsed();
+    if !has_flag("skip_slowlog") && !has_flag("blocking") {
+        let (threshold, max_len) = parameters.slowlog;
+        if threshold >= 0 && duration.as_micros() >= threshold as u128 {
+            let (addr, client_name) = match lock_then_release(&shared.clients).get(connection.id) {
+                Some(client) => (client.addr.clone(), client.name.clone().unwrap_or_default()),
+                None => (String::new(), vec![]),
+            };
+            lock_then_release(&shared.slowlog).record(
+                duration,
+                &get_command_args(bytes),
+                &addr,
+                &client_name,
+                max_len as usize,
+            );
+        }
+    }
+
+    // the latency monitor samples the commands that took too long, in
+    // milliseconds
+    let threshold = parameters.latency_threshold;
+    if threshold > 0 && !has_flag("blocking") && duration.as_millis() >= threshold as u128 {
+        let event = match has_flag("fast") {
+            true => "fast-command",
+            false => "command",
+        };
+        lock_then_release(&shared.latency).record(event, duration.as_millis() as u64);
+    }
+
+    lock_then_release(&shared.clients).update(connection, &full_name);
+    lock_then_release(&shared.stats).record(&full_name, duration, response.first() == Some(&b'-'));
+
+    (Some(command), response)
+}
+
+/// run the commands queued by a transaction one after the other under a
+/// single lock of the databases, returns the array of their responses or a
+/// nil array if a watched key has been modified
+fn exec<T: Storage>(
+    databases: &Shards<T>,
+    shared: &Arc<Shared>,
+    connection: &mut Connection,
+    commands: Vec<(String, Command)>,
+    evicts: bool,
+) -> CommandResponse {
+    let mut databases = lock_then_release(databases);
+
+    let watched = std::mem::take(&mut connection.watched);
+    if watched
+        .iter()
+        .any(|((db, k), version)| databases[*db].version(k) != *version)
+    {
+        return protocol::NIL_ARRAY.to_vec();
+    }
+
+    let databases = RefCell::new(&mut *databases);
+
+    let mut response = format!("*{}\r\n", commands.len()).into_bytes();
+    for (name, command) in &commands {
+        // SELECT switches the database of the commands queued after it
+        let db = connection.db;
+        let started = Instant::now();
+        let command_response = execute(&databases, shared, connection, command);
+        lock_then_release(&shared.stats).record(
+            name,
+            started.elapsed(),
+            command_response.first() == Some(&b'-'),
+        );
+        publish_keyspace_events(
+            &databases,
+            db,
+            shared,
+            connection,
+            command,
+            &command_response,
+            evicts,
+        );
+        response.extend(command_response);
+    }
+
+    response
+}
+
+/// run a command on the database selected by the connection, the databases
+/// being locked by each command or once for a whole transaction or script
+pub fn execute<T: Storage, L: Lock<Vec<T>>>(
+    databases: &L,
+    shared: &Arc<Shared>,
+    connection: &mut Connection,
+    command: &Command,
+) -> CommandResponse {
+    let storage = &Selected::new(databases, connection.db);
+
+    match command {
+        Command::Set(k, v) => {
+            lock_then_release(storage).write(k.as_slice(), v.as_slice());
+            protocol::OK.to_vec()
+        }
+        Command::Setex(k, expiry, v) | Command::PSetex(k, expiry, v) => {
+            let mut storage = lock_then_release(storage);
+
+            storage.write(k.as_slice(), v.as_slice());
+            storage.expire(k.as_slice(), *expiry);
+
+            protocol::OK.to_vec()
+        }
+        Command::Setnx(k, v) => {
+            let mut storage = lock_then_release(storage);
+            match storage.contains(k) {
+                // Key exists, will not re set key
+                true => b":0\r\n".to_vec(),
+                // Key does not exist, will set key
+                false => {
+                    storage.write(k, v);
+                    b":1\r\n".to_vec()
+                }
             }
-            Command::Setnx(k, v) => {
-                let mut storage = lock_then_release(storage);
-                match storage.contains(k) {
-                    // Key exists, will not re set key
-                    true => b":0\r\n".to_vec(),
-                    // Key does not exist, will set key
-                    false => {
-                        storage.write(k, v);
-                        b":1\r\n".to_vec()
+        }
+        Command::MSet(items) => {
+            let mut storage = lock_then_release(storage);
+            items.iter().for_each(|(k, v)| storage.write(k, v));
+            protocol::OK.to_vec()
+        }
+        Command::MSetnx(items) => {
+            // Either set all or not set any at all if any already exist
+            let mut storage = lock_then_release(storage);
+            match items.iter().all(|(key, _)| !storage.contains(key)) {
+                // None of the keys already exist in the storage
+                true => {
+                    items.iter().for_each(|(k, v)| storage.write(k, v));
+                    b":1\r\n".to_vec()
+                }
+                // Some key exists, don't write any of the keys
+                false => b":0\r\n".to_vec(),
+            }
+        }
+        Command::Expire(k, expiry) | Command::PExpire(k, expiry) => {
+            let v = lock_then_release(storage).expire(k.as_slice(), *expiry);
+            format!(":{}\r\n", v).as_bytes().to_vec()
+        }
+        Command::Get(k) => {
+            let mut storage = lock_then_release(storage);
+
+            match storage.type_of(k) {
+                Some(DataType::String) | None => match storage.read(k.as_slice()) {
+                    Some(value) => value_response(&value).to_vec(),
+                    None => protocol::NIL.to_vec(),
+                },
+                Some(_) => protocol::WRONGTYPE.to_vec(),
+            }
+        }
+        Command::GetSet(k, v) => {
+            let mut storage = lock_then_release(storage);
+
+            if !matches!(storage.type_of(k), Some(DataType::String) | None) {
+                return protocol::WRONGTYPE.to_vec();
+            }
+            let response = match storage.read(k.as_slice()) {
+                Some(value) => value_response(&value).to_vec(),
+                None => protocol::NIL.to_vec(),
+            };
+            storage.write(k.as_slice(), v.as_slice());
+            response
+        }
+        Command::MGet(keys) => {
+            let storage = lock_then_release(storage);
+
+            let values = keys
+                .iter()
+                .map(|key| match storage.read(key.as_slice()) {
+                    Some(value) => value_response(&value),
+                    None => RedisResponse::Nil,
+                })
+                .collect();
+
+            RedisResponse::Array(values).to_vec()
+        }
+        Command::HSet(k, items) => {
+            let mut storage = lock_then_release(storage);
+
+            match storage.type_of(k) {
+                Some(DataType::Hash) | None => {
+                    let created: u32 = items
+                        .iter()
+                        .map(|(field, value)| storage.hwrite(k, field, value))
+                        .sum();
+                    format!(":{}\r\n", created).as_bytes().to_vec()
+                }
+                Some(_) => protocol::WRONGTYPE.to_vec(),
+            }
+        }
+        Command::HStrlen(k, field) => {
+            let mut storage = lock_then_release(storage);
+
+            match storage.type_of(k) {
+                Some(DataType::Hash) | None => {
+                    let len = storage.hread(k, field).map_or(0, |value| value.len());
+                    format!(":{}\r\n", len).as_bytes().to_vec()
+                }
+                Some(_) => protocol::WRONGTYPE.to_vec(),
+            }
+        }
+        Command::HExpire(k, expiry, condition, fields)
+        | Command::HPExpire(k, expiry, condition, fields) => {
+            let mut storage = lock_then_release(storage);
+
+            match storage.type_of(k) {
+                Some(DataType::Hash) | None => RedisResponse::Array(
+                    fields
+                        .iter()
+                        .map(|field| {
+                            RedisResponse::Integer(storage.hexpire(k, field, *expiry, *condition))
+                        })
+                        .collect(),
+                )
+                .to_vec(),
+                Some(_) => protocol::WRONGTYPE.to_vec(),
+            }
+        }
+        Command::HTtl(k, fields) | Command::HPTtl(k, fields) => {
+            let mut storage = lock_then_release(storage);
+            let in_secs = matches!(command, Command::HTtl(_, _));
+
+            match storage.type_of(k) {
+                Some(DataType::Hash) | None => RedisResponse::Array(
+                    fields
+                        .iter()
+                        .map(|field| match storage.hpttl(k, field) {
+                            // round up to the next second as redis does
+                            ttl if ttl >= 0 && in_secs => {
+                                RedisResponse::Integer((ttl + 999) / 1000)
+                            }
+                            ttl => RedisResponse::Integer(ttl),
+                        })
+                        .collect(),
+                )
+                .to_vec(),
+                Some(_) => protocol::WRONGTYPE.to_vec(),
+            }
+        }
+        Command::HPersist(k, fields) => {
+            let mut storage = lock_then_release(storage);
+
+            match storage.type_of(k) {
+                Some(DataType::Hash) | None => RedisResponse::Array(
+                    fields
+                        .iter()
+                        .map(|field| RedisResponse::Integer(storage.hpersist(k, field)))
+                        .collect(),
+                )
+                .to_vec(),
+                Some(_) => protocol::WRONGTYPE.to_vec(),
+            }
+        }
+        Command::SAdd(k, members) => {
+            let mut storage = lock_then_release(storage);
+
+            match storage.type_of(k) {
+                Some(DataType::Set) | None => {
+                    let added: u32 = members.iter().map(|m| storage.sadd(k, m)).sum();
+                    RedisResponse::Integer(added as i64).to_vec()
+                }
+                Some(_) => protocol::WRONGTYPE.to_vec(),
+            }
+        }
+        Command::SRem(k, members) => {
+            let mut storage = lock_then_release(storage);
+
+            match storage.type_of(k) {
+                Some(DataType::Set) | None => {
+                    let removed: u32 = members.iter().map(|m| storage.srem(k, m)).sum();
+                    RedisResponse::Integer(removed as i64).to_vec()
+                }
+                Some(_) => protocol::WRONGTYPE.to_vec(),
+            }
+        }
+        Command::SMembers(k) => {
+            let mut storage = lock_then_release(storage);
+
+            match storage.type_of(k) {
+                Some(DataType::Set) | None => {
+                    let members = storage.sread(k).map_or(vec![], |set| {
+                        set.data
+                            .iter()
+                            .map(|m| RedisResponse::BulkString(m.clone()))
+                            .collect()
+                    });
+                    RedisResponse::Array(members).to_vec()
+                }
+                Some(_) => protocol::WRONGTYPE.to_vec(),
+            }
+        }
+        Command::SIsMember(k, member) => {
+            let mut storage = lock_then_release(storage);
+
+            match storage.type_of(k) {
+                Some(DataType::Set) | None => {
+                    let is_member = match storage.sread(k) {
+                        Some(set) => set.data.contains(member),
+                        None => false,
+                    };
+                    RedisResponse::Integer(is_member as i64).to_vec()
+                }
+                Some(_) => protocol::WRONGTYPE.to_vec(),
+            }
+        }
+        Command::SMIsMember(k, members) => {
+            let mut storage = lock_then_release(storage);
+
+            match storage.type_of(k) {
+                Some(DataType::Set) | None => {
+                    let set = storage.sread(k);
+                    let are_members = members
+                        .iter()
+                        .map(|member| match set {
+                            Some(set) => RedisResponse::Integer(set.data.contains(member) as i64),
+                            None => RedisResponse::Integer(0),
+                        })
+                        .collect();
+                    RedisResponse::Array(are_members).to_vec()
+                }
+                Some(_) => protocol::WRONGTYPE.to_vec(),
+            }
+        }
+        Command::SCard(k) => {
+            let mut storage = lock_then_release(storage);
+
+            match storage.type_of(k) {
+                Some(DataType::Set) | None => {
+                    let cardinality = storage.sread(k).map_or(0, |set| set.data.len());
+                    RedisResponse::Integer(cardinality as i64).to_vec()
+                }
+                Some(_) => protocol::WRONGTYPE.to_vec(),
+            }
+        }
+        Command::SScan(k, cursor, pattern, count) => {
+            let mut storage = lock_then_release(storage);
+
+            match storage.type_of(k) {
+                Some(DataType::Set) | None => {
+                    let (next_cursor, members) = match storage.sread(k) {
+                        Some(set) => scan(
+                            set.data.iter().map(|m| (&m[..], m)),
+                            *cursor,
+                            pattern.as_deref(),
+                            *count,
+                        ),
+                        None => (0, vec![]),
+                    };
+
+                    scan_response(
+                        next_cursor,
+                        members
+                            .into_iter()
+                            .map(|m| RedisResponse::BulkString(m.clone()))
+                            .collect(),
+                    )
+                }
+                Some(_) => protocol::WRONGTYPE.to_vec(),
+            }
+        }
+        Command::SMove(source, destination, member) => {
+            // both sets are checked and updated while holding the lock,
+            // so the move can't be observed half done
+            let mut storage = lock_then_release(storage);
+
+            match (storage.type_of(source), storage.type_of(destination)) {
+                (Some(DataType::Set) | None, Some(DataType::Set) | None) => {
+                    let moved = if source == destination {
+                        match storage.sread(source) {
+                            Some(set) => set.data.contains(member) as i64,
+                            None => 0,
+                        }
+                    } else if storage.srem(source, member) == 1 {
+                        storage.sadd(destination, member);
+                        1
+                    } else {
+                        0
+                    };
+                    RedisResponse::Integer(moved).to_vec()
+                }
+                _ => protocol::WRONGTYPE.to_vec(),
+            }
+        }
+        Command::SInter(keys) | Command::SUnion(keys) | Command::SDiff(keys) => {
+            let mut storage = lock_then_release(storage);
+
+            match read_sets(&mut *storage, keys) {
+                Some(sets) => {
+                    let members = match command {
+                        Command::SInter(_) => redis_set::intersection(&sets),
+                        Command::SUnion(_) => redis_set::union(&sets),
+                        _ => redis_set::difference(&sets),
+                    };
+
+                    RedisResponse::Array(
+                        members.into_iter().map(RedisResponse::BulkString).collect(),
+                    )
+                    .to_vec()
+                }
+                None => protocol::WRONGTYPE.to_vec(),
+            }
+        }
+        Command::SInterCard(keys, limit) => {
+            let mut storage = lock_then_release(storage);
+
+            let is_wrong_type = keys
+                .iter()
+                .any(|key| !matches!(storage.type_of(key), Some(DataType::Set) | None));
+
+            match is_wrong_type {
+                false => {
+                    let cardinality = storage.sintercard(keys, *limit);
+                    RedisResponse::Integer(cardinality as i64).to_vec()
+                }
+                true => protocol::WRONGTYPE.to_vec(),
+            }
+        }
+        Command::SInterStore(destination, keys)
+        | Command::SUnionStore(destination, keys)
+        | Command::SDiffStore(destination, keys) => {
+            let mut storage = lock_then_release(storage);
+
+            match read_sets(&mut *storage, keys) {
+                Some(sets) => {
+                    let members = match command {
+                        Command::SInterStore(_, _) => redis_set::intersection(&sets),
+                        Command::SUnionStore(_, _) => redis_set::union(&sets),
+                        _ => redis_set::difference(&sets),
+                    };
+
+                    let cardinality = members.len();
+                    storage.swrite(destination, members);
+                    RedisResponse::Integer(cardinality as i64).to_vec()
+                }
+                None => protocol::WRONGTYPE.to_vec(),
+            }
+        }
+        Command::SPop(k, count) => {
+            let mut storage = lock_then_release(storage);
+
+            match storage.type_of(k) {
+                Some(DataType::Set) | None => {
+                    let members = storage.spop(k, count.unwrap_or(1) as usize);
+                    bulk_strings_response(members, count.is_some())
+                }
+                Some(_) => protocol::WRONGTYPE.to_vec(),
+            }
+        }
+        Command::SRandMember(k, count) => {
+            let mut storage = lock_then_release(storage);
+
+            match storage.type_of(k) {
+                Some(DataType::Set) | None => {
+                    let members = storage
+                        .sread(k)
+                        .map_or(vec![], |set| set.random_members(count.unwrap_or(1)));
+                    bulk_strings_response(members, count.is_some())
+                }
+                Some(_) => protocol::WRONGTYPE.to_vec(),
+            }
+        }
+        Command::ZAdd(k, options, members) => {
+            let mut storage = lock_then_release(storage);
+
+            match storage.type_of(k) {
+                // a single pair is given with INCR, the reply is the new score
+                Some(DataType::SortedSet) | None if options.incr => {
+                    let (increment, member) = &members[0];
+                    let current = storage.zread(k).and_then(|z| z.score(member));
+                    let score = current.unwrap_or(0.0) + increment;
+
+                    if score.is_nan() {
+                        RedisResponse::Error(
+                            "ERR resulting score is not a number (NaN)".to_string(),
+                        )
+                        .to_vec()
+                    } else if options.allows(current, score) {
+                        storage.zadd(k, score, member);
+                        RedisResponse::BulkString(format_score(score).into_bytes()).to_vec()
+                    } else {
+                        // aborted by one of the conditional flags
+                        RedisResponse::Nil.to_vec()
+                    }
+                }
+                Some(DataType::SortedSet) | None => {
+                    let mut added = 0;
+                    let mut updated = 0;
+                    for (score, member) in members {
+                        let current = storage.zread(k).and_then(|z| z.score(member));
+                        if !options.allows(current, *score) {
+                            continue;
+                        }
+
+                        match current {
+                            Some(current) if current == *score => {}
+                            Some(_) => updated += 1,
+                            None => added += 1,
+                        }
+                        storage.zadd(k, *score, member);
+                    }
+
+                    match options.ch {
+                        true => RedisResponse::Integer(added + updated),
+                        false => RedisResponse::Integer(added),
                     }
+                    .to_vec()
                 }
+                Some(_) => protocol::WRONGTYPE.to_vec(),
             }
-            Command::MSet(items) => {
-                let mut storage = lock_then_release(storage);
-                items.iter().for_each(|(k, v)| storage.write(k, v));
-                protocol::OK.to_vec()
+        }
+        Command::ZScore(k, member) => {
+            let mut storage = lock_then_release(storage);
+
+            match storage.type_of(k) {
+                Some(DataType::SortedSet) | None => {
+                    match storage.zread(k).and_then(|z| z.score(member)) {
+                        Some(score) => RedisResponse::BulkString(format_score(score).into_bytes()),
+                        None => RedisResponse::Nil,
+                    }
+                    .to_vec()
+                }
+                Some(_) => protocol::WRONGTYPE.to_vec(),
             }
-            Command::MSetnx(items) => {
-                // Either set all or not set any at all if any already exist
-                let mut storage = lock_then_release(storage);
-                match items.iter().all(|(key, _)| !storage.contains(key)) {
-                    // None of the keys already exist in the storage
-                    true => {
-                        items.iter().for_each(|(k, v)| storage.write(k, v));
-                        b":1\r\n".to_vec()
+        }
+        Command::ZMScore(k, members) => {
+            let mut storage = lock_then_release(storage);
+
+            match storage.type_of(k) {
+                Some(DataType::SortedSet) | None => {
+                    let sorted_set = storage.zread(k);
+                    let scores = members
+                        .iter()
+                        .map(|member| match sorted_set.and_then(|z| z.score(member)) {
+                            Some(score) => {
+                                RedisResponse::BulkString(format_score(score).into_bytes())
+                            }
+                            None => RedisResponse::Nil,
+                        })
+                        .collect();
+
+                    RedisResponse::Array(scores).to_vec()
+                }
+                Some(_) => protocol::WRONGTYPE.to_vec(),
+            }
+        }
+        Command::ZRem(k, members) => {
+            let mut storage = lock_then_release(storage);
+
+            match storage.type_of(k) {
+                Some(DataType::SortedSet) | None => {
+                    let removed: u32 = members.iter().map(|m| storage.zrem(k, m)).sum();
+                    RedisResponse::Integer(removed as i64).to_vec()
+                }
+                Some(_) => protocol::WRONGTYPE.to_vec(),
+            }
+        }
+        Command::ZIncrBy(k, increment, member) => {
+            let mut storage = lock_then_release(storage);
+
+            match storage.type_of(k) {
+                Some(DataType::SortedSet) | None => {
+                    let score = storage
+                        .zread(k)
+                        .and_then(|sorted_set| sorted_set.score(member))
+                        .unwrap_or(0.0)
+                        + increment;
+
+                    // e.g. adding -inf to a score of +inf
+                    if score.is_nan() {
+                        RedisResponse::Error(
+                            "ERR resulting score is not a number (NaN)".to_string(),
+                        )
+                        .to_vec()
+                    } else {
+                        storage.zadd(k, score, member);
+                        RedisResponse::BulkString(format_score(score).into_bytes()).to_vec()
                     }
-                    // Some key exists, don't write any of the keys
-                    false => b":0\r\n".to_vec(),
                 }
+                Some(_) => protocol::WRONGTYPE.to_vec(),
             }
-            Command::Expire(k, expiry) | Command::PExpire(k, expiry) => {
-                let v = lock_then_release(storage).expire(k.as_slice(), *expiry);
-                format!(":{}\r\n", v).as_bytes().to_vec()
+        }
+        Command::ZCard(k) => {
+            let mut storage = lock_then_release(storage);
+
+            match storage.type_of(k) {
+                Some(DataType::SortedSet) | None => {
+                    let len = storage.zread(k).map_or(0, |sorted_set| sorted_set.len());
+                    RedisResponse::Integer(len as i64).to_vec()
+                }
+                Some(_) => protocol::WRONGTYPE.to_vec(),
             }
-            Command::Get(k) => match lock_then_release(storage).read(k.as_slice()) {
-                Some(value) => {
-                    let res = format!("+{}\r\n", std::str::from_utf8(value).unwrap());
-                    res.as_bytes().to_vec()
+        }
+        Command::ZRandMember(k, count, with_scores) => {
+            let mut storage = lock_then_release(storage);
+
+            match storage.type_of(k) {
+                Some(DataType::SortedSet) | None => {
+                    let members = storage.zread(k).map_or(vec![], |sorted_set| {
+                        sorted_set.random_members(count.unwrap_or(1))
+                    });
+
+                    match count {
+                        Some(_) => sorted_set_members_response(members, *with_scores),
+                        None => match members.first() {
+                            Some((member, _)) => RedisResponse::BulkString(member.to_vec()),
+                            None => RedisResponse::Nil,
+                        }
+                        .to_vec(),
+                    }
                 }
-                None => protocol::NIL.to_vec(),
-            },
-            Command::GetSet(k, v) => {
-                let mut storage = lock_then_release(storage);
+                Some(_) => protocol::WRONGTYPE.to_vec(),
+            }
+        }
+        Command::ZScan(k, cursor, pattern, count) => {
+            let mut storage = lock_then_release(storage);
+
+            match storage.type_of(k) {
+                Some(DataType::SortedSet) | None => {
+                    let (next_cursor, members) = match storage.zread(k) {
+                        Some(sorted_set) => scan(
+                            sorted_set.scores.iter().map(|(m, s)| (&m[..], (m, *s))),
+                            *cursor,
+                            pattern.as_deref(),
+                            *count,
+                        ),
+                        None => (0, vec![]),
+                    };
+
+                    // each member is followed by its score
+                    scan_response(
+                        next_cursor,
+                        members
+                            .into_iter()
+                            .flat_map(|(m, score)| {
+                                vec![
+                                    RedisResponse::BulkString(m.clone()),
+                                    RedisResponse::BulkString(format_score(score).into_bytes()),
+                                ]
+                            })
+                            .collect(),
+                    )
+                }
+                Some(_) => protocol::WRONGTYPE.to_vec(),
+            }
+        }
+        Command::ZCount(k, by) => {
+            let mut storage = lock_then_release(storage);
 
-                let response = match storage.read(k.as_slice()) {
-                    Some(value) => {
-                        let res = format!("+{}\r\n", std::str::from_utf8(value).unwrap());
-                        res.as_bytes().to_vec()
+            match storage.type_of(k) {
+                Some(DataType::SortedSet) | None => {
+                    let count = storage
+                        .zread(k)
+                        .map_or(0, |sorted_set| sorted_set.count(by));
+                    RedisResponse::Integer(count as i64).to_vec()
+                }
+                Some(_) => protocol::WRONGTYPE.to_vec(),
+            }
+        }
+        Command::ZRank(k, member, rev, with_score) => {
+            let mut storage = lock_then_release(storage);
+
+            match storage.type_of(k) {
+                Some(DataType::SortedSet) | None => {
+                    let rank = storage.zread(k).and_then(|sorted_set| {
+                        let rank = sorted_set.rank(member, *rev)?;
+                        Some((rank, sorted_set.score(member)?))
+                    });
+
+                    match rank {
+                        Some((rank, score)) if *with_score => RedisResponse::Array(vec![
+                            RedisResponse::Integer(rank as i64),
+                            RedisResponse::BulkString(format_score(score).into_bytes()),
+                        ]),
+                        Some((rank, _)) => RedisResponse::Integer(rank as i64),
+                        None => RedisResponse::Nil,
                     }
-                    None => protocol::NIL.to_vec(),
-                };
-                storage.write(k.as_slice(), v.as_slice());
-                response
+                    .to_vec()
+                }
+                Some(_) => protocol::WRONGTYPE.to_vec(),
             }
-            Command::MGet(keys) => {
-                // Draft, slow ?
-                // better to add a response formatter module?
+        }
+        Command::ZPop(k, count, max) => {
+            let mut storage = lock_then_release(storage);
+
+            match storage.type_of(k) {
+                Some(DataType::SortedSet) | None => {
+                    let members = storage.zpop(k, count.unwrap_or(1) as usize, *max);
+                    let members = members.iter().map(|(m, score)| (&m[..], *score)).collect();
+                    sorted_set_members_response(members, true)
+                }
+                Some(_) => protocol::WRONGTYPE.to_vec(),
+            }
+        }
+        Command::BZPop(keys, timeout, max) => {
+            let response = block_on(*timeout, || {
                 let mut storage = lock_then_release(storage);
-                let mut final_response = format!("*{}\r\n", keys.len());
 
-                for key in keys {
-                    let response_line = match storage.read(key.as_slice()) {
-                        Some(value) => {
-                            format!("+{}\r\n", std::str::from_utf8(value).unwrap())
+                for k in keys {
+                    match storage.type_of(k) {
+                        Some(DataType::SortedSet) => {}
+                        Some(_) => return Some(protocol::WRONGTYPE.to_vec()),
+                        None => continue,
+                    }
+
+                    if let Some((member, score)) = storage.zpop(k, 1, *max).pop() {
+                        return Some(
+                            RedisResponse::Array(vec![
+                                RedisResponse::BulkString(k.clone()),
+                                RedisResponse::BulkString(member),
+                                RedisResponse::BulkString(format_score(score).into_bytes()),
+                            ])
+                            .to_vec(),
+                        );
+                    }
+                }
+
+                None
+            });
+
+            // timed out
+            response.unwrap_or_else(|| RedisResponse::Nil.to_vec())
+        }
+        Command::ZUnion(keys, weights, aggregate, with_scores)
+        | Command::ZInter(keys, weights, aggregate, with_scores) => {
+            let mut storage = lock_then_release(storage);
+
+            match read_sorted_sets(&mut *storage, keys) {
+                Some(sorted_sets) => {
+                    let members = match command {
+                        Command::ZUnion(..) => {
+                            redis_sorted_set::union(&sorted_sets, weights, *aggregate)
                         }
-                        None => "$-1\r\n".to_string(),
+                        _ => redis_sorted_set::intersection(&sorted_sets, weights, *aggregate),
                     };
-                    final_response.push_str(response_line.as_str());
+
+                    let sorted_set = RedisSortedSet::from_scores(members, None);
+                    sorted_set_members_response(sorted_set.iter().collect(), *with_scores)
                 }
-                final_response.as_bytes().to_vec()
+                None => protocol::WRONGTYPE.to_vec(),
             }
-            Command::Del(k) => {
-                let total_del = lock_then_release(storage).remove(k.as_slice());
-                format!(":{}\r\n", total_del).as_bytes().to_vec()
+        }
+        Command::ZDiff(keys, with_scores) => {
+            let mut storage = lock_then_release(storage);
+
+            match read_sorted_sets(&mut *storage, keys) {
+                Some(sorted_sets) => {
+                    let members = redis_sorted_set::difference(&sorted_sets);
+                    let sorted_set = RedisSortedSet::from_scores(members, None);
+                    sorted_set_members_response(sorted_set.iter().collect(), *with_scores)
+                }
+                None => protocol::WRONGTYPE.to_vec(),
             }
-            Command::Incr(k) => {
-                let mut storage = lock_then_release(storage);
+        }
+        Command::ZUnionStore(destination, keys, weights, aggregate)
+        | Command::ZInterStore(destination, keys, weights, aggregate) => {
+            let mut storage = lock_then_release(storage);
+
+            match read_sorted_sets(&mut *storage, keys) {
+                Some(sorted_sets) => {
+                    let members = match command {
+                        Command::ZUnionStore(..) => {
+                            redis_sorted_set::union(&sorted_sets, weights, *aggregate)
+                        }
+                        _ => redis_sorted_set::intersection(&sorted_sets, weights, *aggregate),
+                    };
+
+                    let cardinality = members.len();
+                    storage.zwrite(destination, members);
+                    RedisResponse::Integer(cardinality as i64).to_vec()
+                }
+                None => protocol::WRONGTYPE.to_vec(),
+            }
+        }
+        Command::ZDiffStore(destination, keys) => {
+            let mut storage = lock_then_release(storage);
+
+            match read_sorted_sets(&mut *storage, keys) {
+                Some(sorted_sets) => {
+                    let members = redis_sorted_set::difference(&sorted_sets);
+                    let cardinality = members.len();
+                    storage.zwrite(destination, members);
+                    RedisResponse::Integer(cardinality as i64).to_vec()
+                }
+                None => protocol::WRONGTYPE.to_vec(),
+            }
+        }
+        Command::ZRange(k, query, with_scores) => {
+            let mut storage = lock_then_release(storage);
 
-                match storage.read(k.as_slice()) {
-                    Some(value) => {
-                        if let Ok(mut int_val) = std::str::from_utf8(value).unwrap().parse::<i64>()
-                        {
-                            int_val += 1;
+            match storage.type_of(k) {
+                Some(DataType::SortedSet) | None => {
+                    let members = storage
+                        .zread(k)
+                        .map_or(vec![], |sorted_set| sorted_set.range(query));
+                    sorted_set_members_response(members, *with_scores)
+                }
+                Some(_) => protocol::WRONGTYPE.to_vec(),
+            }
+        }
+        Command::ZRangeStore(destination, source, query) => {
+            let mut storage = lock_then_release(storage);
+
+            match storage.type_of(source) {
+                Some(DataType::SortedSet) | None => {
+                    let members: Scores = storage.zread(source).map_or(Scores::new(), |z| {
+                        z.range(query)
+                            .into_iter()
+                            .map(|(member, score)| (member.to_vec(), score))
+                            .collect()
+                    });
+
+                    let cardinality = members.len();
+                    storage.zwrite(destination, members);
+                    RedisResponse::Integer(cardinality as i64).to_vec()
+                }
+                Some(_) => protocol::WRONGTYPE.to_vec(),
+            }
+        }
+        Command::ZRemRange(k, by) => {
+            let mut storage = lock_then_release(storage);
+
+            match storage.type_of(k) {
+                Some(DataType::SortedSet) | None => {
+                    let query = RangeQuery {
+                        by: by.clone(),
+                        rev: false,
+                        limit: None,
+                    };
+
+                    let members = storage.zread(k).map_or(vec![], |sorted_set| {
+                        sorted_set
+                            .range(&query)
+                            .into_iter()
+                            .map(|(member, _)| member.to_vec())
+                            .collect()
+                    });
+
+                    let removed: u32 = members.iter().map(|m| storage.zrem(k, m)).sum();
+                    RedisResponse::Integer(removed as i64).to_vec()
+                }
+                Some(_) => protocol::WRONGTYPE.to_vec(),
+            }
+        }
+        Command::Del(k) => {
+            let total_del = lock_then_release(storage).remove(k.as_slice());
+            format!(":{}\r\n", total_del).as_bytes().to_vec()
+        }
+        Command::Incr(k) => {
+            let mut storage = lock_then_release(storage);
+
+            if !matches!(storage.type_of(k), Some(DataType::String) | None) {
+                return protocol::WRONGTYPE.to_vec();
+            }
+            match storage.read(k.as_slice()) {
+                Some(value) => {
+                    // the string may hold bytes that are not UTF-8, as SETBIT
+                    // leaves it
+                    let incremented = std::str::from_utf8(&value)
+                        .ok()
+                        .and_then(|value| value.parse::<i64>().ok())
+                        .and_then(|value| value.checked_add(1));
+                    match incremented {
+                        Some(int_val) => {
                             let new_value = int_val.to_string().into_bytes();
                             storage.write(k.as_slice(), new_value.as_slice());
 
                             format!(":{}\r\n", int_val).as_bytes().to_vec()
-                        } else {
-                            b"-WRONGTYPE Operation against a key holding the wrong kind of value}}"
-                                .to_vec()
                         }
+                        None => RedisResponse::Error(
+                            "ERR value is not an integer or out of range".to_string(),
+                        )
+                        .to_vec(),
                     }
-                    None => {
-                        let val = "1";
-                        storage.write(k, val.as_bytes());
-                        format!(":{}\r\n", val).as_bytes().to_vec()
+                }
+                None => {
+                    let val = "1";
+                    storage.write(k, val.as_bytes());
+                    format!(":{}\r\n", val).as_bytes().to_vec()
+                }
+            }
+        }
+        Command::SetBit(k, offset, bit) => {
+            let mut storage = lock_then_release(storage);
+
+            match storage.type_of(k) {
+                Some(DataType::String) | None => {
+                    let previous = storage.setbit(k, *offset, *bit);
+                    RedisResponse::Integer(previous as i64).to_vec()
+                }
+                Some(_) => protocol::WRONGTYPE.to_vec(),
+            }
+        }
+        Command::GetBit(k, offset) => {
+            let mut storage = lock_then_release(storage);
+
+            match storage.type_of(k) {
+                Some(DataType::String) | None => {
+                    let bit = storage.read(k).map_or(0, |value| get_bit(&value, *offset));
+                    RedisResponse::Integer(bit as i64).to_vec()
+                }
+                Some(_) => protocol::WRONGTYPE.to_vec(),
+            }
+        }
+        Command::BitCount(k, range, unit) => {
+            let mut storage = lock_then_release(storage);
+
+            match storage.type_of(k) {
+                Some(DataType::String) | None => {
+                    let count = storage
+                        .read(k)
+                        .map_or(0, |value| bit_count(&value, *range, *unit));
+                    RedisResponse::Integer(count as i64).to_vec()
+                }
+                Some(_) => protocol::WRONGTYPE.to_vec(),
+            }
+        }
+        Command::BitPos(k, bit, start, end, unit) => {
+            let mut storage = lock_then_release(storage);
+
+            match storage.type_of(k) {
+                Some(DataType::String) | None => {
+                    let position = match storage.read(k) {
+                        Some(value) => bit_position(&value, *bit, *start, *end, *unit),
+                        // a missing key is an empty bitmap padded with zeros
+                        None if *bit => -1,
+                        None => 0,
+                    };
+                    RedisResponse::Integer(position).to_vec()
+                }
+                Some(_) => protocol::WRONGTYPE.to_vec(),
+            }
+        }
+        Command::BitOp(operation, destination, keys) => {
+            let mut storage = lock_then_release(storage);
+
+            let is_wrong_type = keys
+                .iter()
+                .any(|key| !matches!(storage.type_of(key), Some(DataType::String) | None));
+
+            match is_wrong_type {
+                false => {
+                    // a missing key is an empty string
+                    let values = keys
+                        .iter()
+                        .map(|key| storage.read(key).map_or(vec![], |value| value.to_vec()))
+                        .collect::<Vec<_>>();
+                    let values = values.iter().map(|value| &value[..]).collect::<Vec<_>>();
+
+                    let result = bit_operation(*operation, &values);
+                    // an empty string does not exist
+                    if result.is_empty() {
+                        storage.remove(destination);
+                    } else {
+                        storage.write(destination, &result);
                     }
+
+                    RedisResponse::Integer(result.len() as i64).to_vec()
                 }
+                true => protocol::WRONGTYPE.to_vec(),
             }
-            Command::Exists(k) => {
-                let exists = lock_then_release(storage).contains(k);
-                let exists: u32 = match exists {
-                    true => 1,
-                    false => 0,
-                };
-                format!(":{}\r\n", exists).as_bytes().to_vec()
+        }
+        Command::BitField(k, operations) => {
+            let mut storage = lock_then_release(storage);
+
+            match storage.type_of(k) {
+                Some(DataType::String) | None => {
+                    let results = storage
+                        .bitfield(k, operations)
+                        .into_iter()
+                        .map(|result| match result {
+                            Some(result) => RedisResponse::Integer(result),
+                            None => RedisResponse::Nil,
+                        })
+                        .collect();
+
+                    RedisResponse::Array(results).to_vec()
+                }
+                Some(_) => protocol::WRONGTYPE.to_vec(),
             }
-            Command::Info => protocol::EMPTY_LIST.to_vec(), // TODO change with some real info?
-            Command::Ping => protocol::PONG.to_vec(),
-            Command::Quit => protocol::OK.to_vec(),
-        },
-        Err(err) => format!("-ERR {}\r\n", err).as_bytes().to_vec(),
-    };
+        }
+        Command::GeoPos(k, members) => {
+            let mut storage = lock_then_release(storage);
+
+            match storage.type_of(k) {
+                Some(DataType::SortedSet) | None => {
+                    let sorted_set = storage.zread(k);
+                    let positions = members
+                        .iter()
+                        .map(|member| match sorted_set.and_then(|z| z.score(member)) {
+                            Some(score) => {
+                                let (longitude, latitude) = geo::decode(score);
+                                RedisResponse::Array(vec![
+                                    RedisResponse::BulkString(format_score(longitude).into_bytes()),
+                                    RedisResponse::BulkString(format_score(latitude).into_bytes()),
+                                ])
+                            }
+                            None => RedisResponse::Nil,
+                        })
+                        .collect();
+
+                    RedisResponse::Array(positions).to_vec()
+                }
+                Some(_) => protocol::WRONGTYPE.to_vec(),
+            }
+        }
+        Command::GeoDist(k, member1, member2, unit) => {
+            let mut storage = lock_then_release(storage);
+
+            match storage.type_of(k) {
+                Some(DataType::SortedSet) | None => {
+                    let sorted_set = storage.zread(k);
+                    let position = |member| sorted_set.and_then(|z| z.score(member));
+
+                    match (position(member1), position(member2)) {
+                        (Some(score1), Some(score2)) => {
+                            let meters = geo::distance(geo::decode(score1), geo::decode(score2));
+                            let distance = format!("{:.4}", unit.from_meters(meters));
+                            RedisResponse::BulkString(distance.into_bytes())
+                        }
+                        _ => RedisResponse::Nil,
+                    }
+                    .to_vec()
+                }
+                Some(_) => protocol::WRONGTYPE.to_vec(),
+            }
+        }
+        Command::GeoSearch(k, query, reply) => {
+            let mut storage = lock_then_release(storage);
+
+            match geo_search(&mut *storage, k, query) {
+                Ok(found) => {
+                    let members = found
+                        .into_iter()
+                        .map(|(member, score, distance)| {
+                            if *reply == GeoReplyOptions::default() {
+                                return RedisResponse::BulkString(member);
+                            }
+
+                            let mut item = vec![RedisResponse::BulkString(member)];
+                            if reply.with_dist {
+                                let distance = format!("{:.4}", query.unit.from_meters(distance));
+                                item.push(RedisResponse::BulkString(distance.into_bytes()));
+                            }
+                            if reply.with_hash {
+                                item.push(RedisResponse::Integer(score as i64));
+                            }
+                            if reply.with_coord {
+                                let (longitude, latitude) = geo::decode(score);
+                                item.push(RedisResponse::Array(vec![
+                                    RedisResponse::BulkString(format_score(longitude).into_bytes()),
+                                    RedisResponse::BulkString(format_score(latitude).into_bytes()),
+                                ]));
+                            }
+                            RedisResponse::Array(item)
+                        })
+                        .collect();
+
+                    RedisResponse::Array(members).to_vec()
+                }
+                Err(response) => response,
+            }
+        }
+        Command::GeoSearchStore(destination, source, query, store_dist) => {
+            let mut storage = lock_then_release(storage);
+
+            match geo_search(&mut *storage, source, query) {
+                Ok(found) => {
+                    let members: Scores = found
+                        .into_iter()
+                        .map(|(member, score, distance)| match store_dist {
+                            true => (member, query.unit.from_meters(distance)),
+                            false => (member, score),
+                        })
+                        .collect();
+
+                    let cardinality = members.len();
+                    storage.zwrite(destination, members);
+                    RedisResponse::Integer(cardinality as i64).to_vec()
+                }
+                Err(response) => response,
+            }
+        }
+        Command::XAdd(k, no_mkstream, trim, id, fields) => {
+            let mut storage = lock_then_release(storage);
+
+            match storage.type_of(k) {
+                None if *no_mkstream => RedisResponse::Nil.to_vec(),
+                Some(DataType::Stream) | None => match storage.xadd(k, *id, fields.clone()) {
+                    Some(id) => {
+                        if let (Some(trim), Some(stream)) = (trim, storage.xread_mut(k)) {
+                            stream.trim(trim);
+                        }
+                        RedisResponse::BulkString(id.to_string().into_bytes()).to_vec()
+                    }
+                    None => RedisResponse::Error(
+                        "ERR The ID specified in XADD is equal or smaller than the target stream top item"
+                            .to_string(),
+                    )
+                    .to_vec(),
+                },
+                Some(_) => protocol::WRONGTYPE.to_vec(),
+            }
+        }
+        Command::XTrim(k, trim) => {
+            let mut storage = lock_then_release(storage);
+
+            match storage.type_of(k) {
+                Some(DataType::Stream) | None => {
+                    let evicted = storage.xread_mut(k).map_or(0, |stream| stream.trim(trim));
+                    RedisResponse::Integer(evicted as i64).to_vec()
+                }
+                Some(_) => protocol::WRONGTYPE.to_vec(),
+            }
+        }
+        Command::XDel(k, ids) => {
+            let mut storage = lock_then_release(storage);
+
+            match storage.type_of(k) {
+                Some(DataType::Stream) | None => {
+                    let deleted = storage.xread_mut(k).map_or(0, |stream| stream.delete(ids));
+                    RedisResponse::Integer(deleted as i64).to_vec()
+                }
+                Some(_) => protocol::WRONGTYPE.to_vec(),
+            }
+        }
+        Command::XLen(k) => {
+            let mut storage = lock_then_release(storage);
+
+            match storage.type_of(k) {
+                Some(DataType::Stream) | None => {
+                    let len = storage.xread(k).map_or(0, |stream| stream.len());
+                    RedisResponse::Integer(len as i64).to_vec()
+                }
+                Some(_) => protocol::WRONGTYPE.to_vec(),
+            }
+        }
+        Command::XRange(k, start, end, count, rev) => {
+            let mut storage = lock_then_release(storage);
+
+            match storage.type_of(k) {
+                Some(DataType::Stream) | None => {
+                    let entries = storage.xread(k).map_or(vec![], |stream| {
+                        stream_entries_response(stream.range(*start, *end, *count, *rev))
+                    });
+
+                    RedisResponse::Array(entries).to_vec()
+                }
+                Some(_) => protocol::WRONGTYPE.to_vec(),
+            }
+        }
+        Command::XRead(streams, count, block) => {
+            // `$` stands for the last id of the stream when the command is run
+            let streams = {
+                let mut storage = lock_then_release(storage);
+                streams
+                    .iter()
+                    .map(|(k, id)| match id {
+                        XReadId::Last => {
+                            let last_id = storage.xread(k).map(|stream| stream.last_id);
+                            (k, last_id.unwrap_or(StreamId::MIN))
+                        }
+                        XReadId::After(id) => (k, *id),
+                    })
+                    .collect::<Vec<_>>()
+            };
+
+            let attempt = || {
+                let mut storage = lock_then_release(storage);
+
+                let mut found = vec![];
+                for (k, id) in &streams {
+                    match storage.type_of(k) {
+                        Some(DataType::Stream) => {}
+                        Some(_) => return Some(protocol::WRONGTYPE.to_vec()),
+                        None => continue,
+                    }
+
+                    let entries = storage.xread(k).map_or(vec![], |stream| {
+                        stream_entries_response(stream.after(*id, *count))
+                    });
+                    if !entries.is_empty() {
+                        found.push(RedisResponse::Array(vec![
+                            RedisResponse::BulkString(k.to_vec()),
+                            RedisResponse::Array(entries),
+                        ]));
+                    }
+                }
+
+                match found.is_empty() {
+                    true => None,
+                    false => Some(RedisResponse::Array(found).to_vec()),
+                }
+            };
+
+            let response = match block {
+                Some(timeout) => block_on(*timeout, attempt),
+                None => attempt(),
+            };
+
+            // nothing to read
+            response.unwrap_or_else(|| RedisResponse::Nil.to_vec())
+        }
+        Command::XGroupCreate(k, group, id, mkstream) => {
+            let mut storage = lock_then_release(storage);
+
+            match storage.type_of(k) {
+                None if !*mkstream => group_requires_key(),
+                Some(DataType::Stream) | None => {
+                    // MKSTREAM creates an empty stream if there is none
+                    storage.xcreate(k);
+
+                    let stream = storage.xread_mut(k).unwrap();
+                    let last_delivered_id = match id {
+                        XReadId::Last => stream.last_id,
+                        XReadId::After(id) => *id,
+                    };
+
+                    match stream.groups.contains_key(group) {
+                        true => RedisResponse::Error(
+                            "BUSYGROUP Consumer Group name already exists".to_string(),
+                        )
+                        .to_vec(),
+                        false => {
+                            let group_value = ConsumerGroup::new(last_delivered_id);
+                            stream.groups.insert(group.clone(), group_value);
+                            protocol::OK.to_vec()
+                        }
+                    }
+                }
+                Some(_) => protocol::WRONGTYPE.to_vec(),
+            }
+        }
+        Command::XGroupSetId(k, group, id) => {
+            let mut storage = lock_then_release(storage);
+
+            let last_id = storage.xread(k).map(|stream| stream.last_id);
+            match consumer_group(&mut *storage, k, group) {
+                Ok(consumer_group) => {
+                    consumer_group.last_delivered_id = match id {
+                        XReadId::Last => last_id.unwrap_or(StreamId::MIN),
+                        XReadId::After(id) => *id,
+                    };
+                    protocol::OK.to_vec()
+                }
+                Err(response) => response,
+            }
+        }
+        Command::XGroupDestroy(k, group) => {
+            let mut storage = lock_then_release(storage);
+
+            match storage.type_of(k) {
+                Some(DataType::Stream) => {
+                    let stream = storage.xread_mut(k).unwrap();
+                    let destroyed = stream.groups.remove(group).is_some();
+                    RedisResponse::Integer(destroyed as i64).to_vec()
+                }
+                None => group_requires_key(),
+                Some(_) => protocol::WRONGTYPE.to_vec(),
+            }
+        }
+        Command::XGroupCreateConsumer(k, group, consumer) => {
+            let mut storage = lock_then_release(storage);
+
+            match consumer_group(&mut *storage, k, group) {
+                Ok(consumer_group) => {
+                    let created = consumer_group.create_consumer(consumer);
+                    RedisResponse::Integer(created as i64).to_vec()
+                }
+                Err(response) => response,
+            }
+        }
+        Command::XGroupDelConsumer(k, group, consumer) => {
+            let mut storage = lock_then_release(storage);
+
+            match consumer_group(&mut *storage, k, group) {
+                Ok(consumer_group) => {
+                    let pending = consumer_group.delete_consumer(consumer).unwrap_or(0);
+                    RedisResponse::Integer(pending as i64).to_vec()
+                }
+                Err(response) => response,
+            }
+        }
+        Command::XReadGroup(group, consumer, streams, count, block, no_ack) => {
+            let attempt = || {
+                let mut storage = lock_then_release(storage);
+
+                for (k, _) in streams {
+                    match storage.type_of(k) {
+                        Some(DataType::Stream) | None => {}
+                        Some(_) => return Some(protocol::WRONGTYPE.to_vec()),
+                    }
+
+                    let has_group = matches!(
+                        storage.xread(k),
+                        Some(stream) if stream.groups.contains_key(group)
+                    );
+                    if !has_group {
+                        let error = format!(
+                            "NOGROUP No such key '{}' or consumer group '{}' in XREADGROUP with GROUP option",
+                            String::from_utf8_lossy(k),
+                            String::from_utf8_lossy(group)
+                        );
+                        return Some(RedisResponse::Error(error).to_vec());
+                    }
+                }
+
+                let mut found = vec![];
+                for (k, id) in streams {
+                    let stream = storage.xread_mut(k).unwrap();
+                    let entries = stream
+                        .read_group(group, consumer, *id, *count, *no_ack)
+                        .unwrap_or_default();
+
+                    // only the new entries are waited for
+                    if *id == XReadGroupId::New && entries.is_empty() {
+                        continue;
+                    }
+
+                    let entries = entries
+                        .iter()
+                        .map(|(id, fields)| stream_entry_response(*id, fields.as_ref()))
+                        .collect();
+                    found.push(RedisResponse::Array(vec![
+                        RedisResponse::BulkString(k.to_vec()),
+                        RedisResponse::Array(entries),
+                    ]));
+                }
+
+                match found.is_empty() {
+                    true => None,
+                    false => Some(RedisResponse::Array(found).to_vec()),
+                }
+            };
+
+            // reading the pending entries of the consumer never blocks
+            let only_new = streams.iter().all(|(_, id)| *id == XReadGroupId::New);
+            let response = match block {
+                Some(timeout) if only_new => block_on(*timeout, attempt),
+                _ => attempt(),
+            };
+
+            // nothing to read
+            response.unwrap_or_else(|| RedisResponse::Nil.to_vec())
+        }
+        Command::XAck(k, group, ids) => {
+            let mut storage = lock_then_release(storage);
+
+            match storage.type_of(k) {
+                Some(DataType::Stream) => {
+                    let acknowledged = storage
+                        .xread_mut(k)
+                        .and_then(|stream| stream.groups.get_mut(group))
+                        .map_or(0, |group| {
+                            ids.iter().filter(|id| group.acknowledge(**id)).count()
+                        });
+
+                    RedisResponse::Integer(acknowledged as i64).to_vec()
+                }
+                None => RedisResponse::Integer(0).to_vec(),
+                Some(_) => protocol::WRONGTYPE.to_vec(),
+            }
+        }
+        Command::XPending(k, group, query) => {
+            let mut storage = lock_then_release(storage);
+
+            match stream_with_group(&mut *storage, k, group) {
+                Ok(stream) => {
+                    let group = &stream.groups[group];
+                    match query {
+                        Some(query) => {
+                            let entries = group
+                                .pending_range(query)
+                                .into_iter()
+                                .map(|(id, pending)| {
+                                    RedisResponse::Array(vec![
+                                        RedisResponse::BulkString(id.to_string().into_bytes()),
+                                        RedisResponse::BulkString(pending.consumer.clone()),
+                                        RedisResponse::Integer(pending.idle() as i64),
+                                        RedisResponse::Integer(pending.delivery_count as i64),
+                                    ])
+                                })
+                                .collect();
+
+                            RedisResponse::Array(entries).to_vec()
+                        }
+                        None => pending_summary_response(group).to_vec(),
+                    }
+                }
+                Err(response) => response,
+            }
+        }
+        Command::XClaim(k, group, consumer, min_idle, ids, options) => {
+            let mut storage = lock_then_release(storage);
+
+            match stream_with_group(&mut *storage, k, group) {
+                Ok(stream) => {
+                    let claimed = stream
+                        .claim(group, consumer, *min_idle, ids, options)
+                        .unwrap_or_default();
+
+                    claimed_response(stream, claimed, options.just_id).to_vec()
+                }
+                Err(response) => response,
+            }
+        }
+        Command::XAutoClaim(k, group, consumer, min_idle, start, count, just_id) => {
+            let mut storage = lock_then_release(storage);
+
+            match stream_with_group(&mut *storage, k, group) {
+                Ok(stream) => {
+                    let (next, claimed, deleted) = stream
+                        .auto_claim(group, consumer, *min_idle, *start, *count, *just_id)
+                        .unwrap_or_default();
+                    let deleted = deleted
+                        .iter()
+                        .map(|id| RedisResponse::BulkString(id.to_string().into_bytes()))
+                        .collect();
+
+                    RedisResponse::Array(vec![
+                        RedisResponse::BulkString(next.to_string().into_bytes()),
+                        claimed_response(stream, claimed, *just_id),
+                        RedisResponse::Array(deleted),
+                    ])
+                    .to_vec()
+                }
+                Err(response) => response,
+            }
+        }
+        Command::XInfoStream(k, full) => {
+            let mut storage = lock_then_release(storage);
+
+            match existing_stream(&mut *storage, k) {
+                Ok(stream) => match full {
+                    Some(count) => stream_full_info_response(stream, *count).to_vec(),
+                    None => stream_info_response(stream).to_vec(),
+                },
+                Err(response) => response,
+            }
+        }
+        Command::XInfoGroups(k) => {
+            let mut storage = lock_then_release(storage);
+
+            match existing_stream(&mut *storage, k) {
+                Ok(stream) => {
+                    let groups = stream
+                        .groups
+                        .iter()
+                        .map(|(name, group)| {
+                            info_response(vec![
+                                ("name", RedisResponse::BulkString(name.clone())),
+                                (
+                                    "consumers",
+                                    RedisResponse::Integer(group.consumers.len() as i64),
+                                ),
+                                (
+                                    "pending",
+                                    RedisResponse::Integer(group.pending.len() as i64),
+                                ),
+                                ("last-delivered-id", id_response(group.last_delivered_id)),
+                                ("lag", RedisResponse::Integer(stream.lag(group) as i64)),
+                            ])
+                        })
+                        .collect();
+
+                    RedisResponse::Array(groups).to_vec()
+                }
+                Err(response) => response,
+            }
+        }
+        Command::XInfoConsumers(k, group) => {
+            let mut storage = lock_then_release(storage);
+
+            match existing_stream(&mut *storage, k).err() {
+                Some(response) => response,
+                None => match consumer_group(&mut *storage, k, group) {
+                    Ok(group) => {
+                        let now = now_millis();
+                        let consumers = group
+                            .consumers
+                            .iter()
+                            .map(|(name, consumer)| {
+                                let idle = now.saturating_sub(consumer.seen_time);
+                                info_response(vec![
+                                    ("name", RedisResponse::BulkString(name.clone())),
+                                    (
+                                        "pending",
+                                        RedisResponse::Integer(consumer.pending.len() as i64),
+                                    ),
+                                    ("idle", RedisResponse::Integer(idle as i64)),
+                                ])
+                            })
+                            .collect();
+
+                        RedisResponse::Array(consumers).to_vec()
+                    }
+                    Err(response) => response,
+                },
+            }
+        }
+        Command::Lcs(k1, k2, options) => {
+            let mut storage = lock_then_release(storage);
+
+            let is_wrong_type = [k1, k2]
+                .iter()
+                .any(|key| !matches!(storage.type_of(key), Some(DataType::String) | None));
+
+            match is_wrong_type {
+                false => {
+                    // a missing key is an empty string
+                    let a = storage.read(k1).map_or(vec![], |value| value.to_vec());
+                    let b = storage.read(k2).map_or(vec![], |value| value.to_vec());
+                    let (subsequence, matches) = lcs(&a, &b);
+
+                    if options.idx {
+                        let range = |(start, end): (usize, usize)| {
+                            RedisResponse::Array(vec![
+                                RedisResponse::Integer(start as i64),
+                                RedisResponse::Integer(end as i64),
+                            ])
+                        };
+
+                        let matches = matches
+                            .into_iter()
+                            .map(|(a, b)| (a, b, a.1 - a.0 + 1))
+                            .filter(|(_, _, len)| *len >= options.min_match_len)
+                            .map(|(a, b, len)| {
+                                let mut response = vec![range(a), range(b)];
+                                if options.with_match_len {
+                                    response.push(RedisResponse::Integer(len as i64));
+                                }
+                                RedisResponse::Array(response)
+                            })
+                            .collect();
+
+                        RedisResponse::Array(vec![
+                            RedisResponse::BulkString(b"matches".to_vec()),
+                            RedisResponse::Array(matches),
+                            RedisResponse::BulkString(b"len".to_vec()),
+                            RedisResponse::Integer(subsequence.len() as i64),
+                        ])
+                        .to_vec()
+                    } else if options.len {
+                        RedisResponse::Integer(subsequence.len() as i64).to_vec()
+                    } else {
+                        RedisResponse::BulkString(subsequence).to_vec()
+                    }
+                }
+                true => protocol::WRONGTYPE.to_vec(),
+            }
+        }
+        Command::Sort(k, query) => {
+            let mut storage = lock_then_release(storage);
+
+            let elements = match storage.type_of(k) {
+                Some(DataType::Set) => Some(
+                    storage
+                        .sread(k)
+                        .map_or(vec![], |set| set.data.iter().cloned().collect()),
+                ),
+                Some(DataType::SortedSet) => Some(storage.zread(k).map_or(vec![], |sorted_set| {
+                    sorted_set
+                        .iter()
+                        .map(|(member, _)| member.to_vec())
+                        .collect()
+                })),
+                None => Some(vec![]),
+                Some(_) => None,
+            };
+
+            match elements.map(|elements| sort(&mut *storage, query, elements)) {
+                None => protocol::WRONGTYPE.to_vec(),
+                Some(Some(values)) => {
+                    let values = values
+                        .into_iter()
+                        .map(|value| match value {
+                            Some(value) => RedisResponse::BulkString(value),
+                            None => RedisResponse::Nil,
+                        })
+                        .collect();
+                    RedisResponse::Array(values).to_vec()
+                }
+                Some(None) => RedisResponse::Error(
+                    "ERR One or more scores can't be converted into double".to_string(),
+                )
+                .to_vec(),
+            }
+        }
+        Command::PfAdd(k, elements) => {
+            let mut storage = lock_then_release(storage);
+
+            match storage.type_of(k) {
+                Some(DataType::String) | None => match storage.pfadd(k, elements) {
+                    Some(changed) => RedisResponse::Integer(changed as i64).to_vec(),
+                    None => protocol::INVALID_HLL.to_vec(),
+                },
+                Some(_) => protocol::WRONGTYPE.to_vec(),
+            }
+        }
+        Command::PfCount(keys) => {
+            let mut storage = lock_then_release(storage);
+
+            match read_hyperloglogs(&mut *storage, keys) {
+                Ok(hyperloglog) => RedisResponse::Integer(hyperloglog.count() as i64).to_vec(),
+                Err(response) => response,
+            }
+        }
+        Command::PfMerge(destination, keys) => {
+            let mut storage = lock_then_release(storage);
+
+            let destination_type = storage.type_of(destination);
+            match read_hyperloglogs(&mut *storage, keys) {
+                Ok(_) if !matches!(destination_type, Some(DataType::String) | None) => {
+                    protocol::WRONGTYPE.to_vec()
+                }
+                Ok(hyperloglog) => match storage.pfmerge(destination, &hyperloglog) {
+                    Some(_) => protocol::OK.to_vec(),
+                    None => protocol::INVALID_HLL.to_vec(),
+                },
+                Err(response) => response,
+            }
+        }
+        Command::Exists(k) => {
+            let exists = lock_then_release(storage).contains(k);
+            let exists: u32 = match exists {
+                true => 1,
+                false => 0,
+            };
+            format!(":{}\r\n", exists).as_bytes().to_vec()
+        }
+        Command::ConfigGet(patterns) => {
+            let parameters = lock_then_release(&shared.config).get(patterns);
+            RedisResponse::Array(
+                parameters
+                    .into_iter()
+                    .flat_map(|(name, value)| {
+                        vec![
+                            RedisResponse::BulkString(name),
+                            RedisResponse::BulkString(value),
+                        ]
+                    })
+                    .collect(),
+            )
+            .to_vec()
+        }
+        Command::ConfigSet(items) => {
+            let mut config = lock_then_release(&shared.config);
+            match config.set(items) {
+                Ok(()) => {
+                    let keyspace_events = config.value("notify-keyspace-events");
+                    lock_then_release(&shared.pubsub).keyspace_events =
+                        KeyspaceEvents::parse(keyspace_events).unwrap_or_default();
+                    let appendonly = config.value("appendonly") == b"yes";
+                    lock_then_release(&shared.aof).fsync =
+                        aof::Fsync::parse(config.value("appendfsync"));
+                    lock_then_release(&shared.replication).backlog_size =
+                        config.integer("repl-backlog-size") as usize;
+                    drop(config);
+                    match appendonly {
+                        true => aof::enable(&mut lock_then_release(databases), shared),
+                        false => aof::disable(shared),
+                    }
+                    protocol::OK.to_vec()
+                }
+                Err(error) => RedisResponse::Error(error).to_vec(),
+            }
+        }
+        Command::Auth(username, password) => {
+            let acl = lock_then_release(&shared.acl);
+            let is_password_only = username.is_none();
+            let username = match username {
+                Some(username) => String::from_utf8_lossy(username).to_string(),
+                None => "default".to_string(),
+            };
+
+            if is_password_only && !acl.default_user_has_password() {
+                let error = "ERR AUTH <password> called without any password configured for the default user. Are you sure your configuration is correct?";
+                RedisResponse::Error(error.to_string()).to_vec()
+            } else if acl.authenticate(&username, password) {
+                connection.user = Some(username);
+                protocol::OK.to_vec()
+            } else {
+                RedisResponse::Error(
+                    "WRONGPASS invalid username-password pair or user is disabled.".to_string(),
+                )
+                .to_vec()
+            }
+        }
+        Command::Hello(protocol, auth, name) => hello(
+            shared,
+            connection,
+            *protocol,
+            auth.as_ref(),
+            name.as_deref(),
+        ),
+        Command::AclSetUser(username, rules) => {
+            let username = String::from_utf8_lossy(username);
+            match lock_then_release(&shared.acl).set_user(&username, rules) {
+                Ok(()) => protocol::OK.to_vec(),
+                Err(error) => RedisResponse::Error(error).to_vec(),
+            }
+        }
+        Command::AclGetUser(username) => {
+            let username = String::from_utf8_lossy(username);
+            lock_then_release(&shared.acl).get_user(&username).to_vec()
+        }
+        Command::AclDelUser(usernames) => {
+            match lock_then_release(&shared.acl).del_users(usernames) {
+                Ok(deleted) => RedisResponse::Integer(deleted as i64).to_vec(),
+                Err(error) => RedisResponse::Error(error).to_vec(),
+            }
+        }
+        Command::AclList => RedisResponse::Array(
+            lock_then_release(&shared.acl)
+                .list()
+                .into_iter()
+                .map(|user| RedisResponse::BulkString(user.into_bytes()))
+                .collect(),
+        )
+        .to_vec(),
+        Command::AclUsers => RedisResponse::Array(
+            lock_then_release(&shared.acl)
+                .usernames()
+                .into_iter()
+                .map(|user| RedisResponse::BulkString(user.into_bytes()))
+                .collect(),
+        )
+        .to_vec(),
+        Command::AclWhoAmI => match lock_then_release(&shared.acl).username(connection) {
+            Some(username) => RedisResponse::BulkString(username.as_bytes().to_vec()).to_vec(),
+            None => protocol::NIL.to_vec(),
+        },
+        Command::AclCat(None) => RedisResponse::Array(
+            table::CATEGORIES
+                .iter()
+                .map(|category| RedisResponse::BulkString(category.as_bytes().to_vec()))
+                .collect(),
+        )
+        .to_vec(),
+        Command::AclCat(Some(category)) => {
+            let category = String::from_utf8_lossy(category).to_lowercase();
+            match table::CATEGORIES.contains(&category.as_str()) {
+                true => RedisResponse::Array(
+                    table::COMMANDS
+                        .iter()
+                        .filter(|info| info.categories.contains(&category.as_str()))
+                        .map(|info| RedisResponse::BulkString(info.name.as_bytes().to_vec()))
+                        .collect(),
+                )
+                .to_vec(),
+                false => {
+                    RedisResponse::Error(format!("ERR Unknown category '{}'", category)).to_vec()
+                }
+            }
+        }
+        Command::SlowLogGet(count) => {
+            let count = match count {
+                Some(count) if *count >= 0 => Some(*count as usize),
+                Some(_) => None,
+                None => Some(DEFAULT_SLOWLOG_COUNT),
+            };
+            lock_then_release(&shared.slowlog).get(count).to_vec()
+        }
+        Command::SlowLogLen => {
+            RedisResponse::Integer(lock_then_release(&shared.slowlog).count() as i64).to_vec()
+        }
+        Command::SlowLogReset => {
+            lock_then_release(&shared.slowlog).reset();
+            protocol::OK.to_vec()
+        }
+        Command::LatencyHistory(event) => lock_then_release(&shared.latency)
+            .history(&String::from_utf8_lossy(event))
+            .to_vec(),
+        Command::LatencyLatest => lock_then_release(&shared.latency).latest().to_vec(),
+        Command::LatencyReset(events) => {
+            let events = events
+                .iter()
+                .map(|event| String::from_utf8_lossy(event).to_string())
+                .collect::<Vec<_>>();
+            RedisResponse::Integer(lock_then_release(&shared.latency).reset(&events) as i64)
+                .to_vec()
+        }
+        Command::LatencyDoctor => {
+            let threshold = lock_then_release(&shared.config).integer("latency-monitor-threshold");
+            RedisResponse::BulkString(
+                lock_then_release(&shared.latency)
+                    .doctor(threshold)
+                    .into_bytes(),
+            )
+            .to_vec()
+        }
+        Command::ClusterInfo => {
+            RedisResponse::BulkString(lock_then_release(&shared.cluster).info().into_bytes())
+                .to_vec()
+        }
+        Command::ClusterMyId => {
+            RedisResponse::BulkString(lock_then_release(&shared.cluster).myid.clone().into_bytes())
+                .to_vec()
+        }
+        Command::ClusterSlots => {
+            let (ip, port) = cluster_address(shared, connection);
+            lock_then_release(&shared.cluster)
+                .slots(&ip, port)
+                .to_vec_in(connection.protocol)
+        }
+        Command::ClusterShards => {
+            let (ip, port) = cluster_address(shared, connection);
+            let offset = lock_then_release(&shared.replication).offset;
+            lock_then_release(&shared.cluster)
+                .shards(&ip, port, offset)
+                .to_vec_in(connection.protocol)
+        }
+        Command::ClusterNodes => {
+            let (ip, port) = cluster_address(shared, connection);
+            let nodes = lock_then_release(&shared.cluster).nodes(&ip, port);
+            RedisResponse::BulkString(nodes.into_bytes()).to_vec()
+        }
+        Command::ClusterKeySlot(k) => RedisResponse::Integer(cluster::key_slot(k) as i64).to_vec(),
+        Command::ClusterMeet(ip, port) => {
+            let mut cluster = lock_then_release(&shared.cluster);
+            match cluster.enabled {
+                true => {
+                    cluster.meet(&String::from_utf8_lossy(ip), *port);
+                    protocol::OK.to_vec()
+                }
+                false => RedisResponse::Error(cluster::DISABLED_ERROR.to_string()).to_vec(),
+            }
+        }
+        Command::ClusterAddSlots(slots) | Command::ClusterDelSlots(slots) => {
+            let mut cluster = lock_then_release(&shared.cluster);
+            if !cluster.enabled {
+                return RedisResponse::Error(cluster::DISABLED_ERROR.to_string()).to_vec();
+            }
+            let changed = match command {
+                Command::ClusterAddSlots(_) => cluster.add_slots(slots),
+                _ => cluster.del_slots(slots),
+            };
+            match changed {
+                Ok(()) => protocol::OK.to_vec(),
+                Err(error) => RedisResponse::Error(error).to_vec(),
+            }
+        }
+        Command::SentinelMasters => {
+            RedisResponse::Array(vec![sentinel::master(shared)]).to_vec_in(connection.protocol)
+        }
+        Command::SentinelMaster(name) => match *name == sentinel::master_name(shared) {
+            true => sentinel::master(shared).to_vec_in(connection.protocol),
+            false => RedisResponse::Error(sentinel::NO_SUCH_MASTER_ERROR.to_string()).to_vec(),
+        },
+        Command::SentinelGetMasterAddrByName(name) => {
+            match *name == sentinel::master_name(shared) {
+                true => {
+                    let (ip, port) = sentinel::master_address(shared);
+                    RedisResponse::Array(vec![
+                        RedisResponse::BulkString(ip.into_bytes()),
+                        RedisResponse::BulkString(port.to_string().into_bytes()),
+                    ])
+                }
+                false => RedisResponse::Nil,
+            }
+            .to_vec_in(connection.protocol)
+        }
+        Command::MemoryUsage(k) => match lock_then_release(storage).memory_usage(k) {
+            Some(bytes) => RedisResponse::Integer(bytes as i64).to_vec(),
+            None => protocol::NIL.to_vec(),
+        },
+        Command::MemoryStats => {
+            let stats = lock_then_release(databases)
+                .iter_mut()
+                .map(|storage| storage.memory_stats())
+                .collect::<Vec<_>>();
+            memory_stats_response(&stats).to_vec()
+        }
+        Command::MemoryDoctor => {
+            let stats = lock_then_release(databases)
+                .iter_mut()
+                .map(|storage| storage.memory_stats())
+                .collect::<Vec<_>>();
+            RedisResponse::BulkString(memory_doctor(&stats).into_bytes()).to_vec()
+        }
+        Command::DebugSleep(duration) => {
+            thread::sleep(*duration);
+            protocol::OK.to_vec()
+        }
+        Command::DebugObject(k) => {
+            let config = lock_then_release(&shared.config);
+            match describe_value(&mut *lock_then_release(storage), k, &config) {
+                Some((encoding, length)) => RedisResponse::Status(format!(
+                    "Value at:{:p} refcount:1 encoding:{} serializedlength:{} lru:0 lru_seconds_idle:0",
+                    k.as_ptr(),
+                    encoding,
+                    length
+                ))
+                .to_vec(),
+                None => RedisResponse::Error("ERR no such key".to_string()).to_vec(),
+            }
+        }
+        Command::DebugSetActiveExpire(enabled) => {
+            shared.active_expire.store(*enabled, Ordering::Relaxed);
+            protocol::OK.to_vec()
+        }
+        // there is no allocator map to dump
+        Command::DebugJmap => protocol::OK.to_vec(),
+        Command::Shutdown(save) => {
+            let save =
+                save.unwrap_or_else(|| !lock_then_release(&shared.config).value("save").is_empty());
+            match save && persistence::save(&mut lock_then_release(databases), shared).is_err() {
+                // the server keeps running rather than losing what it holds
+                true => {
+                    RedisResponse::Error("ERR Errors trying to SHUTDOWN. Check logs.".to_string())
+                        .to_vec()
+                }
+                false => {
+                    shared.shutdown.store(true, Ordering::Relaxed);
+                    // the connection is closed without a reply, as the server
+                    // goes
+                    connection.closing = true;
+                    vec![]
+                }
+            }
+        }
+        Command::Save | Command::BgSave => {
+            let mut databases = lock_then_release(databases);
+            let in_progress = lock_then_release(&shared.persistence).bgsave_in_progress;
+            match command {
+                _ if in_progress => {
+                    RedisResponse::Error("ERR Background save already in progress".to_string())
+                }
+                Command::Save => match persistence::save(&mut databases, shared) {
+                    Ok(()) => RedisResponse::Status("OK".to_string()),
+                    Err(error) => RedisResponse::Error(format!("ERR {}", error)),
+                },
+                _ => {
+                    persistence::bgsave(&mut databases, shared);
+                    RedisResponse::Status("Background saving started".to_string())
+                }
+            }
+            .to_vec()
+        }
+        Command::BgRewriteAof => {
+            let mut databases = lock_then_release(databases);
+            let is_rewriting = lock_then_release(&shared.aof).is_rewriting();
+            match is_rewriting {
+                true => RedisResponse::Error(
+                    "ERR Background append only file rewriting already in progress".to_string(),
+                ),
+                false => {
+                    aof::bgrewrite(&mut databases, shared);
+                    RedisResponse::Status(
+                        "Background append only file rewriting started".to_string(),
+                    )
+                }
+            }
+            .to_vec()
+        }
+        Command::LastSave => {
+            RedisResponse::Integer(lock_then_release(&shared.persistence).last_save as i64).to_vec()
+        }
+        Command::ReplicaOf(master) => {
+            let master = master
+                .as_ref()
+                .map(|(host, port)| (String::from_utf8_lossy(host).to_string(), *port));
+            let mut replication = lock_then_release(&shared.replication);
+            match master.is_some() && replication.master == master {
+                true => {
+                    RedisResponse::Status("OK Already connected to specified master".to_string())
+                        .to_vec()
+                }
+                false => {
+                    replication.replicate(master);
+                    protocol::OK.to_vec()
+                }
+            }
+        }
+        // the writes are held back until the replica is promoted or the
+        // failover is aborted
+        Command::Failover(options) => {
+            let ips = lock_then_release(&shared.clients)
+                .iter()
+                .filter_map(|(id, client)| {
+                    let addr = client.addr.parse::<SocketAddr>().ok()?;
+                    Some((*id, addr.ip().to_string()))
+                })
+                .collect();
+            let target = options
+                .target
+                .as_ref()
+                .map(|(host, port)| (String::from_utf8_lossy(host).to_string(), *port));
+            let started = lock_then_release(&shared.replication).start_failover(
+                &ips,
+                target,
+                options.force,
+                options.timeout.map(Duration::from_millis),
+            );
+            match started {
+                Ok(()) => {
+                    lock_then_release(&shared.clients).pause(replication::FAILOVER_PAUSE, true);
+                    protocol::OK.to_vec()
+                }
+                Err(error) => RedisResponse::Error(error.to_string()).to_vec(),
+            }
+        }
+        Command::FailoverAbort => {
+            let aborted = lock_then_release(&shared.replication).failover.take();
+            match aborted {
+                Some(_) => {
+                    lock_then_release(&shared.clients).unpause();
+                    protocol::OK.to_vec()
+                }
+                None => RedisResponse::Error("ERR No failover in progress.".to_string()).to_vec(),
+            }
+        }
+        Command::Sync => {
+            let mut databases = lock_then_release(databases);
+            replication::full_sync(&mut databases, shared, connection)
+        }
+        // the replica is sent the whole dataset unless it can resume from
+        // the backlog
+        Command::PSync(replid, offset) => {
+            let mut databases = lock_then_release(databases);
+            let resumed =
+                lock_then_release(&shared.replication).partial_sync(connection, replid, *offset);
+            match resumed {
+                Some(response) => response,
+                None => replication::full_sync(&mut databases, shared, connection),
+            }
+        }
+        Command::ReadOnly => {
+            connection.readonly = true;
+            protocol::OK.to_vec()
+        }
+        Command::ReadWrite => {
+            connection.readonly = false;
+            protocol::OK.to_vec()
+        }
+        Command::ReplConf(args) => {
+            let value = args
+                .get(1)
+                .and_then(|value| std::str::from_utf8(value).ok());
+            match args.first() {
+                // the offsets a replica acknowledges are not replied to
+                Some(option) if option.eq_ignore_ascii_case(b"ack") => {
+                    if let Some(offset) = value.and_then(|value| value.parse().ok()) {
+                        lock_then_release(&shared.replication).ack(connection, offset);
+                    }
+                    vec![]
+                }
+                Some(option) if option.eq_ignore_ascii_case(b"listening-port") => {
+                    connection.listening_port = value.and_then(|value| value.parse().ok());
+                    protocol::OK.to_vec()
+                }
+                _ => protocol::OK.to_vec(),
+            }
+        }
+        Command::Monitor => {
+            lock_then_release(&shared.monitors).add(connection);
+            connection.monitoring = true;
+            protocol::OK.to_vec()
+        }
+        Command::Commands => {
+            RedisResponse::Array(table::commands().map(command_info_response).collect()).to_vec()
+        }
+        Command::CountCommands => RedisResponse::Integer(table::commands().count() as i64).to_vec(),
+        Command::DescribeCommands(names) => match names.is_empty() {
+            true => RedisResponse::Array(table::commands().map(command_info_response).collect()),
+            false => RedisResponse::Array(
+                names
+                    .iter()
+                    .map(
+                        |name| match table::find(&String::from_utf8_lossy(name).to_lowercase()) {
+                            Some(info) => command_info_response(info),
+                            None => RedisResponse::Nil,
+                        },
+                    )
+                    .collect(),
+            ),
+        }
+        .to_vec(),
+        Command::DocumentCommands(names) => {
+            let infos = match names.is_empty() {
+                true => table::commands().collect(),
+                false => names
+                    .iter()
+                    .filter_map(|name| table::find(&String::from_utf8_lossy(name).to_lowercase()))
+                    .collect::<Vec<_>>(),
+            };
+            RedisResponse::Array(infos.into_iter().flat_map(command_docs_response).collect())
+                .to_vec()
+        }
+        Command::GetKeys(args) => {
+            let name = String::from_utf8_lossy(&args[0]).to_lowercase();
+            let subcommand = args
+                .get(1)
+                .map(|subcommand| String::from_utf8_lossy(subcommand).to_lowercase());
+
+            let keys = match table::lookup(&name, subcommand.as_deref()) {
+                None => Err("ERR Invalid command specified"),
+                Some(info) if !info.accepts(args.len()) => {
+                    Err("ERR Invalid number of arguments specified for command")
+                }
+                // keys found after parsing the command when they are not at
+                // fixed positions
+                Some(info) if info.flags.contains(&"movablekeys") => {
+                    let resp = args.iter().map(|arg| Resp::BulkString(arg)).collect();
+                    match Command::parse(resp) {
+                        Ok(command) => Ok(command.keys().into_iter().map(<[u8]>::to_vec).collect()),
+                        Err(_) => Err("ERR Invalid arguments specified for command"),
+                    }
+                }
+                Some(info) => Ok(info
+                    .key_positions(args.len())
+                    .into_iter()
+                    .map(|position| args[position].clone())
+                    .collect::<Vec<_>>()),
+            };
+
+            match keys {
+                Ok(keys) if keys.is_empty() => {
+                    RedisResponse::Error("ERR The command has no key arguments".to_string())
+                        .to_vec()
+                }
+                Ok(keys) => bulk_strings_response(keys, true),
+                Err(error) => RedisResponse::Error(error.to_string()).to_vec(),
+            }
+        }
+        Command::Info(sections) => {
+            let info = info::info(&mut lock_then_release(databases), shared, sections);
+            RedisResponse::BulkString(info.into_bytes()).to_vec()
+        }
+        Command::Subscribe(channels) => {
+            let mut pubsub = lock_then_release(&shared.pubsub);
+
+            channels
+                .iter()
+                .flat_map(|channel| {
+                    pubsub.subscribe(channel, connection);
+                    subscription_response(
+                        b"subscribe",
+                        Some(channel),
+                        connection.subscriptions(),
+                        connection.protocol,
+                    )
+                })
+                .collect()
+        }
+        Command::Unsubscribe(channels) => {
+            let mut pubsub = lock_then_release(&shared.pubsub);
+
+            let channels = match channels.is_empty() {
+                true => connection.channels.iter().cloned().collect(),
+                false => channels.clone(),
+            };
+
+            match channels.is_empty() {
+                // not subscribed to any channel
+                true => subscription_response(
+                    b"unsubscribe",
+                    None,
+                    connection.subscriptions(),
+                    connection.protocol,
+                ),
+                false => channels
+                    .iter()
+                    .flat_map(|channel| {
+                        pubsub.unsubscribe(channel, connection);
+                        subscription_response(
+                            b"unsubscribe",
+                            Some(channel),
+                            connection.subscriptions(),
+                            connection.protocol,
+                        )
+                    })
+                    .collect(),
+            }
+        }
+        Command::PSubscribe(patterns) => {
+            let mut pubsub = lock_then_release(&shared.pubsub);
+
+            patterns
+                .iter()
+                .flat_map(|pattern| {
+                    pubsub.psubscribe(pattern, connection);
+                    subscription_response(
+                        b"psubscribe",
+                        Some(pattern),
+                        connection.subscriptions(),
+                        connection.protocol,
+                    )
+                })
+                .collect()
+        }
+        Command::PUnsubscribe(patterns) => {
+            let mut pubsub = lock_then_release(&shared.pubsub);
+
+            let patterns = match patterns.is_empty() {
+                true => connection.patterns.iter().cloned().collect(),
+                false => patterns.clone(),
+            };
+
+            match patterns.is_empty() {
+                // not subscribed to any pattern
+                true => subscription_response(
+                    b"punsubscribe",
+                    None,
+                    connection.subscriptions(),
+                    connection.protocol,
+                ),
+                false => patterns
+                    .iter()
+                    .flat_map(|pattern| {
+                        pubsub.punsubscribe(pattern, connection);
+                        subscription_response(
+                            b"punsubscribe",
+                            Some(pattern),
+                            connection.subscriptions(),
+                            connection.protocol,
+                        )
+                    })
+                    .collect(),
+            }
+        }
+        Command::Publish(channel, message) => {
+            let receivers = lock_then_release(&shared.pubsub).publish(channel, message);
+            RedisResponse::Integer(receivers as i64).to_vec()
+        }
+        Command::PubSubChannels(pattern) => {
+            let channels = lock_then_release(&shared.pubsub).channels(pattern.as_deref());
+            bulk_strings_response(channels, true)
+        }
+        Command::PubSubNumSub(channels) => {
+            let pubsub = lock_then_release(&shared.pubsub);
+
+            let counts = channels
+                .iter()
+                .flat_map(|channel| {
+                    vec![
+                        RedisResponse::BulkString(channel.clone()),
+                        RedisResponse::Integer(pubsub.subscribers(channel) as i64),
+                    ]
+                })
+                .collect();
+            RedisResponse::Array(counts).to_vec()
+        }
+        Command::PubSubNumPat => {
+            let patterns = lock_then_release(&shared.pubsub).patterns();
+            RedisResponse::Integer(patterns as i64).to_vec()
+        }
+        Command::PubSubShardChannels(pattern) => {
+            let channels = lock_then_release(&shared.pubsub).shard_channels(pattern.as_deref());
+            bulk_strings_response(channels, true)
+        }
+        Command::PubSubShardNumSub(channels) => {
+            let pubsub = lock_then_release(&shared.pubsub);
+
+            let counts = channels
+                .iter()
+                .flat_map(|channel| {
+                    vec![
+                        RedisResponse::BulkString(channel.clone()),
+                        RedisResponse::Integer(pubsub.shard_subscribers(channel) as i64),
+                    ]
+                })
+                .collect();
+            RedisResponse::Array(counts).to_vec()
+        }
+        Command::SSubscribe(channels) => {
+            let mut pubsub = lock_then_release(&shared.pubsub);
+
+            channels
+                .iter()
+                .flat_map(|channel| {
+                    pubsub.ssubscribe(channel, connection);
+                    subscription_response(
+                        b"ssubscribe",
+                        Some(channel),
+                        connection.shard_subscriptions(),
+                        connection.protocol,
+                    )
+                })
+                .collect()
+        }
+        Command::SUnsubscribe(channels) => {
+            let mut pubsub = lock_then_release(&shared.pubsub);
+
+            let channels = match channels.is_empty() {
+                true => connection.shard_channels.iter().cloned().collect(),
+                false => channels.clone(),
+            };
+
+            match channels.is_empty() {
+                // not subscribed to any shard channel
+                true => subscription_response(
+                    b"sunsubscribe",
+                    None,
+                    connection.shard_subscriptions(),
+                    connection.protocol,
+                ),
+                false => channels
+                    .iter()
+                    .flat_map(|channel| {
+                        pubsub.sunsubscribe(channel, connection);
+                        subscription_response(
+                            b"sunsubscribe",
+                            Some(channel),
+                            connection.shard_subscriptions(),
+                            connection.protocol,
+                        )
+                    })
+                    .collect(),
+            }
+        }
+        Command::SPublish(channel, message) => {
+            let receivers = lock_then_release(&shared.pubsub).spublish(channel, message);
+            RedisResponse::Integer(receivers as i64).to_vec()
+        }
+        Command::Select(index) => {
+            let count = lock_then_release(databases).len();
+            match usize::try_from(*index) {
+                Ok(db) if db < count => {
+                    connection.db = db;
+                    protocol::OK.to_vec()
+                }
+                _ => RedisResponse::Error("ERR DB index is out of range".to_string()).to_vec(),
+            }
+        }
+        Command::Move(k, db) => {
+            let mut databases = lock_then_release(databases);
+            let source = connection.db;
+            match usize::try_from(*db) {
+                Ok(db) if db == source => RedisResponse::Error(
+                    "ERR source and destination objects are the same".to_string(),
+                )
+                .to_vec(),
+                // the key is left where it is if the destination has it too
+                Ok(db) if db < databases.len() && !databases[db].contains(k) => {
+                    match databases[source].take(k) {
+                        Some(value) => {
+                            databases[db].put(k, value);
+                            b":1\r\n".to_vec()
+                        }
+                        None => b":0\r\n".to_vec(),
+                    }
+                }
+                Ok(db) if db < databases.len() => b":0\r\n".to_vec(),
+                _ => RedisResponse::Error("ERR DB index is out of range".to_string()).to_vec(),
+            }
+        }
+        Command::ClientId => RedisResponse::Integer(connection.id as i64).to_vec(),
+        Command::ClientSetName(name) if name.iter().any(|c| !(b'!'..=b'~').contains(c)) => {
+            RedisResponse::Error(
+                "ERR Client names cannot contain spaces, newlines or special characters."
+                    .to_string(),
+            )
+            .to_vec()
+        }
+        Command::ClientSetName(name) => {
+            if let Some(client) = lock_then_release(&shared.clients).get_mut(connection.id) {
+                // an empty name removes the name
+                client.name = match name.is_empty() {
+                    true => None,
+                    false => Some(name.clone()),
+                };
+            }
+            protocol::OK.to_vec()
+        }
+        Command::ClientGetName => {
+            let clients = lock_then_release(&shared.clients);
+            match clients
+                .get(connection.id)
+                .and_then(|client| client.name.clone())
+            {
+                Some(name) => RedisResponse::BulkString(name),
+                None => RedisResponse::Nil,
+            }
+            .to_vec()
+        }
+        Command::ClientList(client_type, ids) => {
+            let mut clients = lock_then_release(&shared.clients);
+            clients.update(connection, "client|list");
+
+            let list = clients
+                .iter()
+                .filter(|(id, _)| ids.is_empty() || ids.contains(id))
+                .filter(|(_, client)| match client_type {
+                    Some(client_type) => client.is_of_type(client_type),
+                    None => true,
+                })
+                .map(|(id, client)| format!("{}\n", client.describe(*id)))
+                .collect::<String>();
+            RedisResponse::BulkString(list.into_bytes()).to_vec()
+        }
+        Command::ClientInfo => {
+            let mut clients = lock_then_release(&shared.clients);
+            clients.update(connection, "client|info");
+
+            match clients.get(connection.id) {
+                Some(client) => RedisResponse::BulkString(
+                    format!("{}\n", client.describe(connection.id)).into_bytes(),
+                ),
+                None => RedisResponse::Nil,
+            }
+            .to_vec()
+        }
+        Command::ClientKill(filter, old_form) => {
+            let killed = lock_then_release(&shared.clients).kill(filter, connection.id);
+            if killed.contains(&connection.id) {
+                connection.closing = true;
+            }
+
+            match (old_form, killed.len()) {
+                (true, 0) => RedisResponse::Error("ERR No such client".to_string()).to_vec(),
+                (true, _) => protocol::OK.to_vec(),
+                (false, count) => RedisResponse::Integer(count as i64).to_vec(),
+            }
+        }
+        Command::ClientPause(timeout, write_only) => {
+            lock_then_release(&shared.clients).pause(Duration::from_millis(*timeout), *write_only);
+            protocol::OK.to_vec()
+        }
+        Command::ClientUnpause => {
+            lock_then_release(&shared.clients).unpause();
+            protocol::OK.to_vec()
+        }
+        Command::ClientTracking(Some(options))
+            if !options.bcast && !options.prefixes.is_empty() =>
+        {
+            RedisResponse::Error("ERR PREFIX option requires BCAST mode to be enabled".to_string())
+                .to_vec()
+        }
+        Command::ClientTracking(Some(options)) => {
+            let redirect_exists = match options.redirect {
+                Some(redirect) => lock_then_release(&shared.clients).get(redirect).is_some(),
+                None => true,
+            };
+            match redirect_exists {
+                true => {
+                    lock_then_release(&shared.tracking).enable(connection, options.clone());
+                    protocol::OK.to_vec()
+                }
+                false => RedisResponse::Error(
+                    "ERR The client ID you want redirect to does not exist".to_string(),
+                )
+                .to_vec(),
+            }
+        }
+        Command::ClientTracking(None) => {
+            lock_then_release(&shared.tracking).disable(connection);
+            protocol::OK.to_vec()
+        }
+        Command::SwapDb(first, second) => {
+            let mut databases = lock_then_release(databases);
+            let count = databases.len();
+            match (usize::try_from(*first), usize::try_from(*second)) {
+                (Ok(first), Ok(second)) if first < count && second < count => {
+                    databases.swap(first, second);
+                    protocol::OK.to_vec()
+                }
+                _ => RedisResponse::Error("ERR DB index is out of range".to_string()).to_vec(),
+            }
+        }
+        Command::Ping(message) if connection.protocol == 2 && connection.is_subscribed() => {
+            RedisResponse::Array(vec![
+                RedisResponse::BulkString(b"pong".to_vec()),
+                RedisResponse::BulkString(message.clone().unwrap_or_default()),
+            ])
+            .to_vec()
+        }
+        Command::Ping(Some(message)) | Command::Echo(message) => {
+            RedisResponse::BulkString(message.clone()).to_vec()
+        }
+        Command::Ping(None) => protocol::PONG.to_vec(),
+        Command::Time => {
+            let now = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default();
+            bulk_strings_response(
+                vec![
+                    now.as_secs().to_string().into_bytes(),
+                    now.subsec_micros().to_string().into_bytes(),
+                ],
+                true,
+            )
+        }
+        // no replica acknowledges the writes of a standalone server, so
+        // there is none to wait for
+        Command::Wait(..) => RedisResponse::Integer(0).to_vec(),
+        Command::Lolwut => RedisResponse::BulkString(
+            format!(
+                "{}\nRedisLess ver. {}\n",
+                LOLWUT_BANNER,
+                env!("CARGO_PKG_VERSION")
+            )
+            .into_bytes(),
+        )
+        .to_vec(),
+        Command::Quit => protocol::OK.to_vec(),
+        Command::Reset => {
+            // the connection is left as it was when it was opened
+            connection.transaction = None;
+            connection.readonly = false;
+            connection.watched.clear();
+            lock_then_release(&shared.pubsub).remove(connection);
+            lock_then_release(&shared.tracking).disable(connection);
+            lock_then_release(&shared.monitors).remove(connection);
+            connection.monitoring = false;
+            if let Some(client) = lock_then_release(&shared.clients).get_mut(connection.id) {
+                client.name = None;
+            }
+            connection.db = 0;
+            connection.protocol = 2;
+            connection.user = None;
+            RedisResponse::Status("RESET".to_string()).to_vec()
+        }
+        #[cfg(feature = "scripting")]
+        Command::Eval(script, keys, args) => {
+            scripting::eval(databases, shared, connection, script, keys, args)
+        }
+        #[cfg(feature = "scripting")]
+        Command::EvalSha(sha, keys, args) => {
+            let script = lock_then_release(&shared.scripts).get(sha);
+            match script {
+                Some(script) => scripting::eval(databases, shared, connection, &script, keys, args),
+                None => RedisResponse::Error(
+                    "NOSCRIPT No matching script. Please use EVAL.".to_string(),
+                )
+                .to_vec(),
+            }
+        }
+        #[cfg(feature = "scripting")]
+        Command::ScriptLoad(script) => {
+            let sha = lock_then_release(&shared.scripts).insert(script);
+            RedisResponse::BulkString(sha.into_bytes()).to_vec()
+        }
+        #[cfg(feature = "scripting")]
+        Command::ScriptExists(shas) => {
+            let scripts = lock_then_release(&shared.scripts);
+            RedisResponse::Array(
+                shas.iter()
+                    .map(|sha| RedisResponse::Integer(scripts.contains(sha) as i64))
+                    .collect(),
+            )
+            .to_vec()
+        }
+        #[cfg(feature = "scripting")]
+        Command::ScriptFlush => {
+            lock_then_release(&shared.scripts).flush();
+            protocol::OK.to_vec()
+        }
+        #[cfg(feature = "scripting")]
+        Command::ScriptKill => match lock_then_release(&shared.scripts).kill() {
+            Ok(()) => protocol::OK.to_vec(),
+            Err(err) => RedisResponse::Error(err.to_string()).to_vec(),
+        },
+        #[cfg(feature = "scripting")]
+        Command::FunctionLoad(code, replace) => {
+            match lock_then_release(&shared.functions).load(code, *replace) {
+                Ok(name) => RedisResponse::BulkString(name.into_bytes()).to_vec(),
+                Err(err) => RedisResponse::Error(err).to_vec(),
+            }
+        }
+        #[cfg(feature = "scripting")]
+        Command::FunctionList(pattern, with_code) => lock_then_release(&shared.functions)
+            .list(pattern.as_deref(), *with_code)
+            .to_vec(),
+        #[cfg(feature = "scripting")]
+        Command::FunctionDelete(name) => match lock_then_release(&shared.functions).delete(name) {
+            true => protocol::OK.to_vec(),
+            false => RedisResponse::Error("ERR Library not found".to_string()).to_vec(),
+        },
+        #[cfg(feature = "scripting")]
+        Command::FunctionDump => {
+            RedisResponse::BulkString(lock_then_release(&shared.functions).dump()).to_vec()
+        }
+        #[cfg(feature = "scripting")]
+        Command::FunctionFlush => {
+            lock_then_release(&shared.functions).flush();
+            protocol::OK.to_vec()
+        }
+        #[cfg(feature = "scripting")]
+        Command::FunctionKill => match lock_then_release(&shared.scripts).kill() {
+            Ok(()) => protocol::OK.to_vec(),
+            Err(err) => RedisResponse::Error(err.to_string()).to_vec(),
+        },
+        #[cfg(feature = "scripting")]
+        Command::FCall(function, keys, args, read_only) => scripting::fcall(
+            databases, shared, connection, function, keys, args, *read_only,
+        ),
+        #[cfg(not(feature = "scripting"))]
+        Command::Eval(..)
+        | Command::EvalSha(..)
+        | Command::ScriptLoad(_)
+        | Command::ScriptExists(_)
+        | Command::ScriptFlush
+        | Command::ScriptKill
+        | Command::FunctionLoad(..)
+        | Command::FunctionList(..)
+        | Command::FunctionDelete(_)
+        | Command::FunctionDump
+        | Command::FunctionFlush
+        | Command::FunctionKill
+        | Command::FCall(..) => RedisResponse::Error(
+            "ERR scripting is not available, RedisLess being built without the scripting feature"
+                .to_string(),
+        )
+        .to_vec(),
+        Command::Unwatch => {
+            connection.watched.clear();
+            protocol::OK.to_vec()
+        }
+        // handled before, never queued
+        Command::Multi | Command::Exec | Command::Discard | Command::Watch(_) => unreachable!(),
+    }
+}
+
+/// publish the keys that expired while running the command, then the
+/// events the command fired, the connections tracking the keys it changed
+/// being told and the keys it read being tracked and counted as hits or
+/// misses
+pub fn publish_keyspace_events<T: Storage, L: Lock<Vec<T>>>(
+    databases: &L,
+    db: usize,
+    shared: &Arc<Shared>,
+    connection: &Connection,
+    command: &Command,
+    response: &[u8],
+    evicts: bool,
+) {
+    // the keys read that expired were removed as they were read, the
+    // others being counted as used once maxmemory is set, for the eviction
+    // policies to tell the keys used the least
+    let read = match command.is_write() {
+        true => vec![],
+        false => command.keys(),
+    };
+    let (expired, hits) = {
+        let mut databases = lock_then_release(databases);
+        let storage = &mut databases[db];
+        let hits = read.iter().filter(|key| storage.contains(key)).count();
+        if evicts {
+            read.iter().for_each(|key| storage.access(key));
+        }
+        (storage.take_expired(), hits)
+    };
+    if !expired.is_empty() || !read.is_empty() {
+        let mut stats = lock_then_release(&shared.stats);
+        stats.expired_keys += expired.len() as u64;
+        stats.keyspace_hits += hits as u64;
+        stats.keyspace_misses += (read.len() - hits) as u64;
+    }
+    let events = notify::keyspace_events(command, response);
+    let changed = expired
+        .iter()
+        .chain(events.iter().map(|(_, _, key)| key))
+        .cloned()
+        .collect::<Vec<_>>();
+    // most commands change no key, taking none of the locks below but the
+    // one of the connections tracking the keys they read
+    if changed.is_empty() && !matches!(command, Command::SwapDb(..)) {
+        if !read.is_empty() {
+            lock_then_release(&shared.tracking).track(connection, &read);
+        }
+        return;
+    }
+
+    lock_then_release(&shared.persistence).dirty += changed.len() as u64;
+    // the databases are locked before the other locks are taken, as they
+    // are during a transaction
+    if lock_then_release(&shared.aof).is_logging()
+        || lock_then_release(&shared.replication).backlog_active
+    {
+        append_changes(databases, db, shared, command, response, &changed);
+    }
+
+    let pubsub = lock_then_release(&shared.pubsub);
+
+    for key in &expired {
+        pubsub.notify(db, notify::EXPIRED, "expired", key);
+        shared.events.send(KeyEvent::Expired(db, key.clone()));
+    }
+    for (class, event, key) in &events {
+        pubsub.notify(db, *class, event, key);
+    }
+    if let (Command::Move(key, destination), b":1\r\n") = (command, response) {
+        pubsub.notify(*destination as usize, notify::GENERIC, "move_to", key);
+    }
+
+    let mut tracking = lock_then_release(&shared.tracking);
+    tracking.invalidate(&changed, connection.id, &pubsub);
+    if let (Command::SwapDb(..), b"+OK\r\n") = (command, response) {
+        tracking.invalidate_all(&pubsub);
+    }
+    if !command.is_write() {
+        tracking.track(connection, &command.keys());
+    }
+}
+
+/// the reply to GET, MGET or EXISTS, given while the shard is locked for
+/// reading alone so that such commands run alongside each other, with the
+/// keys found and missing counted as hits and misses, or None for the
+/// command to lock the shard as the others do: when GET reads a key of
+/// another type, when one of the keys expired, to be removed and told then,
+/// or when maxmemory is set, as the keys read are then marked as used for
+/// the eviction policies
+fn read_shared<T: Storage>(
+    shard: &Shard<'_, T>,
+    shared: &Shared,
+    connection: &Connection,
+    command: &Command,
+    evicts: bool,
+) -> Option<CommandResponse> {
+    if evicts {
+        return None;
+    }
+
+    let keys = command.keys();
+    let (response, hits) = {
+        let databases = shard.read();
+        let storage = &databases[connection.db];
+        let (response, hits) = match command {
+            Command::Get(key) => match storage.read(key) {
+                Some(value) => (value_response(&value).to_vec(), 1),
+                // a key of another type, for which it replies WRONGTYPE
+                None if storage.contains(key) => return None,
+                None => (protocol::NIL.to_vec(), 0),
+            },
+            Command::MGet(keys) => {
+                let values = keys
+                    .iter()
+                    .map(|key| match storage.read(key) {
+                        Some(value) => value_response(&value),
+                        None => RedisResponse::Nil,
+                    })
+                    .collect::<Vec<_>>();
+                let hits = values
+                    .iter()
+                    .filter(|value| !matches!(value, RedisResponse::Nil))
+                    .count();
+                (RedisResponse::Array(values).to_vec(), hits)
+            }
+            Command::Exists(key) => match storage.contains(key) {
+                true => (b":1\r\n".to_vec(), 1),
+                false => (b":0\r\n".to_vec(), 0),
+            },
+            _ => return None,
+        };
+        // the reads leave the keys that expired to be removed later
+        if storage.lapsed() {
+            return None;
+        }
+        (response, hits)
+    };
+    {
+        let mut stats = lock_then_release(&shared.stats);
+        stats.keyspace_hits += hits as u64;
+        stats.keyspace_misses += (keys.len() - hits) as u64;
+    }
+    lock_then_release(&shared.tracking).track(connection, &keys);
+    Some(response)
+}
+
+/// append what the keys a command changed were left with to the append
+/// only file, for the dataset to be rebuilt from it, and feed it to the
+/// replicas
+fn append_changes<T: Storage, L: Lock<Vec<T>>>(
+    databases: &L,
+    db: usize,
+    shared: &Shared,
+    command: &Command,
+    response: &[u8],
+    changed: &[Vec<u8>],
+) {
+    let mut databases = lock_then_release(databases);
+    let mut aof = lock_then_release(&shared.aof);
+    let mut replication = lock_then_release(&shared.replication);
+    let mut append = |change: Change| {
+        if aof.is_logging() {
+            aof.append(&change);
+        }
+        replication.feed(&change);
+    };
+    let mut append_key = |db: usize, key: &[u8]| {
+        let change = match databases[db].copy(key) {
+            Some(value) => Change::Put(db, key.to_vec(), value),
+            None => Change::Remove(db, key.to_vec()),
+        };
+        append(change);
+    };
+
+    for key in changed {
+        append_key(db, key);
+    }
+    match (command, response) {
+        (Command::Move(key, destination), b":1\r\n") => append_key(*destination as usize, key),
+        (Command::SwapDb(first, second), b"+OK\r\n") => {
+            append(Change::SwapDb(*first as usize, *second as usize))
+        }
+        _ => {}
+    }
+}
+
+/// switch the connection to another version of RESP, authenticating it
+/// and naming it first if asked, and reply what the server is
+fn hello(
+    shared: &Shared,
+    connection: &mut Connection,
+    protocol: Option<i64>,
+    auth: Option<&(Vec<u8>, Vec<u8>)>,
+    name: Option<&[u8]>,
+) -> CommandResponse {
+    let protocol = match protocol {
+        Some(protocol @ 2..=3) => protocol as u8,
+        Some(_) => {
+            return RedisResponse::Error("NOPROTO unsupported protocol version".to_string())
+                .to_vec()
+        }
+        None => connection.protocol,
+    };
+    if let Some(name) = name {
+        if name.iter().any(|c| !(b'!'..=b'~').contains(c)) {
+            return RedisResponse::Error(
+                "ERR Client names cannot contain spaces, newlines or special characters."
+                    .to_string(),
+            )
+            .to_vec();
+        }
+    }
+
+    let acl = lock_then_release(&shared.acl);
+    match auth {
+        Some((username, password)) => {
+            let username = String::from_utf8_lossy(username).to_string();
+            if !acl.authenticate(&username, password) {
+                return RedisResponse::Error(
+                    "WRONGPASS invalid username-password pair or user is disabled.".to_string(),
+                )
+                .to_vec();
+            }
+            connection.user = Some(username);
+        }
+        None if acl.username(connection).is_none() => {
+            return RedisResponse::Error("NOAUTH HELLO must be called with the client already authenticated, otherwise the HELLO <proto> AUTH <user> <pass> option can be used to authenticate the client and select the RESP protocol version at the same time".to_string()).to_vec();
+        }
+        None => {}
+    }
+    drop(acl);
+
+    if let Some(name) = name {
+        if let Some(client) = lock_then_release(&shared.clients).get_mut(connection.id) {
+            client.name = match name.is_empty() {
+                true => None,
+                false => Some(name.to_vec()),
+            };
+        }
+    }
+    connection.protocol = protocol;
+
+    let role = match lock_then_release(&shared.replication).master {
+        Some(_) => "replica",
+        None => "master",
+    };
+    let field = |name: &str| RedisResponse::BulkString(name.as_bytes().to_vec());
+    RedisResponse::Map(vec![
+        (field("server"), field("redis")),
+        (field("version"), field(REDIS_VERSION)),
+        (field("proto"), RedisResponse::Integer(protocol as i64)),
+        (field("id"), RedisResponse::Integer(connection.id as i64)),
+        (field("mode"), field("standalone")),
+        (field("role"), field(role)),
+        (field("modules"), RedisResponse::Array(vec![])),
+    ])
+    .to_vec_in(protocol)
+}
+
+/// confirmation of a subscription change, along with the number of channels
+/// and patterns, or of shard channels, the connection is still subscribed to
+fn subscription_response(
+    kind: &[u8],
+    channel: Option<&[u8]>,
+    subscriptions: usize,
+    protocol: u8,
+) -> CommandResponse {
+    let confirmation = vec![
+        RedisResponse::BulkString(kind.to_vec()),
+        match channel {
+            Some(channel) => RedisResponse::BulkString(channel.to_vec()),
+            None => RedisResponse::Nil,
+        },
+        RedisResponse::Integer(subscriptions as i64),
+    ];
+    // a push in RESP3, as the messages published to the connection
+    match protocol {
+        3 => RedisResponse::Push(confirmation).to_vec_in(protocol),
+        _ => RedisResponse::Array(confirmation).to_vec(),
+    }
+}
+
+/// an array of bulk strings, or a single bulk string (nil if there is none)
+/// for commands called without count
+fn bulk_strings_response(values: Vec<Vec<u8>>, with_count: bool) -> CommandResponse {
+    match with_count {
+        true => RedisResponse::Array(values.into_iter().map(RedisResponse::BulkString).collect()),
+        false => match values.into_iter().next() {
+            Some(value) => RedisResponse::BulkString(value),
+            None => RedisResponse::Nil,
+        },
+    }
+    .to_vec()
+}
+
+/// a string value as GET replies it, falling back to a bulk string when it
+/// is not UTF-8, e.g. a bitmap
+fn value_response(value: &[u8]) -> RedisResponse {
+    match std::str::from_utf8(value) {
+        Ok(value) => RedisResponse::Status(value.to_string()),
+        Err(_) => RedisResponse::BulkString(value.to_vec()),
+    }
+}
+
+/// members of a sorted set, each followed by its score when asked to
+fn sorted_set_members_response(members: Vec<(&[u8], f64)>, with_scores: bool) -> CommandResponse {
+    let mut items = Vec::with_capacity(members.len() * 2);
+    for (member, score) in members {
+        items.push(RedisResponse::BulkString(member.to_vec()));
+        if with_scores {
+            items.push(RedisResponse::BulkString(format_score(score).into_bytes()));
+        }
+    }
+
+    RedisResponse::Array(items).to_vec()
+}
+
+/// the next cursor followed by the items of the current page
+fn scan_response(next_cursor: u64, items: Vec<RedisResponse>) -> CommandResponse {
+    RedisResponse::Array(vec![
+        RedisResponse::BulkString(next_cursor.to_string().into_bytes()),
+        RedisResponse::Array(items),
+    ])
+    .to_vec()
+}
+
+/// members of the sets stored at keys, a missing key being an empty set,
+/// or None if one of the keys holds another type
+fn read_sets<T: Storage>(storage: &mut T, keys: &[Vec<u8>]) -> Option<Vec<HashSet<Vec<u8>>>> {
+    keys.iter()
+        .map(|key| match storage.type_of(key) {
+            Some(DataType::Set) | None => Some(
+                storage
+                    .sread(key)
+                    .map_or_else(HashSet::new, |set| set.data.clone()),
+            ),
+            Some(_) => None,
+        })
+        .collect()
+}
+
+/// members of the sorted set at key found by query, with their score and
+/// distance in meters, or the error to reply with
+fn geo_search<T: Storage>(
+    storage: &mut T,
+    key: &[u8],
+    query: &GeoQuery,
+) -> Result<Vec<(Vec<u8>, f64, f64)>, CommandResponse> {
+    if !matches!(storage.type_of(key), Some(DataType::SortedSet) | None) {
+        return Err(protocol::WRONGTYPE.to_vec());
+    }
+
+    let sorted_set = match storage.zread(key) {
+        Some(sorted_set) => sorted_set,
+        None => return Ok(vec![]),
+    };
+
+    let center = match &query.origin {
+        GeoOrigin::Position(longitude, latitude) => (*longitude, *latitude),
+        GeoOrigin::Member(member) => match sorted_set.score(member) {
+            Some(score) => geo::decode(score),
+            None => {
+                return Err(RedisResponse::Error(
+                    "ERR could not decode requested zset member".to_string(),
+                )
+                .to_vec())
+            }
+        },
+    };
+
+    Ok(query
+        .search(sorted_set.iter(), center)
+        .into_iter()
+        .map(|(member, score, distance)| (member.to_vec(), score, distance))
+        .collect())
+}
+
+/// each entry as its id followed by its fields and values
+fn stream_entries_response(entries: Vec<(StreamId, &StreamFields)>) -> Vec<RedisResponse> {
+    entries
+        .into_iter()
+        .map(|(id, fields)| stream_entry_response(id, Some(fields)))
+        .collect()
+}
+
+/// an entry as its id followed by its fields and values, nil fields
+/// standing for an entry that has been deleted
+fn stream_entry_response(id: StreamId, fields: Option<&StreamFields>) -> RedisResponse {
+    let fields = match fields {
+        Some(fields) => RedisResponse::Array(
+            fields
+                .iter()
+                .flat_map(|(field, value)| {
+                    vec![
+                        RedisResponse::BulkString(field.clone()),
+                        RedisResponse::BulkString(value.clone()),
+                    ]
+                })
+                .collect(),
+        ),
+        None => RedisResponse::Nil,
+    };
+
+    RedisResponse::Array(vec![
+        RedisResponse::BulkString(id.to_string().into_bytes()),
+        fields,
+    ])
+}
+
+/// error replied by the XGROUP subcommands when the key does not exist
+fn group_requires_key() -> CommandResponse {
+    RedisResponse::Error(
+        "ERR The XGROUP subcommand requires the key to exist. Note that for CREATE you may want to use the MKSTREAM option to create an empty stream automatically.".to_string(),
+    )
+    .to_vec()
+}
+
+/// the group of the stream at key, or the error to reply with
+fn consumer_group<'a, T: Storage>(
+    storage: &'a mut T,
+    key: &[u8],
+    group: &[u8],
+) -> Result<&'a mut ConsumerGroup, CommandResponse> {
+    match storage.type_of(key) {
+        Some(DataType::Stream) => {}
+        None => return Err(group_requires_key()),
+        Some(_) => return Err(protocol::WRONGTYPE.to_vec()),
+    }
+
+    storage
+        .xread_mut(key)
+        .and_then(|stream| stream.groups.get_mut(group))
+        .ok_or_else(|| {
+            let error = format!(
+                "NOGROUP No such consumer group '{}' for key name '{}'",
+                String::from_utf8_lossy(group),
+                String::from_utf8_lossy(key)
+            );
+            RedisResponse::Error(error).to_vec()
+        })
+}
+
+/// the elements sorted and then replaced by the values of the GET patterns,
+/// None if a weight is not a number while sorting numerically
+fn sort<T: Storage>(
+    storage: &mut T,
+    query: &SortQuery,
+    elements: Vec<Vec<u8>>,
+) -> Option<Vec<Option<Vec<u8>>>> {
+    // the string at key or the field of the hash at key
+    let mut lookup = |key: &[u8], field: Option<&[u8]>| match (storage.type_of(key), field) {
+        (Some(DataType::String), None) => storage.read(key).map(|value| value.to_vec()),
+        (Some(DataType::Hash), Some(field)) => {
+            storage.hread(key, field).map(|value| value.to_vec())
+        }
+        _ => None,
+    };
+
+    let elements = query.sort(elements, &mut lookup)?;
+    Some(query.get_values(elements, &mut lookup))
+}
+
+/// the stream at key if it has the group, or the error to reply with
+fn stream_with_group<'a, T: Storage>(
+    storage: &'a mut T,
+    key: &[u8],
+    group: &[u8],
+) -> Result<&'a mut RedisStream, CommandResponse> {
+    match storage.type_of(key) {
+        Some(DataType::Stream) | None => {}
+        Some(_) => return Err(protocol::WRONGTYPE.to_vec()),
+    }
+
+    match storage.xread_mut(key) {
+        Some(stream) if stream.groups.contains_key(group) => Ok(stream),
+        _ => {
+            let error = format!(
+                "NOGROUP No such key '{}' or consumer group '{}'",
+                String::from_utf8_lossy(key),
+                String::from_utf8_lossy(group)
+            );
+            Err(RedisResponse::Error(error).to_vec())
+        }
+    }
+}
+
+/// the stream at key, or the error to reply with if there is none
+fn existing_stream<'a, T: Storage>(
+    storage: &'a mut T,
+    key: &[u8],
+) -> Result<&'a mut RedisStream, CommandResponse> {
+    match storage.type_of(key) {
+        Some(DataType::Stream) => {}
+        None => return Err(RedisResponse::Error("ERR no such key".to_string()).to_vec()),
+        Some(_) => return Err(protocol::WRONGTYPE.to_vec()),
+    }
+
+    storage
+        .xread_mut(key)
+        .ok_or_else(|| RedisResponse::Error("ERR no such key".to_string()).to_vec())
+}
+
+/// fields and values flattened the way Redis replies with a map
+fn info_response(fields: Vec<(&str, RedisResponse)>) -> RedisResponse {
+    RedisResponse::Array(
+        fields
+            .into_iter()
+            .flat_map(|(field, value)| {
+                vec![RedisResponse::BulkString(field.as_bytes().to_vec()), value]
+            })
+            .collect(),
+    )
+}
+
+/// a command as COMMAND INFO describes it, along with its subcommands
+fn command_info_response(info: &'static CommandInfo) -> RedisResponse {
+    let strings = |strings: &[&str], prefix: &str| {
+        RedisResponse::Array(
+            strings
+                .iter()
+                .map(|string| RedisResponse::Status(format!("{}{}", prefix, string)))
+                .collect(),
+        )
+    };
+
+    RedisResponse::Array(vec![
+        RedisResponse::BulkString(info.name.as_bytes().to_vec()),
+        RedisResponse::Integer(info.arity),
+        strings(info.flags, ""),
+        RedisResponse::Integer(info.first_key),
+        RedisResponse::Integer(info.last_key),
+        RedisResponse::Integer(info.step),
+        strings(info.categories, "@"),
+        // no tips nor key specifications
+        RedisResponse::Array(vec![]),
+        RedisResponse::Array(vec![]),
+        RedisResponse::Array(
+            table::subcommands(info.name)
+                .map(command_info_response)
+                .collect(),
+        ),
+    ])
+}
+
+/// the name of a command followed by what COMMAND DOCS tells about it,
+/// there being no documentation beyond its group
+fn command_docs_response(info: &'static CommandInfo) -> Vec<RedisResponse> {
+    let mut docs = vec![
+        RedisResponse::BulkString(b"group".to_vec()),
+        RedisResponse::BulkString(info.group().as_bytes().to_vec()),
+    ];
+    let subcommands = table::subcommands(info.name)
+        .flat_map(command_docs_response)
+        .collect::<Vec<_>>();
+    if !subcommands.is_empty() {
+        docs.push(RedisResponse::BulkString(b"subcommands".to_vec()));
+        docs.push(RedisResponse::Array(subcommands));
+    }
+
+    vec![
+        RedisResponse::BulkString(info.name.as_bytes().to_vec()),
+        RedisResponse::Array(docs),
+    ]
+}
+
+/// the encoding Redis would use for the value of a key given the limits of
+/// the configuration, and the number of bytes it holds
+fn describe_value<T: Storage>(
+    storage: &mut T,
+    k: &[u8],
+    config: &Config,
+) -> Option<(&'static str, usize)> {
+    let small = |count: usize, longest: usize, entries: &str, value: &str| {
+        count as i64 <= config.integer(entries) && longest as i64 <= config.integer(value)
+    };
+    let is_integer = |bytes: &[u8]| {
+        bytes.len() <= 20 && matches!(std::str::from_utf8(bytes).map(str::parse::<i64>), Ok(Ok(_)))
+    };
+
+    match storage.type_of(k)? {
+        DataType::String => {
+            let value = storage.read(k)?;
+            let encoding = match value.len() {
+                _ if is_integer(&value) => "int",
+                0..=44 => "embstr",
+                _ => "raw",
+            };
+            Some((encoding, value.len()))
+        }
+        DataType::Set => {
+            let set = storage.sread(k)?;
+            let length = set.data.iter().map(Vec::len).sum();
+            let longest = set.data.iter().map(Vec::len).max().unwrap_or(0);
+            let encoding = match set.data.iter().all(|member| is_integer(member)) {
+                true if set.data.len() as i64 <= config.integer("set-max-intset-entries") => {
+                    "intset"
+                }
+                _ if small(
+                    set.data.len(),
+                    longest,
+                    "set-max-listpack-entries",
+                    "set-max-listpack-value",
+                ) =>
+                {
+                    "listpack"
+                }
+                _ => "hashtable",
+            };
+            Some((encoding, length))
+        }
+        DataType::SortedSet => {
+            let sorted_set = storage.zread(k)?;
+            let longest = sorted_set.scores.keys().map(Vec::len).max().unwrap_or(0);
+            let length = sorted_set
+                .scores
+                .keys()
+                .map(|member| member.len() + 8)
+                .sum();
+            let encoding = match small(
+                sorted_set.scores.len(),
+                longest,
+                "zset-max-listpack-entries",
+                "zset-max-listpack-value",
+            ) {
+                true => "listpack",
+                false => "skiplist",
+            };
+            Some((encoding, length))
+        }
+        DataType::Hash => {
+            let hash = storage.hread_all(k)?;
+            let longest = hash
+                .data
+                .iter()
+                .map(|(field, value)| field.len().max(value.len()))
+                .max()
+                .unwrap_or(0);
+            let length = hash
+                .data
+                .iter()
+                .map(|(field, value)| field.len() + value.len())
+                .sum();
+            let encoding = match small(
+                hash.data.len(),
+                longest,
+                "hash-max-listpack-entries",
+                "hash-max-listpack-value",
+            ) {
+                true => "listpack",
+                false => "hashtable",
+            };
+            Some((encoding, length))
+        }
+        DataType::Stream => {
+            let stream = storage.xread(k)?;
+            let length = stream
+                .entries
+                .values()
+                .flatten()
+                .map(|(field, value)| field.len() + value.len() + 16)
+                .sum();
+            Some(("stream", length))
+        }
+        DataType::List => Some(("quicklist", 0)),
+    }
+}
+
+/// the ip the client connected to and the port the server listens on, as
+/// the cluster commands tell where the node serving the slots is
+fn cluster_address(shared: &Shared, connection: &Connection) -> (String, u16) {
+    let ip = lock_then_release(&shared.clients)
+        .get(connection.id)
+        .and_then(|client| client.laddr.parse::<SocketAddr>().ok())
+        .map_or("127.0.0.1".to_string(), |addr| addr.ip().to_string());
+    let port = lock_then_release(&shared.config).integer("port") as u16;
+    (ip, port)
+}
+
+/// what MEMORY STATS tells about the keys of every database
+fn memory_stats_response(stats: &[MemoryStats]) -> RedisResponse {
+    let overhead = stats.iter().map(|stats| stats.overhead).sum::<usize>();
+    let dataset = stats.iter().map(|stats| stats.dataset).sum::<usize>();
+    let keys = stats.iter().map(|stats| stats.keys).sum::<usize>();
+    let total = overhead + dataset;
+
+    let databases = stats
+        .iter()
+        .enumerate()
+        .filter(|(_, stats)| stats.keys > 0)
+        .map(|(db, stats)| (format!("db.{}", db), stats.overhead))
+        .collect::<Vec<_>>();
+    let mut fields = vec![("total.allocated", RedisResponse::Integer(total as i64))];
+    for (name, overhead) in &databases {
+        fields.push((
+            name,
+            info_response(vec![(
+                "overhead.hashtable.main",
+                RedisResponse::Integer(*overhead as i64),
+            )]),
+        ));
+    }
+    let per_key = match keys {
+        0 => 0,
+        keys => total / keys,
+    };
+    let percentage = match total {
+        0 => 0.0,
+        total => dataset as f64 * 100.0 / total as f64,
+    };
+    fields.extend(vec![
+        ("overhead.total", RedisResponse::Integer(overhead as i64)),
+        ("keys.count", RedisResponse::Integer(keys as i64)),
+        ("keys.bytes-per-key", RedisResponse::Integer(per_key as i64)),
+        ("dataset.bytes", RedisResponse::Integer(dataset as i64)),
+        (
+            "dataset.percentage",
+            RedisResponse::BulkString(percentage.to_string().into_bytes()),
+        ),
+    ]);
+
+    info_response(fields)
+}
+
+/// a report of the memory issues for a human to read, as MEMORY DOCTOR
+/// replies it
+fn memory_doctor(stats: &[MemoryStats]) -> String {
+    let overhead = stats.iter().map(|stats| stats.overhead).sum::<usize>();
+    let dataset = stats.iter().map(|stats| stats.dataset).sum::<usize>();
+
+    if overhead + dataset < MEMORY_DOCTOR_MIN_BYTES {
+        return "Hi Sam, this instance is empty or is using very little memory, my issues \
+                detector can't be used in these conditions. Please, leave for your mission on \
+                Earth and fill it with some data. The new Sam and I will be back to our \
+                programming as soon as I finished rebooting."
+            .to_string();
+    }
+    if overhead > dataset {
+        return format!(
+            "Sam, I detected a few issues in this Redis instance memory implants:\n\n \
+             * High keys overhead: the keys take more memory than the values they hold ({} \
+             bytes against {} bytes). Many small values take less memory as the fields of a \
+             few hashes.\n\nI'm here to keep you safe, Sam. I want to help you.\n",
+            overhead, dataset
+        );
+    }
+
+    "Hi Sam, I can't find any memory issue in your instance. I can only account for what \
+     occurs on this base."
+        .to_string()
+}
+
+fn id_response(id: StreamId) -> RedisResponse {
+    RedisResponse::BulkString(id.to_string().into_bytes())
+}
+
+/// XINFO STREAM, with the first and last entries of the stream
+fn stream_info_response(stream: &RedisStream) -> RedisResponse {
+    let entry = |entry: Option<(&StreamId, &StreamFields)>| match entry {
+        Some((id, fields)) => stream_entry_response(*id, Some(fields)),
+        None => RedisResponse::Nil,
+    };
+
+    info_response(vec![
+        ("length", RedisResponse::Integer(stream.len() as i64)),
+        ("last-generated-id", id_response(stream.last_id)),
+        ("max-deleted-entry-id", id_response(stream.max_deleted_id)),
+        (
+            "entries-added",
+            RedisResponse::Integer(stream.entries_added as i64),
+        ),
+        ("groups", RedisResponse::Integer(stream.groups.len() as i64)),
+        ("first-entry", entry(stream.entries.iter().next())),
+        ("last-entry", entry(stream.entries.iter().next_back())),
+    ])
+}
+
+/// XINFO STREAM FULL, with up to count entries and the pending entries of
+/// each group and consumer, 0 meaning no limit
+fn stream_full_info_response(stream: &RedisStream, count: usize) -> RedisResponse {
+    let count = Some(count).filter(|count| *count > 0);
+    let entries = stream.range(StreamId::MIN, StreamId::MAX, count, false);
+
+    let groups = stream
+        .groups
+        .iter()
+        .map(|(name, group)| {
+            let pending = group
+                .pending
+                .iter()
+                .take(count.unwrap_or(usize::MAX))
+                .map(|(id, pending)| {
+                    RedisResponse::Array(vec![
+                        id_response(*id),
+                        RedisResponse::BulkString(pending.consumer.clone()),
+                        RedisResponse::Integer(pending.delivery_time as i64),
+                        RedisResponse::Integer(pending.delivery_count as i64),
+                    ])
+                })
+                .collect();
+
+            let consumers = group
+                .consumers
+                .iter()
+                .map(|(name, consumer)| {
+                    let pending = consumer
+                        .pending
+                        .iter()
+                        .take(count.unwrap_or(usize::MAX))
+                        .filter_map(|id| {
+                            let pending = group.pending.get(id)?;
+                            Some(RedisResponse::Array(vec![
+                                id_response(*id),
+                                RedisResponse::Integer(pending.delivery_time as i64),
+                                RedisResponse::Integer(pending.delivery_count as i64),
+                            ]))
+                        })
+                        .collect();
+
+                    info_response(vec![
+                        ("name", RedisResponse::BulkString(name.clone())),
+                        (
+                            "seen-time",
+                            RedisResponse::Integer(consumer.seen_time as i64),
+                        ),
+                        (
+                            "pel-count",
+                            RedisResponse::Integer(consumer.pending.len() as i64),
+                        ),
+                        ("pending", RedisResponse::Array(pending)),
+                    ])
+                })
+                .collect();
+
+            info_response(vec![
+                ("name", RedisResponse::BulkString(name.clone())),
+                ("last-delivered-id", id_response(group.last_delivered_id)),
+                ("lag", RedisResponse::Integer(stream.lag(group) as i64)),
+                (
+                    "pel-count",
+                    RedisResponse::Integer(group.pending.len() as i64),
+                ),
+                ("pending", RedisResponse::Array(pending)),
+                ("consumers", RedisResponse::Array(consumers)),
+            ])
+        })
+        .collect();
+
+    info_response(vec![
+        ("length", RedisResponse::Integer(stream.len() as i64)),
+        ("last-generated-id", id_response(stream.last_id)),
+        ("max-deleted-entry-id", id_response(stream.max_deleted_id)),
+        (
+            "entries-added",
+            RedisResponse::Integer(stream.entries_added as i64),
+        ),
+        (
+            "entries",
+            RedisResponse::Array(stream_entries_response(entries)),
+        ),
+        ("groups", RedisResponse::Array(groups)),
+    ])
+}
+
+/// the number of pending entries, the lowest and greatest of their ids and
+/// how many are pending for each consumer
+fn pending_summary_response(group: &ConsumerGroup) -> RedisResponse {
+    let id_response = |id: Option<&StreamId>| match id {
+        Some(id) => RedisResponse::BulkString(id.to_string().into_bytes()),
+        None => RedisResponse::Nil,
+    };
+
+    let consumers: Vec<RedisResponse> = group
+        .consumers
+        .iter()
+        .filter(|(_, consumer)| !consumer.pending.is_empty())
+        .map(|(name, consumer)| {
+            RedisResponse::Array(vec![
+                RedisResponse::BulkString(name.clone()),
+                RedisResponse::BulkString(consumer.pending.len().to_string().into_bytes()),
+            ])
+        })
+        .collect();
+
+    RedisResponse::Array(vec![
+        RedisResponse::Integer(group.pending.len() as i64),
+        id_response(group.pending.keys().next()),
+        id_response(group.pending.keys().next_back()),
+        match consumers.is_empty() {
+            true => RedisResponse::Nil,
+            false => RedisResponse::Array(consumers),
+        },
+    ])
+}
+
+/// the entries claimed, or only their ids with JUSTID
+fn claimed_response(stream: &RedisStream, claimed: Vec<StreamId>, just_id: bool) -> RedisResponse {
+    let claimed = claimed
+        .into_iter()
+        .map(|id| match just_id {
+            true => RedisResponse::BulkString(id.to_string().into_bytes()),
+            false => stream_entry_response(id, stream.entries.get(&id)),
+        })
+        .collect();
+
+    RedisResponse::Array(claimed)
+}
+
+/// union of the HyperLogLogs stored at keys, a missing key being an empty
+/// one, or the error to reply with if a key holds something else
+fn read_hyperloglogs<T: Storage>(
+    storage: &mut T,
+    keys: &[Vec<u8>],
+) -> Result<HyperLogLog, CommandResponse> {
+    let mut union = HyperLogLog::new();
+
+    for key in keys {
+        if !matches!(storage.type_of(key), Some(DataType::String) | None) {
+            return Err(protocol::WRONGTYPE.to_vec());
+        }
+
+        if let Some(value) = storage.read(key) {
+            match HyperLogLog::from_bytes(&value) {
+                Some(hyperloglog) => union.merge(&hyperloglog),
+                None => return Err(protocol::INVALID_HLL.to_vec()),
+            }
+        }
+    }
+
+    Ok(union)
+}
 
-    (command.ok(), response)
+/// scores of the members of each key, a plain set counting as a sorted set
+/// where every score is 1, None if a key holds another type
+fn read_sorted_sets<T: Storage>(storage: &mut T, keys: &[Vec<u8>]) -> Option<Vec<Scores>> {
+    keys.iter()
+        .map(|key| match storage.type_of(key) {
+            Some(DataType::SortedSet) => Some(
+                storage
+                    .zread(key)
+                    .map_or_else(Scores::new, |sorted_set| sorted_set.scores.clone()),
+            ),
+            Some(DataType::Set) => Some(storage.sread(key).map_or_else(Scores::new, |set| {
+                set.data
+                    .iter()
+                    .map(|member| (member.clone(), 1.0))
+                    .collect()
+            })),
+            None => Some(Scores::new()),
+            Some(_) => None,
+        })
+        .collect()
 }
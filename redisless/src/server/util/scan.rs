@@ -0,0 +1,51 @@
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+
+use crate::glob::glob_match;
+
+/// Return one page of a cursor based iteration over items, along with the
+/// cursor of the next page (0 once the iteration is complete).
+///
+/// Items are visited in the order of the hash of their key, and the cursor is
+/// the hash to resume from, so an item present during the whole iteration is
+/// returned at least once even if other items are added or removed between
+/// calls. As in Redis, MATCH is applied after the page has been picked, so a
+/// page can come back empty while the iteration is not over.
+pub fn scan<'a, T>(
+    items: impl Iterator<Item = (&'a [u8], T)>,
+    cursor: u64,
+    pattern: Option<&[u8]>,
+    count: usize,
+) -> (u64, Vec<T>) {
+    let mut items = items
+        .map(|(key, item)| (hash_of(key), key, item))
+        .filter(|(hash, _, _)| *hash >= cursor)
+        .collect::<Vec<_>>();
+    items.sort_by_key(|(hash, _, _)| *hash);
+
+    // never split items sharing the same hash across two pages
+    let mut page_size = count.max(1).min(items.len());
+    while page_size > 0 && page_size < items.len() && items[page_size].0 == items[page_size - 1].0 {
+        page_size += 1;
+    }
+
+    let next_cursor = items.get(page_size).map_or(0, |(hash, _, _)| *hash);
+    items.truncate(page_size);
+
+    let page = items
+        .into_iter()
+        .filter(|(_, key, _)| match pattern {
+            Some(pattern) => glob_match(pattern, key),
+            None => true,
+        })
+        .map(|(_, _, item)| item)
+        .collect();
+
+    (next_cursor, page)
+}
+
+fn hash_of(key: &[u8]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    key.hash(&mut hasher);
+    hasher.finish()
+}
@@ -0,0 +1,700 @@
+use std::{
+    cell::RefCell,
+    collections::{BTreeMap, HashMap},
+    fmt::{Display, Formatter},
+    sync::Arc,
+    time::Instant,
+};
+
+use mlua::{HookTriggers, Lua, Value, Variadic};
+
+use super::connection::Connection;
+use super::notify::keyspace_events;
+use super::shared::Shared;
+use super::*;
+use crate::command::table;
+use crate::glob::glob_match;
+use crate::protocol::{response::RedisResponse, Resp};
+
+/// scripts run so far or loaded, by the SHA1 of their body, and the one
+/// running if any
+#[derive(Default)]
+pub struct Scripts {
+    bodies: HashMap<String, Vec<u8>>,
+    running: Option<RunningScript>,
+}
+
+#[derive(Default)]
+struct RunningScript {
+    // a script that changed the dataset cannot be killed
+    wrote: bool,
+    killed: bool,
+}
+
+impl Scripts {
+    pub fn new() -> Self {
+        Scripts {
+            bodies: HashMap::new(),
+            running: None,
+        }
+    }
+
+    /// returns the SHA1 the script is cached under
+    pub fn insert(&mut self, body: &[u8]) -> String {
+        let sha = sha1_smol::Sha1::from(body).digest().to_string();
+        self.bodies.insert(sha.clone(), body.to_vec());
+        sha
+    }
+
+    pub fn get(&self, sha: &[u8]) -> Option<Vec<u8>> {
+        let sha = String::from_utf8_lossy(sha).to_lowercase();
+        self.bodies.get(&sha).cloned()
+    }
+
+    pub fn contains(&self, sha: &[u8]) -> bool {
+        let sha = String::from_utf8_lossy(sha).to_lowercase();
+        self.bodies.contains_key(&sha)
+    }
+
+    pub fn flush(&mut self) {
+        self.bodies.clear();
+    }
+
+    /// ask the running script to stop, as long as it did not write
+    pub fn kill(&mut self) -> Result<(), &'static str> {
+        match &mut self.running {
+            None => Err("NOTBUSY No scripts in execution right now."),
+            Some(script) if script.wrote => Err("UNKILLABLE Sorry the script already executed write commands against the dataset. You can either wait the script termination or kill the server in a hard way using the SHUTDOWN NOSAVE command."),
+            Some(script) => {
+                script.killed = true;
+                Ok(())
+            }
+        }
+    }
+
+    fn is_killed(&self) -> bool {
+        matches!(self.running, Some(RunningScript { killed: true, .. }))
+    }
+}
+
+/// libraries of functions loaded by FUNCTION LOAD, by name
+#[derive(Default)]
+pub struct Libraries {
+    libraries: BTreeMap<String, Library>,
+}
+
+struct Library {
+    code: Vec<u8>,
+    functions: BTreeMap<String, Function>,
+}
+
+struct Function {
+    description: Option<String>,
+    flags: Vec<String>,
+}
+
+impl Libraries {
+    pub fn new() -> Self {
+        Libraries {
+            libraries: BTreeMap::new(),
+        }
+    }
+
+    /// register the functions of the library, returning its name
+    pub fn load(&mut self, code: &[u8], replace: bool) -> Result<String, String> {
+        let lua = Lua::new();
+        let library = lua
+            .globals()
+            .set("redis", lua.create_table().map_err(|e| e.to_string())?)
+            .and_then(|_| load_library(&lua, code))
+            .and_then(|(name, functions)| {
+                let functions = functions
+                    .pairs::<String, mlua::Table>()
+                    .map(|pair| {
+                        let (name, function) = pair?;
+                        let function = Function {
+                            description: function.get("description")?,
+                            flags: function.get("flags")?,
+                        };
+                        Ok((name, function))
+                    })
+                    .collect::<mlua::Result<BTreeMap<_, _>>>()?;
+                Ok((name, functions))
+            });
+        let (name, functions) = match library {
+            Ok(library) => library,
+            Err(err) => {
+                return match error_message(&err) {
+                    message if message.starts_with("ERR ") => Err(message),
+                    message => Err(format!("ERR Error registering functions: {}", message)),
+                }
+            }
+        };
+
+        if self.libraries.contains_key(&name) && !replace {
+            return Err(format!("ERR Library '{}' already exists", name));
+        }
+        let other_libraries = self.libraries.iter().filter(|(other, _)| **other != name);
+        for (_, library) in other_libraries {
+            if let Some(function) = functions
+                .keys()
+                .find(|f| library.functions.contains_key(*f))
+            {
+                return Err(format!("ERR Function {} already exists", function));
+            }
+        }
+
+        let library = Library {
+            code: code.to_vec(),
+            functions,
+        };
+        self.libraries.insert(name.clone(), library);
+        Ok(name)
+    }
+
+    pub fn delete(&mut self, name: &[u8]) -> bool {
+        let name = String::from_utf8_lossy(name);
+        self.libraries.remove(name.as_ref()).is_some()
+    }
+
+    pub fn flush(&mut self) {
+        self.libraries.clear();
+    }
+
+    /// the code of the library holding the function, and whether the
+    /// function is flagged no-writes
+    pub fn find(&self, function: &str) -> Option<(Vec<u8>, bool)> {
+        self.libraries.values().find_map(|library| {
+            let flags = &library.functions.get(function)?.flags;
+            Some((library.code.clone(), flags.iter().any(|f| f == "no-writes")))
+        })
+    }
+
+    /// the libraries whose name match the pattern, as FUNCTION LIST replies
+    pub fn list(&self, pattern: Option<&[u8]>, with_code: bool) -> RedisResponse {
+        let bulk = |s: &str| RedisResponse::BulkString(s.as_bytes().to_vec());
+
+        let libraries = self
+            .libraries
+            .iter()
+            .filter(|(name, _)| match pattern {
+                Some(pattern) => glob_match(pattern, name.as_bytes()),
+                None => true,
+            })
+            .map(|(name, library)| {
+                let functions = library
+                    .functions
+                    .iter()
+                    .map(|(name, function)| {
+                        RedisResponse::Array(vec![
+                            bulk("name"),
+                            bulk(name),
+                            bulk("description"),
+                            function
+                                .description
+                                .as_deref()
+                                .map_or(RedisResponse::Nil, bulk),
+                            bulk("flags"),
+                            RedisResponse::Array(
+                                function.flags.iter().map(|flag| bulk(flag)).collect(),
+                            ),
+                        ])
+                    })
+                    .collect();
+
+                let mut reply = vec![
+                    bulk("library_name"),
+                    bulk(name),
+                    bulk("engine"),
+                    bulk("LUA"),
+                    bulk("functions"),
+                    RedisResponse::Array(functions),
+                ];
+                if with_code {
+                    reply.push(bulk("library_code"));
+                    reply.push(RedisResponse::BulkString(library.code.clone()));
+                }
+                RedisResponse::Array(reply)
+            })
+            .collect();
+
+        RedisResponse::Array(libraries)
+    }
+
+    /// the code of every library, each preceded by its length as 8 big endian
+    /// bytes
+    pub fn dump(&self) -> Vec<u8> {
+        let mut payload = vec![];
+        for library in self.libraries.values() {
+            payload.extend_from_slice(&(library.code.len() as u64).to_be_bytes());
+            payload.extend_from_slice(&library.code);
+        }
+        payload
+    }
+}
+
+/// run the code of a library given its `#!lua name=<library>` header,
+/// returning the name and the table of the functions it registered
+fn load_library<'lua>(lua: &'lua Lua, code: &[u8]) -> mlua::Result<(String, mlua::Table<'lua>)> {
+    let error = |message: String| mlua::Error::RuntimeError(format!("ERR {}", message));
+
+    if !code.starts_with(b"#!") {
+        return Err(error("Missing library metadata".to_string()));
+    }
+    let header_end = code.iter().position(|c| *c == b'\n').unwrap_or(code.len());
+    let header = String::from_utf8_lossy(&code[2..header_end]).to_string();
+    let mut header = header.split_whitespace();
+    match header.next() {
+        Some("lua") => {}
+        engine => {
+            let engine = engine.unwrap_or_default();
+            return Err(error(format!("Engine '{}' not found", engine)));
+        }
+    }
+    let mut name = None;
+    for metadata in header {
+        match metadata.strip_prefix("name=") {
+            Some(value) => name = Some(value.to_string()),
+            None => return Err(error(format!("Invalid metadata value given: {}", metadata))),
+        }
+    }
+    let name = match name {
+        Some(name) if is_valid_name(&name) => name,
+        Some(_) => {
+            return Err(error(
+                "Library names can only contain letters, numbers, or underscores(_) and must be at least one character long".to_string(),
+            ))
+        }
+        None => return Err(error("Library name was not given".to_string())),
+    };
+
+    lua.set_named_registry_value("functions", lua.create_table()?)?;
+    let redis = lua.globals().get::<_, mlua::Table>("redis")?;
+    redis.set("register_function", lua.create_function(register_function)?)?;
+
+    // the header is no Lua, the lines keeping their numbers
+    lua.load(&code[header_end..])
+        .set_name("@user_function")
+        .exec()?;
+
+    let functions = lua.named_registry_value::<mlua::Table>("functions")?;
+    match functions.clone().pairs::<Value, Value>().next() {
+        Some(_) => Ok((name, functions)),
+        None => Err(error("No functions registered".to_string())),
+    }
+}
+
+/// `redis.register_function(name, callback)`, or given a table with the
+/// function_name, callback, flags and description
+fn register_function(lua: &Lua, args: Variadic<Value>) -> mlua::Result<()> {
+    let error = |message: &str| mlua::Error::RuntimeError(format!("ERR {}", message));
+
+    let (name, callback, flags, description) = match args.as_slice() {
+        [Value::String(name), Value::Function(callback)] => {
+            (name.to_str()?.to_string(), callback.clone(), None, None)
+        }
+        [Value::Table(options)] => (
+            options.get::<_, String>("function_name")?,
+            options.get::<_, mlua::Function>("callback")?,
+            options.get::<_, Option<Vec<String>>>("flags")?,
+            options.get::<_, Option<String>>("description")?,
+        ),
+        _ => {
+            return Err(error(
+                "wrong number of arguments to redis.register_function",
+            ))
+        }
+    };
+
+    if !is_valid_name(&name) {
+        return Err(error("Function names can only contain letters, numbers, or underscores(_) and must be at least one character long"));
+    }
+    let flags = flags.unwrap_or_default();
+    let known_flags = ["no-writes", "allow-oom", "allow-stale", "no-cluster"];
+    if let Some(flag) = flags
+        .iter()
+        .find(|flag| !known_flags.contains(&flag.as_str()))
+    {
+        return Err(error(&format!("Unknown flag given: {}", flag)));
+    }
+
+    let functions = lua.named_registry_value::<mlua::Table>("functions")?;
+    if functions.contains_key(name.as_str())? {
+        return Err(error("Function already exists in the library"));
+    }
+    let function = lua.create_table()?;
+    function.set("callback", callback)?;
+    function.set("flags", flags)?;
+    function.set("description", description)?;
+    functions.set(name, function)
+}
+
+fn is_valid_name(name: &str) -> bool {
+    !name.is_empty() && name.bytes().all(|c| c.is_ascii_alphanumeric() || c == b'_')
+}
+
+/// error reply of a command run by `redis.call`, given back as is once it
+/// stops the script
+#[derive(Debug)]
+struct ErrorReply(String);
+
+impl Display for ErrorReply {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::error::Error for ErrorReply {}
+
+/// run the script with the KEYS and ARGV tables set, the databases being
+/// locked until it returns
+pub fn eval<T: Storage, L: Lock<Vec<T>>>(
+    databases: &L,
+    shared: &Arc<Shared>,
+    connection: &mut Connection,
+    script: &[u8],
+    keys: &[Vec<u8>],
+    args: &[Vec<u8>],
+) -> CommandResponse {
+    let sha = lock_then_release(&shared.scripts).insert(script);
+
+    let result = run(databases, shared, connection, false, |lua| {
+        let globals = lua.globals();
+        globals.set("KEYS", lua_strings(lua, keys)?)?;
+        globals.set("ARGV", lua_strings(lua, args)?)?;
+
+        let value = lua.load(script).set_name("@user_script").eval::<Value>()?;
+        Ok(lua_to_response(&value))
+    });
+
+    script_response(result, &sha)
+}
+
+/// call the function of a library with the keys and arguments, the
+/// databases being locked until it returns
+pub fn fcall<T: Storage, L: Lock<Vec<T>>>(
+    databases: &L,
+    shared: &Arc<Shared>,
+    connection: &mut Connection,
+    function: &[u8],
+    keys: &[Vec<u8>],
+    args: &[Vec<u8>],
+    read_only: bool,
+) -> CommandResponse {
+    let name = String::from_utf8_lossy(function).to_string();
+    let (code, no_writes) = match lock_then_release(&shared.functions).find(&name) {
+        Some(found) => found,
+        None => return RedisResponse::Error("ERR Function not found".to_string()).to_vec(),
+    };
+    if read_only && !no_writes {
+        return RedisResponse::Error(
+            "ERR Can not execute a script with write flag using *_ro command.".to_string(),
+        )
+        .to_vec();
+    }
+
+    let result = run(databases, shared, connection, no_writes, |lua| {
+        let (_, functions) = load_library(lua, &code)?;
+        let callback = functions
+            .get::<_, mlua::Table>(name.as_str())?
+            .get::<_, mlua::Function>("callback")?;
+
+        let value =
+            callback.call::<_, Value>((lua_strings(lua, keys)?, lua_strings(lua, args)?))?;
+        Ok(lua_to_response(&value))
+    });
+
+    script_response(result, &name)
+}
+
+/// run a chunk with the `redis` table set, the commands it calls being
+/// refused if they write when the chunk is read only, and SELECT only
+/// switching the database for the rest of the chunk
+fn run<T: Storage, L: Lock<Vec<T>>>(
+    databases: &L,
+    shared: &Arc<Shared>,
+    connection: &mut Connection,
+    read_only: bool,
+    chunk: impl FnOnce(&Lua) -> mlua::Result<RedisResponse>,
+) -> mlua::Result<RedisResponse> {
+    let mut databases = lock_then_release(databases);
+    let databases = RefCell::new(&mut *databases);
+    let db = connection.db;
+    let connection = RefCell::new(connection);
+    lock_then_release(&shared.scripts).running = Some(RunningScript::default());
+
+    let run = |args: Variadic<Value>| {
+        let mut connection = connection.borrow_mut();
+        call(&databases, shared, &mut connection, read_only, args)
+    };
+
+    let lua = Lua::new();
+    // SCRIPT KILL is checked for every so often
+    let scripts = shared.clone();
+    lua.set_hook(
+        HookTriggers::new().every_nth_instruction(1000),
+        move |_, _| match lock_then_release(&scripts.scripts).is_killed() {
+            true => Err(mlua::Error::RuntimeError(
+                "Script killed by user with SCRIPT KILL...".to_string(),
+            )),
+            false => Ok(()),
+        },
+    );
+    let result = lua.scope(|scope| {
+        let redis = lua.create_table()?;
+        redis.set(
+            "call",
+            scope.create_function_mut(
+                |lua, args: Variadic<Value>| match RedisProtocolParser::parse(&run(args)) {
+                    Ok((Resp::Error(error), _)) => Err(mlua::Error::external(ErrorReply(
+                        String::from_utf8_lossy(error).to_string(),
+                    ))),
+                    Ok((resp, _)) => resp_to_lua(lua, resp),
+                    Err(_) => Ok(Value::Nil),
+                },
+            )?,
+        )?;
+        redis.set(
+            "pcall",
+            scope.create_function_mut(
+                |lua, args: Variadic<Value>| match RedisProtocolParser::parse(&run(args)) {
+                    Ok((resp, _)) => resp_to_lua(lua, resp),
+                    Err(_) => Ok(Value::Nil),
+                },
+            )?,
+        )?;
+        redis.set(
+            "error_reply",
+            lua.create_function(|lua, message: mlua::String| {
+                let reply = lua.create_table()?;
+                reply.set("err", message)?;
+                Ok(reply)
+            })?,
+        )?;
+        redis.set(
+            "status_reply",
+            lua.create_function(|lua, message: mlua::String| {
+                let reply = lua.create_table()?;
+                reply.set("ok", message)?;
+                Ok(reply)
+            })?,
+        )?;
+        redis.set(
+            "sha1hex",
+            lua.create_function(|_, body: mlua::String| {
+                Ok(sha1_smol::Sha1::from(body.as_bytes()).digest().to_string())
+            })?,
+        )?;
+        // there is no log to write to
+        redis.set("log", lua.create_function(|_, _: Variadic<Value>| Ok(()))?)?;
+        redis.set("LOG_DEBUG", 0)?;
+        redis.set("LOG_VERBOSE", 1)?;
+        redis.set("LOG_NOTICE", 2)?;
+        redis.set("LOG_WARNING", 3)?;
+        lua.globals().set("redis", redis)?;
+
+        chunk(&lua)
+    });
+    lock_then_release(&shared.scripts).running = None;
+    connection.borrow_mut().db = db;
+
+    result
+}
+
+/// the reply of the script, or the error that stopped it
+fn script_response(result: mlua::Result<RedisResponse>, name: &str) -> CommandResponse {
+    match result {
+        Ok(response) => response.to_vec(),
+        Err(err) => {
+            let error = match error_reply(&err) {
+                Some(reply) => format!("{} script: {}", reply, name),
+                None => format!("ERR {} script: {}", error_message(&err), name),
+            };
+            RedisResponse::Error(error).to_vec()
+        }
+    }
+}
+
+/// run a command given to `redis.call` or `redis.pcall`, failing with an
+/// error reply
+fn call<T: Storage>(
+    databases: &RefCell<&mut Vec<T>>,
+    shared: &Arc<Shared>,
+    connection: &mut Connection,
+    read_only: bool,
+    args: Variadic<Value>,
+) -> CommandResponse {
+    if args.is_empty() {
+        return RedisResponse::Error(
+            "ERR Please specify at least one argument for this redis lib call".to_string(),
+        )
+        .to_vec();
+    }
+
+    let args = args
+        .iter()
+        .map(|arg| match arg {
+            Value::String(arg) => Some(arg.as_bytes().to_vec()),
+            Value::Integer(arg) => Some(arg.to_string().into_bytes()),
+            Value::Number(arg) => Some(arg.to_string().into_bytes()),
+            _ => None,
+        })
+        .collect::<Option<Vec<_>>>();
+
+    let args = match args {
+        Some(args) => args,
+        None => {
+            return RedisResponse::Error(
+                "ERR Lua redis lib command arguments must be strings or integers".to_string(),
+            )
+            .to_vec()
+        }
+    };
+
+    let command = match Command::parse(args.iter().map(|arg| Resp::BulkString(arg)).collect()) {
+        Ok(command) => command,
+        Err(err) => return format!("-ERR {}\r\n", err).as_bytes().to_vec(),
+    };
+
+    let name = String::from_utf8_lossy(&args[0]).to_lowercase();
+    let subcommand = args
+        .get(1)
+        .map(|arg| String::from_utf8_lossy(arg).to_lowercase());
+    let allowed =
+        lock_then_release(&shared.acl).check(connection, &command, &name, subcommand.as_deref());
+    if let Err(error) = allowed {
+        return RedisResponse::Error(error).to_vec();
+    }
+    if !command.is_allowed_in_script() {
+        return RedisResponse::Error(
+            "ERR This Redis command is not allowed from script".to_string(),
+        )
+        .to_vec();
+    }
+    if read_only && command.is_write() {
+        return RedisResponse::Error(
+            "ERR Write commands are not allowed from read-only scripts.".to_string(),
+        )
+        .to_vec();
+    }
+    if command.is_write() && replication::refuses_writes(shared) {
+        return RedisResponse::Error(replication::READONLY_ERROR.to_string()).to_vec();
+    }
+
+    let command = command.without_blocking();
+    let db = connection.db;
+    let monitors = lock_then_release(&shared.monitors);
+    if !monitors.is_empty() {
+        monitors.feed(db, "lua", &args);
+    }
+    drop(monitors);
+    let started = Instant::now();
+    let response = execute(databases, shared, connection, &command);
+    let full_name = match table::lookup(&name, subcommand.as_deref()) {
+        Some(info) => info.name.to_string(),
+        None => name,
+    };
+    lock_then_release(&shared.stats).record(
+        &full_name,
+        started.elapsed(),
+        response.first() == Some(&b'-'),
+    );
+    if !keyspace_events(&command, &response).is_empty() {
+        if let Some(script) = &mut lock_then_release(&shared.scripts).running {
+            script.wrote = true;
+        }
+    }
+    let evicts = lock_then_release(&shared.config).integer("maxmemory") > 0;
+    publish_keyspace_events(
+        databases, db, shared, connection, &command, &response, evicts,
+    );
+    response
+}
+
+fn lua_strings<'lua>(lua: &'lua Lua, values: &[Vec<u8>]) -> mlua::Result<mlua::Table<'lua>> {
+    lua.create_sequence_from(
+        values
+            .iter()
+            .map(|value| lua.create_string(value))
+            .collect::<mlua::Result<Vec<_>>>()?,
+    )
+}
+
+/// a reply to a command run by the script, as Redis converts it
+fn resp_to_lua<'lua>(lua: &'lua Lua, resp: Resp) -> mlua::Result<Value<'lua>> {
+    let value = match resp {
+        Resp::String(status) => {
+            let reply = lua.create_table()?;
+            reply.set("ok", lua.create_string(status)?)?;
+            Value::Table(reply)
+        }
+        Resp::Error(error) => {
+            let reply = lua.create_table()?;
+            reply.set("err", lua.create_string(error)?)?;
+            Value::Table(reply)
+        }
+        Resp::Integer(integer) => {
+            let integer = std::str::from_utf8(integer).unwrap_or("0");
+            Value::Integer(integer.parse().unwrap_or(0))
+        }
+        Resp::BulkString(bytes) => Value::String(lua.create_string(bytes)?),
+        Resp::Array(values) => Value::Table(
+            lua.create_sequence_from(
+                values
+                    .into_iter()
+                    .map(|value| resp_to_lua(lua, value))
+                    .collect::<mlua::Result<Vec<_>>>()?,
+            )?,
+        ),
+        Resp::Nil => Value::Boolean(false),
+    };
+
+    Ok(value)
+}
+
+/// the value returned by the script, as Redis converts it
+fn lua_to_response(value: &Value) -> RedisResponse {
+    match value {
+        Value::Boolean(true) => RedisResponse::Integer(1),
+        Value::Integer(integer) => RedisResponse::Integer(*integer),
+        // the decimal part is dropped
+        Value::Number(number) => RedisResponse::Integer(*number as i64),
+        Value::String(bytes) => RedisResponse::BulkString(bytes.as_bytes().to_vec()),
+        Value::Table(table) => {
+            if let Ok(mlua::Value::String(error)) = table.raw_get("err") {
+                return RedisResponse::Error(error.to_string_lossy().to_string());
+            }
+            if let Ok(mlua::Value::String(status)) = table.raw_get("ok") {
+                return RedisResponse::Status(status.to_string_lossy().to_string());
+            }
+
+            // the array stops at the first nil
+            let values = table
+                .clone()
+                .sequence_values::<Value>()
+                .map_while(|value| value.ok())
+                .map(|value| lua_to_response(&value))
+                .collect();
+            RedisResponse::Array(values)
+        }
+        _ => RedisResponse::Nil,
+    }
+}
+
+/// the error reply of the command that stopped the script, if any
+fn error_reply(err: &mlua::Error) -> Option<String> {
+    match err {
+        mlua::Error::CallbackError { cause, .. } => error_reply(cause),
+        mlua::Error::ExternalError(err) => err.downcast_ref::<ErrorReply>().map(|e| e.to_string()),
+        _ => None,
+    }
+}
+
+fn error_message(err: &mlua::Error) -> String {
+    match err {
+        mlua::Error::CallbackError { cause, .. } => error_message(cause),
+        mlua::Error::RuntimeError(message) => message.clone(),
+        mlua::Error::SyntaxError { message, .. } => message.clone(),
+        err => err.to_string(),
+    }
+}
@@ -0,0 +1,120 @@
+use super::lock_then_release;
+use super::shared::Shared;
+use crate::protocol::response::RedisResponse;
+
+// the ip the server tells about itself as a master, the applications
+// reaching it on the host its sentinel listener runs on
+const LOCAL_IP: &str = "127.0.0.1";
+// the channel the sentinel listener publishes the changes of master on
+const SWITCH_MASTER: &[u8] = b"+switch-master";
+// what the sentinel listener replies about the master it doesn't know
+pub const NO_SUCH_MASTER_ERROR: &str = "ERR No such master with that name";
+
+/// the master the sentinel listener tells the applications about, the
+/// server itself or the master it replicates
+#[derive(Default)]
+pub struct Sentinel {
+    // the address last told about, and how many times it changed since
+    address: Option<(String, u16)>,
+    pub config_epoch: u64,
+}
+
+impl Sentinel {
+    pub fn new() -> Self {
+        Sentinel::default()
+    }
+}
+
+/// whether the server was given a sentinel listener
+pub fn is_enabled(shared: &Shared) -> bool {
+    lock_then_release(&shared.config).integer("sentinel-port") != 0
+}
+
+/// the name the applications know the master by
+pub fn master_name(shared: &Shared) -> Vec<u8> {
+    lock_then_release(&shared.config)
+        .value("sentinel-master-name")
+        .to_vec()
+}
+
+/// the address of the master, the one the server replicates if it is a
+/// replica
+pub fn master_address(shared: &Shared) -> (String, u16) {
+    let master = lock_then_release(&shared.replication).master.clone();
+    match master {
+        Some(master) => master,
+        None => {
+            let port = lock_then_release(&shared.config).integer("port") as u16;
+            (LOCAL_IP.to_string(), port)
+        }
+    }
+}
+
+/// what SENTINEL MASTER and SENTINEL MASTERS tell about the master, as the
+/// fields of a sentinel that is the only one to monitor it
+pub fn master(shared: &Shared) -> RedisResponse {
+    let (ip, port) = master_address(shared);
+    let replication = lock_then_release(&shared.replication);
+    // the replicas of the master the server replicates are not known, the
+    // server being one of them
+    let replicas = match replication.master {
+        Some(_) => 1,
+        None => replication.replicas().len(),
+    };
+    drop(replication);
+    let config_epoch = lock_then_release(&shared.sentinel).config_epoch;
+
+    let fields = vec![
+        ("name", master_name(shared)),
+        ("ip", ip.into_bytes()),
+        ("port", port.to_string().into_bytes()),
+        ("flags", b"master".to_vec()),
+        ("link-pending-commands", b"0".to_vec()),
+        ("link-refcount", b"1".to_vec()),
+        ("role-reported", b"master".to_vec()),
+        ("config-epoch", config_epoch.to_string().into_bytes()),
+        ("num-slaves", replicas.to_string().into_bytes()),
+        ("num-other-sentinels", b"0".to_vec()),
+        ("quorum", b"1".to_vec()),
+        ("down-after-milliseconds", b"30000".to_vec()),
+        ("failover-timeout", b"180000".to_vec()),
+        ("parallel-syncs", b"1".to_vec()),
+    ];
+    RedisResponse::Map(
+        fields
+            .into_iter()
+            .map(|(field, value)| {
+                (
+                    RedisResponse::BulkString(field.as_bytes().to_vec()),
+                    RedisResponse::BulkString(value),
+                )
+            })
+            .collect(),
+    )
+}
+
+/// publish `+switch-master` once the master changed, as a sentinel does
+/// once it failed over, called over and over while the server runs
+pub fn cron(shared: &Shared) {
+    if !is_enabled(shared) {
+        return;
+    }
+    let address = master_address(shared);
+    let mut sentinel = lock_then_release(&shared.sentinel);
+    let previous = match sentinel.address.replace(address.clone()) {
+        Some(previous) if previous != address => previous,
+        _ => return,
+    };
+    sentinel.config_epoch += 1;
+    drop(sentinel);
+
+    let message = format!(
+        "{} {} {} {} {}",
+        String::from_utf8_lossy(&master_name(shared)),
+        previous.0,
+        previous.1,
+        address.0,
+        address.1
+    );
+    lock_then_release(&shared.pubsub).publish(SWITCH_MASTER, message.as_bytes());
+}
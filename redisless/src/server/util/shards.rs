@@ -0,0 +1,474 @@
+use std::borrow::Cow;
+use std::collections::HashSet;
+use std::io;
+use std::ops::{Deref, DerefMut};
+use std::sync::{RwLock, RwLockReadGuard, RwLockWriteGuard};
+use std::thread;
+use std::time::Duration;
+
+use super::cluster::key_slot;
+use super::{Guard, Lock};
+use crate::command::Command;
+use crate::storage::models::{
+    bitfield::BitFieldOp,
+    expiry::{Expiry, ExpiryCondition},
+    hyperloglog::HyperLogLog,
+    redis_set,
+    redis_sorted_set::Scores,
+    redis_stream::{StreamFields, StreamId, XAddId},
+    DataType, RedisHashMap, RedisSet, RedisSortedSet, RedisStream, StoredValue,
+};
+use crate::storage::{EvictionPolicy, KeyspaceStats, MemoryStats, Sibling, Storage};
+
+/// the databases of a server split into shards by the slot of their keys,
+/// each shard holding a storage of every database behind a lock of its own,
+/// so that the commands whose keys are in a single shard don't wait for the
+/// ones in the others, and the ones only reading them for none
+pub struct Shards<T> {
+    shards: Vec<RwLock<Vec<T>>>,
+}
+
+impl<T: Storage + Sibling> Shards<T> {
+    /// the databases spread over the shards, the storage given being the
+    /// first database of the first shard and each of the others a sibling
+    /// of it, the keys of the storages being moved to the shard they
+    /// belong to
+    pub fn new(storage: T, databases: usize, count: usize) -> io::Result<Self> {
+        let (databases, count) = (databases.max(1), count.max(1));
+        let mut storages = vec![storage];
+        for index in 1..databases * count {
+            let sibling = storages[0].sibling(index)?;
+            storages.push(sibling);
+        }
+
+        let mut storages = storages.into_iter();
+        let shards = Shards {
+            shards: (0..count)
+                .map(|_| RwLock::new(storages.by_ref().take(databases).collect()))
+                .collect(),
+        };
+        if count > 1 {
+            let mut databases = shards.acquire();
+            for storage in databases.iter_mut() {
+                storage.rebalance();
+            }
+        }
+        Ok(shards)
+    }
+}
+
+impl<T> Shards<T> {
+    /// every shard on its own, for what goes through all the keys to lock
+    /// one shard at a time rather than keeping the commands on the others
+    /// waiting
+    pub fn each(&self) -> impl Iterator<Item = Shard<'_, T>> {
+        (0..self.shards.len()).map(move |index| Shard {
+            shards: self,
+            index,
+        })
+    }
+
+    /// the shard holding the keys of the command, None if they are in
+    /// several or if it has none, or may reach keys besides the ones it
+    /// names, such as the scripts do
+    pub fn shard_of(&self, command: &Command) -> Option<Shard<'_, T>> {
+        if matches!(
+            command,
+            Command::Eval(..) | Command::EvalSha(..) | Command::FCall(..) | Command::Sort(..)
+        ) {
+            return None;
+        }
+
+        let mut keys = command.keys().into_iter();
+        let index = shard_index(keys.next()?, self.shards.len());
+        match keys.all(|key| shard_index(key, self.shards.len()) == index) {
+            true => Some(Shard {
+                shards: self,
+                index,
+            }),
+            false => None,
+        }
+    }
+}
+
+/// every shard locked at once, each database being seen as a storage
+/// spanning all of them, the storages being put back in their shard once
+/// dropped
+impl<T: Storage> Lock<Vec<ShardedStorage<T>>> for Shards<T> {
+    fn acquire(&self) -> Guard<'_, Vec<ShardedStorage<T>>> {
+        // always in the same order, so that two of them never wait for
+        // each other
+        let guards = self
+            .shards
+            .iter()
+            .map(|shard| match shard.acquire() {
+                Guard::RwLock(guard) => guard,
+                _ => unreachable!("a shard is acquired for writing"),
+            })
+            .collect();
+        all_shards(guards)
+    }
+}
+
+/// take the databases out of the shards locked, each database being the
+/// storages it has in every shard
+fn all_shards<T: Storage>(
+    mut guards: Vec<RwLockWriteGuard<'_, Vec<T>>>,
+) -> Guard<'_, Vec<ShardedStorage<T>>> {
+    let mut databases = vec![];
+    for guard in guards.iter_mut() {
+        for (db, storage) in std::mem::take(&mut **guard).into_iter().enumerate() {
+            if databases.len() == db {
+                databases.push(ShardedStorage { shards: vec![] });
+            }
+            databases[db].shards.push(storage);
+        }
+    }
+    Guard::Shards(Box::new(AllShards { guards, databases }))
+}
+
+/// the databases of the shards, taken out of them while they are locked
+struct AllShards<'a, T> {
+    guards: Vec<RwLockWriteGuard<'a, Vec<T>>>,
+    databases: Vec<ShardedStorage<T>>,
+}
+
+impl<T> Deref for AllShards<'_, T> {
+    type Target = Vec<ShardedStorage<T>>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.databases
+    }
+}
+
+impl<T> DerefMut for AllShards<'_, T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.databases
+    }
+}
+
+impl<T> Drop for AllShards<'_, T> {
+    fn drop(&mut self) {
+        // the databases swapped by SWAPDB are swapped in every shard
+        for database in self.databases.drain(..) {
+            for (guard, storage) in self.guards.iter_mut().zip(database.shards) {
+                guard.push(storage);
+            }
+        }
+    }
+}
+
+/// the databases of a single shard, as a command whose keys are all in it
+/// locks them
+pub struct Shard<'a, T> {
+    shards: &'a Shards<T>,
+    index: usize,
+}
+
+impl<T> Shard<'_, T> {
+    /// the databases of the shard, None if it is held, such as by a script
+    /// that runs for long
+    pub fn try_acquire(&self) -> Option<Guard<'_, Vec<T>>> {
+        let guard = self.shards.shards[self.index].try_write().ok()?;
+        Some(Guard::RwLock(guard))
+    }
+
+    /// the databases of the shard locked for reading alone, the storages
+    /// being shared with the other readers
+    pub fn read(&self) -> RwLockReadGuard<'_, Vec<T>> {
+        loop {
+            match self.shards.shards[self.index].read() {
+                Ok(guard) => return guard,
+                Err(_) => thread::sleep(Duration::from_millis(10)),
+            }
+        }
+    }
+}
+
+impl<T> Lock<Vec<T>> for Shard<'_, T> {
+    fn acquire(&self) -> Guard<'_, Vec<T>> {
+        self.shards.shards[self.index].acquire()
+    }
+}
+
+/// the shard the key is in, keys sharing a hash tag being in the same one
+fn shard_index(key: &[u8], count: usize) -> usize {
+    key_slot(key) as usize % count
+}
+
+/// a database whose keys are spread over the storages of the shards, each
+/// key being read and written in the one of its shard
+pub struct ShardedStorage<T> {
+    shards: Vec<T>,
+}
+
+impl<T: Storage> ShardedStorage<T> {
+    fn shard(&mut self, key: &[u8]) -> &mut T {
+        let index = shard_index(key, self.shards.len());
+        &mut self.shards[index]
+    }
+
+    /// move the keys of each storage that belong to another shard to it
+    fn rebalance(&mut self) {
+        for index in 0..self.shards.len() {
+            for (key, _) in self.shards[index].snapshot() {
+                if shard_index(&key, self.shards.len()) != index {
+                    if let Some(value) = self.shards[index].take(&key) {
+                        self.shard(&key).put(&key, value);
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl<T: Storage> Storage for ShardedStorage<T> {
+    fn write(&mut self, key: &[u8], value: &[u8]) {
+        self.shard(key).write(key, value)
+    }
+
+    fn setbit(&mut self, key: &[u8], offset: usize, bit: bool) -> u8 {
+        self.shard(key).setbit(key, offset, bit)
+    }
+
+    fn bitfield(&mut self, key: &[u8], operations: &[BitFieldOp]) -> Vec<Option<i64>> {
+        self.shard(key).bitfield(key, operations)
+    }
+
+    fn pfadd(&mut self, key: &[u8], elements: &[Vec<u8>]) -> Option<u32> {
+        self.shard(key).pfadd(key, elements)
+    }
+
+    fn pfmerge(&mut self, key: &[u8], hyperloglog: &HyperLogLog) -> Option<()> {
+        self.shard(key).pfmerge(key, hyperloglog)
+    }
+
+    fn hwrite(&mut self, key: &[u8], field: &[u8], value: &[u8]) -> u32 {
+        self.shard(key).hwrite(key, field, value)
+    }
+
+    fn sadd(&mut self, key: &[u8], member: &[u8]) -> u32 {
+        self.shard(key).sadd(key, member)
+    }
+
+    fn swrite(&mut self, key: &[u8], members: HashSet<Vec<u8>>) {
+        self.shard(key).swrite(key, members)
+    }
+
+    fn zadd(&mut self, key: &[u8], score: f64, member: &[u8]) -> u32 {
+        self.shard(key).zadd(key, score, member)
+    }
+
+    fn xadd(&mut self, key: &[u8], id: XAddId, fields: StreamFields) -> Option<StreamId> {
+        self.shard(key).xadd(key, id, fields)
+    }
+
+    fn expire(&mut self, key: &[u8], expiry: Expiry) -> u32 {
+        self.shard(key).expire(key, expiry)
+    }
+
+    fn hexpire(
+        &mut self,
+        key: &[u8],
+        field: &[u8],
+        expiry: Expiry,
+        condition: Option<ExpiryCondition>,
+    ) -> i64 {
+        self.shard(key).hexpire(key, field, expiry, condition)
+    }
+
+    fn hpttl(&mut self, key: &[u8], field: &[u8]) -> i64 {
+        self.shard(key).hpttl(key, field)
+    }
+
+    fn hpersist(&mut self, key: &[u8], field: &[u8]) -> i64 {
+        self.shard(key).hpersist(key, field)
+    }
+
+    fn read(&self, key: &[u8]) -> Option<Cow<'_, [u8]>> {
+        self.shards[shard_index(key, self.shards.len())].read(key)
+    }
+
+    fn hread(&mut self, key: &[u8], field: &[u8]) -> Option<&[u8]> {
+        self.shard(key).hread(key, field)
+    }
+
+    fn hread_all(&mut self, key: &[u8]) -> Option<&RedisHashMap> {
+        self.shard(key).hread_all(key)
+    }
+
+    fn sread(&mut self, key: &[u8]) -> Option<&RedisSet> {
+        self.shard(key).sread(key)
+    }
+
+    fn sintercard(&mut self, keys: &[Vec<u8>], limit: usize) -> usize {
+        let count = self.shards.len();
+        let index = match keys.first() {
+            Some(key) => shard_index(key, count),
+            None => return 0,
+        };
+        if keys.iter().all(|key| shard_index(key, count) == index) {
+            return self.shards[index].sintercard(keys, limit);
+        }
+
+        // the sets of the other shards can't be borrowed together
+        let sets = keys
+            .iter()
+            .map(|key| self.sread(key).map(|set| set.data.clone()))
+            .collect::<Option<Vec<_>>>();
+        match sets {
+            Some(sets) => {
+                redis_set::intersection_cardinality(&sets.iter().collect::<Vec<_>>(), limit)
+            }
+            None => 0, // a missing key is an empty set
+        }
+    }
+
+    fn zread(&mut self, key: &[u8]) -> Option<&RedisSortedSet> {
+        self.shard(key).zread(key)
+    }
+
+    fn zwrite(&mut self, key: &[u8], members: Scores) {
+        self.shard(key).zwrite(key, members)
+    }
+
+    fn xread(&mut self, key: &[u8]) -> Option<&RedisStream> {
+        self.shard(key).xread(key)
+    }
+
+    fn xread_mut(&mut self, key: &[u8]) -> Option<&mut RedisStream> {
+        self.shard(key).xread_mut(key)
+    }
+
+    fn xcreate(&mut self, key: &[u8]) {
+        self.shard(key).xcreate(key)
+    }
+
+    fn remove(&mut self, key: &[u8]) -> u32 {
+        self.shard(key).remove(key)
+    }
+
+    fn take(&mut self, key: &[u8]) -> Option<StoredValue> {
+        self.shard(key).take(key)
+    }
+
+    fn copy(&mut self, key: &[u8]) -> Option<StoredValue> {
+        self.shard(key).copy(key)
+    }
+
+    fn put(&mut self, key: &[u8], value: StoredValue) {
+        self.shard(key).put(key, value)
+    }
+
+    fn srem(&mut self, key: &[u8], member: &[u8]) -> u32 {
+        self.shard(key).srem(key, member)
+    }
+
+    fn spop(&mut self, key: &[u8], count: usize) -> Vec<Vec<u8>> {
+        self.shard(key).spop(key, count)
+    }
+
+    fn zrem(&mut self, key: &[u8], member: &[u8]) -> u32 {
+        self.shard(key).zrem(key, member)
+    }
+
+    fn zpop(&mut self, key: &[u8], count: usize, max: bool) -> Vec<(Vec<u8>, f64)> {
+        self.shard(key).zpop(key, count, max)
+    }
+
+    fn contains(&self, key: &[u8]) -> bool {
+        self.shards[shard_index(key, self.shards.len())].contains(key)
+    }
+
+    fn type_of(&mut self, key: &[u8]) -> Option<DataType> {
+        self.shard(key).type_of(key)
+    }
+
+    fn take_expired(&mut self) -> Vec<Vec<u8>> {
+        self.shards
+            .iter_mut()
+            .flat_map(|storage| storage.take_expired())
+            .collect()
+    }
+
+    fn lapsed(&self) -> bool {
+        self.shards.iter().any(|storage| storage.lapsed())
+    }
+
+    /// up to count keys of each shard in turn, until count were looked at
+    fn sample_expired(&mut self, count: usize) -> (usize, usize) {
+        let (mut sampled, mut expired) = (0, 0);
+        for storage in self.shards.iter_mut() {
+            if sampled == count {
+                break;
+            }
+            let (shard_sampled, shard_expired) = storage.sample_expired(count - sampled);
+            sampled += shard_sampled;
+            expired += shard_expired;
+        }
+        (sampled, expired)
+    }
+
+    fn version(&mut self, key: &[u8]) -> u64 {
+        self.shard(key).version(key)
+    }
+
+    fn memory_usage(&mut self, key: &[u8]) -> Option<usize> {
+        self.shard(key).memory_usage(key)
+    }
+
+    fn memory_stats(&mut self) -> MemoryStats {
+        let mut stats = MemoryStats::default();
+        for storage in self.shards.iter_mut() {
+            let shard = storage.memory_stats();
+            stats.keys += shard.keys;
+            stats.overhead += shard.overhead;
+            stats.dataset += shard.dataset;
+        }
+        stats
+    }
+
+    fn keyspace_stats(&mut self) -> KeyspaceStats {
+        let mut stats = KeyspaceStats::default();
+        let mut ttl = 0;
+        for storage in self.shards.iter_mut() {
+            let shard = storage.keyspace_stats();
+            stats.keys += shard.keys;
+            stats.expires += shard.expires;
+            ttl += shard.avg_ttl * shard.expires as u64;
+        }
+        if stats.expires > 0 {
+            stats.avg_ttl = ttl / stats.expires as u64;
+        }
+        stats
+    }
+
+    fn snapshot(&mut self) -> Vec<(Vec<u8>, StoredValue)> {
+        self.shards
+            .iter_mut()
+            .flat_map(|storage| storage.snapshot())
+            .collect()
+    }
+
+    fn access(&mut self, key: &[u8]) {
+        self.shard(key).access(key)
+    }
+
+    fn used_memory(&mut self) -> usize {
+        self.shards
+            .iter_mut()
+            .map(|storage| storage.used_memory())
+            .sum()
+    }
+
+    fn eviction_candidate(
+        &mut self,
+        policy: EvictionPolicy,
+        samples: usize,
+    ) -> Option<(Vec<u8>, u64)> {
+        self.shards
+            .iter_mut()
+            .filter_map(|storage| storage.eviction_candidate(policy, samples))
+            .max_by_key(|(_, score)| *score)
+    }
+}
@@ -0,0 +1,81 @@
+use std::sync::atomic::AtomicBool;
+use std::sync::Mutex;
+
+use super::acl::Acl;
+use super::aof::Aof;
+use super::clients::Clients;
+use super::cluster::Cluster;
+use super::config::Config;
+use super::events::Events;
+use super::expire::Expire;
+use super::latency::Latency;
+use super::monitor::Monitors;
+use super::persistence::Persistence;
+use super::pubsub::PubSub;
+use super::replication::Replication;
+#[cfg(feature = "scripting")]
+use super::scripting::{Libraries, Scripts};
+use super::sentinel::Sentinel;
+use super::slowlog::SlowLog;
+use super::stats::Stats;
+use super::tracking::Tracking;
+
+/// what the connections of a server share besides the storage
+#[derive(Default)]
+pub struct Shared {
+    pub acl: Mutex<Acl>,
+    pub aof: Mutex<Aof>,
+    pub clients: Mutex<Clients>,
+    pub cluster: Mutex<Cluster>,
+    pub config: Mutex<Config>,
+    // the channels of the application embedding the server
+    pub events: Events,
+    pub expire: Mutex<Expire>,
+    pub latency: Mutex<Latency>,
+    pub monitors: Mutex<Monitors>,
+    pub persistence: Mutex<Persistence>,
+    pub pubsub: Mutex<PubSub>,
+    pub replication: Mutex<Replication>,
+    pub sentinel: Mutex<Sentinel>,
+    pub slowlog: Mutex<SlowLog>,
+    pub stats: Mutex<Stats>,
+    // whether keys are removed in the background once expired, rather than
+    // only once accessed
+    pub active_expire: AtomicBool,
+    // set by SHUTDOWN for the listener to stop
+    pub shutdown: AtomicBool,
+    pub tracking: Mutex<Tracking>,
+    #[cfg(feature = "scripting")]
+    pub scripts: Mutex<Scripts>,
+    #[cfg(feature = "scripting")]
+    pub functions: Mutex<Libraries>,
+}
+
+impl Shared {
+    pub fn new() -> Self {
+        Shared {
+            acl: Mutex::new(Acl::new(None)),
+            aof: Mutex::new(Aof::new()),
+            clients: Mutex::new(Clients::new()),
+            cluster: Mutex::new(Cluster::new()),
+            config: Mutex::new(Config::new()),
+            events: Events::new(),
+            expire: Mutex::new(Expire::new()),
+            latency: Mutex::new(Latency::new()),
+            monitors: Mutex::new(Monitors::new()),
+            persistence: Mutex::new(Persistence::new()),
+            pubsub: Mutex::new(PubSub::new()),
+            replication: Mutex::new(Replication::new()),
+            sentinel: Mutex::new(Sentinel::new()),
+            slowlog: Mutex::new(SlowLog::new()),
+            stats: Mutex::new(Stats::new()),
+            active_expire: AtomicBool::new(true),
+            shutdown: AtomicBool::new(false),
+            tracking: Mutex::new(Tracking::new()),
+            #[cfg(feature = "scripting")]
+            scripts: Mutex::new(Scripts::new()),
+            #[cfg(feature = "scripting")]
+            functions: Mutex::new(Libraries::new()),
+        }
+    }
+}
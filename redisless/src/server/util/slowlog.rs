@@ -0,0 +1,116 @@
+use std::collections::VecDeque;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::protocol::response::RedisResponse;
+
+// arguments of a command, and bytes of an argument, kept by an entry
+const MAX_ARGS: usize = 32;
+const MAX_ARG_LEN: usize = 128;
+
+/// a command that took longer than the threshold of the slow log
+struct Entry {
+    id: u64,
+    // seconds since the epoch
+    timestamp: u64,
+    duration: Duration,
+    args: Vec<Vec<u8>>,
+    addr: String,
+    name: Vec<u8>,
+}
+
+/// the latest commands that were slow, the newest first, read by SLOWLOG
+/// GET
+#[derive(Default)]
+pub struct SlowLog {
+    entries: VecDeque<Entry>,
+    next_id: u64,
+}
+
+impl SlowLog {
+    pub fn new() -> Self {
+        SlowLog {
+            entries: VecDeque::new(),
+            next_id: 0,
+        }
+    }
+
+    /// record a command run by a client, the oldest entries being dropped
+    /// past the maximum length, its arguments being shortened if too many
+    /// or too long
+    pub fn record(
+        &mut self,
+        duration: Duration,
+        args: &[Vec<u8>],
+        addr: &str,
+        name: &[u8],
+        max_len: usize,
+    ) {
+        let mut kept = args
+            .iter()
+            .take(match args.len() > MAX_ARGS {
+                true => MAX_ARGS - 1,
+                false => MAX_ARGS,
+            })
+            .map(|arg| match arg.len() > MAX_ARG_LEN {
+                true => {
+                    let more = format!("... ({} more bytes)", arg.len() - MAX_ARG_LEN);
+                    [&arg[..MAX_ARG_LEN], more.as_bytes()].concat()
+                }
+                false => arg.clone(),
+            })
+            .collect::<Vec<_>>();
+        if args.len() > MAX_ARGS {
+            let more = format!("... ({} more arguments)", args.len() - MAX_ARGS + 1);
+            kept.push(more.into_bytes());
+        }
+
+        self.entries.push_front(Entry {
+            id: self.next_id,
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs(),
+            duration,
+            args: kept,
+            addr: addr.to_string(),
+            name: name.to_vec(),
+        });
+        self.entries.truncate(max_len);
+        self.next_id += 1;
+    }
+
+    /// the newest entries, all of them when no count is given
+    pub fn get(&self, count: Option<usize>) -> RedisResponse {
+        RedisResponse::Array(
+            self.entries
+                .iter()
+                .take(count.unwrap_or(self.entries.len()))
+                .map(|entry| {
+                    RedisResponse::Array(vec![
+                        RedisResponse::Integer(entry.id as i64),
+                        RedisResponse::Integer(entry.timestamp as i64),
+                        RedisResponse::Integer(entry.duration.as_micros() as i64),
+                        RedisResponse::Array(
+                            entry
+                                .args
+                                .iter()
+                                .map(|arg| RedisResponse::BulkString(arg.clone()))
+                                .collect(),
+                        ),
+                        RedisResponse::BulkString(entry.addr.as_bytes().to_vec()),
+                        RedisResponse::BulkString(entry.name.clone()),
+                    ])
+                })
+                .collect(),
+        )
+    }
+
+    /// the number of entries
+    pub fn count(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn reset(&mut self) {
+        self.entries.clear();
+    }
+}
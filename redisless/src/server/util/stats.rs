@@ -0,0 +1,117 @@
+use std::collections::BTreeMap;
+use std::time::{Duration, Instant};
+
+// significant digits the latencies of the commands are kept with, so that
+// their distribution takes little room however many calls there are
+const LATENCY_SIGNIFICANT_DIGITS: u32 = 3;
+
+/// counters of what the server did since it was built, reported by INFO
+pub struct Stats {
+    started: Instant,
+    pub connections: u64,
+    pub commands: u64,
+    // keys removed because they expired
+    pub expired_keys: u64,
+    // keys removed to free memory once the keys took more than maxmemory
+    pub evicted_keys: u64,
+    // keys read that existed, and that did not
+    pub keyspace_hits: u64,
+    pub keyspace_misses: u64,
+    // by the name of the command, its subcommand included
+    per_command: BTreeMap<String, CommandStats>,
+}
+
+/// the calls of a command, and how long they took
+#[derive(Default)]
+pub struct CommandStats {
+    pub calls: u64,
+    // microseconds spent on all the calls
+    pub usec: u64,
+    // refused before being run, such as by the ACL
+    pub rejected_calls: u64,
+    // run but replied an error
+    pub failed_calls: u64,
+    // how many calls took each latency, in microseconds
+    latencies: BTreeMap<u64, u64>,
+}
+
+impl CommandStats {
+    /// the latency in microseconds the given percentage of the calls took
+    /// at most
+    pub fn percentile(&self, percentage: f64) -> u64 {
+        let rank = (self.calls as f64 * percentage / 100.0).ceil().max(1.0) as u64;
+        let mut seen = 0;
+        for (latency, calls) in &self.latencies {
+            seen += calls;
+            if seen >= rank {
+                return *latency;
+            }
+        }
+
+        0
+    }
+}
+
+impl Stats {
+    pub fn new() -> Self {
+        Stats {
+            started: Instant::now(),
+            connections: 0,
+            commands: 0,
+            expired_keys: 0,
+            evicted_keys: 0,
+            keyspace_hits: 0,
+            keyspace_misses: 0,
+            per_command: BTreeMap::new(),
+        }
+    }
+
+    pub fn uptime(&self) -> Duration {
+        self.started.elapsed()
+    }
+
+    /// count a command that was run, given whether it replied an error
+    pub fn record(&mut self, name: &str, duration: Duration, failed: bool) {
+        self.commands += 1;
+
+        let usec = duration.as_micros() as u64;
+        let stats = self.per_command.entry(name.to_string()).or_default();
+        stats.calls += 1;
+        stats.usec += usec;
+        if failed {
+            stats.failed_calls += 1;
+        }
+        *stats.latencies.entry(round(usec)).or_default() += 1;
+    }
+
+    /// count a command that was refused before being run
+    pub fn reject(&mut self, name: &str) {
+        self.per_command
+            .entry(name.to_string())
+            .or_default()
+            .rejected_calls += 1;
+    }
+
+    /// the commands that were called or refused, by name
+    pub fn per_command(&self) -> impl Iterator<Item = (&String, &CommandStats)> {
+        self.per_command.iter()
+    }
+}
+
+impl Default for Stats {
+    fn default() -> Self {
+        Stats::new()
+    }
+}
+
+/// a latency with only its significant digits left, rounded up
+fn round(usec: u64) -> u64 {
+    let digits = usec.to_string().len() as u32;
+    match digits.checked_sub(LATENCY_SIGNIFICANT_DIGITS) {
+        Some(dropped) if dropped > 0 => {
+            let unit = 10u64.pow(dropped);
+            usec.div_ceil(unit) * unit
+        }
+        _ => usec,
+    }
+}
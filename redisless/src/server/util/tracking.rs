@@ -0,0 +1,132 @@
+use std::collections::{HashMap, HashSet};
+
+use crossbeam_channel::Sender;
+
+use super::connection::Connection;
+use super::pubsub::PubSub;
+use crate::command::client::TrackingOptions;
+use crate::protocol::response::RedisResponse;
+use crate::server::CommandResponse;
+
+// channel a RESP2 connection subscribes to for the invalidations redirected
+// to it
+pub const INVALIDATE_CHANNEL: &[u8] = b"__redis__:invalidate";
+
+/// a connection caching keys on its side, told when they change
+struct Tracker {
+    options: TrackingOptions,
+    resp3: bool,
+    sender: Sender<CommandResponse>,
+}
+
+/// the connections tracking keys, along with the keys each of them read,
+/// shared by every connection of a server
+#[derive(Default)]
+pub struct Tracking {
+    trackers: HashMap<u64, Tracker>,
+    // keys read by the connections tracking them outside of BCAST mode
+    keys: HashMap<Vec<u8>, HashSet<u64>>,
+}
+
+impl Tracking {
+    pub fn new() -> Self {
+        Tracking {
+            trackers: HashMap::new(),
+            keys: HashMap::new(),
+        }
+    }
+
+    pub fn enable(&mut self, connection: &Connection, options: TrackingOptions) {
+        let tracker = Tracker {
+            options,
+            resp3: connection.protocol == 3,
+            sender: connection.sender(),
+        };
+        self.trackers.insert(connection.id, tracker);
+    }
+
+    pub fn disable(&mut self, connection: &Connection) {
+        if self.trackers.remove(&connection.id).is_some() {
+            self.keys.retain(|_, ids| {
+                ids.remove(&connection.id);
+                !ids.is_empty()
+            });
+        }
+    }
+
+    /// remember the keys a connection read, unless it is not tracking them
+    /// or broadcasting already tells it about every key it may read
+    pub fn track(&mut self, connection: &Connection, keys: &[&[u8]]) {
+        match self.trackers.get(&connection.id) {
+            Some(tracker) if !tracker.options.bcast => {
+                for key in keys {
+                    self.keys
+                        .entry(key.to_vec())
+                        .or_default()
+                        .insert(connection.id);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// tell the connections that read the keys, or that broadcast them,
+    /// that they changed, a key being forgotten once its readers are told
+    pub fn invalidate(&mut self, keys: &[Vec<u8>], caller: u64, pubsub: &PubSub) {
+        for key in keys {
+            let readers = self.keys.remove(key).unwrap_or_default();
+            for (id, tracker) in &self.trackers {
+                let told = match tracker.options.bcast {
+                    true => {
+                        tracker.options.prefixes.is_empty()
+                            || tracker
+                                .options
+                                .prefixes
+                                .iter()
+                                .any(|prefix| key.starts_with(prefix))
+                    }
+                    false => readers.contains(id),
+                };
+                if told && !(tracker.options.noloop && *id == caller) {
+                    let keys = RedisResponse::Array(vec![RedisResponse::BulkString(key.clone())]);
+                    tracker.send(keys, pubsub);
+                }
+            }
+        }
+    }
+
+    /// tell every tracking connection that all the keys they cached are
+    /// gone, e.g. once databases are swapped
+    pub fn invalidate_all(&mut self, pubsub: &PubSub) {
+        self.keys.clear();
+        for tracker in self.trackers.values() {
+            tracker.send(RedisResponse::Nil, pubsub);
+        }
+    }
+}
+
+impl Tracker {
+    /// a push message in RESP3, or a message published to the connection
+    /// invalidations are redirected to, which nothing is sent to a RESP2
+    /// connection without
+    fn send(&self, keys: RedisResponse, pubsub: &PubSub) {
+        match (self.options.redirect, self.resp3) {
+            (Some(redirect), _) => {
+                if let Some(subscriber) = pubsub.subscriber(INVALIDATE_CHANNEL, redirect) {
+                    subscriber.push(vec![
+                        RedisResponse::BulkString(b"message".to_vec()),
+                        RedisResponse::BulkString(INVALIDATE_CHANNEL.to_vec()),
+                        keys,
+                    ]);
+                }
+            }
+            (None, true) => {
+                let invalidate = RedisResponse::BulkString(b"invalidate".to_vec());
+                let _ = self
+                    .sender
+                    .send(RedisResponse::Push(vec![invalidate, keys]).to_vec());
+            }
+            (None, false) => {}
+        }
+    }
+}
@@ -1,11 +1,102 @@
-use std::collections::HashMap;
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+use std::io;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, PoisonError};
 
-use super::models::{DataType, Expiry, RedisValue};
-use crate::storage::Storage;
+use super::models::{
+    access::Access,
+    bitfield::BitFieldOp,
+    entry_size,
+    hyperloglog::HyperLogLog,
+    redis_set,
+    redis_sorted_set::Scores,
+    redis_stream::{StreamFields, StreamId, XAddId},
+    DataType, Expiry, ExpiryCondition, ExpiryIndex, RedisHashMap, RedisSet, RedisSortedSet,
+    RedisStream, RedisValue, StoredValue,
+};
+use crate::storage::{EvictionPolicy, KeyspaceStats, MemoryStats, Sibling, Storage};
+
+// shards the keys are spread over unless told otherwise, for the commands
+// on keys of different shards not to wait for each other
+const SHARDS: usize = 4;
+
+// versions are unique across storages, so that a key whose database has
+// been swapped never has the version it was watched with
+static LAST_VERSION: AtomicU64 = AtomicU64::new(0);
+
+/// a version no key ever had
+pub(super) fn next_version() -> u64 {
+    LAST_VERSION.fetch_add(1, Ordering::Relaxed) + 1
+}
 
 pub struct InMemoryStorage {
     data_mapper: HashMap<Vec<u8>, DataType>,
     string_store: HashMap<Vec<u8>, RedisValue>,
+    hash_store: HashMap<Vec<u8>, RedisHashMap>,
+    set_store: HashMap<Vec<u8>, RedisSet>,
+    sorted_set_store: HashMap<Vec<u8>, RedisSortedSet>,
+    stream_store: HashMap<Vec<u8>, RedisStream>,
+    expired_keys: Vec<Vec<u8>>,
+    // the keys the reads through &self found expired, removed by the next
+    // call that may change the storage
+    lapsed: Mutex<Vec<Vec<u8>>>,
+    // the keys given an expiry, for sample_expired to find the ones that
+    // expired without walking through all the keys
+    expiries: ExpiryIndex,
+    // version of each key modified so far, bumped on every change
+    versions: HashMap<Vec<u8>, u64>,
+    // what the eviction policies look at, tracked once asked for
+    usage: Option<Usage>,
+}
+
+/// the bytes each key takes and how it is used, kept up to date as the keys
+/// change rather than walking through all of them each time
+#[derive(Default)]
+struct Usage {
+    sizes: HashMap<Vec<u8>, usize>,
+    // the sum of the sizes
+    used: usize,
+    accesses: HashMap<Vec<u8>, Access>,
+    // the keys changed since their size was last looked at
+    changed: HashSet<Vec<u8>>,
+    // the keys, for the eviction policies to sample them at random
+    keys: KeySample,
+}
+
+/// keys picked at random without walking through all of them, each being
+/// told where it is in the list
+#[derive(Default)]
+struct KeySample {
+    keys: Vec<Vec<u8>>,
+    positions: HashMap<Vec<u8>, usize>,
+}
+
+impl KeySample {
+    fn insert(&mut self, key: &[u8]) {
+        if !self.positions.contains_key(key) {
+            self.positions.insert(key.to_vec(), self.keys.len());
+            self.keys.push(key.to_vec());
+        }
+    }
+
+    fn remove(&mut self, key: &[u8]) {
+        if let Some(position) = self.positions.remove(key) {
+            self.keys.swap_remove(position);
+            if let Some(moved) = self.keys.get(position) {
+                self.positions.insert(moved.clone(), position);
+            }
+        }
+    }
+
+    /// up to `amount` keys, none picked twice
+    fn sample(&self, amount: usize) -> Vec<&[u8]> {
+        let amount = amount.min(self.keys.len());
+        rand::seq::index::sample(&mut rand::thread_rng(), self.keys.len(), amount)
+            .into_iter()
+            .map(|i| self.keys[i].as_slice())
+            .collect()
+    }
 }
 
 impl InMemoryStorage {
@@ -13,56 +104,944 @@ impl InMemoryStorage {
         InMemoryStorage {
             data_mapper: HashMap::new(),
             string_store: HashMap::new(),
+            hash_store: HashMap::new(),
+            set_store: HashMap::new(),
+            sorted_set_store: HashMap::new(),
+            stream_store: HashMap::new(),
+            expired_keys: Vec::new(),
+            lapsed: Mutex::new(Vec::new()),
+            expiries: ExpiryIndex::new(),
+            versions: HashMap::new(),
+            usage: None,
+        }
+    }
+
+    /// mark the key as modified, WATCH checking its version
+    fn touch(&mut self, key: &[u8]) {
+        self.settle();
+        self.versions.insert(key.to_vec(), next_version());
+        if let Some(usage) = &mut self.usage {
+            usage.changed.insert(key.to_vec());
+            // not asked for anymore, as once maxmemory is unset, the usage
+            // is dropped rather than the keys changed piling up
+            if usage.changed.len() > 2 * self.data_mapper.len() + 1024 {
+                self.usage = None;
+            }
+        }
+    }
+
+    /// the usage of the keys, every key being looked at the first time,
+    /// then the ones changed since the last call, a change counting as a use
+    fn usage(&mut self) -> &mut Usage {
+        let changed = match &mut self.usage {
+            Some(usage) => std::mem::take(&mut usage.changed),
+            None => self.data_mapper.keys().cloned().collect(),
+        };
+        let mut usage = self.usage.take().unwrap_or_default();
+        for key in changed {
+            let previous = usage.sizes.remove(&key).unwrap_or(0);
+            usage.used -= previous;
+            match self.stored_size(&key) {
+                Some(size) => {
+                    usage.used += size;
+                    usage.sizes.insert(key.clone(), size);
+                    usage.keys.insert(&key);
+                    usage.accesses.entry(key).or_default().hit();
+                }
+                None => {
+                    usage.keys.remove(&key);
+                    usage.accesses.remove(&key);
+                }
+            }
+        }
+        self.usage.get_or_insert(usage)
+    }
+
+    /// bytes the key takes, as it is stored whether it expired or not
+    fn stored_size(&self, key: &[u8]) -> Option<usize> {
+        let value = match self.data_mapper.get(key)? {
+            DataType::String => self.string_store.get(key)?.memory_usage(),
+            DataType::Hash => self.hash_store.get(key)?.memory_usage(),
+            DataType::Set => self.set_store.get(key)?.memory_usage(),
+            DataType::SortedSet => self.sorted_set_store.get(key)?.memory_usage(),
+            DataType::Stream => self.stream_store.get(key)?.memory_usage(),
+            DataType::List => return None,
+        };
+        Some(self.key_overhead(key) + value)
+    }
+
+    /// remove a key that expired, which is remembered until taken, unless
+    /// removing it settled the same key a read found expired first
+    fn remove_expired(&mut self, key: &[u8]) {
+        if self.remove(key) > 0 {
+            self.expired_keys.push(key.to_vec());
+        }
+    }
+
+    /// remember a key a read through &self found expired, to be removed
+    /// once the storage can be changed
+    fn lapse(&self, key: &[u8]) {
+        self.lapsed
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .push(key.to_vec());
+    }
+
+    /// remove the keys the reads through &self found expired, unless they
+    /// were since
+    fn settle(&mut self) {
+        let lapsed = std::mem::take(
+            self.lapsed
+                .get_mut()
+                .unwrap_or_else(PoisonError::into_inner),
+        );
+        for key in lapsed {
+            self.live_type(&key);
+        }
+    }
+
+    /// the type of the value stored at key, None if there is no such key
+    /// once removed if it expired, every read looking the key up this way
+    fn live_type(&mut self, key: &[u8]) -> Option<DataType> {
+        let data_type = *self.data_mapper.get(key)?;
+        let live = match data_type {
+            DataType::String => self.value_mut(key).is_some(),
+            DataType::Hash => self.hash_mut(key).is_some(),
+            DataType::Set => self.set_mut(key).is_some(),
+            DataType::SortedSet => self.sorted_set_mut(key).is_some(),
+            DataType::Stream => self.stream_mut(key).is_some(),
+            DataType::List => true,
+        };
+        Some(data_type).filter(|_| live)
+    }
+
+    /// when the key expires, as it is stored whether it expired or not
+    fn stored_expiry(&self, key: &[u8]) -> Option<Expiry> {
+        match self.data_mapper.get(key)? {
+            DataType::String => self.string_store.get(key)?.expiry,
+            DataType::Hash => self.hash_store.get(key)?.expiry,
+            DataType::Set => self.set_store.get(key)?.expiry,
+            DataType::SortedSet => self.sorted_set_store.get(key)?.expiry,
+            DataType::Stream => self.stream_store.get(key)?.expiry,
+            DataType::List => None,
+        }
+    }
+
+    /// add the key to the expiry index, the entries left behind by keys
+    /// removed or given another expiry being dropped once they outnumber
+    /// the keys
+    fn index_expiry(&mut self, key: &[u8], expiry: Expiry) {
+        self.expiries.push(key, expiry);
+        if self.expiries.len() > 2 * self.data_mapper.len() + 64 {
+            let mut expiries = std::mem::take(&mut self.expiries);
+            expiries.retain(|key, expiry| self.stored_expiry(key) == Some(expiry));
+            self.expiries = expiries;
+        }
+    }
+
+    /// get the string stored at key, the key is removed if it expired
+    fn value_mut(&mut self, key: &[u8]) -> Option<&mut RedisValue> {
+        match self.string_store.get(key) {
+            Some(value) if value.is_expired() => {
+                self.remove_expired(key);
+                None
+            }
+            _ => self.string_store.get_mut(key),
+        }
+    }
+
+    /// get the hash stored at key once its expired fields are gone,
+    /// the key is removed if the whole hash expired or if it ends up empty
+    fn hash_mut(&mut self, key: &[u8]) -> Option<&mut RedisHashMap> {
+        let is_empty = match self.hash_store.get_mut(key) {
+            Some(hash) if hash.is_expired() => {
+                self.remove_expired(key);
+                return None;
+            }
+            Some(hash) => {
+                hash.remove_expired_fields();
+                hash.data.is_empty()
+            }
+            None => return None,
+        };
+
+        if is_empty {
+            self.remove(key);
+            return None;
+        }
+
+        self.hash_store.get_mut(key)
+    }
+
+    /// get the set stored at key, the key is removed if the set expired
+    fn set_mut(&mut self, key: &[u8]) -> Option<&mut RedisSet> {
+        match self.set_store.get(key) {
+            Some(set) if set.is_expired() => {
+                self.remove_expired(key);
+                None
+            }
+            _ => self.set_store.get_mut(key),
+        }
+    }
+
+    /// get the sorted set stored at key, the key is removed if it expired
+    fn sorted_set_mut(&mut self, key: &[u8]) -> Option<&mut RedisSortedSet> {
+        match self.sorted_set_store.get(key) {
+            Some(sorted_set) if sorted_set.is_expired() => {
+                self.remove_expired(key);
+                None
+            }
+            _ => self.sorted_set_store.get_mut(key),
         }
     }
+
+    /// get the stream stored at key, the key is removed if it expired
+    fn stream_mut(&mut self, key: &[u8]) -> Option<&mut RedisStream> {
+        match self.stream_store.get(key) {
+            Some(stream) if stream.is_expired() => {
+                self.remove_expired(key);
+                None
+            }
+            _ => self.stream_store.get_mut(key),
+        }
+    }
+
+    /// bytes spent on holding a key in the maps, its name included, the
+    /// value aside
+    fn key_overhead(&self, key: &[u8]) -> usize {
+        let versioned = match self.versions.contains_key(key) {
+            true => entry_size::<(Vec<u8>, u64)>() + key.len(),
+            false => 0,
+        };
+        // the key is held by the map of the types and by the store of its
+        // type, the value being counted with the key by the store
+        entry_size::<(Vec<u8>, DataType)>() + entry_size::<Vec<u8>>() + key.len() * 2 + versioned
+    }
+
+    /// when the key expires if it does, None if there is no such key once
+    /// removed if it expired
+    fn key_expiry(&mut self, key: &[u8]) -> Option<Option<Expiry>> {
+        match self.type_of(key)? {
+            DataType::String => self.value_mut(key).map(|value| value.expiry),
+            DataType::Hash => self.hash_mut(key).map(|hash| hash.expiry),
+            DataType::Set => self.set_mut(key).map(|set| set.expiry),
+            DataType::SortedSet => self.sorted_set_mut(key).map(|sorted_set| sorted_set.expiry),
+            DataType::Stream => self.stream_mut(key).map(|stream| stream.expiry),
+            DataType::List => None,
+        }
+    }
+
+    /// a copy of the value stored at key, None if there is no such key
+    /// once removed if it expired
+    fn stored_value(&mut self, key: &[u8]) -> Option<StoredValue> {
+        match self.type_of(key)? {
+            DataType::String => self.value_mut(key).cloned().map(StoredValue::String),
+            DataType::Hash => self.hash_mut(key).cloned().map(StoredValue::Hash),
+            DataType::Set => self.set_mut(key).cloned().map(StoredValue::Set),
+            DataType::SortedSet => self
+                .sorted_set_mut(key)
+                .cloned()
+                .map(StoredValue::SortedSet),
+            DataType::Stream => self.stream_mut(key).cloned().map(StoredValue::Stream),
+            DataType::List => None,
+        }
+    }
+
+    /// bytes the value stored at key takes, once removed if it expired
+    fn value_memory_usage(&mut self, key: &[u8]) -> Option<usize> {
+        match self.type_of(key)? {
+            DataType::String => self.value_mut(key).map(|value| value.memory_usage()),
+            DataType::Hash => self.hash_mut(key).map(|hash| hash.memory_usage()),
+            DataType::Set => self.set_mut(key).map(|set| set.memory_usage()),
+            DataType::SortedSet => self
+                .sorted_set_mut(key)
+                .map(|sorted_set| sorted_set.memory_usage()),
+            DataType::Stream => self.stream_mut(key).map(|stream| stream.memory_usage()),
+            DataType::List => None,
+        }
+    }
+}
+
+impl Default for InMemoryStorage {
+    fn default() -> Self {
+        InMemoryStorage::new()
+    }
+}
+
+impl Sibling for InMemoryStorage {
+    fn sibling(&self, _index: usize) -> io::Result<Self> {
+        Ok(InMemoryStorage::new())
+    }
+
+    fn shards(&self) -> usize {
+        SHARDS
+    }
 }
 
 impl Storage for InMemoryStorage {
     fn write(&mut self, key: &[u8], value: &[u8]) {
+        match self.data_mapper.get(key) {
+            Some(DataType::String) | None => {}
+            // SET overwrites the key whatever the type it was holding
+            Some(_) => {
+                self.remove(key);
+            }
+        }
+
+        self.touch(key);
         self.data_mapper.insert(key.to_vec(), DataType::String);
         self.string_store
             .insert(key.to_vec(), RedisValue::new(value.to_vec(), None));
     }
 
+    fn setbit(&mut self, key: &[u8], offset: usize, bit: bool) -> u8 {
+        let _ = self.value_mut(key);
+        self.touch(key);
+
+        self.data_mapper.insert(key.to_vec(), DataType::String);
+        let value = self
+            .string_store
+            .entry(key.to_vec())
+            .or_insert_with(|| RedisValue::new(vec![], None));
+
+        value.set_bit(offset, bit)
+    }
+
+    fn bitfield(&mut self, key: &[u8], operations: &[BitFieldOp]) -> Vec<Option<i64>> {
+        // only GET, the key is left untouched
+        if operations.iter().all(|op| op.is_read_only()) {
+            let mut data = self.read(key).map_or(vec![], |value| value.to_vec());
+            return operations.iter().map(|op| op.apply(&mut data)).collect();
+        }
+
+        let _ = self.value_mut(key);
+        self.touch(key);
+
+        self.data_mapper.insert(key.to_vec(), DataType::String);
+        let value = self
+            .string_store
+            .entry(key.to_vec())
+            .or_insert_with(|| RedisValue::new(vec![], None));
+
+        // like Redis, the value covers every field written, even if failing
+        let end = operations
+            .iter()
+            .filter(|op| !op.is_read_only())
+            .map(|op| op.end())
+            .max()
+            .unwrap_or(0);
+        if value.data.len() * 8 < end {
+            value.data.resize(end.div_ceil(8), 0);
+        }
+
+        operations
+            .iter()
+            .map(|op| op.apply(&mut value.data))
+            .collect()
+    }
+
+    fn pfadd(&mut self, key: &[u8], elements: &[Vec<u8>]) -> Option<u32> {
+        // every element is added, even once a register has been updated
+        let add_all = |hyperloglog: &mut HyperLogLog| {
+            elements
+                .iter()
+                .filter(|element| hyperloglog.add(element))
+                .count()
+                > 0
+        };
+
+        let value = match self.value_mut(key) {
+            Some(value) => value,
+            None => {
+                let mut hyperloglog = HyperLogLog::new();
+                add_all(&mut hyperloglog);
+                self.write(key, &hyperloglog.to_bytes());
+                return Some(1); // key has been created
+            }
+        };
+
+        let mut hyperloglog = HyperLogLog::from_bytes(&value.data)?;
+        match add_all(&mut hyperloglog) {
+            true => {
+                value.data = hyperloglog.to_bytes();
+                self.touch(key);
+                Some(1) // a register has been updated
+            }
+            false => Some(0),
+        }
+    }
+
+    fn pfmerge(&mut self, key: &[u8], hyperloglog: &HyperLogLog) -> Option<()> {
+        let value = match self.value_mut(key) {
+            Some(value) => value,
+            None => {
+                self.write(key, &hyperloglog.to_bytes());
+                return Some(());
+            }
+        };
+
+        let mut merged = HyperLogLog::from_bytes(&value.data)?;
+        merged.merge(hyperloglog);
+        value.data = merged.to_bytes();
+        self.touch(key);
+        Some(())
+    }
+
+    fn hwrite(&mut self, key: &[u8], field: &[u8], value: &[u8]) -> u32 {
+        let _ = self.hash_mut(key);
+        self.touch(key);
+
+        self.data_mapper.insert(key.to_vec(), DataType::Hash);
+        let hash = self
+            .hash_store
+            .entry(key.to_vec())
+            .or_insert_with(|| RedisHashMap::new(HashMap::new(), None));
+
+        match hash.insert(field, value) {
+            Some(_) => 0, // field already existed and has been updated
+            None => 1,    // field has been created
+        }
+    }
+
+    fn sadd(&mut self, key: &[u8], member: &[u8]) -> u32 {
+        let _ = self.set_mut(key);
+
+        self.data_mapper.insert(key.to_vec(), DataType::Set);
+        let set = self
+            .set_store
+            .entry(key.to_vec())
+            .or_insert_with(|| RedisSet::new(HashSet::new(), None));
+
+        match set.data.insert(member.to_vec()) {
+            true => {
+                self.touch(key);
+                1 // member has been added
+            }
+            false => 0, // member was already there
+        }
+    }
+
+    fn swrite(&mut self, key: &[u8], members: HashSet<Vec<u8>>) {
+        // the key is overwritten whatever the type it was holding
+        self.remove(key);
+        self.touch(key);
+
+        // an empty set does not exist
+        if !members.is_empty() {
+            self.data_mapper.insert(key.to_vec(), DataType::Set);
+            self.set_store
+                .insert(key.to_vec(), RedisSet::new(members, None));
+        }
+    }
+
+    fn zwrite(&mut self, key: &[u8], members: Scores) {
+        // the key is overwritten whatever the type it was holding
+        self.remove(key);
+        self.touch(key);
+
+        // an empty sorted set does not exist
+        if !members.is_empty() {
+            self.data_mapper.insert(key.to_vec(), DataType::SortedSet);
+            self.sorted_set_store
+                .insert(key.to_vec(), RedisSortedSet::from_scores(members, None));
+        }
+    }
+
+    fn zadd(&mut self, key: &[u8], score: f64, member: &[u8]) -> u32 {
+        let _ = self.sorted_set_mut(key);
+        self.touch(key);
+
+        self.data_mapper.insert(key.to_vec(), DataType::SortedSet);
+        let sorted_set = self
+            .sorted_set_store
+            .entry(key.to_vec())
+            .or_insert_with(|| RedisSortedSet::new(None));
+
+        match sorted_set.insert(member, score) {
+            Some(_) => 0, // member already existed and its score has been updated
+            None => 1,    // member has been added
+        }
+    }
+
+    fn xadd(&mut self, key: &[u8], id: XAddId, fields: StreamFields) -> Option<StreamId> {
+        let _ = self.stream_mut(key);
+
+        // the key is only created once the id is known to be valid
+        let id = match self.stream_store.get(key) {
+            Some(stream) => stream.next_id(id)?,
+            None => RedisStream::new(None).next_id(id)?,
+        };
+
+        self.touch(key);
+        self.data_mapper.insert(key.to_vec(), DataType::Stream);
+        self.stream_store
+            .entry(key.to_vec())
+            .or_insert_with(|| RedisStream::new(None))
+            .add(XAddId::Explicit(id), fields)
+    }
+
     fn expire(&mut self, key: &[u8], expiry: Expiry) -> u32 {
+        // a key that expired is not brought back
+        if self.live_type(key).is_some() {
+            self.touch(key);
+        }
+
         if let Some(value) = self.string_store.get_mut(key) {
             value.expiry = Some(expiry);
-            1 // timeout was set
+        } else if let Some(hash) = self.hash_store.get_mut(key) {
+            hash.expiry = Some(expiry);
+        } else if let Some(set) = self.set_store.get_mut(key) {
+            set.expiry = Some(expiry);
+        } else if let Some(sorted_set) = self.sorted_set_store.get_mut(key) {
+            sorted_set.expiry = Some(expiry);
+        } else if let Some(stream) = self.stream_store.get_mut(key) {
+            stream.expiry = Some(expiry);
         } else {
-            0 // key does not exist
+            return 0; // key does not exist
         }
+
+        self.index_expiry(key, expiry);
+        1 // timeout was set
     }
 
-    fn read(&mut self, key: &[u8]) -> Option<&[u8]> {
-        if let Some(value) = self.string_store.get(key) {
-            match value.is_expired() {
-                true => {
-                    self.remove(key);
-                    None
-                }
-                false => Some(&self.string_store.get(key).unwrap().data[..]),
+    fn read(&self, key: &[u8]) -> Option<Cow<'_, [u8]>> {
+        let value = self.string_store.get(key)?;
+        match value.is_expired() {
+            true => {
+                self.lapse(key);
+                None
             }
-        } else {
-            None
+            false => Some(Cow::Borrowed(&value.data[..])),
+        }
+    }
+
+    fn hread(&mut self, key: &[u8], field: &[u8]) -> Option<&[u8]> {
+        self.hash_mut(key)
+            .and_then(|hash| hash.get(field))
+            .map(|value| &value[..])
+    }
+
+    fn hread_all(&mut self, key: &[u8]) -> Option<&RedisHashMap> {
+        self.hash_mut(key).map(|hash| &*hash)
+    }
+
+    fn hexpire(
+        &mut self,
+        key: &[u8],
+        field: &[u8],
+        expiry: Expiry,
+        condition: Option<ExpiryCondition>,
+    ) -> i64 {
+        let result = match self.hash_mut(key) {
+            Some(hash) => hash.expire_field(field, expiry, condition),
+            None => return -2, // key does not exist
+        };
+        self.touch(key);
+
+        // drop the key if its last field has just been deleted
+        let _ = self.hash_mut(key);
+        result
+    }
+
+    fn hpttl(&mut self, key: &[u8], field: &[u8]) -> i64 {
+        match self.hash_mut(key) {
+            Some(hash) => hash.field_ttl_millis(field),
+            None => -2, // key does not exist
+        }
+    }
+
+    fn hpersist(&mut self, key: &[u8], field: &[u8]) -> i64 {
+        let result = match self.hash_mut(key) {
+            Some(hash) => hash.persist_field(field),
+            None => return -2, // key does not exist
+        };
+        self.touch(key);
+        result
+    }
+
+    fn sread(&mut self, key: &[u8]) -> Option<&RedisSet> {
+        self.set_mut(key).map(|set| &*set)
+    }
+
+    fn sintercard(&mut self, keys: &[Vec<u8>], limit: usize) -> usize {
+        // drop the expired sets first so the others can be borrowed together
+        keys.iter().for_each(|key| {
+            let _ = self.set_mut(key);
+        });
+
+        let sets = keys
+            .iter()
+            .map(|key| self.set_store.get(key).map(|set| &set.data))
+            .collect::<Option<Vec<_>>>();
+
+        match sets {
+            Some(sets) => redis_set::intersection_cardinality(&sets, limit),
+            None => 0, // a missing key is an empty set
+        }
+    }
+
+    fn zread(&mut self, key: &[u8]) -> Option<&RedisSortedSet> {
+        self.sorted_set_mut(key).map(|sorted_set| &*sorted_set)
+    }
+
+    fn xread(&mut self, key: &[u8]) -> Option<&RedisStream> {
+        self.stream_mut(key).map(|stream| &*stream)
+    }
+
+    fn xread_mut(&mut self, key: &[u8]) -> Option<&mut RedisStream> {
+        // the stream is assumed to be modified
+        if self.stream_mut(key).is_some() {
+            self.touch(key);
+        }
+        self.stream_mut(key)
+    }
+
+    fn xcreate(&mut self, key: &[u8]) {
+        // unlike other types, an empty stream exists
+        if self.stream_mut(key).is_none() {
+            self.touch(key);
+            self.data_mapper.insert(key.to_vec(), DataType::Stream);
+            self.stream_store
+                .insert(key.to_vec(), RedisStream::new(None));
         }
     }
 
     fn remove(&mut self, key: &[u8]) -> u32 {
-        match self.data_mapper.get(key) {
+        if self.data_mapper.contains_key(key) {
+            self.touch(key);
+        }
+
+        match self.data_mapper.remove(key) {
             Some(data_type) => match data_type {
                 DataType::String => match self.string_store.remove(key) {
                     Some(_) => 1,
                     None => 0,
                 },
                 DataType::List => 0,
-                DataType::Set => 0,
-                DataType::Hash => 0,
+                DataType::Set => match self.set_store.remove(key) {
+                    Some(_) => 1,
+                    None => 0,
+                },
+                DataType::SortedSet => match self.sorted_set_store.remove(key) {
+                    Some(_) => 1,
+                    None => 0,
+                },
+                DataType::Hash => match self.hash_store.remove(key) {
+                    Some(_) => 1,
+                    None => 0,
+                },
+                DataType::Stream => match self.stream_store.remove(key) {
+                    Some(_) => 1,
+                    None => 0,
+                },
             },
             None => 0,
         }
     }
 
-    fn contains(&mut self, key: &[u8]) -> bool {
-        self.data_mapper.contains_key(key)
+    fn take(&mut self, key: &[u8]) -> Option<StoredValue> {
+        // an expired key is removed first
+        let value = match self.type_of(key)? {
+            DataType::String => {
+                self.value_mut(key)?;
+                StoredValue::String(self.string_store.remove(key)?)
+            }
+            DataType::Set => {
+                self.set_mut(key)?;
+                StoredValue::Set(self.set_store.remove(key)?)
+            }
+            DataType::SortedSet => {
+                self.sorted_set_mut(key)?;
+                StoredValue::SortedSet(self.sorted_set_store.remove(key)?)
+            }
+            DataType::Hash => {
+                self.hash_mut(key)?;
+                StoredValue::Hash(self.hash_store.remove(key)?)
+            }
+            DataType::Stream => {
+                self.stream_mut(key)?;
+                StoredValue::Stream(self.stream_store.remove(key)?)
+            }
+            DataType::List => return None,
+        };
+
+        self.data_mapper.remove(key);
+        self.touch(key);
+        Some(value)
+    }
+
+    fn copy(&mut self, key: &[u8]) -> Option<StoredValue> {
+        self.stored_value(key)
+    }
+
+    fn put(&mut self, key: &[u8], value: StoredValue) {
+        self.remove(key);
+        if let Some(expiry) = value.expiry() {
+            self.index_expiry(key, *expiry);
+        }
+
+        let data_type = match value {
+            StoredValue::String(value) => {
+                self.string_store.insert(key.to_vec(), value);
+                DataType::String
+            }
+            StoredValue::Set(set) => {
+                self.set_store.insert(key.to_vec(), set);
+                DataType::Set
+            }
+            StoredValue::SortedSet(sorted_set) => {
+                self.sorted_set_store.insert(key.to_vec(), sorted_set);
+                DataType::SortedSet
+            }
+            StoredValue::Hash(hash) => {
+                self.hash_store.insert(key.to_vec(), hash);
+                DataType::Hash
+            }
+            StoredValue::Stream(stream) => {
+                self.stream_store.insert(key.to_vec(), stream);
+                DataType::Stream
+            }
+        };
+
+        self.data_mapper.insert(key.to_vec(), data_type);
+        self.touch(key);
+    }
+
+    fn srem(&mut self, key: &[u8], member: &[u8]) -> u32 {
+        let (removed, is_empty) = match self.set_mut(key) {
+            Some(set) => (set.data.remove(member), set.data.is_empty()),
+            None => return 0,
+        };
+        if removed {
+            self.touch(key);
+        }
+
+        if is_empty {
+            // an empty set does not exist
+            self.remove(key);
+        }
+
+        match removed {
+            true => 1,
+            false => 0,
+        }
+    }
+
+    fn spop(&mut self, key: &[u8], count: usize) -> Vec<Vec<u8>> {
+        let (members, is_empty) = match self.set_mut(key) {
+            Some(set) => {
+                let members = set.pop(count);
+                (members, set.data.is_empty())
+            }
+            None => return vec![],
+        };
+        if !members.is_empty() {
+            self.touch(key);
+        }
+
+        if is_empty {
+            // an empty set does not exist
+            self.remove(key);
+        }
+
+        members
+    }
+
+    fn zrem(&mut self, key: &[u8], member: &[u8]) -> u32 {
+        let (removed, is_empty) = match self.sorted_set_mut(key) {
+            Some(sorted_set) => (sorted_set.remove(member), sorted_set.is_empty()),
+            None => return 0,
+        };
+        if removed.is_some() {
+            self.touch(key);
+        }
+
+        if is_empty {
+            // an empty sorted set does not exist
+            self.remove(key);
+        }
+
+        match removed {
+            Some(_) => 1,
+            None => 0,
+        }
+    }
+
+    fn zpop(&mut self, key: &[u8], count: usize, max: bool) -> Vec<(Vec<u8>, f64)> {
+        let (members, is_empty) = match self.sorted_set_mut(key) {
+            Some(sorted_set) => {
+                let members = sorted_set.pop(count, max);
+                (members, sorted_set.is_empty())
+            }
+            None => return vec![],
+        };
+        if !members.is_empty() {
+            self.touch(key);
+        }
+
+        if is_empty {
+            // an empty sorted set does not exist
+            self.remove(key);
+        }
+
+        members
+    }
+
+    fn contains(&self, key: &[u8]) -> bool {
+        if !self.data_mapper.contains_key(key) {
+            return false;
+        }
+
+        // a hash whose fields all expired is gone as well
+        let expired = matches!(self.stored_expiry(key), Some(expiry) if expiry.is_expired())
+            || matches!(self.hash_store.get(key), Some(hash) if !hash.has_live_fields());
+        if expired {
+            self.lapse(key);
+        }
+        !expired
+    }
+
+    fn type_of(&mut self, key: &[u8]) -> Option<DataType> {
+        self.live_type(key)
+    }
+
+    fn take_expired(&mut self) -> Vec<Vec<u8>> {
+        self.settle();
+        std::mem::take(&mut self.expired_keys)
+    }
+
+    fn lapsed(&self) -> bool {
+        !self
+            .lapsed
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .is_empty()
+    }
+
+    fn sample_expired(&mut self, count: usize) -> (usize, usize) {
+        let mut sampled = 0;
+        let mut expired = 0;
+        while sampled < count {
+            let (key, expiry) = match self.expiries.pop_expired() {
+                Some(entry) => entry,
+                None => break,
+            };
+            sampled += 1;
+            // an entry left behind is dropped, the key it was for being
+            // gone or given another expiry
+            if self.stored_expiry(&key) == Some(expiry) {
+                self.remove_expired(&key);
+                expired += 1;
+            }
+        }
+        (sampled, expired)
+    }
+
+    fn version(&mut self, key: &[u8]) -> u64 {
+        // an expired key is removed first, which counts as a change
+        self.live_type(key);
+
+        self.versions.get(key).copied().unwrap_or(0)
+    }
+
+    fn memory_usage(&mut self, key: &[u8]) -> Option<usize> {
+        let value = self.value_memory_usage(key)?;
+        Some(self.key_overhead(key) + value)
+    }
+
+    fn memory_stats(&mut self) -> MemoryStats {
+        let keys = self.data_mapper.keys().cloned().collect::<Vec<_>>();
+        let mut stats = MemoryStats::default();
+        for key in keys {
+            if let Some(value) = self.value_memory_usage(&key) {
+                stats.keys += 1;
+                stats.overhead += self.key_overhead(&key);
+                stats.dataset += value;
+            }
+        }
+
+        stats
+    }
+
+    fn keyspace_stats(&mut self) -> KeyspaceStats {
+        let keys = self.data_mapper.keys().cloned().collect::<Vec<_>>();
+        let mut stats = KeyspaceStats::default();
+        let mut ttl = 0;
+        for key in keys {
+            match self.key_expiry(&key) {
+                Some(Some(expiry)) => {
+                    stats.keys += 1;
+                    stats.expires += 1;
+                    ttl += expiry.ttl_millis();
+                }
+                Some(None) => stats.keys += 1,
+                None => {}
+            }
+        }
+        if stats.expires > 0 {
+            stats.avg_ttl = ttl / stats.expires as u64;
+        }
+
+        stats
+    }
+
+    fn snapshot(&mut self) -> Vec<(Vec<u8>, StoredValue)> {
+        let keys = self.data_mapper.keys().cloned().collect::<Vec<_>>();
+        keys.into_iter()
+            .filter_map(|key| {
+                let value = self.stored_value(&key)?;
+                Some((key, value))
+            })
+            .collect()
+    }
+
+    fn access(&mut self, key: &[u8]) {
+        if let Some(access) = self.usage().accesses.get_mut(key) {
+            access.hit();
+        }
+    }
+
+    fn used_memory(&mut self) -> usize {
+        self.usage().used
+    }
+
+    fn eviction_candidate(
+        &mut self,
+        policy: EvictionPolicy,
+        samples: usize,
+    ) -> Option<(Vec<u8>, u64)> {
+        self.usage();
+        let usage = self.usage.as_ref()?;
+        let keys = match policy.is_volatile() {
+            // the entries the keys whose expiry changed left behind in the
+            // index are skipped, more of them being sampled to make up
+            true => self
+                .expiries
+                .sample(2 * samples)
+                .into_iter()
+                .filter(|(key, expiry)| self.stored_expiry(key) == Some(*expiry))
+                .map(|(key, _)| key)
+                .take(samples)
+                .collect(),
+            false => usage.keys.sample(samples),
+        };
+
+        let score = |key: &[u8]| {
+            let access = usage.accesses.get(key).copied().unwrap_or_default();
+            match policy {
+                EvictionPolicy::VolatileLru | EvictionPolicy::AllKeysLru => access.idle(),
+                EvictionPolicy::VolatileLfu | EvictionPolicy::AllKeysLfu => {
+                    (u8::MAX - access.frequency()) as u64
+                }
+                // the sooner the key expires, the sooner it is evicted
+                EvictionPolicy::VolatileTtl => self
+                    .stored_expiry(key)
+                    .map_or(0, |expiry| u64::MAX - expiry.ttl_millis()),
+                EvictionPolicy::VolatileRandom | EvictionPolicy::AllKeysRandom => rand::random(),
+            }
+        };
+        keys.into_iter()
+            .map(|key| (key.to_vec(), score(key)))
+            .max_by_key(|(_, score)| *score)
     }
 }
@@ -0,0 +1,389 @@
+use std::borrow::Cow;
+use std::collections::HashSet;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufReader, BufWriter, ErrorKind, Write};
+use std::path::{Path, PathBuf};
+
+use super::in_memory::InMemoryStorage;
+use super::models::{
+    bitfield::BitFieldOp,
+    hyperloglog::HyperLogLog,
+    redis_sorted_set::Scores,
+    redis_stream::{StreamFields, StreamId, XAddId},
+    DataType, Expiry, ExpiryCondition, RedisHashMap, RedisSet, RedisSortedSet, RedisStream,
+    StoredValue,
+};
+use super::snapshot::{self, Change};
+use crate::storage::{sibling_path, EvictionPolicy, KeyspaceStats, MemoryStats, Sibling, Storage};
+
+/// a storage holding its keys in memory and appending each key it changes
+/// to a log file, as the append only file does, the keys being read back
+/// from the log once opened again
+pub struct LogFileStorage {
+    memory: InMemoryStorage,
+    // the log and where it is, none for a storage kept in memory alone
+    log: Option<(File, PathBuf)>,
+    // the stream handed out by xread_mut to be modified in place, written
+    // once the next call is made
+    borrowed: Option<Vec<u8>>,
+    // the first write that failed since the last flush
+    error: Option<io::Error>,
+}
+
+impl LogFileStorage {
+    /// open the log file at path, created if there is none, the keys being
+    /// read into memory then the log compacted
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let mut memory = InMemoryStorage::new();
+        match File::open(&path) {
+            // a change cut short, as a crash leaves it, is dropped
+            Ok(file) => {
+                for change in snapshot::read_changes(&mut BufReader::new(file))? {
+                    match change {
+                        Change::Put(_, key, value) => memory.put(&key, value),
+                        Change::Remove(_, key) => {
+                            memory.remove(&key);
+                        }
+                        Change::SwapDb(..) => {}
+                    }
+                }
+            }
+            Err(error) if error.kind() == ErrorKind::NotFound => {}
+            Err(error) => return Err(error),
+        }
+
+        let mut storage = LogFileStorage {
+            memory,
+            log: None,
+            borrowed: None,
+            error: None,
+        };
+        storage.rewrite(path)?;
+        Ok(storage)
+    }
+
+    /// rewrite the log with the keys as they are now, each key changed many
+    /// times being written once
+    pub fn compact(&mut self) -> io::Result<()> {
+        self.write_borrowed();
+        match self.log.take() {
+            Some((_, path)) => self.rewrite(path),
+            None => Ok(()),
+        }
+    }
+
+    /// write the changes made so far to the disk, or return the error a
+    /// write met since the last flush
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.write_borrowed();
+        if let Some(error) = self.error.take() {
+            return Err(error);
+        }
+        match &self.log {
+            Some((file, _)) => file.sync_data(),
+            None => Ok(()),
+        }
+    }
+
+    /// write the keys in memory to a new log which replaces the one at path,
+    /// so that a rewrite failing halfway leaves the previous log whole
+    fn rewrite(&mut self, path: PathBuf) -> io::Result<()> {
+        let mut temporary = path.clone().into_os_string();
+        temporary.push(".rewrite");
+        let keys = self.memory.snapshot();
+        let written = File::create(&temporary).and_then(|file| {
+            let mut writer = BufWriter::new(file);
+            for (key, value) in keys {
+                snapshot::write_change(&mut writer, &Change::Put(0, key, value))?;
+            }
+            writer.flush()?;
+            writer.get_ref().sync_all()
+        });
+        if let Err(error) = written.and_then(|()| fs::rename(&temporary, &path)) {
+            let _ = fs::remove_file(&temporary);
+            return Err(error);
+        }
+
+        let file = OpenOptions::new().append(true).open(&path)?;
+        self.log = Some((file, path));
+        Ok(())
+    }
+
+    /// append the key as it is in memory, removed if it is not there anymore
+    fn write_key(&mut self, key: &[u8]) {
+        let file = match &mut self.log {
+            Some((file, _)) => file,
+            None => return,
+        };
+        let change = match self.memory.copy(key) {
+            Some(value) => Change::Put(0, key.to_vec(), value),
+            None => Change::Remove(0, key.to_vec()),
+        };
+        let mut bytes = vec![];
+        let _ = snapshot::write_change(&mut bytes, &change);
+
+        if let Err(error) = file.write_all(&bytes) {
+            self.error.get_or_insert(error);
+        }
+    }
+
+    /// write the stream handed out by xread_mut, modified since
+    fn write_borrowed(&mut self) {
+        if let Some(key) = self.borrowed.take() {
+            self.write_key(&key);
+        }
+    }
+
+    /// make a change to the key in memory then write it
+    fn change<R>(&mut self, key: &[u8], change: impl FnOnce(&mut InMemoryStorage) -> R) -> R {
+        self.write_borrowed();
+        let changed = change(&mut self.memory);
+        self.write_key(key);
+        changed
+    }
+}
+
+impl Default for LogFileStorage {
+    /// no log at all, the keys being kept in memory alone
+    fn default() -> Self {
+        LogFileStorage {
+            memory: InMemoryStorage::new(),
+            log: None,
+            borrowed: None,
+            error: None,
+        }
+    }
+}
+
+impl Sibling for LogFileStorage {
+    /// a log of its own next to this one, none if this one has none
+    fn sibling(&self, index: usize) -> io::Result<Self> {
+        match &self.log {
+            Some((_, path)) => LogFileStorage::open(sibling_path(path, index)),
+            None => Ok(LogFileStorage::default()),
+        }
+    }
+}
+
+impl Drop for LogFileStorage {
+    fn drop(&mut self) {
+        let _ = self.flush();
+    }
+}
+
+impl Storage for LogFileStorage {
+    fn write(&mut self, key: &[u8], value: &[u8]) {
+        self.change(key, |memory| memory.write(key, value))
+    }
+
+    fn setbit(&mut self, key: &[u8], offset: usize, bit: bool) -> u8 {
+        self.change(key, |memory| memory.setbit(key, offset, bit))
+    }
+
+    fn bitfield(&mut self, key: &[u8], operations: &[BitFieldOp]) -> Vec<Option<i64>> {
+        self.change(key, |memory| memory.bitfield(key, operations))
+    }
+
+    fn pfadd(&mut self, key: &[u8], elements: &[Vec<u8>]) -> Option<u32> {
+        self.change(key, |memory| memory.pfadd(key, elements))
+    }
+
+    fn pfmerge(&mut self, key: &[u8], hyperloglog: &HyperLogLog) -> Option<()> {
+        self.change(key, |memory| memory.pfmerge(key, hyperloglog))
+    }
+
+    fn hwrite(&mut self, key: &[u8], field: &[u8], value: &[u8]) -> u32 {
+        self.change(key, |memory| memory.hwrite(key, field, value))
+    }
+
+    fn sadd(&mut self, key: &[u8], member: &[u8]) -> u32 {
+        self.change(key, |memory| memory.sadd(key, member))
+    }
+
+    fn swrite(&mut self, key: &[u8], members: HashSet<Vec<u8>>) {
+        self.change(key, |memory| memory.swrite(key, members))
+    }
+
+    fn zadd(&mut self, key: &[u8], score: f64, member: &[u8]) -> u32 {
+        self.change(key, |memory| memory.zadd(key, score, member))
+    }
+
+    fn xadd(&mut self, key: &[u8], id: XAddId, fields: StreamFields) -> Option<StreamId> {
+        self.change(key, |memory| memory.xadd(key, id, fields))
+    }
+
+    fn expire(&mut self, key: &[u8], expiry: Expiry) -> u32 {
+        self.change(key, |memory| memory.expire(key, expiry))
+    }
+
+    fn hexpire(
+        &mut self,
+        key: &[u8],
+        field: &[u8],
+        expiry: Expiry,
+        condition: Option<ExpiryCondition>,
+    ) -> i64 {
+        self.change(key, |memory| memory.hexpire(key, field, expiry, condition))
+    }
+
+    fn hpttl(&mut self, key: &[u8], field: &[u8]) -> i64 {
+        self.write_borrowed();
+        self.memory.hpttl(key, field)
+    }
+
+    fn hpersist(&mut self, key: &[u8], field: &[u8]) -> i64 {
+        self.change(key, |memory| memory.hpersist(key, field))
+    }
+
+    fn read(&self, key: &[u8]) -> Option<Cow<'_, [u8]>> {
+        self.memory.read(key)
+    }
+
+    fn hread(&mut self, key: &[u8], field: &[u8]) -> Option<&[u8]> {
+        self.write_borrowed();
+        self.memory.hread(key, field)
+    }
+
+    fn hread_all(&mut self, key: &[u8]) -> Option<&RedisHashMap> {
+        self.write_borrowed();
+        self.memory.hread_all(key)
+    }
+
+    fn sread(&mut self, key: &[u8]) -> Option<&RedisSet> {
+        self.write_borrowed();
+        self.memory.sread(key)
+    }
+
+    fn sintercard(&mut self, keys: &[Vec<u8>], limit: usize) -> usize {
+        self.write_borrowed();
+        self.memory.sintercard(keys, limit)
+    }
+
+    fn zread(&mut self, key: &[u8]) -> Option<&RedisSortedSet> {
+        self.write_borrowed();
+        self.memory.zread(key)
+    }
+
+    fn zwrite(&mut self, key: &[u8], members: Scores) {
+        self.change(key, |memory| memory.zwrite(key, members))
+    }
+
+    fn xread(&mut self, key: &[u8]) -> Option<&RedisStream> {
+        self.write_borrowed();
+        self.memory.xread(key)
+    }
+
+    fn xread_mut(&mut self, key: &[u8]) -> Option<&mut RedisStream> {
+        self.write_borrowed();
+        self.borrowed = Some(key.to_vec());
+        self.memory.xread_mut(key)
+    }
+
+    fn xcreate(&mut self, key: &[u8]) {
+        self.change(key, |memory| memory.xcreate(key))
+    }
+
+    fn remove(&mut self, key: &[u8]) -> u32 {
+        self.change(key, |memory| memory.remove(key))
+    }
+
+    fn take(&mut self, key: &[u8]) -> Option<StoredValue> {
+        self.change(key, |memory| memory.take(key))
+    }
+
+    fn copy(&mut self, key: &[u8]) -> Option<StoredValue> {
+        self.write_borrowed();
+        self.memory.copy(key)
+    }
+
+    fn put(&mut self, key: &[u8], value: StoredValue) {
+        self.change(key, |memory| memory.put(key, value))
+    }
+
+    fn srem(&mut self, key: &[u8], member: &[u8]) -> u32 {
+        self.change(key, |memory| memory.srem(key, member))
+    }
+
+    fn spop(&mut self, key: &[u8], count: usize) -> Vec<Vec<u8>> {
+        self.change(key, |memory| memory.spop(key, count))
+    }
+
+    fn zrem(&mut self, key: &[u8], member: &[u8]) -> u32 {
+        self.change(key, |memory| memory.zrem(key, member))
+    }
+
+    fn zpop(&mut self, key: &[u8], count: usize, max: bool) -> Vec<(Vec<u8>, f64)> {
+        self.change(key, |memory| memory.zpop(key, count, max))
+    }
+
+    fn contains(&self, key: &[u8]) -> bool {
+        self.memory.contains(key)
+    }
+
+    fn type_of(&mut self, key: &[u8]) -> Option<DataType> {
+        self.write_borrowed();
+        self.memory.type_of(key)
+    }
+
+    fn take_expired(&mut self) -> Vec<Vec<u8>> {
+        self.write_borrowed();
+        let expired = self.memory.take_expired();
+        for key in &expired {
+            self.write_key(key);
+        }
+        expired
+    }
+
+    fn lapsed(&self) -> bool {
+        self.memory.lapsed()
+    }
+
+    fn sample_expired(&mut self, count: usize) -> (usize, usize) {
+        self.write_borrowed();
+        self.memory.sample_expired(count)
+    }
+
+    fn version(&mut self, key: &[u8]) -> u64 {
+        self.memory.version(key)
+    }
+
+    fn memory_usage(&mut self, key: &[u8]) -> Option<usize> {
+        self.write_borrowed();
+        self.memory.memory_usage(key)
+    }
+
+    fn memory_stats(&mut self) -> MemoryStats {
+        self.write_borrowed();
+        self.memory.memory_stats()
+    }
+
+    fn keyspace_stats(&mut self) -> KeyspaceStats {
+        self.write_borrowed();
+        self.memory.keyspace_stats()
+    }
+
+    fn snapshot(&mut self) -> Vec<(Vec<u8>, StoredValue)> {
+        self.write_borrowed();
+        self.memory.snapshot()
+    }
+
+    fn access(&mut self, key: &[u8]) {
+        self.write_borrowed();
+        self.memory.access(key)
+    }
+
+    fn used_memory(&mut self) -> usize {
+        self.write_borrowed();
+        self.memory.used_memory()
+    }
+
+    fn eviction_candidate(
+        &mut self,
+        policy: EvictionPolicy,
+        samples: usize,
+    ) -> Option<(Vec<u8>, u64)> {
+        self.write_borrowed();
+        self.memory.eviction_candidate(policy, samples)
+    }
+}
@@ -2,14 +2,188 @@
 mod tests;
 
 pub mod in_memory;
+pub mod log_file;
 pub mod models;
+pub mod rdb;
+#[cfg(feature = "rocksdb-storage")]
+pub mod rocksdb;
+#[cfg(feature = "sled-storage")]
+pub mod sled;
+pub mod snapshot;
 
-use models::expiry::Expiry;
+use std::borrow::Cow;
+use std::collections::HashSet;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use models::bitfield::BitFieldOp;
+use models::expiry::{Expiry, ExpiryCondition};
+use models::hyperloglog::HyperLogLog;
+use models::redis_sorted_set::Scores;
+use models::redis_stream::{StreamFields, StreamId, XAddId};
+use models::{DataType, RedisHashMap, RedisSet, RedisSortedSet, RedisStream, StoredValue};
 
 pub trait Storage {
     fn write(&mut self, key: &[u8], value: &[u8]);
+    fn setbit(&mut self, key: &[u8], offset: usize, bit: bool) -> u8;
+    fn bitfield(&mut self, key: &[u8], operations: &[BitFieldOp]) -> Vec<Option<i64>>;
+    fn pfadd(&mut self, key: &[u8], elements: &[Vec<u8>]) -> Option<u32>;
+    fn pfmerge(&mut self, key: &[u8], hyperloglog: &HyperLogLog) -> Option<()>;
+    fn hwrite(&mut self, key: &[u8], field: &[u8], value: &[u8]) -> u32;
+    fn sadd(&mut self, key: &[u8], member: &[u8]) -> u32;
+    fn swrite(&mut self, key: &[u8], members: HashSet<Vec<u8>>);
+    fn zadd(&mut self, key: &[u8], score: f64, member: &[u8]) -> u32;
+    fn xadd(&mut self, key: &[u8], id: XAddId, fields: StreamFields) -> Option<StreamId>;
     fn expire(&mut self, key: &[u8], expiry: Expiry) -> u32;
-    fn read(&mut self, key: &[u8]) -> Option<&[u8]>;
+    fn hexpire(
+        &mut self,
+        key: &[u8],
+        field: &[u8],
+        expiry: Expiry,
+        condition: Option<ExpiryCondition>,
+    ) -> i64;
+    fn hpttl(&mut self, key: &[u8], field: &[u8]) -> i64;
+    fn hpersist(&mut self, key: &[u8], field: &[u8]) -> i64;
+    // through &self for the readers to share the storage, an expired key
+    // being left out then removed by the next call that takes &mut self
+    fn read(&self, key: &[u8]) -> Option<Cow<'_, [u8]>>;
+    fn hread(&mut self, key: &[u8], field: &[u8]) -> Option<&[u8]>;
+    fn hread_all(&mut self, key: &[u8]) -> Option<&RedisHashMap>;
+    fn sread(&mut self, key: &[u8]) -> Option<&RedisSet>;
+    fn sintercard(&mut self, keys: &[Vec<u8>], limit: usize) -> usize;
+    fn zread(&mut self, key: &[u8]) -> Option<&RedisSortedSet>;
+    fn zwrite(&mut self, key: &[u8], members: Scores);
+    fn xread(&mut self, key: &[u8]) -> Option<&RedisStream>;
+    fn xread_mut(&mut self, key: &[u8]) -> Option<&mut RedisStream>;
+    fn xcreate(&mut self, key: &[u8]);
     fn remove(&mut self, key: &[u8]) -> u32;
-    fn contains(&mut self, key: &[u8]) -> bool;
+    // remove the key and return its value, unless it expired
+    fn take(&mut self, key: &[u8]) -> Option<StoredValue>;
+    // a copy of the value of the key, unless it expired
+    fn copy(&mut self, key: &[u8]) -> Option<StoredValue>;
+    // replace whatever is stored at key with the value
+    fn put(&mut self, key: &[u8], value: StoredValue);
+    fn srem(&mut self, key: &[u8], member: &[u8]) -> u32;
+    fn spop(&mut self, key: &[u8], count: usize) -> Vec<Vec<u8>>;
+    fn zrem(&mut self, key: &[u8], member: &[u8]) -> u32;
+    fn zpop(&mut self, key: &[u8], count: usize, max: bool) -> Vec<(Vec<u8>, f64)>;
+    // like every read, leaves the key out if it expired, so that an
+    // expired key is never seen whatever the command
+    fn contains(&self, key: &[u8]) -> bool;
+    fn type_of(&mut self, key: &[u8]) -> Option<DataType>;
+    // keys removed because they expired since the last call, the ones the
+    // reads through &self left out included
+    fn take_expired(&mut self) -> Vec<Vec<u8>>;
+    // whether the reads through &self left out keys that expired, which
+    // take_expired is yet to remove
+    fn lapsed(&self) -> bool;
+    // look at up to count keys whose time to live is over, the one that
+    // expired first first, the ones that did still being removed as if
+    // read, returns how many were looked at and how many expired
+    fn sample_expired(&mut self, count: usize) -> (usize, usize);
+    // bumped every time the key is modified, 0 if it never was
+    fn version(&mut self, key: &[u8]) -> u64;
+    // bytes the key takes, its name, its value and what holds them included
+    fn memory_usage(&mut self, key: &[u8]) -> Option<usize>;
+    fn memory_stats(&mut self) -> MemoryStats;
+    fn keyspace_stats(&mut self) -> KeyspaceStats;
+    // every key along with a copy of its value, the expired ones left out
+    fn snapshot(&mut self) -> Vec<(Vec<u8>, StoredValue)>;
+    // count a read of the key, for the eviction policies to tell the keys
+    // used the least, writes being counted as the key is changed
+    fn access(&mut self, key: &[u8]);
+    // bytes the keys take, as memory_usage tells them, kept up to date as
+    // the keys change once it is first called
+    fn used_memory(&mut self) -> usize;
+    // the key the policy evicts first among a few picked at random, along
+    // with how much it should be, the higher the sooner, None if there is no
+    // key the policy may evict
+    fn eviction_candidate(
+        &mut self,
+        policy: EvictionPolicy,
+        samples: usize,
+    ) -> Option<(Vec<u8>, u64)>;
+}
+
+/// a storage the server opens more of, one for each database and shard
+/// besides the one it is given
+pub trait Sibling: Sized {
+    // the storage of the database or shard `index`, the storages writing
+    // their keys to disk writing them next to where this one does, for
+    // them to be read back once opened again
+    fn sibling(&self, index: usize) -> io::Result<Self>;
+    // how many shards the server spreads the keys over unless told, a
+    // single one for the storages writing to disk, as each shard of each
+    // database is then a database on disk of its own
+    fn shards(&self) -> usize {
+        1
+    }
+}
+
+/// where the storage `index` writes its keys, next to the one at path
+pub fn sibling_path(path: &Path, index: usize) -> PathBuf {
+    let mut path = path.as_os_str().to_os_string();
+    path.push(format!(".{}", index));
+    PathBuf::from(path)
+}
+
+/// the keys maxmemory-policy evicts once the keys take more memory than
+/// maxmemory, and the ones it evicts first, noeviction being none
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum EvictionPolicy {
+    VolatileLru,
+    VolatileLfu,
+    VolatileRandom,
+    VolatileTtl,
+    AllKeysLru,
+    AllKeysLfu,
+    AllKeysRandom,
+}
+
+impl EvictionPolicy {
+    /// the policy named as maxmemory-policy names it, None for noeviction
+    pub fn parse(name: &[u8]) -> Option<Self> {
+        match name {
+            b"volatile-lru" => Some(EvictionPolicy::VolatileLru),
+            b"volatile-lfu" => Some(EvictionPolicy::VolatileLfu),
+            b"volatile-random" => Some(EvictionPolicy::VolatileRandom),
+            b"volatile-ttl" => Some(EvictionPolicy::VolatileTtl),
+            b"allkeys-lru" => Some(EvictionPolicy::AllKeysLru),
+            b"allkeys-lfu" => Some(EvictionPolicy::AllKeysLfu),
+            b"allkeys-random" => Some(EvictionPolicy::AllKeysRandom),
+            _ => None,
+        }
+    }
+
+    /// whether only the keys with a time to live may be evicted
+    pub fn is_volatile(&self) -> bool {
+        matches!(
+            self,
+            EvictionPolicy::VolatileLru
+                | EvictionPolicy::VolatileLfu
+                | EvictionPolicy::VolatileRandom
+                | EvictionPolicy::VolatileTtl
+        )
+    }
+}
+
+/// how much memory the keys of a storage take, as MEMORY STATS reports it
+#[derive(Debug, Default, PartialEq)]
+pub struct MemoryStats {
+    pub keys: usize,
+    // bytes spent on holding the keys, their names included
+    pub overhead: usize,
+    // bytes of the values
+    pub dataset: usize,
+}
+
+/// how many keys a storage holds, as the keyspace section of INFO reports
+/// it
+#[derive(Debug, Default, PartialEq)]
+pub struct KeyspaceStats {
+    pub keys: usize,
+    // keys with a time to live
+    pub expires: usize,
+    // average time to live of those keys, in milliseconds
+    pub avg_ttl: u64,
 }
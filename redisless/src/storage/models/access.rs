@@ -0,0 +1,73 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+// the frequency of a key just created, so that it is not evicted before it
+// had a chance to be used
+const LFU_INIT_VAL: u8 = 5;
+// the higher, the more hits it takes for the frequency to grow
+const LFU_LOG_FACTOR: f64 = 10.0;
+// the frequency goes down by one for each period the key is not used
+const LFU_DECAY_TIME: Duration = Duration::from_secs(60);
+
+// the LRU clock, ticking once for each use of a key rather than with time,
+// so that of two keys the one used first is the one used the least however
+// close the uses were
+static LRU_CLOCK: AtomicU64 = AtomicU64::new(0);
+
+fn tick() -> u64 {
+    LRU_CLOCK.fetch_add(1, Ordering::Relaxed) + 1
+}
+
+/// when a key was last used and how often it is, as the LRU and LFU
+/// eviction policies of Redis tell the keys to evict apart
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct Access {
+    // the LRU clock once last used
+    used: u64,
+    last: Instant,
+    // a logarithmic counter, as Redis keeps it in 8 bits
+    frequency: u8,
+}
+
+impl Access {
+    pub fn new() -> Self {
+        Access {
+            used: tick(),
+            last: Instant::now(),
+            frequency: LFU_INIT_VAL,
+        }
+    }
+
+    /// how many times the keys were used since the key was last used
+    pub fn idle(&self) -> u64 {
+        LRU_CLOCK.load(Ordering::Relaxed).saturating_sub(self.used)
+    }
+
+    /// the frequency once decayed for the time the key was not used
+    pub fn frequency(&self) -> u8 {
+        let periods = self.last.elapsed().as_secs() / LFU_DECAY_TIME.as_secs();
+        self.frequency
+            .saturating_sub(periods.min(u8::MAX as u64) as u8)
+    }
+
+    /// count a use of the key, the frequency being incremented less and
+    /// less likely as it grows
+    pub fn hit(&mut self) {
+        let mut frequency = self.frequency();
+        if frequency < u8::MAX {
+            let base = frequency.saturating_sub(LFU_INIT_VAL) as f64;
+            if rand::random::<f64>() < 1.0 / (base * LFU_LOG_FACTOR + 1.0) {
+                frequency += 1;
+            }
+        }
+        self.frequency = frequency;
+        self.used = tick();
+        self.last = Instant::now();
+    }
+}
+
+impl Default for Access {
+    fn default() -> Self {
+        Access::new()
+    }
+}
@@ -0,0 +1,126 @@
+use super::redis_value::get_bit;
+
+/// signed or unsigned integer of 1 to 64 bits, as in `i5` or `u8`
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct BitFieldType {
+    pub signed: bool,
+    pub bits: u32,
+}
+
+impl BitFieldType {
+    fn min(&self) -> i128 {
+        match self.signed {
+            true => -(1 << (self.bits - 1)),
+            false => 0,
+        }
+    }
+
+    fn max(&self) -> i128 {
+        match self.signed {
+            true => (1 << (self.bits - 1)) - 1,
+            false => (1 << self.bits) - 1,
+        }
+    }
+}
+
+/// what happens when SET or INCRBY goes beyond the range of the type
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum Overflow {
+    Wrap,
+    Sat,
+    Fail,
+}
+
+impl Overflow {
+    /// bring the value within the range of the type, None when failing
+    fn apply(&self, field_type: BitFieldType, value: i128) -> Option<i64> {
+        let (min, max) = (field_type.min(), field_type.max());
+        if value >= min && value <= max {
+            return Some(value as i64);
+        }
+
+        match self {
+            Overflow::Wrap => Some(((value - min).rem_euclid(max - min + 1) + min) as i64),
+            Overflow::Sat if value < min => Some(min as i64),
+            Overflow::Sat => Some(max as i64),
+            Overflow::Fail => None,
+        }
+    }
+}
+
+/// a BITFIELD subcommand, with its offset in bits and the overflow
+/// behavior in force when it was given
+#[derive(Debug, PartialEq, Clone)]
+pub enum BitFieldOp {
+    Get(BitFieldType, usize),
+    Set(BitFieldType, usize, i64, Overflow),
+    IncrBy(BitFieldType, usize, i64, Overflow),
+}
+
+impl BitFieldOp {
+    pub fn is_read_only(&self) -> bool {
+        matches!(self, BitFieldOp::Get(..))
+    }
+
+    /// offset of the bit following the field
+    pub fn end(&self) -> usize {
+        match self {
+            BitFieldOp::Get(field_type, offset)
+            | BitFieldOp::Set(field_type, offset, _, _)
+            | BitFieldOp::IncrBy(field_type, offset, _, _) => offset + field_type.bits as usize,
+        }
+    }
+
+    /// run the subcommand against the value, returns the field for GET, its
+    /// previous value for SET and its new value for INCRBY, None when the
+    /// overflow behavior is FAIL and the field would overflow
+    pub fn apply(&self, data: &mut Vec<u8>) -> Option<i64> {
+        match self {
+            BitFieldOp::Get(field_type, offset) => Some(get_field(data, *field_type, *offset)),
+            BitFieldOp::Set(field_type, offset, value, overflow) => {
+                let value = overflow.apply(*field_type, *value as i128)?;
+                let previous = get_field(data, *field_type, *offset);
+                set_field(data, *field_type, *offset, value);
+                Some(previous)
+            }
+            BitFieldOp::IncrBy(field_type, offset, increment, overflow) => {
+                let current = get_field(data, *field_type, *offset);
+                let value = overflow.apply(*field_type, current as i128 + *increment as i128)?;
+                set_field(data, *field_type, *offset, value);
+                Some(value)
+            }
+        }
+    }
+}
+
+/// integer stored in the bits starting at offset, most significant bit first
+fn get_field(data: &[u8], field_type: BitFieldType, offset: usize) -> i64 {
+    let bits = (offset..offset + field_type.bits as usize).fold(0u64, |field, offset| {
+        (field << 1) | get_bit(data, offset) as u64
+    });
+
+    // sign extension
+    let shift = 64 - field_type.bits;
+    match field_type.signed {
+        true => ((bits << shift) as i64) >> shift,
+        false => bits as i64,
+    }
+}
+
+/// store the lowest bits of value starting at offset, zero-extending the
+/// data if needed
+fn set_field(data: &mut Vec<u8>, field_type: BitFieldType, offset: usize, value: i64) {
+    let end = offset + field_type.bits as usize;
+    if data.len() * 8 < end {
+        data.resize(end.div_ceil(8), 0);
+    }
+
+    for (i, offset) in (offset..end).enumerate() {
+        let bit = (value >> (field_type.bits as usize - 1 - i)) & 1;
+        let mask = 1 << (7 - offset % 8);
+        match bit {
+            1 => data[offset / 8] |= mask,
+            _ => data[offset / 8] &= !mask,
+        }
+    }
+}
@@ -1,4 +1,6 @@
-use std::time::{Duration, Instant};
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
 #[derive(Debug, PartialEq, Copy, Clone)]
 pub struct Expiry {
@@ -22,4 +24,129 @@ impl Expiry {
             .map(|t| Self { timestamp: t })
             .ok_or(TimeOverflow {})
     }
+
+    pub fn is_expired(&self) -> bool {
+        self.timestamp <= Instant::now()
+    }
+
+    /// remaining time to live in milliseconds, 0 when already expired
+    pub fn ttl_millis(&self) -> u64 {
+        self.timestamp
+            .checked_duration_since(Instant::now())
+            .map_or(0, |duration| duration.as_millis() as u64)
+    }
+
+    /// the expiry at a unix time in milliseconds, as a snapshot keeps it,
+    /// one in the past having expired already
+    pub fn new_from_unix_millis(time: u64) -> Result<Self, TimeOverflow> {
+        Self::new_from_millis(time.saturating_sub(unix_millis()))
+    }
+
+    /// the unix time in milliseconds of the expiry
+    pub fn unix_millis(&self) -> u64 {
+        unix_millis() + self.ttl_millis()
+    }
+}
+
+fn unix_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_millis() as u64)
+}
+
+/// NX | XX | GT | LT options of the expire command family
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum ExpiryCondition {
+    // set expiry only when the target has no expiry
+    Nx,
+    // set expiry only when the target already has an expiry
+    Xx,
+    // set expiry only when the new expiry is greater than the current one
+    Gt,
+    // set expiry only when the new expiry is less than the current one
+    Lt,
+}
+
+impl ExpiryCondition {
+    pub fn is_met(&self, current: Option<&Expiry>, new: &Expiry) -> bool {
+        match (self, current) {
+            (ExpiryCondition::Nx, current) => current.is_none(),
+            (ExpiryCondition::Xx, current) => current.is_some(),
+            // no expiry means an infinite ttl
+            (ExpiryCondition::Gt, None) => false,
+            (ExpiryCondition::Gt, Some(current)) => new.timestamp > current.timestamp,
+            (ExpiryCondition::Lt, None) => true,
+            (ExpiryCondition::Lt, Some(current)) => new.timestamp < current.timestamp,
+        }
+    }
+}
+
+/// the keys given an expiry, the one expiring first on top, so that the
+/// keys that expired are found without looking at the others. An entry is
+/// left behind when its key is removed or given another expiry, so it has
+/// to be checked against the key once popped
+#[derive(Debug, Default)]
+pub struct ExpiryIndex {
+    heap: BinaryHeap<Reverse<(Instant, Vec<u8>)>>,
+}
+
+impl ExpiryIndex {
+    pub fn new() -> Self {
+        ExpiryIndex::default()
+    }
+
+    pub fn push(&mut self, key: &[u8], expiry: Expiry) {
+        self.heap.push(Reverse((expiry.timestamp, key.to_vec())));
+    }
+
+    /// the next key whose expiry is over, along with that expiry
+    pub fn pop_expired(&mut self) -> Option<(Vec<u8>, Expiry)> {
+        match self.heap.peek() {
+            Some(Reverse((timestamp, _))) if *timestamp <= Instant::now() => {
+                let Reverse((timestamp, key)) = self.heap.pop()?;
+                Some((key, Expiry { timestamp }))
+            }
+            _ => None,
+        }
+    }
+
+    /// up to `amount` entries picked at random, the ones left behind included
+    pub fn sample(&self, amount: usize) -> Vec<(&[u8], Expiry)> {
+        let entries = self.heap.as_slice();
+        let amount = amount.min(entries.len());
+        rand::seq::index::sample(&mut rand::thread_rng(), entries.len(), amount)
+            .into_iter()
+            .map(|i| {
+                let Reverse((timestamp, key)) = &entries[i];
+                (
+                    key.as_slice(),
+                    Expiry {
+                        timestamp: *timestamp,
+                    },
+                )
+            })
+            .collect()
+    }
+
+    /// entries, the ones left behind included
+    pub fn len(&self) -> usize {
+        self.heap.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.heap.is_empty()
+    }
+
+    /// keep the entries for which f is true, to get rid of the ones left
+    /// behind
+    pub fn retain(&mut self, mut f: impl FnMut(&[u8], Expiry) -> bool) {
+        self.heap.retain(|Reverse((timestamp, key))| {
+            f(
+                key,
+                Expiry {
+                    timestamp: *timestamp,
+                },
+            )
+        });
+    }
 }
@@ -0,0 +1,202 @@
+/// bits of precision of each coordinate, the score holding 52 bits
+const STEP: u32 = 26;
+const LONGITUDE_MIN: f64 = -180.0;
+const LONGITUDE_MAX: f64 = 180.0;
+/// the limits of the Web Mercator projection, as used by Redis
+const LATITUDE_MIN: f64 = -85.051_128_78;
+const LATITUDE_MAX: f64 = 85.051_128_78;
+const EARTH_RADIUS_IN_METERS: f64 = 6_372_797.560_856;
+
+/// unit of the distances given to and returned by the geo commands
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum DistanceUnit {
+    Meters,
+    Kilometers,
+    Miles,
+    Feet,
+}
+
+impl DistanceUnit {
+    fn meters(&self) -> f64 {
+        match self {
+            DistanceUnit::Meters => 1.0,
+            DistanceUnit::Kilometers => 1000.0,
+            DistanceUnit::Miles => 1609.34,
+            DistanceUnit::Feet => 0.3048,
+        }
+    }
+
+    pub fn from_meters(&self, meters: f64) -> f64 {
+        meters / self.meters()
+    }
+
+    pub fn to_meters(&self, distance: f64) -> f64 {
+        distance * self.meters()
+    }
+}
+
+/// center of a GEOSEARCH
+#[derive(Debug, PartialEq, Clone)]
+pub enum GeoOrigin {
+    Member(Vec<u8>),
+    // longitude and latitude
+    Position(f64, f64),
+}
+
+/// area of a GEOSEARCH around its center, in meters
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum GeoShape {
+    Radius(f64),
+    // width and height
+    Box(f64, f64),
+}
+
+impl GeoShape {
+    /// distance in meters between center and position, None if the
+    /// position is outside of the shape
+    pub fn distance_within(&self, center: (f64, f64), position: (f64, f64)) -> Option<f64> {
+        let distance = distance(center, position);
+
+        match *self {
+            GeoShape::Radius(radius) if distance <= radius => Some(distance),
+            GeoShape::Box(width, height) => {
+                // like Redis, the width is measured at the latitude of the position
+                let latitude_distance = EARTH_RADIUS_IN_METERS
+                    * (position.1.to_radians() - center.1.to_radians()).abs();
+                let longitude_distance = self::distance((center.0, position.1), position);
+
+                match latitude_distance <= height / 2.0 && longitude_distance <= width / 2.0 {
+                    true => Some(distance),
+                    false => None,
+                }
+            }
+            GeoShape::Radius(_) => None,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct GeoQuery {
+    pub origin: GeoOrigin,
+    pub shape: GeoShape,
+    // unit of the shape, and of the distances replied or stored
+    pub unit: DistanceUnit,
+    // Some(true) to sort from the farthest to the nearest member
+    pub desc: Option<bool>,
+    // maximum number of members, and whether any of the matching ones do
+    pub count: Option<(usize, bool)>,
+}
+
+impl GeoQuery {
+    /// members within the shape around center, with their score and their
+    /// distance in meters
+    pub fn search<'a>(
+        &self,
+        members: impl Iterator<Item = (&'a [u8], f64)>,
+        center: (f64, f64),
+    ) -> Vec<(&'a [u8], f64, f64)> {
+        let mut found = vec![];
+        for (member, score) in members {
+            if let Some(distance) = self.shape.distance_within(center, decode(score)) {
+                found.push((member, score, distance));
+            }
+
+            match self.count {
+                Some((count, true)) if found.len() == count => break,
+                _ => {}
+            }
+        }
+
+        // a COUNT with no ANY keeps the nearest members
+        let desc = match (self.desc, self.count) {
+            (Some(desc), _) => Some(desc),
+            (None, Some((_, false))) => Some(false),
+            (None, _) => None,
+        };
+
+        if let Some(desc) = desc {
+            found.sort_by(|a, b| match desc {
+                true => b.2.partial_cmp(&a.2).unwrap(),
+                false => a.2.partial_cmp(&b.2).unwrap(),
+            });
+        }
+
+        if let Some((count, _)) = self.count {
+            found.truncate(count);
+        }
+
+        found
+    }
+}
+
+/// what GEOSEARCH replies with along with each member
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct GeoReplyOptions {
+    pub with_coord: bool,
+    pub with_dist: bool,
+    pub with_hash: bool,
+}
+
+/// whether a longitude and latitude pair can be indexed
+pub fn is_valid(longitude: f64, latitude: f64) -> bool {
+    (LONGITUDE_MIN..=LONGITUDE_MAX).contains(&longitude)
+        && (LATITUDE_MIN..=LATITUDE_MAX).contains(&latitude)
+}
+
+/// the 52 bits geohash of a position, used as the score of its member
+pub fn encode(longitude: f64, latitude: f64) -> f64 {
+    let scale = |value: f64, min: f64, max: f64| {
+        let offset = (value - min) / (max - min);
+        ((offset * (1u64 << STEP) as f64) as u64).min((1 << STEP) - 1)
+    };
+
+    let longitude = scale(longitude, LONGITUDE_MIN, LONGITUDE_MAX);
+    let latitude = scale(latitude, LATITUDE_MIN, LATITUDE_MAX);
+
+    interleave(latitude, longitude) as f64
+}
+
+/// the position at the center of the area covered by a geohash score
+pub fn decode(score: f64) -> (f64, f64) {
+    let (latitude, longitude) = deinterleave(score as u64);
+
+    let center = |offset: u64, min: f64, max: f64| {
+        let cell = (max - min) / (1u64 << STEP) as f64;
+        let center = min + cell * offset as f64 + cell / 2.0;
+        center.max(min).min(max)
+    };
+
+    (
+        center(longitude, LONGITUDE_MIN, LONGITUDE_MAX),
+        center(latitude, LATITUDE_MIN, LATITUDE_MAX),
+    )
+}
+
+/// haversine distance in meters between two longitude and latitude pairs
+pub fn distance(from: (f64, f64), to: (f64, f64)) -> f64 {
+    let (longitude1, latitude1) = (from.0.to_radians(), from.1.to_radians());
+    let (longitude2, latitude2) = (to.0.to_radians(), to.1.to_radians());
+
+    let u = ((latitude2 - latitude1) / 2.0).sin();
+    let v = ((longitude2 - longitude1) / 2.0).sin();
+    2.0 * EARTH_RADIUS_IN_METERS
+        * (u * u + latitude1.cos() * latitude2.cos() * v * v)
+            .sqrt()
+            .asin()
+}
+
+/// bits of x at the even positions and bits of y at the odd ones
+fn interleave(x: u64, y: u64) -> u64 {
+    (0..STEP).fold(0, |bits, i| {
+        bits | ((x >> i) & 1) << (2 * i) | ((y >> i) & 1) << (2 * i + 1)
+    })
+}
+
+fn deinterleave(bits: u64) -> (u64, u64) {
+    (0..STEP).fold((0, 0), |(x, y), i| {
+        (
+            x | ((bits >> (2 * i)) & 1) << i,
+            y | ((bits >> (2 * i + 1)) & 1) << i,
+        )
+    })
+}
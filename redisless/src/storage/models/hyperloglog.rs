@@ -0,0 +1,232 @@
+/// number of bits of the hash used to pick a register
+const INDEX_BITS: u32 = 14;
+const REGISTERS: usize = 1 << INDEX_BITS;
+const REGISTER_BITS: usize = 6;
+const REGISTER_MAX: u8 = (1 << REGISTER_BITS) - 1;
+/// "HYLL", the encoding, 3 unused bytes and the cached cardinality
+const HEADER_LEN: usize = 16;
+const DENSE_LEN: usize = HEADER_LEN + REGISTERS * REGISTER_BITS / 8;
+const DENSE: u8 = 0;
+const SPARSE: u8 = 1;
+const SEED: u64 = 0xadc8_3b19;
+const ALPHA_INF: f64 = 0.721_347_520_444_481_7;
+
+/// a HyperLogLog with 16384 registers, stored within a string the same way
+/// Redis does so that values can be moved back and forth with GET and SET
+#[derive(Debug, PartialEq, Clone)]
+pub struct HyperLogLog {
+    registers: Vec<u8>,
+}
+
+impl HyperLogLog {
+    pub fn new() -> Self {
+        HyperLogLog {
+            registers: vec![0; REGISTERS],
+        }
+    }
+
+    /// read a dense or sparse HyperLogLog, None if the bytes are not one
+    pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        if bytes.len() < HEADER_LEN || &bytes[..4] != b"HYLL" {
+            return None;
+        }
+
+        match bytes[4] {
+            DENSE if bytes.len() == DENSE_LEN => {
+                let registers = (0..REGISTERS)
+                    .map(|index| get_dense_register(&bytes[HEADER_LEN..], index))
+                    .collect();
+                Some(HyperLogLog { registers })
+            }
+            SPARSE => read_sparse(&bytes[HEADER_LEN..]).map(|registers| HyperLogLog { registers }),
+            _ => None,
+        }
+    }
+
+    /// the dense representation, with the cached cardinality marked stale
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = vec![0; DENSE_LEN];
+        bytes[..4].copy_from_slice(b"HYLL");
+        bytes[4] = DENSE;
+        bytes[HEADER_LEN - 1] = 1 << 7;
+
+        for (index, register) in self.registers.iter().enumerate() {
+            set_dense_register(&mut bytes[HEADER_LEN..], index, *register);
+        }
+
+        bytes
+    }
+
+    /// returns true if a register has been updated
+    pub fn add(&mut self, element: &[u8]) -> bool {
+        let hash = murmur_hash_64a(element, SEED);
+        let index = (hash & (REGISTERS as u64 - 1)) as usize;
+        // the bit past the 50 remaining ones guarantees the loop ends
+        let hash = (hash >> INDEX_BITS) | (1 << (64 - INDEX_BITS));
+        let count = hash.trailing_zeros() as u8 + 1;
+
+        match count > self.registers[index] {
+            true => {
+                self.registers[index] = count;
+                true
+            }
+            false => false,
+        }
+    }
+
+    /// keep the highest of both registers, which is the union of both sets
+    pub fn merge(&mut self, other: &HyperLogLog) {
+        self.registers
+            .iter_mut()
+            .zip(other.registers.iter())
+            .for_each(|(register, other)| *register = (*register).max(*other));
+    }
+
+    /// estimated cardinality, using the same estimator as Redis
+    pub fn count(&self) -> u64 {
+        let q = 64 - INDEX_BITS as usize;
+        let m = REGISTERS as f64;
+
+        let mut histogram = [0u32; 64];
+        self.registers
+            .iter()
+            .for_each(|register| histogram[*register as usize] += 1);
+
+        let mut z = m * tau((m - histogram[q + 1] as f64) / m);
+        for j in (1..=q).rev() {
+            z += histogram[j] as f64;
+            z *= 0.5;
+        }
+        z += m * sigma(histogram[0] as f64 / m);
+
+        (ALPHA_INF * m * m / z).round() as u64
+    }
+}
+
+impl Default for HyperLogLog {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// registers are 6 bits long and packed starting from the least
+/// significant bit of each byte
+fn get_dense_register(registers: &[u8], index: usize) -> u8 {
+    let bit = index * REGISTER_BITS;
+    let (byte, shift) = (bit / 8, bit % 8);
+    let low = registers[byte] as u16 >> shift;
+    let high = (registers.get(byte + 1).copied().unwrap_or(0) as u16) << (8 - shift);
+
+    ((low | high) as u8) & REGISTER_MAX
+}
+
+fn set_dense_register(registers: &mut [u8], index: usize, value: u8) {
+    let bit = index * REGISTER_BITS;
+    let (byte, shift) = (bit / 8, bit % 8);
+    let value = value as u16 & REGISTER_MAX as u16;
+
+    registers[byte] &= !((REGISTER_MAX as u16) << shift) as u8;
+    registers[byte] |= (value << shift) as u8;
+    if shift > 8 - REGISTER_BITS {
+        registers[byte + 1] &= !((REGISTER_MAX as u16) >> (8 - shift)) as u8;
+        registers[byte + 1] |= (value >> (8 - shift)) as u8;
+    }
+}
+
+/// the sparse representation is a sequence of runs: ZERO is 00xxxxxx,
+/// XZERO is 01xxxxxx yyyyyyyy and VAL is 1vvvvvxx
+fn read_sparse(bytes: &[u8]) -> Option<Vec<u8>> {
+    let mut registers = Vec::with_capacity(REGISTERS);
+
+    let mut bytes = bytes.iter();
+    while let Some(byte) = bytes.next() {
+        let (value, run) = match byte >> 6 {
+            0b00 => (0, (byte & 0x3f) as usize + 1),
+            0b01 => {
+                let low = *bytes.next()? as usize;
+                (0, (((byte & 0x3f) as usize) << 8 | low) + 1)
+            }
+            _ => (((byte >> 2) & 0x1f) + 1, (byte & 0x03) as usize + 1),
+        };
+
+        if registers.len() + run > REGISTERS {
+            return None;
+        }
+        registers.resize(registers.len() + run, value);
+    }
+
+    match registers.len() == REGISTERS {
+        true => Some(registers),
+        false => None,
+    }
+}
+
+fn sigma(mut x: f64) -> f64 {
+    if x == 1.0 {
+        return f64::INFINITY;
+    }
+
+    let mut y = 1.0;
+    let mut z = x;
+    loop {
+        x *= x;
+        let previous = z;
+        z += x * y;
+        y += y;
+        if previous == z {
+            return z;
+        }
+    }
+}
+
+fn tau(mut x: f64) -> f64 {
+    if x == 0.0 || x == 1.0 {
+        return 0.0;
+    }
+
+    let mut y = 1.0;
+    let mut z = 1.0 - x;
+    loop {
+        x = x.sqrt();
+        let previous = z;
+        y *= 0.5;
+        z -= (1.0 - x).powi(2) * y;
+        if previous == z {
+            return z / 3.0;
+        }
+    }
+}
+
+/// MurmurHash64A, the hash Redis uses for its HyperLogLogs
+fn murmur_hash_64a(key: &[u8], seed: u64) -> u64 {
+    const M: u64 = 0xc6a4_a793_5bd1_e995;
+    const R: u32 = 47;
+
+    let mut h = seed ^ (key.len() as u64).wrapping_mul(M);
+
+    let chunks = key.chunks_exact(8);
+    let tail = chunks.remainder();
+    for chunk in chunks {
+        let mut k = u64::from_le_bytes([
+            chunk[0], chunk[1], chunk[2], chunk[3], chunk[4], chunk[5], chunk[6], chunk[7],
+        ]);
+        k = k.wrapping_mul(M);
+        k ^= k >> R;
+        k = k.wrapping_mul(M);
+
+        h ^= k;
+        h = h.wrapping_mul(M);
+    }
+
+    if !tail.is_empty() {
+        for (i, byte) in tail.iter().enumerate() {
+            h ^= (*byte as u64) << (8 * i);
+        }
+        h = h.wrapping_mul(M);
+    }
+
+    h ^= h >> R;
+    h = h.wrapping_mul(M);
+    h ^= h >> R;
+    h
+}
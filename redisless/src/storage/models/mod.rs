@@ -1,14 +1,61 @@
+pub mod access;
+pub mod bitfield;
 pub mod expiry;
+pub mod geo;
+pub mod hyperloglog;
+pub mod redis_hash_map;
+pub mod redis_set;
+pub mod redis_sorted_set;
+pub mod redis_stream;
 pub mod redis_value;
+pub mod sort;
+
+use std::mem::size_of;
 
 // re-export so one can use with models::Expiry
 // rather than models::expiry::Expiry
-pub use expiry::Expiry;
+pub use expiry::{Expiry, ExpiryCondition, ExpiryIndex};
+pub use redis_hash_map::RedisHashMap;
+pub use redis_set::RedisSet;
+pub use redis_sorted_set::RedisSortedSet;
+pub use redis_stream::RedisStream;
 pub use redis_value::RedisValue;
 
+/// bytes an entry of a collection takes, besides the bytes it points to,
+/// the control byte of a hash table included
+pub fn entry_size<T>() -> usize {
+    size_of::<T>() + 1
+}
+
+/// the value of a key whatever its type, along with its expiry, as taken
+/// out of a storage to be put in another one
+#[derive(Debug, PartialEq, Clone)]
+pub enum StoredValue {
+    String(RedisValue),
+    Set(RedisSet),
+    SortedSet(RedisSortedSet),
+    Hash(RedisHashMap),
+    Stream(RedisStream),
+}
+
+impl StoredValue {
+    pub fn expiry(&self) -> Option<&Expiry> {
+        match self {
+            StoredValue::String(value) => value.expiry.as_ref(),
+            StoredValue::Set(set) => set.expiry.as_ref(),
+            StoredValue::SortedSet(sorted_set) => sorted_set.expiry.as_ref(),
+            StoredValue::Hash(hash) => hash.expiry.as_ref(),
+            StoredValue::Stream(stream) => stream.expiry.as_ref(),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Copy, Clone)]
 pub enum DataType {
     String,
     List,
     Set,
+    SortedSet,
     Hash,
+    Stream,
 }
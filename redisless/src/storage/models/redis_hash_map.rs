@@ -0,0 +1,131 @@
+use std::collections::HashMap;
+use std::mem::size_of;
+use std::time::Instant;
+
+use super::{entry_size, Expiry, ExpiryCondition};
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct RedisHashMap {
+    pub data: HashMap<Vec<u8>, Vec<u8>>,
+    pub expiry: Option<Expiry>,
+    pub field_expiry: HashMap<Vec<u8>, Expiry>,
+}
+
+impl RedisHashMap {
+    pub fn new(data: HashMap<Vec<u8>, Vec<u8>>, expiry: Option<Expiry>) -> Self {
+        RedisHashMap {
+            data,
+            expiry,
+            field_expiry: HashMap::new(),
+        }
+    }
+
+    /// bytes the hash takes in memory, estimated from the lengths of its
+    /// fields and values
+    pub fn memory_usage(&self) -> usize {
+        let data = self
+            .data
+            .iter()
+            .map(|(field, value)| entry_size::<(Vec<u8>, Vec<u8>)>() + field.len() + value.len());
+        let field_expiry = self
+            .field_expiry
+            .keys()
+            .map(|field| entry_size::<(Vec<u8>, Expiry)>() + field.len());
+        size_of::<Self>() + data.sum::<usize>() + field_expiry.sum::<usize>()
+    }
+
+    pub fn is_expired(&self) -> bool {
+        matches!(&self.expiry, Some(expiry) if expiry.timestamp <= Instant::now())
+    }
+
+    /// write a field, dropping the ttl it may have had
+    pub fn insert(&mut self, field: &[u8], value: &[u8]) -> Option<Vec<u8>> {
+        self.field_expiry.remove(field);
+        self.data.insert(field.to_vec(), value.to_vec())
+    }
+
+    pub fn get(&self, field: &[u8]) -> Option<&Vec<u8>> {
+        match self.field_expiry.get(field) {
+            Some(expiry) if expiry.is_expired() => None,
+            _ => self.data.get(field),
+        }
+    }
+
+    pub fn remove(&mut self, field: &[u8]) -> Option<Vec<u8>> {
+        self.field_expiry.remove(field);
+        self.data.remove(field)
+    }
+
+    /// remove the fields whose ttl is over
+    pub fn remove_expired_fields(&mut self) {
+        let expired_fields = self
+            .field_expiry
+            .iter()
+            .filter(|(_, expiry)| expiry.is_expired())
+            .map(|(field, _)| field.clone())
+            .collect::<Vec<_>>();
+
+        for field in expired_fields {
+            self.remove(&field);
+        }
+    }
+
+    /// whether a field is left once the expired ones are left out
+    pub fn has_live_fields(&self) -> bool {
+        self.data.keys().any(|field| self.get(field).is_some())
+    }
+
+    /// -2 if the field does not exist, 0 if the condition is not met,
+    /// 1 if the expiry has been set and 2 if the field has been deleted
+    /// because the expiry is already in the past
+    pub fn expire_field(
+        &mut self,
+        field: &[u8],
+        expiry: Expiry,
+        condition: Option<ExpiryCondition>,
+    ) -> i64 {
+        if self.get(field).is_none() {
+            return -2;
+        }
+
+        if let Some(condition) = condition {
+            if !condition.is_met(self.field_expiry.get(field), &expiry) {
+                return 0;
+            }
+        }
+
+        if expiry.is_expired() {
+            self.remove(field);
+            return 2;
+        }
+
+        self.field_expiry.insert(field.to_vec(), expiry);
+        1
+    }
+
+    /// -2 if the field does not exist, -1 if the field has no ttl,
+    /// the remaining ttl in milliseconds otherwise
+    pub fn field_ttl_millis(&self, field: &[u8]) -> i64 {
+        if self.get(field).is_none() {
+            return -2;
+        }
+
+        match self.field_expiry.get(field) {
+            Some(expiry) => expiry.ttl_millis() as i64,
+            None => -1,
+        }
+    }
+
+    /// -2 if the field does not exist, -1 if the field has no ttl,
+    /// 1 if the ttl has been removed
+    pub fn persist_field(&mut self, field: &[u8]) -> i64 {
+        if self.get(field).is_none() {
+            return -2;
+        }
+
+        match self.field_expiry.remove(field) {
+            Some(_) => 1,
+            None => -1,
+        }
+    }
+}
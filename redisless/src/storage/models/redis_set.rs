@@ -0,0 +1,112 @@
+use std::collections::HashSet;
+use std::mem::size_of;
+use std::time::Instant;
+
+use rand::seq::IteratorRandom;
+
+use super::{entry_size, Expiry};
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct RedisSet {
+    pub data: HashSet<Vec<u8>>,
+    pub expiry: Option<Expiry>,
+}
+
+impl RedisSet {
+    pub fn new(data: HashSet<Vec<u8>>, expiry: Option<Expiry>) -> Self {
+        RedisSet { data, expiry }
+    }
+
+    /// bytes the set takes in memory, estimated from the lengths of its
+    /// members
+    pub fn memory_usage(&self) -> usize {
+        let data = self
+            .data
+            .iter()
+            .map(|member| entry_size::<Vec<u8>>() + member.len());
+        size_of::<Self>() + data.sum::<usize>()
+    }
+
+    pub fn is_expired(&self) -> bool {
+        matches!(&self.expiry, Some(expiry) if expiry.timestamp <= Instant::now())
+    }
+
+    /// remove and return up to count random members
+    pub fn pop(&mut self, count: usize) -> Vec<Vec<u8>> {
+        let members = self.random_members(count as i64);
+        members.iter().for_each(|member| {
+            self.data.remove(member);
+        });
+
+        members
+    }
+
+    /// up to count distinct random members when count is positive,
+    /// exactly -count members that may repeat when count is negative
+    pub fn random_members(&self, count: i64) -> Vec<Vec<u8>> {
+        let mut rng = rand::thread_rng();
+
+        if count >= 0 {
+            self.data
+                .iter()
+                .choose_multiple(&mut rng, count as usize)
+                .into_iter()
+                .cloned()
+                .collect()
+        } else {
+            (0..count.unsigned_abs())
+                .filter_map(|_| self.data.iter().choose(&mut rng).cloned())
+                .collect()
+        }
+    }
+}
+
+/// members found in every set
+pub fn intersection(sets: &[HashSet<Vec<u8>>]) -> HashSet<Vec<u8>> {
+    match sets.split_first() {
+        Some((first, others)) => first
+            .iter()
+            .filter(|member| others.iter().all(|set| set.contains(*member)))
+            .cloned()
+            .collect(),
+        None => HashSet::new(),
+    }
+}
+
+/// number of members found in every set, counting stops once limit is
+/// reached (0 means no limit) and the intersection is never materialized
+pub fn intersection_cardinality(sets: &[&HashSet<Vec<u8>>], limit: usize) -> usize {
+    let smallest = match sets.iter().min_by_key(|set| set.len()) {
+        Some(smallest) => smallest,
+        None => return 0,
+    };
+
+    let mut cardinality = 0;
+    for member in smallest.iter() {
+        if sets.iter().all(|set| set.contains(member)) {
+            cardinality += 1;
+            if cardinality == limit {
+                break;
+            }
+        }
+    }
+
+    cardinality
+}
+
+/// members found in at least one set
+pub fn union(sets: &[HashSet<Vec<u8>>]) -> HashSet<Vec<u8>> {
+    sets.iter().flatten().cloned().collect()
+}
+
+/// members of the first set not found in any of the other sets
+pub fn difference(sets: &[HashSet<Vec<u8>>]) -> HashSet<Vec<u8>> {
+    match sets.split_first() {
+        Some((first, others)) => first
+            .iter()
+            .filter(|member| !others.iter().any(|set| set.contains(*member)))
+            .cloned()
+            .collect(),
+        None => HashSet::new(),
+    }
+}
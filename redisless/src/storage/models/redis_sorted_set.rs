@@ -0,0 +1,487 @@
+use std::cmp::Ordering;
+use std::collections::{BTreeSet, HashMap};
+use std::mem::size_of;
+use std::time::Instant;
+
+use rand::seq::IteratorRandom;
+
+use super::{entry_size, Expiry};
+
+/// members and their scores, in no particular order
+pub type Scores = HashMap<Vec<u8>, f64>;
+
+/// f64 wrapper ordering scores, NaN is never stored in a sorted set
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct Score(pub f64);
+
+impl Eq for Score {}
+
+impl PartialOrd for Score {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Score {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0.partial_cmp(&other.0).unwrap_or(Ordering::Equal)
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum ScoreBound {
+    Inclusive(f64),
+    Exclusive(f64),
+}
+
+impl ScoreBound {
+    fn is_above(&self, score: f64) -> bool {
+        match self {
+            ScoreBound::Inclusive(min) => score >= *min,
+            ScoreBound::Exclusive(min) => score > *min,
+        }
+    }
+
+    fn is_below(&self, score: f64) -> bool {
+        match self {
+            ScoreBound::Inclusive(max) => score <= *max,
+            ScoreBound::Exclusive(max) => score < *max,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum LexBound {
+    Inclusive(Vec<u8>),
+    Exclusive(Vec<u8>),
+    // `-`, lower than any member
+    Min,
+    // `+`, greater than any member
+    Max,
+}
+
+impl LexBound {
+    fn is_above(&self, member: &[u8]) -> bool {
+        match self {
+            LexBound::Inclusive(min) => member >= &min[..],
+            LexBound::Exclusive(min) => member > &min[..],
+            LexBound::Min => true,
+            LexBound::Max => false,
+        }
+    }
+
+    fn is_below(&self, member: &[u8]) -> bool {
+        match self {
+            LexBound::Inclusive(max) => member <= &max[..],
+            LexBound::Exclusive(max) => member < &max[..],
+            LexBound::Min => false,
+            LexBound::Max => true,
+        }
+    }
+}
+
+/// flags of ZADD
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct AddOptions {
+    // only add new members
+    pub nx: bool,
+    // only update existing members
+    pub xx: bool,
+    // only update a score to a greater one
+    pub gt: bool,
+    // only update a score to a lower one
+    pub lt: bool,
+    // count the updated members along with the added ones
+    pub ch: bool,
+    // increment the score like ZINCRBY
+    pub incr: bool,
+}
+
+impl AddOptions {
+    /// whether a member having the current score, if any, can get the new one
+    pub fn allows(&self, current: Option<f64>, new: f64) -> bool {
+        match current {
+            Some(current) => !self.nx && (!self.gt || new > current) && (!self.lt || new < current),
+            None => !self.xx,
+        }
+    }
+}
+
+/// how the scores of a member found in several sorted sets are combined
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum Aggregate {
+    Sum,
+    Min,
+    Max,
+}
+
+impl Aggregate {
+    fn apply(&self, a: f64, b: f64) -> f64 {
+        match self {
+            Aggregate::Sum => nan_to_zero(a + b),
+            Aggregate::Min => a.min(b),
+            Aggregate::Max => a.max(b),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum RangeBy {
+    // start and stop ranks, negative ones counting from the end
+    Rank(i64, i64),
+    // min and max scores
+    Score(ScoreBound, ScoreBound),
+    // min and max members, assuming all the members have the same score
+    Lex(LexBound, LexBound),
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct RangeQuery {
+    pub by: RangeBy,
+    // walk the sorted set from the highest to the lowest score
+    pub rev: bool,
+    // offset and count, a negative count meaning all the remaining members
+    pub limit: Option<(i64, i64)>,
+}
+
+/// members ordered by score then lexicographically, with a member index
+/// to look up scores
+#[derive(Debug, PartialEq, Clone)]
+pub struct RedisSortedSet {
+    pub scores: HashMap<Vec<u8>, f64>,
+    pub ordered: BTreeSet<(Score, Vec<u8>)>,
+    pub expiry: Option<Expiry>,
+}
+
+impl RedisSortedSet {
+    pub fn new(expiry: Option<Expiry>) -> Self {
+        RedisSortedSet {
+            scores: HashMap::new(),
+            ordered: BTreeSet::new(),
+            expiry,
+        }
+    }
+
+    pub fn from_scores(scores: Scores, expiry: Option<Expiry>) -> Self {
+        let ordered = scores
+            .iter()
+            .map(|(member, score)| (Score(*score), member.clone()))
+            .collect();
+
+        RedisSortedSet {
+            scores,
+            ordered,
+            expiry,
+        }
+    }
+
+    /// bytes the sorted set takes in memory, estimated from the lengths of
+    /// its members, which are held by the index and by the ordered members
+    pub fn memory_usage(&self) -> usize {
+        let scores = self
+            .scores
+            .keys()
+            .map(|member| entry_size::<(Vec<u8>, f64)>() + member.len());
+        let ordered = self
+            .ordered
+            .iter()
+            .map(|(_, member)| entry_size::<(Score, Vec<u8>)>() + member.len());
+        size_of::<Self>() + scores.sum::<usize>() + ordered.sum::<usize>()
+    }
+
+    pub fn is_expired(&self) -> bool {
+        matches!(&self.expiry, Some(expiry) if expiry.timestamp <= Instant::now())
+    }
+
+    pub fn len(&self) -> usize {
+        self.scores.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.scores.is_empty()
+    }
+
+    pub fn score(&self, member: &[u8]) -> Option<f64> {
+        self.scores.get(member).copied()
+    }
+
+    /// add the member or update its score, returns the previous score
+    pub fn insert(&mut self, member: &[u8], score: f64) -> Option<f64> {
+        let previous = self.scores.insert(member.to_vec(), score);
+        if let Some(previous) = previous {
+            self.ordered.remove(&(Score(previous), member.to_vec()));
+        }
+
+        self.ordered.insert((Score(score), member.to_vec()));
+        previous
+    }
+
+    pub fn remove(&mut self, member: &[u8]) -> Option<f64> {
+        let score = self.scores.remove(member)?;
+        self.ordered.remove(&(Score(score), member.to_vec()));
+        Some(score)
+    }
+
+    /// remove and return up to count members with the lowest scores, or the
+    /// highest ones when max is set
+    pub fn pop(&mut self, count: usize, max: bool) -> Vec<(Vec<u8>, f64)> {
+        let members: Vec<(Vec<u8>, f64)> = match max {
+            true => self
+                .iter()
+                .rev()
+                .take(count)
+                .map(|(m, s)| (m.to_vec(), s))
+                .collect(),
+            false => self
+                .iter()
+                .take(count)
+                .map(|(m, s)| (m.to_vec(), s))
+                .collect(),
+        };
+
+        members.iter().for_each(|(member, _)| {
+            self.remove(member);
+        });
+
+        members
+    }
+
+    /// up to count distinct random members when count is positive,
+    /// exactly -count members that may repeat when count is negative
+    pub fn random_members(&self, count: i64) -> Vec<(&[u8], f64)> {
+        let mut rng = rand::thread_rng();
+        let members = self
+            .scores
+            .iter()
+            .map(|(member, score)| (&member[..], *score));
+
+        if count >= 0 {
+            members.choose_multiple(&mut rng, count as usize)
+        } else {
+            (0..count.unsigned_abs())
+                .filter_map(|_| members.clone().choose(&mut rng))
+                .collect()
+        }
+    }
+
+    /// position of the member counted from the lowest score, or from the
+    /// highest one in reverse mode
+    pub fn rank(&self, member: &[u8], rev: bool) -> Option<usize> {
+        let score = self.score(member)?;
+        let rank = self
+            .ordered
+            .range(..(Score(score), member.to_vec()))
+            .count();
+
+        match rev {
+            true => Some(self.len() - 1 - rank),
+            false => Some(rank),
+        }
+    }
+
+    /// number of members within the range
+    pub fn count(&self, by: &RangeBy) -> usize {
+        let query = RangeQuery {
+            by: by.clone(),
+            rev: false,
+            limit: None,
+        };
+
+        self.range(&query).len()
+    }
+
+    /// members and scores in ascending order
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = (&[u8], f64)> {
+        self.ordered
+            .iter()
+            .map(|(score, member)| (&member[..], score.0))
+    }
+
+    /// members and scores matching the query, in the order of the query
+    pub fn range(&self, query: &RangeQuery) -> Vec<(&[u8], f64)> {
+        let mut members = match &query.by {
+            RangeBy::Rank(start, stop) => {
+                let (start, stop) = match normalize_ranks(*start, *stop, self.len()) {
+                    Some(ranks) => ranks,
+                    None => return vec![],
+                };
+
+                let count = stop - start + 1;
+                match query.rev {
+                    // ranks are counted from the highest score in reverse mode
+                    true => self.iter().rev().skip(start).take(count).collect(),
+                    false => self.iter().skip(start).take(count).collect(),
+                }
+            }
+            RangeBy::Score(min, max) => {
+                let lowest = match min {
+                    ScoreBound::Inclusive(min) | ScoreBound::Exclusive(min) => {
+                        (Score(*min), Vec::new())
+                    }
+                };
+
+                let mut members = self
+                    .ordered
+                    .range(lowest..)
+                    .map(|(score, member)| (&member[..], score.0))
+                    .skip_while(|(_, score)| !min.is_above(*score))
+                    .take_while(|(_, score)| max.is_below(*score))
+                    .collect::<Vec<_>>();
+
+                if query.rev {
+                    members.reverse();
+                }
+                members
+            }
+            RangeBy::Lex(min, max) => {
+                let mut members = self
+                    .iter()
+                    .skip_while(|(member, _)| !min.is_above(member))
+                    .take_while(|(member, _)| max.is_below(member))
+                    .collect::<Vec<_>>();
+
+                if query.rev {
+                    members.reverse();
+                }
+                members
+            }
+        };
+
+        match query.limit {
+            Some((offset, _)) if offset < 0 => vec![],
+            Some((offset, count)) => {
+                let offset = (offset as usize).min(members.len());
+                members.drain(..offset);
+                if count >= 0 {
+                    members.truncate(count as usize);
+                }
+                members
+            }
+            None => members,
+        }
+    }
+}
+
+/// members found in at least one sorted set, with their weighted scores
+/// aggregated
+pub fn union(sorted_sets: &[Scores], weights: &[f64], aggregate: Aggregate) -> Scores {
+    let mut union = Scores::new();
+    for (scores, weight) in sorted_sets.iter().zip(weights) {
+        for (member, score) in scores {
+            let score = weighted(*score, *weight);
+            union
+                .entry(member.clone())
+                .and_modify(|aggregated| *aggregated = aggregate.apply(*aggregated, score))
+                .or_insert(score);
+        }
+    }
+
+    union
+}
+
+/// members found in every sorted set, with their weighted scores aggregated
+pub fn intersection(sorted_sets: &[Scores], weights: &[f64], aggregate: Aggregate) -> Scores {
+    let (first, others) = match sorted_sets.split_first() {
+        Some(split) => split,
+        None => return Scores::new(),
+    };
+
+    first
+        .iter()
+        .filter_map(|(member, score)| {
+            let mut aggregated = weighted(*score, weights[0]);
+            for (scores, weight) in others.iter().zip(&weights[1..]) {
+                let score = scores.get(member)?;
+                aggregated = aggregate.apply(aggregated, weighted(*score, *weight));
+            }
+
+            Some((member.clone(), aggregated))
+        })
+        .collect()
+}
+
+/// members of the first sorted set not found in any of the others, with
+/// their scores in the first one
+pub fn difference(sorted_sets: &[Scores]) -> Scores {
+    match sorted_sets.split_first() {
+        Some((first, others)) => first
+            .iter()
+            .filter(|(member, _)| !others.iter().any(|scores| scores.contains_key(*member)))
+            .map(|(member, score)| (member.clone(), *score))
+            .collect(),
+        None => Scores::new(),
+    }
+}
+
+fn weighted(score: f64, weight: f64) -> f64 {
+    nan_to_zero(score * weight)
+}
+
+// like Redis, 0 * inf and inf - inf give 0
+fn nan_to_zero(score: f64) -> f64 {
+    match score.is_nan() {
+        true => 0.0,
+        false => score,
+    }
+}
+
+/// turn start and stop ranks, possibly negative, into positions within
+/// 0..len, None if the range is empty
+pub fn normalize_ranks(start: i64, stop: i64, len: usize) -> Option<(usize, usize)> {
+    let len = len as i64;
+    let start = if start < 0 {
+        (start + len).max(0)
+    } else {
+        start
+    };
+    let stop = if stop < 0 {
+        stop + len
+    } else {
+        stop.min(len - 1)
+    };
+
+    if start > stop || start >= len {
+        return None;
+    }
+
+    Some((start as usize, stop as usize))
+}
+
+/// format a score the way Redis does: the shortest representation that
+/// reads back to the same f64, e.g. `1`, `1.5`, `1e+30`, `inf` or `-inf`
+pub fn format_score(score: f64) -> String {
+    if score.is_infinite() {
+        return match score.is_sign_positive() {
+            true => "inf".to_string(),
+            false => "-inf".to_string(),
+        };
+    }
+
+    // `{:e}` gives the shortest digits, e.g. `-1.5e-7`
+    let scientific = format!("{:e}", score);
+    let (mantissa, exponent) = scientific.split_at(scientific.find('e').unwrap());
+    let exponent = exponent[1..].parse::<i32>().unwrap();
+    let (sign, mantissa) = match mantissa.strip_prefix('-') {
+        Some(mantissa) => ("-", mantissa),
+        None => ("", mantissa),
+    };
+    let digits = mantissa.replace('.', "");
+    let ndigits = digits.len() as i32;
+    // power of ten applied to the digits read as an integer
+    let k = exponent - ndigits + 1;
+
+    if k >= 0 && exponent.abs() < ndigits + 7 {
+        format!("{}{}{}", sign, digits, "0".repeat(k as usize))
+    } else if k < 0 && (k > -7 || exponent.abs() < 4) {
+        score.to_string()
+    } else {
+        let sign_of_exponent = if exponent < 0 { '-' } else { '+' };
+        format!(
+            "{}{}e{}{}",
+            sign,
+            mantissa,
+            sign_of_exponent,
+            exponent.abs()
+        )
+    }
+}
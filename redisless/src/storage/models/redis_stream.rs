@@ -0,0 +1,605 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::{Display, Formatter};
+use std::mem::size_of;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+use super::{entry_size, Expiry};
+
+pub type StreamFields = Vec<(Vec<u8>, Vec<u8>)>;
+
+/// milliseconds and sequence number of an entry, ordered by both
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Copy, Clone, Default)]
+pub struct StreamId {
+    pub ms: u64,
+    pub seq: u64,
+}
+
+impl StreamId {
+    pub const MIN: StreamId = StreamId { ms: 0, seq: 0 };
+    pub const MAX: StreamId = StreamId {
+        ms: u64::MAX,
+        seq: u64::MAX,
+    };
+
+    pub fn new(ms: u64, seq: u64) -> Self {
+        StreamId { ms, seq }
+    }
+
+    /// the smallest id greater than this one
+    pub fn next(&self) -> Option<StreamId> {
+        match (self.seq.checked_add(1), self.ms.checked_add(1)) {
+            (Some(seq), _) => Some(StreamId::new(self.ms, seq)),
+            (None, Some(ms)) => Some(StreamId::new(ms, 0)),
+            (None, None) => None,
+        }
+    }
+
+    /// the greatest id lower than this one
+    pub fn previous(&self) -> Option<StreamId> {
+        match (self.seq.checked_sub(1), self.ms.checked_sub(1)) {
+            (Some(seq), _) => Some(StreamId::new(self.ms, seq)),
+            (None, Some(ms)) => Some(StreamId::new(ms, u64::MAX)),
+            (None, None) => None,
+        }
+    }
+}
+
+impl Display for StreamId {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}-{}", self.ms, self.seq)
+    }
+}
+
+/// id given to XADD
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum XAddId {
+    // `*`, generated from the current time
+    Auto,
+    // `ms-*`, the sequence number being generated
+    AutoSeq(u64),
+    Explicit(StreamId),
+}
+
+/// id given to XREAD, the entries after it being read
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum XReadId {
+    // `$`, the last id of the stream when the command is run
+    Last,
+    After(StreamId),
+}
+
+/// id given to XREADGROUP
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum XReadGroupId {
+    // `>`, the entries never delivered to the group
+    New,
+    // the entries after id pending for the consumer
+    Pending(StreamId),
+}
+
+/// which entries trimming a stream evicts
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum TrimStrategy {
+    // the oldest ones past the given length
+    MaxLen(usize),
+    // the ones with a lower id
+    MinId(StreamId),
+}
+
+/// MAXLEN or MINID of XTRIM and XADD, with the maximum number of entries
+/// evicted at once
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct StreamTrim {
+    pub strategy: TrimStrategy,
+    pub limit: Option<usize>,
+}
+
+/// an entry delivered to a consumer and not acknowledged yet
+#[derive(Debug, PartialEq, Clone)]
+pub struct PendingEntry {
+    pub consumer: Vec<u8>,
+    // unix time in milliseconds of the last delivery
+    pub delivery_time: u64,
+    pub delivery_count: u64,
+}
+
+impl PendingEntry {
+    /// milliseconds elapsed since the last delivery
+    pub fn idle(&self) -> u64 {
+        now_millis().saturating_sub(self.delivery_time)
+    }
+}
+
+/// extended form of XPENDING, the entries pending between start and end
+#[derive(Debug, PartialEq, Clone)]
+pub struct PendingQuery {
+    pub min_idle: Option<u64>,
+    pub start: StreamId,
+    pub end: StreamId,
+    pub count: usize,
+    pub consumer: Option<Vec<u8>>,
+}
+
+/// options of XCLAIM
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct ClaimOptions {
+    // milliseconds elapsed since the delivery, or its unix time
+    pub idle: Option<u64>,
+    pub time: Option<u64>,
+    pub retry_count: Option<u64>,
+    // claim the entries of the stream that are not pending yet
+    pub force: bool,
+    // leave the delivery count as is
+    pub just_id: bool,
+    pub last_id: Option<StreamId>,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Consumer {
+    pub pending: BTreeSet<StreamId>,
+    // unix time in milliseconds of the last read
+    pub seen_time: u64,
+}
+
+impl Consumer {
+    pub fn new() -> Self {
+        Consumer {
+            pending: BTreeSet::new(),
+            seen_time: now_millis(),
+        }
+    }
+}
+
+impl Default for Consumer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// a consumer group with its pending entries list, shared by the consumers
+#[derive(Debug, PartialEq, Clone)]
+pub struct ConsumerGroup {
+    pub last_delivered_id: StreamId,
+    pub pending: BTreeMap<StreamId, PendingEntry>,
+    pub consumers: BTreeMap<Vec<u8>, Consumer>,
+}
+
+impl ConsumerGroup {
+    pub fn new(last_delivered_id: StreamId) -> Self {
+        ConsumerGroup {
+            last_delivered_id,
+            pending: BTreeMap::new(),
+            consumers: BTreeMap::new(),
+        }
+    }
+
+    /// returns true if the consumer has been created
+    pub fn create_consumer(&mut self, consumer: &[u8]) -> bool {
+        match self.consumers.contains_key(consumer) {
+            true => false,
+            false => {
+                self.consumers.insert(consumer.to_vec(), Consumer::new());
+                true
+            }
+        }
+    }
+
+    /// remove the consumer along with its pending entries, returns how
+    /// many entries were pending
+    pub fn delete_consumer(&mut self, consumer: &[u8]) -> Option<usize> {
+        let consumer = self.consumers.remove(consumer)?;
+        consumer.pending.iter().for_each(|id| {
+            self.pending.remove(id);
+        });
+
+        Some(consumer.pending.len())
+    }
+
+    /// hand the entry to the consumer, creating it if needed
+    pub fn deliver(&mut self, id: StreamId, consumer: &[u8]) {
+        let delivery_count = self
+            .pending
+            .get(&id)
+            .map_or(1, |pending| pending.delivery_count + 1);
+
+        self.transfer(id, consumer, now_millis(), delivery_count);
+    }
+
+    /// make the entry pending for the consumer, taking it from the consumer
+    /// it was pending for if any
+    fn transfer(&mut self, id: StreamId, consumer: &[u8], delivery_time: u64, delivery_count: u64) {
+        if let Some(pending) = self.pending.get(&id) {
+            if let Some(previous) = self.consumers.get_mut(&pending.consumer) {
+                previous.pending.remove(&id);
+            }
+        }
+
+        self.pending.insert(
+            id,
+            PendingEntry {
+                consumer: consumer.to_vec(),
+                delivery_time,
+                delivery_count,
+            },
+        );
+
+        let consumer = self.consumers.entry(consumer.to_vec()).or_default();
+        consumer.pending.insert(id);
+        consumer.seen_time = now_millis();
+    }
+
+    /// the entries pending between start and end included matching the query
+    pub fn pending_range(&self, query: &PendingQuery) -> Vec<(StreamId, &PendingEntry)> {
+        if query.start > query.end {
+            return vec![];
+        }
+
+        self.pending
+            .range(query.start..=query.end)
+            .filter(|(_, pending)| match &query.consumer {
+                Some(consumer) => pending.consumer == *consumer,
+                None => true,
+            })
+            .filter(|(_, pending)| pending.idle() >= query.min_idle.unwrap_or(0))
+            .take(query.count)
+            .map(|(id, pending)| (*id, pending))
+            .collect()
+    }
+
+    /// returns true if the entry was pending
+    pub fn acknowledge(&mut self, id: StreamId) -> bool {
+        match self.pending.remove(&id) {
+            Some(pending) => {
+                if let Some(consumer) = self.consumers.get_mut(&pending.consumer) {
+                    consumer.pending.remove(&id);
+                }
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+/// entries ordered by id, along with the greatest id ever added and the
+/// consumer groups
+#[derive(Debug, PartialEq, Clone)]
+pub struct RedisStream {
+    pub entries: BTreeMap<StreamId, StreamFields>,
+    pub last_id: StreamId,
+    // greatest id of the entries deleted or trimmed
+    pub max_deleted_id: StreamId,
+    // number of entries ever added
+    pub entries_added: u64,
+    pub groups: BTreeMap<Vec<u8>, ConsumerGroup>,
+    pub expiry: Option<Expiry>,
+}
+
+impl RedisStream {
+    pub fn new(expiry: Option<Expiry>) -> Self {
+        RedisStream {
+            entries: BTreeMap::new(),
+            last_id: StreamId::MIN,
+            max_deleted_id: StreamId::MIN,
+            entries_added: 0,
+            groups: BTreeMap::new(),
+            expiry,
+        }
+    }
+
+    /// bytes the stream takes in memory, estimated from the lengths of the
+    /// fields of its entries and of its consumer groups
+    pub fn memory_usage(&self) -> usize {
+        let entries = self.entries.values().map(|fields| {
+            let fields = fields
+                .iter()
+                .map(|(field, value)| size_of::<(Vec<u8>, Vec<u8>)>() + field.len() + value.len());
+            entry_size::<(StreamId, StreamFields)>() + fields.sum::<usize>()
+        });
+        let groups = self.groups.iter().map(|(name, group)| {
+            let pending = group
+                .pending
+                .values()
+                .map(|entry| entry_size::<(StreamId, PendingEntry)>() + entry.consumer.len());
+            let consumers = group.consumers.iter().map(|(name, consumer)| {
+                entry_size::<(Vec<u8>, Consumer)>()
+                    + name.len()
+                    + consumer.pending.len() * entry_size::<StreamId>()
+            });
+            entry_size::<(Vec<u8>, ConsumerGroup)>()
+                + name.len()
+                + pending.sum::<usize>()
+                + consumers.sum::<usize>()
+        });
+        size_of::<Self>() + entries.sum::<usize>() + groups.sum::<usize>()
+    }
+
+    pub fn is_expired(&self) -> bool {
+        matches!(&self.expiry, Some(expiry) if expiry.timestamp <= Instant::now())
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// the id the entry would get, None if it is not greater than the last one
+    pub fn next_id(&self, id: XAddId) -> Option<StreamId> {
+        let id = match id {
+            XAddId::Auto => {
+                let now = now_millis();
+                match now > self.last_id.ms {
+                    true => StreamId::new(now, 0),
+                    false => self.last_id.next()?,
+                }
+            }
+            XAddId::AutoSeq(ms) if ms == self.last_id.ms => {
+                StreamId::new(ms, self.last_id.seq.checked_add(1)?)
+            }
+            XAddId::AutoSeq(ms) => StreamId::new(ms, 0),
+            XAddId::Explicit(id) => id,
+        };
+
+        match id > self.last_id {
+            true => Some(id),
+            false => None,
+        }
+    }
+
+    /// add the entry, returns its id or None if it would not be the last one
+    pub fn add(&mut self, id: XAddId, fields: StreamFields) -> Option<StreamId> {
+        let id = self.next_id(id)?;
+        self.entries.insert(id, fields);
+        self.last_id = id;
+        self.entries_added += 1;
+        Some(id)
+    }
+
+    /// remove the entries, returns how many existed. They stay pending in
+    /// the groups they were delivered to.
+    pub fn delete(&mut self, ids: &[StreamId]) -> usize {
+        let mut deleted = 0;
+        for id in ids {
+            if self.entries.remove(id).is_some() {
+                self.max_deleted_id = self.max_deleted_id.max(*id);
+                deleted += 1;
+            }
+        }
+
+        deleted
+    }
+
+    /// evict the oldest entries, returns how many were evicted
+    pub fn trim(&mut self, trim: &StreamTrim) -> usize {
+        let evicted = match trim.strategy {
+            TrimStrategy::MaxLen(len) => self.len().saturating_sub(len),
+            TrimStrategy::MinId(id) => self.entries.range(..id).count(),
+        };
+        let evicted = evicted.min(trim.limit.unwrap_or(usize::MAX));
+
+        let ids: Vec<StreamId> = self.entries.keys().take(evicted).copied().collect();
+        self.delete(&ids)
+    }
+
+    /// number of entries the group has not been delivered yet
+    pub fn lag(&self, group: &ConsumerGroup) -> usize {
+        match group.last_delivered_id.next() {
+            Some(start) => self.entries.range(start..).count(),
+            None => 0,
+        }
+    }
+
+    /// up to count entries with an id greater than id
+    pub fn after(&self, id: StreamId, count: Option<usize>) -> Vec<(StreamId, &StreamFields)> {
+        match id.next() {
+            Some(start) => self.range(start, StreamId::MAX, count, false),
+            None => vec![],
+        }
+    }
+
+    /// read entries on behalf of a consumer of a group, either the new ones
+    /// that get delivered or the ones already pending for the consumer, the
+    /// latter having no fields if they have been deleted since. None if the
+    /// group does not exist.
+    pub fn read_group(
+        &mut self,
+        group: &[u8],
+        consumer: &[u8],
+        id: XReadGroupId,
+        count: Option<usize>,
+        no_ack: bool,
+    ) -> Option<Vec<(StreamId, Option<StreamFields>)>> {
+        let stream_entries = &self.entries;
+        let group = self.groups.get_mut(group)?;
+        let count = count.unwrap_or(usize::MAX);
+
+        match id {
+            XReadGroupId::New => {
+                let start = match group.last_delivered_id.next() {
+                    Some(start) => start,
+                    None => return Some(vec![]),
+                };
+
+                let entries: Vec<(StreamId, Option<StreamFields>)> = stream_entries
+                    .range(start..)
+                    .take(count)
+                    .map(|(id, fields)| (*id, Some(fields.clone())))
+                    .collect();
+
+                group
+                    .consumers
+                    .entry(consumer.to_vec())
+                    .or_default()
+                    .seen_time = now_millis();
+                for (id, _) in &entries {
+                    group.last_delivered_id = *id;
+                    if !no_ack {
+                        group.deliver(*id, consumer);
+                    }
+                }
+
+                Some(entries)
+            }
+            XReadGroupId::Pending(after) => {
+                let consumer = group.consumers.entry(consumer.to_vec()).or_default();
+                consumer.seen_time = now_millis();
+
+                let entries = match after.next() {
+                    Some(start) => consumer
+                        .pending
+                        .range(start..)
+                        .take(count)
+                        .map(|id| (*id, stream_entries.get(id).cloned()))
+                        .collect(),
+                    None => vec![],
+                };
+
+                Some(entries)
+            }
+        }
+    }
+
+    /// give the consumer the entries idle for at least min_idle milliseconds,
+    /// returns the ids claimed. The entries deleted from the stream are no
+    /// longer pending. None if the group does not exist.
+    pub fn claim(
+        &mut self,
+        group: &[u8],
+        consumer: &[u8],
+        min_idle: u64,
+        ids: &[StreamId],
+        options: &ClaimOptions,
+    ) -> Option<Vec<StreamId>> {
+        let stream_entries = &self.entries;
+        let group = self.groups.get_mut(group)?;
+        let now = now_millis();
+
+        group
+            .consumers
+            .entry(consumer.to_vec())
+            .or_default()
+            .seen_time = now;
+
+        if let Some(last_id) = options.last_id {
+            group.last_delivered_id = group.last_delivered_id.max(last_id);
+        }
+
+        let delivery_time = match (options.time, options.idle) {
+            (Some(time), _) => time,
+            (None, Some(idle)) => now.saturating_sub(idle),
+            (None, None) => now,
+        };
+
+        let mut claimed = vec![];
+        for id in ids {
+            if !stream_entries.contains_key(id) {
+                group.acknowledge(*id);
+                continue;
+            }
+
+            let delivery_count = match group.pending.get(id) {
+                Some(pending) if pending.idle() < min_idle => continue,
+                Some(pending) => pending.delivery_count,
+                None if options.force => 0,
+                None => continue,
+            };
+            let delivery_count = match (options.retry_count, options.just_id) {
+                (Some(retry_count), _) => retry_count,
+                (None, true) => delivery_count,
+                (None, false) => delivery_count + 1,
+            };
+
+            group.transfer(*id, consumer, delivery_time, delivery_count);
+            claimed.push(*id);
+        }
+
+        Some(claimed)
+    }
+
+    /// claim up to count pending entries from start that have been idle for
+    /// at least min_idle milliseconds, returns the id to continue from, 0-0
+    /// once every entry has been scanned, the ids claimed and the ids of the
+    /// entries deleted from the stream, which are no longer pending. None if
+    /// the group does not exist.
+    pub fn auto_claim(
+        &mut self,
+        group: &[u8],
+        consumer: &[u8],
+        min_idle: u64,
+        start: StreamId,
+        count: usize,
+        just_id: bool,
+    ) -> Option<(StreamId, Vec<StreamId>, Vec<StreamId>)> {
+        let stream_entries = &self.entries;
+        let group = self.groups.get_mut(group)?;
+        let now = now_millis();
+
+        group
+            .consumers
+            .entry(consumer.to_vec())
+            .or_default()
+            .seen_time = now;
+
+        // the deleted entries count as claimed ones
+        let mut candidates = vec![];
+        let mut next = StreamId::MIN;
+        for (id, pending) in group.pending.range(start..) {
+            if candidates.len() == count {
+                next = *id;
+                break;
+            }
+            if !stream_entries.contains_key(id) || pending.idle() >= min_idle {
+                candidates.push((*id, pending.delivery_count));
+            }
+        }
+
+        let mut claimed = vec![];
+        let mut deleted = vec![];
+        for (id, delivery_count) in candidates {
+            if !stream_entries.contains_key(&id) {
+                group.acknowledge(id);
+                deleted.push(id);
+                continue;
+            }
+
+            let delivery_count = match just_id {
+                true => delivery_count,
+                false => delivery_count + 1,
+            };
+            group.transfer(id, consumer, now, delivery_count);
+            claimed.push(id);
+        }
+
+        Some((next, claimed, deleted))
+    }
+
+    /// up to count entries between start and end included, from the end
+    /// when reversed
+    pub fn range(
+        &self,
+        start: StreamId,
+        end: StreamId,
+        count: Option<usize>,
+        rev: bool,
+    ) -> Vec<(StreamId, &StreamFields)> {
+        if start > end {
+            return vec![];
+        }
+
+        let entries = self.entries.range(start..=end).map(|(id, f)| (*id, f));
+        let count = count.unwrap_or(usize::MAX);
+        match rev {
+            true => entries.rev().take(count).collect(),
+            false => entries.take(count).collect(),
+        }
+    }
+}
+
+/// unix time in milliseconds
+pub fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_millis() as u64)
+}
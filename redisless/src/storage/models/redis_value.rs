@@ -1,7 +1,40 @@
 use super::Expiry;
+use std::mem::size_of;
 use std::time::Instant;
 
-#[derive(Debug, PartialEq)]
+/// unit of the ranges given to BITCOUNT and BITPOS
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum BitUnit {
+    Byte,
+    Bit,
+}
+
+/// operation of BITOP
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum BitOperation {
+    And,
+    Or,
+    Xor,
+    Not,
+}
+
+/// what LCS replies with
+#[derive(Debug, PartialEq, Copy, Clone, Default)]
+pub struct LcsOptions {
+    // the length of the subsequence rather than the subsequence
+    pub len: bool,
+    // the ranges matching in both values
+    pub idx: bool,
+    // the ranges shorter than this being left out
+    pub min_match_len: usize,
+    pub with_match_len: bool,
+}
+
+/// ranges of both values, start and end included, that are part of a
+/// longest common subsequence
+pub type LcsMatch = ((usize, usize), (usize, usize));
+
+#[derive(Debug, PartialEq, Clone)]
 pub struct RedisValue {
     pub data: Vec<u8>,
     pub expiry: Option<Expiry>,
@@ -12,10 +45,183 @@ impl RedisValue {
         RedisValue { data, expiry }
     }
 
+    /// bytes the value takes in memory, estimated from its length
+    pub fn memory_usage(&self) -> usize {
+        size_of::<Self>() + self.data.len()
+    }
+
     pub fn is_expired(&self) -> bool {
         match &self.expiry {
             Some(expiry) if expiry.timestamp <= Instant::now() => true,
             _ => false,
         }
     }
+
+    /// set the bit at offset, zero-extending the value if needed,
+    /// returns the bit previously stored there
+    pub fn set_bit(&mut self, offset: usize, bit: bool) -> u8 {
+        let index = offset / 8;
+        if self.data.len() <= index {
+            self.data.resize(index + 1, 0);
+        }
+
+        let previous = get_bit(&self.data, offset);
+        let mask = 1 << (7 - offset % 8);
+        match bit {
+            true => self.data[index] |= mask,
+            false => self.data[index] &= !mask,
+        }
+
+        previous
+    }
+}
+
+/// bit at offset, the most significant bit of the first byte being at
+/// offset 0 and the bits past the end being 0
+pub fn get_bit(data: &[u8], offset: usize) -> u8 {
+    match data.get(offset / 8) {
+        Some(byte) => (byte >> (7 - offset % 8)) & 1,
+        None => 0,
+    }
+}
+
+/// number of bits set within the range, which is inclusive and counted in
+/// bytes or bits, negative bounds counting from the end
+pub fn bit_count(data: &[u8], range: Option<(i64, i64)>, unit: BitUnit) -> usize {
+    let (start, end) = match range {
+        Some((start, end)) => (start, end),
+        None => return data.iter().map(|byte| byte.count_ones() as usize).sum(),
+    };
+
+    match (unit, normalize_bit_range(start, end, data.len(), unit)) {
+        (BitUnit::Byte, Some((start, end))) => data[start..=end]
+            .iter()
+            .map(|byte| byte.count_ones() as usize)
+            .sum(),
+        (BitUnit::Bit, Some((start, end))) => (start..=end)
+            .filter(|offset| get_bit(data, *offset) == 1)
+            .count(),
+        (_, None) => 0,
+    }
+}
+
+/// offset of the first bit equal to bit within the range, -1 if none. A
+/// value is considered padded with zeros on the right when looking for a 0
+/// with no explicit end.
+pub fn bit_position(
+    data: &[u8],
+    bit: bool,
+    start: Option<i64>,
+    end: Option<i64>,
+    unit: BitUnit,
+) -> i64 {
+    let len = match unit {
+        BitUnit::Byte => data.len() as i64,
+        BitUnit::Bit => data.len() as i64 * 8,
+    };
+
+    let (first, last) =
+        match normalize_bit_range(start.unwrap_or(0), end.unwrap_or(len - 1), data.len(), unit) {
+            Some((first, last)) => match unit {
+                BitUnit::Byte => (first * 8, last * 8 + 7),
+                BitUnit::Bit => (first, last),
+            },
+            None => return -1,
+        };
+
+    let wanted = bit as u8;
+    match (first..=last).find(|offset| get_bit(data, *offset) == wanted) {
+        Some(offset) => offset as i64,
+        None if !bit && end.is_none() => last as i64 + 1,
+        None => -1,
+    }
+}
+
+/// combine the values byte by byte, the shorter ones being padded with
+/// zeros up to the length of the longest one
+pub fn bit_operation(operation: BitOperation, values: &[&[u8]]) -> Vec<u8> {
+    let len = values.iter().map(|value| value.len()).max().unwrap_or(0);
+    let byte_at = |value: &[u8], i: usize| value.get(i).copied().unwrap_or(0);
+
+    (0..len)
+        .map(|i| {
+            let mut bytes = values.iter().map(|value| byte_at(value, i));
+            let first = bytes.next().unwrap_or(0);
+            match operation {
+                BitOperation::And => bytes.fold(first, |result, byte| result & byte),
+                BitOperation::Or => bytes.fold(first, |result, byte| result | byte),
+                BitOperation::Xor => bytes.fold(first, |result, byte| result ^ byte),
+                BitOperation::Not => !first,
+            }
+        })
+        .collect()
+}
+
+/// a longest common subsequence of both values, along with the ranges it is
+/// made of from the last one to the first one, as Redis replies with them
+pub fn lcs(a: &[u8], b: &[u8]) -> (Vec<u8>, Vec<LcsMatch>) {
+    // lengths[i][j] is the length of the subsequence of a[..i] and b[..j]
+    let mut lengths = vec![vec![0u32; b.len() + 1]; a.len() + 1];
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            lengths[i][j] = match a[i - 1] == b[j - 1] {
+                true => lengths[i - 1][j - 1] + 1,
+                false => lengths[i - 1][j].max(lengths[i][j - 1]),
+            };
+        }
+    }
+
+    let mut subsequence = vec![];
+    let mut matches: Vec<LcsMatch> = vec![];
+    let (mut i, mut j) = (a.len(), b.len());
+    while i > 0 && j > 0 {
+        if a[i - 1] == b[j - 1] {
+            subsequence.push(a[i - 1]);
+
+            // extend the current range when both bytes are right before it
+            match matches.last_mut() {
+                Some(((a_start, _), (b_start, _))) if *a_start == i && *b_start == j => {
+                    *a_start -= 1;
+                    *b_start -= 1;
+                }
+                _ => matches.push(((i - 1, i - 1), (j - 1, j - 1))),
+            }
+
+            i -= 1;
+            j -= 1;
+        } else if lengths[i - 1][j] > lengths[i][j - 1] {
+            i -= 1;
+        } else {
+            j -= 1;
+        }
+    }
+
+    subsequence.reverse();
+    (subsequence, matches)
+}
+
+/// turn start and end, possibly negative, into offsets within a value of
+/// len bytes the way Redis does, None if the range is empty
+fn normalize_bit_range(start: i64, end: i64, len: usize, unit: BitUnit) -> Option<(usize, usize)> {
+    let len = match unit {
+        BitUnit::Byte => len as i64,
+        BitUnit::Bit => len as i64 * 8,
+    };
+
+    let start = if start < 0 {
+        (start + len).max(0)
+    } else {
+        start
+    };
+    let end = if end < 0 {
+        (end + len).max(0)
+    } else {
+        end.min(len - 1)
+    };
+
+    if start > end || len == 0 {
+        return None;
+    }
+
+    Some((start as usize, end as usize))
 }
@@ -0,0 +1,144 @@
+use std::cmp::Ordering;
+
+/// options of SORT and SORT_RO
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct SortQuery {
+    // pattern of the keys holding the weights, the elements being their own
+    // weights when None
+    pub by: Option<Vec<u8>>,
+    // offset and count
+    pub limit: Option<(i64, i64)>,
+    // patterns of the keys holding the values replied, `#` being the element
+    pub get: Vec<Vec<u8>>,
+    pub desc: bool,
+    pub alpha: bool,
+}
+
+impl SortQuery {
+    /// sort the elements, lookup reading the string at a key or the field of
+    /// the hash at a key. None if a weight is not a number while sorting
+    /// numerically.
+    pub fn sort(
+        &self,
+        elements: Vec<Vec<u8>>,
+        mut lookup: impl FnMut(&[u8], Option<&[u8]>) -> Option<Vec<u8>>,
+    ) -> Option<Vec<Vec<u8>>> {
+        let mut elements = match &self.by {
+            // a BY pattern with no `*` keeps the elements as they are
+            Some(pattern) if !pattern.contains(&b'*') => elements,
+            by => {
+                let mut weighted = Vec::with_capacity(elements.len());
+                for element in elements {
+                    let weight = match by {
+                        Some(pattern) => substitute(pattern, &element)
+                            .and_then(|(key, field)| lookup(&key, field.as_deref())),
+                        None => Some(element.clone()),
+                    };
+
+                    let weight = match self.alpha {
+                        true => Weight::Bytes(weight),
+                        // a missing weight counts as 0
+                        false => Weight::Number(match weight {
+                            Some(weight) => std::str::from_utf8(&weight)
+                                .ok()?
+                                .trim()
+                                .parse::<f64>()
+                                .ok()
+                                .filter(|weight| !weight.is_nan())?,
+                            None => 0.0,
+                        }),
+                    };
+                    weighted.push((element, weight));
+                }
+
+                // elements of equal weights are compared to each other
+                weighted.sort_by(|(a, a_weight), (b, b_weight)| {
+                    let ordering = a_weight.compare(b_weight).then_with(|| a.cmp(b));
+                    match self.desc {
+                        true => ordering.reverse(),
+                        false => ordering,
+                    }
+                });
+
+                weighted.into_iter().map(|(element, _)| element).collect()
+            }
+        };
+
+        if let Some((offset, count)) = self.limit {
+            let offset = (offset.max(0) as usize).min(elements.len());
+            let count = match count {
+                count if count < 0 => elements.len(),
+                count => count as usize,
+            };
+            elements = elements.into_iter().skip(offset).take(count).collect();
+        }
+
+        Some(elements)
+    }
+
+    /// the values of the GET patterns for each element, or the elements
+    /// themselves when there are none
+    pub fn get_values(
+        &self,
+        elements: Vec<Vec<u8>>,
+        mut lookup: impl FnMut(&[u8], Option<&[u8]>) -> Option<Vec<u8>>,
+    ) -> Vec<Option<Vec<u8>>> {
+        if self.get.is_empty() {
+            return elements.into_iter().map(Some).collect();
+        }
+
+        let mut values = Vec::with_capacity(elements.len() * self.get.len());
+        for element in &elements {
+            for pattern in &self.get {
+                let value = match pattern.as_slice() {
+                    b"#" => Some(element.clone()),
+                    pattern => substitute(pattern, element)
+                        .and_then(|(key, field)| lookup(&key, field.as_deref())),
+                };
+                values.push(value);
+            }
+        }
+
+        values
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum Weight {
+    Number(f64),
+    // a missing weight sorts first
+    Bytes(Option<Vec<u8>>),
+}
+
+impl Weight {
+    fn compare(&self, other: &Weight) -> Ordering {
+        match (self, other) {
+            (Weight::Number(a), Weight::Number(b)) => a.partial_cmp(b).unwrap_or(Ordering::Equal),
+            (Weight::Bytes(a), Weight::Bytes(b)) => a.cmp(b),
+            _ => Ordering::Equal,
+        }
+    }
+}
+
+/// the key a pattern stands for once its first `*` is replaced by the
+/// element, along with the hash field following `->` if any. None if the
+/// pattern has no `*`.
+pub fn substitute(pattern: &[u8], element: &[u8]) -> Option<(Vec<u8>, Option<Vec<u8>>)> {
+    let star = pattern.iter().position(|byte| *byte == b'*')?;
+
+    let arrow = pattern[star + 1..]
+        .windows(2)
+        .position(|window| window == b"->")
+        .map(|arrow| star + 1 + arrow)
+        .filter(|arrow| arrow + 2 < pattern.len());
+    let (key_pattern, field) = match arrow {
+        Some(arrow) => (&pattern[..arrow], Some(pattern[arrow + 2..].to_vec())),
+        None => (pattern, None),
+    };
+
+    let mut key = key_pattern[..star].to_vec();
+    key.extend_from_slice(element);
+    key.extend_from_slice(&key_pattern[star + 1..]);
+
+    Some((key, field))
+}
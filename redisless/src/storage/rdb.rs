@@ -0,0 +1,1071 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::convert::TryInto;
+use std::io::{self, ErrorKind, Read, Write};
+
+use super::models::redis_stream::{Consumer, ConsumerGroup, PendingEntry, StreamFields, StreamId};
+use super::models::{
+    Expiry, RedisHashMap, RedisSet, RedisSortedSet, RedisStream, RedisValue, StoredValue,
+};
+use super::snapshot::Databases;
+
+// what a dump of Redis starts with, followed by the version of its format
+// in four digits
+pub const MAGIC: &[u8] = b"REDIS";
+// the newest version of the format that is read
+const MAX_VERSION: u32 = 12;
+// the versions written, that of Redis 7.0 unless fields of hashes expire,
+// which came with Redis 7.4
+const VERSION: u32 = 10;
+const VERSION_FIELD_EXPIRY: u32 = 12;
+// the first version ending with a checksum
+const VERSION_CHECKSUM: u32 = 5;
+
+const OPCODE_SLOT_INFO: u8 = 244;
+const OPCODE_FUNCTION2: u8 = 245;
+const OPCODE_IDLE: u8 = 248;
+const OPCODE_FREQ: u8 = 249;
+const OPCODE_AUX: u8 = 250;
+const OPCODE_RESIZEDB: u8 = 251;
+const OPCODE_EXPIRETIME_MS: u8 = 252;
+const OPCODE_EXPIRETIME: u8 = 253;
+const OPCODE_SELECTDB: u8 = 254;
+const OPCODE_EOF: u8 = 255;
+
+const TYPE_STRING: u8 = 0;
+const TYPE_LIST: u8 = 1;
+const TYPE_SET: u8 = 2;
+const TYPE_ZSET: u8 = 3;
+const TYPE_HASH: u8 = 4;
+const TYPE_ZSET_2: u8 = 5;
+const TYPE_HASH_ZIPMAP: u8 = 9;
+const TYPE_LIST_ZIPLIST: u8 = 10;
+const TYPE_SET_INTSET: u8 = 11;
+const TYPE_ZSET_ZIPLIST: u8 = 12;
+const TYPE_HASH_ZIPLIST: u8 = 13;
+const TYPE_LIST_QUICKLIST: u8 = 14;
+const TYPE_STREAM_LISTPACKS: u8 = 15;
+const TYPE_HASH_LISTPACK: u8 = 16;
+const TYPE_ZSET_LISTPACK: u8 = 17;
+const TYPE_LIST_QUICKLIST_2: u8 = 18;
+const TYPE_STREAM_LISTPACKS_2: u8 = 19;
+const TYPE_SET_LISTPACK: u8 = 20;
+const TYPE_STREAM_LISTPACKS_3: u8 = 21;
+const TYPE_HASH_METADATA: u8 = 24;
+const TYPE_HASH_LISTPACK_EX: u8 = 25;
+
+// how an entry of a stream differs from the master entry of its node
+const STREAM_ITEM_FLAG_DELETED: i64 = 1;
+const STREAM_ITEM_FLAG_SAMEFIELDS: i64 = 2;
+// the number of entries a consumer group read, when it is not known
+const STREAM_ENTRIES_READ_UNKNOWN: u64 = u64::MAX;
+
+// the polynomial of the checksum ending a dump, CRC-64-Jones, reflected
+const CRC64_POLY: u64 = 0x95ac_9329_ac4b_c9b5;
+const CRC64_TABLE: [u64; 256] = crc64_table();
+
+// the lengths whose first two bits are set give how a string is encoded
+// rather than its length
+const ENCODED_INT8: u8 = 0;
+const ENCODED_INT16: u8 = 1;
+const ENCODED_INT32: u8 = 2;
+const ENCODED_LZF: u8 = 3;
+
+/// write the keys of every database as Redis does, so that the dump can be
+/// loaded by Redis 7 or later
+pub fn write<W: Write>(
+    writer: &mut W,
+    databases: &[Vec<(Vec<u8>, StoredValue)>],
+) -> io::Result<()> {
+    let field_expiry = databases.iter().flatten().any(|(_, value)| match value {
+        StoredValue::Hash(hash) => !hash.field_expiry.is_empty(),
+        _ => false,
+    });
+    let version = match field_expiry {
+        true => VERSION_FIELD_EXPIRY,
+        false => VERSION,
+    };
+    let mut writer = Checksummed::new(writer);
+    write!(writer, "REDIS{:04}", version)?;
+
+    for (db, keys) in databases.iter().enumerate() {
+        if keys.is_empty() {
+            continue;
+        }
+
+        let expires = keys
+            .iter()
+            .filter(|(_, value)| value.expiry().is_some())
+            .count();
+        writer.write_all(&[OPCODE_SELECTDB])?;
+        write_length(&mut writer, db as u64)?;
+        writer.write_all(&[OPCODE_RESIZEDB])?;
+        write_length(&mut writer, keys.len() as u64)?;
+        write_length(&mut writer, expires as u64)?;
+        for (key, value) in keys {
+            write_entry(&mut writer, key, value)?;
+        }
+    }
+
+    writer.write_all(&[OPCODE_EOF])?;
+    let checksum = writer.checksum;
+    writer.inner.write_all(&checksum.to_le_bytes())
+}
+
+/// read the keys of every database of a dump written by Redis, or by
+/// `write`, the ones that expired being left out, as are the lists
+/// RedisLess can't hold
+pub fn read<R: Read>(reader: &mut R) -> io::Result<Databases> {
+    let mut reader = Checksummed::new(reader);
+    let reader = &mut reader;
+    let mut header = [0; 9];
+    reader.read_exact(&mut header)?;
+    if &header[..MAGIC.len()] != MAGIC {
+        return Err(invalid("not a Redis dump"));
+    }
+    let version = std::str::from_utf8(&header[MAGIC.len()..])
+        .ok()
+        .and_then(|version| version.parse::<u32>().ok())
+        .ok_or_else(|| invalid("invalid version"))?;
+    if version > MAX_VERSION {
+        return Err(invalid(&format!("version {} is not supported", version)));
+    }
+
+    let mut databases: Databases = vec![vec![]];
+    let mut db = 0;
+    let mut expiry = None;
+    loop {
+        match read_u8(reader)? {
+            OPCODE_EOF => {
+                if version < VERSION_CHECKSUM {
+                    return Ok(databases);
+                }
+                let computed = reader.checksum;
+                let mut checksum = [0; 8];
+                reader.inner.read_exact(&mut checksum)?;
+                // a dump saved without its checksum ends with zeros
+                return match u64::from_le_bytes(checksum) {
+                    0 => Ok(databases),
+                    checksum if checksum == computed => Ok(databases),
+                    _ => Err(invalid("wrong checksum")),
+                };
+            }
+            OPCODE_SELECTDB => {
+                db = read_length(reader)? as usize;
+                if databases.len() <= db {
+                    databases.resize_with(db + 1, Vec::new);
+                }
+            }
+            OPCODE_RESIZEDB => {
+                read_length(reader)?;
+                read_length(reader)?;
+            }
+            OPCODE_AUX => {
+                read_string(reader)?;
+                read_string(reader)?;
+            }
+            OPCODE_SLOT_INFO => {
+                for _ in 0..3 {
+                    read_length(reader)?;
+                }
+            }
+            OPCODE_FUNCTION2 => {
+                read_string(reader)?;
+            }
+            OPCODE_IDLE => {
+                read_length(reader)?;
+            }
+            OPCODE_FREQ => {
+                read_u8(reader)?;
+            }
+            OPCODE_EXPIRETIME_MS => {
+                expiry = Some(read_millis(reader)?);
+            }
+            OPCODE_EXPIRETIME => {
+                let mut time = [0; 4];
+                reader.read_exact(&mut time)?;
+                expiry = Some(u32::from_le_bytes(time) as u64 * 1000);
+            }
+            data_type => {
+                let key = read_string(reader)?;
+                let expiry = match expiry.take() {
+                    Some(time) => Some(
+                        Expiry::new_from_unix_millis(time)
+                            .map_err(|_| invalid("expiry out of range"))?,
+                    ),
+                    None => None,
+                };
+                let value = read_value(reader, data_type, expiry)?;
+                match (value, expiry) {
+                    (_, Some(expiry)) if expiry.is_expired() => {}
+                    (Some(value), _) => databases[db].push((key, value)),
+                    (None, _) => {}
+                }
+            }
+        }
+    }
+}
+
+fn invalid(reason: &str) -> io::Error {
+    io::Error::new(ErrorKind::InvalidData, format!("invalid dump: {}", reason))
+}
+
+/// a reader or a writer keeping the checksum of the bytes gone through it
+struct Checksummed<T> {
+    inner: T,
+    checksum: u64,
+}
+
+impl<T> Checksummed<T> {
+    fn new(inner: T) -> Self {
+        Checksummed { inner, checksum: 0 }
+    }
+}
+
+impl<T: Read> Read for Checksummed<T> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        self.checksum = crc64(self.checksum, &buf[..read]);
+        Ok(read)
+    }
+}
+
+impl<T: Write> Write for Checksummed<T> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.checksum = crc64(self.checksum, &buf[..written]);
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+const fn crc64_table() -> [u64; 256] {
+    let mut table = [0; 256];
+    let mut byte = 0;
+    while byte < 256 {
+        let mut crc = byte as u64;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = match crc & 1 {
+                1 => (crc >> 1) ^ CRC64_POLY,
+                _ => crc >> 1,
+            };
+            bit += 1;
+        }
+        table[byte] = crc;
+        byte += 1;
+    }
+    table
+}
+
+fn crc64(crc: u64, bytes: &[u8]) -> u64 {
+    bytes.iter().fold(crc, |crc, byte| {
+        CRC64_TABLE[((crc ^ *byte as u64) & 0xff) as usize] ^ (crc >> 8)
+    })
+}
+
+fn write_entry<W: Write>(writer: &mut W, key: &[u8], value: &StoredValue) -> io::Result<()> {
+    if let Some(expiry) = value.expiry() {
+        writer.write_all(&[OPCODE_EXPIRETIME_MS])?;
+        writer.write_all(&expiry.unix_millis().max(1).to_le_bytes())?;
+    }
+    let data_type = match value {
+        StoredValue::String(_) => TYPE_STRING,
+        StoredValue::Set(_) => TYPE_SET,
+        StoredValue::SortedSet(_) => TYPE_ZSET_2,
+        StoredValue::Hash(hash) if !hash.field_expiry.is_empty() => TYPE_HASH_METADATA,
+        StoredValue::Hash(_) => TYPE_HASH,
+        StoredValue::Stream(_) => TYPE_STREAM_LISTPACKS_2,
+    };
+    writer.write_all(&[data_type])?;
+    write_string(writer, key)?;
+
+    match value {
+        StoredValue::String(value) => write_string(writer, &value.data),
+        StoredValue::Set(set) => {
+            write_length(writer, set.data.len() as u64)?;
+            for member in &set.data {
+                write_string(writer, member)?;
+            }
+            Ok(())
+        }
+        StoredValue::SortedSet(sorted_set) => {
+            write_length(writer, sorted_set.scores.len() as u64)?;
+            for (member, score) in &sorted_set.scores {
+                write_string(writer, member)?;
+                writer.write_all(&score.to_le_bytes())?;
+            }
+            Ok(())
+        }
+        StoredValue::Hash(hash) => {
+            // the ttls of the fields follow the earliest one, the fields
+            // that don't expire having none
+            let field_expiry = hash
+                .field_expiry
+                .iter()
+                .map(|(field, expiry)| (field, expiry.unix_millis().max(1)))
+                .collect::<HashMap<_, _>>();
+            let earliest = field_expiry.values().min().copied();
+            if let Some(earliest) = earliest {
+                writer.write_all(&earliest.to_le_bytes())?;
+            }
+            write_length(writer, hash.data.len() as u64)?;
+            for (field, value) in &hash.data {
+                if let Some(earliest) = earliest {
+                    let ttl = field_expiry
+                        .get(field)
+                        .map_or(0, |time| time - earliest + 1);
+                    write_length(writer, ttl)?;
+                }
+                write_string(writer, field)?;
+                write_string(writer, value)?;
+            }
+            Ok(())
+        }
+        StoredValue::Stream(stream) => write_stream(writer, stream),
+    }
+}
+
+/// write a stream as nodes of one entry each, the fields of the entry being
+/// those of the master entry of its node
+fn write_stream<W: Write>(writer: &mut W, stream: &RedisStream) -> io::Result<()> {
+    write_length(writer, stream.entries.len() as u64)?;
+    for (id, fields) in &stream.entries {
+        write_string(writer, &raw_stream_id(*id))?;
+        let mut node = vec![b"1".to_vec(), b"0".to_vec()];
+        node.push(fields.len().to_string().into_bytes());
+        node.extend(fields.iter().map(|(field, _)| field.clone()));
+        node.push(b"0".to_vec());
+        node.push(STREAM_ITEM_FLAG_SAMEFIELDS.to_string().into_bytes());
+        node.extend(vec![b"0".to_vec(); 2]);
+        node.extend(fields.iter().map(|(_, value)| value.clone()));
+        node.push((fields.len() + 3).to_string().into_bytes());
+        write_string(writer, &to_listpack(&node))?;
+    }
+
+    let first_id = stream.entries.keys().next().copied();
+    for length in &[
+        stream.entries.len() as u64,
+        stream.last_id.ms,
+        stream.last_id.seq,
+        first_id.map_or(0, |id| id.ms),
+        first_id.map_or(0, |id| id.seq),
+        stream.max_deleted_id.ms,
+        stream.max_deleted_id.seq,
+        stream.entries_added,
+    ] {
+        write_length(writer, *length)?;
+    }
+
+    write_length(writer, stream.groups.len() as u64)?;
+    for (name, group) in &stream.groups {
+        write_string(writer, name)?;
+        write_length(writer, group.last_delivered_id.ms)?;
+        write_length(writer, group.last_delivered_id.seq)?;
+        write_length(writer, STREAM_ENTRIES_READ_UNKNOWN)?;
+        write_length(writer, group.pending.len() as u64)?;
+        for (id, entry) in &group.pending {
+            writer.write_all(&raw_stream_id(*id))?;
+            writer.write_all(&entry.delivery_time.to_le_bytes())?;
+            write_length(writer, entry.delivery_count)?;
+        }
+        write_length(writer, group.consumers.len() as u64)?;
+        for (name, consumer) in &group.consumers {
+            write_string(writer, name)?;
+            writer.write_all(&consumer.seen_time.to_le_bytes())?;
+            write_length(writer, consumer.pending.len() as u64)?;
+            for id in &consumer.pending {
+                writer.write_all(&raw_stream_id(*id))?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn write_length<W: Write>(writer: &mut W, length: u64) -> io::Result<()> {
+    match length {
+        0..=0x3f => writer.write_all(&[length as u8]),
+        0x40..=0x3fff => writer.write_all(&(0x4000 | length as u16).to_be_bytes()),
+        0x4000..=0xffff_ffff => {
+            writer.write_all(&[0x80])?;
+            writer.write_all(&(length as u32).to_be_bytes())
+        }
+        _ => {
+            writer.write_all(&[0x81])?;
+            writer.write_all(&length.to_be_bytes())
+        }
+    }
+}
+
+fn write_string<W: Write>(writer: &mut W, bytes: &[u8]) -> io::Result<()> {
+    write_length(writer, bytes.len() as u64)?;
+    writer.write_all(bytes)
+}
+
+/// the id of an entry of a stream as the key of its node, big endian so
+/// that the keys sort as the ids
+fn raw_stream_id(id: StreamId) -> [u8; 16] {
+    let mut raw = [0; 16];
+    raw[..8].copy_from_slice(&id.ms.to_be_bytes());
+    raw[8..].copy_from_slice(&id.seq.to_be_bytes());
+    raw
+}
+
+/// a listpack of the entries, the ones that are integers written as such
+fn to_listpack(entries: &[Vec<u8>]) -> Vec<u8> {
+    let mut bytes = vec![0; 6];
+    for entry in entries {
+        let integer = std::str::from_utf8(entry)
+            .ok()
+            .and_then(|text| text.parse::<i64>().ok())
+            .filter(|integer| integer.to_string().as_bytes() == &entry[..]);
+        let start = bytes.len();
+        match (integer, entry.len()) {
+            (Some(integer @ 0..=127), _) => bytes.push(integer as u8),
+            (Some(integer @ -4096..=4095), _) => {
+                bytes.push(0xc0 | ((integer >> 8) & 0x1f) as u8);
+                bytes.push(integer as u8);
+            }
+            (Some(integer), _) => {
+                let (encoding, size) = match integer {
+                    -0x8000..=0x7fff => (0xf1, 2),
+                    -0x80_0000..=0x7f_ffff => (0xf2, 3),
+                    -0x8000_0000..=0x7fff_ffff => (0xf3, 4),
+                    _ => (0xf4, 8),
+                };
+                bytes.push(encoding);
+                bytes.extend_from_slice(&integer.to_le_bytes()[..size]);
+            }
+            (None, length @ 0..=63) => {
+                bytes.push(0x80 | length as u8);
+                bytes.extend_from_slice(entry);
+            }
+            (None, length @ 64..=4095) => {
+                bytes.push(0xe0 | (length >> 8) as u8);
+                bytes.push(length as u8);
+                bytes.extend_from_slice(entry);
+            }
+            (None, length) => {
+                bytes.push(0xf0);
+                bytes.extend_from_slice(&(length as u32).to_le_bytes());
+                bytes.extend_from_slice(entry);
+            }
+        }
+
+        // the length of the entry, for it to be read backwards, the lowest
+        // bits last and flagged but for the highest ones
+        let length = bytes.len() - start;
+        let backlen = backlen_size(length);
+        for byte in (0..backlen).rev() {
+            let bits = ((length >> (7 * byte)) & 0x7f) as u8;
+            bytes.push(match byte < backlen - 1 {
+                true => bits | 0x80,
+                false => bits,
+            });
+        }
+    }
+    bytes.push(0xff);
+
+    let total = bytes.len() as u32;
+    let count = entries.len().min(u16::MAX as usize) as u16;
+    bytes[..4].copy_from_slice(&total.to_le_bytes());
+    bytes[4..6].copy_from_slice(&count.to_le_bytes());
+    bytes
+}
+
+/// the value of a key of the given type, None for a list or a hash whose
+/// fields all expired
+fn read_value<R: Read>(
+    reader: &mut R,
+    data_type: u8,
+    expiry: Option<Expiry>,
+) -> io::Result<Option<StoredValue>> {
+    let value = match data_type {
+        TYPE_STRING => StoredValue::String(RedisValue::new(read_string(reader)?, expiry)),
+        TYPE_SET => {
+            let members = (0..read_length(reader)?)
+                .map(|_| read_string(reader))
+                .collect::<io::Result<HashSet<_>>>()?;
+            StoredValue::Set(RedisSet::new(members, expiry))
+        }
+        TYPE_SET_INTSET => {
+            let members = intset(&read_string(reader)?)?.into_iter().collect();
+            StoredValue::Set(RedisSet::new(members, expiry))
+        }
+        TYPE_SET_LISTPACK => {
+            let members = listpack(&read_string(reader)?)?.into_iter().collect();
+            StoredValue::Set(RedisSet::new(members, expiry))
+        }
+        TYPE_ZSET | TYPE_ZSET_2 => {
+            let mut scores = HashMap::new();
+            for _ in 0..read_length(reader)? {
+                let member = read_string(reader)?;
+                let score = match data_type {
+                    TYPE_ZSET => read_string_score(reader)?,
+                    _ => {
+                        let mut score = [0; 8];
+                        reader.read_exact(&mut score)?;
+                        f64::from_le_bytes(score)
+                    }
+                };
+                scores.insert(member, score);
+            }
+            StoredValue::SortedSet(RedisSortedSet::from_scores(scores, expiry))
+        }
+        TYPE_ZSET_ZIPLIST | TYPE_ZSET_LISTPACK => {
+            let entries = match data_type {
+                TYPE_ZSET_ZIPLIST => ziplist(&read_string(reader)?)?,
+                _ => listpack(&read_string(reader)?)?,
+            };
+            let scores = pairs(entries)?
+                .into_iter()
+                .map(|(member, score)| Ok((member, parse_score(&score)?)))
+                .collect::<io::Result<HashMap<_, _>>>()?;
+            StoredValue::SortedSet(RedisSortedSet::from_scores(scores, expiry))
+        }
+        TYPE_HASH => {
+            let mut data = HashMap::new();
+            for _ in 0..read_length(reader)? {
+                let field = read_string(reader)?;
+                data.insert(field, read_string(reader)?);
+            }
+            StoredValue::Hash(RedisHashMap::new(data, expiry))
+        }
+        TYPE_HASH_ZIPMAP | TYPE_HASH_ZIPLIST | TYPE_HASH_LISTPACK => {
+            let entries = match data_type {
+                TYPE_HASH_ZIPMAP => zipmap(&read_string(reader)?)?,
+                TYPE_HASH_ZIPLIST => pairs(ziplist(&read_string(reader)?)?)?,
+                _ => pairs(listpack(&read_string(reader)?)?)?,
+            };
+            StoredValue::Hash(RedisHashMap::new(entries.into_iter().collect(), expiry))
+        }
+        TYPE_HASH_METADATA | TYPE_HASH_LISTPACK_EX => {
+            // the earliest expiry of the fields, which the ttls of the
+            // fields follow in a hash that is not a listpack
+            let earliest = read_millis(reader)?;
+            let fields = match data_type {
+                TYPE_HASH_METADATA => (0..read_length(reader)?)
+                    .map(|_| {
+                        let time = match read_length(reader)? {
+                            0 => 0,
+                            ttl => earliest + ttl - 1,
+                        };
+                        Ok((read_string(reader)?, read_string(reader)?, time))
+                    })
+                    .collect::<io::Result<Vec<_>>>()?,
+                _ => {
+                    let entries = listpack(&read_string(reader)?)?;
+                    let entries = entries.chunks_exact(3);
+                    if !entries.remainder().is_empty() {
+                        return Err(invalid("invalid hash listpack"));
+                    }
+                    entries
+                        .map(|entry| {
+                            Ok((
+                                entry[0].clone(),
+                                entry[1].clone(),
+                                parse_integer(&entry[2])? as u64,
+                            ))
+                        })
+                        .collect::<io::Result<Vec<_>>>()?
+                }
+            };
+
+            let mut hash = RedisHashMap::new(HashMap::new(), expiry);
+            for (field, value, time) in fields {
+                if time != 0 {
+                    let expiry = Expiry::new_from_unix_millis(time)
+                        .map_err(|_| invalid("expiry out of range"))?;
+                    if expiry.is_expired() {
+                        continue;
+                    }
+                    hash.field_expiry.insert(field.clone(), expiry);
+                }
+                hash.data.insert(field, value);
+            }
+            // a hash whose fields all expired does not exist
+            if hash.data.is_empty() {
+                return Ok(None);
+            }
+            StoredValue::Hash(hash)
+        }
+        TYPE_STREAM_LISTPACKS | TYPE_STREAM_LISTPACKS_2 | TYPE_STREAM_LISTPACKS_3 => {
+            StoredValue::Stream(read_stream(reader, data_type, expiry)?)
+        }
+        // there are no lists to put them in, they are read to get past them
+        TYPE_LIST | TYPE_LIST_ZIPLIST => {
+            match data_type {
+                TYPE_LIST => {
+                    for _ in 0..read_length(reader)? {
+                        read_string(reader)?;
+                    }
+                }
+                _ => {
+                    read_string(reader)?;
+                }
+            }
+            return Ok(None);
+        }
+        TYPE_LIST_QUICKLIST | TYPE_LIST_QUICKLIST_2 => {
+            for _ in 0..read_length(reader)? {
+                if data_type == TYPE_LIST_QUICKLIST_2 {
+                    read_length(reader)?;
+                }
+                read_string(reader)?;
+            }
+            return Ok(None);
+        }
+        data_type => {
+            return Err(invalid(&format!(
+                "values of type {} are not supported",
+                data_type
+            )))
+        }
+    };
+
+    Ok(Some(value))
+}
+
+/// read a stream, whose entries are in nodes made of a master entry whose
+/// fields the entries may share followed by the entries, whose ids are
+/// relative to the id of the node
+fn read_stream<R: Read>(
+    reader: &mut R,
+    data_type: u8,
+    expiry: Option<Expiry>,
+) -> io::Result<RedisStream> {
+    let mut stream = RedisStream::new(expiry);
+    for _ in 0..read_length(reader)? {
+        let master = read_string(reader)?;
+        let master = read_stream_id(&mut master.as_slice())?;
+        let node = listpack(&read_string(reader)?)?;
+        read_stream_node(node, master, &mut stream.entries)?;
+    }
+
+    // the number of entries, which are counted once read
+    read_length(reader)?;
+    stream.last_id = StreamId::new(read_length(reader)?, read_length(reader)?);
+    match data_type {
+        TYPE_STREAM_LISTPACKS => stream.entries_added = stream.entries.len() as u64,
+        _ => {
+            // the first entry, which is known once read
+            read_length(reader)?;
+            read_length(reader)?;
+            stream.max_deleted_id = StreamId::new(read_length(reader)?, read_length(reader)?);
+            stream.entries_added = read_length(reader)?;
+        }
+    }
+
+    for _ in 0..read_length(reader)? {
+        let name = read_string(reader)?;
+        let last_delivered_id = StreamId::new(read_length(reader)?, read_length(reader)?);
+        let mut group = ConsumerGroup::new(last_delivered_id);
+        if data_type != TYPE_STREAM_LISTPACKS {
+            // the number of entries read, which is not kept
+            read_length(reader)?;
+        }
+
+        // the entries pending are listed for the group then for each
+        // consumer, which tells whom they are pending for
+        let mut pending = BTreeMap::new();
+        for _ in 0..read_length(reader)? {
+            let id = read_stream_id(reader)?;
+            pending.insert(id, (read_millis(reader)?, read_length(reader)?));
+        }
+        for _ in 0..read_length(reader)? {
+            let name = read_string(reader)?;
+            let mut consumer = Consumer {
+                pending: BTreeSet::new(),
+                seen_time: read_millis(reader)?,
+            };
+            if data_type == TYPE_STREAM_LISTPACKS_3 {
+                // when the consumer last read successfully
+                read_millis(reader)?;
+            }
+            for _ in 0..read_length(reader)? {
+                let id = read_stream_id(reader)?;
+                let (delivery_time, delivery_count) = pending
+                    .remove(&id)
+                    .ok_or_else(|| invalid("entry pending for a consumer only"))?;
+                let entry = PendingEntry {
+                    consumer: name.clone(),
+                    delivery_time,
+                    delivery_count,
+                };
+                group.pending.insert(id, entry);
+                consumer.pending.insert(id);
+            }
+            group.consumers.insert(name, consumer);
+        }
+        stream.groups.insert(name, group);
+    }
+
+    Ok(stream)
+}
+
+fn read_stream_node(
+    node: Vec<Vec<u8>>,
+    master: StreamId,
+    entries: &mut BTreeMap<StreamId, StreamFields>,
+) -> io::Result<()> {
+    let mut node = node.into_iter();
+    let mut next = || node.next().ok_or_else(|| invalid("stream node cut short"));
+
+    let count = parse_integer(&next()?)? + parse_integer(&next()?)?;
+    let master_fields = (0..parse_integer(&next()?)?)
+        .map(|_| next())
+        .collect::<io::Result<Vec<_>>>()?;
+    // the end of the master entry
+    next()?;
+
+    for _ in 0..count {
+        let flags = parse_integer(&next()?)?;
+        let ms = master.ms.wrapping_add(parse_integer(&next()?)? as u64);
+        let seq = master.seq.wrapping_add(parse_integer(&next()?)? as u64);
+        let fields = match flags & STREAM_ITEM_FLAG_SAMEFIELDS {
+            0 => (0..parse_integer(&next()?)?)
+                .map(|_| Ok((next()?, next()?)))
+                .collect::<io::Result<Vec<_>>>()?,
+            _ => master_fields
+                .iter()
+                .map(|field| Ok((field.clone(), next()?)))
+                .collect::<io::Result<Vec<_>>>()?,
+        };
+        // the number of parts of the entry, for it to be read backwards
+        next()?;
+        if flags & STREAM_ITEM_FLAG_DELETED == 0 {
+            entries.insert(StreamId::new(ms, seq), fields);
+        }
+    }
+
+    Ok(())
+}
+
+fn read_stream_id<R: Read>(reader: &mut R) -> io::Result<StreamId> {
+    let mut ms = [0; 8];
+    let mut seq = [0; 8];
+    reader.read_exact(&mut ms)?;
+    reader.read_exact(&mut seq)?;
+    Ok(StreamId::new(
+        u64::from_be_bytes(ms),
+        u64::from_be_bytes(seq),
+    ))
+}
+
+fn read_millis<R: Read>(reader: &mut R) -> io::Result<u64> {
+    let mut time = [0; 8];
+    reader.read_exact(&mut time)?;
+    Ok(u64::from_le_bytes(time))
+}
+
+fn read_u8<R: Read>(reader: &mut R) -> io::Result<u8> {
+    let mut byte = [0; 1];
+    reader.read_exact(&mut byte)?;
+    Ok(byte[0])
+}
+
+/// a length, or how the string that follows is encoded if its first two
+/// bits are set
+fn read_length_or_encoding<R: Read>(reader: &mut R) -> io::Result<Result<u64, u8>> {
+    let first = read_u8(reader)?;
+    let length = match first >> 6 {
+        0 => (first & 0x3f) as u64,
+        1 => ((first & 0x3f) as u64) << 8 | read_u8(reader)? as u64,
+        2 => match first {
+            0x80 => {
+                let mut length = [0; 4];
+                reader.read_exact(&mut length)?;
+                u32::from_be_bytes(length) as u64
+            }
+            0x81 => {
+                let mut length = [0; 8];
+                reader.read_exact(&mut length)?;
+                u64::from_be_bytes(length)
+            }
+            _ => return Err(invalid("unknown length encoding")),
+        },
+        _ => return Ok(Err(first & 0x3f)),
+    };
+
+    Ok(Ok(length))
+}
+
+fn read_length<R: Read>(reader: &mut R) -> io::Result<u64> {
+    read_length_or_encoding(reader)?.map_err(|_| invalid("unexpected string encoding"))
+}
+
+fn read_string<R: Read>(reader: &mut R) -> io::Result<Vec<u8>> {
+    match read_length_or_encoding(reader)? {
+        Ok(length) => read_bytes(reader, length),
+        Err(ENCODED_INT8) => Ok((read_u8(reader)? as i8).to_string().into_bytes()),
+        Err(ENCODED_INT16) => {
+            let mut integer = [0; 2];
+            reader.read_exact(&mut integer)?;
+            Ok(i16::from_le_bytes(integer).to_string().into_bytes())
+        }
+        Err(ENCODED_INT32) => {
+            let mut integer = [0; 4];
+            reader.read_exact(&mut integer)?;
+            Ok(i32::from_le_bytes(integer).to_string().into_bytes())
+        }
+        Err(ENCODED_LZF) => {
+            let compressed_length = read_length(reader)?;
+            let length = read_length(reader)?;
+            lzf_decompress(&read_bytes(reader, compressed_length)?, length as usize)
+        }
+        Err(_) => Err(invalid("unknown string encoding")),
+    }
+}
+
+fn read_bytes<R: Read>(reader: &mut R, length: u64) -> io::Result<Vec<u8>> {
+    let mut bytes = vec![];
+    // the length is not trusted to allocate, a truncated file failing
+    // once its end is reached
+    reader.take(length).read_to_end(&mut bytes)?;
+    match bytes.len() as u64 == length {
+        true => Ok(bytes),
+        false => Err(io::Error::from(ErrorKind::UnexpectedEof)),
+    }
+}
+
+/// a score written as text after its length, the lengths past the text
+/// ones standing for the scores that are not numbers
+fn read_string_score<R: Read>(reader: &mut R) -> io::Result<f64> {
+    match read_u8(reader)? {
+        253 => Ok(f64::NAN),
+        254 => Ok(f64::INFINITY),
+        255 => Ok(f64::NEG_INFINITY),
+        length => parse_score(&read_bytes(reader, length as u64)?),
+    }
+}
+
+fn parse_integer(integer: &[u8]) -> io::Result<i64> {
+    std::str::from_utf8(integer)
+        .ok()
+        .and_then(|integer| integer.parse().ok())
+        .ok_or_else(|| invalid("invalid integer"))
+}
+
+fn parse_score(score: &[u8]) -> io::Result<f64> {
+    std::str::from_utf8(score)
+        .ok()
+        .and_then(|score| score.parse().ok())
+        .ok_or_else(|| invalid("invalid score"))
+}
+
+/// the fields and values, or members and scores, that follow each other
+fn pairs(entries: Vec<Vec<u8>>) -> io::Result<Vec<(Vec<u8>, Vec<u8>)>> {
+    let mut entries = entries.into_iter();
+    let mut pairs = vec![];
+    while let Some(first) = entries.next() {
+        let second = entries
+            .next()
+            .ok_or_else(|| invalid("odd number of entries"))?;
+        pairs.push((first, second));
+    }
+
+    Ok(pairs)
+}
+
+/// take the given number of bytes from the start of an encoded collection
+fn take<'a>(bytes: &mut &'a [u8], count: usize) -> io::Result<&'a [u8]> {
+    if bytes.len() < count {
+        return Err(invalid("collection cut short"));
+    }
+    let (taken, rest) = bytes.split_at(count);
+    *bytes = rest;
+    Ok(taken)
+}
+
+fn take_integer(bytes: &mut &[u8], size: usize) -> io::Result<i64> {
+    let taken = take(bytes, size)?;
+    let mut integer = [0; 8];
+    integer[..size].copy_from_slice(taken);
+    // sign extended from the last byte, the integers being little endian
+    let shift = 64 - 8 * size as u32;
+    Ok(i64::from_le_bytes(integer) << shift >> shift)
+}
+
+/// the integers of a set made of integers only
+fn intset(bytes: &[u8]) -> io::Result<Vec<Vec<u8>>> {
+    let mut bytes = bytes;
+    let size = take_integer(&mut bytes, 4)? as usize;
+    let count = take_integer(&mut bytes, 4)?;
+    if ![2, 4, 8].contains(&size) {
+        return Err(invalid("invalid intset encoding"));
+    }
+
+    (0..count)
+        .map(|_| Ok(take_integer(&mut bytes, size)?.to_string().into_bytes()))
+        .collect()
+}
+
+/// the entries of a ziplist, the integers as text
+fn ziplist(bytes: &[u8]) -> io::Result<Vec<Vec<u8>>> {
+    let mut bytes = bytes;
+    // the bytes it takes, the offset of its last entry and its number of
+    // entries
+    take(&mut bytes, 10)?;
+
+    let mut entries = vec![];
+    loop {
+        // the length of the previous entry
+        match take(&mut bytes, 1)?[0] {
+            0xff => return Ok(entries),
+            0xfe => {
+                take(&mut bytes, 4)?;
+            }
+            _ => {}
+        }
+
+        let encoding = take(&mut bytes, 1)?[0];
+        let entry = match encoding >> 6 {
+            0 => take(&mut bytes, (encoding & 0x3f) as usize)?.to_vec(),
+            1 => {
+                let length = ((encoding & 0x3f) as usize) << 8 | take(&mut bytes, 1)?[0] as usize;
+                take(&mut bytes, length)?.to_vec()
+            }
+            2 => {
+                let length = u32::from_be_bytes(take(&mut bytes, 4)?.try_into().unwrap());
+                take(&mut bytes, length as usize)?.to_vec()
+            }
+            _ => {
+                let integer = match encoding {
+                    0xc0 => take_integer(&mut bytes, 2)?,
+                    0xd0 => take_integer(&mut bytes, 4)?,
+                    0xe0 => take_integer(&mut bytes, 8)?,
+                    0xf0 => take_integer(&mut bytes, 3)?,
+                    0xfe => take_integer(&mut bytes, 1)?,
+                    0xf1..=0xfd => (encoding & 0x0f) as i64 - 1,
+                    _ => return Err(invalid("unknown ziplist encoding")),
+                };
+                integer.to_string().into_bytes()
+            }
+        };
+        entries.push(entry);
+    }
+}
+
+/// the entries of a listpack, the integers as text
+fn listpack(bytes: &[u8]) -> io::Result<Vec<Vec<u8>>> {
+    let mut bytes = bytes;
+    // the bytes it takes and its number of entries
+    take(&mut bytes, 6)?;
+
+    let mut entries = vec![];
+    loop {
+        let encoding = take(&mut bytes, 1)?[0];
+        let (entry, length) = match encoding {
+            0xff => return Ok(entries),
+            0x00..=0x7f => (encoding.to_string().into_bytes(), 1),
+            0x80..=0xbf => {
+                let length = (encoding & 0x3f) as usize;
+                (take(&mut bytes, length)?.to_vec(), 1 + length)
+            }
+            0xc0..=0xdf => {
+                let integer = ((encoding & 0x1f) as i64) << 8 | take(&mut bytes, 1)?[0] as i64;
+                // 13 bits, sign extended
+                let integer = integer << 51 >> 51;
+                (integer.to_string().into_bytes(), 2)
+            }
+            0xe0..=0xef => {
+                let length = ((encoding & 0x0f) as usize) << 8 | take(&mut bytes, 1)?[0] as usize;
+                (take(&mut bytes, length)?.to_vec(), 2 + length)
+            }
+            0xf0 => {
+                let length = take_integer(&mut bytes, 4)? as usize;
+                (take(&mut bytes, length)?.to_vec(), 5 + length)
+            }
+            0xf1..=0xf4 => {
+                let size = [2, 3, 4, 8][(encoding - 0xf1) as usize];
+                let integer = take_integer(&mut bytes, size)?;
+                (integer.to_string().into_bytes(), 1 + size)
+            }
+            _ => return Err(invalid("unknown listpack encoding")),
+        };
+        // the length of the entry, for it to be read backwards
+        take(&mut bytes, backlen_size(length))?;
+        entries.push(entry);
+    }
+}
+
+/// the bytes the length of an entry of a listpack takes once written after
+/// it, seven bits a byte
+fn backlen_size(length: usize) -> usize {
+    match length {
+        0..=127 => 1,
+        128..=16382 => 2,
+        16383..=2097150 => 3,
+        2097151..=268435454 => 4,
+        _ => 5,
+    }
+}
+
+/// the fields and values of a zipmap, as the oldest dumps hold small hashes
+fn zipmap(bytes: &[u8]) -> io::Result<Vec<(Vec<u8>, Vec<u8>)>> {
+    let mut bytes = bytes;
+    // the number of entries, if below 254
+    take(&mut bytes, 1)?;
+
+    let length = |bytes: &mut &[u8]| -> io::Result<Option<usize>> {
+        match take(bytes, 1)?[0] {
+            0xff => Ok(None),
+            254 => Ok(Some(take_integer(bytes, 4)? as usize)),
+            length => Ok(Some(length as usize)),
+        }
+    };
+    let mut entries = vec![];
+    while let Some(field_length) = length(&mut bytes)? {
+        let field = take(&mut bytes, field_length)?.to_vec();
+        let value_length = length(&mut bytes)?.ok_or_else(|| invalid("zipmap cut short"))?;
+        // the bytes left free after the value
+        let free = take(&mut bytes, 1)?[0] as usize;
+        let value = take(&mut bytes, value_length)?.to_vec();
+        take(&mut bytes, free)?;
+        entries.push((field, value));
+    }
+
+    Ok(entries)
+}
+
+/// the bytes an LZF compressed string stands for
+fn lzf_decompress(compressed: &[u8], length: usize) -> io::Result<Vec<u8>> {
+    let cut_short = || invalid("compressed string cut short");
+    let mut output = Vec::with_capacity(length);
+    let mut input = compressed.iter().copied();
+
+    while let Some(control) = input.next() {
+        match control >> 5 {
+            // a run of literal bytes
+            0 => {
+                for _ in 0..=control {
+                    output.push(input.next().ok_or_else(cut_short)?);
+                }
+            }
+            // a reference to bytes already output
+            run => {
+                let mut run = run as usize;
+                if run == 7 {
+                    run += input.next().ok_or_else(cut_short)? as usize;
+                }
+                let offset =
+                    ((control & 0x1f) as usize) << 8 | input.next().ok_or_else(cut_short)? as usize;
+                let start = output
+                    .len()
+                    .checked_sub(offset + 1)
+                    .ok_or_else(|| invalid("invalid compressed string"))?;
+                for i in 0..run + 2 {
+                    output.push(output[start + i]);
+                }
+            }
+        }
+    }
+
+    match output.len() == length {
+        true => Ok(output),
+        false => Err(invalid("invalid compressed string")),
+    }
+}
@@ -0,0 +1,715 @@
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+use std::convert::TryInto;
+use std::fs;
+use std::io::{self, ErrorKind};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Mutex, PoisonError};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use ::rocksdb::compaction_filter::Decision;
+use ::rocksdb::{Direction, IteratorMode, Options, DB};
+
+use super::in_memory::{self, InMemoryStorage};
+use super::models::{
+    bitfield::BitFieldOp,
+    hyperloglog::HyperLogLog,
+    redis_sorted_set::Scores,
+    redis_stream::{StreamFields, StreamId, XAddId},
+    DataType, Expiry, ExpiryCondition, RedisHashMap, RedisSet, RedisSortedSet, RedisStream,
+    StoredValue,
+};
+use super::snapshot;
+use crate::storage::{sibling_path, EvictionPolicy, KeyspaceStats, MemoryStats, Sibling, Storage};
+
+// each value starts with the unix time in milliseconds it expires at, for
+// the compactions to drop it once it did without reading the rest of it
+const EXPIRY_SIZE: usize = 8;
+const NO_EXPIRY: u64 = 0;
+// the keys the stats are estimated from once there are more of them,
+// rather than going through all of them
+const STATS_SAMPLES: usize = 1000;
+// the keys eviction_candidate looks at for each one it samples, for the
+// volatile policies not to go through all the keys when few of them have a
+// time to live
+const EVICTION_LOOKUPS: usize = 16;
+
+// the temporary databases opened so far, each in a directory of its own
+static TEMPORARY_DATABASES: AtomicUsize = AtomicUsize::new(0);
+
+/// a storage keeping its keys in a RocksDB database rather than in memory,
+/// so that they can take more than there is, the keys each call works on
+/// being read into memory then written back if changed, and the keys that
+/// expired being dropped by the compactions
+pub struct RocksDbStorage {
+    db: DB,
+    // the directory of a temporary database, dropped after the database
+    temporary: Option<TemporaryDirectory>,
+    // the keys the last call worked on
+    memory: InMemoryStorage,
+    // the stream handed out by xread_mut to be modified in place, along
+    // with its version once read, written back once the next call is made
+    borrowed: Option<(Vec<u8>, u64)>,
+    // the version of each key modified, as WATCH reads them
+    versions: HashMap<Vec<u8>, u64>,
+    // the keys found expired since the last call to take_expired
+    expired: Vec<Vec<u8>>,
+    // the keys the reads through &self found expired, along with the first
+    // of them that failed, both taken by the next call that may change the
+    // storage
+    lapsed: Mutex<(Vec<Vec<u8>>, Option<io::Error>)>,
+    // the key sample_expired resumes from, none to start over
+    expire_cursor: Option<Vec<u8>>,
+    // the first read or write that failed since the last flush
+    error: Option<io::Error>,
+}
+
+/// a directory removed once dropped
+struct TemporaryDirectory(PathBuf);
+
+impl Drop for TemporaryDirectory {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+impl RocksDbStorage {
+    /// open the RocksDB database at path, created if there is none
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let mut options = Options::default();
+        options.create_if_missing(true);
+        options.set_compaction_filter("redisless_expiry", drop_expired);
+
+        Ok(RocksDbStorage {
+            db: DB::open(&options, path).map_err(into_io_error)?,
+            temporary: None,
+            memory: InMemoryStorage::new(),
+            borrowed: None,
+            versions: HashMap::new(),
+            expired: vec![],
+            lapsed: Mutex::new((vec![], None)),
+            expire_cursor: None,
+            error: None,
+        })
+    }
+
+    /// a RocksDB database of its own in the temporary directory, removed
+    /// once dropped
+    pub fn temporary() -> io::Result<Self> {
+        let path = std::env::temp_dir().join(format!(
+            "redisless-rocksdb-{}-{}",
+            process::id(),
+            TEMPORARY_DATABASES.fetch_add(1, Ordering::Relaxed)
+        ));
+        // left by a process that had the same id
+        let _ = fs::remove_dir_all(&path);
+
+        let mut storage = RocksDbStorage::open(&path)?;
+        storage.temporary = Some(TemporaryDirectory(path));
+        Ok(storage)
+    }
+
+    /// write the changes made so far to the disk, or return the error a
+    /// read or a write met since the last flush
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.write_borrowed();
+        let lapsed = self
+            .lapsed
+            .get_mut()
+            .unwrap_or_else(PoisonError::into_inner);
+        if let Some(error) = self.error.take().or_else(|| lapsed.1.take()) {
+            return Err(error);
+        }
+        self.db.flush().map_err(into_io_error)
+    }
+
+    /// read the keys into memory for a call to work on, in place of the
+    /// ones the last call worked on
+    fn load(&mut self, keys: &[&[u8]]) {
+        self.write_borrowed();
+        self.memory = InMemoryStorage::new();
+        for key in keys {
+            match self.get(key) {
+                Ok(Some(Some(value))) => self.memory.put(key, value),
+                // removing a key that expired counts as a change
+                Ok(Some(None)) => {
+                    self.delete(key);
+                    self.versions
+                        .insert(key.to_vec(), in_memory::next_version());
+                    self.expired.push(key.to_vec());
+                }
+                Ok(None) => {}
+                Err(error) => {
+                    self.error.get_or_insert(error);
+                }
+            }
+        }
+    }
+
+    /// the value stored at key, None inside if it expired
+    fn get(&self, key: &[u8]) -> io::Result<Option<Option<StoredValue>>> {
+        match self.db.get(key).map_err(into_io_error)? {
+            Some(bytes) => Ok(Some(decode(&bytes)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// the value stored at key as written, without reading it into memory,
+    /// so that the readers can share the storage, a key that expired being
+    /// left for the next call that may change the storage to remove
+    fn peek(&self, key: &[u8]) -> Option<StoredValue> {
+        let failed = match self.get(key) {
+            Ok(Some(Some(value))) => return Some(value),
+            Ok(None) => return None,
+            Ok(Some(None)) => None,
+            Err(error) => Some(error),
+        };
+
+        // locked once read, for the readers not to wait for each other
+        let mut lapsed = self.lapsed.lock().unwrap_or_else(PoisonError::into_inner);
+        match failed {
+            Some(error) => {
+                lapsed.1.get_or_insert(error);
+            }
+            None => lapsed.0.push(key.to_vec()),
+        }
+        None
+    }
+
+    fn delete(&mut self, key: &[u8]) {
+        if let Err(error) = self.db.delete(key) {
+            self.error.get_or_insert(into_io_error(error));
+        }
+    }
+
+    /// write the key back if the call changed it, that is if its version
+    /// is not the one it was read with
+    fn store(&mut self, key: &[u8], loaded: u64) {
+        let version = self.memory.version(key);
+        if version == loaded {
+            return;
+        }
+
+        self.versions.insert(key.to_vec(), version);
+        match self.memory.copy(key) {
+            Some(value) => {
+                if let Err(error) = self.db.put(key, encode(key, &value)) {
+                    self.error.get_or_insert(into_io_error(error));
+                }
+            }
+            None => self.delete(key),
+        }
+    }
+
+    /// write back the stream handed out by xread_mut, modified since
+    fn write_borrowed(&mut self) {
+        if let Some((key, loaded)) = self.borrowed.take() {
+            self.store(&key, loaded);
+        }
+    }
+
+    /// make a change to the key once read into memory then write it back
+    fn change<R>(&mut self, key: &[u8], change: impl FnOnce(&mut InMemoryStorage) -> R) -> R {
+        self.load(&[key]);
+        let loaded = self.memory.version(key);
+        let changed = change(&mut self.memory);
+        self.store(key, loaded);
+        changed
+    }
+
+    /// go through every key along with its value as written, the ones that
+    /// expired left out, one at a time rather than all read into memory
+    fn scan(&mut self, mut visit: impl FnMut(&[u8], &[u8])) {
+        self.write_borrowed();
+        let now = now_millis();
+        for entry in self.db.iterator(IteratorMode::Start) {
+            match entry {
+                Ok((key, value)) => match expires_at(&value) {
+                    Some(time) if time <= now => {}
+                    _ => visit(&key, &value),
+                },
+                Err(error) => {
+                    self.error.get_or_insert(into_io_error(error));
+                    return;
+                }
+            }
+        }
+    }
+
+    /// go through the keys following one picked at random along with their
+    /// value as written, the expired ones left out, then the ones before it,
+    /// until visit returns false or `limit` keys were looked at, returns
+    /// whether every key was
+    fn sample(&mut self, limit: usize, mut visit: impl FnMut(&[u8], &[u8]) -> bool) -> bool {
+        self.write_borrowed();
+        let start = rand::random::<[u8; 8]>();
+        let now = now_millis();
+        let mut looked = 0;
+        for (mode, end) in [
+            (IteratorMode::From(&start, Direction::Forward), None),
+            (IteratorMode::Start, Some(&start[..])),
+        ] {
+            for entry in self.db.iterator(mode) {
+                let (key, value) = match entry {
+                    Ok(entry) => entry,
+                    Err(error) => {
+                        self.error.get_or_insert(into_io_error(error));
+                        return false;
+                    }
+                };
+                if matches!(end, Some(end) if &key[..] >= end) {
+                    break;
+                }
+                if looked == limit {
+                    return false;
+                }
+                looked += 1;
+                let expired = matches!(expires_at(&value), Some(time) if time <= now);
+                if !expired && !visit(&key, &value) {
+                    return false;
+                }
+            }
+        }
+        true
+    }
+
+    /// what was counted over the keys sampled, scaled to the keys RocksDB
+    /// estimates the database holds
+    fn scale(&mut self, sampled: usize) -> impl Fn(usize) -> usize {
+        let keys = match self.db.property_int_value("rocksdb.estimate-num-keys") {
+            Ok(keys) => keys.unwrap_or(0) as usize,
+            Err(error) => {
+                self.error.get_or_insert(into_io_error(error));
+                0
+            }
+        };
+        // the keys sampled are there, whatever RocksDB estimates
+        let keys = keys.max(sampled);
+        move |value| match sampled {
+            0 => 0,
+            sampled => (value as u128 * keys as u128 / sampled as u128) as usize,
+        }
+    }
+}
+
+impl Default for RocksDbStorage {
+    /// a temporary database, removed once dropped
+    fn default() -> Self {
+        RocksDbStorage::temporary().expect("the temporary RocksDB database can't be created")
+    }
+}
+
+impl Sibling for RocksDbStorage {
+    /// a database of its own next to this one, temporary if this one is
+    fn sibling(&self, index: usize) -> io::Result<Self> {
+        match self.temporary {
+            Some(_) => RocksDbStorage::temporary(),
+            None => RocksDbStorage::open(sibling_path(self.db.path(), index)),
+        }
+    }
+}
+
+impl Drop for RocksDbStorage {
+    fn drop(&mut self) {
+        let _ = self.flush();
+    }
+}
+
+impl Storage for RocksDbStorage {
+    fn write(&mut self, key: &[u8], value: &[u8]) {
+        self.change(key, |memory| memory.write(key, value))
+    }
+
+    fn setbit(&mut self, key: &[u8], offset: usize, bit: bool) -> u8 {
+        self.change(key, |memory| memory.setbit(key, offset, bit))
+    }
+
+    fn bitfield(&mut self, key: &[u8], operations: &[BitFieldOp]) -> Vec<Option<i64>> {
+        self.change(key, |memory| memory.bitfield(key, operations))
+    }
+
+    fn pfadd(&mut self, key: &[u8], elements: &[Vec<u8>]) -> Option<u32> {
+        self.change(key, |memory| memory.pfadd(key, elements))
+    }
+
+    fn pfmerge(&mut self, key: &[u8], hyperloglog: &HyperLogLog) -> Option<()> {
+        self.change(key, |memory| memory.pfmerge(key, hyperloglog))
+    }
+
+    fn hwrite(&mut self, key: &[u8], field: &[u8], value: &[u8]) -> u32 {
+        self.change(key, |memory| memory.hwrite(key, field, value))
+    }
+
+    fn sadd(&mut self, key: &[u8], member: &[u8]) -> u32 {
+        self.change(key, |memory| memory.sadd(key, member))
+    }
+
+    fn swrite(&mut self, key: &[u8], members: HashSet<Vec<u8>>) {
+        self.change(key, |memory| memory.swrite(key, members))
+    }
+
+    fn zadd(&mut self, key: &[u8], score: f64, member: &[u8]) -> u32 {
+        self.change(key, |memory| memory.zadd(key, score, member))
+    }
+
+    fn xadd(&mut self, key: &[u8], id: XAddId, fields: StreamFields) -> Option<StreamId> {
+        self.change(key, |memory| memory.xadd(key, id, fields))
+    }
+
+    fn expire(&mut self, key: &[u8], expiry: Expiry) -> u32 {
+        self.change(key, |memory| memory.expire(key, expiry))
+    }
+
+    fn hexpire(
+        &mut self,
+        key: &[u8],
+        field: &[u8],
+        expiry: Expiry,
+        condition: Option<ExpiryCondition>,
+    ) -> i64 {
+        self.change(key, |memory| memory.hexpire(key, field, expiry, condition))
+    }
+
+    fn hpttl(&mut self, key: &[u8], field: &[u8]) -> i64 {
+        self.load(&[key]);
+        self.memory.hpttl(key, field)
+    }
+
+    fn hpersist(&mut self, key: &[u8], field: &[u8]) -> i64 {
+        self.change(key, |memory| memory.hpersist(key, field))
+    }
+
+    fn read(&self, key: &[u8]) -> Option<Cow<'_, [u8]>> {
+        match self.peek(key)? {
+            StoredValue::String(value) => Some(Cow::Owned(value.data)),
+            _ => None,
+        }
+    }
+
+    fn hread(&mut self, key: &[u8], field: &[u8]) -> Option<&[u8]> {
+        self.load(&[key]);
+        self.memory.hread(key, field)
+    }
+
+    fn hread_all(&mut self, key: &[u8]) -> Option<&RedisHashMap> {
+        self.load(&[key]);
+        self.memory.hread_all(key)
+    }
+
+    fn sread(&mut self, key: &[u8]) -> Option<&RedisSet> {
+        self.load(&[key]);
+        self.memory.sread(key)
+    }
+
+    fn sintercard(&mut self, keys: &[Vec<u8>], limit: usize) -> usize {
+        self.load(&keys.iter().map(|key| &key[..]).collect::<Vec<_>>());
+        self.memory.sintercard(keys, limit)
+    }
+
+    fn zread(&mut self, key: &[u8]) -> Option<&RedisSortedSet> {
+        self.load(&[key]);
+        self.memory.zread(key)
+    }
+
+    fn zwrite(&mut self, key: &[u8], members: Scores) {
+        self.change(key, |memory| memory.zwrite(key, members))
+    }
+
+    fn xread(&mut self, key: &[u8]) -> Option<&RedisStream> {
+        self.load(&[key]);
+        self.memory.xread(key)
+    }
+
+    fn xread_mut(&mut self, key: &[u8]) -> Option<&mut RedisStream> {
+        self.load(&[key]);
+        self.borrowed = Some((key.to_vec(), self.memory.version(key)));
+        self.memory.xread_mut(key)
+    }
+
+    fn xcreate(&mut self, key: &[u8]) {
+        self.change(key, |memory| memory.xcreate(key))
+    }
+
+    fn remove(&mut self, key: &[u8]) -> u32 {
+        self.change(key, |memory| memory.remove(key))
+    }
+
+    fn take(&mut self, key: &[u8]) -> Option<StoredValue> {
+        self.change(key, |memory| memory.take(key))
+    }
+
+    fn copy(&mut self, key: &[u8]) -> Option<StoredValue> {
+        self.load(&[key]);
+        self.memory.copy(key)
+    }
+
+    fn put(&mut self, key: &[u8], value: StoredValue) {
+        self.change(key, |memory| memory.put(key, value))
+    }
+
+    fn srem(&mut self, key: &[u8], member: &[u8]) -> u32 {
+        self.change(key, |memory| memory.srem(key, member))
+    }
+
+    fn spop(&mut self, key: &[u8], count: usize) -> Vec<Vec<u8>> {
+        self.change(key, |memory| memory.spop(key, count))
+    }
+
+    fn zrem(&mut self, key: &[u8], member: &[u8]) -> u32 {
+        self.change(key, |memory| memory.zrem(key, member))
+    }
+
+    fn zpop(&mut self, key: &[u8], count: usize, max: bool) -> Vec<(Vec<u8>, f64)> {
+        self.change(key, |memory| memory.zpop(key, count, max))
+    }
+
+    fn contains(&self, key: &[u8]) -> bool {
+        match self.peek(key) {
+            // a hash whose fields all expired is gone as well
+            Some(StoredValue::Hash(hash)) => hash.has_live_fields(),
+            value => value.is_some(),
+        }
+    }
+
+    fn type_of(&mut self, key: &[u8]) -> Option<DataType> {
+        self.load(&[key]);
+        self.memory.type_of(key)
+    }
+
+    fn take_expired(&mut self) -> Vec<Vec<u8>> {
+        // the keys the reads found expired are removed as they are once read
+        // into memory, unless they were since
+        let lapsed = std::mem::take(
+            &mut self
+                .lapsed
+                .get_mut()
+                .unwrap_or_else(PoisonError::into_inner)
+                .0,
+        );
+        if !lapsed.is_empty() {
+            self.load(&lapsed.iter().map(|key| &key[..]).collect::<Vec<_>>());
+        }
+
+        let mut expired = std::mem::take(&mut self.expired);
+        expired.extend(self.memory.take_expired());
+        expired
+    }
+
+    fn lapsed(&self) -> bool {
+        !self
+            .lapsed
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .0
+            .is_empty()
+    }
+
+    fn sample_expired(&mut self, count: usize) -> (usize, usize) {
+        self.write_borrowed();
+        let mode = match &self.expire_cursor {
+            Some(key) => IteratorMode::From(key, Direction::Forward),
+            None => IteratorMode::Start,
+        };
+        let mut entries = vec![];
+        for entry in self.db.iterator(mode).take(count + 1) {
+            match entry {
+                Ok((key, value)) => entries.push((key.to_vec(), expires_at(&value))),
+                Err(error) => {
+                    self.error.get_or_insert(into_io_error(error));
+                    break;
+                }
+            }
+        }
+        // the key after the last one looked at is where the next call
+        // resumes from
+        self.expire_cursor = match entries.len() > count {
+            true => entries.pop().map(|(key, _)| key),
+            false => None,
+        };
+
+        let now = now_millis();
+        let (mut sampled, mut expired) = (0, 0);
+        for (key, time) in entries {
+            match time {
+                // removing a key that expired counts as a change
+                Some(time) if time <= now => {
+                    self.delete(&key);
+                    self.versions.insert(key.clone(), in_memory::next_version());
+                    self.expired.push(key);
+                    sampled += 1;
+                    expired += 1;
+                }
+                Some(_) => sampled += 1,
+                None => {}
+            }
+        }
+        (sampled, expired)
+    }
+
+    fn version(&mut self, key: &[u8]) -> u64 {
+        // an expired key is removed first
+        self.load(&[key]);
+        self.versions.get(key).copied().unwrap_or(0)
+    }
+
+    fn memory_usage(&mut self, key: &[u8]) -> Option<usize> {
+        self.load(&[key]);
+        self.memory.memory_usage(key)
+    }
+
+    /// the bytes the keys take once written, before being compressed,
+    /// estimated from a sample of them once there are too many
+    fn memory_stats(&mut self) -> MemoryStats {
+        let mut stats = MemoryStats::default();
+        let all = self.sample(STATS_SAMPLES, |key, value| {
+            stats.keys += 1;
+            stats.overhead += key.len() + EXPIRY_SIZE;
+            stats.dataset += value.len().saturating_sub(EXPIRY_SIZE);
+            true
+        });
+        if !all {
+            let scale = self.scale(stats.keys);
+            stats = MemoryStats {
+                keys: scale(stats.keys),
+                overhead: scale(stats.overhead),
+                dataset: scale(stats.dataset),
+            };
+        }
+        stats
+    }
+
+    /// estimated from a sample of the keys once there are too many, as
+    /// Redis estimates avg_ttl
+    fn keyspace_stats(&mut self) -> KeyspaceStats {
+        let now = now_millis();
+        let mut stats = KeyspaceStats::default();
+        let mut ttl = 0;
+        let all = self.sample(STATS_SAMPLES, |_, value| {
+            stats.keys += 1;
+            if let Some(time) = expires_at(value) {
+                stats.expires += 1;
+                ttl += time.saturating_sub(now);
+            }
+            true
+        });
+        if stats.expires > 0 {
+            stats.avg_ttl = ttl / stats.expires as u64;
+        }
+        if !all {
+            let scale = self.scale(stats.keys);
+            stats.expires = scale(stats.expires);
+            stats.keys = scale(stats.keys);
+        }
+
+        stats
+    }
+
+    fn snapshot(&mut self) -> Vec<(Vec<u8>, StoredValue)> {
+        let mut snapshot = vec![];
+        let mut failed = None;
+        self.scan(|key, value| match decode(value) {
+            Ok(Some(value)) => snapshot.push((key.to_vec(), value)),
+            Ok(None) => {}
+            Err(error) => {
+                failed.get_or_insert(error);
+            }
+        });
+        if let Some(error) = failed {
+            self.error.get_or_insert(error);
+        }
+        snapshot
+    }
+
+    /// the reads are not tracked, the keys being kept on disk
+    fn access(&mut self, _key: &[u8]) {}
+
+    /// the bytes RocksDB estimates the keys take on disk
+    fn used_memory(&mut self) -> usize {
+        self.write_borrowed();
+        match self
+            .db
+            .property_int_value("rocksdb.estimate-live-data-size")
+        {
+            Ok(size) => size.unwrap_or(0) as usize,
+            Err(error) => {
+                self.error.get_or_insert(into_io_error(error));
+                0
+            }
+        }
+    }
+
+    /// the keys following one picked at random, how they are used not being
+    /// tracked, so that the policies other than volatile-ttl evict one of
+    /// them at random
+    fn eviction_candidate(
+        &mut self,
+        policy: EvictionPolicy,
+        samples: usize,
+    ) -> Option<(Vec<u8>, u64)> {
+        let now = now_millis();
+        let mut entries = vec![];
+        self.sample(samples * EVICTION_LOOKUPS, |key, value| {
+            match expires_at(value) {
+                None if policy.is_volatile() => {}
+                time => entries.push((key.to_vec(), time)),
+            }
+            entries.len() < samples
+        });
+
+        entries
+            .into_iter()
+            .map(|(key, time)| match (policy, time) {
+                (EvictionPolicy::VolatileTtl, Some(time)) => {
+                    (key, u64::MAX - time.saturating_sub(now))
+                }
+                _ => (key, rand::random()),
+            })
+            .max_by_key(|(_, score)| *score)
+    }
+}
+
+/// the compaction filter, which drops the keys that expired
+fn drop_expired(_level: u32, _key: &[u8], value: &[u8]) -> Decision {
+    match expires_at(value) {
+        Some(time) if time <= now_millis() => Decision::Remove,
+        _ => Decision::Keep,
+    }
+}
+
+fn encode(key: &[u8], value: &StoredValue) -> Vec<u8> {
+    let expiry = value
+        .expiry()
+        .map_or(NO_EXPIRY, |expiry| expiry.unix_millis().max(1));
+    let mut bytes = expiry.to_le_bytes().to_vec();
+    let _ = snapshot::write_entry(&mut bytes, key, value);
+    bytes
+}
+
+/// the value, None if it expired
+fn decode(bytes: &[u8]) -> io::Result<Option<StoredValue>> {
+    let mut entry = bytes
+        .get(EXPIRY_SIZE..)
+        .ok_or_else(|| io::Error::from(ErrorKind::UnexpectedEof))?;
+    Ok(snapshot::read_entry(&mut entry)?.1)
+}
+
+/// the unix time in milliseconds the value expires at, None if it doesn't
+fn expires_at(bytes: &[u8]) -> Option<u64> {
+    let expiry = bytes.get(..EXPIRY_SIZE)?.try_into().ok()?;
+    match u64::from_le_bytes(expiry) {
+        NO_EXPIRY => None,
+        time => Some(time),
+    }
+}
+
+fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_millis() as u64)
+}
+
+fn into_io_error(error: ::rocksdb::Error) -> io::Error {
+    io::Error::new(ErrorKind::Other, error)
+}
@@ -0,0 +1,349 @@
+use std::borrow::Cow;
+use std::collections::HashSet;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use super::in_memory::InMemoryStorage;
+use super::models::{
+    bitfield::BitFieldOp,
+    hyperloglog::HyperLogLog,
+    redis_sorted_set::Scores,
+    redis_stream::{StreamFields, StreamId, XAddId},
+    DataType, Expiry, ExpiryCondition, RedisHashMap, RedisSet, RedisSortedSet, RedisStream,
+    StoredValue,
+};
+use super::snapshot;
+use crate::storage::{sibling_path, EvictionPolicy, KeyspaceStats, MemoryStats, Sibling, Storage};
+
+/// a storage holding its keys in memory and writing each key it changes to
+/// a sled database, whole and as a snapshot holds it, the keys being read
+/// back once the database is opened again
+pub struct SledStorage {
+    memory: InMemoryStorage,
+    db: ::sled::Db,
+    // where the database is, none for a temporary one
+    path: Option<PathBuf>,
+    // the stream handed out by xread_mut to be modified in place, written
+    // once the next call is made
+    borrowed: Option<Vec<u8>>,
+    // the first write that failed since the last flush
+    error: Option<::sled::Error>,
+}
+
+impl SledStorage {
+    /// open the sled database at path, created if there is none, the keys
+    /// it holds being read into memory
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        SledStorage::from_db(::sled::open(&path)?, Some(path))
+    }
+
+    /// a sled database of its own, removed once dropped
+    pub fn temporary() -> io::Result<Self> {
+        SledStorage::from_db(::sled::Config::new().temporary(true).open()?, None)
+    }
+
+    fn from_db(db: ::sled::Db, path: Option<PathBuf>) -> io::Result<Self> {
+        let mut memory = InMemoryStorage::new();
+        let mut expired = vec![];
+        for entry in db.iter() {
+            let (_, bytes) = entry?;
+            match snapshot::read_entry(&mut bytes.as_ref())? {
+                (key, Some(value)) => memory.put(&key, value),
+                (key, None) => expired.push(key),
+            }
+        }
+        for key in expired {
+            db.remove(key)?;
+        }
+
+        Ok(SledStorage {
+            memory,
+            db,
+            path,
+            borrowed: None,
+            error: None,
+        })
+    }
+
+    /// write the changes made so far to the disk, or return the error a
+    /// write met since the last flush
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.write_borrowed();
+        if let Some(error) = self.error.take() {
+            return Err(error.into());
+        }
+        self.db.flush()?;
+        Ok(())
+    }
+
+    /// write the key as it is in memory, removed if it is not there anymore
+    fn write_key(&mut self, key: &[u8]) {
+        let written = match self.memory.copy(key) {
+            Some(value) => {
+                let mut bytes = vec![];
+                let _ = snapshot::write_entry(&mut bytes, key, &value);
+                self.db.insert(key, bytes).map(|_| ())
+            }
+            None => self.db.remove(key).map(|_| ()),
+        };
+
+        if let Err(error) = written {
+            self.error.get_or_insert(error);
+        }
+    }
+
+    /// write the stream handed out by xread_mut, modified since
+    fn write_borrowed(&mut self) {
+        if let Some(key) = self.borrowed.take() {
+            self.write_key(&key);
+        }
+    }
+
+    /// make a change to the key in memory then write it
+    fn change<R>(&mut self, key: &[u8], change: impl FnOnce(&mut InMemoryStorage) -> R) -> R {
+        self.write_borrowed();
+        let changed = change(&mut self.memory);
+        self.write_key(key);
+        changed
+    }
+}
+
+impl Default for SledStorage {
+    /// a temporary database, removed once dropped
+    fn default() -> Self {
+        SledStorage::temporary().expect("the temporary sled database can't be created")
+    }
+}
+
+impl Sibling for SledStorage {
+    /// a database of its own next to this one, temporary if this one is
+    fn sibling(&self, index: usize) -> io::Result<Self> {
+        match &self.path {
+            Some(path) => SledStorage::open(sibling_path(path, index)),
+            None => SledStorage::temporary(),
+        }
+    }
+}
+
+impl Drop for SledStorage {
+    fn drop(&mut self) {
+        let _ = self.flush();
+    }
+}
+
+impl Storage for SledStorage {
+    fn write(&mut self, key: &[u8], value: &[u8]) {
+        self.change(key, |memory| memory.write(key, value))
+    }
+
+    fn setbit(&mut self, key: &[u8], offset: usize, bit: bool) -> u8 {
+        self.change(key, |memory| memory.setbit(key, offset, bit))
+    }
+
+    fn bitfield(&mut self, key: &[u8], operations: &[BitFieldOp]) -> Vec<Option<i64>> {
+        self.change(key, |memory| memory.bitfield(key, operations))
+    }
+
+    fn pfadd(&mut self, key: &[u8], elements: &[Vec<u8>]) -> Option<u32> {
+        self.change(key, |memory| memory.pfadd(key, elements))
+    }
+
+    fn pfmerge(&mut self, key: &[u8], hyperloglog: &HyperLogLog) -> Option<()> {
+        self.change(key, |memory| memory.pfmerge(key, hyperloglog))
+    }
+
+    fn hwrite(&mut self, key: &[u8], field: &[u8], value: &[u8]) -> u32 {
+        self.change(key, |memory| memory.hwrite(key, field, value))
+    }
+
+    fn sadd(&mut self, key: &[u8], member: &[u8]) -> u32 {
+        self.change(key, |memory| memory.sadd(key, member))
+    }
+
+    fn swrite(&mut self, key: &[u8], members: HashSet<Vec<u8>>) {
+        self.change(key, |memory| memory.swrite(key, members))
+    }
+
+    fn zadd(&mut self, key: &[u8], score: f64, member: &[u8]) -> u32 {
+        self.change(key, |memory| memory.zadd(key, score, member))
+    }
+
+    fn xadd(&mut self, key: &[u8], id: XAddId, fields: StreamFields) -> Option<StreamId> {
+        self.change(key, |memory| memory.xadd(key, id, fields))
+    }
+
+    fn expire(&mut self, key: &[u8], expiry: Expiry) -> u32 {
+        self.change(key, |memory| memory.expire(key, expiry))
+    }
+
+    fn hexpire(
+        &mut self,
+        key: &[u8],
+        field: &[u8],
+        expiry: Expiry,
+        condition: Option<ExpiryCondition>,
+    ) -> i64 {
+        self.change(key, |memory| memory.hexpire(key, field, expiry, condition))
+    }
+
+    fn hpttl(&mut self, key: &[u8], field: &[u8]) -> i64 {
+        self.write_borrowed();
+        self.memory.hpttl(key, field)
+    }
+
+    fn hpersist(&mut self, key: &[u8], field: &[u8]) -> i64 {
+        self.change(key, |memory| memory.hpersist(key, field))
+    }
+
+    fn read(&self, key: &[u8]) -> Option<Cow<'_, [u8]>> {
+        self.memory.read(key)
+    }
+
+    fn hread(&mut self, key: &[u8], field: &[u8]) -> Option<&[u8]> {
+        self.write_borrowed();
+        self.memory.hread(key, field)
+    }
+
+    fn hread_all(&mut self, key: &[u8]) -> Option<&RedisHashMap> {
+        self.write_borrowed();
+        self.memory.hread_all(key)
+    }
+
+    fn sread(&mut self, key: &[u8]) -> Option<&RedisSet> {
+        self.write_borrowed();
+        self.memory.sread(key)
+    }
+
+    fn sintercard(&mut self, keys: &[Vec<u8>], limit: usize) -> usize {
+        self.write_borrowed();
+        self.memory.sintercard(keys, limit)
+    }
+
+    fn zread(&mut self, key: &[u8]) -> Option<&RedisSortedSet> {
+        self.write_borrowed();
+        self.memory.zread(key)
+    }
+
+    fn zwrite(&mut self, key: &[u8], members: Scores) {
+        self.change(key, |memory| memory.zwrite(key, members))
+    }
+
+    fn xread(&mut self, key: &[u8]) -> Option<&RedisStream> {
+        self.write_borrowed();
+        self.memory.xread(key)
+    }
+
+    fn xread_mut(&mut self, key: &[u8]) -> Option<&mut RedisStream> {
+        self.write_borrowed();
+        self.borrowed = Some(key.to_vec());
+        self.memory.xread_mut(key)
+    }
+
+    fn xcreate(&mut self, key: &[u8]) {
+        self.change(key, |memory| memory.xcreate(key))
+    }
+
+    fn remove(&mut self, key: &[u8]) -> u32 {
+        self.change(key, |memory| memory.remove(key))
+    }
+
+    fn take(&mut self, key: &[u8]) -> Option<StoredValue> {
+        self.change(key, |memory| memory.take(key))
+    }
+
+    fn copy(&mut self, key: &[u8]) -> Option<StoredValue> {
+        self.write_borrowed();
+        self.memory.copy(key)
+    }
+
+    fn put(&mut self, key: &[u8], value: StoredValue) {
+        self.change(key, |memory| memory.put(key, value))
+    }
+
+    fn srem(&mut self, key: &[u8], member: &[u8]) -> u32 {
+        self.change(key, |memory| memory.srem(key, member))
+    }
+
+    fn spop(&mut self, key: &[u8], count: usize) -> Vec<Vec<u8>> {
+        self.change(key, |memory| memory.spop(key, count))
+    }
+
+    fn zrem(&mut self, key: &[u8], member: &[u8]) -> u32 {
+        self.change(key, |memory| memory.zrem(key, member))
+    }
+
+    fn zpop(&mut self, key: &[u8], count: usize, max: bool) -> Vec<(Vec<u8>, f64)> {
+        self.change(key, |memory| memory.zpop(key, count, max))
+    }
+
+    fn contains(&self, key: &[u8]) -> bool {
+        self.memory.contains(key)
+    }
+
+    fn type_of(&mut self, key: &[u8]) -> Option<DataType> {
+        self.write_borrowed();
+        self.memory.type_of(key)
+    }
+
+    fn take_expired(&mut self) -> Vec<Vec<u8>> {
+        self.write_borrowed();
+        let expired = self.memory.take_expired();
+        for key in &expired {
+            self.write_key(key);
+        }
+        expired
+    }
+
+    fn lapsed(&self) -> bool {
+        self.memory.lapsed()
+    }
+
+    fn sample_expired(&mut self, count: usize) -> (usize, usize) {
+        self.write_borrowed();
+        self.memory.sample_expired(count)
+    }
+
+    fn version(&mut self, key: &[u8]) -> u64 {
+        self.memory.version(key)
+    }
+
+    fn memory_usage(&mut self, key: &[u8]) -> Option<usize> {
+        self.write_borrowed();
+        self.memory.memory_usage(key)
+    }
+
+    fn memory_stats(&mut self) -> MemoryStats {
+        self.write_borrowed();
+        self.memory.memory_stats()
+    }
+
+    fn keyspace_stats(&mut self) -> KeyspaceStats {
+        self.write_borrowed();
+        self.memory.keyspace_stats()
+    }
+
+    fn snapshot(&mut self) -> Vec<(Vec<u8>, StoredValue)> {
+        self.write_borrowed();
+        self.memory.snapshot()
+    }
+
+    fn access(&mut self, key: &[u8]) {
+        self.write_borrowed();
+        self.memory.access(key)
+    }
+
+    fn used_memory(&mut self) -> usize {
+        self.write_borrowed();
+        self.memory.used_memory()
+    }
+
+    fn eviction_candidate(
+        &mut self,
+        policy: EvictionPolicy,
+        samples: usize,
+    ) -> Option<(Vec<u8>, u64)> {
+        self.write_borrowed();
+        self.memory.eviction_candidate(policy, samples)
+    }
+}
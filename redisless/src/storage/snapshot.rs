@@ -0,0 +1,415 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::io::{self, ErrorKind, Read, Write};
+
+use super::models::redis_stream::{Consumer, ConsumerGroup, PendingEntry, StreamId};
+use super::models::{
+    Expiry, RedisHashMap, RedisSet, RedisSortedSet, RedisStream, RedisValue, StoredValue,
+};
+
+// what a snapshot starts with, followed by the version of its format
+pub const MAGIC: &[u8] = b"REDISLESS";
+const VERSION: u8 = 1;
+
+// what each part of a snapshot starts with
+const OPCODE_SELECTDB: u8 = 0xfe;
+const OPCODE_EOF: u8 = 0xff;
+// what each change appended after a snapshot starts with
+const OPCODE_PUT: u8 = 0xfd;
+const OPCODE_REMOVE: u8 = 0xfc;
+const OPCODE_SWAPDB: u8 = 0xfb;
+
+const TYPE_STRING: u8 = 0;
+const TYPE_SET: u8 = 1;
+const TYPE_SORTED_SET: u8 = 2;
+const TYPE_HASH: u8 = 3;
+const TYPE_STREAM: u8 = 4;
+
+// the expiry of a key or of a field that has none
+const NO_EXPIRY: u64 = 0;
+
+/// the keys of each database, along with their value, indexed by database
+pub type Databases = Vec<Vec<(Vec<u8>, StoredValue)>>;
+
+/// a change made to a database, as appended to a snapshot so that the
+/// dataset can be rebuilt without being written whole every time
+#[derive(Debug, PartialEq)]
+pub enum Change {
+    // the value a key was left with
+    Put(usize, Vec<u8>, StoredValue),
+    Remove(usize, Vec<u8>),
+    SwapDb(usize, usize),
+}
+
+/// write the keys of every database, the expiries being kept as unix times
+/// so that they still hold once read by another process
+pub fn write<W: Write>(
+    writer: &mut W,
+    databases: &[Vec<(Vec<u8>, StoredValue)>],
+) -> io::Result<()> {
+    writer.write_all(MAGIC)?;
+    writer.write_all(&[VERSION])?;
+
+    for (db, keys) in databases.iter().enumerate() {
+        if keys.is_empty() {
+            continue;
+        }
+
+        writer.write_all(&[OPCODE_SELECTDB])?;
+        write_u64(writer, db as u64)?;
+        write_u64(writer, keys.len() as u64)?;
+        for (key, value) in keys {
+            write_entry(writer, key, value)?;
+        }
+    }
+
+    writer.write_all(&[OPCODE_EOF])
+}
+
+/// read the keys of every database of a snapshot, the ones that expired
+/// since it was written being left out
+pub fn read<R: Read>(reader: &mut R) -> io::Result<Databases> {
+    let mut magic = [0; MAGIC.len()];
+    reader.read_exact(&mut magic)?;
+    if magic != MAGIC {
+        return Err(invalid("not a RedisLess snapshot"));
+    }
+    if read_u8(reader)? != VERSION {
+        return Err(invalid("unknown snapshot version"));
+    }
+
+    let mut databases: Databases = vec![];
+    loop {
+        match read_u8(reader)? {
+            OPCODE_SELECTDB => {
+                let db = read_u64(reader)? as usize;
+                let count = read_u64(reader)?;
+                if databases.len() <= db {
+                    databases.resize_with(db + 1, Vec::new);
+                }
+                for _ in 0..count {
+                    if let (key, Some(value)) = read_entry(reader)? {
+                        databases[db].push((key, value));
+                    }
+                }
+            }
+            OPCODE_EOF => return Ok(databases),
+            _ => return Err(invalid("unknown opcode")),
+        }
+    }
+}
+
+/// write a change, to be appended to a snapshot
+pub fn write_change<W: Write>(writer: &mut W, change: &Change) -> io::Result<()> {
+    match change {
+        Change::Put(db, key, value) => {
+            writer.write_all(&[OPCODE_PUT])?;
+            write_u64(writer, *db as u64)?;
+            write_entry(writer, key, value)
+        }
+        Change::Remove(db, key) => {
+            writer.write_all(&[OPCODE_REMOVE])?;
+            write_u64(writer, *db as u64)?;
+            write_bytes(writer, key)
+        }
+        Change::SwapDb(first, second) => {
+            writer.write_all(&[OPCODE_SWAPDB])?;
+            write_u64(writer, *first as u64)?;
+            write_u64(writer, *second as u64)
+        }
+    }
+}
+
+/// read the changes appended to a snapshot, up to the end of the reader,
+/// a key that expired since being put being removed, and a change cut
+/// short by the end, as left by a crash while it was written, being left
+/// out
+pub fn read_changes<R: Read>(reader: &mut R) -> io::Result<Vec<Change>> {
+    let mut changes = vec![];
+    loop {
+        match read_change(reader) {
+            Ok(change) => changes.push(change),
+            Err(error) if error.kind() == ErrorKind::UnexpectedEof => return Ok(changes),
+            Err(error) => return Err(error),
+        }
+    }
+}
+
+/// read a single change, as the changes fed to a replica are, a key that
+/// expired since being put being removed
+pub fn read_change<R: Read>(reader: &mut R) -> io::Result<Change> {
+    match read_u8(reader)? {
+        OPCODE_PUT => {
+            let db = read_u64(reader)? as usize;
+            Ok(match read_entry(reader)? {
+                (key, Some(value)) => Change::Put(db, key, value),
+                (key, None) => Change::Remove(db, key),
+            })
+        }
+        OPCODE_REMOVE => Ok(Change::Remove(
+            read_u64(reader)? as usize,
+            read_bytes(reader)?,
+        )),
+        OPCODE_SWAPDB => Ok(Change::SwapDb(
+            read_u64(reader)? as usize,
+            read_u64(reader)? as usize,
+        )),
+        _ => Err(invalid("unknown opcode")),
+    }
+}
+
+fn invalid(reason: &str) -> io::Error {
+    io::Error::new(
+        ErrorKind::InvalidData,
+        format!("invalid snapshot: {}", reason),
+    )
+}
+
+/// write a key along with its value, as a snapshot holds them
+pub fn write_entry<W: Write>(writer: &mut W, key: &[u8], value: &StoredValue) -> io::Result<()> {
+    let (data_type, expiry) = match value {
+        StoredValue::String(value) => (TYPE_STRING, value.expiry),
+        StoredValue::Set(set) => (TYPE_SET, set.expiry),
+        StoredValue::SortedSet(sorted_set) => (TYPE_SORTED_SET, sorted_set.expiry),
+        StoredValue::Hash(hash) => (TYPE_HASH, hash.expiry),
+        StoredValue::Stream(stream) => (TYPE_STREAM, stream.expiry),
+    };
+    writer.write_all(&[data_type])?;
+    write_bytes(writer, key)?;
+    write_expiry(writer, expiry.as_ref())?;
+
+    match value {
+        StoredValue::String(value) => write_bytes(writer, &value.data),
+        StoredValue::Set(set) => {
+            write_u64(writer, set.data.len() as u64)?;
+            for member in &set.data {
+                write_bytes(writer, member)?;
+            }
+            Ok(())
+        }
+        StoredValue::SortedSet(sorted_set) => {
+            write_u64(writer, sorted_set.scores.len() as u64)?;
+            for (member, score) in &sorted_set.scores {
+                write_bytes(writer, member)?;
+                write_u64(writer, score.to_bits())?;
+            }
+            Ok(())
+        }
+        StoredValue::Hash(hash) => {
+            write_u64(writer, hash.data.len() as u64)?;
+            for (field, value) in &hash.data {
+                write_bytes(writer, field)?;
+                write_bytes(writer, value)?;
+                write_expiry(writer, hash.field_expiry.get(field))?;
+            }
+            Ok(())
+        }
+        StoredValue::Stream(stream) => write_stream(writer, stream),
+    }
+}
+
+/// the key and its value, None if it expired
+pub fn read_entry<R: Read>(reader: &mut R) -> io::Result<(Vec<u8>, Option<StoredValue>)> {
+    let data_type = read_u8(reader)?;
+    let key = read_bytes(reader)?;
+    let expiry = read_expiry(reader)?;
+
+    let value = match data_type {
+        TYPE_STRING => StoredValue::String(RedisValue::new(read_bytes(reader)?, expiry)),
+        TYPE_SET => {
+            let count = read_u64(reader)?;
+            let mut members = HashSet::new();
+            for _ in 0..count {
+                members.insert(read_bytes(reader)?);
+            }
+            StoredValue::Set(RedisSet::new(members, expiry))
+        }
+        TYPE_SORTED_SET => {
+            let count = read_u64(reader)?;
+            let mut scores = HashMap::new();
+            for _ in 0..count {
+                let member = read_bytes(reader)?;
+                scores.insert(member, f64::from_bits(read_u64(reader)?));
+            }
+            StoredValue::SortedSet(RedisSortedSet::from_scores(scores, expiry))
+        }
+        TYPE_HASH => {
+            let count = read_u64(reader)?;
+            let mut hash = RedisHashMap::new(HashMap::new(), expiry);
+            for _ in 0..count {
+                let field = read_bytes(reader)?;
+                let value = read_bytes(reader)?;
+                match read_expiry(reader)? {
+                    Some(expiry) if expiry.is_expired() => continue,
+                    Some(expiry) => {
+                        hash.field_expiry.insert(field.clone(), expiry);
+                    }
+                    None => {}
+                }
+                hash.data.insert(field, value);
+            }
+            // a hash whose fields all expired does not exist
+            if hash.data.is_empty() {
+                return Ok((key, None));
+            }
+            StoredValue::Hash(hash)
+        }
+        TYPE_STREAM => StoredValue::Stream(read_stream(reader, expiry)?),
+        _ => return Err(invalid("unknown type")),
+    };
+
+    match expiry {
+        Some(expiry) if expiry.is_expired() => Ok((key, None)),
+        _ => Ok((key, Some(value))),
+    }
+}
+
+fn write_stream<W: Write>(writer: &mut W, stream: &RedisStream) -> io::Result<()> {
+    write_stream_id(writer, stream.last_id)?;
+    write_stream_id(writer, stream.max_deleted_id)?;
+    write_u64(writer, stream.entries_added)?;
+
+    write_u64(writer, stream.entries.len() as u64)?;
+    for (id, fields) in &stream.entries {
+        write_stream_id(writer, *id)?;
+        write_u64(writer, fields.len() as u64)?;
+        for (field, value) in fields {
+            write_bytes(writer, field)?;
+            write_bytes(writer, value)?;
+        }
+    }
+
+    write_u64(writer, stream.groups.len() as u64)?;
+    for (name, group) in &stream.groups {
+        write_bytes(writer, name)?;
+        write_stream_id(writer, group.last_delivered_id)?;
+        write_u64(writer, group.pending.len() as u64)?;
+        for (id, entry) in &group.pending {
+            write_stream_id(writer, *id)?;
+            write_bytes(writer, &entry.consumer)?;
+            write_u64(writer, entry.delivery_time)?;
+            write_u64(writer, entry.delivery_count)?;
+        }
+        // the entries pending for each consumer are in the pending entries
+        // list of the group
+        write_u64(writer, group.consumers.len() as u64)?;
+        for (name, consumer) in &group.consumers {
+            write_bytes(writer, name)?;
+            write_u64(writer, consumer.seen_time)?;
+        }
+    }
+
+    Ok(())
+}
+
+fn read_stream<R: Read>(reader: &mut R, expiry: Option<Expiry>) -> io::Result<RedisStream> {
+    let mut stream = RedisStream::new(expiry);
+    stream.last_id = read_stream_id(reader)?;
+    stream.max_deleted_id = read_stream_id(reader)?;
+    stream.entries_added = read_u64(reader)?;
+
+    let count = read_u64(reader)?;
+    let mut entries = BTreeMap::new();
+    for _ in 0..count {
+        let id = read_stream_id(reader)?;
+        let fields = read_u64(reader)?;
+        let fields = (0..fields)
+            .map(|_| Ok((read_bytes(reader)?, read_bytes(reader)?)))
+            .collect::<io::Result<Vec<_>>>()?;
+        entries.insert(id, fields);
+    }
+    stream.entries = entries;
+
+    let count = read_u64(reader)?;
+    for _ in 0..count {
+        let name = read_bytes(reader)?;
+        let mut group = ConsumerGroup::new(read_stream_id(reader)?);
+        let pending = read_u64(reader)?;
+        for _ in 0..pending {
+            let id = read_stream_id(reader)?;
+            let entry = PendingEntry {
+                consumer: read_bytes(reader)?,
+                delivery_time: read_u64(reader)?,
+                delivery_count: read_u64(reader)?,
+            };
+            group.pending.insert(id, entry);
+        }
+        let consumers = read_u64(reader)?;
+        for _ in 0..consumers {
+            let name = read_bytes(reader)?;
+            let consumer = Consumer {
+                pending: BTreeSet::new(),
+                seen_time: read_u64(reader)?,
+            };
+            group.consumers.insert(name, consumer);
+        }
+        for (id, entry) in &group.pending {
+            if let Some(consumer) = group.consumers.get_mut(&entry.consumer) {
+                consumer.pending.insert(*id);
+            }
+        }
+        stream.groups.insert(name, group);
+    }
+
+    Ok(stream)
+}
+
+fn write_u64<W: Write>(writer: &mut W, value: u64) -> io::Result<()> {
+    writer.write_all(&value.to_le_bytes())
+}
+
+fn write_bytes<W: Write>(writer: &mut W, bytes: &[u8]) -> io::Result<()> {
+    write_u64(writer, bytes.len() as u64)?;
+    writer.write_all(bytes)
+}
+
+fn write_expiry<W: Write>(writer: &mut W, expiry: Option<&Expiry>) -> io::Result<()> {
+    match expiry {
+        Some(expiry) => write_u64(writer, expiry.unix_millis().max(1)),
+        None => write_u64(writer, NO_EXPIRY),
+    }
+}
+
+fn write_stream_id<W: Write>(writer: &mut W, id: StreamId) -> io::Result<()> {
+    write_u64(writer, id.ms)?;
+    write_u64(writer, id.seq)
+}
+
+fn read_u8<R: Read>(reader: &mut R) -> io::Result<u8> {
+    let mut byte = [0; 1];
+    reader.read_exact(&mut byte)?;
+    Ok(byte[0])
+}
+
+fn read_u64<R: Read>(reader: &mut R) -> io::Result<u64> {
+    let mut bytes = [0; 8];
+    reader.read_exact(&mut bytes)?;
+    Ok(u64::from_le_bytes(bytes))
+}
+
+fn read_bytes<R: Read>(reader: &mut R) -> io::Result<Vec<u8>> {
+    let length = read_u64(reader)?;
+    let mut bytes = vec![];
+    // the length is not trusted to allocate, a truncated file failing
+    // once its end is reached
+    reader.take(length).read_to_end(&mut bytes)?;
+    match bytes.len() as u64 == length {
+        true => Ok(bytes),
+        false => Err(io::Error::from(ErrorKind::UnexpectedEof)),
+    }
+}
+
+fn read_expiry<R: Read>(reader: &mut R) -> io::Result<Option<Expiry>> {
+    match read_u64(reader)? {
+        NO_EXPIRY => Ok(None),
+        time => Expiry::new_from_unix_millis(time)
+            .map(Some)
+            .map_err(|_| invalid("expiry out of range")),
+    }
+}
+
+fn read_stream_id<R: Read>(reader: &mut R) -> io::Result<StreamId> {
+    Ok(StreamId {
+        ms: read_u64(reader)?,
+        seq: read_u64(reader)?,
+    })
+}
@@ -1,13 +1,20 @@
 use std::{thread::sleep, time::Duration};
 
-use crate::storage::Storage;
-use crate::storage::{in_memory::InMemoryStorage, models::Expiry};
+use crate::storage::{
+    in_memory::InMemoryStorage,
+    models::{redis_stream::XAddId, DataType, Expiry},
+};
+use crate::storage::{
+    rdb,
+    snapshot::{self, Change},
+    EvictionPolicy, KeyspaceStats, MemoryStats, Storage,
+};
 
 #[test]
 fn test_in_memory_storage() {
     let mut mem = InMemoryStorage::new();
     mem.write(b"key", b"xxx");
-    assert_eq!(mem.read(b"key"), Some(&b"xxx"[..]));
+    assert_eq!(mem.read(b"key").as_deref(), Some(&b"xxx"[..]));
     assert_eq!(mem.remove(b"key"), 1);
     assert_eq!(mem.remove(b"key"), 0);
     assert_eq!(mem.read(b"does not exist"), None);
@@ -22,7 +29,7 @@ fn test_expire() {
     if let Ok(e) = Expiry::new_from_secs(duration) {
         let ret_val = mem.expire(b"key", e);
         assert_eq!(ret_val, 1);
-        assert_eq!(mem.read(b"key"), Some(&b"xxx"[..]));
+        assert_eq!(mem.read(b"key").as_deref(), Some(&b"xxx"[..]));
         sleep(Duration::from_secs(duration));
         assert_eq!(mem.read(b"key"), None);
     }
@@ -32,7 +39,7 @@ fn test_expire() {
     if let Ok(e) = Expiry::new_from_millis(duration) {
         let ret_val = mem.expire(b"key", e);
         assert_eq!(ret_val, 1);
-        assert_eq!(mem.read(b"key"), Some(&b"xxx"[..]));
+        assert_eq!(mem.read(b"key").as_deref(), Some(&b"xxx"[..]));
         sleep(Duration::from_millis(duration));
         assert_eq!(mem.read(b"key"), None);
     }
@@ -47,3 +54,1108 @@ fn contains() {
     let x = mem.contains(b"key2");
     assert_eq!(x, false);
 }
+
+#[test]
+fn expired_reads() {
+    let mut mem = InMemoryStorage::new();
+    mem.write(b"string", b"value");
+    mem.sadd(b"set", b"member");
+    mem.hwrite(b"hash", b"field", b"value");
+    for key in &[&b"string"[..], b"set", b"hash"] {
+        mem.expire(key, Expiry::new_from_millis(1).unwrap());
+    }
+    sleep(Duration::from_millis(2));
+
+    // whatever looks the keys up, none of them is there anymore
+    assert!(!mem.contains(b"string"));
+    assert_eq!(mem.type_of(b"set"), None);
+    assert_eq!(mem.expire(b"hash", Expiry::new_from_secs(100).unwrap()), 0);
+    assert_eq!(mem.take_expired().len(), 3);
+    assert_eq!(mem.keyspace_stats().keys, 0);
+}
+
+#[test]
+fn shared_reads() {
+    let mut mem = InMemoryStorage::new();
+    mem.write(b"string", b"value");
+    mem.write(b"expired", b"value");
+    mem.expire(b"expired", Expiry::new_from_millis(1).unwrap());
+    sleep(Duration::from_millis(2));
+    assert!(!mem.lapsed());
+
+    // readers share the storage, the key they find expired being removed
+    // once the storage is changed
+    std::thread::scope(|scope| {
+        for _ in 0..4 {
+            scope.spawn(|| {
+                assert_eq!(mem.read(b"string").as_deref(), Some(&b"value"[..]));
+                assert_eq!(mem.read(b"expired"), None);
+                assert!(!mem.contains(b"expired"));
+            });
+        }
+    });
+    assert!(mem.lapsed());
+    // told once, whether it is looked up again before being settled or not
+    assert_eq!(mem.type_of(b"expired"), None);
+    assert_eq!(mem.take_expired(), vec![b"expired".to_vec()]);
+    assert!(!mem.lapsed());
+    assert_eq!(mem.take_expired(), Vec::<Vec<u8>>::new());
+    assert_eq!(mem.keyspace_stats().keys, 1);
+}
+
+#[test]
+fn hash() {
+    let mut mem = InMemoryStorage::new();
+    assert_eq!(mem.hwrite(b"hash", b"field", b"value"), 1);
+    assert_eq!(mem.hwrite(b"hash", b"field", b"other value"), 0);
+    assert_eq!(mem.hread(b"hash", b"field"), Some(&b"other value"[..]));
+    assert_eq!(mem.hread(b"hash", b"does not exist"), None);
+    assert_eq!(mem.type_of(b"hash"), Some(DataType::Hash));
+
+    // a string write replaces the hash
+    mem.write(b"hash", b"value");
+    assert_eq!(mem.hread(b"hash", b"field"), None);
+    assert_eq!(mem.type_of(b"hash"), Some(DataType::String));
+    assert_eq!(mem.remove(b"hash"), 1);
+    assert_eq!(mem.type_of(b"hash"), None);
+}
+
+#[test]
+fn set() {
+    let mut mem = InMemoryStorage::new();
+    assert_eq!(mem.sadd(b"set", b"a"), 1);
+    assert_eq!(mem.sadd(b"set", b"a"), 0);
+    assert_eq!(mem.sadd(b"set", b"b"), 1);
+    assert_eq!(mem.sread(b"set").map(|set| set.data.len()), Some(2));
+    assert_eq!(mem.type_of(b"set"), Some(DataType::Set));
+
+    assert_eq!(mem.srem(b"set", b"a"), 1);
+    assert_eq!(mem.srem(b"set", b"a"), 0);
+    assert_eq!(mem.srem(b"set", b"b"), 1);
+    assert_eq!(mem.sread(b"set"), None);
+    assert_eq!(mem.type_of(b"set"), None);
+}
+
+#[test]
+fn sorted_set() {
+    let mut mem = InMemoryStorage::new();
+    assert_eq!(mem.zadd(b"zset", 2.0, b"b"), 1);
+    assert_eq!(mem.zadd(b"zset", 1.0, b"c"), 1);
+    assert_eq!(mem.zadd(b"zset", 1.0, b"a"), 1);
+    assert_eq!(mem.zadd(b"zset", 3.0, b"c"), 0);
+    assert_eq!(mem.type_of(b"zset"), Some(DataType::SortedSet));
+
+    // ordered by score, then lexicographically
+    let members = mem
+        .zread(b"zset")
+        .unwrap()
+        .iter()
+        .map(|(member, score)| (member.to_vec(), score))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        members,
+        vec![
+            (b"a".to_vec(), 1.0),
+            (b"b".to_vec(), 2.0),
+            (b"c".to_vec(), 3.0)
+        ]
+    );
+
+    assert_eq!(mem.zrem(b"zset", b"a"), 1);
+    assert_eq!(mem.zrem(b"zset", b"a"), 0);
+    assert_eq!(mem.zread(b"zset").unwrap().score(b"c"), Some(3.0));
+}
+
+#[test]
+fn format_score() {
+    use crate::storage::models::redis_sorted_set::format_score;
+
+    assert_eq!(format_score(1.0), "1");
+    assert_eq!(format_score(-2.5), "-2.5");
+    assert_eq!(format_score(0.1 + 0.2), "0.30000000000000004");
+    assert_eq!(format_score(0.00001), "0.00001");
+    assert_eq!(format_score(1e-7), "1e-7");
+    assert_eq!(format_score(12345678.0), "12345678");
+    assert_eq!(format_score(1e20), "1e+20");
+    assert_eq!(format_score(-1.5e300), "-1.5e+300");
+    assert_eq!(format_score(f64::INFINITY), "inf");
+    assert_eq!(format_score(f64::NEG_INFINITY), "-inf");
+}
+
+#[test]
+fn sorted_set_combinations() {
+    use crate::storage::models::redis_sorted_set::{
+        difference, intersection, union, Aggregate, Scores,
+    };
+
+    let a: Scores = vec![(b"x".to_vec(), 1.0), (b"y".to_vec(), 2.0)]
+        .into_iter()
+        .collect();
+    let b: Scores = vec![(b"y".to_vec(), 3.0), (b"z".to_vec(), f64::INFINITY)]
+        .into_iter()
+        .collect();
+    let sorted_sets = vec![a, b];
+
+    let members = union(&sorted_sets, &[1.0, 2.0], Aggregate::Sum);
+    assert_eq!(members.len(), 3);
+    assert_eq!(members[&b"y".to_vec()], 8.0);
+    assert_eq!(members[&b"z".to_vec()], f64::INFINITY);
+
+    // 0 * inf gives 0
+    let members = union(&sorted_sets, &[1.0, 0.0], Aggregate::Max);
+    assert_eq!(members[&b"z".to_vec()], 0.0);
+
+    let members = intersection(&sorted_sets, &[1.0, 1.0], Aggregate::Min);
+    assert_eq!(members.len(), 1);
+    assert_eq!(members[&b"y".to_vec()], 2.0);
+
+    let members = difference(&sorted_sets);
+    assert_eq!(members.len(), 1);
+    assert_eq!(members[&b"x".to_vec()], 1.0);
+}
+
+#[test]
+fn bit_count_and_position() {
+    use crate::storage::models::redis_value::{bit_count, bit_position, BitUnit};
+
+    // "foobar"
+    let data = b"foobar";
+    assert_eq!(bit_count(data, None, BitUnit::Byte), 26);
+    assert_eq!(bit_count(data, Some((0, 0)), BitUnit::Byte), 4);
+    assert_eq!(bit_count(data, Some((1, 1)), BitUnit::Byte), 6);
+    assert_eq!(bit_count(data, Some((1, 1)), BitUnit::Bit), 1);
+    assert_eq!(bit_count(data, Some((5, 30)), BitUnit::Bit), 17);
+    assert_eq!(bit_count(data, Some((-2, -1)), BitUnit::Byte), 7);
+    assert_eq!(bit_count(data, Some((3, 1)), BitUnit::Byte), 0);
+
+    let data = [0xff, 0xf0, 0x00];
+    assert_eq!(bit_position(&data, false, None, None, BitUnit::Byte), 12);
+    assert_eq!(bit_position(&data, true, Some(2), None, BitUnit::Byte), -1);
+    assert_eq!(
+        bit_position(&data, true, Some(2), Some(-1), BitUnit::Bit),
+        2
+    );
+    assert_eq!(
+        bit_position(&data, true, Some(7), Some(15), BitUnit::Bit),
+        7
+    );
+
+    // padded with zeros unless an end is given
+    let data = [0xff, 0xff];
+    assert_eq!(bit_position(&data, false, None, None, BitUnit::Byte), 16);
+    assert_eq!(
+        bit_position(&data, false, Some(0), Some(-1), BitUnit::Byte),
+        -1
+    );
+}
+
+#[test]
+fn bitfield() {
+    use crate::storage::models::bitfield::{BitFieldOp, BitFieldType, Overflow};
+
+    let i8_type = BitFieldType {
+        signed: true,
+        bits: 8,
+    };
+    let u2_type = BitFieldType {
+        signed: false,
+        bits: 2,
+    };
+
+    let mut data = vec![];
+    assert_eq!(BitFieldOp::Get(i8_type, 0).apply(&mut data), Some(0));
+    assert_eq!(
+        BitFieldOp::Set(i8_type, 4, -1, Overflow::Wrap).apply(&mut data),
+        Some(0)
+    );
+    assert_eq!(data, vec![0x0f, 0xf0]);
+    assert_eq!(BitFieldOp::Get(i8_type, 4).apply(&mut data), Some(-1));
+    assert_eq!(BitFieldOp::Get(u2_type, 4).apply(&mut data), Some(3));
+
+    assert_eq!(
+        BitFieldOp::IncrBy(u2_type, 0, 5, Overflow::Wrap).apply(&mut data),
+        Some(1)
+    );
+    assert_eq!(
+        BitFieldOp::IncrBy(u2_type, 0, 5, Overflow::Sat).apply(&mut data),
+        Some(3)
+    );
+    assert_eq!(
+        BitFieldOp::IncrBy(u2_type, 0, 1, Overflow::Fail).apply(&mut data),
+        None
+    );
+    assert_eq!(
+        BitFieldOp::IncrBy(i8_type, 8, -200, Overflow::Sat).apply(&mut data),
+        Some(-128)
+    );
+    assert_eq!(
+        BitFieldOp::Set(i8_type, 8, 200, Overflow::Wrap).apply(&mut data),
+        Some(-128)
+    );
+    assert_eq!(BitFieldOp::Get(i8_type, 8).apply(&mut data), Some(-56));
+}
+
+#[test]
+fn hyperloglog() {
+    use crate::storage::models::hyperloglog::HyperLogLog;
+
+    let mut hyperloglog = HyperLogLog::new();
+    assert_eq!(hyperloglog.count(), 0);
+    assert_eq!(hyperloglog.add(b"a"), true);
+    assert_eq!(hyperloglog.add(b"a"), false);
+
+    let mut other = HyperLogLog::new();
+    (0..10000).for_each(|i| {
+        other.add(format!("element:{}", i).as_bytes());
+    });
+    let count = other.count() as f64;
+    assert!((count - 10000.0).abs() < 10000.0 * 0.02);
+
+    hyperloglog.merge(&other);
+    assert!(hyperloglog.count() >= other.count());
+
+    let bytes = hyperloglog.to_bytes();
+    assert_eq!(bytes.len(), 12304);
+    assert_eq!(&bytes[..4], b"HYLL");
+    assert_eq!(HyperLogLog::from_bytes(&bytes), Some(hyperloglog));
+
+    // an empty sparse HyperLogLog as created by Redis
+    let mut sparse = b"HYLL\x01\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00".to_vec();
+    sparse.extend_from_slice(&[0x7f, 0xff]);
+    assert_eq!(HyperLogLog::from_bytes(&sparse), Some(HyperLogLog::new()));
+
+    assert_eq!(HyperLogLog::from_bytes(b"HYLL"), None);
+    assert_eq!(HyperLogLog::from_bytes(b"not a hyperloglog at all"), None);
+}
+
+#[test]
+fn geo() {
+    use crate::storage::models::geo::{self, DistanceUnit};
+
+    let score = geo::encode(13.361389, 38.115556);
+    assert_eq!(score, 3479099956230698.0);
+    let (longitude, latitude) = geo::decode(score);
+    assert!((longitude - 13.361389).abs() < 0.00001);
+    assert!((latitude - 38.115556).abs() < 0.00001);
+
+    assert_eq!(geo::is_valid(180.0, 85.05), true);
+    assert_eq!(geo::is_valid(180.1, 0.0), false);
+    assert_eq!(geo::is_valid(0.0, -85.06), false);
+
+    let meters = geo::distance((13.361389, 38.115556), (15.087269, 37.502669));
+    assert!((meters - 166274.15).abs() < 1.0);
+    assert!((DistanceUnit::Kilometers.from_meters(meters) - 166.274).abs() < 0.001);
+}
+
+#[test]
+fn stream() {
+    use crate::storage::models::redis_stream::{RedisStream, StreamId, XAddId};
+
+    assert_eq!(StreamId::new(1, u64::MAX).next(), Some(StreamId::new(2, 0)));
+    assert_eq!(
+        StreamId::new(2, 0).previous(),
+        Some(StreamId::new(1, u64::MAX))
+    );
+    assert_eq!(StreamId::MAX.next(), None);
+    assert_eq!(StreamId::MIN.previous(), None);
+    assert_eq!(StreamId::new(5, 3).to_string(), "5-3");
+
+    let mut stream = RedisStream::new(None);
+    let fields = vec![(b"field".to_vec(), b"value".to_vec())];
+    assert_eq!(
+        stream.add(XAddId::AutoSeq(0), fields.clone()),
+        Some(StreamId::new(0, 1))
+    );
+    assert_eq!(
+        stream.add(XAddId::Explicit(StreamId::new(5, 0)), fields.clone()),
+        Some(StreamId::new(5, 0))
+    );
+    assert_eq!(
+        stream.add(XAddId::AutoSeq(5), fields.clone()),
+        Some(StreamId::new(5, 1))
+    );
+    assert_eq!(stream.add(XAddId::AutoSeq(4), fields.clone()), None);
+    assert_eq!(
+        stream.add(XAddId::Explicit(StreamId::new(5, 1)), fields.clone()),
+        None
+    );
+    assert!(stream.add(XAddId::Auto, fields).unwrap() > StreamId::new(5, 1));
+    assert_eq!(stream.len(), 4);
+
+    let ids =
+        |entries: Vec<(StreamId, _)>| entries.into_iter().map(|(id, _)| id).collect::<Vec<_>>();
+    assert_eq!(
+        ids(stream.range(StreamId::new(1, 0), StreamId::new(5, u64::MAX), None, false)),
+        vec![StreamId::new(5, 0), StreamId::new(5, 1)]
+    );
+    assert_eq!(
+        ids(stream.range(StreamId::MIN, StreamId::MAX, Some(2), true)).len(),
+        2
+    );
+    assert_eq!(
+        ids(stream.range(StreamId::MAX, StreamId::MIN, None, false)).len(),
+        0
+    );
+}
+
+#[test]
+fn key_versions() {
+    let mut mem = InMemoryStorage::new();
+    assert_eq!(mem.version(b"key"), 0);
+
+    mem.write(b"key", b"xxx");
+    let version = mem.version(b"key");
+    assert!(version > 0);
+    // reading or adding a member already there changes nothing
+    assert_eq!(mem.read(b"key").as_deref(), Some(&b"xxx"[..]));
+    assert_eq!(mem.version(b"key"), version);
+    mem.sadd(b"set", b"a");
+    let set_version = mem.version(b"set");
+    mem.sadd(b"set", b"a");
+    assert_eq!(mem.version(b"set"), set_version);
+
+    // nor does removing a missing key
+    assert_eq!(mem.remove(b"missing"), 0);
+    assert_eq!(mem.version(b"missing"), 0);
+
+    mem.remove(b"key");
+    assert!(mem.version(b"key") > version);
+
+    mem.write(b"key", b"xxx");
+    mem.expire(b"key", Expiry::new_from_millis(10).unwrap());
+    let version = mem.version(b"key");
+    sleep(Duration::from_millis(20));
+    assert!(mem.version(b"key") > version);
+    assert!(!mem.contains(b"key"));
+}
+
+#[test]
+fn stream_consumer_group() {
+    use crate::storage::models::redis_stream::{
+        ConsumerGroup, RedisStream, StreamId, XAddId, XReadGroupId,
+    };
+
+    let mut stream = RedisStream::new(None);
+    for ms in 1..=3 {
+        let fields = vec![(b"n".to_vec(), ms.to_string().into_bytes())];
+        stream.add(XAddId::Explicit(StreamId::new(ms, 0)), fields);
+    }
+    stream
+        .groups
+        .insert(b"g".to_vec(), ConsumerGroup::new(StreamId::MIN));
+
+    let ids = |entries: Option<Vec<(StreamId, _)>>| {
+        entries
+            .unwrap()
+            .into_iter()
+            .map(|(id, _)| id.ms)
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(
+        ids(stream.read_group(b"g", b"alice", XReadGroupId::New, Some(2), false)),
+        vec![1, 2]
+    );
+    assert_eq!(
+        ids(stream.read_group(b"g", b"bob", XReadGroupId::New, None, false)),
+        vec![3]
+    );
+    assert_eq!(
+        ids(stream.read_group(b"g", b"bob", XReadGroupId::New, None, false)),
+        Vec::<u64>::new()
+    );
+    assert_eq!(
+        ids(stream.read_group(
+            b"g",
+            b"alice",
+            XReadGroupId::Pending(StreamId::MIN),
+            None,
+            false
+        )),
+        vec![1, 2]
+    );
+    assert_eq!(
+        stream.read_group(b"nogroup", b"alice", XReadGroupId::New, None, false),
+        None
+    );
+
+    let group = stream.groups.get_mut(&b"g".to_vec()).unwrap();
+    assert_eq!(group.last_delivered_id, StreamId::new(3, 0));
+    assert_eq!(group.pending.len(), 3);
+    assert_eq!(group.acknowledge(StreamId::new(1, 0)), true);
+    assert_eq!(group.acknowledge(StreamId::new(1, 0)), false);
+    assert_eq!(group.delete_consumer(b"alice"), Some(1));
+    assert_eq!(group.pending.len(), 1);
+    assert_eq!(group.create_consumer(b"bob"), false);
+    assert_eq!(group.create_consumer(b"carol"), true);
+
+    let group = ConsumerGroup::new(StreamId::new(1, 0));
+    assert_eq!(stream.lag(&group), 2);
+}
+
+#[test]
+fn stream_claim() {
+    use crate::storage::models::redis_stream::{
+        ClaimOptions, ConsumerGroup, PendingQuery, RedisStream, StreamId, XAddId, XReadGroupId,
+    };
+
+    let mut stream = RedisStream::new(None);
+    for ms in 1..=4 {
+        let fields = vec![(b"f".to_vec(), ms.to_string().into_bytes())];
+        stream.add(XAddId::Explicit(StreamId::new(ms, 0)), fields);
+    }
+    stream
+        .groups
+        .insert(b"g".to_vec(), ConsumerGroup::new(StreamId::MIN));
+    stream.read_group(b"g", b"alice", XReadGroupId::New, Some(3), false);
+
+    let query = PendingQuery {
+        min_idle: None,
+        start: StreamId::MIN,
+        end: StreamId::MAX,
+        count: 10,
+        consumer: Some(b"alice".to_vec()),
+    };
+    assert_eq!(stream.groups[&b"g".to_vec()].pending_range(&query).len(), 3);
+
+    // not idle for long enough
+    assert_eq!(
+        stream.claim(
+            b"g",
+            b"bob",
+            60_000,
+            &[StreamId::new(1, 0)],
+            &ClaimOptions::default()
+        ),
+        Some(vec![])
+    );
+
+    let options = ClaimOptions {
+        force: true,
+        ..ClaimOptions::default()
+    };
+    let ids = [StreamId::new(1, 0), StreamId::new(4, 0)];
+    assert_eq!(
+        stream.claim(b"g", b"bob", 0, &ids, &options),
+        Some(ids.to_vec())
+    );
+
+    let group = &stream.groups[&b"g".to_vec()];
+    assert_eq!(
+        group.pending[&StreamId::new(1, 0)].consumer,
+        b"bob".to_vec()
+    );
+    assert_eq!(group.pending[&StreamId::new(1, 0)].delivery_count, 2);
+    assert_eq!(group.pending[&StreamId::new(4, 0)].delivery_count, 1);
+    assert_eq!(group.consumers[&b"alice".to_vec()].pending.len(), 2);
+
+    stream.entries.remove(&StreamId::new(2, 0));
+    assert_eq!(
+        stream.auto_claim(b"g", b"carol", 0, StreamId::MIN, 2, true),
+        Some((
+            StreamId::new(3, 0),
+            vec![StreamId::new(1, 0)],
+            vec![StreamId::new(2, 0)]
+        ))
+    );
+    assert_eq!(
+        stream.auto_claim(b"g", b"carol", 0, StreamId::new(3, 0), 2, false),
+        Some((
+            StreamId::MIN,
+            vec![StreamId::new(3, 0), StreamId::new(4, 0)],
+            vec![]
+        ))
+    );
+    assert_eq!(stream.groups[&b"g".to_vec()].pending.len(), 3);
+    assert_eq!(
+        stream.auto_claim(b"nogroup", b"carol", 0, StreamId::MIN, 2, true),
+        None
+    );
+}
+
+#[test]
+fn stream_trim() {
+    use crate::storage::models::redis_stream::{
+        RedisStream, StreamId, StreamTrim, TrimStrategy, XAddId,
+    };
+
+    let mut stream = RedisStream::new(None);
+    for ms in 1..=10 {
+        stream.add(XAddId::Explicit(StreamId::new(ms, 0)), vec![]);
+    }
+
+    let trim = |strategy, limit| StreamTrim { strategy, limit };
+    assert_eq!(stream.trim(&trim(TrimStrategy::MaxLen(8), None)), 2);
+    assert_eq!(stream.trim(&trim(TrimStrategy::MaxLen(2), Some(3))), 3);
+    assert_eq!(stream.len(), 5);
+    assert_eq!(
+        stream.trim(&trim(TrimStrategy::MinId(StreamId::new(8, 0)), None)),
+        2
+    );
+    assert_eq!(stream.trim(&trim(TrimStrategy::MaxLen(3), None)), 0);
+
+    assert_eq!(
+        stream.delete(&[
+            StreamId::new(8, 0),
+            StreamId::new(8, 0),
+            StreamId::new(1, 0)
+        ]),
+        1
+    );
+    assert_eq!(stream.len(), 2);
+    // deleting entries does not change the last id
+    assert_eq!(stream.last_id, StreamId::new(10, 0));
+    assert_eq!(stream.max_deleted_id, StreamId::new(8, 0));
+    assert_eq!(stream.entries_added, 10);
+}
+
+#[test]
+fn sort() {
+    use crate::storage::models::sort::{substitute, SortQuery};
+    use std::collections::HashMap;
+
+    assert_eq!(
+        substitute(b"weight_*", b"a"),
+        Some((b"weight_a".to_vec(), None))
+    );
+    assert_eq!(
+        substitute(b"obj_*->name", b"a"),
+        Some((b"obj_a".to_vec(), Some(b"name".to_vec())))
+    );
+    assert_eq!(
+        substitute(b"obj_*->", b"a"),
+        Some((b"obj_a->".to_vec(), None))
+    );
+    assert_eq!(substitute(b"nosort", b"a"), None);
+
+    let mut strings = HashMap::new();
+    strings.insert(b"w_a".to_vec(), b"3".to_vec());
+    strings.insert(b"w_b".to_vec(), b"1".to_vec());
+    strings.insert(b"w_c".to_vec(), b"2".to_vec());
+    strings.insert(b"name_a".to_vec(), b"alice".to_vec());
+    let lookup = |key: &[u8], _: Option<&[u8]>| strings.get(key).cloned();
+    let elements = |elements: &[&[u8]]| elements.iter().map(|e| e.to_vec()).collect::<Vec<_>>();
+
+    let query = SortQuery::default();
+    assert_eq!(
+        query.sort(elements(&[b"10", b"9", b"-1.5"]), lookup),
+        Some(elements(&[b"-1.5", b"9", b"10"]))
+    );
+    assert_eq!(query.sort(elements(&[b"10", b"a"]), lookup), None);
+
+    let query = SortQuery {
+        alpha: true,
+        desc: true,
+        limit: Some((1, -1)),
+        ..SortQuery::default()
+    };
+    assert_eq!(
+        query.sort(elements(&[b"10", b"9", b"a"]), lookup),
+        Some(elements(&[b"9", b"10"]))
+    );
+
+    let query = SortQuery {
+        by: Some(b"w_*".to_vec()),
+        get: vec![b"#".to_vec(), b"name_*".to_vec()],
+        ..SortQuery::default()
+    };
+    let sorted = query
+        .sort(elements(&[b"a", b"b", b"c", b"d"]), lookup)
+        .unwrap();
+    // d has no weight, which counts as 0
+    assert_eq!(sorted, elements(&[b"d", b"b", b"c", b"a"]));
+    assert_eq!(
+        query.get_values(elements(&[b"b", b"a"]), lookup),
+        vec![
+            Some(b"b".to_vec()),
+            None,
+            Some(b"a".to_vec()),
+            Some(b"alice".to_vec())
+        ]
+    );
+}
+
+#[test]
+fn lcs() {
+    use crate::storage::models::redis_value::lcs;
+
+    let (subsequence, matches) = lcs(b"ohmytext", b"mynewtext");
+    assert_eq!(subsequence, b"mytext".to_vec());
+    assert_eq!(matches, vec![((4, 7), (5, 8)), ((2, 3), (0, 1))]);
+
+    assert_eq!(lcs(b"", b"abc"), (vec![], vec![]));
+    assert_eq!(lcs(b"abc", b"xyz"), (vec![], vec![]));
+}
+
+#[test]
+fn take_and_put() {
+    let mut source = InMemoryStorage::new();
+    let mut destination = InMemoryStorage::new();
+    source.sadd(b"set", b"member");
+    source.write(b"string", b"value");
+
+    let set = source.take(b"set").unwrap();
+    assert!(!source.contains(b"set"));
+    assert!(source.take(b"set").is_none());
+    destination.put(b"set", set);
+    assert_eq!(destination.type_of(b"set"), Some(DataType::Set));
+    assert_eq!(destination.sread(b"set").unwrap().data.len(), 1);
+
+    // the value put replaces the one stored at key
+    let string = source.take(b"string").unwrap();
+    destination.put(b"set", string);
+    assert_eq!(destination.type_of(b"set"), Some(DataType::String));
+    assert_eq!(destination.read(b"set").as_deref(), Some(&b"value"[..]));
+
+    // an expired key cannot be taken
+    source.write(b"expiring", b"value");
+    source.expire(b"expiring", Expiry::new_from_millis(1).unwrap());
+    sleep(Duration::from_millis(2));
+    assert!(source.take(b"expiring").is_none());
+    assert_eq!(source.take_expired(), vec![b"expiring".to_vec()]);
+}
+
+#[test]
+fn hread_all() {
+    let mut mem = InMemoryStorage::new();
+    mem.hwrite(b"hash", b"field", b"value");
+    mem.hwrite(b"hash", b"other", b"value");
+    assert_eq!(mem.hread_all(b"hash").unwrap().data.len(), 2);
+    assert!(mem.hread_all(b"missing").is_none());
+
+    // the fields that expired are left out
+    mem.hexpire(b"hash", b"other", Expiry::new_from_millis(1).unwrap(), None);
+    sleep(Duration::from_millis(2));
+    assert_eq!(mem.hread_all(b"hash").unwrap().data.len(), 1);
+}
+
+#[test]
+fn memory_usage() {
+    let mut mem = InMemoryStorage::new();
+    assert_eq!(mem.memory_stats(), MemoryStats::default());
+    assert!(mem.memory_usage(b"key").is_none());
+
+    mem.write(b"key", b"value");
+    let small = mem.memory_usage(b"key").unwrap();
+    assert!(small > b"key".len() + b"value".len());
+    mem.write(b"key", &[b'x'; 1000]);
+    assert_eq!(mem.memory_usage(b"key").unwrap(), small + 1000 - 5);
+
+    mem.sadd(b"set", b"member");
+    let stats = mem.memory_stats();
+    assert_eq!(stats.keys, 2);
+    assert_eq!(
+        stats.overhead + stats.dataset,
+        mem.memory_usage(b"key").unwrap() + mem.memory_usage(b"set").unwrap()
+    );
+
+    // the keys that expired are not counted
+    mem.expire(b"set", Expiry::new_from_millis(1).unwrap());
+    sleep(Duration::from_millis(2));
+    assert!(mem.memory_usage(b"set").is_none());
+    assert_eq!(mem.memory_stats().keys, 1);
+}
+
+#[test]
+fn keyspace_stats() {
+    let mut mem = InMemoryStorage::new();
+    assert_eq!(mem.keyspace_stats(), KeyspaceStats::default());
+
+    mem.write(b"key", b"value");
+    mem.sadd(b"set", b"member");
+    mem.write(b"volatile", b"value");
+    mem.expire(b"volatile", Expiry::new_from_secs(100).unwrap());
+    let stats = mem.keyspace_stats();
+    assert_eq!((stats.keys, stats.expires), (3, 1));
+    assert!(stats.avg_ttl > 99_000 && stats.avg_ttl <= 100_000);
+
+    // the keys that expired are not counted
+    mem.expire(b"set", Expiry::new_from_millis(1).unwrap());
+    sleep(Duration::from_millis(2));
+    let stats = mem.keyspace_stats();
+    assert_eq!((stats.keys, stats.expires), (2, 1));
+}
+
+#[test]
+fn sample_expired() {
+    let mut mem = InMemoryStorage::new();
+    assert_eq!(mem.sample_expired(20), (0, 0));
+
+    mem.write(b"key", b"value");
+    for i in 0..10 {
+        let key = format!("expiring{}", i);
+        mem.write(key.as_bytes(), b"value");
+        mem.expire(key.as_bytes(), Expiry::new_from_millis(1).unwrap());
+    }
+    mem.write(b"volatile", b"value");
+    mem.expire(b"volatile", Expiry::new_from_secs(100).unwrap());
+    // given another expiry, the key leaves behind the one it had
+    mem.write(b"renewed", b"value");
+    mem.expire(b"renewed", Expiry::new_from_millis(1).unwrap());
+    mem.expire(b"renewed", Expiry::new_from_secs(100).unwrap());
+    sleep(Duration::from_millis(2));
+
+    // only the keys whose time to live is over are looked at, a few at a
+    // time
+    let (mut sampled, mut expired) = (0, 0);
+    for _ in 0..4 {
+        let (x, y) = mem.sample_expired(3);
+        sampled += x;
+        expired += y;
+    }
+    assert_eq!((sampled, expired), (11, 10));
+    assert_eq!(mem.take_expired().len(), 10);
+    assert_eq!(mem.keyspace_stats().keys, 3);
+    assert_eq!(mem.sample_expired(20), (0, 0));
+}
+
+#[test]
+fn eviction() {
+    let mut mem = InMemoryStorage::new();
+    assert_eq!(mem.eviction_candidate(EvictionPolicy::AllKeysLru, 5), None);
+    mem.write(b"used", b"value");
+    mem.write(b"unused", b"value");
+    mem.sadd(b"set", b"member");
+
+    // the memory used is kept up to date as the keys change
+    let used = |mem: &mut InMemoryStorage| {
+        let stats = mem.memory_stats();
+        stats.overhead + stats.dataset
+    };
+    assert_eq!(mem.used_memory(), used(&mut mem));
+    mem.write(b"used", b"a longer value");
+    mem.remove(b"set");
+    assert_eq!(mem.used_memory(), used(&mut mem));
+
+    // the key used the least is evicted first
+    mem.access(b"used");
+    let (key, _) = mem
+        .eviction_candidate(EvictionPolicy::AllKeysLru, 5)
+        .unwrap();
+    assert_eq!(key, b"unused");
+
+    // the volatile policies evict the keys with a time to live alone, the
+    // one expiring first for volatile-ttl
+    assert_eq!(mem.eviction_candidate(EvictionPolicy::VolatileTtl, 5), None);
+    mem.expire(b"used", Expiry::new_from_secs(10).unwrap());
+    mem.expire(b"unused", Expiry::new_from_secs(100).unwrap());
+    let (key, _) = mem
+        .eviction_candidate(EvictionPolicy::VolatileTtl, 5)
+        .unwrap();
+    assert_eq!(key, b"used");
+    // as told by the expiry a key was given last
+    mem.expire(b"unused", Expiry::new_from_secs(5).unwrap());
+    let (key, _) = mem
+        .eviction_candidate(EvictionPolicy::VolatileTtl, 5)
+        .unwrap();
+    assert_eq!(key, b"unused");
+}
+
+#[test]
+fn snapshot_roundtrip() {
+    let mut mem = InMemoryStorage::new();
+    mem.write(b"string", b"value");
+    mem.expire(b"string", Expiry::new_from_secs(100).unwrap());
+    mem.sadd(b"set", b"member");
+    mem.zadd(b"zset", 1.5, b"member");
+    mem.hwrite(b"hash", b"field", b"value");
+    mem.hwrite(b"hash", b"volatile", b"value");
+    mem.hexpire(
+        b"hash",
+        b"volatile",
+        Expiry::new_from_secs(100).unwrap(),
+        None,
+    );
+    let id = mem
+        .xadd(
+            b"stream",
+            XAddId::Auto,
+            vec![(b"field".to_vec(), b"value".to_vec())],
+        )
+        .unwrap();
+    mem.write(b"expired", b"value");
+    mem.expire(b"expired", Expiry::new_from_millis(20).unwrap());
+
+    let mut bytes = vec![];
+    snapshot::write(&mut bytes, &[vec![], mem.snapshot()]).unwrap();
+    // the key expiring meanwhile is left out once read
+    sleep(Duration::from_millis(30));
+    let databases = snapshot::read(&mut bytes.as_slice()).unwrap();
+    assert_eq!(databases.len(), 2);
+    assert!(databases[0].is_empty());
+
+    let mut copy = InMemoryStorage::new();
+    for (key, value) in databases.into_iter().nth(1).unwrap() {
+        copy.put(&key, value);
+    }
+    assert_eq!(copy.read(b"string").as_deref(), Some(&b"value"[..]));
+    assert!(copy.sread(b"set").unwrap().data.contains(&b"member"[..]));
+    assert_eq!(copy.zread(b"zset").unwrap().scores[&b"member"[..]], 1.5);
+    assert_eq!(copy.hread(b"hash", b"field"), Some(&b"value"[..]));
+    assert!(copy.hpttl(b"hash", b"volatile") > 99_000);
+    assert_eq!(copy.xread(b"stream").unwrap().last_id, id);
+    assert!(!copy.contains(b"expired"));
+    let stats = copy.keyspace_stats();
+    assert_eq!((stats.keys, stats.expires), (5, 1));
+    assert!(stats.avg_ttl > 99_000 && stats.avg_ttl <= 100_000);
+
+    // anything else than a snapshot is refused
+    assert!(snapshot::read(&mut &b"REDIS0011"[..]).is_err());
+    assert!(snapshot::read(&mut &bytes[..bytes.len() - 1]).is_err());
+}
+
+#[test]
+fn snapshot_changes() {
+    let mut mem = InMemoryStorage::new();
+    mem.sadd(b"set", b"member");
+    mem.write(b"expired", b"value");
+    mem.expire(b"expired", Expiry::new_from_millis(20).unwrap());
+
+    let mut bytes = vec![];
+    let changes = [
+        Change::Put(1, b"set".to_vec(), mem.copy(b"set").unwrap()),
+        Change::Remove(0, b"key".to_vec()),
+        Change::SwapDb(0, 1),
+        Change::Put(0, b"expired".to_vec(), mem.copy(b"expired").unwrap()),
+    ];
+    for change in &changes {
+        snapshot::write_change(&mut bytes, change).unwrap();
+    }
+    sleep(Duration::from_millis(30));
+
+    // a key that expired since is removed rather than put
+    let read = snapshot::read_changes(&mut bytes.as_slice()).unwrap();
+    assert_eq!(read[..3], changes[..3]);
+    assert_eq!(read[3], Change::Remove(0, b"expired".to_vec()));
+
+    // a change cut short is left out
+    let read = snapshot::read_changes(&mut &bytes[..bytes.len() - 1]).unwrap();
+    assert_eq!(read[..], changes[..3]);
+    assert!(snapshot::read_changes(&mut &b"\x00"[..]).is_err());
+}
+
+#[test]
+fn rdb_read() {
+    let mut bytes = b"REDIS0011".to_vec();
+    // the version of Redis that wrote it, and the sizes of the database
+    bytes.extend_from_slice(b"\xfa\x09redis-ver\x057.2.0\xfe\x01\xfb\x07\x01");
+    // a string encoded as an integer, and one compressed
+    bytes.extend_from_slice(b"\x00\x06string\xc0\x7b");
+    bytes.extend_from_slice(b"\x00\x03lzf\xc3\x05\x0a\x00a\xe0\x00\x00");
+    // a key expiring in 2100, and one that expired in 1970
+    bytes.extend_from_slice(b"\xfc\x00\xd8\xc3\x2c\xbb\x03\x00\x00\x00\x08volatile\x05value");
+    bytes.extend_from_slice(b"\xfc\xe8\x03\x00\x00\x00\x00\x00\x00\x00\x07expired\x05value");
+    // a set of integers, a hash in a listpack and a sorted set
+    bytes.extend_from_slice(b"\x0b\x03set\x0c\x02\x00\x00\x00\x02\x00\x00\x00\x01\x00\xff\xff");
+    bytes.extend_from_slice(b"\x10\x04hash\x10\x10\x00\x00\x00\x02\x00\x85field\x06\x07\x01\xff");
+    bytes.extend_from_slice(b"\x05\x04zset\x01\x01m");
+    bytes.extend_from_slice(&1.5f64.to_le_bytes());
+    // a list, left out
+    bytes.extend_from_slice(b"\x12\x04list\x01\x02\x03abc");
+    bytes.extend_from_slice(b"\xff\x00\x00\x00\x00\x00\x00\x00\x00");
+
+    let databases = rdb::read(&mut bytes.as_slice()).unwrap();
+    assert_eq!(databases.len(), 2);
+    assert!(databases[0].is_empty());
+    let mut mem = InMemoryStorage::new();
+    for (key, value) in databases.into_iter().nth(1).unwrap() {
+        mem.put(&key, value);
+    }
+    assert_eq!(mem.read(b"string").as_deref(), Some(&b"123"[..]));
+    assert_eq!(mem.read(b"lzf").as_deref(), Some(&b"aaaaaaaaaa"[..]));
+    assert_eq!(mem.read(b"volatile").as_deref(), Some(&b"value"[..]));
+    assert!(!mem.contains(b"expired"));
+    let set = &mem.sread(b"set").unwrap().data;
+    assert!(set.len() == 2 && set.contains(&b"1"[..]) && set.contains(&b"-1"[..]));
+    assert_eq!(mem.hread(b"hash", b"field"), Some(&b"7"[..]));
+    assert_eq!(mem.zread(b"zset").unwrap().scores[&b"m"[..]], 1.5);
+    assert!(!mem.contains(b"list"));
+    let stats = mem.keyspace_stats();
+    assert_eq!((stats.keys, stats.expires), (6, 1));
+
+    // a snapshot of RedisLess, a newer version, a stream and a file cut
+    // short are refused
+    assert!(rdb::read(&mut &b"REDISLESS\x01\xff"[..]).is_err());
+    assert!(rdb::read(&mut &b"REDIS0099\xff"[..]).is_err());
+    assert!(rdb::read(&mut &b"REDIS0011\x15\x03key\x00\xff"[..]).is_err());
+    assert!(rdb::read(&mut &bytes[..bytes.len() - 10]).is_err());
+}
+
+#[test]
+fn rdb_roundtrip() {
+    use crate::storage::models::redis_stream::{ConsumerGroup, StreamId};
+
+    let mut mem = InMemoryStorage::new();
+    mem.write(b"string", b"value");
+    mem.expire(b"string", Expiry::new_from_secs(100).unwrap());
+    mem.sadd(b"set", b"member");
+    mem.zadd(b"zset", f64::INFINITY, b"member");
+    mem.hwrite(b"hash", b"field", b"value");
+    mem.hwrite(b"hash", b"volatile", b"value");
+    mem.hexpire(
+        b"hash",
+        b"volatile",
+        Expiry::new_from_secs(100).unwrap(),
+        None,
+    );
+    // the values of a stream are written in listpacks, whatever their size
+    let (short, long) = ("b".repeat(100), "c".repeat(5000));
+    let values = ["1", "-1000", "-5000", "100000", "-1234567890123", "a"];
+    for value in values.iter().copied().chain(vec![&*short, &*long]) {
+        let fields = vec![(b"field".to_vec(), value.as_bytes().to_vec())];
+        mem.xadd(b"stream", XAddId::Auto, fields).unwrap();
+    }
+    let stream = mem.xread_mut(b"stream").unwrap();
+    let mut group = ConsumerGroup::new(StreamId::MIN);
+    for id in stream.entries.keys().take(2) {
+        group.deliver(*id, b"consumer");
+    }
+    group.last_delivered_id = *stream.entries.keys().nth(1).unwrap();
+    stream.groups.insert(b"group".to_vec(), group);
+
+    let mut bytes = vec![];
+    rdb::write(&mut bytes, &[vec![], mem.snapshot()]).unwrap();
+    assert_eq!(&bytes[..9], b"REDIS0012");
+    let databases = rdb::read(&mut bytes.as_slice()).unwrap();
+    assert!(databases[0].is_empty());
+    let mut copy = InMemoryStorage::new();
+    for (key, value) in databases.into_iter().nth(1).unwrap() {
+        copy.put(&key, value);
+    }
+    assert_eq!(copy.read(b"string").as_deref(), Some(&b"value"[..]));
+    assert!(copy.sread(b"set").unwrap().data.contains(&b"member"[..]));
+    assert_eq!(
+        copy.zread(b"zset").unwrap().scores[&b"member"[..]],
+        f64::INFINITY
+    );
+    assert_eq!(copy.hread(b"hash", b"field"), Some(&b"value"[..]));
+    assert!(copy.hpttl(b"hash", b"volatile") > 99_000);
+    assert_eq!(copy.xread(b"stream"), mem.xread(b"stream"));
+    let stats = copy.keyspace_stats();
+    assert_eq!((stats.keys, stats.expires), (5, 1));
+    assert!(stats.avg_ttl > 99_000 && stats.avg_ttl <= 100_000);
+
+    // a dump whose checksum doesn't match is refused
+    let last = bytes.len() - 9;
+    bytes[last - 1] ^= 1;
+    assert!(rdb::read(&mut bytes.as_slice()).is_err());
+}
+
+#[test]
+fn log_file_storage() {
+    use crate::storage::log_file::LogFileStorage;
+    use std::io::Write;
+
+    let path = std::env::temp_dir().join(format!("redisless-log-{}.log", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    let mut storage = LogFileStorage::open(&path).unwrap();
+    storage.write(b"string", b"value");
+    storage.write(b"string", b"other value");
+    storage.sadd(b"set", b"member");
+    storage.write(b"removed", b"value");
+    storage.remove(b"removed");
+    storage.write(b"expiring", b"value");
+    storage.expire(b"expiring", Expiry::new_from_millis(20).unwrap());
+    let fields = vec![(b"field".to_vec(), b"value".to_vec())];
+    let id = storage.xadd(b"stream", XAddId::Auto, fields).unwrap();
+    // a stream modified in place is written once done with
+    storage.xread_mut(b"stream").unwrap().delete(&[id]);
+    drop(storage);
+    sleep(Duration::from_millis(30));
+
+    // a change cut short is dropped
+    let mut file = std::fs::OpenOptions::new()
+        .append(true)
+        .open(&path)
+        .unwrap();
+    file.write_all(&[0xfd, 0, 0]).unwrap();
+    drop(file);
+    let length = std::fs::metadata(&path).unwrap().len();
+
+    let mut storage = LogFileStorage::open(&path).unwrap();
+    assert_eq!(
+        storage.read(b"string").as_deref(),
+        Some(&b"other value"[..])
+    );
+    assert!(storage.sread(b"set").unwrap().data.contains(&b"member"[..]));
+    assert!(!storage.contains(b"removed"));
+    assert!(!storage.contains(b"expiring"));
+    assert!(storage.xread(b"stream").unwrap().is_empty());
+    assert_eq!(storage.keyspace_stats().keys, 3);
+    // the log is compacted once opened
+    assert!(std::fs::metadata(&path).unwrap().len() < length);
+    storage.flush().unwrap();
+    drop(storage);
+
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+#[cfg(feature = "sled-storage")]
+fn sled_storage() {
+    use crate::storage::sled::SledStorage;
+
+    let path = std::env::temp_dir().join(format!("redisless-sled-{}", std::process::id()));
+    let mut storage = SledStorage::open(&path).unwrap();
+    storage.write(b"string", b"value");
+    storage.sadd(b"set", b"member");
+    storage.write(b"removed", b"value");
+    storage.remove(b"removed");
+    storage.write(b"expiring", b"value");
+    storage.expire(b"expiring", Expiry::new_from_millis(20).unwrap());
+    let fields = vec![(b"field".to_vec(), b"value".to_vec())];
+    let id = storage.xadd(b"stream", XAddId::Auto, fields).unwrap();
+    // a stream modified in place is written once done with
+    storage.xread_mut(b"stream").unwrap().delete(&[id]);
+    drop(storage);
+    sleep(Duration::from_millis(30));
+
+    let mut storage = SledStorage::open(&path).unwrap();
+    assert_eq!(storage.read(b"string").as_deref(), Some(&b"value"[..]));
+    assert!(storage.sread(b"set").unwrap().data.contains(&b"member"[..]));
+    assert!(!storage.contains(b"removed"));
+    assert!(!storage.contains(b"expiring"));
+    assert!(storage.xread(b"stream").unwrap().is_empty());
+    assert_eq!(storage.keyspace_stats().keys, 3);
+    storage.flush().unwrap();
+    drop(storage);
+
+    std::fs::remove_dir_all(&path).unwrap();
+}
+
+#[test]
+#[cfg(feature = "rocksdb-storage")]
+fn rocksdb_storage() {
+    use crate::storage::rocksdb::RocksDbStorage;
+
+    let path = std::env::temp_dir().join(format!("redisless-rocksdb-test-{}", std::process::id()));
+    let mut storage = RocksDbStorage::open(&path).unwrap();
+    storage.write(b"string", b"value");
+    storage.hwrite(b"hash", b"field", b"value");
+    storage.write(b"removed", b"value");
+    storage.remove(b"removed");
+    storage.write(b"expiring", b"value");
+    storage.expire(b"expiring", Expiry::new_from_millis(20).unwrap());
+    let fields = vec![(b"field".to_vec(), b"value".to_vec())];
+    let id = storage.xadd(b"stream", XAddId::Auto, fields).unwrap();
+    // a stream modified in place is written once done with
+    storage.xread_mut(b"stream").unwrap().delete(&[id]);
+    let version = storage.version(b"string");
+    assert_eq!(storage.read(b"string").as_deref(), Some(&b"value"[..]));
+    assert_eq!(storage.version(b"string"), version);
+    drop(storage);
+    sleep(Duration::from_millis(30));
+
+    let mut storage = RocksDbStorage::open(&path).unwrap();
+    assert_eq!(storage.read(b"string").as_deref(), Some(&b"value"[..]));
+    assert_eq!(storage.hread(b"hash", b"field"), Some(&b"value"[..]));
+    assert!(!storage.contains(b"removed"));
+    assert!(storage.xread(b"stream").unwrap().is_empty());
+    assert_eq!(storage.keyspace_stats().keys, 3);
+    assert!(!storage.contains(b"expiring"));
+    assert_eq!(storage.take_expired(), vec![b"expiring".to_vec()]);
+    assert_eq!(storage.snapshot().len(), 3);
+    storage.flush().unwrap();
+    drop(storage);
+
+    std::fs::remove_dir_all(&path).unwrap();
+}